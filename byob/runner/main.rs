@@ -28,6 +28,12 @@ fn main() -> Result<()> {
     let engine = arg;
     let raw = std::io::read_to_string(std::io::stdin())?;
     let config = Config::read(&raw)?;
+    for key in config.ignored.iter() {
+        eprintln!(
+            "warning: ignored KLV key '{key}' from KLV protocol version {}",
+            config.version,
+        );
+    }
     let samples = match (&*engine, &*config.model) {
         ("rust/memmem", "iter") => rust_memmem_iter(&config),
         ("rust/memmem/restricted", "iter") => {
@@ -39,13 +45,14 @@ fn main() -> Result<()> {
         }
     };
     let mut stdout = std::io::stdout().lock();
-    for s in samples.iter() {
+    writeln!(stdout, "# warmup_iters={}", samples.warmup_iters)?;
+    for s in samples.list.iter() {
         writeln!(stdout, "{},{}", s.duration.as_nanos(), s.count)?;
     }
     Ok(())
 }
 
-fn rust_memmem_iter(c: &Config) -> Vec<Sample> {
+fn rust_memmem_iter(c: &Config) -> Samples {
     let finder = memchr::memmem::Finder::new(&c.needle);
     run(c, || {
         let mut haystack = c.haystack.as_bytes();
@@ -62,7 +69,7 @@ fn rust_memmem_iter(c: &Config) -> Vec<Sample> {
     })
 }
 
-fn rust_memmem_restricted_iter(c: &Config) -> Vec<Sample> {
+fn rust_memmem_restricted_iter(c: &Config) -> Samples {
     let memmem = memchr::memmem::find;
     run(c, || {
         let mut haystack = c.haystack.as_bytes();
@@ -79,7 +86,7 @@ fn rust_memmem_restricted_iter(c: &Config) -> Vec<Sample> {
     })
 }
 
-fn libc_memmem_iter(c: &Config) -> Vec<Sample> {
+fn libc_memmem_iter(c: &Config) -> Samples {
     run(c, || {
         let mut haystack = c.haystack.as_bytes();
         let mut count = 0;
@@ -101,10 +108,18 @@ struct Sample {
     count: usize,
 }
 
-fn run(c: &Config, mut bench: impl FnMut() -> usize) -> Vec<Sample> {
+#[derive(Clone, Debug)]
+struct Samples {
+    warmup_iters: u64,
+    list: Vec<Sample>,
+}
+
+fn run(c: &Config, mut bench: impl FnMut() -> usize) -> Samples {
     let warmup_start = Instant::now();
+    let mut warmup_iters = 0;
     for _ in 0..c.max_warmup_iters {
         let _count = bench();
+        warmup_iters += 1;
         if warmup_start.elapsed() >= c.max_warmup_time {
             break;
         }
@@ -121,11 +136,12 @@ fn run(c: &Config, mut bench: impl FnMut() -> usize) -> Vec<Sample> {
             break;
         }
     }
-    samples
+    Samples { warmup_iters, list: samples }
 }
 
 #[derive(Clone, Debug, Default)]
 struct Config {
+    version: u64,
     name: String,
     model: String,
     needle: String,
@@ -134,14 +150,17 @@ struct Config {
     max_warmup_iters: u64,
     max_time: Duration,
     max_warmup_time: Duration,
+    ignored: Vec<String>,
 }
 
 impl Config {
     fn read(mut raw: &str) -> Result<Config> {
         let mut config = Config::default();
+        let mut offset = 0;
         while !raw.is_empty() {
-            let klv = OneKLV::read(raw)?;
+            let klv = OneKLV::read(raw, offset)?;
             raw = &raw[klv.len..];
+            offset += klv.len;
             config.set(klv)?;
         }
         Ok(config)
@@ -153,6 +172,7 @@ impl Config {
         };
         let OneKLV { key, value, .. } = klv;
         match &*key {
+            "klv-version" => self.version = value.parse()?,
             "name" => self.name = value,
             "model" => self.model = value,
             "pattern" => self.needle = value,
@@ -161,7 +181,9 @@ impl Config {
             "max-warmup-iters" => self.max_warmup_iters = value.parse()?,
             "max-time" => self.max_time = parse_duration(value)?,
             "max-warmup-time" => self.max_warmup_time = parse_duration(value)?,
-            _ => {}
+            "case-insensitive" | "unicode" => {}
+            _ if self.version > 1 => self.ignored.push(key),
+            _ => err!("unrecognized KLV key '{key}'"),
         }
         Ok(())
     }
@@ -175,7 +197,10 @@ struct OneKLV {
 }
 
 impl OneKLV {
-    fn read(mut raw: &str) -> Result<OneKLV> {
+    /// Read a single KLV item from the beginning of `raw`, where `offset`
+    /// is the position of `raw` in the full input. (The offset is only used
+    /// for error messages.)
+    fn read(mut raw: &str, offset: usize) -> Result<OneKLV> {
         let Some(key_end) = raw.find(':') else {
             err!("invalid KLV item: could not find first ':'")
         };
@@ -194,9 +219,27 @@ impl OneKLV {
                  is not a number for '{key}'",
             )
         };
-        let value = &raw[..value_len];
-        if raw.as_bytes()[value_len] != b'\n' {
-            err!("invalid KLV item: no line terminator for '{key}'")
+        let value_offset = offset + key_end + 1 + value_len_end + 1;
+        if raw.len() < value_len {
+            err!(
+                "invalid KLV item: value length {value_len} for '{key}' \
+                 at byte offset {value_offset} exceeds the {} bytes \
+                 remaining",
+                raw.len(),
+            )
+        }
+        let Some(value) = raw.get(..value_len) else {
+            err!(
+                "invalid KLV item: value for '{key}' at byte offset \
+                 {value_offset} does not end on a UTF-8 boundary",
+            )
+        };
+        if raw.as_bytes().get(value_len) != Some(&b'\n') {
+            err!(
+                "invalid KLV item: no line terminator for '{key}' \
+                 at byte offset {}",
+                value_offset + value_len,
+            )
         }
         let len = key.len() + 1 + value_len_end + 1 + value.len() + 1;
         Ok(OneKLV { key: key.to_string(), value: value.to_string(), len })
@@ -0,0 +1,361 @@
+// A runner program for benchmarking the regex engine in C++'s standard
+// library, std::regex. Like all runner programs, it reads a benchmark
+// definition in KLV format on stdin and writes one 'duration_nanos,count' line
+// per sample to stdout.
+//
+// We always use the ECMAScript grammar since it's the default and is by far
+// the most commonly used. std::regex has no Unicode mode (it works on
+// individual 'char' values), so any benchmark asking for Unicode mode is
+// rejected.
+
+#include <algorithm>
+#include <cerrno>
+#include <chrono>
+#include <cstdint>
+#include <cstdlib>
+#include <cstring>
+#include <functional>
+#include <iostream>
+#include <iterator>
+#include <regex>
+#include <stdexcept>
+#include <string>
+#include <vector>
+
+namespace {
+
+// The newest version of the KLV protocol that this runner program knows
+// about. Unrecognized keys from a newer version are ignored instead of being
+// reported as an error.
+const uint64_t KLV_VERSION = 1;
+
+struct Config {
+    uint64_t klv_version = 0;
+    std::string name;
+    std::string model;
+    std::vector<std::string> patterns;
+    bool case_insensitive = false;
+    bool unicode = false;
+    std::string haystack;
+    uint64_t max_iters = 0;
+    uint64_t max_warmup_iters = 0;
+    std::chrono::nanoseconds max_time{0};
+    std::chrono::nanoseconds max_warmup_time{0};
+};
+
+struct Sample {
+    uint64_t duration;
+    size_t count;
+};
+
+[[noreturn]] void fail(const std::string& msg) {
+    throw std::runtime_error(msg);
+}
+
+uint64_t parse_u64(const std::string& key, const std::string& value) {
+    if (value.empty()) {
+        fail("failed to parse '" + key + "': empty value");
+    }
+    char* end = nullptr;
+    errno = 0;
+    unsigned long long n = std::strtoull(value.c_str(), &end, 10);
+    if (errno != 0 || *end != '\0') {
+        fail("failed to parse '" + key + "': invalid integer '" + value + "'");
+    }
+    return n;
+}
+
+bool parse_bool(const std::string& key, const std::string& value) {
+    if (value == "true") {
+        return true;
+    } else if (value == "false") {
+        return false;
+    }
+    fail("failed to parse '" + key + "': invalid boolean '" + value + "'");
+}
+
+// Parses a single KLV item starting at 'at' in 'raw'. On success, 'key' and
+// 'value' are set and the offset just past the item is returned.
+size_t parse_one_klv(
+    const std::string& raw,
+    size_t at,
+    std::string& key,
+    std::string& value
+) {
+    size_t colon = raw.find(':', at);
+    if (colon == std::string::npos) {
+        fail("failed to find first ':' in key-length-value triple");
+    }
+    key = raw.substr(at, colon - at);
+    size_t colon2 = raw.find(':', colon + 1);
+    if (colon2 == std::string::npos) {
+        fail("failed to find second ':' in key-length-value triple");
+    }
+    std::string len_str = raw.substr(colon + 1, colon2 - (colon + 1));
+    uint64_t len = parse_u64("length for key '" + key + "'", len_str);
+    size_t start = colon2 + 1;
+    if (len > raw.size() - start) {
+        fail("not enough bytes remaining for value of key '" + key + "'");
+    }
+    value = raw.substr(start, len);
+    size_t end = start + len;
+    if (end >= raw.size() || raw[end] != '\n') {
+        fail("did not find \\n after value for key '" + key + "'");
+    }
+    return end + 1;
+}
+
+Config parse_config(std::istream& in) {
+    std::string raw(
+        (std::istreambuf_iterator<char>(in)),
+        std::istreambuf_iterator<char>()
+    );
+    Config c;
+    size_t at = 0;
+    std::string key, value;
+    while (at < raw.size()) {
+        at = parse_one_klv(raw, at, key, value);
+        if (key == "klv-version") {
+            c.klv_version = parse_u64(key, value);
+        } else if (key == "name") {
+            c.name = value;
+        } else if (key == "model") {
+            c.model = value;
+        } else if (key == "pattern") {
+            c.patterns.push_back(value);
+        } else if (key == "case-insensitive") {
+            c.case_insensitive = parse_bool(key, value);
+        } else if (key == "unicode") {
+            c.unicode = parse_bool(key, value);
+        } else if (key == "haystack") {
+            c.haystack = value;
+        } else if (key == "max-iters") {
+            c.max_iters = parse_u64(key, value);
+        } else if (key == "max-warmup-iters") {
+            c.max_warmup_iters = parse_u64(key, value);
+        } else if (key == "max-time") {
+            c.max_time = std::chrono::nanoseconds(parse_u64(key, value));
+        } else if (key == "max-warmup-time") {
+            c.max_warmup_time =
+                std::chrono::nanoseconds(parse_u64(key, value));
+        } else if (c.klv_version > KLV_VERSION) {
+            std::cerr
+                << "warning: ignored unrecognized KLV key '" << key
+                << "' from KLV protocol version " << c.klv_version
+                << " (this runner program supports up to version "
+                << KLV_VERSION << ")" << std::endl;
+        } else {
+            fail("unrecognized KLV item key '" + key + "'");
+        }
+    }
+    return c;
+}
+
+// Runs 'bench' according to the iteration and time limits in the given
+// config, and prints each sample to stdout. The value returned by 'bench' is
+// turned into a count via 'count', which is not included in the measurement.
+template <typename T>
+void run_and_count(
+    const Config& c,
+    const std::function<size_t(T&)>& count,
+    const std::function<T()>& bench
+) {
+    using clock = std::chrono::steady_clock;
+
+    auto warmup_start = clock::now();
+    for (uint64_t i = 0; i < c.max_warmup_iters; i++) {
+        T result = bench();
+        count(result);
+        if (clock::now() - warmup_start >= c.max_warmup_time) {
+            break;
+        }
+    }
+
+    // The maximum number of iterations can be effectively infinite (e.g.,
+    // with 'rebar measure --hold'), so we cap how much we reserve up front.
+    std::vector<Sample> samples;
+    samples.reserve(std::min<uint64_t>(c.max_iters, 1000000));
+    auto run_start = clock::now();
+    for (uint64_t i = 0; i < c.max_iters; i++) {
+        auto bench_start = clock::now();
+        T result = bench();
+        auto elapsed = clock::now() - bench_start;
+        size_t n = count(result);
+        uint64_t nanos =
+            std::chrono::duration_cast<std::chrono::nanoseconds>(elapsed)
+                .count();
+        samples.push_back(Sample{nanos, n});
+        if (clock::now() - run_start >= c.max_time) {
+            break;
+        }
+    }
+    for (const Sample& s : samples) {
+        std::cout << s.duration << "," << s.count << "\n";
+    }
+    std::cout.flush();
+}
+
+void run(const Config& c, const std::function<size_t()>& bench) {
+    run_and_count<size_t>(c, [](size_t& n) { return n; }, bench);
+}
+
+std::regex compile(const Config& c) {
+    if (c.patterns.size() != 1) {
+        fail(
+            "expected 1 pattern, but got " + std::to_string(c.patterns.size())
+        );
+    }
+    auto flags = std::regex_constants::ECMAScript;
+    if (c.case_insensitive) {
+        flags |= std::regex_constants::icase;
+    }
+    return std::regex(c.patterns[0], flags);
+}
+
+// Counts all non-overlapping matches in the given range. The standard regex
+// iterator takes care of empty matches for us.
+size_t count_matches(const std::regex& re, const char* begin, const char* end) {
+    size_t count = 0;
+    std::cregex_iterator it(begin, end, re), last;
+    for (; it != last; ++it) {
+        count++;
+    }
+    return count;
+}
+
+// Calls 'f' for each line in the given haystack. Line terminators (including
+// a '\r' that precedes a '\n') are not included in each line.
+template <typename F>
+void for_each_line(const std::string& haystack, F f) {
+    const char* at = haystack.data();
+    const char* end = haystack.data() + haystack.size();
+    while (at < end) {
+        const char* nl = static_cast<const char*>(
+            std::memchr(at, '\n', static_cast<size_t>(end - at))
+        );
+        const char* line_end = nl == nullptr ? end : nl;
+        const char* next = nl == nullptr ? end : nl + 1;
+        if (line_end > at && line_end[-1] == '\r') {
+            line_end--;
+        }
+        f(at, line_end);
+        at = next;
+    }
+}
+
+void model_compile(const Config& c) {
+    const char* begin = c.haystack.data();
+    const char* end = c.haystack.data() + c.haystack.size();
+    run_and_count<std::regex>(
+        c,
+        [&](std::regex& re) { return count_matches(re, begin, end); },
+        [&]() { return compile(c); }
+    );
+}
+
+void model_count(const Config& c, const std::regex& re) {
+    const char* begin = c.haystack.data();
+    const char* end = c.haystack.data() + c.haystack.size();
+    run(c, [&]() { return count_matches(re, begin, end); });
+}
+
+void model_count_spans(const Config& c, const std::regex& re) {
+    const char* begin = c.haystack.data();
+    const char* end = c.haystack.data() + c.haystack.size();
+    run(c, [&]() {
+        size_t sum = 0;
+        std::cregex_iterator it(begin, end, re), last;
+        for (; it != last; ++it) {
+            sum += static_cast<size_t>((*it)[0].length());
+        }
+        return sum;
+    });
+}
+
+void model_count_captures(const Config& c, const std::regex& re) {
+    const char* begin = c.haystack.data();
+    const char* end = c.haystack.data() + c.haystack.size();
+    run(c, [&]() {
+        size_t count = 0;
+        std::cregex_iterator it(begin, end, re), last;
+        for (; it != last; ++it) {
+            const std::cmatch& m = *it;
+            for (size_t i = 0; i < m.size(); i++) {
+                if (m[i].matched) {
+                    count++;
+                }
+            }
+        }
+        return count;
+    });
+}
+
+void model_grep(const Config& c, const std::regex& re) {
+    run(c, [&]() {
+        size_t count = 0;
+        for_each_line(c.haystack, [&](const char* begin, const char* end) {
+            if (std::regex_search(begin, end, re)) {
+                count++;
+            }
+        });
+        return count;
+    });
+}
+
+std::string version() {
+    std::string lib;
+#if defined(_LIBCPP_VERSION)
+    lib = "libc++ " + std::to_string(_LIBCPP_VERSION);
+#elif defined(__GLIBCXX__)
+    lib = "libstdc++ " + std::to_string(__GLIBCXX__);
+#else
+    lib = "unknown";
+#endif
+#if defined(__clang__)
+    std::string compiler = "clang " __clang_version__;
+#elif defined(__GNUC__)
+    std::string compiler = "gcc " __VERSION__;
+#else
+    std::string compiler = "unknown";
+#endif
+    return lib + " (" + compiler + ")";
+}
+
+}  // namespace
+
+int main(int argc, char** argv) {
+    if (argc > 1) {
+        if (std::strcmp(argv[1], "--version") == 0) {
+            std::cout << version() << std::endl;
+            return 0;
+        }
+        std::cerr << "unrecognized argument: " << argv[1] << std::endl;
+        return 1;
+    }
+    try {
+        Config c = parse_config(std::cin);
+        if (c.unicode) {
+            fail("std::regex does not support Unicode mode");
+        }
+        if (c.model == "compile") {
+            model_compile(c);
+        } else if (c.model == "count") {
+            model_count(c, compile(c));
+        } else if (c.model == "count-spans") {
+            model_count_spans(c, compile(c));
+        } else if (c.model == "count-captures") {
+            model_count_captures(c, compile(c));
+        } else if (c.model == "grep") {
+            model_grep(c, compile(c));
+        } else {
+            fail("unrecognized benchmark model '" + c.model + "'");
+        }
+    } catch (const std::regex_error& e) {
+        std::cerr << "failed to compile regex: " << e.what() << std::endl;
+        return 1;
+    } catch (const std::exception& e) {
+        std::cerr << e.what() << std::endl;
+        return 1;
+    }
+    return 0;
+}
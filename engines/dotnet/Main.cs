@@ -6,7 +6,15 @@ using System.Text.RegularExpressions;
 /// data.</summary>
 struct Config
 {
+    /// <summary>
+    /// The newest version of the KLV protocol that this runner program
+    /// knows about. Unrecognized keys from a newer version are ignored
+    /// instead of being reported as an error.
+    /// </summary>
+    public const int KlvVersion = 1;
+
     public string engine;
+    public int klvVersion;
     public string? name;
     public string? model;
     public string? pattern;
@@ -148,6 +156,9 @@ class Program
             raw = raw.Slice(klv.len);
             switch (klv.key)
             {
+                case "klv-version":
+                    config.klvVersion = int.Parse(klv.value);
+                    break;
                 case "name":
                     config.name = klv.value;
                     break;
@@ -182,7 +193,18 @@ class Program
                     config.maxWarmupTime = long.Parse(klv.value);
                     break;
                 default:
-                    throw new Exception($"unrecognized KLV key {klv.key}");
+                    if (config.klvVersion <= Config.KlvVersion) {
+                        throw new Exception(
+                            $"unrecognized KLV key {klv.key}"
+                        );
+                    }
+                    Console.Error.WriteLine(
+                        "warning: ignored unrecognized KLV key " +
+                        $"'{klv.key}' from KLV protocol version " +
+                        $"{config.klvVersion} (this runner program " +
+                        $"supports up to version {Config.KlvVersion})"
+                    );
+                    break;
             }
         }
 
@@ -197,6 +219,7 @@ class Program
             "count" => ModelCount(config),
             "count-spans" => ModelCountSpans(config),
             "count-captures" => ModelCountCaptures(config),
+            "count-empty" => ModelCountEmpty(config),
             "grep" => ModelGrep(config),
             "grep-captures" => ModelGrepCaptures(config),
             "regex-redux" => ModelRegexRedux(config),
@@ -272,6 +295,26 @@ class Program
         );
     }
 
+    static List<Sample> ModelCountEmpty(Config config)
+    {
+        var re = config.CompileRegex();
+        return RunAndCount(
+            config,
+            n => n,
+            () => {
+                int count = 0;
+                // After an empty match, .NET resumes the search one UTF-16
+                // code unit later. This means empty matches can split a
+                // surrogate pair, and so counts for haystacks containing
+                // non-BMP codepoints may differ from other regex engines.
+                foreach (ValueMatch m in re.EnumerateMatches(config.haystack!)) {
+                    count++;
+                }
+                return count;
+            }
+        );
+    }
+
     static List<Sample> ModelGrep(Config config)
     {
         var re = config.CompileRegex();
@@ -281,7 +324,8 @@ class Program
             () => {
                 int count = 0;
                 var span = config.haystack.AsSpan();
-                foreach (ReadOnlySpan<char> line in span.EnumerateLines()) {
+                while (!span.IsEmpty) {
+                    ReadOnlySpan<char> line = NextLine(ref span);
                     if (re.IsMatch(line)) {
                         count++;
                     }
@@ -300,7 +344,8 @@ class Program
             () => {
                 int count = 0;
                 var span = config.haystack.AsSpan();
-                foreach (ReadOnlySpan<char> line in span.EnumerateLines()) {
+                while (!span.IsEmpty) {
+                    ReadOnlySpan<char> line = NextLine(ref span);
                     Match m = re.Match(line.ToString());
                     while (m.Success) {
                         foreach (Group g in m.Groups) {
@@ -379,6 +424,31 @@ agggtaa[cgt]|[acg]ttaccct 43
         );
     }
 
+    // Returns the next line in the given haystack and advances the haystack
+    // past it. Lines are split on '\n', and a single trailing '\r' is
+    // stripped from each line. If the haystack ends with a '\n', then there
+    // is no empty line after it, since the haystack is empty after the last
+    // line is returned.
+    //
+    // We don't use EnumerateLines because it also treats a lone '\r' (and
+    // a few other characters) as a line terminator.
+    static ReadOnlySpan<char> NextLine(ref ReadOnlySpan<char> haystack)
+    {
+        ReadOnlySpan<char> line;
+        int nl = haystack.IndexOf('\n');
+        if (nl == -1) {
+            line = haystack;
+            haystack = ReadOnlySpan<char>.Empty;
+        } else {
+            line = haystack.Slice(0, nl);
+            haystack = haystack.Slice(nl + 1);
+        }
+        if (!line.IsEmpty && line[line.Length - 1] == '\r') {
+            line = line.Slice(0, line.Length - 1);
+        }
+        return line;
+    }
+
     // Takes in a benchmark config, a closure that returns the count from the
     // benchmark function and a benchmark function that returns a result that
     // can be converted into a count. As output, it returns a list of samples
@@ -13,7 +13,13 @@ import (
 	"time"
 )
 
+// The newest version of the KLV protocol that this runner program knows
+// about. Unrecognized keys from a newer version are ignored instead of being
+// reported as an error.
+const klvVersion = 1
+
 type config struct {
+	KLVVersion      int
 	Name            string
 	Model           string
 	Pattern         string
@@ -47,6 +53,15 @@ func parseConfig(rdr io.Reader) (*config, error) {
 			c.Model = string(klv.Value)
 		case "pattern":
 			patterns = append(patterns, string(klv.Value))
+		case "klv-version":
+			n, err := strconv.Atoi(string(klv.Value))
+			if err != nil {
+				return nil, fmt.Errorf(
+					"failed to parse 'klv-version': %w",
+					err,
+				)
+			}
+			c.KLVVersion = n
 		case "case-insensitive":
 			c.CaseInsensitive = string(klv.Value) == "true"
 		case "unicode":
@@ -90,9 +105,20 @@ func parseConfig(rdr io.Reader) (*config, error) {
 			}
 			c.MaxWarmupTime = time.Duration(int64(n))
 		default:
-			return nil, fmt.Errorf(
-				"unrecognized KLV item key '%s'",
+			if c.KLVVersion <= klvVersion {
+				return nil, fmt.Errorf(
+					"unrecognized KLV item key '%s'",
+					klv.Key,
+				)
+			}
+			fmt.Fprintf(
+				os.Stderr,
+				"warning: ignored unrecognized KLV key '%s' from "+
+					"KLV protocol version %d (this runner program "+
+					"supports up to version %d)\n",
 				klv.Key,
+				c.KLVVersion,
+				klvVersion,
 			)
 		}
 	}
@@ -2,9 +2,9 @@ use std::io::Write;
 
 use {
     anyhow::Context,
-    bstr::ByteSlice,
     hyperscan::{
-        BlockDatabase, Builder, Matching, Pattern, PatternFlags, Patterns,
+        BlockDatabase, Builder, HsError, Matching, Pattern, PatternFlags,
+        Patterns,
     },
     lexopt::Arg,
 };
@@ -36,23 +36,35 @@ fn main() -> anyhow::Result<()> {
     let samples = match b.model.as_str() {
         "compile" => model_compile(&b)?,
         "count" => model_count(&b)?,
+        "count-per-pattern" => model_count_per_pattern(&b)?,
         "count-spans" => model_count_spans(&b)?,
-        "grep" => model_grep(&b)?,
+        "count-offsets" => model_count_offsets(&b)?,
+        "first-match" => model_first_match(&b)?,
+        // 'model_grep' already stops scanning each line at the first match
+        // without tracking the start of the match. So 'grep-fast' is the
+        // same as 'grep'.
+        "grep" | "grep-fast" => model_grep(&b)?,
+        "grep-max" => model_grep_max(&b)?,
         "regex-redux" => model_regex_redux(&b)?,
+        "size" => model_size(&b)?,
         _ => anyhow::bail!("unrecognized benchmark model '{}'", b.model),
     };
     if !quiet {
         let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "# warmup_iters={}", samples.warmup_iters)?;
+        if samples.partial {
+            writeln!(stdout, "# partial=true")?;
+        }
         for s in samples.iter() {
-            writeln!(stdout, "{},{}", s.duration.as_nanos(), s.count)?;
+            writeln!(stdout, "{}", s)?;
         }
     }
     Ok(())
 }
 
-fn model_compile(b: &klv::Benchmark) -> anyhow::Result<Vec<timer::Sample>> {
+fn model_compile(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
-    timer::run_and_count(
+    timer::run_and_count_with_size(
         b,
         |re: BlockDatabase| {
             let scratch = re.alloc_scratch()?;
@@ -63,12 +75,23 @@ fn model_compile(b: &klv::Benchmark) -> anyhow::Result<Vec<timer::Sample>> {
             })?;
             Ok(count)
         },
+        // This is the size reported by 'hs_database_size'.
+        |re: &BlockDatabase| Ok(re.size()?),
         // Does SOM have an impact on compilation times..?
         || compile(b, PatternFlags::empty()),
     )
 }
 
-fn model_count(b: &klv::Benchmark) -> anyhow::Result<Vec<timer::Sample>> {
+fn model_size(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
+    timer::run_and_size(
+        b,
+        // This is the size reported by 'hs_database_size'.
+        |re: &BlockDatabase| Ok(re.size()?),
+        || compile(b, PatternFlags::empty()),
+    )
+}
+
+fn model_count(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     // If all we need to do is count matches, we don't care about SOM.
     let re = compile(b, PatternFlags::empty())?;
@@ -83,9 +106,27 @@ fn model_count(b: &klv::Benchmark) -> anyhow::Result<Vec<timer::Sample>> {
     })
 }
 
-fn model_count_spans(
+fn model_count_per_pattern(
     b: &klv::Benchmark,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
+    let haystack = &*b.haystack;
+    let re = compile(b, PatternFlags::empty())?;
+    let scratch = re.alloc_scratch()?;
+    timer::run(b, || {
+        // Each match contributes the square of its pattern index plus one.
+        // See the description of the 'count-per-pattern' model in MODELS.md.
+        // The ID reported by Hyperscan is the index of the pattern that
+        // matched, since we don't assign IDs to patterns explicitly.
+        let mut sum = 0;
+        re.scan(haystack, &scratch, |id, _from, _to, _flags| {
+            sum += ((id as usize) + 1).pow(2);
+            Matching::Continue
+        })?;
+        Ok(sum)
+    })
+}
+
+fn model_count_spans(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     // In order to compute the length of a match span, we need the start of the
     // match, so we ask Hyperscan to compute it.
@@ -101,30 +142,85 @@ fn model_count_spans(
     })
 }
 
-fn model_grep(b: &klv::Benchmark) -> anyhow::Result<Vec<timer::Sample>> {
+fn model_count_offsets(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
+    let haystack = &*b.haystack;
+    // Like 'count-spans', we need the start of each match.
+    let re = compile(b, PatternFlags::SOM_LEFTMOST)?;
+    let scratch = re.alloc_scratch()?;
+    timer::run(b, || {
+        let mut sum = 0;
+        re.scan(haystack, &scratch, |_id, from, to, _flags| {
+            sum += (from as usize) + (to as usize);
+            Matching::Continue
+        })?;
+        Ok(sum)
+    })
+}
+
+fn model_first_match(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
+    let haystack = &*b.haystack;
+    // Only the end of the match is reported, so we don't need SOM. Note that
+    // Hyperscan reports matches in order of where they end, so the first
+    // match it reports is the one that ends earliest. For regexes where the
+    // leftmost-first match can be longer than that (e.g., 'a+'), this means
+    // Hyperscan will report a different offset than most other regex engines.
+    let re = compile(b, PatternFlags::empty())?;
+    let scratch = re.alloc_scratch()?;
+    timer::run(b, || {
+        let mut end = 0;
+        let result = re.scan(haystack, &scratch, |_id, _from, to, _flags| {
+            end = to as usize;
+            Matching::Terminate
+        });
+        allow_terminated(result)?;
+        Ok(end)
+    })
+}
+
+fn model_grep(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     // We don't need SOM handling to detect if a line matched.
     let re = compile(b, PatternFlags::empty())?;
     let scratch = re.alloc_scratch()?;
     timer::run(b, || {
         let mut count = 0;
-        for line in haystack.lines() {
-            // Apparently the 'scan' API returns an error if we tell searching
-            // to stop, which seems... strange. So we ignore errors for now,
-            // but probably we should check that if an error is returned, it's
-            // the "search was terminated" error and not something else.
-            let _ = re.scan(line, &scratch, |_id, _from, _to, _flags| {
+        for line in lines::bytes(haystack) {
+            let result = re.scan(line, &scratch, |_id, _from, _to, _flags| {
                 count += 1;
                 Matching::Terminate
             });
+            allow_terminated(result)?;
         }
         Ok(count)
     })
 }
 
-fn model_regex_redux(
-    b: &klv::Benchmark,
-) -> anyhow::Result<Vec<timer::Sample>> {
+fn model_grep_max(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
+    let haystack = &*b.haystack;
+    let max = b.max_count_per_line();
+    // Like 'grep', we only need to count matches, so we don't need SOM.
+    let re = compile(b, PatternFlags::empty())?;
+    let scratch = re.alloc_scratch()?;
+    timer::run(b, || {
+        let mut count = 0;
+        for line in lines::bytes(haystack) {
+            let mut line_count = 0;
+            let result = re.scan(line, &scratch, |_id, _from, _to, _flags| {
+                line_count += 1;
+                if line_count >= max {
+                    Matching::Terminate
+                } else {
+                    Matching::Continue
+                }
+            });
+            allow_terminated(result)?;
+            count += line_count;
+        }
+        Ok(count)
+    })
+}
+
+fn model_regex_redux(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
     let haystack = b.haystack_str()?;
     let compile = |p: &str| -> anyhow::Result<regexredux::RegexFn> {
         let re: BlockDatabase =
@@ -132,14 +228,11 @@ fn model_regex_redux(
         let scratch = re.alloc_scratch()?;
         let find = move |h: &str| {
             let mut m: Option<(usize, usize)> = None;
-            // Apparently the 'scan' API returns an error if we tell searching
-            // to stop, which seems... strange. So we ignore errors for now,
-            // but probably we should check that if an error is returned, it's
-            // the "search was terminated" error and not something else.
-            let _ = re.scan(h, &scratch, |_id, from, to, _flags| {
+            let result = re.scan(h, &scratch, |_id, from, to, _flags| {
                 m = Some((from as usize, to as usize));
                 Matching::Terminate
             });
+            allow_terminated(result)?;
             Ok(m)
         };
         Ok(Box::new(find))
@@ -147,6 +240,25 @@ fn model_regex_redux(
     timer::run(b, || regexredux::generic(haystack, compile))
 }
 
+/// Converts the result of a scan into an error, unless the scan failed only
+/// because the match callback asked for it to stop.
+///
+/// Hyperscan reports a search that was stopped via 'Matching::Terminate' as
+/// an error, but that's the only way to stop at the first match. So that
+/// particular error is expected and means success, while every other error is
+/// real. This matches on the error directly instead of looking at its message,
+/// since it's called once per line in the 'grep' model and we don't want to
+/// skew timings.
+#[inline(always)]
+fn allow_terminated(
+    result: Result<(), hyperscan::Error>,
+) -> anyhow::Result<()> {
+    match result {
+        Ok(()) | Err(hyperscan::Error::Hs(HsError::ScanTerminated)) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
 fn compile(
     b: &klv::Benchmark,
     additional_flags: PatternFlags,
@@ -199,5 +311,11 @@ fn bench_flags(b: &klv::Benchmark) -> anyhow::Result<PatternFlags> {
     if b.regex.case_insensitive {
         f |= PatternFlags::CASELESS;
     }
+    if b.regex.multiline {
+        f |= PatternFlags::MULTILINE;
+    }
+    if b.regex.dot_all {
+        f |= PatternFlags::DOTALL;
+    }
     Ok(f)
 }
@@ -231,6 +231,10 @@ impl<'r, 'h> Drop for Matcher<'r, 'h> {
 pub struct Options {
     /// When enabled, ICU regex's case insensitive option is enabled.
     pub case_insensitive: bool,
+    /// When enabled, ICU regex's multiline option is enabled.
+    pub multiline: bool,
+    /// When enabled, ICU regex's dotall option is enabled.
+    pub dot_all: bool,
 }
 
 impl Options {
@@ -239,13 +243,19 @@ impl Options {
         if self.case_insensitive {
             flags |= U_REGEXP_FLAG_CASE_INSENSITIVE;
         }
+        if self.multiline {
+            flags |= U_REGEXP_FLAG_MULTILINE;
+        }
+        if self.dot_all {
+            flags |= U_REGEXP_FLAG_DOTALL;
+        }
         flags
     }
 }
 
 impl Default for Options {
     fn default() -> Options {
-        Options { case_insensitive: false }
+        Options { case_insensitive: false, multiline: false, dot_all: false }
     }
 }
 
@@ -296,6 +306,8 @@ type UErrorCode = c_int;
 type URegexpFlag = u32;
 
 const U_REGEXP_FLAG_CASE_INSENSITIVE: URegexpFlag = 2;
+const U_REGEXP_FLAG_MULTILINE: URegexpFlag = 8;
+const U_REGEXP_FLAG_DOTALL: URegexpFlag = 32;
 
 extern "C" {
     // Regex constructor and destructor.
@@ -30,6 +30,7 @@ fn main() -> anyhow::Result<()> {
         "compile" => model_compile(&b)?,
         "count" => model_count(&b, &mut compile(&b)?)?,
         "count-spans" => model_count_spans(&b, &mut compile(&b)?)?,
+        "count-offsets" => model_count_offsets(&b, &mut compile(&b)?)?,
         "count-captures" => model_count_captures(&b, &mut compile(&b)?)?,
         "grep" => model_grep(&b, &mut compile(&b)?)?,
         "grep-captures" => model_grep_captures(&b, &mut compile(&b)?)?,
@@ -38,14 +39,18 @@ fn main() -> anyhow::Result<()> {
         _ => anyhow::bail!("unrecognized benchmark model '{}'", b.model),
     };
     let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "# warmup_iters={}", samples.warmup_iters)?;
+    if samples.partial {
+        writeln!(stdout, "# partial=true")?;
+    }
     for s in samples.iter() {
-        writeln!(stdout, "{},{}", s.duration.as_nanos(), s.count)?;
+        writeln!(stdout, "{}", s)?;
     }
     Ok(())
 }
 
-fn model_compile(b: &klv::Benchmark) -> anyhow::Result<Vec<timer::Sample>> {
-    let haystack = utf16(&b.haystack).context("invalid haystack")?;
+fn model_compile(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
+    let haystack = haystack(b)?;
     timer::run_and_count(
         b,
         |mut re: Regex| re.matcher(&haystack)?.count(),
@@ -56,16 +61,16 @@ fn model_compile(b: &klv::Benchmark) -> anyhow::Result<Vec<timer::Sample>> {
 fn model_count(
     b: &klv::Benchmark,
     re: &mut Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
-    let haystack = utf16(&b.haystack).context("invalid haystack")?;
+) -> anyhow::Result<timer::Samples> {
+    let haystack = haystack(b)?;
     timer::run(b, || re.matcher(&haystack)?.count())
 }
 
 fn model_count_spans(
     b: &klv::Benchmark,
     re: &mut Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
-    let haystack = utf16(&b.haystack).context("invalid haystack")?;
+) -> anyhow::Result<timer::Samples> {
+    let haystack = haystack(b)?;
     timer::run(b, || {
         let mut sum = 0;
         let mut m = re.matcher(&haystack)?;
@@ -76,11 +81,29 @@ fn model_count_spans(
     })
 }
 
+fn model_count_offsets(
+    b: &klv::Benchmark,
+    re: &mut Regex,
+) -> anyhow::Result<timer::Samples> {
+    let haystack = haystack(b)?;
+    let offsets = byte_offsets(b)?;
+    timer::run(b, || {
+        let mut sum = 0;
+        let mut m = re.matcher(&haystack)?;
+        while m.find()? {
+            let start = m.start(0)?.unwrap();
+            let end = m.end(0)?.unwrap();
+            sum += offsets[start] + offsets[end];
+        }
+        Ok(sum)
+    })
+}
+
 fn model_count_captures(
     b: &klv::Benchmark,
     re: &mut Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
-    let haystack = utf16(&b.haystack).context("invalid haystack")?;
+) -> anyhow::Result<timer::Samples> {
+    let haystack = haystack(b)?;
     let group_len = re.group_len()?;
     timer::run(b, || {
         let mut count = 0;
@@ -99,11 +122,11 @@ fn model_count_captures(
 fn model_grep(
     b: &klv::Benchmark,
     re: &mut Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
-    let haystack = utf16(&b.haystack).context("invalid haystack")?;
+) -> anyhow::Result<timer::Samples> {
+    let haystack = haystack(b)?;
     timer::run(b, || {
         let mut count = 0;
-        for line in Lines::new(&haystack) {
+        for line in lines::utf16(&haystack) {
             if re.matcher(line)?.find()? {
                 count += 1;
             }
@@ -115,12 +138,12 @@ fn model_grep(
 fn model_grep_captures(
     b: &klv::Benchmark,
     re: &mut Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
-    let haystack = utf16(&b.haystack).context("invalid haystack")?;
+) -> anyhow::Result<timer::Samples> {
+    let haystack = haystack(b)?;
     let group_len = re.group_len()?;
     timer::run(b, || {
         let mut count = 0;
-        for line in Lines::new(&haystack) {
+        for line in lines::utf16(&haystack) {
             let mut m = re.matcher(line)?;
             while m.find()? {
                 for i in 0..group_len {
@@ -141,7 +164,68 @@ fn compile(b: &klv::Benchmark) -> anyhow::Result<Regex> {
 
 /// Build ICU regex options from benchmark configuration.
 fn options(b: &klv::Benchmark) -> Options {
-    Options { case_insensitive: b.regex.case_insensitive }
+    Options {
+        case_insensitive: b.regex.case_insensitive,
+        multiline: b.regex.multiline,
+        dot_all: b.regex.dot_all,
+    }
+}
+
+/// Returns the haystack in the given benchmark as UTF-16.
+///
+/// When the haystack is already UTF-16, its bytes are reinterpreted as code
+/// units without any validation or transcoding. (rebar validates UTF-16
+/// haystacks when it loads them.) Latin-1 haystacks map every byte to the
+/// code unit with the same value. Otherwise, the haystack is converted from
+/// UTF-8 and it is an error if it isn't valid UTF-8.
+fn haystack(b: &klv::Benchmark) -> anyhow::Result<Vec<u16>> {
+    match b.haystack_encoding {
+        klv::HaystackEncoding::Utf16le => {
+            anyhow::ensure!(
+                b.haystack.len() % 2 == 0,
+                "UTF-16 haystack has an odd number of bytes",
+            );
+            Ok(b.haystack
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect())
+        }
+        klv::HaystackEncoding::Latin1 => {
+            Ok(b.haystack.iter().map(|&byte| u16::from(byte)).collect())
+        }
+        klv::HaystackEncoding::Utf8 => {
+            utf16(&b.haystack).context("invalid haystack")
+        }
+    }
+}
+
+/// Returns a map from each UTF-16 code unit index in the haystack returned
+/// by `haystack` (plus one for the end of the haystack) to the corresponding
+/// byte offset in the original haystack.
+///
+/// This is used by the 'count-offsets' model, which is defined in terms of
+/// byte offsets. We do the conversion outside of the timed region, so that
+/// this doesn't count against ICU.
+fn byte_offsets(b: &klv::Benchmark) -> anyhow::Result<Vec<usize>> {
+    let units = haystack(b)?.len();
+    match b.haystack_encoding {
+        klv::HaystackEncoding::Utf16le => {
+            Ok((0..=units).map(|i| i * 2).collect())
+        }
+        klv::HaystackEncoding::Latin1 => Ok((0..=units).collect()),
+        klv::HaystackEncoding::Utf8 => {
+            let haystack = std::str::from_utf8(&b.haystack)
+                .context("invalid haystack")?;
+            let mut offsets = Vec::with_capacity(units + 1);
+            for (i, ch) in haystack.char_indices() {
+                for _ in 0..ch.len_utf16() {
+                    offsets.push(i);
+                }
+            }
+            offsets.push(haystack.len());
+            Ok(offsets)
+        }
+    }
 }
 
 /// Converts the given bytes to UTF-16. If the bytes aren't valid UTF-8, then
@@ -155,39 +239,3 @@ fn utf16<T: AsRef<[u8]>>(bytes: T) -> anyhow::Result<Vec<u16>> {
         String::from_utf8(bytes.as_ref().to_vec()).context("invalid UTF-8")?;
     Ok(s.encode_utf16().collect())
 }
-
-struct Lines<'a> {
-    haystack: &'a [u16],
-}
-
-impl<'a> Lines<'a> {
-    fn new(haystack: &'a [u16]) -> Lines<'a> {
-        Lines { haystack }
-    }
-}
-
-impl<'a> Iterator for Lines<'a> {
-    type Item = &'a [u16];
-
-    #[inline]
-    fn next(&mut self) -> Option<&'a [u16]> {
-        let newline = u16::from(b'\n');
-        let mut line = match self.haystack.iter().position(|&c| c == newline) {
-            None if self.haystack.is_empty() => return None,
-            None => {
-                let line = self.haystack;
-                self.haystack = &[];
-                line
-            }
-            Some(end) => {
-                let line = &self.haystack[..end];
-                self.haystack = &self.haystack[end + 1..];
-                line
-            }
-        };
-        if line.last() == Some(&u16::from(b'\r')) {
-            line = &line[..line.len() - 1];
-        }
-        Some(line)
-    }
-}
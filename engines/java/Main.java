@@ -5,10 +5,17 @@ import java.nio.charset.StandardCharsets;
 import java.nio.charset.CodingErrorAction;
 import java.util.ArrayList;
 import java.util.List;
+import java.util.function.Consumer;
 import java.util.regex.Matcher;
 import java.util.regex.Pattern;
 
 final class Config {
+    // The newest version of the KLV protocol that this runner program knows
+    // about. Unrecognized keys from a newer version are ignored instead of
+    // being reported as an error.
+    public static final int KLV_VERSION = 1;
+
+    public int klvVersion;
     public String name;
     public String model;
     public String pattern;
@@ -144,7 +151,9 @@ public final class Main {
         while (raw.size() > 0) {
             OneKLV klv = new OneKLV(decoder, raw);
             raw = raw.subList(klv.length, raw.size());
-            if (klv.key.equals("name")) {
+            if (klv.key.equals("klv-version")) {
+                config.klvVersion = Integer.parseInt(klv.value);
+            } else if (klv.key.equals("name")) {
                 config.name = klv.value;
             } else if (klv.key.equals("model")) {
                 config.model = klv.value;
@@ -164,6 +173,15 @@ public final class Main {
                 config.maxTime = Long.parseLong(klv.value);
             } else if (klv.key.equals("max-warmup-time")) {
                 config.maxWarmupTime = Long.parseLong(klv.value);
+            } else if (config.klvVersion > Config.KLV_VERSION) {
+                System.err.printf(
+                    "warning: ignored unrecognized KLV key '%s' from KLV "
+                    + "protocol version %d (this runner program supports "
+                    + "up to version %d)\n",
+                    klv.key,
+                    config.klvVersion,
+                    Config.KLV_VERSION
+                );
             } else {
                 throw new Exception(String.format(
                     "unrecognized KLV key '%s'",
@@ -281,7 +299,7 @@ public final class Main {
                 // variable directly, so we have to stuff the count inside
                 // an array of length 1.
                 int[] count = new int[]{0};
-                config.haystack.lines().forEach(line -> {
+                forEachLine(config.haystack, line -> {
                     if (re.matcher(line).find()) {
                         count[0]++;
                     }
@@ -298,7 +316,7 @@ public final class Main {
             n -> n,
             () -> {
                 int[] count = new int[]{0};
-                config.haystack.lines().forEach(line -> {
+                forEachLine(config.haystack, line -> {
                     Matcher m = re.matcher(line);
                     while (m.find()) {
                         for (int i = 0; i < m.groupCount() + 1; i++) {
@@ -437,6 +455,26 @@ agggtaa[cgt]|[acg]ttaccct 43
         return samples;
     }
 
+    // Calls 'f' for each line in the given haystack. Lines are split on '\n',
+    // and a single trailing '\r' is stripped from each line. If the haystack
+    // ends with a '\n', then there is no empty line after it.
+    //
+    // We don't use String.lines() because it also treats a lone '\r' as a
+    // line terminator.
+    static void forEachLine(String haystack, Consumer<String> f) {
+        int at = 0;
+        while (at < haystack.length()) {
+            int nl = haystack.indexOf('\n', at);
+            int lineEnd = nl == -1 ? haystack.length() : nl;
+            int next = nl == -1 ? haystack.length() : nl + 1;
+            if (lineEnd > at && haystack.charAt(lineEnd - 1) == '\r') {
+                lineEnd--;
+            }
+            f.accept(haystack.substring(at, lineEnd));
+            at = next;
+        }
+    }
+
     static List<Byte> readStdin() throws Exception {
         ByteArrayOutputStream out = new ByteArrayOutputStream();
         byte[] buf = new byte[1024];
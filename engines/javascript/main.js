@@ -1,6 +1,11 @@
 const buffer = require('buffer')
 const fs = require('fs')
 
+// The newest version of the KLV protocol that this runner program knows
+// about. Unrecognized keys from a newer version are ignored instead of being
+// reported as an error.
+const KLV_VERSION = 1;
+
 function main() {
   // Beware, do not use 'process.stdin.fd'!
   // See: https://github.com/nodejs/node/issues/7439
@@ -97,11 +102,7 @@ function modelGrep(config) {
   const re = compileRegex(config);
   return run(config, () => {
     let count = 0;
-    const lines = config.haystack.split('\n');
-    for (let line of lines) {
-      if (line.endsWith('\r')) {
-        line = line.slice(0, line.length - 1);
-      }
+    for (const line of splitLines(config.haystack)) {
       re.lastIndex = 0;
       if (re.test(line)) {
         count++;
@@ -115,11 +116,7 @@ function modelGrepCaptures(config) {
   const re = compileRegex(config);
   return run(config, () => {
     let count = 0;
-    const lines = config.haystack.split('\n');
-    for (let line of lines) {
-      if (line.endsWith('\r')) {
-        line = line.slice(0, line.length - 1);
-      }
+    for (const line of splitLines(config.haystack)) {
       let m;
       while ((m = re.exec(line)) != null) {
         for (const group of m) {
@@ -233,6 +230,7 @@ function runAndCount(config, count, bench) {
 // data.
 function parseConfig(raw) {
   const config = {
+    klvVersion: 0,
     name: null,
     model: null,
     pattern: null,
@@ -247,7 +245,9 @@ function parseConfig(raw) {
   while (raw.length > 0) {
     const klv = parseOneKLV(raw);
     raw = raw.subarray(klv.length);
-    if (klv.key == "name") {
+    if (klv.key == "klv-version") {
+      config.klvVersion = parseInt(klv.value, 10);
+    } else if (klv.key == "name") {
       config.name = klv.value;
     } else if (klv.key == "model") {
       config.model = klv.value;
@@ -270,6 +270,12 @@ function parseConfig(raw) {
       config.maxTime = BigInt(klv.value);
     } else if (klv.key == "max-warmup-time") {
       config.maxWarmupTime = BigInt(klv.value);
+    } else if (config.klvVersion > KLV_VERSION) {
+      console.error(
+        `warning: ignored unrecognized KLV key '${klv.key}' from KLV ` +
+        `protocol version ${config.klvVersion} (this runner program ` +
+        `supports up to version ${KLV_VERSION})`
+      );
     } else {
       throw new Error(`unrecognized KLV key '${klv.key}'`);
     }
@@ -341,6 +347,22 @@ function regexCount(re, haystack) {
   return count;
 }
 
+// Splits the given haystack into lines. Lines are split on '\n', and a
+// single trailing '\r' is stripped from each line. If the haystack ends
+// with a '\n', then there is no empty line after it.
+function splitLines(haystack) {
+  const lines = haystack.split('\n');
+  if (lines.length > 0 && lines[lines.length - 1].length == 0) {
+    lines.pop();
+  }
+  for (let i = 0; i < lines.length; i++) {
+    if (lines[i].endsWith('\r')) {
+      lines[i] = lines[i].slice(0, lines[i].length - 1);
+    }
+  }
+  return lines;
+}
+
 // Decodes the given 'Buffer' into a string, assuming that
 // the buffer given is UTF-8 encoded bytes. If it's invalid
 // UTF-8, then this throws an exception.
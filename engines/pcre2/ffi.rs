@@ -16,13 +16,24 @@ use {
 const MIN_JIT_STACK_SIZE: usize = 32 * (1 << 10);
 const MAX_JIT_STACK_SIZE: usize = 10 * (1 << 20);
 
+// The workspace for DFA matching is measured in ints. PCRE2 requires at least
+// 20, and pcre2test uses 1000 by default, so we start there. If a search runs
+// out of space, we double the workspace and try again, up to the maximum
+// below (which is 4MB).
+const MIN_DFA_WORKSPACE_SIZE: usize = 1000;
+const MAX_DFA_WORKSPACE_SIZE: usize = 1 << 20;
+
 /// A low level representation of a compiled PCRE2 code object.
 pub struct Regex {
     code: NonNull<pcre2_code_8>,
-    // The pattern string.
-    pattern: String,
+    // The pattern. It isn't necessarily valid UTF-8.
+    pattern: bstr::BString,
     // Whether we've successfully JIT compiled this code object.
     compiled_jit: bool,
+    // Whether this code object was compiled for DFA matching in UTF mode.
+    // In this case, we are responsible for checking that subjects are valid
+    // UTF-8. See 'Subject'.
+    dfa_utf: bool,
 }
 
 // SAFETY: Compiled PCRE2 code objects are immutable once built and explicitly
@@ -52,7 +63,9 @@ impl Drop for Regex {
 impl Regex {
     /// Compile the given pattern with the given options. If there was a
     /// problem compiling the pattern, then return an error.
-    pub fn new(pattern: &str, opts: Options) -> anyhow::Result<Regex> {
+    ///
+    /// The pattern need not be valid UTF-8 when Unicode mode is disabled.
+    pub fn new(pattern: &[u8], opts: Options) -> anyhow::Result<Regex> {
         // We require at least PCRE2 10.34 because we use make critical use of
         // PCRE2_MATCH_INVALID_UTF, which was introduced in PCRE2 10.34. If we
         // really needed to support older versions of PCRE2 we could, but 10.34
@@ -84,11 +97,26 @@ impl Regex {
         pcre2_opts |= PCRE2_USE_OFFSET_LIMIT;
         if opts.ucp {
             pcre2_opts |= PCRE2_UCP;
-            pcre2_opts |= PCRE2_MATCH_INVALID_UTF;
+            // PCRE2's DFA matcher doesn't support PCRE2_MATCH_INVALID_UTF, so
+            // we fall back to plain UTF mode and check subjects ourselves.
+            if opts.dfa {
+                pcre2_opts |= PCRE2_UTF;
+            } else {
+                pcre2_opts |= PCRE2_MATCH_INVALID_UTF;
+            }
         }
         if opts.caseless {
             pcre2_opts |= PCRE2_CASELESS;
         }
+        if opts.multiline {
+            pcre2_opts |= PCRE2_MULTILINE;
+        }
+        if opts.dotall {
+            pcre2_opts |= PCRE2_DOTALL;
+        }
+        if opts.anchored {
+            pcre2_opts |= PCRE2_ANCHORED;
+        }
 
         let mut error_code = 0;
         // SAFETY: Our pattern is valid and our length is correct. It is also
@@ -106,11 +134,16 @@ impl Regex {
             None => return Err(Error { error_code }.into()),
             Some(code) => Regex {
                 code,
-                pattern: pattern.to_string(),
+                pattern: bstr::BString::from(pattern),
                 compiled_jit: false,
+                dfa_utf: opts.dfa && opts.ucp,
             },
         };
         if opts.jit {
+            anyhow::ensure!(
+                !opts.dfa,
+                "the JIT cannot be used with DFA matching",
+            );
             anyhow::ensure!(
                 is_jit_available(),
                 "asked for JIT, but it's unavailable in your build of PCRE2",
@@ -138,6 +171,35 @@ impl Regex {
         }
     }
 
+    /// Returns the size, in bytes, of the memory used by this compiled code
+    /// object. This includes the size of the JIT compiled code, if any.
+    pub fn size(&self) -> anyhow::Result<usize> {
+        let mut size = self.pattern_info_size(PCRE2_INFO_SIZE)?;
+        if self.compiled_jit {
+            size += self.pattern_info_size(PCRE2_INFO_JITSIZE)?;
+        }
+        Ok(size)
+    }
+
+    /// Returns the value of a 'pcre2_pattern_info' request whose result is a
+    /// 'size_t'.
+    fn pattern_info_size(&self, what: u32) -> anyhow::Result<usize> {
+        let mut size: usize = 0;
+        // SAFETY: Our code is a valid pointer, and callers only pass requests
+        // whose result is a 'size_t', which is what 'size' is.
+        let error_code = unsafe {
+            pcre2_pattern_info_8(
+                self.code.as_ptr(),
+                what,
+                &mut size as *mut usize as *mut c_void,
+            )
+        };
+        if error_code < 0 {
+            return Err(Error { error_code }.into());
+        }
+        Ok(size)
+    }
+
     /// Create a new match data block that is sized to be able to hold all
     /// possible capturing groups (including the implicit unnamed group) in
     /// this regex.
@@ -163,10 +225,70 @@ impl Regex {
         end: usize,
         match_data: &mut MatchData,
     ) -> anyhow::Result<bool> {
-        let matched = match_data.try_find(self, haystack, start, end)?;
+        let matched = match_data.try_find(self, haystack, start, end, 0)?;
         Ok(matched)
     }
 
+    /// Like `try_find`, but only reports a non-empty match that begins at
+    /// `start`.
+    ///
+    /// This is useful for implementing PCRE2's recommended strategy for
+    /// iterating over matches: after an empty match, one should first look
+    /// for a non-empty match at the same position before advancing. Note that
+    /// since this sets options at match time, PCRE2 will not use the JIT for
+    /// this search even if the regex was JIT compiled.
+    pub fn try_find_nonempty_anchored(
+        &self,
+        haystack: &[u8],
+        start: usize,
+        end: usize,
+        match_data: &mut MatchData,
+    ) -> anyhow::Result<bool> {
+        let options = PCRE2_NOTEMPTY_ATSTART | PCRE2_ANCHORED;
+        let matched =
+            match_data.try_find(self, haystack, start, end, options)?;
+        Ok(matched)
+    }
+
+    /// Like `try_find`, but uses PCRE2's DFA matcher via `pcre2_dfa_match`.
+    ///
+    /// The DFA matcher finds every match starting at the leftmost position
+    /// where a match begins, and we always report the longest one. So unlike
+    /// `try_find`, this implements leftmost-longest semantics. It also never
+    /// reports capturing groups, so the match data block should be created
+    /// via `Regex::create_match_data_for_matches_only`.
+    pub fn try_find_dfa(
+        &self,
+        subject: &Subject<'_>,
+        start: usize,
+        end: usize,
+        match_data: &mut MatchData,
+    ) -> anyhow::Result<bool> {
+        match match_data.try_find_dfa(self, subject, start, end) {
+            Ok(matched) => Ok(matched),
+            Err(err) => match err.dfa_hint() {
+                None => Err(err.into()),
+                Some(hint) => Err(anyhow::Error::from(err).context(hint)),
+            },
+        }
+    }
+
+    /// Like `try_find_iter`, but uses PCRE2's DFA matcher. See `try_find_dfa`
+    /// for how this differs from `try_find_iter`.
+    pub fn try_find_dfa_iter<'r, 's, 'h, 'm>(
+        &'r self,
+        subject: &'s Subject<'h>,
+        match_data: &'m mut MatchData,
+    ) -> TryFindDfaMatches<'r, 's, 'h, 'm> {
+        TryFindDfaMatches {
+            re: self,
+            match_data,
+            subject,
+            at: 0,
+            last_match_end: None,
+        }
+    }
+
     /// Return an iterator over all non-overlapping successive matches
     /// in the given input. This iterator only reports overall match
     /// spans, so callers should pass a match data block created via
@@ -188,6 +310,30 @@ impl Regex {
             last_match_end: None,
         }
     }
+
+    /// Like `try_find_iter`, but only reports matches in the given window of
+    /// the haystack.
+    ///
+    /// The start of the window is given to PCRE2 as its starting offset, so
+    /// look-behind assertions (including `\b`) at the start of the window can
+    /// see the bytes before it. But PCRE2 has no way to stop a search before
+    /// the end of the subject. (Its offset limit only bounds where a match may
+    /// start.) So the haystack is sliced at the end of the window, and PCRE2
+    /// treats the end of the window as the end of the subject.
+    pub fn try_find_iter_window<'r, 'h, 'm>(
+        &'r self,
+        haystack: &'h [u8],
+        window: std::ops::Range<usize>,
+        match_data: &'m mut MatchData,
+    ) -> TryFindMatches<'r, 'h, 'm> {
+        TryFindMatches {
+            re: self,
+            match_data,
+            haystack: &haystack[..window.end],
+            at: window.start,
+            last_match_end: None,
+        }
+    }
 }
 
 /// Options that can be passed to Regex::new to configure a subset
@@ -206,11 +352,73 @@ pub struct Options {
     /// When enabled, PCRE2's "caseless" option is enabled when compiling the
     /// regex.
     pub caseless: bool,
+    /// When enabled, PCRE2's "multiline" option is enabled when compiling the
+    /// regex. This makes `^` and `$` match at the start and end of lines.
+    pub multiline: bool,
+    /// When enabled, PCRE2's "dotall" option is enabled when compiling the
+    /// regex. This makes `.` match every character, including `\n`.
+    pub dotall: bool,
+    /// When enabled, PCRE2's "anchored" option is enabled when compiling the
+    /// regex. This makes every search anchored at its starting offset. We set
+    /// it at compile time instead of match time because passing it at match
+    /// time prevents the JIT from being used.
+    pub anchored: bool,
+    /// When enabled, the regex is compiled for use with PCRE2's DFA matcher,
+    /// i.e., `Regex::try_find_dfa`. This cannot be combined with `jit`.
+    pub dfa: bool,
 }
 
 impl Default for Options {
     fn default() -> Options {
-        Options { jit: true, ucp: true, caseless: false }
+        Options {
+            jit: true,
+            ucp: true,
+            caseless: false,
+            multiline: false,
+            dotall: false,
+            anchored: false,
+            dfa: false,
+        }
+    }
+}
+
+/// A haystack to search with PCRE2's DFA matcher.
+///
+/// In UTF mode, PCRE2 checks that the subject is valid UTF-8 on every search,
+/// from the starting offset to the end of the subject. When iterating over
+/// matches in a big haystack, that makes each search linear in the size of
+/// the haystack, even when the match is found right away. And since the DFA
+/// matcher doesn't support PCRE2_MATCH_INVALID_UTF, we can't sidestep it like
+/// we do for `pcre2_match`. So instead, we check the subject once when this
+/// is created and then tell PCRE2 not to check it again. The borrow ensures
+/// the subject can't change in the meantime.
+#[derive(Clone, Debug)]
+pub struct Subject<'h> {
+    bytes: &'h [u8],
+    valid_utf8: bool,
+}
+
+impl<'h> Subject<'h> {
+    /// Create a new subject for searching with the given regex. The subject
+    /// is only checked for UTF-8 validity if the regex needs it.
+    pub fn new(re: &Regex, bytes: &'h [u8]) -> Subject<'h> {
+        let valid_utf8 = re.dfa_utf && std::str::from_utf8(bytes).is_ok();
+        Subject { bytes, valid_utf8 }
+    }
+
+    /// Return the PCRE2 options that should be used when searching this
+    /// subject at the given offset. If the subject (or the offset) isn't
+    /// valid UTF-8, then we let PCRE2 check it so that it reports an error.
+    fn options(&self, start: usize) -> u32 {
+        let at_boundary = !matches!(
+            self.bytes.get(start),
+            Some(&b) if b & 0b1100_0000 == 0b1000_0000,
+        );
+        if self.valid_utf8 && at_boundary {
+            PCRE2_NO_UTF_CHECK
+        } else {
+            0
+        }
     }
 }
 
@@ -225,6 +433,9 @@ pub struct MatchData {
     jit_stack: Option<NonNull<pcre2_jit_stack_8>>,
     ovector_ptr: NonNull<usize>,
     ovector_count: u32,
+    // The workspace used by the DFA matcher. This is empty until the first
+    // DFA search, and grows when a search needs more space.
+    dfa_workspace: Vec<c_int>,
     // We specifically record whether the ovector in this match data block
     // corresponds to a match or not. PCRE2 does not appear to clear or write
     // anything in ovector if there is no match, so there is no other way to
@@ -366,6 +577,7 @@ impl MatchData {
             jit_stack,
             ovector_ptr,
             ovector_count,
+            dfa_workspace: vec![],
             matched: false,
         }
     }
@@ -383,6 +595,7 @@ impl MatchData {
         mut haystack: &[u8],
         start: usize,
         end: usize,
+        options: u32,
     ) -> Result<bool, Error> {
         self.matched = false;
         // The regex-automata handle this case correctly, but I'm not sure if
@@ -408,15 +621,15 @@ impl MatchData {
             pcre2_set_offset_limit_8(self.match_context.as_ptr(), end);
         }
         // SAFETY: Our 'code', 'haystack', 'match_data' and 'match_context'
-        // pointers are all valid, by construction. We don't permit setting any
-        // options at match time (we set them all at regex compile time).
+        // pointers are all valid, by construction. Most options are set at
+        // regex compile time, but callers may pass a few at match time.
         let rc = unsafe {
             pcre2_match_8(
                 re.code.as_ptr(),
                 haystack.as_ptr(),
                 haystack.len(),
                 start,
-                0,
+                options,
                 self.match_data.as_ptr(),
                 self.match_context.as_ptr(),
             )
@@ -442,6 +655,77 @@ impl MatchData {
         }
     }
 
+    /// Execute PCRE2's DFA match routine on the given subject starting at the
+    /// given offset. If the workspace is too small, then it is grown and the
+    /// search is retried.
+    ///
+    /// This returns false if no match occurred.
+    fn try_find_dfa(
+        &mut self,
+        re: &Regex,
+        subject: &Subject<'_>,
+        start: usize,
+        end: usize,
+    ) -> Result<bool, Error> {
+        self.matched = false;
+        if start > end {
+            return Ok(false);
+        }
+        // See 'try_find' for why we do this.
+        const EMPTY: &[u8] = &[];
+        let haystack =
+            if subject.bytes.is_empty() { EMPTY } else { subject.bytes };
+        let options = subject.options(start);
+        if self.dfa_workspace.is_empty() {
+            self.dfa_workspace = vec![0; MIN_DFA_WORKSPACE_SIZE];
+        }
+
+        // SAFETY: Our match context is valid and 'end' is treated as
+        // a limit, so it shouldn't matter if it's a valid index into the
+        // haystack.
+        unsafe {
+            // This always returns 0.
+            pcre2_set_offset_limit_8(self.match_context.as_ptr(), end);
+        }
+        loop {
+            // SAFETY: Our 'code', 'haystack', 'match_data', 'match_context'
+            // and workspace pointers are all valid, by construction, and the
+            // workspace length is correct. We only pass PCRE2_NO_UTF_CHECK
+            // when 'Subject' has verified that the haystack is valid UTF-8 and
+            // that 'start' is at a character boundary.
+            let rc = unsafe {
+                pcre2_dfa_match_8(
+                    re.code.as_ptr(),
+                    haystack.as_ptr(),
+                    haystack.len(),
+                    start,
+                    options,
+                    self.match_data.as_ptr(),
+                    self.match_context.as_ptr(),
+                    self.dfa_workspace.as_mut_ptr(),
+                    self.dfa_workspace.len(),
+                )
+            };
+            if rc == PCRE2_ERROR_NOMATCH {
+                return Ok(false);
+            } else if rc >= 0 {
+                // A return value of 0 means there were more matches (of
+                // different lengths, all starting at the same position) than
+                // fit in the ovector. That's expected since we only make room
+                // for one, and the longest match is always first.
+                self.matched = true;
+                return Ok(true);
+            } else if rc == PCRE2_ERROR_DFA_WSSIZE
+                && self.dfa_workspace.len() < MAX_DFA_WORKSPACE_SIZE
+            {
+                let len = self.dfa_workspace.len() * 2;
+                self.dfa_workspace.resize(len, 0);
+                continue;
+            }
+            return Err(Error { error_code: rc });
+        }
+    }
+
     /// Return the match for this match data block. The match span always
     /// corresponds to the group span at index 0.
     pub fn get_match(&self) -> Option<(usize, usize)> {
@@ -576,12 +860,99 @@ impl<'r, 'h, 'm> TryFindMatches<'r, 'h, 'm> {
     }
 }
 
+/// Like `TryFindMatches`, but for PCRE2's DFA matcher. Each match reported is
+/// the longest match starting at the leftmost position possible.
+#[derive(Debug)]
+pub struct TryFindDfaMatches<'r, 's, 'h, 'm> {
+    re: &'r Regex,
+    match_data: &'m mut MatchData,
+    subject: &'s Subject<'h>,
+    at: usize,
+    last_match_end: Option<usize>,
+}
+
+impl<'r, 's, 'h, 'm> Iterator for TryFindDfaMatches<'r, 's, 'h, 'm> {
+    type Item = anyhow::Result<(usize, usize)>;
+
+    #[inline]
+    fn next(&mut self) -> Option<anyhow::Result<(usize, usize)>> {
+        let len = self.subject.bytes.len();
+        loop {
+            if let Err(err) = self.re.try_find_dfa(
+                self.subject,
+                self.at,
+                len,
+                self.match_data,
+            ) {
+                return Some(Err(err));
+            }
+            let m = self.match_data.get_match()?;
+            // An empty match right where the last match ended would overlap
+            // with it, so skip ahead by one character and search again.
+            if m.0 >= m.1 && Some(m.1) == self.last_match_end {
+                if self.at >= len {
+                    return None;
+                }
+                self.at += 1;
+                if self.re.dfa_utf {
+                    while self.at < len
+                        && self.subject.bytes[self.at] & 0b1100_0000
+                            == 0b1000_0000
+                    {
+                        self.at += 1;
+                    }
+                }
+                continue;
+            }
+            self.at = m.1;
+            self.last_match_end = Some(m.1);
+            return Some(Ok(m));
+        }
+    }
+}
+
 /// An error reported by PCRE2.
 #[derive(Clone, Debug)]
 pub struct Error {
     error_code: c_int,
 }
 
+impl Error {
+    /// If this is one of the errors specific to PCRE2's DFA matcher, then
+    /// this returns a more detailed explanation of what went wrong than
+    /// PCRE2's own message.
+    fn dfa_hint(&self) -> Option<&'static str> {
+        Some(match self.error_code {
+            PCRE2_ERROR_DFA_UITEM => {
+                "DFA matching doesn't support some pattern items, \
+                 e.g., backreferences, \\C in UTF mode or callouts \
+                 that use captures"
+            }
+            PCRE2_ERROR_DFA_UCOND => {
+                "DFA matching doesn't support conditions that test \
+                 backreferences or recursion"
+            }
+            PCRE2_ERROR_DFA_UFUNC => {
+                "DFA matching doesn't support this function, which \
+                 suggests a bug in this runner"
+            }
+            PCRE2_ERROR_DFA_WSSIZE => {
+                "DFA matching needed more workspace than the maximum \
+                 this runner permits"
+            }
+            PCRE2_ERROR_DFA_RECURSE => {
+                "DFA matching ran out of room for the nested calls \
+                 needed by recursion or lookaround in the pattern"
+            }
+            PCRE2_ERROR_DFA_BADRESTART | PCRE2_ERROR_DFA_UINVALID_UTF => {
+                "DFA matching was used incorrectly, which suggests a bug \
+                 in this runner"
+            }
+            _ => return None,
+        })
+    }
+}
+
 impl std::error::Error for Error {}
 
 impl std::fmt::Display for Error {
@@ -698,16 +1069,31 @@ type pcre2_match_data_8 = c_void;
 type PCRE2_UCHAR8 = u8;
 type PCRE2_SPTR8 = *const PCRE2_UCHAR8;
 
+const PCRE2_ANCHORED: u32 = 2147483648;
 const PCRE2_CASELESS: u32 = 8;
 const PCRE2_CONFIG_JIT: u32 = 1;
 const PCRE2_CONFIG_VERSION: u32 = 11;
+const PCRE2_DOTALL: u32 = 32;
 const PCRE2_ERROR_BADDATA: i32 = -29;
+const PCRE2_ERROR_DFA_BADRESTART: i32 = -38;
+const PCRE2_ERROR_DFA_RECURSE: i32 = -39;
+const PCRE2_ERROR_DFA_UCOND: i32 = -40;
+const PCRE2_ERROR_DFA_UFUNC: i32 = -41;
+const PCRE2_ERROR_DFA_UINVALID_UTF: i32 = -66;
+const PCRE2_ERROR_DFA_UITEM: i32 = -42;
+const PCRE2_ERROR_DFA_WSSIZE: i32 = -43;
 const PCRE2_ERROR_NOMEMORY: i32 = -48;
 const PCRE2_ERROR_NOMATCH: i32 = -1;
+const PCRE2_INFO_JITSIZE: u32 = 10;
+const PCRE2_INFO_SIZE: u32 = 22;
 const PCRE2_JIT_COMPLETE: u32 = 1;
 const PCRE2_MATCH_INVALID_UTF: u32 = 67108864;
+const PCRE2_MULTILINE: u32 = 1024;
+const PCRE2_NOTEMPTY_ATSTART: u32 = 8;
+const PCRE2_NO_UTF_CHECK: u32 = 1073741824;
 const PCRE2_USE_OFFSET_LIMIT: u32 = 8388608;
 const PCRE2_UCP: u32 = 131072;
+const PCRE2_UTF: u32 = 524288;
 const PCRE2_UNSET: usize = std::usize::MAX;
 
 extern "C" {
@@ -721,6 +1107,17 @@ extern "C" {
         ctx: *mut pcre2_compile_context_8,
     ) -> *mut pcre2_code_8;
     fn pcre2_config_8(option: u32, code: *mut c_void) -> c_int;
+    fn pcre2_dfa_match_8(
+        code: *const pcre2_code_8,
+        subject: PCRE2_SPTR8,
+        subject_len: usize,
+        start: usize,
+        options: u32,
+        data: *mut pcre2_match_data_8,
+        ctx: *mut pcre2_match_context_8,
+        workspace: *mut c_int,
+        workspace_len: usize,
+    ) -> c_int;
     fn pcre2_get_error_message_8(
         error_code: c_int,
         buf: *mut PCRE2_UCHAR8,
@@ -764,6 +1161,11 @@ extern "C" {
         ctx: *mut pcre2_general_context_8,
     ) -> *mut pcre2_match_data_8;
     fn pcre2_match_data_free_8(data: *mut pcre2_match_data_8);
+    fn pcre2_pattern_info_8(
+        code: *const pcre2_code_8,
+        what: u32,
+        r#where: *mut c_void,
+    ) -> c_int;
     fn pcre2_set_match_limit_8(
         ctx: *mut pcre2_match_context_8,
         limit: u32,
@@ -831,4 +1233,35 @@ mod tests {
         // The last search was unsuccessful, so there should be no match.
         assert_eq!(None, match_data.get_match());
     }
+
+    // Check that DFA matching reports the longest match at the leftmost
+    // position, that it handles empty matches and that unsupported patterns
+    // report an error when searching.
+    #[test]
+    fn dfa() {
+        let opts = Options { jit: false, dfa: true, ..Options::default() };
+        let re = Regex::new(r"sam|samwise", opts.clone()).unwrap();
+        let hay = Subject::new(&re, "samwise and sam".as_bytes());
+        let mut md = re.create_match_data_for_matches_only();
+        let got: Vec<(usize, usize)> = re
+            .try_find_dfa_iter(&hay, &mut md)
+            .collect::<anyhow::Result<_>>()
+            .unwrap();
+        assert_eq!(vec![(0, 7), (12, 15)], got);
+
+        let re = Regex::new(r"a*", opts.clone()).unwrap();
+        let hay = Subject::new(&re, "bäaab".as_bytes());
+        let mut md = re.create_match_data_for_matches_only();
+        let got: Vec<(usize, usize)> = re
+            .try_find_dfa_iter(&hay, &mut md)
+            .collect::<anyhow::Result<_>>()
+            .unwrap();
+        assert_eq!(vec![(0, 0), (1, 1), (3, 5), (6, 6)], got);
+
+        let re = Regex::new(r"(a)\1", opts).unwrap();
+        let hay = Subject::new(&re, b"aa");
+        let mut md = re.create_match_data_for_matches_only();
+        let err = re.try_find_dfa(&hay, 0, 2, &mut md).unwrap_err();
+        assert!(format!("{:#}", err).contains("backreferences"));
+    }
 }
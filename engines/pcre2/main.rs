@@ -2,11 +2,10 @@ use std::io::Write;
 
 use {
     anyhow::Context,
-    bstr::ByteSlice,
     lexopt::{Arg, ValueExt},
 };
 
-use crate::ffi::{is_jit_available, Options, Regex};
+use crate::ffi::{is_jit_available, Options, Regex, Subject};
 
 mod ffi;
 
@@ -22,19 +21,22 @@ fn main() -> anyhow::Result<()> {
         }
     };
     anyhow::ensure!(
-        engine == "interp" || engine == "jit",
+        engine == "interp" || engine == "jit" || engine == "dfa",
         "unrecognized engine '{}'",
         engine,
     );
     let jit = engine == "jit";
+    let dfa = engine == "dfa";
     if jit && !is_jit_available() {
         anyhow::bail!("JIT engine unavailable because JIT is not enabled");
     }
-    let (mut quiet, mut version) = (false, false);
+    let (mut quiet, mut version, mut validate) = (false, false, false);
     while let Some(arg) = p.next()? {
         match arg {
             Arg::Short('h') | Arg::Long("help") => {
-                anyhow::bail!("main <engine> [--version | --quiet]")
+                anyhow::bail!(
+                    "main <engine> [--version | --quiet | --validate]"
+                )
             }
             Arg::Short('q') | Arg::Long("quiet") => {
                 quiet = true;
@@ -42,6 +44,9 @@ fn main() -> anyhow::Result<()> {
             Arg::Long("version") => {
                 version = true;
             }
+            Arg::Long("validate") => {
+                validate = true;
+            }
             _ => return Err(arg.unexpected().into()),
         }
     }
@@ -50,31 +55,87 @@ fn main() -> anyhow::Result<()> {
         writeln!(std::io::stdout(), "{}", v)?;
         return Ok(());
     }
+    if validate {
+        return timer::validate(|b| run(b, jit, dfa));
+    }
     let b = klv::Benchmark::read(std::io::stdin())
         .context("failed to read KLV data from <stdin>")?;
-    let samples = match b.model.as_str() {
-        "compile" => model_compile(&b, jit)?,
-        "count" => model_count(&b, &compile(&b, jit)?)?,
-        "count-spans" => model_count_spans(&b, &compile(&b, jit)?)?,
-        "count-captures" => model_count_captures(&b, &compile(&b, jit)?)?,
-        "grep" => model_grep(&b, &compile(&b, jit)?)?,
-        "grep-captures" => model_grep_captures(&b, &compile(&b, jit)?)?,
-        "regex-redux" => model_regex_redux(&b, jit)?,
-        _ => anyhow::bail!("unrecognized benchmark model '{}'", b.model),
-    };
+    let samples = run(&b, jit, dfa)?;
     if !quiet {
         let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "# warmup_iters={}", samples.warmup_iters)?;
+        if samples.partial {
+            writeln!(stdout, "# partial=true")?;
+        }
         for s in samples.iter() {
-            writeln!(stdout, "{},{}", s.duration.as_nanos(), s.count)?;
+            writeln!(stdout, "{}", s)?;
         }
     }
     Ok(())
 }
 
+/// Runs the given benchmark with the engine selected by `jit` and `dfa` and
+/// returns its samples.
+fn run(
+    b: &klv::Benchmark,
+    jit: bool,
+    dfa: bool,
+) -> anyhow::Result<timer::Samples> {
+    if dfa {
+        return match b.model.as_str() {
+            "compile" => model_compile_dfa(b),
+            // See below for why 'count-cold' is the same as 'count'.
+            "count" | "count-cold" => model_count_dfa(b, &compile_dfa(b)?),
+            // DFA matching always reports the longest match at the leftmost
+            // position, so 'count-longest' is the same as 'count-spans'.
+            "count-spans" | "count-longest" => {
+                model_count_spans_dfa(b, &compile_dfa(b)?)
+            }
+            "count-offsets" => model_count_offsets_dfa(b, &compile_dfa(b)?),
+            "grep" | "grep-fast" => model_grep_dfa(b, &compile_dfa(b)?),
+            "size" => model_size(b, || compile_dfa(b)),
+            "anchored-count" | "count-captures" | "count-empty"
+            | "first-match" | "grep-captures" | "grep-max" | "regex-redux" => {
+                anyhow::bail!(
+                    "benchmark model '{}' is unsupported by DFA matching",
+                    b.model,
+                )
+            }
+            _ => anyhow::bail!("unrecognized benchmark model '{}'", b.model),
+        };
+    }
+    match b.model.as_str() {
+        "anchored-count" => model_anchored_count(b, jit),
+        "compile" => model_compile(b, jit),
+        // PCRE2 doesn't build any search state lazily. (The JIT, when
+        // enabled, does all of its work at compile time.) So there's nothing
+        // to make cold and 'count-cold' is the same as 'count'.
+        "count" | "count-cold" => model_count(b, &compile(b, jit)?),
+        // The standard interpreter (and the JIT) only has leftmost-first match
+        // semantics, so 'count-longest' is the same as 'count-spans'.
+        "count-spans" | "count-longest" => {
+            model_count_spans(b, &compile(b, jit)?)
+        }
+        "count-offsets" => model_count_offsets(b, &compile(b, jit)?),
+        "count-captures" => model_count_captures(b, &compile(b, jit)?),
+        "count-empty" => model_count_empty(b, &compile(b, jit)?),
+        "first-match" => model_first_match(b, &compile(b, jit)?),
+        // 'model_grep' already uses match data without room for capturing
+        // groups, which is the least work PCRE2 can do to report whether a
+        // line matches. So 'grep-fast' is the same as 'grep'.
+        "grep" | "grep-fast" => model_grep(b, &compile(b, jit)?),
+        "grep-captures" => model_grep_captures(b, &compile(b, jit)?),
+        "grep-max" => model_grep_max(b, &compile(b, jit)?),
+        "regex-redux" => model_regex_redux(b, jit),
+        "size" => model_size(b, || compile(b, jit)),
+        _ => anyhow::bail!("unrecognized benchmark model '{}'", b.model),
+    }
+}
+
 fn model_compile(
     b: &klv::Benchmark,
     jit: bool,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run_and_count(
         b,
@@ -86,15 +147,48 @@ fn model_compile(
     )
 }
 
+fn model_size(
+    b: &klv::Benchmark,
+    compile: impl FnMut() -> anyhow::Result<Regex>,
+) -> anyhow::Result<timer::Samples> {
+    timer::run_and_size(b, |re: &Regex| re.size(), compile)
+}
+
+fn model_anchored_count(
+    b: &klv::Benchmark,
+    jit: bool,
+) -> anyhow::Result<timer::Samples> {
+    let haystack = &*b.haystack;
+    let opts = Options { anchored: true, ..options(b, jit) };
+    let re = Regex::new(pattern(b)?, opts)?;
+    let mut md = re.create_match_data_for_matches_only();
+    timer::run(b, || {
+        let (mut count, mut at) = (0, 0);
+        while at < haystack.len() {
+            re.try_find(haystack, at, haystack.len(), &mut md)?;
+            at = match md.get_match() {
+                None => at + 1,
+                Some((_, end)) => {
+                    count += 1;
+                    // Always make progress, even on an empty match.
+                    end.max(at + 1)
+                }
+            };
+        }
+        Ok(count)
+    })
+}
+
 fn model_count(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
-    let haystack = &*b.haystack;
+) -> anyhow::Result<timer::Samples> {
+    let (haystack, window) = (&*b.haystack, b.search_window()?);
     let mut md = re.create_match_data_for_matches_only();
     timer::run(b, || {
         let mut count = 0;
-        for result in re.try_find_iter(haystack, &mut md) {
+        let it = re.try_find_iter_window(haystack, window.clone(), &mut md);
+        for result in it {
             result?;
             count += 1;
         }
@@ -102,10 +196,25 @@ fn model_count(
     })
 }
 
+fn model_first_match(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    let haystack = &*b.haystack;
+    let mut md = re.create_match_data_for_matches_only();
+    timer::run(b, || {
+        if !re.try_find(haystack, 0, haystack.len(), &mut md)? {
+            return Ok(0);
+        }
+        // OK because we just found a match.
+        Ok(md.get_match().unwrap().1)
+    })
+}
+
 fn model_count_spans(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     let mut md = re.create_match_data_for_matches_only();
     timer::run(b, || {
@@ -118,10 +227,26 @@ fn model_count_spans(
     })
 }
 
+fn model_count_offsets(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    let haystack = &*b.haystack;
+    let mut md = re.create_match_data_for_matches_only();
+    timer::run(b, || {
+        let mut sum = 0;
+        for result in re.try_find_iter(haystack, &mut md) {
+            let (start, end) = result?;
+            sum += start + end;
+        }
+        Ok(sum)
+    })
+}
+
 fn model_count_captures(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     let mut md = re.create_match_data();
     timer::run(b, || {
@@ -144,15 +269,65 @@ fn model_count_captures(
     })
 }
 
+fn model_count_empty(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    // PCRE2 doesn't provide a match iterator, so we follow the strategy
+    // recommended by PCRE2's documentation (and used by pcre2demo). Namely,
+    // after an empty match, we first look for a non-empty match anchored at
+    // the same position. Only if that fails do we advance by one character.
+    // In UTF mode, a character may be more than one byte.
+    let haystack = &*b.haystack;
+    let utf = b.regex.unicode;
+    let mut md = re.create_match_data_for_matches_only();
+    timer::run(b, || {
+        let (mut count, mut at, mut empty) = (0, 0, false);
+        loop {
+            if empty {
+                if at >= haystack.len() {
+                    break;
+                }
+                if re.try_find_nonempty_anchored(
+                    haystack,
+                    at,
+                    haystack.len(),
+                    &mut md,
+                )? {
+                    count += 1;
+                    at = md.get_match().unwrap().1;
+                    empty = false;
+                    continue;
+                }
+                at += 1;
+                while utf
+                    && at < haystack.len()
+                    && haystack[at] & 0b1100_0000 == 0b1000_0000
+                {
+                    at += 1;
+                }
+            }
+            if !re.try_find(haystack, at, haystack.len(), &mut md)? {
+                break;
+            }
+            let (start, end) = md.get_match().unwrap();
+            count += 1;
+            at = end;
+            empty = start == end;
+        }
+        Ok(count)
+    })
+}
+
 fn model_grep(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     let mut md = re.create_match_data_for_matches_only();
     timer::run(b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             if re.try_find(line, 0, line.len(), &mut md)? {
                 count += 1;
             }
@@ -164,12 +339,12 @@ fn model_grep(
 fn model_grep_captures(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     let mut md = re.create_match_data();
     timer::run(b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             let mut at = 0;
             while let Some((_, end)) = {
                 re.try_find(line, at, line.len(), &mut md)?;
@@ -189,13 +364,38 @@ fn model_grep_captures(
     })
 }
 
+fn model_grep_max(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    let haystack = &*b.haystack;
+    let max = b.max_count_per_line();
+    let mut md = re.create_match_data_for_matches_only();
+    timer::run(b, || {
+        let mut count = 0;
+        for line in lines::bytes(haystack) {
+            let (mut at, mut line_count) = (0, 0);
+            while line_count < max
+                && re.try_find(line, at, line.len(), &mut md)?
+            {
+                line_count += 1;
+                // Benchmark definition says we may assume empty matches are
+                // impossible.
+                at = md.get_match().unwrap().1;
+            }
+            count += line_count;
+        }
+        Ok(count)
+    })
+}
+
 fn model_regex_redux(
     b: &klv::Benchmark,
     jit: bool,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = b.haystack_str()?;
     let compile = |pattern: &str| -> anyhow::Result<regexredux::RegexFn> {
-        let re = Regex::new(pattern, options(b, jit))?;
+        let re = Regex::new(pattern.as_bytes(), options(b, jit))?;
         let mut md = re.create_match_data_for_matches_only();
         let find = move |h: &str| {
             re.try_find(h.as_bytes(), 0, h.len(), &mut md)?;
@@ -206,11 +406,128 @@ fn model_regex_redux(
     timer::run(b, || regexredux::generic(haystack, compile))
 }
 
+// The models below use PCRE2's DFA matcher. It finds the longest match
+// starting at the leftmost position instead of the leftmost-first match, and
+// it doesn't support capturing groups at all. So only the models that don't
+// need captures are supported, and counts may differ from the other PCRE2
+// engines.
+
+fn model_compile_dfa(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
+    let haystack = &*b.haystack;
+    timer::run_and_count(
+        b,
+        |re: Regex| {
+            let subject = Subject::new(&re, haystack);
+            let mut md = re.create_match_data_for_matches_only();
+            let mut count = 0;
+            for result in re.try_find_dfa_iter(&subject, &mut md) {
+                result?;
+                count += 1;
+            }
+            Ok(count)
+        },
+        || compile_dfa(b),
+    )
+}
+
+fn model_count_dfa(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    let subject = Subject::new(re, &b.haystack);
+    let mut md = re.create_match_data_for_matches_only();
+    timer::run(b, || {
+        let mut count = 0;
+        for result in re.try_find_dfa_iter(&subject, &mut md) {
+            result?;
+            count += 1;
+        }
+        Ok(count)
+    })
+}
+
+fn model_count_spans_dfa(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    let subject = Subject::new(re, &b.haystack);
+    let mut md = re.create_match_data_for_matches_only();
+    timer::run(b, || {
+        let mut sum = 0;
+        for result in re.try_find_dfa_iter(&subject, &mut md) {
+            let (start, end) = result?;
+            sum += end - start;
+        }
+        Ok(sum)
+    })
+}
+
+fn model_count_offsets_dfa(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    let subject = Subject::new(re, &b.haystack);
+    let mut md = re.create_match_data_for_matches_only();
+    timer::run(b, || {
+        let mut sum = 0;
+        for result in re.try_find_dfa_iter(&subject, &mut md) {
+            let (start, end) = result?;
+            sum += start + end;
+        }
+        Ok(sum)
+    })
+}
+
+fn model_grep_dfa(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    let haystack = &*b.haystack;
+    let mut md = re.create_match_data_for_matches_only();
+    timer::run(b, || {
+        let mut count = 0;
+        for line in lines::bytes(haystack) {
+            // In Unicode mode, this checks that each line is valid UTF-8.
+            // PCRE2 would do the same on every search anyway.
+            let subject = Subject::new(re, line);
+            if re.try_find_dfa(&subject, 0, line.len(), &mut md)? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    })
+}
+
 fn compile(b: &klv::Benchmark, jit: bool) -> anyhow::Result<Regex> {
-    let re = Regex::new(&b.regex.one()?, options(b, jit))?;
+    let re = Regex::new(pattern(b)?, options(b, jit))?;
     Ok(re)
 }
 
+fn compile_dfa(b: &klv::Benchmark) -> anyhow::Result<Regex> {
+    let opts = Options { dfa: true, ..options(b, false) };
+    let re = Regex::new(pattern(b)?, opts)?;
+    Ok(re)
+}
+
+/// Returns the single pattern in the given benchmark. Since PCRE2 treats a
+/// pattern as a sequence of bytes when UTF mode is disabled, the pattern may
+/// contain bytes that aren't valid UTF-8 in that case.
+fn pattern(b: &klv::Benchmark) -> anyhow::Result<&[u8]> {
+    anyhow::ensure!(
+        b.regex.binary_patterns.is_none() || !b.regex.unicode,
+        "patterns that aren't valid UTF-8 require 'unicode = false'",
+    );
+    b.regex.one_bytes()
+}
+
 fn options(b: &klv::Benchmark, jit: bool) -> Options {
-    Options { jit, ucp: b.regex.unicode, caseless: b.regex.case_insensitive }
+    Options {
+        jit,
+        ucp: b.regex.unicode,
+        caseless: b.regex.case_insensitive,
+        multiline: b.regex.multiline,
+        dotall: b.regex.dot_all,
+        anchored: false,
+        dfa: false,
+    }
 }
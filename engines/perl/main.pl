@@ -88,6 +88,28 @@ sub benchmark {
     return (\@durations, \@counts);
 }
 
+# Splits the given haystack into lines. Lines are split on \n, and a
+# single trailing \r is stripped from each line. If the haystack ends
+# with a \n, then there is no empty line after it.
+#
+# It's a little weird to iterate over lines using regex, when we are
+# trying to measure regex search time. But this model is about
+# idiomatically iterating over lines, and this appears to be the
+# standard approach for a string that is already in memory. We can't
+# just use 'split /\r?\n/' though, since that drops every trailing
+# empty line instead of just the one after a final \n.
+sub splitlines {
+    my $haystack = shift;
+    my @lines = split /\n/, $haystack, -1;
+    if (@lines > 0 && length $lines[-1] == 0) {
+        pop @lines;
+    }
+    foreach my $line (@lines) {
+        $line =~ s/\r\z//;
+    }
+    return @lines;
+}
+
 sub modelcompile {
     my %config = %{shift()};
     my $count = sub {
@@ -177,12 +199,7 @@ sub modelgrep {
     my $count = sub { my $n = shift; $n };
     my $bench = sub {
         my $count = 0;
-        # It's a little weird to iterate over lines using
-        # regex, when we are trying to measure regex search
-        # time. But this model is about idiomatically iterating
-        # over lines, and this appears to be the standard
-        # approach for a string that is already in memory.
-        foreach my $line (split /\r?\n/, $config{"haystack"}) {
+        foreach my $line (splitlines $config{"haystack"}) {
             if ($line =~ $re) {
                 $count++;
             }
@@ -198,12 +215,7 @@ sub modelgrepcaptures {
     my $count = sub { my $n = shift; $n };
     my $bench = sub {
         my $count = 0;
-        # It's a little weird to iterate over lines using
-        # regex, when we are trying to measure regex search
-        # time. But this model is about idiomatically iterating
-        # over lines, and this appears to be the standard
-        # approach for a string that is already in memory.
-        foreach my $line (split /\r?\n/, $config{"haystack"}) {
+        foreach my $line (splitlines $config{"haystack"}) {
             while ($line =~ /$re/g) {
                 # ^CAPTURE only includes the explicit groups,
                 # but rebar wants the count to include the
@@ -4,7 +4,14 @@ import sys
 import time
 
 
+# The newest version of the KLV protocol that this runner program knows about.
+# Unrecognized keys from a newer version are ignored instead of being reported
+# as an error.
+KLV_VERSION = 1
+
+
 class Config(collections.namedtuple('Config', [
+    'klv_version',
     'name',
     'model',
     'patterns',
@@ -29,6 +36,7 @@ class Config(collections.namedtuple('Config', [
         This raises an exception if the format is invalid.
         '''
         c = Config(
+            klv_version=0,
             name='',
             model='',
             patterns=[],
@@ -44,7 +52,9 @@ class Config(collections.namedtuple('Config', [
         while len(raw) > 0:
             klv, nread = OneKLV.parse(raw)
             raw = raw[nread:]
-            if klv.key == 'name':
+            if klv.key == 'klv-version':
+                c = c._replace(klv_version=int(klv.value))
+            elif klv.key == 'name':
                 c = c._replace(name=klv.value.decode('utf-8'))
             elif klv.key == 'model':
                 c = c._replace(model=klv.value.decode('utf-8'))
@@ -64,6 +74,14 @@ class Config(collections.namedtuple('Config', [
                 c = c._replace(max_time=int(klv.value))
             elif klv.key == 'max-warmup-time':
                 c = c._replace(max_warmup_time=int(klv.value))
+            elif c.klv_version > KLV_VERSION:
+                print(
+                    "warning: ignored unrecognized KLV key "
+                    f"'{klv.key}' from KLV protocol version "
+                    f"{c.klv_version} (this runner program supports up to "
+                    f"version {KLV_VERSION})",
+                    file=sys.stderr,
+                )
             else:
                 raise ValueError(f"unrecognized KLV item key '{klv.key}'")
         return c
@@ -365,17 +383,17 @@ def run_and_count(c, count, bench):
     verify the benchmark) is separate from 'bench' (which is what is
     actually measured).
     '''
-    warmup_start = time.time_ns()
+    warmup_start = time.perf_counter_ns()
     for _ in range(c.max_warmup_iters):
         # See comment below for why we do this.
         re.purge()
         result = bench()
         _count = count(result)
-        if (time.time_ns() - warmup_start) >= c.max_warmup_time:
+        if (time.perf_counter_ns() - warmup_start) >= c.max_warmup_time:
             break
 
     results = []
-    run_start = time.time_ns()
+    run_start = time.perf_counter_ns()
     for _ in range(c.max_iters):
         # Purge's the re module's regex cache, otherwise we wind up just
         # measuring how long it takes to fetch a regex from its internal cache.
@@ -391,11 +409,11 @@ def run_and_count(c, count, bench):
         # re-orient how this program is structured so that purging only happens
         # in the 'compile' model implementation.
         re.purge()
-        bench_start = time.time_ns()
+        bench_start = time.perf_counter_ns()
         result = bench()
-        elapsed = time.time_ns() - bench_start
+        elapsed = time.perf_counter_ns() - bench_start
         results.append((elapsed, count(result)))
-        if (time.time_ns() - run_start) >= c.max_time:
+        if (time.perf_counter_ns() - run_start) >= c.max_time:
             break
     return results
 
@@ -35,6 +35,8 @@ extern "C" {
     typedef struct re2_options {
         bool utf8;
         bool case_sensitive;
+        bool dot_nl;
+        bool longest_match;
     } re2_options;
 
     // An opaque type representing a sequence of RE2 StringPieces. Internally,
@@ -78,6 +80,14 @@ extern "C" {
             if (!opts.case_sensitive) {
                 re2_opts.set_case_sensitive(false);
             }
+            // N.B. '.' not matching '\n' is the default.
+            if (opts.dot_nl) {
+                re2_opts.set_dot_nl(true);
+            }
+            // N.B. Leftmost-first match semantics are the default.
+            if (opts.longest_match) {
+                re2_opts.set_longest_match(true);
+            }
             return reinterpret_cast<re2_regexp*>(new RE2(re2_pat, re2_opts));
         } catch (...) {
             return nullptr;
@@ -121,12 +131,14 @@ extern "C" {
     // Check whether a match exists in the haystack in the given sub-range, and
     // if so, write the start and end offsets of that match into 'match_start'
     // and 'match_end'. Use this when all you care about is the overall match
-    // position.
+    // position. When 'anchored' is true, a match is only reported if it begins
+    // at 'startpos'.
     bool re2_regexp_find(
         re2_regexp *re,
         re2_string haystack,
         int startpos,
         int endpos,
+        bool anchored,
         int *match_start,
         int *match_end
     ) {
@@ -143,7 +155,7 @@ extern "C" {
             re2_haystack,
             startpos,
             endpos,
-            RE2::UNANCHORED,
+            anchored ? RE2::ANCHOR_START : RE2::UNANCHORED,
             &re2_submatch,
             1
         );
@@ -87,6 +87,27 @@ impl Regex {
         haystack: &[u8],
         start: usize,
         end: usize,
+    ) -> Option<(usize, usize)> {
+        self.find_impl(haystack, start, end, false)
+    }
+
+    /// Return the first 'Match' found in the given input that begins at
+    /// 'start'. If no such match exists, then return None.
+    pub fn find_anchored(
+        &self,
+        haystack: &[u8],
+        start: usize,
+        end: usize,
+    ) -> Option<(usize, usize)> {
+        self.find_impl(haystack, start, end, true)
+    }
+
+    fn find_impl(
+        &self,
+        haystack: &[u8],
+        start: usize,
+        end: usize,
+        anchored: bool,
     ) -> Option<(usize, usize)> {
         let (mut match_start, mut match_end): (c_int, c_int) = (0, 0);
         // SAFETY: By construction, self.re is non-null. So is our haystack.
@@ -97,6 +118,7 @@ impl Regex {
                 haystack.into(),
                 start as c_int,
                 end as c_int,
+                anchored,
                 &mut match_start,
                 &mut match_end,
             )
@@ -114,7 +136,28 @@ impl Regex {
         &'r self,
         haystack: &'h [u8],
     ) -> FindMatches<'r, 'h> {
-        FindMatches { re: self, haystack, at: 0, last_match_end: None }
+        self.find_iter_window(haystack, 0..haystack.len())
+    }
+
+    /// Like `find_iter`, but only reports matches in the given window of the
+    /// haystack.
+    ///
+    /// The window is given to RE2 as its `startpos` and `endpos`, which
+    /// means RE2 still uses the bytes on either side of it as context for
+    /// assertions like `\b`. This is different from searching a slice of the
+    /// haystack.
+    pub fn find_iter_window<'r, 'h>(
+        &'r self,
+        haystack: &'h [u8],
+        window: std::ops::Range<usize>,
+    ) -> FindMatches<'r, 'h> {
+        FindMatches {
+            re: self,
+            haystack,
+            at: window.start,
+            end: window.end,
+            last_match_end: None,
+        }
     }
 
     /// Write the matching capturing groups in 'caps' if a match could be
@@ -175,11 +218,21 @@ pub struct Options {
     /// When enabled, RE2's case sensitive mode is enabled. When disabled,
     /// matching is done case insensitively.
     pub case_sensitive: bool,
+    /// When enabled, `.` matches any character, including `\n`.
+    pub dot_nl: bool,
+    /// When enabled, matches are reported with leftmost-longest semantics
+    /// instead of leftmost-first semantics.
+    pub longest_match: bool,
 }
 
 impl Default for Options {
     fn default() -> Options {
-        Options { utf8: true, case_sensitive: true }
+        Options {
+            utf8: true,
+            case_sensitive: true,
+            dot_nl: false,
+            longest_match: false,
+        }
     }
 }
 
@@ -295,6 +348,7 @@ pub struct FindMatches<'r, 'h> {
     re: &'r Regex,
     haystack: &'h [u8],
     at: usize,
+    end: usize,
     last_match_end: Option<usize>,
 }
 
@@ -303,8 +357,7 @@ impl<'r, 'h> Iterator for FindMatches<'r, 'h> {
 
     #[inline]
     fn next(&mut self) -> Option<(usize, usize)> {
-        let mut m =
-            self.re.find(self.haystack, self.at, self.haystack.len())?;
+        let mut m = self.re.find(self.haystack, self.at, self.end)?;
         if m.0 >= m.1 {
             m = self.handle_overlapping_empty_match(m)?;
         }
@@ -333,7 +386,7 @@ impl<'r, 'h> FindMatches<'r, 'h> {
         assert!(m.0 >= m.1);
         if Some(m.1) == self.last_match_end {
             self.at = self.at.checked_add(1).unwrap();
-            m = self.re.find(self.haystack, self.at, self.haystack.len())?;
+            m = self.re.find(self.haystack, self.at, self.end)?;
         }
         Some(m)
     }
@@ -387,6 +440,7 @@ extern "C" {
         haystack: re2_string,
         startpos: c_int,
         endpos: c_int,
+        anchored: bool,
         match_start: *mut c_int,
         match_end: *mut c_int,
     ) -> bool;
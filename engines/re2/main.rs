@@ -1,6 +1,6 @@
 use std::io::Write;
 
-use {anyhow::Context, bstr::ByteSlice, lexopt::Arg};
+use {anyhow::Context, lexopt::Arg};
 
 use crate::ffi::{Options, Regex};
 
@@ -31,25 +31,41 @@ fn main() -> anyhow::Result<()> {
     let b = klv::Benchmark::read(std::io::stdin())
         .context("failed to read KLV data from <stdin>")?;
     let samples = match b.model.as_str() {
+        "anchored-count" => model_anchored_count(&b, &compile(&b)?)?,
         "compile" => model_compile(&b)?,
-        "count" => model_count(&b, &compile(&b)?)?,
+        // RE2 does have a lazy DFA with a cache, but the cache is owned by
+        // the compiled regex and there's no way to reset it short of
+        // recompiling the regex. So 'count-cold' is the same as 'count'.
+        "count" | "count-cold" => model_count(&b, &compile(&b)?)?,
         "count-spans" => model_count_spans(&b, &compile(&b)?)?,
+        "count-longest" => model_count_spans(&b, &compile_longest(&b)?)?,
+        "count-offsets" => model_count_offsets(&b, &compile(&b)?)?,
         "count-captures" => model_count_captures(&b, &compile(&b)?)?,
-        "grep" => model_grep(&b, &compile(&b)?)?,
+        "count-empty" => model_count_empty(&b, &compile(&b)?)?,
+        "first-match" => model_first_match(&b, &compile(&b)?)?,
+        // 'model_grep' already uses a match without any capturing groups,
+        // which is what RE2::PartialMatch does when not given any. So
+        // 'grep-fast' is the same as 'grep'.
+        "grep" | "grep-fast" => model_grep(&b, &compile(&b)?)?,
         "grep-captures" => model_grep_captures(&b, &compile(&b)?)?,
+        "grep-max" => model_grep_max(&b, &compile(&b)?)?,
         "regex-redux" => model_regex_redux(&b)?,
         _ => anyhow::bail!("unrecognized benchmark model '{}'", b.model),
     };
     if !quiet {
         let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "# warmup_iters={}", samples.warmup_iters)?;
+        if samples.partial {
+            writeln!(stdout, "# partial=true")?;
+        }
         for s in samples.iter() {
-            writeln!(stdout, "{},{}", s.duration.as_nanos(), s.count)?;
+            writeln!(stdout, "{}", s)?;
         }
     }
     Ok(())
 }
 
-fn model_compile(b: &klv::Benchmark) -> anyhow::Result<Vec<timer::Sample>> {
+fn model_compile(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run_and_count(
         b,
@@ -58,26 +74,65 @@ fn model_compile(b: &klv::Benchmark) -> anyhow::Result<Vec<timer::Sample>> {
     )
 }
 
+fn model_anchored_count(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    let haystack = &*b.haystack;
+    timer::run(b, || {
+        let (mut count, mut at) = (0, 0);
+        while at < haystack.len() {
+            at = match re.find_anchored(haystack, at, haystack.len()) {
+                None => at + 1,
+                Some((_, end)) => {
+                    count += 1;
+                    // Always make progress, even on an empty match.
+                    end.max(at + 1)
+                }
+            };
+        }
+        Ok(count)
+    })
+}
+
 fn model_count(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
+    let (haystack, window) = (&*b.haystack, b.search_window()?);
+    timer::run(b, || Ok(re.find_iter_window(haystack, window.clone()).count()))
+}
+
+fn model_first_match(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
-    timer::run(b, || Ok(re.find_iter(haystack).count()))
+    timer::run(b, || {
+        Ok(re.find(haystack, 0, haystack.len()).map_or(0, |(_, e)| e))
+    })
 }
 
 fn model_count_spans(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run(b, || Ok(re.find_iter(haystack).map(|(s, e)| e - s).sum()))
 }
 
+fn model_count_offsets(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    let haystack = &*b.haystack;
+    timer::run(b, || Ok(re.find_iter(haystack).map(|(s, e)| s + e).sum()))
+}
+
 fn model_count_captures(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     let mut caps = re.create_captures();
     timer::run(b, || {
@@ -100,14 +155,49 @@ fn model_count_captures(
     })
 }
 
+fn model_count_empty(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    // RE2 doesn't provide a match iterator, so we mimic what RE2's own
+    // 'GlobalReplace' does. Namely, an empty match that begins where the
+    // previous match ended is skipped, and the search resumes one character
+    // later. In UTF-8 mode, a character may be more than one byte.
+    let haystack = &*b.haystack;
+    let utf8 = b.regex.unicode;
+    timer::run(b, || {
+        let (mut count, mut at, mut last_match_end) = (0, 0, None);
+        while at <= haystack.len() {
+            let (start, end) = match re.find(haystack, at, haystack.len()) {
+                None => break,
+                Some(m) => m,
+            };
+            if start == end && Some(end) == last_match_end {
+                at += 1;
+                while utf8
+                    && at < haystack.len()
+                    && haystack[at] & 0b1100_0000 == 0b1000_0000
+                {
+                    at += 1;
+                }
+                continue;
+            }
+            count += 1;
+            at = end;
+            last_match_end = Some(end);
+        }
+        Ok(count)
+    })
+}
+
 fn model_grep(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run(b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             if re.is_match(line, 0, line.len()) {
                 count += 1;
             }
@@ -119,12 +209,12 @@ fn model_grep(
 fn model_grep_captures(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     let mut caps = re.create_captures();
     timer::run(b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             let mut at = 0;
             while let Some((_, end)) = {
                 re.captures(line, at, line.len(), &mut caps);
@@ -144,9 +234,22 @@ fn model_grep_captures(
     })
 }
 
-fn model_regex_redux(
+fn model_grep_max(
     b: &klv::Benchmark,
-) -> anyhow::Result<Vec<timer::Sample>> {
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    let haystack = &*b.haystack;
+    let max = b.max_count_per_line();
+    timer::run(b, || {
+        let mut count = 0;
+        for line in lines::bytes(haystack) {
+            count += re.find_iter(line).take(max).count();
+        }
+        Ok(count)
+    })
+}
+
+fn model_regex_redux(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
     let haystack = b.haystack_str()?;
     let compile = |pattern: &str| -> anyhow::Result<regexredux::RegexFn> {
         let re = Regex::new(pattern, options(b))?;
@@ -157,12 +260,35 @@ fn model_regex_redux(
 }
 
 fn compile(b: &klv::Benchmark) -> anyhow::Result<Regex> {
-    Regex::new(&b.regex.one()?, options(b))
+    Regex::new(&pattern(b, &b.regex.one()?), options(b))
+}
+
+/// Like 'compile', but enables RE2's 'longest_match' option so that matches
+/// are reported with leftmost-longest (POSIX) semantics. This is used for the
+/// 'count-longest' model.
+fn compile_longest(b: &klv::Benchmark) -> anyhow::Result<Regex> {
+    let opts = Options { longest_match: true, ..options(b) };
+    Regex::new(&pattern(b, &b.regex.one()?), opts)
+}
+
+/// Returns the given pattern with any flags that can't be set via RE2's
+/// options enabled inline.
+///
+/// In particular, RE2 only respects its 'one_line' option when in POSIX mode.
+/// So we enable multi-line mode via an inline flag instead.
+fn pattern(b: &klv::Benchmark, pattern: &str) -> String {
+    if b.regex.multiline {
+        format!("(?m:{})", pattern)
+    } else {
+        pattern.to_string()
+    }
 }
 
 fn options(b: &klv::Benchmark) -> Options {
     Options {
         utf8: b.regex.unicode,
         case_sensitive: !b.regex.case_insensitive,
+        dot_nl: b.regex.dot_all,
+        longest_match: false,
     }
 }
@@ -32,8 +32,15 @@ fn main() -> anyhow::Result<()> {
     let samples = match b.model.as_str() {
         "compile" => model_compile(&b)?,
         "count" => model_count(&b, &compile(&b)?)?,
-        "count-spans" => model_count_spans(&b, &compile(&b)?)?,
+        // regress only has leftmost-first match semantics (as required by
+        // EcmaScript), so 'count-longest' is the same as 'count-spans'.
+        "count-spans" | "count-longest" => {
+            model_count_spans(&b, &compile(&b)?)?
+        }
+        "count-offsets" => model_count_offsets(&b, &compile(&b)?)?,
         "count-captures" => model_count_captures(&b, &compile(&b)?)?,
+        "count-empty" => model_count_empty(&b, &compile(&b)?)?,
+        "first-match" => model_first_match(&b, &compile(&b)?)?,
         "grep" => model_grep(&b, &compile(&b)?)?,
         "grep-captures" => model_grep_captures(&b, &compile(&b)?)?,
         "regex-redux" => model_regex_redux(&b)?,
@@ -41,14 +48,18 @@ fn main() -> anyhow::Result<()> {
     };
     if !quiet {
         let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "# warmup_iters={}", samples.warmup_iters)?;
+        if samples.partial {
+            writeln!(stdout, "# partial=true")?;
+        }
         for s in samples.iter() {
-            writeln!(stdout, "{},{}", s.duration.as_nanos(), s.count)?;
+            writeln!(stdout, "{}", s)?;
         }
     }
     Ok(())
 }
 
-fn model_compile(b: &klv::Benchmark) -> anyhow::Result<Vec<timer::Sample>> {
+fn model_compile(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
     let haystack = b.haystack_str()?;
     timer::run_and_count(
         b,
@@ -60,25 +71,43 @@ fn model_compile(b: &klv::Benchmark) -> anyhow::Result<Vec<timer::Sample>> {
 fn model_count(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = b.haystack_str()?;
     timer::run(b, || Ok(re.find_iter(haystack).count()))
 }
 
+fn model_first_match(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    let haystack = b.haystack_str()?;
+    timer::run(b, || Ok(re.find(haystack).map_or(0, |m| m.end())))
+}
+
 fn model_count_spans(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = b.haystack_str()?;
     timer::run(b, || {
         Ok(re.find_iter(haystack).map(|m| m.end() - m.start()).sum())
     })
 }
 
+fn model_count_offsets(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    let haystack = b.haystack_str()?;
+    timer::run(b, || {
+        Ok(re.find_iter(haystack).map(|m| m.start() + m.end()).sum())
+    })
+}
+
 fn model_count_captures(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = b.haystack_str()?;
     timer::run(b, || {
         let mut count = 0;
@@ -90,19 +119,25 @@ fn model_count_captures(
     })
 }
 
+fn model_count_empty(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    // regress follows ECMAScript here: after an empty match, the next search
+    // begins one codepoint later. Unlike some other regex engines, an empty
+    // match is permitted immediately after the end of a non-empty match.
+    let haystack = b.haystack_str()?;
+    timer::run(b, || Ok(re.find_iter(haystack).count()))
+}
+
 fn model_grep(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = b.haystack_str()?;
     timer::run(b, || {
         let mut count = 0;
-        // NOTE: This is actually using std's line iteration logic instead
-        // of bstr's because haystack is a &str here and not a &[u8]. This
-        // technically means we aren't comparing apples-to-oranges here, but
-        // the benchmark model definition explicitly permits these sorts of
-        // cases because it reflects reality.
-        for line in haystack.lines() {
+        for line in lines::str(haystack) {
             if re.find(line).is_some() {
                 count += 1;
             }
@@ -114,16 +149,11 @@ fn model_grep(
 fn model_grep_captures(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = b.haystack_str()?;
     timer::run(b, || {
         let mut count = 0;
-        // NOTE: This is actually using std's line iteration logic instead
-        // of bstr's because haystack is a &str here and not a &[u8]. This
-        // technically means we aren't comparing apples-to-oranges here, but
-        // the benchmark model definition explicitly permits these sorts of
-        // cases because it reflects reality.
-        for line in haystack.lines() {
+        for line in lines::str(haystack) {
             for m in re.find_iter(line) {
                 // +1 to count the implicit group
                 count += 1 + m.captures.iter().filter(|c| c.is_some()).count();
@@ -133,9 +163,7 @@ fn model_grep_captures(
     })
 }
 
-fn model_regex_redux(
-    b: &klv::Benchmark,
-) -> anyhow::Result<Vec<timer::Sample>> {
+fn model_regex_redux(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
     let haystack = b.haystack_str()?;
     let compile = |pattern: &str| -> anyhow::Result<regexredux::RegexFn> {
         let re = Regex::with_flags(pattern, flags(b))?;
@@ -152,8 +180,8 @@ fn compile(b: &klv::Benchmark) -> anyhow::Result<Regex> {
 fn flags(b: &klv::Benchmark) -> Flags {
     Flags {
         icase: b.regex.case_insensitive,
-        multiline: false,
-        dot_all: false,
+        multiline: b.regex.multiline,
+        dot_all: b.regex.dot_all,
         no_opt: false,
         unicode: b.regex.unicode,
     }
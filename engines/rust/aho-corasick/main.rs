@@ -3,10 +3,13 @@ use std::io::Write;
 use {
     aho_corasick::{packed, AhoCorasick, AhoCorasickKind, MatchKind},
     anyhow::Context,
-    bstr::ByteSlice,
     lexopt::{Arg, ValueExt},
 };
 
+/// The cargo features enabled for the regex engine in Cargo.toml. This is
+/// reported by '--metadata' and should be kept in sync with Cargo.toml.
+const FEATURES: &[&str] = &["default"];
+
 fn main() -> anyhow::Result<()> {
     let mut p = lexopt::Parser::from_env();
     let engine = match p.next()? {
@@ -18,11 +21,11 @@ fn main() -> anyhow::Result<()> {
             );
         }
     };
-    let (mut quiet, mut version) = (false, false);
+    let (mut quiet, mut version, mut metadata) = (false, false, false);
     while let Some(arg) = p.next()? {
         match arg {
             Arg::Short('h') | Arg::Long("help") => {
-                anyhow::bail!("main [--version | --quiet]")
+                anyhow::bail!("main [--version | --metadata | --quiet]")
             }
             Arg::Short('q') | Arg::Long("quiet") => {
                 quiet = true;
@@ -30,15 +33,26 @@ fn main() -> anyhow::Result<()> {
             Arg::Long("version") => {
                 version = true;
             }
+            Arg::Long("metadata") => {
+                metadata = true;
+            }
             _ => return Err(arg.unexpected().into()),
         }
     }
+    if metadata {
+        writeln!(std::io::stdout(), "{}", timer::metadata(FEATURES))?;
+        return Ok(());
+    }
     if version {
         writeln!(std::io::stdout(), "{}", env!("CARGO_PKG_VERSION"))?;
         return Ok(());
     }
     let b = klv::Benchmark::read(std::io::stdin())
         .context("failed to read KLV data from <stdin>")?;
+    if let Some(reason) = unsupported(&b, &engine) {
+        writeln!(std::io::stdout(), "skip:{}", reason)?;
+        return Ok(());
+    }
     let kind = match &*engine {
         "dfa" => AhoCorasickKind::DFA,
         "nfa" => AhoCorasickKind::ContiguousNFA,
@@ -48,31 +62,66 @@ fn main() -> anyhow::Result<()> {
     let samples = match b.model.as_str() {
         "compile" => model_compile_ac(&b, kind)?,
         "count" => model_count_ac(&b, &compile_ac(&b, kind)?)?,
+        "count-per-pattern" => {
+            model_count_per_pattern_ac(&b, &compile_ac(&b, kind)?)?
+        }
         "count-spans" => model_count_spans_ac(&b, &compile_ac(&b, kind)?)?,
         "grep" => model_grep_ac(&b, &compile_ac(&b, kind)?)?,
+        "size" => model_size_ac(&b, kind)?,
         _ => anyhow::bail!("unsupported benchmark model '{}'", b.model),
     };
     if !quiet {
         let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "# warmup_iters={}", samples.warmup_iters)?;
+        if samples.partial {
+            writeln!(stdout, "# partial=true")?;
+        }
         for s in samples.iter() {
-            writeln!(stdout, "{},{}", s.duration.as_nanos(), s.count)?;
+            writeln!(stdout, "{}", s)?;
         }
     }
     Ok(())
 }
 
+/// Returns the reason why the given engine can't run the given benchmark, if
+/// there is one. These aren't errors, since they're just combinations of
+/// options that the engine doesn't support, so we report them as skipped.
+fn unsupported(b: &klv::Benchmark, engine: &str) -> Option<&'static str> {
+    if engine == "teddy" && b.regex.case_insensitive {
+        return Some(
+            "rust/aho-corasick/teddy engine is incompatible with \
+             'case-insensitive = true'",
+        );
+    }
+    if b.regex.unicode && b.regex.case_insensitive {
+        return Some(
+            "rust/aho-corasick engines are incompatible with \
+             'unicode = true' and 'case-insensitive = true'",
+        );
+    }
+    None
+}
+
 fn main_teddy(b: &klv::Benchmark, quiet: bool) -> anyhow::Result<()> {
     let samples = match b.model.as_str() {
         "compile" => model_compile_teddy(&b)?,
         "count" => model_count_teddy(&b, &compile_teddy(&b)?)?,
+        "count-per-pattern" => {
+            model_count_per_pattern_teddy(&b, &compile_teddy(&b)?)?
+        }
         "count-spans" => model_count_spans_teddy(&b, &compile_teddy(&b)?)?,
         "grep" => model_grep_teddy(&b, &compile_teddy(&b)?)?,
+        "size" => model_size_teddy(&b)?,
         _ => anyhow::bail!("unsupported benchmark model '{}'", b.model),
     };
     if !quiet {
         let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "# warmup_iters={}", samples.warmup_iters)?;
+        if samples.partial {
+            writeln!(stdout, "# partial=true")?;
+        }
         for s in samples.iter() {
-            writeln!(stdout, "{},{}", s.duration.as_nanos(), s.count)?;
+            writeln!(stdout, "{}", s)?;
         }
     }
     Ok(())
@@ -81,11 +130,23 @@ fn main_teddy(b: &klv::Benchmark, quiet: bool) -> anyhow::Result<()> {
 fn model_compile_ac(
     b: &klv::Benchmark,
     kind: AhoCorasickKind,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
-    timer::run_and_count(
+    timer::run_and_count_with_size(
         b,
         |re: AhoCorasick| Ok(re.find_iter(haystack).count()),
+        |re: &AhoCorasick| Ok(re.memory_usage()),
+        || compile_ac(b, kind),
+    )
+}
+
+fn model_size_ac(
+    b: &klv::Benchmark,
+    kind: AhoCorasickKind,
+) -> anyhow::Result<timer::Samples> {
+    timer::run_and_size(
+        b,
+        |re: &AhoCorasick| Ok(re.memory_usage()),
         || compile_ac(b, kind),
     )
 }
@@ -93,15 +154,30 @@ fn model_compile_ac(
 fn model_count_ac(
     b: &klv::Benchmark,
     re: &AhoCorasick,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run(b, || Ok(re.find_iter(haystack).count()))
 }
 
+fn model_count_per_pattern_ac(
+    b: &klv::Benchmark,
+    re: &AhoCorasick,
+) -> anyhow::Result<timer::Samples> {
+    // Each match contributes the square of its pattern index plus one. See
+    // the description of the 'count-per-pattern' model in MODELS.md.
+    let haystack = &*b.haystack;
+    timer::run(b, || {
+        Ok(re
+            .find_iter(haystack)
+            .map(|m| (m.pattern().as_usize() + 1).pow(2))
+            .sum())
+    })
+}
+
 fn model_count_spans_ac(
     b: &klv::Benchmark,
     re: &AhoCorasick,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run(b, || Ok(re.find_iter(haystack).map(|m| m.len()).sum()))
 }
@@ -109,11 +185,11 @@ fn model_count_spans_ac(
 fn model_grep_ac(
     b: &klv::Benchmark,
     re: &AhoCorasick,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run(b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             if re.is_match(line) {
                 count += 1;
             }
@@ -126,11 +202,7 @@ fn compile_ac(
     b: &klv::Benchmark,
     kind: AhoCorasickKind,
 ) -> anyhow::Result<AhoCorasick> {
-    anyhow::ensure!(
-        !(b.regex.unicode && b.regex.case_insensitive),
-        "rust/aho-corasick engines are incompatible with 'unicode = true' and \
-         'case-insensitive = true'"
-    );
+    b.regex.ensure_no_multiline_or_dot_all()?;
     let ac = AhoCorasick::builder()
         .kind(Some(kind))
         .match_kind(MatchKind::LeftmostFirst)
@@ -140,13 +212,20 @@ fn compile_ac(
     Ok(ac)
 }
 
-fn model_compile_teddy(
-    b: &klv::Benchmark,
-) -> anyhow::Result<Vec<timer::Sample>> {
+fn model_compile_teddy(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
-    timer::run_and_count(
+    timer::run_and_count_with_size(
         b,
         |re: packed::Searcher| Ok(re.find_iter(haystack).count()),
+        |re: &packed::Searcher| Ok(re.memory_usage()),
+        || compile_teddy(b),
+    )
+}
+
+fn model_size_teddy(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
+    timer::run_and_size(
+        b,
+        |re: &packed::Searcher| Ok(re.memory_usage()),
         || compile_teddy(b),
     )
 }
@@ -154,15 +233,28 @@ fn model_compile_teddy(
 fn model_count_teddy(
     b: &klv::Benchmark,
     re: &packed::Searcher,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run(b, || Ok(re.find_iter(haystack).count()))
 }
 
+fn model_count_per_pattern_teddy(
+    b: &klv::Benchmark,
+    re: &packed::Searcher,
+) -> anyhow::Result<timer::Samples> {
+    let haystack = &*b.haystack;
+    timer::run(b, || {
+        Ok(re
+            .find_iter(haystack)
+            .map(|m| (m.pattern().as_usize() + 1).pow(2))
+            .sum())
+    })
+}
+
 fn model_count_spans_teddy(
     b: &klv::Benchmark,
     re: &packed::Searcher,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run(b, || Ok(re.find_iter(haystack).map(|m| m.len()).sum()))
 }
@@ -170,11 +262,11 @@ fn model_count_spans_teddy(
 fn model_grep_teddy(
     b: &klv::Benchmark,
     re: &packed::Searcher,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run(b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             if re.find(line).is_some() {
                 count += 1;
             }
@@ -184,11 +276,7 @@ fn model_grep_teddy(
 }
 
 fn compile_teddy(b: &klv::Benchmark) -> anyhow::Result<packed::Searcher> {
-    anyhow::ensure!(
-        !b.regex.case_insensitive,
-        "rust/aho-corasick/teddy engine is incompatible with \
-         'case-insensitive = true'"
-    );
+    b.regex.ensure_no_multiline_or_dot_all()?;
     let searcher = packed::Config::new()
         .match_kind(packed::MatchKind::LeftmostFirst)
         .builder()
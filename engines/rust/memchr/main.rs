@@ -1,14 +1,18 @@
 use std::io::Write;
 
-use {anyhow::Context, bstr::ByteSlice, lexopt::Arg, memchr::memmem::Finder};
+use {anyhow::Context, lexopt::Arg, memchr::memmem::Finder};
+
+/// The cargo features enabled for the regex engine in Cargo.toml. This is
+/// reported by '--metadata' and should be kept in sync with Cargo.toml.
+const FEATURES: &[&str] = &["default"];
 
 fn main() -> anyhow::Result<()> {
     let mut p = lexopt::Parser::from_env();
-    let (mut quiet, mut version) = (false, false);
+    let (mut quiet, mut version, mut metadata) = (false, false, false);
     while let Some(arg) = p.next()? {
         match arg {
             Arg::Short('h') | Arg::Long("help") => {
-                anyhow::bail!("main [--version | --quiet]")
+                anyhow::bail!("main [--version | --metadata | --quiet]")
             }
             Arg::Short('q') | Arg::Long("quiet") => {
                 quiet = true;
@@ -16,15 +20,33 @@ fn main() -> anyhow::Result<()> {
             Arg::Long("version") => {
                 version = true;
             }
+            Arg::Long("metadata") => {
+                metadata = true;
+            }
             _ => return Err(arg.unexpected().into()),
         }
     }
+    if metadata {
+        writeln!(std::io::stdout(), "{}", timer::metadata(FEATURES))?;
+        return Ok(());
+    }
     if version {
         writeln!(std::io::stdout(), "{}", env!("CARGO_PKG_VERSION"))?;
         return Ok(());
     }
     let b = klv::Benchmark::read(std::io::stdin())
         .context("failed to read KLV data from <stdin>")?;
+    // memmem only does exact substring search, so it can't run case
+    // insensitive benchmarks. This isn't an error, so we report it as
+    // skipped.
+    if b.regex.case_insensitive {
+        writeln!(
+            std::io::stdout(),
+            "skip:rust/memchr/memmem engine is incompatible with case \
+             insensitive mode",
+        )?;
+        return Ok(());
+    }
     let samples = match b.model.as_str() {
         "compile" => model_compile(&b)?,
         "count" => model_count(&b, &compile(&b)?)?,
@@ -34,14 +56,18 @@ fn main() -> anyhow::Result<()> {
     };
     if !quiet {
         let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "# warmup_iters={}", samples.warmup_iters)?;
+        if samples.partial {
+            writeln!(stdout, "# partial=true")?;
+        }
         for s in samples.iter() {
-            writeln!(stdout, "{},{}", s.duration.as_nanos(), s.count)?;
+            writeln!(stdout, "{}", s)?;
         }
     }
     Ok(())
 }
 
-fn model_compile(b: &klv::Benchmark) -> anyhow::Result<Vec<timer::Sample>> {
+fn model_compile(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run_and_count(
         b,
@@ -53,7 +79,7 @@ fn model_compile(b: &klv::Benchmark) -> anyhow::Result<Vec<timer::Sample>> {
 fn model_count(
     b: &klv::Benchmark,
     f: &Finder,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run(b, || Ok(f.find_iter(haystack).count()))
 }
@@ -61,7 +87,7 @@ fn model_count(
 fn model_count_spans(
     b: &klv::Benchmark,
     f: &Finder,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run(b, || Ok(f.find_iter(haystack).map(|_| f.needle().len()).sum()))
 }
@@ -69,11 +95,11 @@ fn model_count_spans(
 fn model_grep(
     b: &klv::Benchmark,
     f: &Finder,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run(b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             if f.find(line).is_some() {
                 count += 1;
             }
@@ -83,9 +109,6 @@ fn model_grep(
 }
 
 fn compile(b: &klv::Benchmark) -> anyhow::Result<Finder> {
-    anyhow::ensure!(
-        !b.regex.case_insensitive,
-        "rust/memchr/memmem engine is incompatible with case insensitive mode",
-    );
+    b.regex.ensure_no_multiline_or_dot_all()?;
     Ok(Finder::new(b.regex.one()?.as_bytes()))
 }
@@ -30,6 +30,10 @@ struct Config {
     engine: String,
 }
 
+/// The cargo features enabled for the regex engine in Cargo.toml. This is
+/// reported by '--metadata' and should be kept in sync with Cargo.toml.
+const FEATURES: &[&str] = &["default", "logging"];
+
 fn main() -> anyhow::Result<()> {
     env_logger::init();
 
@@ -48,11 +52,15 @@ fn main() -> anyhow::Result<()> {
         "unrecognized engine '{}'",
         engine,
     );
-    let (mut quiet, mut version) = (false, false);
+    let (mut quiet, mut version, mut validate) = (false, false, false);
+    let mut metadata = false;
     while let Some(arg) = p.next()? {
         match arg {
             Arg::Short('h') | Arg::Long("help") => {
-                anyhow::bail!("main [--version | --quiet]")
+                anyhow::bail!(
+                    "main <engine> \
+                     [--version | --metadata | --quiet | --validate]"
+                )
             }
             Arg::Short('q') | Arg::Long("quiet") => {
                 quiet = true;
@@ -60,31 +68,90 @@ fn main() -> anyhow::Result<()> {
             Arg::Long("version") => {
                 version = true;
             }
+            Arg::Long("metadata") => {
+                metadata = true;
+            }
+            Arg::Long("validate") => {
+                validate = true;
+            }
             _ => return Err(arg.unexpected().into()),
         }
     }
+    if metadata {
+        writeln!(std::io::stdout(), "{}", timer::metadata(FEATURES))?;
+        return Ok(());
+    }
     if version {
         writeln!(std::io::stdout(), "{}", env!("CARGO_PKG_VERSION"))?;
         return Ok(());
     }
+    if validate {
+        return timer::validate(|b| {
+            run(&Config { b: b.clone(), engine: engine.clone() })
+        });
+    }
     let b = klv::Benchmark::read(std::io::stdin())
         .context("failed to read KLV data from <stdin>")?;
-    let c = Config { b, engine };
-    let samples = match c.b.model.as_str() {
-        "compile" => model::compile::run(&c)?,
-        "count" => model::count::run(&c)?,
-        "count-spans" => model::count_spans::run(&c)?,
-        "count-captures" => model::count_captures::run(&c)?,
-        "grep" => model::grep::run(&c)?,
-        "grep-captures" => model::grep_captures::run(&c)?,
-        "regex-redux" => model::regexredux::run(&c)?,
-        _ => anyhow::bail!("unsupported benchmark model '{}'", c.b.model),
+    let samples = match run(&Config { b, engine }) {
+        Ok(samples) => samples,
+        Err(err) => match skip_reason(&err) {
+            None => return Err(err),
+            Some(reason) => {
+                writeln!(std::io::stdout(), "skip:{}", reason)?;
+                return Ok(());
+            }
+        },
     };
     if !quiet {
         let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "# warmup_iters={}", samples.warmup_iters)?;
+        if samples.partial {
+            writeln!(stdout, "# partial=true")?;
+        }
         for s in samples.iter() {
-            writeln!(stdout, "{},{}", s.duration.as_nanos(), s.count)?;
+            writeln!(stdout, "{}", s)?;
         }
     }
     Ok(())
 }
+
+/// If the given error indicates that the regex engine can't run a benchmark
+/// at all (as opposed to failing while running it), then this returns the
+/// reason why. rebar records these as skipped instead of as errors.
+///
+/// This covers regexes that a one-pass DFA can't be built for, and haystacks
+/// that are too long for the bounded backtracker.
+fn skip_reason(err: &anyhow::Error) -> Option<String> {
+    use regex_automata::MatchErrorKind;
+
+    for cause in err.chain() {
+        if let Some(err) = cause.downcast_ref::<new::Unsupported>() {
+            return Some(err.to_string());
+        }
+        if let Some(err) = cause.downcast_ref::<regex_automata::MatchError>() {
+            if let MatchErrorKind::HaystackTooLong { .. } = *err.kind() {
+                return Some(err.to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Runs the given benchmark and returns its samples.
+fn run(c: &Config) -> anyhow::Result<timer::Samples> {
+    match c.b.model.as_str() {
+        "anchored-count" => model::anchored_count::run(c),
+        "compile" => model::compile::run(c),
+        "count" => model::count::run(c),
+        "count-spans" => model::count_spans::run(c),
+        "count-captures" => model::count_captures::run(c),
+        "count-cold" => model::count_cold::run(c),
+        "count-longest" => model::count_longest::run(c),
+        "first-match" => model::first_match::run(c),
+        "grep" => model::grep::run(c),
+        "grep-captures" => model::grep_captures::run(c),
+        "regex-redux" => model::regexredux::run(c),
+        "size" => model::size::run(c),
+        _ => anyhow::bail!("unsupported benchmark model '{}'", c.b.model),
+    }
+}
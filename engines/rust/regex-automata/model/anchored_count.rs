@@ -0,0 +1,141 @@
+use regex_automata::{Anchored, Input};
+
+use crate::{new, Config};
+
+pub(crate) fn run(c: &Config) -> anyhow::Result<timer::Samples> {
+    match &*c.engine {
+        "meta" => meta(c),
+        "dense" => dense(c),
+        "sparse" => sparse(c),
+        "hybrid" => hybrid(c),
+        "pikevm" => pikevm(c),
+        "onepass" => onepass(c),
+        _ => anyhow::bail!(
+            "engine '{}' does not support the 'anchored-count' model",
+            c.engine,
+        ),
+    }
+}
+
+fn meta(c: &Config) -> anyhow::Result<timer::Samples> {
+    let haystack = &*c.b.haystack;
+    let re = new::meta(c)?;
+    timer::run(&c.b, || {
+        let mut input = Input::new(haystack).anchored(Anchored::Yes);
+        let (mut count, mut at) = (0, 0);
+        while at < haystack.len() {
+            input.set_start(at);
+            at = match re.search(&input) {
+                None => at + 1,
+                Some(m) => {
+                    count += 1;
+                    m.end().max(at + 1)
+                }
+            };
+        }
+        Ok(count)
+    })
+}
+
+fn dense(c: &Config) -> anyhow::Result<timer::Samples> {
+    let haystack = &*c.b.haystack;
+    let re = new::dense(c)?;
+    timer::run(&c.b, || {
+        let mut input = Input::new(haystack).anchored(Anchored::Yes);
+        let (mut count, mut at) = (0, 0);
+        while at < haystack.len() {
+            input.set_start(at);
+            at = match re.find(input.clone()) {
+                None => at + 1,
+                Some(m) => {
+                    count += 1;
+                    m.end().max(at + 1)
+                }
+            };
+        }
+        Ok(count)
+    })
+}
+
+fn sparse(c: &Config) -> anyhow::Result<timer::Samples> {
+    let haystack = &*c.b.haystack;
+    let re = new::sparse(c)?;
+    timer::run(&c.b, || {
+        let mut input = Input::new(haystack).anchored(Anchored::Yes);
+        let (mut count, mut at) = (0, 0);
+        while at < haystack.len() {
+            input.set_start(at);
+            at = match re.find(input.clone()) {
+                None => at + 1,
+                Some(m) => {
+                    count += 1;
+                    m.end().max(at + 1)
+                }
+            };
+        }
+        Ok(count)
+    })
+}
+
+fn hybrid(c: &Config) -> anyhow::Result<timer::Samples> {
+    let haystack = &*c.b.haystack;
+    let re = new::hybrid(c)?;
+    let mut cache = re.create_cache();
+    timer::run(&c.b, || {
+        let mut input = Input::new(haystack).anchored(Anchored::Yes);
+        let (mut count, mut at) = (0, 0);
+        while at < haystack.len() {
+            input.set_start(at);
+            at = match re.find(&mut cache, input.clone()) {
+                None => at + 1,
+                Some(m) => {
+                    count += 1;
+                    m.end().max(at + 1)
+                }
+            };
+        }
+        Ok(count)
+    })
+}
+
+fn pikevm(c: &Config) -> anyhow::Result<timer::Samples> {
+    let haystack = &*c.b.haystack;
+    let re = new::pikevm(c)?;
+    let mut cache = re.create_cache();
+    timer::run(&c.b, || {
+        let mut input = Input::new(haystack).anchored(Anchored::Yes);
+        let (mut count, mut at) = (0, 0);
+        while at < haystack.len() {
+            input.set_start(at);
+            at = match re.find(&mut cache, input.clone()) {
+                None => at + 1,
+                Some(m) => {
+                    count += 1;
+                    m.end().max(at + 1)
+                }
+            };
+        }
+        Ok(count)
+    })
+}
+
+fn onepass(c: &Config) -> anyhow::Result<timer::Samples> {
+    let haystack = &*c.b.haystack;
+    let re = new::onepass(c)?;
+    let mut cache = re.create_cache();
+    timer::run(&c.b, || {
+        let mut input = Input::new(haystack).anchored(Anchored::Yes);
+        let (mut count, mut at) = (0, 0);
+        while at < haystack.len() {
+            input.set_start(at);
+            at = match re.find(&mut cache, input.clone()) {
+                None => at + 1,
+                Some(m) => {
+                    count += 1;
+                    m.end().max(at + 1)
+                }
+            };
+        }
+        Ok(count)
+    })
+}
@@ -1,6 +1,6 @@
 use crate::{new, Config};
 
-pub(crate) fn run(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+pub(crate) fn run(c: &Config) -> anyhow::Result<timer::Samples> {
     match &*c.engine {
         "nfa" => nfa(c),
         "meta" => meta(c),
@@ -14,7 +14,7 @@ pub(crate) fn run(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     }
 }
 
-fn nfa(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn nfa(c: &Config) -> anyhow::Result<timer::Samples> {
     use regex_automata::nfa::thompson::{pikevm::PikeVM, Compiler, NFA};
     use regex_syntax::ParserBuilder;
 
@@ -25,84 +25,102 @@ fn nfa(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
         .case_insensitive(c.b.regex.case_insensitive)
         .build()
         .parse(&pattern)?;
-    timer::run_and_count(
+    timer::run_and_count_with_size(
         &c.b,
         |nfa: NFA| {
             let re = PikeVM::builder().build_from_nfa(nfa)?;
             let mut cache = re.create_cache();
             Ok(re.find_iter(&mut cache, &c.b.haystack).count())
         },
+        |nfa: &NFA| Ok(nfa.memory_usage()),
         || Compiler::new().build_from_hir(&hir).map_err(|e| e.into()),
     )
 }
 
-fn meta(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
-    timer::run_and_count(
+fn meta(c: &Config) -> anyhow::Result<timer::Samples> {
+    timer::run_and_count_with_size(
         &c.b,
         |re: regex_automata::meta::Regex| {
             Ok(re.find_iter(&c.b.haystack).count())
         },
+        |re: &regex_automata::meta::Regex| Ok(re.memory_usage()),
         || new::meta(c),
     )
 }
 
-fn dense(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
-    timer::run_and_count(
+fn dense(c: &Config) -> anyhow::Result<timer::Samples> {
+    timer::run_and_count_with_size(
         &c.b,
         |re: regex_automata::dfa::regex::Regex| {
             Ok(re.find_iter(&c.b.haystack).count())
         },
+        |re: &regex_automata::dfa::regex::Regex| {
+            Ok(re.forward().memory_usage() + re.reverse().memory_usage())
+        },
         || new::dense(c),
     )
 }
 
-fn sparse(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn sparse(c: &Config) -> anyhow::Result<timer::Samples> {
     use regex_automata::dfa::{regex::Regex, sparse::DFA};
-    timer::run_and_count(
+    timer::run_and_count_with_size(
         &c.b,
         |re: Regex<DFA<Vec<u8>>>| Ok(re.find_iter(&c.b.haystack).count()),
+        |re: &Regex<DFA<Vec<u8>>>| {
+            Ok(re.forward().memory_usage() + re.reverse().memory_usage())
+        },
         || new::sparse(c),
     )
 }
 
-fn hybrid(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
-    timer::run_and_count(
+fn hybrid(c: &Config) -> anyhow::Result<timer::Samples> {
+    timer::run_and_count_with_size(
         &c.b,
         |re: regex_automata::hybrid::regex::Regex| {
             let mut cache = re.create_cache();
             Ok(re.find_iter(&mut cache, &c.b.haystack).count())
         },
+        // This doesn't include the lazy DFA's cache, since that's built
+        // during a search and not at compile time.
+        |re: &regex_automata::hybrid::regex::Regex| {
+            Ok(re.forward().memory_usage() + re.reverse().memory_usage())
+        },
         || new::hybrid(c),
     )
 }
 
-fn backtrack(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
-    timer::run_and_count(
+fn backtrack(c: &Config) -> anyhow::Result<timer::Samples> {
+    use regex_automata::nfa::thompson::backtrack::BoundedBacktracker;
+    timer::run_and_count_with_size(
         &c.b,
-        |re: regex_automata::nfa::thompson::backtrack::BoundedBacktracker| {
+        |re: BoundedBacktracker| {
             let mut cache = re.create_cache();
             Ok(re.try_find_iter(&mut cache, &c.b.haystack).count())
         },
+        |re: &BoundedBacktracker| Ok(re.get_nfa().memory_usage()),
         || new::backtrack(c),
     )
 }
 
-fn pikevm(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
-    timer::run_and_count(
+fn pikevm(c: &Config) -> anyhow::Result<timer::Samples> {
+    timer::run_and_count_with_size(
         &c.b,
         |re: regex_automata::nfa::thompson::pikevm::PikeVM| {
             let mut cache = re.create_cache();
             Ok(re.find_iter(&mut cache, &c.b.haystack).count())
         },
+        |re: &regex_automata::nfa::thompson::pikevm::PikeVM| {
+            Ok(re.get_nfa().memory_usage())
+        },
         || new::pikevm(c),
     )
 }
 
-fn onepass(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn onepass(c: &Config) -> anyhow::Result<timer::Samples> {
     use regex_automata::{
         dfa::onepass::DFA, util::iter::Searcher, Anchored, Input,
     };
-    timer::run_and_count(
+    timer::run_and_count_with_size(
         &c.b,
         |re: DFA| {
             // The one-pass DFA only does anchored searches, so it doesn't
@@ -120,6 +138,7 @@ fn onepass(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
                 .infallible();
             Ok(it.count())
         },
+        |re: &DFA| Ok(re.memory_usage()),
         || new::onepass(c),
     )
 }
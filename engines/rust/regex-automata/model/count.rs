@@ -1,6 +1,8 @@
+use regex_automata::Input;
+
 use crate::{new, Config};
 
-pub(crate) fn run(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+pub(crate) fn run(c: &Config) -> anyhow::Result<timer::Samples> {
     match &*c.engine {
         "meta" => meta(c),
         "dense" => dense(c),
@@ -12,33 +14,41 @@ pub(crate) fn run(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     }
 }
 
-fn meta(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
-    let haystack = &*c.b.haystack;
+/// Returns the input to search for every engine. When the benchmark has a
+/// search window, we give it to the regex engine instead of slicing the
+/// haystack. This way, look-around assertions at the edges of the window can
+/// see the bytes outside of it.
+fn input(c: &Config) -> anyhow::Result<Input<'_>> {
+    Ok(Input::new(&*c.b.haystack).span(c.b.search_window()?))
+}
+
+fn meta(c: &Config) -> anyhow::Result<timer::Samples> {
+    let input = input(c)?;
     let re = new::meta(c)?;
-    timer::run(&c.b, || Ok(re.find_iter(haystack).count()))
+    timer::run(&c.b, || Ok(re.find_iter(input.clone()).count()))
 }
 
-fn dense(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
-    let haystack = &*c.b.haystack;
+fn dense(c: &Config) -> anyhow::Result<timer::Samples> {
+    let input = input(c)?;
     let re = new::dense(c)?;
-    timer::run(&c.b, || Ok(re.find_iter(haystack).count()))
+    timer::run(&c.b, || Ok(re.find_iter(input.clone()).count()))
 }
 
-fn sparse(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
-    let haystack = &*c.b.haystack;
+fn sparse(c: &Config) -> anyhow::Result<timer::Samples> {
+    let input = input(c)?;
     let re = new::sparse(c)?;
-    timer::run(&c.b, || Ok(re.find_iter(haystack).count()))
+    timer::run(&c.b, || Ok(re.find_iter(input.clone()).count()))
 }
 
-fn hybrid(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
-    let haystack = &*c.b.haystack;
+fn hybrid(c: &Config) -> anyhow::Result<timer::Samples> {
+    let input = input(c)?;
     let re = new::hybrid(c)?;
     let mut cache = re.create_cache();
-    timer::run(&c.b, || Ok(re.find_iter(&mut cache, haystack).count()))
+    timer::run(&c.b, || Ok(re.find_iter(&mut cache, input.clone()).count()))
 }
 
-fn backtrack(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
-    let haystack = &*c.b.haystack;
+fn backtrack(c: &Config) -> anyhow::Result<timer::Samples> {
+    let input = input(c)?;
     let re = new::backtrack(c)?;
     let mut cache = re.create_cache();
     timer::run(&c.b, || {
@@ -49,7 +59,7 @@ fn backtrack(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
         // seem to show much of a difference between this and the panicking
         // APIs.
         let mut count = 0;
-        for result in re.try_find_iter(&mut cache, haystack) {
+        for result in re.try_find_iter(&mut cache, input.clone()) {
             result?;
             count += 1;
         }
@@ -57,9 +67,9 @@ fn backtrack(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     })
 }
 
-fn pikevm(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
-    let haystack = &*c.b.haystack;
+fn pikevm(c: &Config) -> anyhow::Result<timer::Samples> {
+    let input = input(c)?;
     let re = new::pikevm(c)?;
     let mut cache = re.create_cache();
-    timer::run(&c.b, || Ok(re.find_iter(&mut cache, haystack).count()))
+    timer::run(&c.b, || Ok(re.find_iter(&mut cache, input.clone()).count()))
 }
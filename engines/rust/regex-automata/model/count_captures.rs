@@ -2,7 +2,7 @@ use regex_automata::Input;
 
 use crate::{new, Config};
 
-pub(crate) fn run(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+pub(crate) fn run(c: &Config) -> anyhow::Result<timer::Samples> {
     match &*c.engine {
         "meta" => meta(c),
         "backtrack" => backtrack(c),
@@ -11,7 +11,7 @@ pub(crate) fn run(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     }
 }
 
-fn meta(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn meta(c: &Config) -> anyhow::Result<timer::Samples> {
     let mut input = Input::new(&c.b.haystack);
     let re = new::meta(c)?;
     let mut caps = re.create_captures();
@@ -35,7 +35,7 @@ fn meta(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     })
 }
 
-fn backtrack(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn backtrack(c: &Config) -> anyhow::Result<timer::Samples> {
     let mut input = Input::new(&c.b.haystack);
     let re = new::backtrack(c)?;
     let (mut cache, mut caps) = (re.create_cache(), re.create_captures());
@@ -59,7 +59,7 @@ fn backtrack(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     })
 }
 
-fn pikevm(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn pikevm(c: &Config) -> anyhow::Result<timer::Samples> {
     let mut input = Input::new(&c.b.haystack);
     let re = new::pikevm(c)?;
     let (mut cache, mut caps) = (re.create_cache(), re.create_captures());
@@ -0,0 +1,71 @@
+use regex_automata::{util::iter::Searcher, Input};
+
+use crate::{new, Config};
+
+pub(crate) fn run(c: &Config) -> anyhow::Result<timer::Samples> {
+    match &*c.engine {
+        "meta" => meta(c),
+        "hybrid" => hybrid(c),
+        "backtrack" => backtrack(c),
+        "pikevm" => pikevm(c),
+        // Fully compiled DFAs don't have any mutable search state, so there
+        // is nothing to make cold. This is exactly the 'count' model.
+        "dense" | "sparse" => super::count::run(c),
+        _ => anyhow::bail!(
+            "engine '{}' does not support the 'count-cold' model",
+            c.engine,
+        ),
+    }
+}
+
+fn meta(c: &Config) -> anyhow::Result<timer::Samples> {
+    let haystack = &*c.b.haystack;
+    let re = new::meta(c)?;
+    timer::run(&c.b, || {
+        // The meta regex's convenience APIs use a cache from an internal
+        // pool, which would stay warm across iterations. So we create our
+        // own and use the lower level search APIs.
+        let mut cache = re.create_cache();
+        let count = Searcher::new(Input::new(haystack))
+            .into_matches_iter(|input| Ok(re.search_with(&mut cache, input)))
+            .infallible()
+            .count();
+        Ok(count)
+    })
+}
+
+fn hybrid(c: &Config) -> anyhow::Result<timer::Samples> {
+    let haystack = &*c.b.haystack;
+    let re = new::hybrid(c)?;
+    timer::run(&c.b, || {
+        // A fresh cache means the lazy DFA has to build every state it
+        // needs from scratch.
+        let mut cache = re.create_cache();
+        Ok(re.find_iter(&mut cache, haystack).count())
+    })
+}
+
+fn backtrack(c: &Config) -> anyhow::Result<timer::Samples> {
+    let haystack = &*c.b.haystack;
+    let re = new::backtrack(c)?;
+    timer::run(&c.b, || {
+        let mut cache = re.create_cache();
+        // Like for the 'count' model, we handle the error (which occurs when
+        // the haystack is too long) at search time.
+        let mut count = 0;
+        for result in re.try_find_iter(&mut cache, haystack) {
+            result?;
+            count += 1;
+        }
+        Ok(count)
+    })
+}
+
+fn pikevm(c: &Config) -> anyhow::Result<timer::Samples> {
+    let haystack = &*c.b.haystack;
+    let re = new::pikevm(c)?;
+    timer::run(&c.b, || {
+        let mut cache = re.create_cache();
+        Ok(re.find_iter(&mut cache, haystack).count())
+    })
+}
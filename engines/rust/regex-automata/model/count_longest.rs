@@ -0,0 +1,69 @@
+use regex_automata::{dfa::Automaton, Anchored, Input};
+
+use crate::{new, Config};
+
+pub(crate) fn run(c: &Config) -> anyhow::Result<timer::Samples> {
+    match &*c.engine {
+        "dense" => dense(c),
+        "hybrid" => hybrid(c),
+        _ => anyhow::bail!(
+            "engine '{}' does not support the 'count-longest' model",
+            c.engine,
+        ),
+    }
+}
+
+// None of the regex engines in regex-automata support leftmost-longest match
+// semantics directly. But the DFAs can emulate them. Namely, the leftmost
+// match starts at the same position under both leftmost-first and
+// leftmost-longest semantics. So we find that position with a normal
+// leftmost-first search, and then run an anchored search from there with a
+// DFA built with 'MatchKind::All', which reports the longest match possible.
+//
+// Benchmark definitions say we may assume empty matches are impossible, but
+// we still always make progress to avoid an infinite loop.
+
+fn dense(c: &Config) -> anyhow::Result<timer::Samples> {
+    let haystack = &*c.b.haystack;
+    let re = new::dense(c)?;
+    let longest = new::dense_longest(c)?;
+    timer::run(&c.b, || {
+        let (mut sum, mut at) = (0, 0);
+        while at <= haystack.len() {
+            let input = Input::new(haystack).range(at..);
+            let Some(m) = re.try_search(&input)? else { break };
+            let input = input.range(m.start()..).anchored(Anchored::Yes);
+            let end = match longest.try_search_fwd(&input)? {
+                Some(hm) => hm.offset(),
+                None => m.end(),
+            };
+            sum += end - m.start();
+            at = if end == m.start() { end + 1 } else { end };
+        }
+        Ok(sum)
+    })
+}
+
+fn hybrid(c: &Config) -> anyhow::Result<timer::Samples> {
+    let haystack = &*c.b.haystack;
+    let re = new::hybrid(c)?;
+    let longest = new::hybrid_longest(c)?;
+    let (mut cache, mut longest_cache) =
+        (re.create_cache(), longest.create_cache());
+    timer::run(&c.b, || {
+        let (mut sum, mut at) = (0, 0);
+        while at <= haystack.len() {
+            let input = Input::new(haystack).range(at..);
+            let Some(m) = re.try_search(&mut cache, &input)? else { break };
+            let input = input.range(m.start()..).anchored(Anchored::Yes);
+            let end =
+                match longest.try_search_fwd(&mut longest_cache, &input)? {
+                    Some(hm) => hm.offset(),
+                    None => m.end(),
+                };
+            sum += end - m.start();
+            at = if end == m.start() { end + 1 } else { end };
+        }
+        Ok(sum)
+    })
+}
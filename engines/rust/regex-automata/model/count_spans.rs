@@ -1,6 +1,6 @@
 use crate::{new, Config};
 
-pub(crate) fn run(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+pub(crate) fn run(c: &Config) -> anyhow::Result<timer::Samples> {
     match &*c.engine {
         "meta" => meta(c),
         "dense" => dense(c),
@@ -12,25 +12,25 @@ pub(crate) fn run(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     }
 }
 
-fn meta(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn meta(c: &Config) -> anyhow::Result<timer::Samples> {
     let haystack = &*c.b.haystack;
     let re = new::meta(c)?;
     timer::run(&c.b, || Ok(re.find_iter(haystack).map(|m| m.len()).sum()))
 }
 
-fn dense(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn dense(c: &Config) -> anyhow::Result<timer::Samples> {
     let haystack = &*c.b.haystack;
     let re = new::dense(c)?;
     timer::run(&c.b, || Ok(re.find_iter(haystack).map(|m| m.len()).sum()))
 }
 
-fn sparse(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn sparse(c: &Config) -> anyhow::Result<timer::Samples> {
     let haystack = &*c.b.haystack;
     let re = new::sparse(c)?;
     timer::run(&c.b, || Ok(re.find_iter(haystack).map(|m| m.len()).sum()))
 }
 
-fn hybrid(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn hybrid(c: &Config) -> anyhow::Result<timer::Samples> {
     let haystack = &*c.b.haystack;
     let re = new::hybrid(c)?;
     let mut cache = re.create_cache();
@@ -39,7 +39,7 @@ fn hybrid(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     })
 }
 
-fn backtrack(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn backtrack(c: &Config) -> anyhow::Result<timer::Samples> {
     let haystack = &*c.b.haystack;
     let re = new::backtrack(c)?;
     let mut cache = re.create_cache();
@@ -59,7 +59,7 @@ fn backtrack(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     })
 }
 
-fn pikevm(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn pikevm(c: &Config) -> anyhow::Result<timer::Samples> {
     let haystack = &*c.b.haystack;
     let re = new::pikevm(c)?;
     let mut cache = re.create_cache();
@@ -0,0 +1,63 @@
+use crate::{new, Config};
+
+pub(crate) fn run(c: &Config) -> anyhow::Result<timer::Samples> {
+    match &*c.engine {
+        "meta" => meta(c),
+        "dense" => dense(c),
+        "sparse" => sparse(c),
+        "hybrid" => hybrid(c),
+        "backtrack" => backtrack(c),
+        "pikevm" => pikevm(c),
+        _ => anyhow::bail!(
+            "engine '{}' does not support the 'first-match' model",
+            c.engine,
+        ),
+    }
+}
+
+fn meta(c: &Config) -> anyhow::Result<timer::Samples> {
+    let haystack = &*c.b.haystack;
+    let re = new::meta(c)?;
+    timer::run(&c.b, || Ok(re.find(haystack).map_or(0, |m| m.end())))
+}
+
+fn dense(c: &Config) -> anyhow::Result<timer::Samples> {
+    let haystack = &*c.b.haystack;
+    let re = new::dense(c)?;
+    timer::run(&c.b, || Ok(re.find(haystack).map_or(0, |m| m.end())))
+}
+
+fn sparse(c: &Config) -> anyhow::Result<timer::Samples> {
+    let haystack = &*c.b.haystack;
+    let re = new::sparse(c)?;
+    timer::run(&c.b, || Ok(re.find(haystack).map_or(0, |m| m.end())))
+}
+
+fn hybrid(c: &Config) -> anyhow::Result<timer::Samples> {
+    let haystack = &*c.b.haystack;
+    let re = new::hybrid(c)?;
+    let mut cache = re.create_cache();
+    timer::run(&c.b, || {
+        Ok(re.find(&mut cache, haystack).map_or(0, |m| m.end()))
+    })
+}
+
+fn backtrack(c: &Config) -> anyhow::Result<timer::Samples> {
+    let haystack = &*c.b.haystack;
+    let re = new::backtrack(c)?;
+    let mut cache = re.create_cache();
+    timer::run(&c.b, || {
+        // Like for the 'count' model, we handle the error (which occurs when
+        // the haystack is too long) at search time.
+        Ok(re.try_find(&mut cache, haystack)?.map_or(0, |m| m.end()))
+    })
+}
+
+fn pikevm(c: &Config) -> anyhow::Result<timer::Samples> {
+    let haystack = &*c.b.haystack;
+    let re = new::pikevm(c)?;
+    let mut cache = re.create_cache();
+    timer::run(&c.b, || {
+        Ok(re.find(&mut cache, haystack).map_or(0, |m| m.end()))
+    })
+}
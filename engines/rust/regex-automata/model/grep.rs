@@ -1,8 +1,6 @@
-use bstr::ByteSlice;
-
 use crate::{new, Config};
 
-pub(crate) fn run(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+pub(crate) fn run(c: &Config) -> anyhow::Result<timer::Samples> {
     match &*c.engine {
         "meta" => meta(c),
         "dense" => dense(c),
@@ -15,12 +13,12 @@ pub(crate) fn run(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     }
 }
 
-fn meta(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn meta(c: &Config) -> anyhow::Result<timer::Samples> {
     let haystack = &*c.b.haystack;
     let re = new::meta(c)?;
     timer::run(&c.b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             if re.is_match(line) {
                 count += 1;
             }
@@ -29,12 +27,12 @@ fn meta(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     })
 }
 
-fn dense(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn dense(c: &Config) -> anyhow::Result<timer::Samples> {
     let haystack = &*c.b.haystack;
     let re = new::dense(c)?;
     timer::run(&c.b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             if re.is_match(line) {
                 count += 1;
             }
@@ -43,12 +41,12 @@ fn dense(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     })
 }
 
-fn sparse(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn sparse(c: &Config) -> anyhow::Result<timer::Samples> {
     let haystack = &*c.b.haystack;
     let re = new::sparse(c)?;
     timer::run(&c.b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             if re.is_match(line) {
                 count += 1;
             }
@@ -57,13 +55,13 @@ fn sparse(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     })
 }
 
-fn hybrid(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn hybrid(c: &Config) -> anyhow::Result<timer::Samples> {
     let haystack = &*c.b.haystack;
     let re = new::hybrid(c)?;
     let mut cache = re.create_cache();
     timer::run(&c.b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             if re.is_match(&mut cache, line) {
                 count += 1;
             }
@@ -72,13 +70,13 @@ fn hybrid(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     })
 }
 
-fn backtrack(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn backtrack(c: &Config) -> anyhow::Result<timer::Samples> {
     let haystack = &*c.b.haystack;
     let re = new::backtrack(c)?;
     let mut cache = re.create_cache();
     timer::run(&c.b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             if re.try_is_match(&mut cache, line)? {
                 count += 1;
             }
@@ -87,13 +85,13 @@ fn backtrack(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     })
 }
 
-fn pikevm(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn pikevm(c: &Config) -> anyhow::Result<timer::Samples> {
     let haystack = &*c.b.haystack;
     let re = new::pikevm(c)?;
     let mut cache = re.create_cache();
     timer::run(&c.b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             if re.is_match(&mut cache, line) {
                 count += 1;
             }
@@ -102,13 +100,13 @@ fn pikevm(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     })
 }
 
-fn onepass(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn onepass(c: &Config) -> anyhow::Result<timer::Samples> {
     let haystack = &*c.b.haystack;
     let re = new::onepass(c)?;
     let mut cache = re.create_cache();
     timer::run(&c.b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             if re.is_match(&mut cache, line) {
                 count += 1;
             }
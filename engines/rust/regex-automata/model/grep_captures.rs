@@ -1,10 +1,8 @@
-use bstr::ByteSlice;
-
 use regex_automata::{Anchored, Input};
 
 use crate::{new, Config};
 
-pub(crate) fn run(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+pub(crate) fn run(c: &Config) -> anyhow::Result<timer::Samples> {
     match &*c.engine {
         "meta" => meta(c),
         "backtrack" => backtrack(c),
@@ -14,13 +12,13 @@ pub(crate) fn run(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     }
 }
 
-fn meta(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn meta(c: &Config) -> anyhow::Result<timer::Samples> {
     let haystack = &*c.b.haystack;
     let re = new::meta(c)?;
     let mut caps = re.create_captures();
     timer::run(&c.b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             let mut input = Input::new(line);
             while let Some(m) = {
                 re.search_captures(&input, &mut caps);
@@ -40,13 +38,13 @@ fn meta(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     })
 }
 
-fn backtrack(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn backtrack(c: &Config) -> anyhow::Result<timer::Samples> {
     let haystack = &*c.b.haystack;
     let re = new::backtrack(c)?;
     let (mut cache, mut caps) = (re.create_cache(), re.create_captures());
     timer::run(&c.b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             let mut input = Input::new(line);
             while let Some(m) = {
                 re.try_search(&mut cache, &input, &mut caps)?;
@@ -66,13 +64,13 @@ fn backtrack(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     })
 }
 
-fn pikevm(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn pikevm(c: &Config) -> anyhow::Result<timer::Samples> {
     let haystack = &*c.b.haystack;
     let re = new::pikevm(c)?;
     let (mut cache, mut caps) = (re.create_cache(), re.create_captures());
     timer::run(&c.b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             let mut input = Input::new(line);
             while let Some(m) = {
                 re.search(&mut cache, &input, &mut caps);
@@ -92,13 +90,13 @@ fn pikevm(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     })
 }
 
-fn onepass(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn onepass(c: &Config) -> anyhow::Result<timer::Samples> {
     let haystack = &*c.b.haystack;
     let re = new::onepass(c)?;
     let (mut cache, mut caps) = (re.create_cache(), re.create_captures());
     timer::run(&c.b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             let mut input = Input::new(line).anchored(Anchored::Yes);
             while let Some(m) = {
                 re.try_search(&mut cache, &input, &mut caps)?;
@@ -1,7 +1,12 @@
+pub mod anchored_count;
 pub mod compile;
 pub mod count;
 pub mod count_captures;
+pub mod count_cold;
+pub mod count_longest;
 pub mod count_spans;
+pub mod first_match;
 pub mod grep;
 pub mod grep_captures;
 pub mod regexredux;
+pub mod size;
@@ -1,6 +1,6 @@
 use crate::{new, Config};
 
-pub(crate) fn run(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+pub(crate) fn run(c: &Config) -> anyhow::Result<timer::Samples> {
     match &*c.engine {
         "meta" => meta(c),
         "dense" => dense(c),
@@ -10,7 +10,7 @@ pub(crate) fn run(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     }
 }
 
-fn meta(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn meta(c: &Config) -> anyhow::Result<timer::Samples> {
     use regex_automata::meta::Regex;
 
     let haystack = c.b.haystack_str()?;
@@ -25,7 +25,7 @@ fn meta(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     timer::run(&c.b, || regexredux::generic(haystack, compile))
 }
 
-fn dense(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn dense(c: &Config) -> anyhow::Result<timer::Samples> {
     use regex_automata::{dfa::regex::Regex, nfa::thompson};
 
     let haystack = c.b.haystack_str()?;
@@ -42,7 +42,7 @@ fn dense(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     timer::run(&c.b, || regexredux::generic(haystack, compile))
 }
 
-fn hybrid(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn hybrid(c: &Config) -> anyhow::Result<timer::Samples> {
     use regex_automata::{
         hybrid::{dfa::DFA, regex::Regex},
         nfa::thompson,
@@ -64,7 +64,7 @@ fn hybrid(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
     timer::run(&c.b, || regexredux::generic(haystack, compile))
 }
 
-fn pikevm(c: &Config) -> anyhow::Result<Vec<timer::Sample>> {
+fn pikevm(c: &Config) -> anyhow::Result<timer::Samples> {
     use regex_automata::{
         nfa::thompson::{self, pikevm::PikeVM},
         util::captures::Captures,
@@ -0,0 +1,102 @@
+use crate::{new, Config};
+
+pub(crate) fn run(c: &Config) -> anyhow::Result<timer::Samples> {
+    match &*c.engine {
+        "nfa" => nfa(c),
+        "meta" => meta(c),
+        "dense" => dense(c),
+        "sparse" => sparse(c),
+        "hybrid" => hybrid(c),
+        "backtrack" => backtrack(c),
+        "pikevm" => pikevm(c),
+        "onepass" => onepass(c),
+        _ => unreachable!(),
+    }
+}
+
+fn nfa(c: &Config) -> anyhow::Result<timer::Samples> {
+    use regex_automata::nfa::thompson::{Compiler, NFA};
+    use regex_syntax::ParserBuilder;
+
+    let pattern = c.b.regex.one()?;
+    let hir = ParserBuilder::new()
+        .utf8(false)
+        .unicode(c.b.regex.unicode)
+        .case_insensitive(c.b.regex.case_insensitive)
+        .build()
+        .parse(&pattern)?;
+    timer::run_and_size(
+        &c.b,
+        |nfa: &NFA| Ok(nfa.memory_usage()),
+        || Compiler::new().build_from_hir(&hir).map_err(|e| e.into()),
+    )
+}
+
+fn meta(c: &Config) -> anyhow::Result<timer::Samples> {
+    timer::run_and_size(
+        &c.b,
+        |re: &regex_automata::meta::Regex| Ok(re.memory_usage()),
+        || new::meta(c),
+    )
+}
+
+fn dense(c: &Config) -> anyhow::Result<timer::Samples> {
+    timer::run_and_size(
+        &c.b,
+        |re: &regex_automata::dfa::regex::Regex| {
+            Ok(re.forward().memory_usage() + re.reverse().memory_usage())
+        },
+        || new::dense(c),
+    )
+}
+
+fn sparse(c: &Config) -> anyhow::Result<timer::Samples> {
+    use regex_automata::dfa::{regex::Regex, sparse::DFA};
+    timer::run_and_size(
+        &c.b,
+        |re: &Regex<DFA<Vec<u8>>>| {
+            Ok(re.forward().memory_usage() + re.reverse().memory_usage())
+        },
+        || new::sparse(c),
+    )
+}
+
+fn hybrid(c: &Config) -> anyhow::Result<timer::Samples> {
+    timer::run_and_size(
+        &c.b,
+        // This doesn't include the lazy DFA's cache, since that's built
+        // during a search and not at compile time.
+        |re: &regex_automata::hybrid::regex::Regex| {
+            Ok(re.forward().memory_usage() + re.reverse().memory_usage())
+        },
+        || new::hybrid(c),
+    )
+}
+
+fn backtrack(c: &Config) -> anyhow::Result<timer::Samples> {
+    use regex_automata::nfa::thompson::backtrack::BoundedBacktracker;
+    timer::run_and_size(
+        &c.b,
+        |re: &BoundedBacktracker| Ok(re.get_nfa().memory_usage()),
+        || new::backtrack(c),
+    )
+}
+
+fn pikevm(c: &Config) -> anyhow::Result<timer::Samples> {
+    timer::run_and_size(
+        &c.b,
+        |re: &regex_automata::nfa::thompson::pikevm::PikeVM| {
+            Ok(re.get_nfa().memory_usage())
+        },
+        || new::pikevm(c),
+    )
+}
+
+fn onepass(c: &Config) -> anyhow::Result<timer::Samples> {
+    use regex_automata::dfa::onepass::DFA;
+    timer::run_and_size(
+        &c.b,
+        |re: &DFA| Ok(re.memory_usage()),
+        || new::onepass(c),
+    )
+}
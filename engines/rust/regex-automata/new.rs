@@ -4,11 +4,19 @@ use crate::Config;
 pub(crate) fn meta(c: &Config) -> anyhow::Result<regex_automata::meta::Regex> {
     use regex_automata::meta::Regex;
 
-    let config = Regex::config()
+    let nfa_size_limit =
+        c.b.param_size("nfa-size-limit")?.unwrap_or((1 << 20) * 100);
+    let mut config = Regex::config()
         // Disabling UTF-8 here just means that zero-width matches that split
         // a codepoint are allowed.
         .utf8_empty(false)
-        .nfa_size_limit(Some((1 << 20) * 100));
+        .nfa_size_limit(Some(nfa_size_limit));
+    if let Some(limit) = c.b.param_size("dfa-size-limit")? {
+        config = config.dfa_size_limit(Some(limit));
+    }
+    if let Some(limit) = c.b.param_size("hybrid-cache-capacity")? {
+        config = config.hybrid_cache_capacity(limit);
+    }
     let re = Regex::builder()
         .syntax(syntax_config(c))
         .configure(config)
@@ -20,13 +28,12 @@ pub(crate) fn meta(c: &Config) -> anyhow::Result<regex_automata::meta::Regex> {
 pub(crate) fn dense(
     c: &Config,
 ) -> anyhow::Result<regex_automata::dfa::regex::Regex> {
-    use regex_automata::{dfa::regex::Regex, nfa::thompson};
+    use regex_automata::dfa::regex::Regex;
 
     let re = Regex::builder()
         .syntax(syntax_config(c))
-        // Disabling UTF-8 here just means that zero-width matches that split
-        // a codepoint are allowed.
-        .thompson(thompson::Config::new().utf8(false))
+        .thompson(thompson_config(c)?)
+        .dense(dense_config(c)?)
         .build_many(&c.b.regex.patterns)?;
     Ok(re)
 }
@@ -42,13 +49,12 @@ pub(crate) fn sparse(
         regex_automata::dfa::sparse::DFA<Vec<u8>>,
     >,
 > {
-    use regex_automata::{dfa::regex::Regex, nfa::thompson};
+    use regex_automata::dfa::regex::Regex;
 
     let re = Regex::builder()
         .syntax(syntax_config(c))
-        // Disabling UTF-8 here just means that zero-width matches that split
-        // a codepoint are allowed.
-        .thompson(thompson::Config::new().utf8(false))
+        .thompson(thompson_config(c)?)
+        .dense(dense_config(c)?)
         .build_many_sparse(&c.b.regex.patterns)?;
     Ok(re)
 }
@@ -61,10 +67,7 @@ pub(crate) fn sparse(
 pub(crate) fn hybrid(
     c: &Config,
 ) -> anyhow::Result<regex_automata::hybrid::regex::Regex> {
-    use regex_automata::{
-        hybrid::{dfa::DFA, regex::Regex},
-        nfa::thompson,
-    };
+    use regex_automata::hybrid::regex::Regex;
 
     let re = Regex::builder()
         // This makes it so the cache built by this regex will be at least bit
@@ -77,14 +80,52 @@ pub(crate) fn hybrid(
         // enough, it's likely to get cleared very frequently and this will
         // overall reduce search speed.)
         .syntax(syntax_config(c))
-        // Disabling UTF-8 here just means that zero-width matches that split
-        // a codepoint are allowed.
-        .thompson(thompson::Config::new().utf8(false))
-        .dfa(DFA::config().skip_cache_capacity_check(true))
+        .thompson(thompson_config(c)?)
+        .dfa(hybrid_config(c)?)
         .build_many(&c.b.regex.patterns)?;
     Ok(re)
 }
 
+/// Constructor for a fully compiled "dense" DFA that reports the longest
+/// match from where an anchored search begins. This is used to implement
+/// leftmost-longest match semantics for the 'count-longest' model. That is,
+/// the leftmost-first regex from 'dense' finds where the leftmost match
+/// starts (which is the same for both semantics), and this DFA finds where
+/// the longest match starting there ends.
+pub(crate) fn dense_longest(
+    c: &Config,
+) -> anyhow::Result<regex_automata::dfa::dense::DFA<Vec<u32>>> {
+    use regex_automata::{
+        dfa::{dense, StartKind},
+        MatchKind,
+    };
+
+    let dfa = dense::Builder::new()
+        .configure(
+            dense_config(c)?
+                .match_kind(MatchKind::All)
+                .start_kind(StartKind::Anchored),
+        )
+        .syntax(syntax_config(c))
+        .thompson(thompson_config(c)?)
+        .build_many(&c.b.regex.patterns)?;
+    Ok(dfa)
+}
+
+/// Like 'dense_longest', but for a lazy DFA.
+pub(crate) fn hybrid_longest(
+    c: &Config,
+) -> anyhow::Result<regex_automata::hybrid::dfa::DFA> {
+    use regex_automata::{hybrid::dfa::DFA, MatchKind};
+
+    let dfa = DFA::builder()
+        .configure(hybrid_config(c)?.match_kind(MatchKind::All))
+        .syntax(syntax_config(c))
+        .thompson(thompson_config(c)?)
+        .build_many(&c.b.regex.patterns)?;
+    Ok(dfa)
+}
+
 /// Constructor for the PikeVM, which can handle anything including Unicode
 /// word boundaries and resolving capturing groups, but can be quite slow.
 pub(crate) fn pikevm(
@@ -127,18 +168,83 @@ pub(crate) fn backtrack(
 /// Unicode word boundaries and resolving capturing groups, but only works on a
 /// specific class of regexes known as "one-pass." Moreover, it can only handle
 /// regexes with at most a small number of explicit capturing groups.
+///
+/// When the regex can't be built because of one of these limitations (as
+/// opposed to, say, a syntax error), then the error returned is an
+/// `Unsupported` error.
 pub(crate) fn onepass(
     c: &Config,
 ) -> anyhow::Result<regex_automata::dfa::onepass::DFA> {
     use regex_automata::{dfa::onepass::DFA, nfa::thompson};
 
-    let re = DFA::builder()
+    let result = DFA::builder()
         .syntax(syntax_config(c))
         // Disabling UTF-8 here just means that zero-width matches that split
         // a codepoint are allowed.
         .thompson(thompson::Config::new().utf8(false))
-        .build_many(&c.b.regex.patterns)?;
-    Ok(re)
+        .build_many(&c.b.regex.patterns);
+    match result {
+        Ok(re) => Ok(re),
+        // The one-pass DFA's error kinds are private, but the only errors
+        // with a source are failures to build the NFA (e.g., a syntax error)
+        // or a Unicode word boundary when the corresponding crate feature is
+        // disabled. Every other error means the regex is outside of what a
+        // one-pass DFA can handle.
+        Err(err) => match std::error::Error::source(&err) {
+            Some(src) if src.is::<thompson::BuildError>() => Err(err.into()),
+            _ => Err(Unsupported(err.to_string()).into()),
+        },
+    }
+}
+
+/// An error indicating that a regex engine can't run a benchmark at all,
+/// e.g., because the regex isn't one-pass. Unlike other errors, these are
+/// reported to rebar as a skip instead of as a failure.
+#[derive(Debug)]
+pub(crate) struct Unsupported(pub(crate) String);
+
+impl std::fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for Unsupported {}
+
+/// Returns the Thompson NFA configuration used by the fully compiled and lazy
+/// DFAs. The NFA size limit may be set via the 'nfa-size-limit' parameter.
+fn thompson_config(
+    c: &Config,
+) -> anyhow::Result<regex_automata::nfa::thompson::Config> {
+    let limit = c.b.param_size("nfa-size-limit")?;
+    Ok(regex_automata::nfa::thompson::Config::new()
+        // Disabling UTF-8 here just means that zero-width matches that split
+        // a codepoint are allowed.
+        .utf8(false)
+        .nfa_size_limit(limit))
+}
+
+/// Returns the configuration for building fully compiled DFAs. The size
+/// limit may be set via the 'dfa-size-limit' parameter.
+fn dense_config(
+    c: &Config,
+) -> anyhow::Result<regex_automata::dfa::dense::Config> {
+    let limit = c.b.param_size("dfa-size-limit")?;
+    Ok(regex_automata::dfa::dense::Config::new().dfa_size_limit(limit))
+}
+
+/// Returns the configuration for building lazy DFAs. The cache capacity may
+/// be set via the 'hybrid-cache-capacity' parameter.
+fn hybrid_config(
+    c: &Config,
+) -> anyhow::Result<regex_automata::hybrid::dfa::Config> {
+    use regex_automata::hybrid::dfa::DFA;
+
+    let mut config = DFA::config().skip_cache_capacity_check(true);
+    if let Some(limit) = c.b.param_size("hybrid-cache-capacity")? {
+        config = config.cache_capacity(limit);
+    }
+    Ok(config)
 }
 
 /// For regex-automata based regex engines, this builds a syntax configuration
@@ -153,4 +259,6 @@ pub(crate) fn syntax_config(
         .utf8(false)
         .unicode(c.b.regex.unicode)
         .case_insensitive(c.b.regex.case_insensitive)
+        .multi_line(c.b.regex.multiline)
+        .dot_matches_new_line(c.b.regex.dot_all)
 }
@@ -6,13 +6,20 @@ use {
     regex_lite::{Regex, RegexBuilder},
 };
 
+/// The cargo features enabled for the regex engine in Cargo.toml. This is
+/// reported by '--metadata' and should be kept in sync with Cargo.toml.
+const FEATURES: &[&str] = &["default"];
+
 fn main() -> anyhow::Result<()> {
     let mut p = lexopt::Parser::from_env();
-    let (mut quiet, mut version) = (false, false);
+    let (mut quiet, mut version, mut validate) = (false, false, false);
+    let mut metadata = false;
     while let Some(arg) = p.next()? {
         match arg {
             Arg::Short('h') | Arg::Long("help") => {
-                anyhow::bail!("main [--version | --quiet]")
+                anyhow::bail!(
+                    "main [--version | --metadata | --quiet | --validate]"
+                )
             }
             Arg::Short('q') | Arg::Long("quiet") => {
                 quiet = true;
@@ -20,35 +27,57 @@ fn main() -> anyhow::Result<()> {
             Arg::Long("version") => {
                 version = true;
             }
+            Arg::Long("metadata") => {
+                metadata = true;
+            }
+            Arg::Long("validate") => {
+                validate = true;
+            }
             _ => return Err(arg.unexpected().into()),
         }
     }
+    if metadata {
+        writeln!(std::io::stdout(), "{}", timer::metadata(FEATURES))?;
+        return Ok(());
+    }
     if version {
         writeln!(std::io::stdout(), "{}", env!("CARGO_PKG_VERSION"))?;
         return Ok(());
     }
+    if validate {
+        return timer::validate(run);
+    }
     let b = klv::Benchmark::read(std::io::stdin())
         .context("failed to read KLV data from <stdin>")?;
-    let samples = match b.model.as_str() {
-        "compile" => model_compile(&b)?,
-        "count" => model_count(&b, &compile(&b)?)?,
-        "count-spans" => model_count_spans(&b, &compile(&b)?)?,
-        "count-captures" => model_count_captures(&b, &compile(&b)?)?,
-        "grep" => model_grep(&b, &compile(&b)?)?,
-        "grep-captures" => model_grep_captures(&b, &compile(&b)?)?,
-        "regex-redux" => model_regex_redux(&b)?,
-        _ => anyhow::bail!("unrecognized benchmark model '{}'", b.model),
-    };
+    let samples = run(&b)?;
     if !quiet {
         let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "# warmup_iters={}", samples.warmup_iters)?;
+        if samples.partial {
+            writeln!(stdout, "# partial=true")?;
+        }
         for s in samples.iter() {
-            writeln!(stdout, "{},{}", s.duration.as_nanos(), s.count)?;
+            writeln!(stdout, "{}", s)?;
         }
     }
     Ok(())
 }
 
-fn model_compile(b: &klv::Benchmark) -> anyhow::Result<Vec<timer::Sample>> {
+/// Runs the given benchmark and returns its samples.
+fn run(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
+    match b.model.as_str() {
+        "compile" => model_compile(b),
+        "count" => model_count(b, &compile(b)?),
+        "count-spans" => model_count_spans(b, &compile(b)?),
+        "count-captures" => model_count_captures(b, &compile(b)?),
+        "grep" => model_grep(b, &compile(b)?),
+        "grep-captures" => model_grep_captures(b, &compile(b)?),
+        "regex-redux" => model_regex_redux(b),
+        _ => anyhow::bail!("unrecognized benchmark model '{}'", b.model),
+    }
+}
+
+fn model_compile(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
     let haystack = b.haystack_str()?;
     timer::run_and_count(
         b,
@@ -60,7 +89,7 @@ fn model_compile(b: &klv::Benchmark) -> anyhow::Result<Vec<timer::Sample>> {
 fn model_count(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = b.haystack_str()?;
     timer::run(b, || Ok(re.find_iter(haystack).count()))
 }
@@ -68,7 +97,7 @@ fn model_count(
 fn model_count_spans(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = b.haystack_str()?;
     timer::run(b, || Ok(re.find_iter(haystack).map(|m| m.len()).sum()))
 }
@@ -76,7 +105,7 @@ fn model_count_spans(
 fn model_count_captures(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = b.haystack_str()?;
     let mut caps = re.capture_locations();
     timer::run(b, || {
@@ -99,11 +128,11 @@ fn model_count_captures(
 fn model_grep(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = b.haystack_str()?;
     timer::run(b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::str(haystack) {
             if re.is_match(line) {
                 count += 1;
             }
@@ -115,12 +144,12 @@ fn model_grep(
 fn model_grep_captures(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = b.haystack_str()?;
     let mut caps = re.capture_locations();
     timer::run(b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::str(haystack) {
             let mut at = 0;
             while let Some(m) = re.captures_read_at(&mut caps, line, at) {
                 for i in 0..caps.len() {
@@ -137,9 +166,7 @@ fn model_grep_captures(
     })
 }
 
-fn model_regex_redux(
-    b: &klv::Benchmark,
-) -> anyhow::Result<Vec<timer::Sample>> {
+fn model_regex_redux(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
     let haystack = b.haystack_str()?;
     let compile = |pattern: &str| -> anyhow::Result<regexredux::RegexFn> {
         let re = compile_pattern(b, pattern)?;
@@ -156,6 +183,8 @@ fn compile(b: &klv::Benchmark) -> anyhow::Result<Regex> {
 fn compile_pattern(b: &klv::Benchmark, pat: &str) -> anyhow::Result<Regex> {
     let re = RegexBuilder::new(pat)
         .case_insensitive(b.regex.case_insensitive)
+        .multi_line(b.regex.multiline)
+        .dot_matches_new_line(b.regex.dot_all)
         .size_limit((1 << 20) * 100)
         .build()?;
     Ok(re)
@@ -2,18 +2,21 @@ use std::io::Write;
 
 use {
     anyhow::Context,
-    bstr::ByteSlice,
     lexopt::Arg,
     regex::bytes::{Regex, RegexBuilder},
 };
 
+/// The cargo features enabled for the regex engine in Cargo.toml. This is
+/// reported by '--metadata' and should be kept in sync with Cargo.toml.
+const FEATURES: &[&str] = &["default"];
+
 fn main() -> anyhow::Result<()> {
     let mut p = lexopt::Parser::from_env();
-    let (mut quiet, mut version) = (false, false);
+    let (mut quiet, mut version, mut metadata) = (false, false, false);
     while let Some(arg) = p.next()? {
         match arg {
             Arg::Short('h') | Arg::Long("help") => {
-                anyhow::bail!("main [--version | --quiet]")
+                anyhow::bail!("main [--version | --metadata | --quiet]")
             }
             Arg::Short('q') | Arg::Long("quiet") => {
                 quiet = true;
@@ -21,9 +24,16 @@ fn main() -> anyhow::Result<()> {
             Arg::Long("version") => {
                 version = true;
             }
+            Arg::Long("metadata") => {
+                metadata = true;
+            }
             _ => return Err(arg.unexpected().into()),
         }
     }
+    if metadata {
+        writeln!(std::io::stdout(), "{}", timer::metadata(FEATURES))?;
+        return Ok(());
+    }
     if version {
         writeln!(std::io::stdout(), "{}", env!("CARGO_PKG_VERSION"))?;
         return Ok(());
@@ -42,14 +52,18 @@ fn main() -> anyhow::Result<()> {
     };
     if !quiet {
         let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "# warmup_iters={}", samples.warmup_iters)?;
+        if samples.partial {
+            writeln!(stdout, "# partial=true")?;
+        }
         for s in samples.iter() {
-            writeln!(stdout, "{},{}", s.duration.as_nanos(), s.count)?;
+            writeln!(stdout, "{}", s)?;
         }
     }
     Ok(())
 }
 
-fn model_compile(b: &klv::Benchmark) -> anyhow::Result<Vec<timer::Sample>> {
+fn model_compile(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run_and_count(
         b,
@@ -61,7 +75,7 @@ fn model_compile(b: &klv::Benchmark) -> anyhow::Result<Vec<timer::Sample>> {
 fn model_count(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run(b, || Ok(re.find_iter(haystack).count()))
 }
@@ -69,7 +83,7 @@ fn model_count(
 fn model_count_spans(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run(b, || {
         Ok(re.find_iter(haystack).map(|m| m.as_bytes().len()).sum())
@@ -79,7 +93,7 @@ fn model_count_spans(
 fn model_count_captures(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     let mut caps = re.capture_locations();
     timer::run(b, || {
@@ -102,11 +116,11 @@ fn model_count_captures(
 fn model_grep(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run(b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             if re.is_match(line) {
                 count += 1;
             }
@@ -118,12 +132,12 @@ fn model_grep(
 fn model_grep_captures(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     let mut caps = re.capture_locations();
     timer::run(b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             let mut at = 0;
             while let Some(m) = re.captures_read_at(&mut caps, line, at) {
                 for i in 0..caps.len() {
@@ -140,9 +154,7 @@ fn model_grep_captures(
     })
 }
 
-fn model_regex_redux(
-    b: &klv::Benchmark,
-) -> anyhow::Result<Vec<timer::Sample>> {
+fn model_regex_redux(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
     let haystack = b.haystack_str()?;
     let compile = |pattern: &str| -> anyhow::Result<regexredux::RegexFn> {
         let re = compile_pattern(b, pattern)?;
@@ -162,6 +174,8 @@ fn compile_pattern(b: &klv::Benchmark, pat: &str) -> anyhow::Result<Regex> {
     let re = RegexBuilder::new(pat)
         .unicode(b.regex.unicode)
         .case_insensitive(b.regex.case_insensitive)
+        .multi_line(b.regex.multiline)
+        .dot_matches_new_line(b.regex.dot_all)
         .size_limit((1 << 20) * 100)
         .build()?;
     Ok(re)
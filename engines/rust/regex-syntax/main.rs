@@ -10,6 +10,10 @@ use {
     },
 };
 
+/// The cargo features enabled for the regex engine in Cargo.toml. This is
+/// reported by '--metadata' and should be kept in sync with Cargo.toml.
+const FEATURES: &[&str] = &["default"];
+
 fn main() -> anyhow::Result<()> {
     let mut p = lexopt::Parser::from_env();
     let engine = match p.next()? {
@@ -22,15 +26,15 @@ fn main() -> anyhow::Result<()> {
         }
     };
     anyhow::ensure!(
-        engine == "ast" || engine == "hir",
+        engine == "ast" || engine == "hir" || engine == "nfa",
         "unrecognized engine '{}'",
         engine,
     );
-    let (mut quiet, mut version) = (false, false);
+    let (mut quiet, mut version, mut metadata) = (false, false, false);
     while let Some(arg) = p.next()? {
         match arg {
             Arg::Short('h') | Arg::Long("help") => {
-                anyhow::bail!("main [--version | --quiet]")
+                anyhow::bail!("main [--version | --metadata | --quiet]")
             }
             Arg::Short('q') | Arg::Long("quiet") => {
                 quiet = true;
@@ -38,9 +42,16 @@ fn main() -> anyhow::Result<()> {
             Arg::Long("version") => {
                 version = true;
             }
+            Arg::Long("metadata") => {
+                metadata = true;
+            }
             _ => return Err(arg.unexpected().into()),
         }
     }
+    if metadata {
+        writeln!(std::io::stdout(), "{}", timer::metadata(FEATURES))?;
+        return Ok(());
+    }
     if version {
         writeln!(std::io::stdout(), "{}", env!("CARGO_PKG_VERSION"))?;
         return Ok(());
@@ -55,20 +66,23 @@ fn main() -> anyhow::Result<()> {
     let samples = match &*engine {
         "ast" => model_compile_ast(&b)?,
         "hir" => model_compile_hir(&b)?,
+        "nfa" => model_compile_nfa(&b)?,
         _ => unreachable!(),
     };
     if !quiet {
         let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "# warmup_iters={}", samples.warmup_iters)?;
+        if samples.partial {
+            writeln!(stdout, "# partial=true")?;
+        }
         for s in samples.iter() {
-            writeln!(stdout, "{},{}", s.duration.as_nanos(), s.count)?;
+            writeln!(stdout, "{}", s)?;
         }
     }
     Ok(())
 }
 
-fn model_compile_ast(
-    b: &klv::Benchmark,
-) -> anyhow::Result<Vec<timer::Sample>> {
+fn model_compile_ast(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
     let pattern = b.regex.one()?;
     timer::run_and_count(
         b,
@@ -77,6 +91,8 @@ fn model_compile_ast(
                 .utf8(false)
                 .unicode(b.regex.unicode)
                 .case_insensitive(b.regex.case_insensitive)
+                .multi_line(b.regex.multiline)
+                .dot_matches_new_line(b.regex.dot_all)
                 .build()
                 .translate(pattern, &ast)?;
             let nfa = Compiler::new().build_from_hir(&hir)?;
@@ -88,15 +104,15 @@ fn model_compile_ast(
     )
 }
 
-fn model_compile_hir(
-    b: &klv::Benchmark,
-) -> anyhow::Result<Vec<timer::Sample>> {
+fn model_compile_hir(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
     let pattern = b.regex.one()?;
     let ast = ParserBuilder::new().build().parse(&pattern)?;
     let mut translator = TranslatorBuilder::new()
         .utf8(false)
         .unicode(b.regex.unicode)
         .case_insensitive(b.regex.case_insensitive)
+        .multi_line(b.regex.multiline)
+        .dot_matches_new_line(b.regex.dot_all)
         .build();
     timer::run_and_count(
         b,
@@ -109,3 +125,25 @@ fn model_compile_hir(
         || translator.translate(pattern, &ast).map_err(|e| e.into()),
     )
 }
+
+fn model_compile_nfa(b: &klv::Benchmark) -> anyhow::Result<timer::Samples> {
+    let pattern = b.regex.one()?;
+    let ast = ParserBuilder::new().build().parse(&pattern)?;
+    let hir = TranslatorBuilder::new()
+        .utf8(false)
+        .unicode(b.regex.unicode)
+        .case_insensitive(b.regex.case_insensitive)
+        .multi_line(b.regex.multiline)
+        .dot_matches_new_line(b.regex.dot_all)
+        .build()
+        .translate(pattern, &ast)?;
+    timer::run_and_count(
+        b,
+        |nfa| {
+            let re = PikeVM::builder().build_from_nfa(nfa)?;
+            let mut cache = re.create_cache();
+            Ok(re.find_iter(&mut cache, &b.haystack).count())
+        },
+        || Compiler::new().build_from_hir(&hir).map_err(|e| e.into()),
+    )
+}
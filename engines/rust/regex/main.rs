@@ -2,21 +2,29 @@ use std::io::Write;
 
 use {
     anyhow::Context,
-    bstr::ByteSlice,
-    lexopt::Arg,
+    lexopt::{Arg, ValueExt},
     // See README for why we use regex-automata instead of regex.
-    regex_automata::{meta::Regex, Input},
+    regex_automata::{meta::Regex, util::iter::Searcher, Anchored, Input},
 };
 
+/// The cargo features enabled for the regex engine in Cargo.toml. This is
+/// reported by '--metadata' and should be kept in sync with Cargo.toml.
+const FEATURES: &[&str] = &["default", "logging", "perf-dfa-full"];
+
 fn main() -> anyhow::Result<()> {
     env_logger::init();
 
     let mut p = lexopt::Parser::from_env();
-    let (mut quiet, mut version) = (false, false);
+    let (mut quiet, mut version, mut validate) = (false, false, false);
+    let mut metadata = false;
+    let mut tuning = Tuning::default();
     while let Some(arg) = p.next()? {
         match arg {
             Arg::Short('h') | Arg::Long("help") => {
-                anyhow::bail!("main [--version | --quiet]")
+                anyhow::bail!(
+                    "main [--version | --metadata | --quiet | --validate] \
+                     [--dfa-size-limit <size>] [--no-hybrid]"
+                )
             }
             Arg::Short('q') | Arg::Long("quiet") => {
                 quiet = true;
@@ -24,63 +32,220 @@ fn main() -> anyhow::Result<()> {
             Arg::Long("version") => {
                 version = true;
             }
+            Arg::Long("metadata") => {
+                metadata = true;
+            }
+            Arg::Long("validate") => {
+                validate = true;
+            }
+            Arg::Long("dfa-size-limit") => {
+                let size = p.value()?.string()?;
+                let size = klv::parse_size(&size).with_context(|| {
+                    format!("invalid --dfa-size-limit '{}'", size)
+                })?;
+                tuning.dfa_size_limit = Some(size);
+            }
+            Arg::Long("no-hybrid") => {
+                tuning.hybrid = false;
+            }
             _ => return Err(arg.unexpected().into()),
         }
     }
+    if metadata {
+        writeln!(std::io::stdout(), "{}", timer::metadata(FEATURES))?;
+        return Ok(());
+    }
     if version {
         writeln!(std::io::stdout(), "{}", env!("CARGO_PKG_VERSION"))?;
         return Ok(());
     }
+    if validate {
+        return timer::validate(|b| run(b, &tuning));
+    }
     let b = klv::Benchmark::read(std::io::stdin())
         .context("failed to read KLV data from <stdin>")?;
-    let samples = match b.model.as_str() {
-        "compile" => model_compile(&b)?,
-        "count" => model_count(&b, &compile(&b)?)?,
-        "count-spans" => model_count_spans(&b, &compile(&b)?)?,
-        "count-captures" => model_count_captures(&b, &compile(&b)?)?,
-        "grep" => model_grep(&b, &compile(&b)?)?,
-        "grep-captures" => model_grep_captures(&b, &compile(&b)?)?,
-        "regex-redux" => model_regex_redux(&b)?,
-        _ => anyhow::bail!("unrecognized benchmark model '{}'", b.model),
-    };
+    let samples = run(&b, &tuning)?;
     if !quiet {
         let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "# warmup_iters={}", samples.warmup_iters)?;
+        if samples.partial {
+            writeln!(stdout, "# partial=true")?;
+        }
         for s in samples.iter() {
-            writeln!(stdout, "{},{}", s.duration.as_nanos(), s.count)?;
+            writeln!(stdout, "{}", s)?;
         }
     }
     Ok(())
 }
 
-fn model_compile(b: &klv::Benchmark) -> anyhow::Result<Vec<timer::Sample>> {
+/// Optional configuration of the regex engine, given by flags on the command
+/// line. Benchmark definitions can pass these flags via the 'args' of an
+/// engine reference in order to measure the same engine under different
+/// configurations.
+#[derive(Clone, Debug)]
+struct Tuning {
+    /// The capacity of the lazy DFA's cache, in bytes. This is what the
+    /// 'regex' crate calls the DFA size limit. When absent, the default is
+    /// used.
+    dfa_size_limit: Option<usize>,
+    /// Whether the lazy DFA is enabled.
+    hybrid: bool,
+}
+
+impl Default for Tuning {
+    fn default() -> Tuning {
+        Tuning { dfa_size_limit: None, hybrid: true }
+    }
+}
+
+/// Runs the given benchmark and returns its samples.
+fn run(b: &klv::Benchmark, tuning: &Tuning) -> anyhow::Result<timer::Samples> {
+    match b.model.as_str() {
+        "anchored-count" => model_anchored_count(b, &compile(b, tuning)?),
+        "compile" => model_compile(b, tuning),
+        "count" => model_count(b, &compile(b, tuning)?),
+        "count-spans" => model_count_spans(b, &compile(b, tuning)?),
+        "count-offsets" => model_count_offsets(b, &compile(b, tuning)?),
+        "count-captures" => model_count_captures(b, &compile(b, tuning)?),
+        "count-cold" => model_count_cold(b, &compile(b, tuning)?),
+        "count-empty" => model_count_empty(b, &compile(b, tuning)?),
+        "first-match" => model_first_match(b, &compile(b, tuning)?),
+        "count-per-pattern" => {
+            model_count_per_pattern(b, &compile(b, tuning)?)
+        }
+        // 'model_grep' already uses 'is_match', which is the cheapest way to
+        // ask whether a line matches. So 'grep-fast' is the same as 'grep'.
+        "grep" | "grep-fast" => model_grep(b, &compile(b, tuning)?),
+        "grep-captures" => model_grep_captures(b, &compile(b, tuning)?),
+        "grep-max" => model_grep_max(b, &compile(b, tuning)?),
+        "regex-redux" => model_regex_redux(b, tuning),
+        "size" => model_size(b, tuning),
+        _ => anyhow::bail!("unrecognized benchmark model '{}'", b.model),
+    }
+}
+
+fn model_anchored_count(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
-    timer::run_and_count(
+    let mut input = Input::new(haystack).anchored(Anchored::Yes);
+    timer::run(b, || {
+        let (mut count, mut at) = (0, 0);
+        while at < haystack.len() {
+            input.set_start(at);
+            at = match re.search(&input) {
+                None => at + 1,
+                Some(m) => {
+                    count += 1;
+                    // Always make progress, even on an empty match.
+                    m.end().max(at + 1)
+                }
+            };
+        }
+        Ok(count)
+    })
+}
+
+fn model_compile(
+    b: &klv::Benchmark,
+    tuning: &Tuning,
+) -> anyhow::Result<timer::Samples> {
+    let haystack = &*b.haystack;
+    timer::run_and_count_with_size(
         b,
         |re: Regex| Ok(re.find_iter(haystack).count()),
-        || compile(b),
+        |re: &Regex| Ok(re.memory_usage()),
+        || compile(b, tuning),
+    )
+}
+
+fn model_size(
+    b: &klv::Benchmark,
+    tuning: &Tuning,
+) -> anyhow::Result<timer::Samples> {
+    timer::run_and_size(
+        b,
+        |re: &Regex| Ok(re.memory_usage()),
+        || compile(b, tuning),
     )
 }
 
 fn model_count(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
+    // When the benchmark has a search window, we give it to the regex engine
+    // instead of slicing the haystack. This way, look-around assertions at
+    // the edges of the window can see the bytes outside of it.
+    let input = Input::new(&*b.haystack).span(b.search_window()?);
+    timer::run(b, || Ok(re.find_iter(input.clone()).count()))
+}
+
+fn model_count_cold(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
-    timer::run(b, || Ok(re.find_iter(haystack).count()))
+    timer::run(b, || {
+        // 'find_iter' uses a cache from a pool inside the regex, which stays
+        // warm across iterations. A fresh cache on every iteration means the
+        // lazy DFA (and everything else) has to rebuild its state, just like
+        // the first search with a newly compiled regex.
+        let mut cache = re.create_cache();
+        let count = Searcher::new(Input::new(haystack))
+            .into_matches_iter(|input| Ok(re.search_with(&mut cache, input)))
+            .infallible()
+            .count();
+        Ok(count)
+    })
+}
+
+fn model_first_match(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    let haystack = &*b.haystack;
+    timer::run(b, || Ok(re.find(haystack).map_or(0, |m| m.end())))
+}
+
+fn model_count_per_pattern(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    // Each match contributes the square of its pattern index plus one. See
+    // the description of the 'count-per-pattern' model in MODELS.md.
+    let haystack = &*b.haystack;
+    timer::run(b, || {
+        Ok(re
+            .find_iter(haystack)
+            .map(|m| (m.pattern().as_usize() + 1).pow(2))
+            .sum())
+    })
 }
 
 fn model_count_spans(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run(b, || Ok(re.find_iter(haystack).map(|m| m.len()).sum()))
 }
 
+fn model_count_offsets(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    let haystack = &*b.haystack;
+    timer::run(b, || {
+        Ok(re.find_iter(haystack).map(|m| m.start() + m.end()).sum())
+    })
+}
+
 fn model_count_captures(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let mut input = Input::new(&*b.haystack);
     let mut caps = re.create_captures();
     timer::run(b, || {
@@ -103,14 +268,25 @@ fn model_count_captures(
     })
 }
 
+fn model_count_empty(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    // The iterator skips any empty match that begins where the previous
+    // match ended. And since we disable 'utf8_empty' (just like the regex
+    // crate's 'bytes::Regex' does), empty matches may split a codepoint.
+    let haystack = &*b.haystack;
+    timer::run(b, || Ok(re.find_iter(haystack).count()))
+}
+
 fn model_grep(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     timer::run(b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             if re.is_match(line) {
                 count += 1;
             }
@@ -122,12 +298,12 @@ fn model_grep(
 fn model_grep_captures(
     b: &klv::Benchmark,
     re: &Regex,
-) -> anyhow::Result<Vec<timer::Sample>> {
+) -> anyhow::Result<timer::Samples> {
     let haystack = &*b.haystack;
     let mut caps = re.create_captures();
     timer::run(b, || {
         let mut count = 0;
-        for line in haystack.lines() {
+        for line in lines::bytes(haystack) {
             let mut input = Input::new(line);
             while let Some(m) = {
                 re.search_captures(&input, &mut caps);
@@ -147,12 +323,28 @@ fn model_grep_captures(
     })
 }
 
+fn model_grep_max(
+    b: &klv::Benchmark,
+    re: &Regex,
+) -> anyhow::Result<timer::Samples> {
+    let haystack = &*b.haystack;
+    let max = b.max_count_per_line();
+    timer::run(b, || {
+        let mut count = 0;
+        for line in lines::bytes(haystack) {
+            count += re.find_iter(line).take(max).count();
+        }
+        Ok(count)
+    })
+}
+
 fn model_regex_redux(
     b: &klv::Benchmark,
-) -> anyhow::Result<Vec<timer::Sample>> {
+    tuning: &Tuning,
+) -> anyhow::Result<timer::Samples> {
     let haystack = b.haystack_str()?;
     let compile = |pattern: &str| -> anyhow::Result<regexredux::RegexFn> {
-        let re = compile_pattern(b, &[pattern])?;
+        let re = compile_pattern(b, tuning, &[pattern])?;
         let find = move |h: &str| {
             Ok(re.find(h.as_bytes()).map(|m| (m.start(), m.end())))
         };
@@ -161,19 +353,61 @@ fn model_regex_redux(
     timer::run(b, || regexredux::generic(haystack, compile))
 }
 
-fn compile(b: &klv::Benchmark) -> anyhow::Result<Regex> {
-    compile_pattern(b, &b.regex.patterns)
+fn compile(b: &klv::Benchmark, tuning: &Tuning) -> anyhow::Result<Regex> {
+    match b.regex.binary_patterns {
+        None => compile_pattern(b, tuning, &b.regex.patterns),
+        Some(ref patterns) => {
+            // The regex crate only accepts patterns that are valid UTF-8, but
+            // with Unicode mode disabled, '\xNN' matches the raw byte NN. So
+            // we can represent any pattern by escaping the bytes that aren't
+            // valid UTF-8.
+            anyhow::ensure!(
+                !b.regex.unicode,
+                "patterns that aren't valid UTF-8 require 'unicode = false'",
+            );
+            let patterns: Vec<String> =
+                patterns.iter().map(|p| escape_invalid_utf8(p)).collect();
+            compile_pattern(b, tuning, &patterns)
+        }
+    }
+}
+
+/// Returns the given pattern as a string, where every byte that isn't part of
+/// valid UTF-8 is replaced by a '\xNN' escape.
+fn escape_invalid_utf8(pattern: &[u8]) -> String {
+    let mut escaped = String::new();
+    for chunk in pattern.utf8_chunks() {
+        escaped.push_str(chunk.valid());
+        for &b in chunk.invalid() {
+            escaped.push_str(&format!("\\x{:02X}", b));
+        }
+    }
+    escaped
 }
 
 fn compile_pattern<P: AsRef<str>>(
     b: &klv::Benchmark,
+    tuning: &Tuning,
     patterns: &[P],
 ) -> anyhow::Result<Regex> {
-    let config = Regex::config()
+    let nfa_size_limit =
+        b.param_size("nfa-size-limit")?.unwrap_or((1 << 20) * 100);
+    let mut config = Regex::config()
         // Disabling UTF-8 here just means that zero-width matches that split
         // a codepoint are allowed.
         .utf8_empty(false)
-        .nfa_size_limit(Some((1 << 20) * 100));
+        .nfa_size_limit(Some(nfa_size_limit))
+        .hybrid(tuning.hybrid);
+    if let Some(limit) = b.param_size("dfa-size-limit")? {
+        config = config.dfa_size_limit(Some(limit));
+    }
+    // The '--dfa-size-limit' flag takes precedence over the parameter, since
+    // it's what distinguishes one engine configuration from another.
+    let hybrid_cache_capacity =
+        tuning.dfa_size_limit.or(b.param_size("hybrid-cache-capacity")?);
+    if let Some(limit) = hybrid_cache_capacity {
+        config = config.hybrid_cache_capacity(limit);
+    }
     let syntax = regex_automata::util::syntax::Config::new()
         // Disabling UTF-8 just makes it possible to build regexes that won't
         // necessarily match UTF-8. Whether Unicode is actually usable or not
@@ -183,7 +417,9 @@ fn compile_pattern<P: AsRef<str>>(
         // of regex::Regex.
         .utf8(false)
         .unicode(b.regex.unicode)
-        .case_insensitive(b.regex.case_insensitive);
+        .case_insensitive(b.regex.case_insensitive)
+        .multi_line(b.regex.multiline)
+        .dot_matches_new_line(b.regex.dot_all);
 
     let re = Regex::builder()
         .configure(config)
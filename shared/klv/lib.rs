@@ -1,25 +1,149 @@
 use std::{
     cmp::min,
+    collections::BTreeMap,
     io::{Read, Write},
+    ops::Range,
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use {anyhow::Context, bstr::ByteSlice};
 
+/// The version of the KLV protocol written, and understood, by this library.
+///
+/// This should be incremented whenever a new key is added to the protocol.
+/// Runner programs use it to decide whether a key they don't recognize is an
+/// error (the key is bogus) or merely something they can ignore (the key was
+/// added by a newer version of rebar).
+pub const VERSION: u64 = 10;
+
 /// A single benchmark execution.
 ///
 /// This type knows how to be read from KLV format and written to KLV format.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Benchmark {
+    /// The version of the KLV protocol used to write this benchmark.
+    ///
+    /// When reading, this is `0` if the `klv-version` key was absent, which
+    /// corresponds to data written before the protocol was versioned. When
+    /// writing, this is ignored and `VERSION` is always written.
+    pub version: u64,
     pub name: String,
     pub model: String,
     pub regex: Regex,
     pub haystack: Arc<[u8]>,
+    /// The encoding of `haystack`. Regardless of the encoding, the haystack
+    /// is always given as raw bytes. Runner programs for regex engines that
+    /// search a non-UTF-8 encoding natively (like UTF-16) can use this to
+    /// skip transcoding when the haystack is already in that encoding.
+    ///
+    /// This defaults to UTF-8 and was added in version 5 of the protocol. It
+    /// is only written when it isn't UTF-8.
+    pub haystack_encoding: HaystackEncoding,
     pub max_iters: u64,
     pub max_warmup_iters: u64,
     pub max_time: Duration,
     pub max_warmup_time: Duration,
+    /// How often the result of a benchmark iteration should be verified. When
+    /// this is `N`, then the first iteration is verified and then every `N`th
+    /// iteration after it. Runner programs may skip computing the count for
+    /// iterations that aren't verified and report the most recently computed
+    /// count instead. This is useful for models like 'compile', where
+    /// computing the count can take much longer than the thing being
+    /// measured.
+    ///
+    /// This defaults to `1` (every iteration is verified) and was added in
+    /// version 2 of the protocol. It is only written when it isn't `1`.
+    pub verify_every: u64,
+    /// The number of times the benchmark should be executed for each timed
+    /// sample. When this is `N`, runner programs run the thing being measured
+    /// `N` times in a row, and report the total duration divided by `N` as
+    /// the sample's duration. The count reported is still the count of a
+    /// single execution, not the sum of all `N` executions.
+    ///
+    /// This is useful for benchmarks where a single execution is so fast
+    /// that the overhead of reading the clock dominates the measurement.
+    ///
+    /// This defaults to `1` and was added in version 3 of the protocol. It is
+    /// only written when it isn't `1`.
+    pub iter_batch: u64,
+    /// When present, runner programs should stop taking samples once this
+    /// much time has passed since they started, and report the samples they
+    /// have so far. Runner programs that stop early should say so with a
+    /// `# partial=true` header line.
+    ///
+    /// The harness sets this to a bit less than the time after which it
+    /// kills the runner program, so that a benchmark that runs longer than
+    /// expected still produces some samples instead of none. Since the
+    /// harness starts that clock as soon as it spawns the runner program,
+    /// the deadline includes setup (like compiling the regex) and not just
+    /// the time spent running the benchmark. See `Benchmark::started`.
+    ///
+    /// This was added in version 6 of the protocol. It is only written when
+    /// present.
+    pub soft_deadline: Option<Duration>,
+    /// When present, the count that every verified iteration (including
+    /// warmup iterations) is expected to report. Runner programs can use this
+    /// to fail on the first iteration with a wrong count, instead of the
+    /// harness only noticing once all samples have been reported. This lets
+    /// the error say which iteration went wrong.
+    ///
+    /// The harness only sets this when the benchmark verifies counts and the
+    /// count for the regex engine is known. Since the harness verifies counts
+    /// itself anyway, runner programs are free to ignore it.
+    ///
+    /// This was added in version 7 of the protocol. It is only written when
+    /// present.
+    pub expected_count: Option<u64>,
+    /// When present, the byte offset in `haystack` at which searches should
+    /// start. Unlike slicing the haystack, look-around assertions like `\b`
+    /// and look-behind at this offset should still see the bytes before it.
+    /// Use `Benchmark::search_window` to get the bounds of the search.
+    ///
+    /// This was added in version 8 of the protocol. It is only written when
+    /// present.
+    pub search_start: Option<usize>,
+    /// When present, the byte offset in `haystack` at which searches should
+    /// end. Like `search_start`, this is meant to be given to a regex engine's
+    /// positional search API rather than used to slice the haystack.
+    ///
+    /// This was added in version 8 of the protocol. It is only written when
+    /// present.
+    pub search_end: Option<usize>,
+    /// When present, the maximum number of matches to count in each line for
+    /// the 'grep-max' model. Searching a line stops once this many matches
+    /// have been found in it, like the '--max-count' flag of grep tools. When
+    /// absent, there is no limit. Use `Benchmark::max_count_per_line` to get
+    /// the limit.
+    ///
+    /// This was added in version 10 of the protocol. It is only written when
+    /// present.
+    pub max_count: Option<u64>,
+    /// Free-form parameters given by the benchmark definition, keyed by
+    /// name. Runner programs consult the parameters they understand (e.g.,
+    /// a size limit for the regex engine) and ignore the rest. Use
+    /// `Benchmark::param_size` to parse a parameter that is a size.
+    ///
+    /// Each parameter is sent as a `param` key with a value of the form
+    /// `name=value`. This was added in version 9 of the protocol. It is only
+    /// written when non-empty.
+    pub params: BTreeMap<String, String>,
+    /// The keys that were read but not recognized. This can only be non-empty
+    /// when `version` is newer than `VERSION`, since unrecognized keys are
+    /// otherwise an error. Runner programs should generally print these to
+    /// stderr as warnings, since it likely means the runner program is older
+    /// than the rebar that is running it.
+    ///
+    /// This is ignored when writing.
+    pub ignored: Vec<IgnoredKey>,
+    /// When this benchmark started being read by `Benchmark::read`. Runner
+    /// programs read the benchmark first thing, so this is a good stand-in
+    /// for when the runner program started. The soft deadline is measured
+    /// from here. When absent, it's measured from when the benchmark starts
+    /// running instead.
+    ///
+    /// This isn't part of the protocol and is ignored when writing.
+    pub started: Option<Instant>,
 }
 
 impl Benchmark {
@@ -32,30 +156,47 @@ impl Benchmark {
     /// configuration will have an empty name and model, which is almost
     /// certainly going to lead to an error when determining how to actually
     /// execute the benchmark.
+    ///
+    /// If present, the `klv-version` key must come first. When it indicates
+    /// a version newer than `VERSION`, unrecognized keys are collected into
+    /// `Benchmark::ignored` instead of resulting in an error.
     pub fn read<R: Read>(mut rdr: R) -> anyhow::Result<Benchmark> {
+        let started = Instant::now();
         // We just slurp everything into memory. While haystacks can sometimes
         // get a little big, it's almost never more than a few MB. We can spare
         // the memory in exchange for simplicity. Besides, the rebar benchmark
         // runner already holds all haystacks in memory anyway.
         let mut buf = vec![];
         rdr.read_to_end(&mut buf)
-            .with_context(|| format!("failed to read KLV data into memory"))?;
+            .context("failed to read KLV data into memory")?;
 
         let mut bench = Benchmark {
-            name: String::default(),
-            model: String::default(),
-            regex: Regex::default(),
-            haystack: Arc::from(vec![]),
-            max_iters: u64::default(),
-            max_warmup_iters: u64::default(),
-            max_time: Duration::default(),
-            max_warmup_time: Duration::default(),
+            version: 0,
+            started: Some(started),
+            ..Benchmark::default()
         };
+        // Patterns are collected as bytes, since they may not be valid UTF-8.
+        // See 'Regex::set_patterns_bytes'.
+        let mut patterns: Vec<Vec<u8>> = vec![];
         let mut buf = buf.as_slice();
+        let mut first = true;
+        let mut offset = 0;
         while !buf.is_empty() {
-            let (klv, nread) = OneKLV::read(buf)?;
+            let (klv, nread) = OneKLV::read(buf, offset)?;
             buf = &buf[nread..];
+            offset += nread;
+            let is_first = std::mem::replace(&mut first, false);
             match klv.key.as_str() {
+                "klv-version" => {
+                    // The version has to come first, since it determines how
+                    // we treat all of the keys that follow it.
+                    anyhow::ensure!(
+                        is_first,
+                        "'klv-version' must be the first KLV key, \
+                         but it appeared after other keys",
+                    );
+                    bench.version = klv.to_u64()?;
+                }
                 "name" => {
                     bench.name = klv.to_str()?.to_string();
                 }
@@ -63,7 +204,7 @@ impl Benchmark {
                     bench.model = klv.to_str()?.to_string();
                 }
                 "pattern" => {
-                    bench.regex.patterns.push(klv.to_str()?.to_string());
+                    patterns.push(klv.value.to_vec());
                 }
                 "case-insensitive" => {
                     bench.regex.case_insensitive = klv.to_bool()?;
@@ -71,9 +212,18 @@ impl Benchmark {
                 "unicode" => {
                     bench.regex.unicode = klv.to_bool()?;
                 }
+                "multiline" => {
+                    bench.regex.multiline = klv.to_bool()?;
+                }
+                "dot-all" => {
+                    bench.regex.dot_all = klv.to_bool()?;
+                }
                 "haystack" => {
                     bench.haystack = klv.value;
                 }
+                "haystack-encoding" => {
+                    bench.haystack_encoding = klv.to_str()?.parse()?;
+                }
                 "max-iters" => {
                     bench.max_iters = klv.to_u64()?;
                 }
@@ -86,9 +236,74 @@ impl Benchmark {
                 "max-warmup-time" => {
                     bench.max_warmup_time = klv.to_duration()?;
                 }
-                _ => anyhow::bail!("unrecognized KLV key '{}'", klv.key),
+                "verify-every" => {
+                    bench.verify_every = klv.to_u64()?;
+                    anyhow::ensure!(
+                        bench.verify_every >= 1,
+                        "'verify-every' must be at least 1",
+                    );
+                }
+                "iter-batch" => {
+                    bench.iter_batch = klv.to_u64()?;
+                    anyhow::ensure!(
+                        bench.iter_batch >= 1,
+                        "'iter-batch' must be at least 1",
+                    );
+                }
+                "soft-deadline" => {
+                    bench.soft_deadline = Some(klv.to_duration()?);
+                }
+                "expected-count" => {
+                    bench.expected_count = Some(klv.to_u64()?);
+                }
+                "search-start" => {
+                    bench.search_start = Some(klv.to_usize()?);
+                }
+                "search-end" => {
+                    bench.search_end = Some(klv.to_usize()?);
+                }
+                "max-count" => {
+                    let max = klv.to_u64()?;
+                    anyhow::ensure!(
+                        max >= 1,
+                        "'max-count' must be at least 1"
+                    );
+                    bench.max_count = Some(max);
+                }
+                "param" => {
+                    let param = klv.to_str()?;
+                    let Some((name, value)) = param.split_once('=') else {
+                        anyhow::bail!(
+                            "expected 'name=value' for key 'param', \
+                             but got '{}'",
+                            param,
+                        )
+                    };
+                    let old = bench
+                        .params
+                        .insert(name.to_string(), value.to_string());
+                    anyhow::ensure!(
+                        old.is_none(),
+                        "duplicate parameter '{}' for key 'param'",
+                        name,
+                    );
+                }
+                _ if bench.version > VERSION => {
+                    bench.ignored.push(IgnoredKey {
+                        key: klv.key,
+                        len: klv.value.len(),
+                    });
+                }
+                _ => anyhow::bail!(
+                    "unrecognized KLV key '{}' (KLV protocol version of \
+                     input is {}, and this runner supports up to version {})",
+                    klv.key,
+                    bench.version,
+                    VERSION,
+                ),
             }
         }
+        bench.regex.set_patterns_bytes(patterns);
         Ok(bench)
     }
 
@@ -96,6 +311,11 @@ impl Benchmark {
     /// Any errors returned by the given writer are returned to the caller.
     pub fn write<W: Write>(&self, wtr: W) -> anyhow::Result<()> {
         fn imp<W: Write>(b: &Benchmark, mut wtr: W) -> anyhow::Result<()> {
+            // The version always comes first. See 'Benchmark::read'.
+            OneKLV::new("klv-version", &VERSION.to_string())
+                .write(&mut wtr)
+                .context("failed to write 'klv-version'")?;
+
             OneKLV::new("name", &b.name)
                 .write(&mut wtr)
                 .context("failed to write 'name'")?;
@@ -115,6 +335,21 @@ impl Benchmark {
                 .write(&mut wtr)
                 .context("failed to write 'unicode'")?;
 
+            // These are only written when enabled, since most runner programs
+            // don't support them and reject keys they don't know. That way,
+            // those runner programs only fail when they would otherwise
+            // silently ignore an option.
+            if b.regex.multiline {
+                OneKLV::new("multiline", "true")
+                    .write(&mut wtr)
+                    .context("failed to write 'multiline'")?;
+            }
+            if b.regex.dot_all {
+                OneKLV::new("dot-all", "true")
+                    .write(&mut wtr)
+                    .context("failed to write 'dot-all'")?;
+            }
+
             OneKLV::new("max-iters", &b.max_iters.to_string())
                 .write(&mut wtr)
                 .context("failed to write 'max-iters'")?;
@@ -134,14 +369,66 @@ impl Benchmark {
             .write(&mut wtr)
             .context("failed to write 'max-warmup-time'")?;
 
+            // We only write this when it's non-default, since most runner
+            // programs don't support it and reject keys they don't know.
+            if b.verify_every != 1 {
+                OneKLV::new("verify-every", &b.verify_every.to_string())
+                    .write(&mut wtr)
+                    .context("failed to write 'verify-every'")?;
+            }
+            if b.iter_batch != 1 {
+                OneKLV::new("iter-batch", &b.iter_batch.to_string())
+                    .write(&mut wtr)
+                    .context("failed to write 'iter-batch'")?;
+            }
+            if let Some(deadline) = b.soft_deadline {
+                OneKLV::new("soft-deadline", &deadline.as_nanos().to_string())
+                    .write(&mut wtr)
+                    .context("failed to write 'soft-deadline'")?;
+            }
+            if let Some(count) = b.expected_count {
+                OneKLV::new("expected-count", &count.to_string())
+                    .write(&mut wtr)
+                    .context("failed to write 'expected-count'")?;
+            }
+            if let Some(start) = b.search_start {
+                OneKLV::new("search-start", &start.to_string())
+                    .write(&mut wtr)
+                    .context("failed to write 'search-start'")?;
+            }
+            if let Some(end) = b.search_end {
+                OneKLV::new("search-end", &end.to_string())
+                    .write(&mut wtr)
+                    .context("failed to write 'search-end'")?;
+            }
+            if let Some(max) = b.max_count {
+                OneKLV::new("max-count", &max.to_string())
+                    .write(&mut wtr)
+                    .context("failed to write 'max-count'")?;
+            }
+            if b.haystack_encoding != HaystackEncoding::Utf8 {
+                OneKLV::new("haystack-encoding", b.haystack_encoding.as_str())
+                    .write(&mut wtr)
+                    .context("failed to write 'haystack-encoding'")?;
+            }
+            for (name, value) in b.params.iter() {
+                OneKLV::new("param", &format!("{}={}", name, value))
+                    .write(&mut wtr)
+                    .with_context(|| {
+                        format!("failed to write parameter '{}'", name)
+                    })?;
+            }
+
             // We write the patterns and haystack last because they can be big.
             // If there are things after it, they can be easy to miss. This is
             // also why we write patterns second to last, since there can be
             // many patterns. (But usually there's only one.)
-            for (i, p) in b.regex.patterns.iter().enumerate() {
-                OneKLV::new("pattern", p).write(&mut wtr).with_context(
-                    || format!("failed to write pattern {}", i),
-                )?;
+            for (i, p) in b.regex.patterns_bytes().into_iter().enumerate() {
+                OneKLV { key: "pattern".to_string(), value: Arc::from(p) }
+                    .write(&mut wtr)
+                    .with_context(|| {
+                        format!("failed to write pattern {}", i)
+                    })?;
             }
             OneKLV {
                 key: "haystack".to_string(),
@@ -158,7 +445,8 @@ impl Benchmark {
     }
 
     /// Return the haystack in this benchmark as a UTF-8 encoded string. This
-    /// will return an error if the haystack is invalid UTF-8.
+    /// will return an error if the haystack is invalid UTF-8, or if the
+    /// haystack's encoding isn't UTF-8.
     ///
     /// Most benchmarks use a haystack that is valid UTF-8, but some do not.
     /// Some regex engines (like 'regress', at time of writing) do not provide
@@ -167,34 +455,213 @@ impl Benchmark {
     /// is valid UTF-8. Generally speaking, this means those engines should not
     /// be run at all for benchmarks using invalid UTF-8 in their haystacks.
     pub fn haystack_str(&self) -> anyhow::Result<&str> {
+        anyhow::ensure!(
+            self.haystack_encoding == HaystackEncoding::Utf8,
+            "haystack is encoded as {}, but this regex engine requires UTF-8",
+            self.haystack_encoding.as_str(),
+        );
         self.haystack.to_str().context("failed to decode haystack as UTF-8")
     }
+
+    /// Returns the range of `haystack` that searches should be limited to,
+    /// according to `search_start` and `search_end`. When neither is present,
+    /// this is the entire haystack.
+    ///
+    /// This returns an error if the range is out of bounds for the haystack
+    /// or if its start is greater than its end.
+    pub fn search_window(&self) -> anyhow::Result<Range<usize>> {
+        let start = self.search_start.unwrap_or(0);
+        let end = self.search_end.unwrap_or(self.haystack.len());
+        anyhow::ensure!(
+            start <= end && end <= self.haystack.len(),
+            "invalid search window {}..{} for haystack of length {}",
+            start,
+            end,
+            self.haystack.len(),
+        );
+        Ok(start..end)
+    }
+
+    /// Returns the maximum number of matches to count in each line for the
+    /// 'grep-max' model. When `max_count` is absent, this returns
+    /// `usize::MAX`, which is effectively unlimited.
+    pub fn max_count_per_line(&self) -> usize {
+        self.max_count.map_or(usize::MAX, |max| {
+            usize::try_from(max).unwrap_or(usize::MAX)
+        })
+    }
+
+    /// Returns the value of the parameter with the given name, parsed as a
+    /// size in bytes. See `parse_size` for the format. If the benchmark
+    /// doesn't have the parameter, then this returns `None`.
+    ///
+    /// This returns an error if the value isn't a valid size.
+    pub fn param_size(&self, name: &str) -> anyhow::Result<Option<usize>> {
+        let Some(value) = self.params.get(name) else { return Ok(None) };
+        let size = parse_size(value).with_context(|| {
+            format!("invalid size '{}' for parameter '{}'", value, name)
+        })?;
+        Ok(Some(size))
+    }
+
+    /// Returns a tiny benchmark that runner programs can use to check that
+    /// they work at all, without needing to be given a benchmark on stdin.
+    ///
+    /// It uses the 'count' model with the pattern `a+b` and a short ASCII
+    /// haystack. Every regex engine should report `VALIDATION_COUNT` matches
+    /// for it. It only runs for a single iteration with no warmup.
+    ///
+    /// This is what runner programs execute when given the `--validate`
+    /// flag.
+    pub fn validation() -> Benchmark {
+        Benchmark {
+            name: "validate".to_string(),
+            model: "count".to_string(),
+            regex: Regex {
+                patterns: vec!["a+b".to_string()],
+                ..Regex::default()
+            },
+            haystack: Arc::from(&b"ab xaab b aaab ba"[..]),
+            max_iters: 1,
+            max_time: Duration::from_secs(1),
+            ..Benchmark::default()
+        }
+    }
+}
+
+/// The number of matches every regex engine should report for the benchmark
+/// returned by `Benchmark::validation`.
+pub const VALIDATION_COUNT: u64 = 3;
+
+/// Parses a size in bytes, with an optional `K`, `M` or `G` suffix (in powers
+/// of 1024). For example, `200M` is `200 * (1 << 20)` bytes.
+pub fn parse_size(size: &str) -> anyhow::Result<usize> {
+    let (digits, multiplier) = match size.as_bytes().last() {
+        Some(b'K') => (&size[..size.len() - 1], 1 << 10),
+        Some(b'M') => (&size[..size.len() - 1], 1 << 20),
+        Some(b'G') => (&size[..size.len() - 1], 1 << 30),
+        _ => (size, 1),
+    };
+    let n: usize = digits.parse()?;
+    n.checked_mul(multiplier).context("size is too big")
 }
 
 // We do this manually because Arc<[u8]> doesn't have a Default impl...
 impl Default for Benchmark {
     fn default() -> Benchmark {
         Benchmark {
+            version: VERSION,
             name: String::default(),
             model: String::default(),
             regex: Regex::default(),
             haystack: Arc::from(vec![]),
+            haystack_encoding: HaystackEncoding::Utf8,
             max_iters: u64::default(),
             max_warmup_iters: u64::default(),
             max_time: Duration::default(),
             max_warmup_time: Duration::default(),
+            verify_every: 1,
+            iter_batch: 1,
+            soft_deadline: None,
+            expected_count: None,
+            search_start: None,
+            search_end: None,
+            max_count: None,
+            params: BTreeMap::new(),
+            ignored: vec![],
+            started: None,
+        }
+    }
+}
+
+/// The encoding of a benchmark's haystack.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum HaystackEncoding {
+    /// UTF-8, which is the default. Note that haystacks in this encoding
+    /// aren't necessarily valid UTF-8, since some benchmarks specifically
+    /// test how regex engines deal with invalid UTF-8.
+    #[default]
+    Utf8,
+    /// UTF-16, with each code unit in little endian byte order. Haystacks in
+    /// this encoding are always valid UTF-16.
+    Utf16le,
+    /// Latin-1 (ISO 8859-1), where every byte is the codepoint with the same
+    /// value.
+    Latin1,
+}
+
+impl HaystackEncoding {
+    /// Returns the name of this encoding as it's written in KLV data.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            HaystackEncoding::Utf8 => "utf-8",
+            HaystackEncoding::Utf16le => "utf-16le",
+            HaystackEncoding::Latin1 => "latin-1",
         }
     }
 }
 
+impl std::str::FromStr for HaystackEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<HaystackEncoding> {
+        let enc = match s {
+            "utf-8" => HaystackEncoding::Utf8,
+            "utf-16le" => HaystackEncoding::Utf16le,
+            "latin-1" => HaystackEncoding::Latin1,
+            unknown => anyhow::bail!(
+                "unrecognized haystack encoding '{}', must be one of \
+                 utf-8, utf-16le or latin-1",
+                unknown,
+            ),
+        };
+        Ok(enc)
+    }
+}
+
+/// A key that was read from KLV data but ignored because it wasn't
+/// recognized and the data was written with a newer version of the KLV
+/// protocol than this library understands.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IgnoredKey {
+    /// The name of the key.
+    pub key: String,
+    /// The length, in bytes, of the ignored value.
+    pub len: usize,
+}
+
+impl std::fmt::Display for IgnoredKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "ignored unrecognized KLV key '{}' (value has {} bytes) \
+             from a newer KLV protocol version, this runner program \
+             may need to be updated",
+            self.key, self.len,
+        )
+    }
+}
+
 /// The configuration of zero or more regex patterns in a single benchmark.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct Regex {
     /// The patterns that should be compiled to a regular expression.
     ///
     /// Zero patterns is legal, although more regex engines expected exactly
     /// one. In which case, that regex engine should report an error.
+    ///
+    /// When any pattern isn't valid UTF-8, this is empty and all of the
+    /// patterns are in `binary_patterns` instead.
     pub patterns: Vec<String>,
+    /// The patterns, as raw bytes, when at least one of them isn't valid
+    /// UTF-8. Otherwise, this is `None`.
+    ///
+    /// Only regex engines that accept byte oriented patterns (and declare
+    /// the `binary-pattern` capability) are given patterns that aren't valid
+    /// UTF-8. Runner programs that support them should use `patterns_bytes`
+    /// or `one_bytes`. For all other runner programs, `one` reports an
+    /// error.
+    pub binary_patterns: Option<Vec<Vec<u8>>>,
     /// Whether the patterns should be compiled case insensitively.
     pub case_insensitive: bool,
     /// Whether the patterns should be compiled with Unicode mode enabled.
@@ -205,9 +672,37 @@ pub struct Regex {
     /// usually enables the use of things like \pL and makes things like .,
     /// [^a] and \w Unicode aware.
     pub unicode: bool,
+    /// Whether the patterns should be compiled with multi-line mode enabled,
+    /// i.e., where `^` and `$` match at the beginning and end of lines.
+    ///
+    /// This was added in version 4 of the protocol. It is only written when
+    /// it's enabled.
+    pub multiline: bool,
+    /// Whether the patterns should be compiled such that `.` matches any
+    /// character, including `\n`.
+    ///
+    /// This was added in version 4 of the protocol. It is only written when
+    /// it's enabled.
+    pub dot_all: bool,
 }
 
 impl Regex {
+    /// Returns an error if either `multiline` or `dot_all` is enabled.
+    ///
+    /// This is useful for regex engines that have no equivalent for these
+    /// options, so that they fail loudly instead of silently ignoring them.
+    pub fn ensure_no_multiline_or_dot_all(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            !self.multiline,
+            "regex engine does not support the 'multiline' option",
+        );
+        anyhow::ensure!(
+            !self.dot_all,
+            "regex engine does not support the 'dot-all' option",
+        );
+        Ok(())
+    }
+
     /// When the configuration contains exactly one pattern, then return that
     /// pattern. Otherwise, including when the number of patterns is zero,
     /// return an error.
@@ -215,6 +710,11 @@ impl Regex {
     /// This is useful to get a single pattern for regex engines that only
     /// support compiling one pattern.
     pub fn one(&self) -> anyhow::Result<&str> {
+        anyhow::ensure!(
+            self.binary_patterns.is_none(),
+            "regex engine only supports patterns that are valid UTF-8, \
+             but was given a pattern that isn't",
+        );
         anyhow::ensure!(
             self.patterns.len() == 1,
             "regex engine only supports one pattern at a time, \
@@ -223,6 +723,46 @@ impl Regex {
         );
         Ok(&self.patterns[0])
     }
+
+    /// Like `one`, but returns the pattern as bytes. Unlike `one`, this
+    /// succeeds even when the pattern isn't valid UTF-8.
+    pub fn one_bytes(&self) -> anyhow::Result<&[u8]> {
+        let patterns = self.patterns_bytes();
+        anyhow::ensure!(
+            patterns.len() == 1,
+            "regex engine only supports one pattern at a time, \
+             but was given {} patterns",
+            patterns.len(),
+        );
+        Ok(patterns[0])
+    }
+
+    /// Returns every pattern as a sequence of bytes, regardless of whether
+    /// they're valid UTF-8 or not.
+    pub fn patterns_bytes(&self) -> Vec<&[u8]> {
+        match self.binary_patterns {
+            Some(ref ps) => ps.iter().map(|p| p.as_slice()).collect(),
+            None => self.patterns.iter().map(|p| p.as_bytes()).collect(),
+        }
+    }
+
+    /// Sets the patterns of this configuration from the given sequence of
+    /// bytes. When every pattern is valid UTF-8, they're stored in `patterns`
+    /// and `binary_patterns` is `None`. Otherwise, `patterns` is empty and
+    /// they're stored in `binary_patterns`.
+    pub fn set_patterns_bytes(&mut self, patterns: Vec<Vec<u8>>) {
+        if patterns.iter().all(|p| p.to_str().is_ok()) {
+            // OK because we just checked that every pattern is valid UTF-8.
+            self.patterns = patterns
+                .into_iter()
+                .map(|p| String::from_utf8(p).unwrap())
+                .collect();
+            self.binary_patterns = None;
+        } else {
+            self.patterns = vec![];
+            self.binary_patterns = Some(patterns);
+        }
+    }
 }
 
 /// Represents a single key-length-value pair. It knows how to read and write
@@ -244,7 +784,10 @@ impl OneKLV {
     /// bytes. The slice given may contain more than a single KLV. Upon
     /// success, the second element of the tuple returned corresponds to the
     /// total number of bytes read from the slice.
-    fn read(bytes: &[u8]) -> anyhow::Result<(OneKLV, usize)> {
+    ///
+    /// `offset` is the position of the given slice in the full KLV data. It
+    /// is only used to report more helpful error messages.
+    fn read(bytes: &[u8], offset: usize) -> anyhow::Result<(OneKLV, usize)> {
         let mut nread = 0;
         let (key, bytes) = match bytes.split_once_str(":") {
             Some(x) => x,
@@ -283,9 +826,11 @@ impl OneKLV {
 
         anyhow::ensure!(
             bytes.len() >= len,
-            "got length of {} for key '{}', but only {} bytes remain",
+            "got length of {} for key '{}' at byte offset {}, \
+             but only {} bytes remain",
             len,
             key,
+            offset + nread,
             bytes.len(),
         );
         let value = bytes[..len].into();
@@ -293,12 +838,18 @@ impl OneKLV {
         nread += len;
 
         anyhow::ensure!(
-            bytes.len() >= 1,
-            "expected trailing '\\n' after value, but got EOF",
+            !bytes.is_empty(),
+            "expected trailing '\\n' after value for key '{}' \
+             at byte offset {}, but got EOF",
+            key,
+            offset + nread,
         );
         anyhow::ensure!(
             bytes[0] == b'\n',
-            "expected '\\n' after value, but got {:?}",
+            "expected '\\n' after value for key '{}' at byte offset {}, \
+             but got {:?}",
+            key,
+            offset + nread,
             bytes[0..1].as_bstr(),
         );
         nread += 1;
@@ -352,6 +903,14 @@ impl OneKLV {
         })
     }
 
+    /// Parse the value as an unsigned pointer-sized integer, otherwise return
+    /// an error.
+    fn to_usize(&self) -> anyhow::Result<usize> {
+        self.to_str()?.parse().with_context(|| {
+            format!("expected unsigned integer value for key '{}'", self.key)
+        })
+    }
+
     /// Parse the value as a duration in nanoseconds, otherwise return an
     /// error.
     fn to_duration(&self) -> anyhow::Result<Duration> {
@@ -372,3 +931,523 @@ impl std::fmt::Debug for OneKLV {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn read(data: &str) -> anyhow::Result<Benchmark> {
+        Benchmark::read(data.as_bytes())
+    }
+
+    #[test]
+    fn roundtrip() {
+        let b1 = Benchmark {
+            name: "foo/bar".to_string(),
+            model: "count".to_string(),
+            regex: Regex {
+                patterns: vec!["a".to_string(), "b:c".to_string()],
+                binary_patterns: None,
+                case_insensitive: true,
+                unicode: false,
+                multiline: true,
+                dot_all: false,
+            },
+            haystack: Arc::from(&b"abc\nxyz\n"[..]),
+            max_iters: 5,
+            max_warmup_iters: 2,
+            max_time: Duration::from_millis(3),
+            max_warmup_time: Duration::from_millis(1),
+            ..Benchmark::default()
+        };
+        let mut buf = vec![];
+        b1.write(&mut buf).unwrap();
+        // The version must always be written first.
+        let len = VERSION.to_string().len();
+        let want = format!("klv-version:{len}:{VERSION}\n");
+        assert!(buf.starts_with(want.as_bytes()));
+        // And 'verify-every' and 'iter-batch' are only written when they
+        // aren't the default.
+        assert!(!buf.contains_str("verify-every"));
+        assert!(!buf.contains_str("iter-batch"));
+        // Same for 'dot-all' and 'soft-deadline'.
+        assert!(!buf.contains_str("dot-all"));
+        assert!(!buf.contains_str("soft-deadline"));
+        assert!(!buf.contains_str("expected-count"));
+        assert!(!buf.contains_str("search-"));
+        assert!(!buf.contains_str("param"));
+
+        let b2 = Benchmark::read(&*buf).unwrap();
+        assert_eq!(VERSION, b2.version);
+        assert_eq!(b1.name, b2.name);
+        assert_eq!(b1.model, b2.model);
+        assert_eq!(b1.regex.patterns, b2.regex.patterns);
+        assert_eq!(b1.regex.case_insensitive, b2.regex.case_insensitive);
+        assert_eq!(b1.regex.unicode, b2.regex.unicode);
+        assert_eq!(b1.regex.multiline, b2.regex.multiline);
+        assert_eq!(b1.regex.dot_all, b2.regex.dot_all);
+        assert_eq!(b1.haystack, b2.haystack);
+        assert_eq!(b1.max_iters, b2.max_iters);
+        assert_eq!(b1.max_warmup_iters, b2.max_warmup_iters);
+        assert_eq!(b1.max_time, b2.max_time);
+        assert_eq!(b1.max_warmup_time, b2.max_warmup_time);
+        assert_eq!(1, b2.verify_every);
+        assert_eq!(1, b2.iter_batch);
+        assert_eq!(None, b2.soft_deadline);
+        assert_eq!(None, b2.expected_count);
+        assert_eq!(0..8, b2.search_window().unwrap());
+        assert!(b2.params.is_empty());
+        assert!(b2.ignored.is_empty());
+    }
+
+    #[test]
+    fn verify_every() {
+        let b1 = Benchmark { verify_every: 10, ..Benchmark::default() };
+        let mut buf = vec![];
+        b1.write(&mut buf).unwrap();
+        let b2 = Benchmark::read(&*buf).unwrap();
+        assert_eq!(10, b2.verify_every);
+
+        let err = read("klv-version:1:2\nverify-every:1:0\n").unwrap_err();
+        assert!(err.to_string().contains("at least 1"), "{}", err);
+    }
+
+    #[test]
+    fn iter_batch() {
+        let b1 = Benchmark { iter_batch: 50, ..Benchmark::default() };
+        let mut buf = vec![];
+        b1.write(&mut buf).unwrap();
+        let b2 = Benchmark::read(&*buf).unwrap();
+        assert_eq!(50, b2.iter_batch);
+
+        let err = read("klv-version:1:3\niter-batch:1:0\n").unwrap_err();
+        assert!(err.to_string().contains("at least 1"), "{}", err);
+    }
+
+    #[test]
+    fn soft_deadline() {
+        let b1 = Benchmark {
+            soft_deadline: Some(Duration::from_millis(1500)),
+            ..Benchmark::default()
+        };
+        let mut buf = vec![];
+        b1.write(&mut buf).unwrap();
+        assert!(buf.contains_str("soft-deadline:10:1500000000\n"));
+        let b2 = Benchmark::read(&*buf).unwrap();
+        assert_eq!(Some(Duration::from_millis(1500)), b2.soft_deadline);
+    }
+
+    #[test]
+    fn expected_count() {
+        let b1 =
+            Benchmark { expected_count: Some(42), ..Benchmark::default() };
+        let mut buf = vec![];
+        b1.write(&mut buf).unwrap();
+        assert!(buf.contains_str("expected-count:2:42\n"));
+        let b2 = Benchmark::read(&*buf).unwrap();
+        assert_eq!(Some(42), b2.expected_count);
+
+        let err = read("klv-version:1:7\nexpected-count:2:-1\n");
+        assert!(err.unwrap_err().to_string().contains("'expected-count'"));
+    }
+
+    #[test]
+    fn search_window() {
+        let b1 = Benchmark {
+            haystack: Arc::from(&b"foobar"[..]),
+            search_start: Some(3),
+            ..Benchmark::default()
+        };
+        let mut buf = vec![];
+        b1.write(&mut buf).unwrap();
+        assert!(buf.contains_str("search-start:1:3\n"));
+        assert!(!buf.contains_str("search-end"));
+        let b2 = Benchmark::read(&*buf).unwrap();
+        assert_eq!(Some(3), b2.search_start);
+        assert_eq!(None, b2.search_end);
+        assert_eq!(3..6, b2.search_window().unwrap());
+
+        let b = Benchmark { search_end: Some(2), ..b1.clone() };
+        assert!(b.search_window().is_err());
+        let b = Benchmark { search_end: Some(7), ..b1.clone() };
+        assert!(b.search_window().is_err());
+        let b = Benchmark { search_start: Some(6), ..b1 };
+        assert_eq!(6..6, b.search_window().unwrap());
+    }
+
+    #[test]
+    fn max_count() {
+        let b1 = Benchmark::default();
+        let mut buf = vec![];
+        b1.write(&mut buf).unwrap();
+        assert!(!buf.contains_str("max-count"));
+        let b2 = Benchmark::read(&*buf).unwrap();
+        assert_eq!(None, b2.max_count);
+        assert_eq!(usize::MAX, b2.max_count_per_line());
+
+        let b1 = Benchmark { max_count: Some(3), ..Benchmark::default() };
+        let mut buf = vec![];
+        b1.write(&mut buf).unwrap();
+        assert!(buf.contains_str("max-count:1:3\n"));
+        let b2 = Benchmark::read(&*buf).unwrap();
+        assert_eq!(Some(3), b2.max_count);
+        assert_eq!(3, b2.max_count_per_line());
+
+        let err = read("klv-version:2:10\nmax-count:1:0\n");
+        assert!(err.unwrap_err().to_string().contains("at least 1"));
+    }
+
+    #[test]
+    fn params() {
+        let b1 = Benchmark {
+            params: BTreeMap::from([
+                ("nfa-size-limit".to_string(), "200M".to_string()),
+                ("flavor".to_string(), "a=b".to_string()),
+            ]),
+            ..Benchmark::default()
+        };
+        let mut buf = vec![];
+        b1.write(&mut buf).unwrap();
+        assert!(buf.contains_str("param:19:nfa-size-limit=200M\n"));
+        let b2 = Benchmark::read(&*buf).unwrap();
+        assert_eq!(b1.params, b2.params);
+        assert_eq!(Some(200 << 20), b2.param_size("nfa-size-limit").unwrap());
+        assert_eq!(None, b2.param_size("dfa-size-limit").unwrap());
+        let err = b2.param_size("flavor").unwrap_err();
+        assert!(format!("{:#}", err).contains("'flavor'"), "{:#}", err);
+
+        let err = read("klv-version:1:9\nparam:3:foo\n").unwrap_err();
+        assert!(err.to_string().contains("'name=value'"), "{}", err);
+        let err = read("klv-version:1:9\nparam:3:a=1\nparam:3:a=2\n");
+        assert!(err.unwrap_err().to_string().contains("duplicate"));
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(5, parse_size("5").unwrap());
+        assert_eq!(5 << 10, parse_size("5K").unwrap());
+        assert_eq!(1 << 30, parse_size("1G").unwrap());
+        assert!(parse_size("").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("5k").is_err());
+    }
+
+    #[test]
+    fn haystack_encoding() {
+        // Only written when it isn't UTF-8.
+        let mut buf = vec![];
+        Benchmark::default().write(&mut buf).unwrap();
+        assert!(!buf.contains_str("haystack-encoding"));
+
+        let b1 = Benchmark {
+            haystack: Arc::from(&b"a\x00b\x00"[..]),
+            haystack_encoding: HaystackEncoding::Utf16le,
+            ..Benchmark::default()
+        };
+        let mut buf = vec![];
+        b1.write(&mut buf).unwrap();
+        let b2 = Benchmark::read(&*buf).unwrap();
+        assert_eq!(HaystackEncoding::Utf16le, b2.haystack_encoding);
+        assert_eq!(b1.haystack, b2.haystack);
+        // Asking for a UTF-8 haystack fails even though the bytes happen to
+        // be valid UTF-8.
+        assert!(b2.haystack_str().is_err());
+
+        let b = read("klv-version:1:5\nhaystack-encoding:7:latin-1\n");
+        assert_eq!(HaystackEncoding::Latin1, b.unwrap().haystack_encoding);
+        let err = read("klv-version:1:5\nhaystack-encoding:6:utf-32\n");
+        assert!(err.unwrap_err().to_string().contains("'utf-32'"));
+    }
+
+    #[test]
+    fn validation() {
+        let b1 = Benchmark::validation();
+        let mut buf = vec![];
+        b1.write(&mut buf).unwrap();
+        let b2 = Benchmark::read(&*buf).unwrap();
+        assert_eq!("count", b2.model);
+        assert_eq!(vec!["a+b".to_string()], b2.regex.patterns);
+        assert_eq!(b1.haystack, b2.haystack);
+        assert_eq!(1, b2.max_iters);
+        // Every word ending in "ab" contains exactly one match of `a+b`.
+        let count = b2.haystack.split(|&b| b == b' ').filter(|w| {
+            w.ends_with(b"b") && w.len() > 1 && w[w.len() - 2] == b'a'
+        });
+        assert_eq!(VALIDATION_COUNT, count.count() as u64);
+    }
+
+    #[test]
+    fn version_missing() {
+        let b = read("name:3:foo\n").unwrap();
+        assert_eq!(0, b.version);
+        assert_eq!("foo", b.name);
+    }
+
+    #[test]
+    fn version_not_first() {
+        let err = read("name:3:foo\nklv-version:1:1\n").unwrap_err();
+        assert!(err.to_string().contains("must be the first"), "{}", err);
+    }
+
+    #[test]
+    fn unknown_key_same_version() {
+        let err = read("klv-version:1:2\nwat:2:hi\n").unwrap_err();
+        assert!(err.to_string().contains("'wat'"), "{}", err);
+
+        // Unknown keys are also an error without a version.
+        assert!(read("wat:2:hi\n").is_err());
+    }
+
+    #[test]
+    fn unknown_key_newer_version() {
+        let newer = (VERSION + 1).to_string();
+        let len = newer.len();
+        let b = read(&format!(
+            "klv-version:{len}:{newer}\nwat:2:hi\nname:3:foo\nwut:0:\n"
+        ))
+        .unwrap();
+        assert_eq!(VERSION + 1, b.version);
+        assert_eq!("foo", b.name);
+        assert_eq!(
+            vec![
+                IgnoredKey { key: "wat".to_string(), len: 2 },
+                IgnoredKey { key: "wut".to_string(), len: 0 },
+            ],
+            b.ignored,
+        );
+    }
+
+    #[test]
+    fn length_exceeds_input() {
+        let err = read("klv-version:1:5\nname:10:foo\n").unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("'name'"), "{}", msg);
+        assert!(msg.contains("byte offset 24"), "{}", msg);
+
+        let err = read("klv-version:1:5\nname:3:foox\n").unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("'name'"), "{}", msg);
+        assert!(msg.contains("byte offset 26"), "{}", msg);
+    }
+
+    #[test]
+    fn binary_patterns() {
+        let mut regex = Regex::default();
+        regex.set_patterns_bytes(vec![b"a".to_vec(), b"\xFF+".to_vec()]);
+        assert!(regex.patterns.is_empty());
+        assert!(regex.one().is_err());
+        let b1 = Benchmark {
+            name: "foo/bar".to_string(),
+            model: "count".to_string(),
+            regex,
+            haystack: Arc::from(&b"a\xFF\xFF"[..]),
+            ..Benchmark::default()
+        };
+        let mut buf = vec![];
+        b1.write(&mut buf).unwrap();
+        let b2 = Benchmark::read(&*buf).unwrap();
+        assert_eq!(vec![&b"a"[..], &b"\xFF+"[..]], b2.regex.patterns_bytes(),);
+        assert_eq!(b1.regex, b2.regex);
+        let err = b2.regex.one().unwrap_err().to_string();
+        assert!(err.contains("valid UTF-8"), "{}", err);
+
+        // When every pattern is valid UTF-8, the string API works as usual.
+        let mut regex = Regex::default();
+        regex.set_patterns_bytes(vec![b"a+".to_vec()]);
+        assert_eq!(None, regex.binary_patterns);
+        assert_eq!("a+", regex.one().unwrap());
+        assert_eq!(b"a+", regex.one_bytes().unwrap());
+    }
+
+    #[test]
+    fn truncated() {
+        let b = Benchmark {
+            name: "foo/bar".to_string(),
+            model: "count".to_string(),
+            regex: Regex {
+                patterns: vec!["a".to_string()],
+                ..Regex::default()
+            },
+            haystack: Arc::from(&b"abc\nxyz\n"[..]),
+            soft_deadline: Some(Duration::from_secs(1)),
+            ..Benchmark::default()
+        };
+        let mut buf = vec![];
+        b.write(&mut buf).unwrap();
+        // Every prefix either fails or, when it ends on an item boundary,
+        // succeeds. But it never panics.
+        for end in 0..buf.len() {
+            let result = Benchmark::read(&buf[..end]);
+            if end > 0 && buf[end - 1] != b'\n' {
+                assert!(result.is_err(), "prefix of length {}", end);
+            }
+        }
+    }
+
+    fn arb_duration() -> impl Strategy<Value = Duration> {
+        // Durations are written in nanoseconds and read back as a u64.
+        any::<u64>().prop_map(Duration::from_nanos)
+    }
+
+    fn arb_benchmark() -> impl Strategy<Value = Benchmark> {
+        let regex = (
+            // Patterns may or may not be valid UTF-8.
+            prop::collection::vec(
+                prop_oneof![
+                    any::<String>().prop_map(String::into_bytes),
+                    any::<Vec<u8>>(),
+                ],
+                0..4,
+            ),
+            any::<bool>(),
+            any::<bool>(),
+            any::<bool>(),
+            any::<bool>(),
+        )
+            .prop_map(
+                |(patterns, ci, unicode, multiline, dot_all)| {
+                    let mut regex = Regex {
+                        case_insensitive: ci,
+                        unicode,
+                        multiline,
+                        dot_all,
+                        ..Regex::default()
+                    };
+                    regex.set_patterns_bytes(patterns);
+                    regex
+                },
+            );
+        let encoding = prop_oneof![
+            Just(HaystackEncoding::Utf8),
+            Just(HaystackEncoding::Utf16le),
+            Just(HaystackEncoding::Latin1),
+        ];
+        let limits = (
+            any::<u64>(),
+            any::<u64>(),
+            arb_duration(),
+            arb_duration(),
+            1..=u64::MAX,
+            1..=u64::MAX,
+            proptest::option::of(arb_duration()),
+            proptest::option::of(any::<u64>()),
+            proptest::option::of(any::<usize>()),
+            proptest::option::of(any::<usize>()),
+            proptest::option::of(1..=u64::MAX),
+        );
+        (
+            any::<String>(),
+            any::<String>(),
+            regex,
+            any::<Vec<u8>>(),
+            encoding,
+            limits,
+            // Parameter names can't contain '=', since that's what separates
+            // them from their values.
+            prop::collection::btree_map("[^=]*", any::<String>(), 0..4),
+        )
+            .prop_map(
+                |(name, model, regex, haystack, encoding, limits, params)| {
+                    let (
+                        max_iters,
+                        max_warmup_iters,
+                        max_time,
+                        max_warmup_time,
+                        verify_every,
+                        iter_batch,
+                        soft_deadline,
+                        expected_count,
+                        search_start,
+                        search_end,
+                        max_count,
+                    ) = limits;
+                    Benchmark {
+                        version: VERSION,
+                        name,
+                        model,
+                        regex,
+                        haystack: Arc::from(haystack),
+                        haystack_encoding: encoding,
+                        max_iters,
+                        max_warmup_iters,
+                        max_time,
+                        max_warmup_time,
+                        verify_every,
+                        iter_batch,
+                        soft_deadline,
+                        expected_count,
+                        search_start,
+                        search_end,
+                        max_count,
+                        params,
+                        ignored: vec![],
+                        started: None,
+                    }
+                },
+            )
+    }
+
+    proptest! {
+        #[test]
+        fn prop_roundtrip(b1 in arb_benchmark()) {
+            let mut buf = vec![];
+            b1.write(&mut buf).unwrap();
+            let b2 = Benchmark::read(&*buf).unwrap();
+            // The time it was read is never written, so it can't roundtrip.
+            prop_assert!(b2.started.is_some());
+            prop_assert_eq!(b1, Benchmark { started: None, ..b2 });
+        }
+
+        #[test]
+        fn prop_arbitrary_bytes(bytes in any::<Vec<u8>>()) {
+            let _ = Benchmark::read(&*bytes);
+        }
+
+        #[test]
+        fn prop_arbitrary_items(
+            items in prop::collection::vec(
+                (
+                    "[a-z:-]{0,12}",
+                    "[0-9]{0,3}",
+                    any::<Vec<u8>>(),
+                    any::<Option<u8>>(),
+                ),
+                0..8,
+            ),
+        ) {
+            // Build data that looks like KLV items, but with lengths that
+            // often don't match and terminators that are often wrong, so
+            // that we exercise more than the first ':' search.
+            let mut buf = vec![];
+            for (key, len, value, term) in items {
+                buf.extend_from_slice(key.as_bytes());
+                buf.push(b':');
+                buf.extend_from_slice(len.as_bytes());
+                buf.push(b':');
+                buf.extend_from_slice(&value);
+                buf.extend(term);
+            }
+            let _ = Benchmark::read(&*buf);
+        }
+
+        #[test]
+        fn prop_mutated(
+            b in arb_benchmark(),
+            index in any::<prop::sample::Index>(),
+            byte in any::<u8>(),
+            truncate in any::<bool>(),
+        ) {
+            let mut buf = vec![];
+            b.write(&mut buf).unwrap();
+            let i = index.index(buf.len());
+            if truncate {
+                buf.truncate(i);
+            } else {
+                buf[i] = byte;
+            }
+            let _ = Benchmark::read(&*buf);
+        }
+    }
+}
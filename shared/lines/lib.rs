@@ -0,0 +1,193 @@
+/*!
+Line iterators with identical semantics for bytes, `&str` and UTF-16 haystacks.
+
+The `grep` and `grep-captures` benchmark models search each line of a haystack
+individually. In order for their counts to agree across regex engines, every
+runner program needs to agree on what a line is. Different line iterators
+(e.g., `str::lines`, bstr's `lines` or a hand-rolled one) disagree on some
+corner cases, so every runner program written in Rust should use this crate
+instead.
+
+The semantics are as follows:
+
+* Lines are terminated by `\n`. The `\n` is never included in a line.
+* If a line ends with `\r` (after removing its `\n`), then exactly one `\r`
+  is removed. This applies to the last line in a haystack even if it isn't
+  followed by a `\n`. A `\r` anywhere else is part of the line.
+* If the haystack ends with a `\n`, then there is no empty line after it.
+  Similarly, an empty haystack has no lines.
+
+For example, the haystack `a\r\n\r\nb\rc\r` has three lines: `a`, an empty
+line and `b\rc`.
+*/
+
+/// Returns an iterator over the lines in the given haystack of bytes.
+pub fn bytes(haystack: &[u8]) -> Bytes<'_> {
+    Bytes { haystack }
+}
+
+/// Returns an iterator over the lines in the given string.
+pub fn str(haystack: &str) -> Str<'_> {
+    Str { haystack }
+}
+
+/// Returns an iterator over the lines in the given haystack of UTF-16 code
+/// units.
+pub fn utf16(haystack: &[u16]) -> Utf16<'_> {
+    Utf16 { haystack }
+}
+
+/// An iterator over the lines in a haystack of bytes.
+#[derive(Clone, Debug)]
+pub struct Bytes<'a> {
+    haystack: &'a [u8],
+}
+
+impl<'a> Iterator for Bytes<'a> {
+    type Item = &'a [u8];
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a [u8]> {
+        if self.haystack.is_empty() {
+            return None;
+        }
+        let newline = memchr::memchr(b'\n', self.haystack);
+        let (end, next) = bounds(self.haystack.len(), newline);
+        let mut line = &self.haystack[..end];
+        self.haystack = &self.haystack[next..];
+        if line.last() == Some(&b'\r') {
+            line = &line[..line.len() - 1];
+        }
+        Some(line)
+    }
+}
+
+/// An iterator over the lines in a string.
+#[derive(Clone, Debug)]
+pub struct Str<'a> {
+    haystack: &'a str,
+}
+
+impl<'a> Iterator for Str<'a> {
+    type Item = &'a str;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a str> {
+        if self.haystack.is_empty() {
+            return None;
+        }
+        let newline = memchr::memchr(b'\n', self.haystack.as_bytes());
+        let (end, next) = bounds(self.haystack.len(), newline);
+        // Slicing can't panic here since '\n' and '\r' are always on a
+        // character boundary in valid UTF-8.
+        let mut line = &self.haystack[..end];
+        self.haystack = &self.haystack[next..];
+        if line.ends_with('\r') {
+            line = &line[..line.len() - 1];
+        }
+        Some(line)
+    }
+}
+
+/// An iterator over the lines in a haystack of UTF-16 code units.
+#[derive(Clone, Debug)]
+pub struct Utf16<'a> {
+    haystack: &'a [u16],
+}
+
+impl<'a> Iterator for Utf16<'a> {
+    type Item = &'a [u16];
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a [u16]> {
+        if self.haystack.is_empty() {
+            return None;
+        }
+        let newline =
+            self.haystack.iter().position(|&u| u == u16::from(b'\n'));
+        let (end, next) = bounds(self.haystack.len(), newline);
+        let mut line = &self.haystack[..end];
+        self.haystack = &self.haystack[next..];
+        if line.last() == Some(&u16::from(b'\r')) {
+            line = &line[..line.len() - 1];
+        }
+        Some(line)
+    }
+}
+
+/// Given the length of a non-empty haystack and the position of the first
+/// `\n` in it (if one exists), this returns the end of the first line
+/// (excluding its `\n`) and the start of the rest of the haystack.
+#[inline]
+fn bounds(len: usize, newline: Option<usize>) -> (usize, usize) {
+    match newline {
+        None => (len, len),
+        Some(i) => (i, i + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A corpus of haystacks and the lines we expect from each one.
+    const CORPUS: &[(&str, &[&str])] = &[
+        ("", &[]),
+        ("\n", &[""]),
+        ("\n\n", &["", ""]),
+        ("a", &["a"]),
+        ("a\n", &["a"]),
+        ("a\nb", &["a", "b"]),
+        ("a\nb\n", &["a", "b"]),
+        ("a\n\nb\n\n", &["a", "", "b", ""]),
+        // CRLF
+        ("a\r\n", &["a"]),
+        ("a\r\nb\r\n", &["a", "b"]),
+        ("a\r\n\r\nb", &["a", "", "b"]),
+        ("\r\n", &[""]),
+        // Only one '\r' is stripped.
+        ("a\r\r\n", &["a\r"]),
+        // Lone CR
+        ("\r", &[""]),
+        ("a\r", &["a"]),
+        ("a\rb", &["a\rb"]),
+        ("a\rb\n", &["a\rb"]),
+        ("\ra\n", &["\ra"]),
+        ("a\r\nb\rc\r", &["a", "b\rc"]),
+        ("a\r\n\r\nb\rc\r", &["a", "", "b\rc"]),
+        // Non-ASCII
+        ("☃\r\nβ\n", &["☃", "β"]),
+    ];
+
+    #[test]
+    fn corpus_bytes() {
+        for &(haystack, expected) in CORPUS {
+            let got: Vec<&[u8]> = bytes(haystack.as_bytes()).collect();
+            let expected: Vec<&[u8]> =
+                expected.iter().map(|line| line.as_bytes()).collect();
+            assert_eq!(expected, got, "haystack: {:?}", haystack);
+        }
+    }
+
+    #[test]
+    fn corpus_str() {
+        for &(haystack, expected) in CORPUS {
+            let got: Vec<&str> = str(haystack).collect();
+            assert_eq!(expected, got, "haystack: {:?}", haystack);
+        }
+    }
+
+    #[test]
+    fn corpus_utf16() {
+        for &(haystack, expected) in CORPUS {
+            let haystack16: Vec<u16> = haystack.encode_utf16().collect();
+            let got: Vec<Vec<u16>> =
+                utf16(&haystack16).map(|line| line.to_vec()).collect();
+            let expected: Vec<Vec<u16>> = expected
+                .iter()
+                .map(|line| line.encode_utf16().collect())
+                .collect();
+            assert_eq!(expected, got, "haystack: {:?}", haystack);
+        }
+    }
+}
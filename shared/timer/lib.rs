@@ -1,33 +1,171 @@
 use std::time::{Duration, Instant};
 
+/// The samples computed from running a benchmark, along with some
+/// information about the run as a whole.
+#[derive(Clone, Debug)]
+pub struct Samples {
+    /// The number of warmup iterations that were actually executed before
+    /// any samples were taken. This may be less than the benchmark's
+    /// `max_warmup_iters` setting, since warmup stops early once
+    /// `max_warmup_time` has been reached.
+    pub warmup_iters: u64,
+    /// The samples in the order in which they were taken. Runner programs
+    /// should print a `# warmup_iters=N` header line before printing these.
+    pub list: Vec<Sample>,
+    /// Whether the benchmark's soft deadline was reached before the maximum
+    /// time or number of iterations. When this is true, runner programs
+    /// should print a `# partial=true` header line before printing the
+    /// samples.
+    pub partial: bool,
+}
+
+impl Samples {
+    /// Returns an iterator over every sample.
+    pub fn iter(&self) -> std::slice::Iter<'_, Sample> {
+        self.list.iter()
+    }
+}
+
 /// A sample computed from a single benchmark iteration.
 #[derive(Clone, Debug)]
 pub struct Sample {
+    /// The index of the iteration that produced this sample, starting at
+    /// zero. Warmup iterations are not counted. Since samples are returned in
+    /// the order in which they were taken, this is also the sample's index
+    /// in `Samples::list`. It is included so that consumers can keep
+    /// track of the order of samples even after sorting them (e.g., to detect
+    /// benchmarks whose timings change over the course of a run).
+    pub iter: u64,
     /// The duration of the iteration.
+    ///
+    /// When the benchmark's `iter_batch` setting is greater than `1`, each
+    /// sample corresponds to a batch of runs, and this is the total time of
+    /// the batch divided by the number of runs in it.
     pub duration: Duration,
     /// The count reported by the benchmark. This is used by the harness to
     /// verify that the result is correct.
     ///
+    /// When the benchmark's `verify_every` setting is greater than `1`, the
+    /// count is only computed for some iterations. Every other sample
+    /// repeats the count (and size) of the most recent sample for which it
+    /// was computed.
+    ///
     /// All benchmark models except for regex-redux use this. For regex-redux,
     /// it is always zero.
     pub count: u64,
+    /// The amount of heap memory, in bytes, used by the compiled regex as
+    /// reported by the regex engine itself.
+    ///
+    /// This is only ever present for the 'compile' and 'size' models, and
+    /// only when the runner used `run_and_count_with_size` or `run_and_size`.
+    pub size: Option<u64>,
+}
+
+impl std::fmt::Display for Sample {
+    /// Writes this sample in the format expected by rebar, i.e.,
+    /// `duration_nanos,count,size_bytes,iter`, where `size_bytes` is empty
+    /// when there is no size.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{},{},", self.duration.as_nanos(), self.count)?;
+        if let Some(size) = self.size {
+            write!(f, "{}", size)?;
+        }
+        write!(f, ",{}", self.iter)
+    }
 }
 
+/// An error returned when a benchmark iteration reports a count that is
+/// inconsistent with either the benchmark's expected count or the counts
+/// reported by other iterations.
+///
+/// Runner programs don't need to do anything special with this error. It's
+/// its own type so that callers can tell it apart from a failure to run the
+/// benchmark at all.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CountError {
+    /// A verified iteration reported a count different from the benchmark's
+    /// `expected_count`.
+    Unexpected {
+        /// Whether the iteration was a warmup iteration.
+        warmup: bool,
+        /// The index of the iteration, starting at zero. Warmup and measured
+        /// iterations are numbered separately.
+        iter: u64,
+        /// The count the iteration reported.
+        got: u64,
+        /// The count given by the benchmark's `expected_count`.
+        expected: u64,
+    },
+    /// A warmup iteration reported a different count than the first measured
+    /// iteration. Since the thing being run is the same in both cases, this
+    /// almost always means the regex engine (or the runner program) carries
+    /// state from one iteration to the next that changes its result, like a
+    /// scratch buffer that isn't reset.
+    WarmupMismatch {
+        /// The index of the first warmup iteration with a different count.
+        warmup_iter: u64,
+        /// The count reported by that warmup iteration.
+        warmup_count: u64,
+        /// The count reported by the first measured iteration.
+        measured_count: u64,
+    },
+}
+
+impl std::fmt::Display for CountError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            CountError::Unexpected { warmup, iter, got, expected } => {
+                let kind =
+                    if warmup { "warmup iteration" } else { "iteration" };
+                write!(
+                    f,
+                    "{kind} {iter} reported a count of {got}, \
+                     but expected {expected}",
+                )
+            }
+            CountError::WarmupMismatch {
+                warmup_iter,
+                warmup_count,
+                measured_count,
+            } => write!(
+                f,
+                "warmup/measure inconsistency: warmup iteration \
+                 {warmup_iter} reported a count of {warmup_count}, but the \
+                 first measured iteration reported {measured_count} (this \
+                 usually means the regex engine keeps state between \
+                 iterations that changes its result)",
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CountError {}
+
 /// Run the given `bench` function repeatedly until either the maximum
 /// time or number of iterations has been reached and return the set of
 /// samples.
+///
+/// When the benchmark's `iter_batch` setting is `N`, then `bench` is run `N`
+/// times for each sample. The count of each sample comes from the last run in
+/// its batch.
 pub fn run(
     b: &klv::Benchmark,
     bench: impl FnMut() -> anyhow::Result<usize>,
-) -> anyhow::Result<Vec<Sample>> {
-    run_and_count(b, |count| Ok(count), bench)
+) -> anyhow::Result<Samples> {
+    // The count is produced by `bench` itself here, so there's nothing to
+    // gain by skipping verification.
+    run_and_measure(b, 1, |count| Ok((count, None)), bench)
 }
 
 /// Run the given `bench` function repeatedly until either the maximum
 /// time or number of iterations has been reached and return the set of
 /// samples. The count for each sample is determined by running `count` on
 /// the result of `bench`. The execution time of `count` is specifically
-/// not included in the sample's duration.
+/// not included in the sample's duration, nor is it counted against the
+/// benchmark's time budget.
+///
+/// When the benchmark's `verify_every` setting is `N`, then `count` is only
+/// run on the first iteration and every `N`th iteration after that.
 ///
 /// N.B. This variant only exists for the 'compile' model. We want to only
 /// measure compile time, but still do extra work that we specifically
@@ -36,32 +174,486 @@ pub fn run(
 pub fn run_and_count<T>(
     b: &klv::Benchmark,
     mut count: impl FnMut(T) -> anyhow::Result<usize>,
+    bench: impl FnMut() -> anyhow::Result<T>,
+) -> anyhow::Result<Samples> {
+    run_and_measure(
+        b,
+        b.verify_every,
+        |result| Ok((count(result)?, None)),
+        bench,
+    )
+}
+
+/// Like `run_and_count`, but also records the size of the value returned by
+/// `bench` in each sample via `size`. Like `count`, the execution time of
+/// `size` is not included in the sample's duration.
+///
+/// This is meant for the 'compile' model, where `size` should return the heap
+/// memory used by the compiled regex as reported by the regex engine.
+pub fn run_and_count_with_size<T>(
+    b: &klv::Benchmark,
+    mut count: impl FnMut(T) -> anyhow::Result<usize>,
+    mut size: impl FnMut(&T) -> anyhow::Result<usize>,
+    bench: impl FnMut() -> anyhow::Result<T>,
+) -> anyhow::Result<Samples> {
+    run_and_measure(
+        b,
+        b.verify_every,
+        |result| {
+            let size = size(&result)?;
+            Ok((count(result)?, Some(size)))
+        },
+        bench,
+    )
+}
+
+/// Run the given `bench` function repeatedly, just like `run_and_count`, but
+/// report the size of the value it returns as both the count and the size of
+/// each sample.
+///
+/// This is meant for the 'size' model, where `bench` should compile a regex
+/// and `size` should return the heap memory used by the compiled regex as
+/// reported by the regex engine. The duration of each sample is the time it
+/// took to compile the regex.
+pub fn run_and_size<T>(
+    b: &klv::Benchmark,
+    mut size: impl FnMut(&T) -> anyhow::Result<usize>,
+    bench: impl FnMut() -> anyhow::Result<T>,
+) -> anyhow::Result<Samples> {
+    run_and_measure(
+        b,
+        b.verify_every,
+        |result| {
+            let size = size(&result)?;
+            Ok((size, Some(size)))
+        },
+        bench,
+    )
+}
+
+/// Run the benchmark returned by `klv::Benchmark::validation` with the given
+/// `run` function and check that every sample has the expected count.
+///
+/// `run` should execute a benchmark in exactly the same way that a runner
+/// program does for a benchmark read from stdin. An error is returned if
+/// `run` fails, if it returns no samples or if any sample has the wrong
+/// count. Runner programs call this when given the `--validate` flag, which
+/// `rebar build` uses as a quick check that a runner program works.
+pub fn validate(
+    run: impl FnOnce(&klv::Benchmark) -> anyhow::Result<Samples>,
+) -> anyhow::Result<()> {
+    let b = klv::Benchmark::validation();
+    let samples = run(&b)?;
+    anyhow::ensure!(
+        !samples.list.is_empty(),
+        "validation benchmark produced no samples",
+    );
+    for s in samples.iter() {
+        anyhow::ensure!(
+            s.count == klv::VALIDATION_COUNT,
+            "validation benchmark reported a count of {}, but expected {}",
+            s.count,
+            klv::VALIDATION_COUNT,
+        );
+    }
+    Ok(())
+}
+
+/// Returns a single line of build metadata for a runner program, given the
+/// cargo features it enabled for its regex engine (including 'default' when
+/// the default features are enabled). Runner programs print this when given
+/// the `--metadata` flag.
+///
+/// The line includes the CPU features detected at runtime that regex engines
+/// commonly use for SIMD acceleration, since two builds of the same engine
+/// can perform very differently depending on which are available.
+pub fn metadata(features: &[&str]) -> String {
+    format!("features={} cpu={}", features.join(","), cpu_features().join(","))
+}
+
+/// Returns the names of the SIMD related CPU features detected at runtime.
+#[cfg(target_arch = "x86_64")]
+fn cpu_features() -> Vec<&'static str> {
+    let mut found = vec!["sse2"];
+    if std::is_x86_feature_detected!("ssse3") {
+        found.push("ssse3");
+    }
+    if std::is_x86_feature_detected!("sse4.2") {
+        found.push("sse4.2");
+    }
+    if std::is_x86_feature_detected!("avx2") {
+        found.push("avx2");
+    }
+    if std::is_x86_feature_detected!("popcnt") {
+        found.push("popcnt");
+    }
+    if std::is_x86_feature_detected!("bmi2") {
+        found.push("bmi2");
+    }
+    found
+}
+
+/// Returns the names of the SIMD related CPU features detected at runtime.
+#[cfg(target_arch = "aarch64")]
+fn cpu_features() -> Vec<&'static str> {
+    let mut found = vec![];
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        found.push("neon");
+    }
+    found
+}
+
+/// Returns the names of the SIMD related CPU features detected at runtime.
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn cpu_features() -> Vec<&'static str> {
+    vec![]
+}
+
+/// The implementation of `run_and_count`, `run_and_count_with_size` and
+/// `run_and_size`.
+/// `measure` returns the count and the (optional) size of the result of
+/// `bench`, and is only called on the first and then every `verify_every`
+/// iterations.
+///
+/// Every count computed, including during warmup, is checked against the
+/// benchmark's expected count (when it has one). Counts computed during
+/// warmup are also checked against the count of the first measured
+/// iteration. Either kind of mismatch results in a `CountError`.
+///
+/// If the benchmark has a soft deadline, then warmup and sampling both stop
+/// once the deadline has passed, and the samples returned are marked as
+/// partial. Unlike the maximum times, the deadline is measured in wall clock
+/// time since `Benchmark::started`, i.e., since the runner program began
+/// reading its KLV input. (If that isn't set, then it's measured since this
+/// function was called.) This is because its purpose is to finish before the
+/// harness kills the runner program, and the harness's timeout includes
+/// everything the runner did before getting here. At least one sample is
+/// always taken.
+///
+/// Only the time spent in `bench` counts toward the maximum warmup and
+/// benchmark times. Otherwise, an expensive `measure` (e.g., a full search
+/// for the 'compile' model on a big haystack) could use up the entire time
+/// budget before enough samples have been collected.
+///
+/// When the benchmark's `iter_batch` setting is `N`, then `bench` is run `N`
+/// times for every sample (and every warmup iteration), and the duration of
+/// each sample is the total time divided by `N`. Only the result of the last
+/// run in each batch is given to `measure`, so the count reported is always
+/// the count of a single run.
+fn run_and_measure<T>(
+    b: &klv::Benchmark,
+    verify_every: u64,
+    mut measure: impl FnMut(T) -> anyhow::Result<(usize, Option<usize>)>,
     mut bench: impl FnMut() -> anyhow::Result<T>,
-) -> anyhow::Result<Vec<Sample>> {
-    let warmup_start = Instant::now();
-    for _ in 0..b.max_warmup_iters {
-        let result = bench();
-        // We still compute the count in case there was a problem doing so,
-        // even though we don't do anything with the count.
-        let _count = count(result?)?;
-        if warmup_start.elapsed() >= b.max_warmup_time {
+) -> anyhow::Result<Samples> {
+    anyhow::ensure!(verify_every >= 1, "'verify-every' must be at least 1");
+    anyhow::ensure!(b.iter_batch >= 1, "'iter-batch' must be at least 1");
+
+    // The soft deadline includes everything the runner program did before
+    // getting here, since that's what the harness's timeout includes too.
+    let start = b.started.unwrap_or_else(Instant::now);
+    let past_deadline =
+        || b.soft_deadline.is_some_and(|d| start.elapsed() >= d);
+    let mut partial = false;
+    let mut warmup_elapsed = Duration::ZERO;
+    let mut warmup_iters = 0;
+    // Pairs of (warmup iteration, count) to compare against the count of
+    // the first measured iteration.
+    let mut warmup_counts: Vec<(u64, u64)> = vec![];
+    for i in 0..b.max_warmup_iters {
+        let (result, total) = run_batch(b.iter_batch, &mut bench);
+        warmup_elapsed += total;
+        warmup_iters += 1;
+        let result = result?;
+        if i % verify_every == 0 {
+            let count = to_u64(measure(result)?.0);
+            check_expected(b, true, i, count)?;
+            // We only need to remember the first iteration for each distinct
+            // count, which in practice means we only remember one.
+            if warmup_counts.iter().all(|&(_, c)| c != count) {
+                warmup_counts.push((i, count));
+            }
+        }
+        if warmup_elapsed >= b.max_warmup_time {
+            break;
+        }
+        if past_deadline() {
+            partial = true;
             break;
         }
     }
 
     let mut samples = vec![];
-    let run_start = Instant::now();
-    for _ in 0..b.max_iters {
-        let bench_start = Instant::now();
-        let result = bench();
-        let duration = bench_start.elapsed();
-        // Should be fine since it's unreasonable for a match count to
-        // exceed u64::MAX.
-        let count = u64::try_from(count(result?)?).unwrap();
-        samples.push(Sample { duration, count });
-        if run_start.elapsed() >= b.max_time {
+    let mut elapsed = Duration::ZERO;
+    let (mut count, mut size) = (0, None);
+    for iter in 0..b.max_iters {
+        let (result, total) = run_batch(b.iter_batch, &mut bench);
+        elapsed += total;
+        let result = result?;
+        let duration = per_iter(total, b.iter_batch);
+        // The first iteration is always verified, so 'count' and 'size' are
+        // always set by the time they're used below.
+        if iter % verify_every == 0 {
+            let (c, s) = measure(result)?;
+            count = to_u64(c);
+            size = s.map(to_u64);
+            check_expected(b, false, iter, count)?;
+            if iter == 0 {
+                let mismatch =
+                    warmup_counts.iter().find(|&&(_, c)| c != count);
+                if let Some(&(warmup_iter, warmup_count)) = mismatch {
+                    return Err(anyhow::Error::new(
+                        CountError::WarmupMismatch {
+                            warmup_iter,
+                            warmup_count,
+                            measured_count: count,
+                        },
+                    ));
+                }
+            }
+        }
+        samples.push(Sample { iter, duration, count, size });
+        if elapsed >= b.max_time {
+            break;
+        }
+        if partial || past_deadline() {
+            partial = true;
+            break;
+        }
+    }
+    Ok(Samples { warmup_iters, list: samples, partial })
+}
+
+/// Returns an error if the benchmark has an expected count and the given
+/// count of the given iteration doesn't match it.
+fn check_expected(
+    b: &klv::Benchmark,
+    warmup: bool,
+    iter: u64,
+    got: u64,
+) -> anyhow::Result<()> {
+    match b.expected_count {
+        Some(expected) if got != expected => {
+            Err(anyhow::Error::new(CountError::Unexpected {
+                warmup,
+                iter,
+                got,
+                expected,
+            }))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Converts a count or size reported by a benchmark to a `u64`.
+fn to_u64(n: usize) -> u64 {
+    // Should be fine since it's unreasonable for a match count or a size to
+    // exceed u64::MAX.
+    u64::try_from(n).unwrap()
+}
+
+/// Runs `bench` `batch` times in a row and returns the result of the last run
+/// along with the total time it took. If any run fails, then its error is
+/// returned immediately without doing the rest of the batch.
+///
+/// Note that the results of every run except for the last are dropped while
+/// the clock is running.
+fn run_batch<T>(
+    batch: u64,
+    bench: &mut impl FnMut() -> anyhow::Result<T>,
+) -> (anyhow::Result<T>, Duration) {
+    let start = Instant::now();
+    let mut result = bench();
+    for _ in 1..batch {
+        if result.is_err() {
             break;
         }
+        result = bench();
+    }
+    (result, start.elapsed())
+}
+
+/// Divides the total duration of a batch of `batch` runs to get the duration
+/// of a single run.
+fn per_iter(total: Duration, batch: u64) -> Duration {
+    // A batch should never be so big or so slow that the per-run duration
+    // overflows a u64 worth of nanoseconds.
+    let nanos = total.as_nanos() / u128::from(batch);
+    Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a benchmark that would take about a second to run, with
+    /// a slow `bench` function sleeping for 10ms per iteration, unless it's
+    /// cut short by a soft deadline.
+    fn slow(soft_deadline: Option<Duration>) -> Samples {
+        let b = klv::Benchmark {
+            max_iters: 100,
+            max_warmup_iters: 10,
+            max_time: Duration::from_secs(10),
+            max_warmup_time: Duration::from_secs(10),
+            soft_deadline,
+            ..klv::Benchmark::default()
+        };
+        run(&b, || {
+            std::thread::sleep(Duration::from_millis(10));
+            Ok(1)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn sample_format() {
+        let b = klv::Benchmark {
+            max_iters: 3,
+            max_time: Duration::from_secs(10),
+            ..klv::Benchmark::default()
+        };
+        let samples = run(&b, || Ok(5)).unwrap();
+        let iters: Vec<u64> = samples.iter().map(|s| s.iter).collect();
+        assert_eq!(vec![0, 1, 2], iters);
+
+        let mut s = Sample {
+            iter: 2,
+            duration: Duration::from_nanos(100),
+            count: 5,
+            size: None,
+        };
+        assert_eq!("100,5,,2", s.to_string());
+        s.size = Some(42);
+        assert_eq!("100,5,42,2", s.to_string());
+    }
+
+    #[test]
+    fn soft_deadline() {
+        let samples = slow(Some(Duration::from_millis(200)));
+        assert!(samples.partial);
+        assert_eq!(10, samples.warmup_iters);
+        assert!(!samples.list.is_empty());
+        assert!(samples.list.len() < 100, "{}", samples.list.len());
+        assert!(samples.iter().all(|s| s.count == 1));
+
+        // When the deadline passes during warmup, we still get one sample.
+        let samples = slow(Some(Duration::from_millis(1)));
+        assert!(samples.partial);
+        assert_eq!(1, samples.warmup_iters);
+        assert_eq!(1, samples.list.len());
+    }
+
+    #[test]
+    fn soft_deadline_includes_setup() {
+        // The deadline is measured from when the benchmark was read, so a
+        // slow setup eats into it. Here, setup took longer than the whole
+        // deadline, so only the one required sample is taken.
+        let b = klv::Benchmark {
+            max_iters: 100,
+            max_warmup_iters: 10,
+            max_time: Duration::from_secs(10),
+            max_warmup_time: Duration::from_secs(10),
+            soft_deadline: Some(Duration::from_millis(200)),
+            started: Some(Instant::now() - Duration::from_millis(300)),
+            ..klv::Benchmark::default()
+        };
+        let samples = run(&b, || Ok(1)).unwrap();
+        assert!(samples.partial);
+        assert_eq!(1, samples.warmup_iters);
+        assert_eq!(1, samples.list.len());
+    }
+
+    #[test]
+    fn no_soft_deadline() {
+        let b = klv::Benchmark {
+            max_iters: 5,
+            max_time: Duration::from_secs(10),
+            ..klv::Benchmark::default()
+        };
+        let samples = run(&b, || Ok(1)).unwrap();
+        assert!(!samples.partial);
+        assert_eq!(5, samples.list.len());
+    }
+
+    fn counting(expected_count: Option<u64>) -> klv::Benchmark {
+        klv::Benchmark {
+            max_iters: 5,
+            max_warmup_iters: 3,
+            max_time: Duration::from_secs(10),
+            max_warmup_time: Duration::from_secs(10),
+            expected_count,
+            ..klv::Benchmark::default()
+        }
+    }
+
+    #[test]
+    fn expected_count() {
+        let samples = run(&counting(Some(1)), || Ok(1)).unwrap();
+        assert_eq!(5, samples.list.len());
+
+        // The first bad iteration fails immediately, whether it's a warmup
+        // iteration or not.
+        let mut i = 0;
+        let err = run(&counting(Some(1)), || {
+            i += 1;
+            Ok(if i == 2 { 2 } else { 1 })
+        })
+        .unwrap_err();
+        assert_eq!(
+            Some(&CountError::Unexpected {
+                warmup: true,
+                iter: 1,
+                got: 2,
+                expected: 1,
+            }),
+            err.downcast_ref::<CountError>(),
+        );
+
+        let mut i = 0;
+        let err = run(&counting(Some(1)), || {
+            i += 1;
+            Ok(if i == 6 { 2 } else { 1 })
+        })
+        .unwrap_err();
+        assert_eq!(
+            Some(&CountError::Unexpected {
+                warmup: false,
+                iter: 2,
+                got: 2,
+                expected: 1,
+            }),
+            err.downcast_ref::<CountError>(),
+        );
+    }
+
+    #[test]
+    fn warmup_mismatch() {
+        // Without an expected count, warmup counts are still compared with
+        // the first measured count.
+        let mut i = 0;
+        let err = run(&counting(None), || {
+            i += 1;
+            Ok(if i == 3 { 7 } else { 1 })
+        })
+        .unwrap_err();
+        assert_eq!(
+            Some(&CountError::WarmupMismatch {
+                warmup_iter: 2,
+                warmup_count: 7,
+                measured_count: 1,
+            }),
+            err.downcast_ref::<CountError>(),
+        );
+        assert!(err.to_string().contains("warmup/measure inconsistency"));
+
+        // Counts that change after the first measured iteration aren't the
+        // timer's business. The harness reports those.
+        let mut i = 0;
+        let samples = run(&counting(None), || {
+            i += 1;
+            Ok(if i <= 4 { 1 } else { i })
+        })
+        .unwrap();
+        assert_eq!(8, samples.list.last().unwrap().count);
     }
-    Ok(samples)
 }
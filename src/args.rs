@@ -1,5 +1,6 @@
 use std::{
     fmt::{Debug, Display, Write},
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
@@ -45,21 +46,22 @@ where
     let strv = match osv.to_str() {
         Some(strv) => strv,
         None => {
-            let err = lexopt::Error::NonUnicodeValue(osv.into());
+            let err = lexopt::Error::NonUnicodeValue(osv);
             return Err(anyhow::Error::from(err).context(flag_name));
         }
     };
     let parsed = match strv.parse() {
-        Err(err) => return Err(anyhow::Error::msg(err)),
+        Err(err) => return Err(anyhow::Error::msg(err).context(flag_name)),
         Ok(parsed) => parsed,
     };
     Ok(parsed)
 }
 
 /// This defines a flag for controlling the use of color in the output.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default)]
 pub enum Color {
     /// Color is only enabled when the output is a tty.
+    #[default]
     Auto,
     /// Color is always enabled.
     Always,
@@ -131,12 +133,6 @@ colorized. The choices are: auto, always, never.
     }
 }
 
-impl Default for Color {
-    fn default() -> Color {
-        Color::Auto
-    }
-}
-
 impl std::str::FromStr for Color {
     type Err = anyhow::Error;
 
@@ -168,6 +164,10 @@ pub struct Filters {
     /// is not known. (Usually this means the regex engine is unavailable for
     /// one reason or another.)
     pub ignore_missing_engines: bool,
+    /// When enabled, regex engines that lack a capability required by a
+    /// benchmark are kept instead of being silently filtered out. (Which
+    /// usually means they'll fail when the benchmark is run.)
+    pub strict_capabilities: bool,
 }
 
 impl Filters {
@@ -258,6 +258,62 @@ included if the last pattern it matches is not a blacklist pattern.
 Filter regexes are matched on the full name of the benchmark, which takes the
 form '{group}/{name}'. Regexes use unanchored search. So to match the full
 name, use, e.g., '^test/func/dollar-only-matches-end$'.
+
+Any filter pattern (including those for regex engines and models) may instead
+be written as a glob by prefixing it with 'glob:'. A glob must match the full
+name. '*' matches any sequence of characters (including '/') and '?' matches
+any single character. Everything else matches literally. For example,
+'glob:curated/*-literal*/sherlock-en'.
+"#,
+    );
+
+    pub const USAGE_BENCH_FILE: Usage = Usage::new(
+        "--filter-file <path> ...",
+        "Include benchmarks by name patterns read from a file.",
+        r#"
+Include benchmarks by name patterns read from a file.
+
+This is like giving -f/--filter once for every pattern in the file. Patterns
+are read one per line. Blank lines and lines starting with '#' are ignored.
+Patterns from the file are added to the filter at the position of this flag,
+so the order of rules works just like it does for -f/--filter. This is useful
+when the list of benchmarks to run is too long to comfortably put on the
+command line.
+"#,
+    );
+
+    pub const USAGE_BENCH_NOT_FILE: Usage = Usage::new(
+        "--filter-not-file <path> ...",
+        "Exclude benchmarks by name patterns read from a file.",
+        r#"
+Exclude benchmarks by name patterns read from a file.
+
+This is like giving -F/--filter-not once for every pattern in the file. The
+file format is described in the docs for the --filter-file flag.
+"#,
+    );
+
+    pub const USAGE_ENGINE_FILE: Usage = Usage::new(
+        "--engine-file <path> ...",
+        "Include regex engines by name patterns read from a file.",
+        r#"
+Include regex engines by name patterns read from a file.
+
+This is like giving -e/--engine once for every pattern in the file. Patterns
+are read one per line. Blank lines and lines starting with '#' are ignored.
+Patterns from the file are added to the filter at the position of this flag,
+so the order of rules works just like it does for -e/--engine.
+"#,
+    );
+
+    pub const USAGE_ENGINE_NOT_FILE: Usage = Usage::new(
+        "--engine-not-file <path> ...",
+        "Exclude regex engines by name patterns read from a file.",
+        r#"
+Exclude regex engines by name patterns read from a file.
+
+This is like giving -E/--engine-not once for every pattern in the file. The
+file format is described in the docs for the --engine-file flag.
 "#,
     );
 
@@ -327,7 +383,7 @@ The -m/--model flag treats the pattern as a whitelist rule.
         let strval = match osval.to_str() {
             Some(strval) => strval,
             None => {
-                let err = lexopt::Error::NonUnicodeValue(osval.into());
+                let err = lexopt::Error::NonUnicodeValue(osval);
                 return Err(anyhow::Error::from(err).context(flag_name));
             }
         };
@@ -349,19 +405,45 @@ The -m/--model flag treats the pattern as a whitelist rule.
         let strval = match osval.to_str() {
             Some(strval) => strval,
             None => {
-                let err = lexopt::Error::NonUnicodeValue(osval.into());
+                let err = lexopt::Error::NonUnicodeValue(osval);
                 return Err(anyhow::Error::from(err).context(flag_name));
             }
         };
         self.blacklist(strval).context(flag_name)
     }
 
+    /// Add whitelist patterns to this filter by reading them from the file
+    /// path parsed from the given arg parser.
+    ///
+    /// The file format is described by `Filter::patterns_file`.
+    pub fn arg_whitelist_file(
+        &mut self,
+        p: &mut lexopt::Parser,
+        flag_name: &'static str,
+    ) -> anyhow::Result<()> {
+        let path = PathBuf::from(p.value().context(flag_name)?);
+        self.patterns_file(&path, false).context(flag_name)
+    }
+
+    /// Add blacklist patterns to this filter by reading them from the file
+    /// path parsed from the given arg parser.
+    ///
+    /// The file format is described by `Filter::patterns_file`.
+    pub fn arg_blacklist_file(
+        &mut self,
+        p: &mut lexopt::Parser,
+        flag_name: &'static str,
+    ) -> anyhow::Result<()> {
+        let path = PathBuf::from(p.value().context(flag_name)?);
+        self.patterns_file(&path, true).context(flag_name)
+    }
+
     /// Add a whitelist pattern to this filter.
     ///
     /// If the pattern is not a valid regex, then this returns an error.
     pub fn whitelist(&mut self, pattern: &str) -> anyhow::Result<()> {
         let re =
-            Regex::new(pattern).context("whitelist regex is not valid")?;
+            filter_regex(pattern).context("whitelist regex is not valid")?;
         self.rules.push(FilterRule { re, blacklist: false });
         Ok(())
     }
@@ -371,11 +453,41 @@ The -m/--model flag treats the pattern as a whitelist rule.
     /// If the pattern is not a valid regex, then this returns an error.
     pub fn blacklist(&mut self, pattern: &str) -> anyhow::Result<()> {
         let re =
-            Regex::new(pattern).context("blacklist regex is not valid")?;
+            filter_regex(pattern).context("blacklist regex is not valid")?;
         self.rules.push(FilterRule { re, blacklist: true });
         Ok(())
     }
 
+    /// Add every pattern in the given file to this filter, in order.
+    ///
+    /// Patterns are read one per line. Leading and trailing whitespace is
+    /// trimmed, and blank lines and lines starting with `#` are skipped. If
+    /// a pattern is not valid, then the error includes the file path and the
+    /// line number of the offending pattern.
+    fn patterns_file(
+        &mut self,
+        path: &Path,
+        blacklist: bool,
+    ) -> anyhow::Result<()> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        for (i, line) in contents.lines().enumerate() {
+            let pattern = line.trim();
+            if pattern.is_empty() || pattern.starts_with('#') {
+                continue;
+            }
+            let result = if blacklist {
+                self.blacklist(pattern)
+            } else {
+                self.whitelist(pattern)
+            };
+            result.with_context(|| {
+                format!("{}:{}", path.display(), i.saturating_add(1))
+            })?;
+        }
+        Ok(())
+    }
+
     /// Return true if and only if the given subject passes this filter.
     pub fn include(&self, subject: &str) -> bool {
         // If we have no rules, then everything matches.
@@ -394,6 +506,19 @@ The -m/--model flag treats the pattern as a whitelist rule.
         }
         include
     }
+
+    /// Returns the position, among only the whitelist rules in this filter,
+    /// of the first whitelist rule matching the given subject. If no
+    /// whitelist rule matches, then this returns `None`.
+    ///
+    /// This is useful for ordering things in the same order as the patterns
+    /// given on the command line.
+    pub fn whitelist_index(&self, subject: &str) -> Option<usize> {
+        self.rules
+            .iter()
+            .filter(|r| !r.blacklist)
+            .position(|r| r.re.is_match(subject))
+    }
 }
 
 /// A single rule in a filter, which is a combination of a regex and whether
@@ -408,17 +533,51 @@ impl std::str::FromStr for FilterRule {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> anyhow::Result<FilterRule> {
-        let (pattern, blacklist) =
-            if s.starts_with('!') { (&s[1..], true) } else { (&*s, false) };
-        let re = Regex::new(pattern).context("filter regex is not valid")?;
+        let (pattern, blacklist) = match s.strip_prefix('!') {
+            Some(pattern) => (pattern, true),
+            None => (s, false),
+        };
+        let re = filter_regex(pattern).context("filter regex is not valid")?;
         Ok(FilterRule { re, blacklist })
     }
 }
 
+/// Compile a filter pattern into a regex.
+///
+/// If the pattern starts with `glob:`, then the rest of it is treated as a
+/// glob and translated to an anchored regex via `glob_to_regex`. Otherwise,
+/// the pattern is compiled as a regex as-is.
+fn filter_regex(pattern: &str) -> anyhow::Result<Regex> {
+    let re = match pattern.strip_prefix("glob:") {
+        Some(glob) => Regex::new(&glob_to_regex(glob))?,
+        None => Regex::new(pattern)?,
+    };
+    Ok(re)
+}
+
+/// Translate a glob into an anchored regex pattern.
+///
+/// `*` becomes `.*` and `?` becomes `.`. Every other character is escaped so
+/// that it matches literally. Notably, there is no special treatment of `/`,
+/// so `*` can match across path-like separators in benchmark names.
+fn glob_to_regex(glob: &str) -> String {
+    let mut re = String::from("^");
+    for ch in glob.chars() {
+        match ch {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            _ => re.push_str(&regex_lite::escape(ch.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    re.push('$');
+    re
+}
+
 /// The choice of statistic to use. This is used in the commands for comparing
 /// benchmark measurements.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default)]
 pub enum Stat {
+    #[default]
     Median,
     Mad, // median absolute deviation
     Mean,
@@ -436,16 +595,14 @@ The aggregate statistic on which to compare (default: median).
 
 Comparisons are only performed on the basis of a single statistic. The choices
 are: median, mad (median absolute deviation), mean, stddev, min, max.
+
+The max statistic is the slowest single iteration, i.e., the worst case
+latency. For regex engines that build a DFA lazily or that have garbage
+collection pauses, it can be many times the median.
 "#,
     );
 }
 
-impl Default for Stat {
-    fn default() -> Stat {
-        Stat::Median
-    }
-}
-
 impl std::fmt::Display for Stat {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let nice = match *self {
@@ -544,8 +701,8 @@ interesting to filter on.
 
     /// Returns true if and only if the given ratio falls within this range.
     pub fn contains(&self, ratio: f64) -> bool {
-        self.min.map_or(true, |min| min.0 <= ratio)
-            && self.max.map_or(true, |max| ratio <= max.0)
+        self.min.is_none_or(|min| min.0 <= ratio)
+            && self.max.is_none_or(|max| ratio <= max.0)
     }
 
     /// Sets the minimum of this range to the given threshold.
@@ -576,32 +733,48 @@ impl std::str::FromStr for Threshold {
 
 /// The choice of units to use when representing an aggregate statistic based
 /// on time.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
 pub enum Units {
     Time,
+    #[default]
     Throughput,
+    LinesPerSec,
+    ItersPerSec,
 }
 
 impl Units {
     pub const USAGE: Usage = Usage::new(
         "-u, --units <unit>",
-        "One of: time, throughput.",
+        "One of: time, throughput, lines-per-sec, iters-per-sec.",
         r#"
 The units to use in comparisons (default: thoughput).
 
-The same units are used in all comparisons. The choices are: time or thoughput.
+The same units are used in all comparisons. The choices are: time, thoughput,
+lines-per-sec or iters-per-sec.
 
 If any particular group of measurements are all missing throughputs (i.e.,
 when their haystack length is missing or non-sensical), then absolute timings
 are reported for that group instead of throughput, even when throughput was
 specifically asked for.
+
+The 'lines-per-sec' units divide the number of lines in each benchmark's
+haystack by the time statistic selected. This is usually more meaningful than
+throughput for benchmarks using the 'grep' model, since their work scales with
+the number of lines more so than the number of bytes. Line counts are computed
+from benchmark definitions, so those must be available (see -d/--dir).
+
+The 'iters-per-sec' units are just the reciprocal of the time statistic
+selected, i.e., how many times the benchmark could run in one second. This is
+useful for benchmarks without a meaningful haystack, like ones using the
+'compile' model.
 "#,
     );
-}
 
-impl Default for Units {
-    fn default() -> Units {
-        Units::Throughput
+    /// Returns true when these units require the number of lines in each
+    /// benchmark's haystack, which in turn requires loading benchmark
+    /// definitions.
+    pub fn needs_line_counts(&self) -> bool {
+        *self == Units::LinesPerSec
     }
 }
 
@@ -612,10 +785,12 @@ impl std::str::FromStr for Units {
         let stat = match s {
             "time" => Units::Time,
             "throughput" => Units::Throughput,
+            "lines-per-sec" => Units::LinesPerSec,
+            "iters-per-sec" => Units::ItersPerSec,
             unknown => {
                 anyhow::bail!(
-                    "unrecognized units name '{}', must be \
-                     one of time or throughput.",
+                    "unrecognized units name '{}', must be one of time, \
+                     throughput, lines-per-sec or iters-per-sec.",
                     unknown,
                 )
             }
@@ -659,6 +834,92 @@ The default for this value is 'benchmarks'.
 "#,
     );
 
+    pub const ALLOW_DOWNLOAD: Usage = Usage::new(
+        "--allow-download",
+        "Permit downloading haystacks that are defined by a URL.",
+        r#"
+Permit downloading haystacks that are defined by a URL.
+
+Some haystacks are too big to include in the rebar repository, so a benchmark
+definition may instead give a URL, a SHA-256 checksum and a 'path-cache'
+inside of '{{dir}}/haystacks/downloads/'. When the file at 'path-cache' exists
+and matches the checksum, it's used as is. Otherwise, loading the benchmark
+fails unless this flag is given, in which case the haystack is downloaded
+(with 'curl'), its checksum is verified and it's written to 'path-cache'.
+
+rebar never accesses the network unless this flag is given. A checksum
+mismatch on a downloaded haystack is always an error.
+"#,
+    );
+
+    pub const LOCKED: Usage = Usage::new(
+        "--locked",
+        "Fail when a regex engine's version isn't its expected version.",
+        r#"
+Fail when a regex engine's version doesn't match its expected version.
+
+A regex engine may declare an 'expected-version' in 'engines.toml', which is
+either an exact version string or a regex that must match the entire version
+string. By default, a regex engine whose version doesn't match is reported
+with a warning. When this flag is given, it's an error instead. This is useful
+for making sure that measurements taken on different machines use the same
+versions of every regex engine.
+
+Use 'rebar version --engines' to see the version of every regex engine along
+with its expected version.
+"#,
+    );
+
+    pub const NO_CACHE: Usage = Usage::new(
+        "--no-cache",
+        "Don't use the cache of haystacks and regexes.",
+        r#"
+Don't read from or write to the cache of transformed haystacks and regexes.
+
+By default, after a haystack or regex file is read and transformed according
+to a benchmark definition, the result is written to '{{dir}}/.rebar-cache/'.
+Subsequent loads of the same haystack or regex (with the same transformations)
+read from the cache instead, so long as the contents of the source file haven't
+changed.
+
+This flag disables the cache entirely. It is an escape hatch for cases where
+the cache is suspected of being wrong. The cache can be deleted with
+'rebar clean --cache'.
+"#,
+    );
+
+    pub const NO_VERSION_CACHE: Usage = Usage::new(
+        "--no-version-cache",
+        "Don't use the cache of regex engine versions.",
+        r#"
+Don't read from or write to the cache of regex engine versions.
+
+By default, after the version of a regex engine is detected by running its
+version command, the result is written to '{{dir}}/.rebar-version-cache.json'.
+Subsequent loads of the same regex engine use the cached version instead, so
+long as the modification time of the engine's runner program hasn't changed
+and the cached version is younger than --version-cache-ttl.
+
+This flag disables the version cache entirely, which forces every regex
+engine's version command to run. The version cache can be deleted with
+'rebar clean --cache'.
+"#,
+    );
+
+    pub const VERSION_CACHE_TTL: Usage = Usage::new(
+        "--version-cache-ttl <duration>",
+        "How long a cached regex engine version is valid. (default: 1h)",
+        r#"
+How long a cached regex engine version remains valid after it was detected.
+Once a cached version is older than this, the regex engine's version command is
+run again. The default is 1 hour. Setting this to 0 has the same effect as
+--no-version-cache, except that detected versions are still written to the
+cache.
+
+The duration is specified in the same format as --max-time, e.g., '30m'.
+"#,
+    );
+
     pub const MAX_ITERS: Usage = Usage::new(
         "--max-iters <number>",
         "The max number of iterations to run.",
@@ -675,6 +936,9 @@ will be executed for. In general, one should not need to change this, as it
 would be better to tweak --max-time instead. However, it is exposed in case
 it's useful, and in particular, you might want to increase it in certain
 circumstances for an usually fast routine.
+
+Like --max-time, a benchmark definition may override the default with its own
+'max-iters' field, but this flag takes precedence when given.
 "#,
     );
 
@@ -706,8 +970,14 @@ how long one wants each benchmark to run for. This flag sets that time.
 In general, unless a benchmark is unusually fast, one should generally expect
 each benchmark to take roughly this amount of time to complete.
 
-The format for this flag is a duration specified in seconds, milliseconds,
-microseconds or nanoseconds. Namely, '^[0-9]+(s|ms|us|ns)$'.
+Some benchmarks need a much bigger (or smaller) budget than others. So a
+benchmark definition may override the default budgets for itself with the
+'max-time', 'max-warmup-time', 'max-iters' and 'max-warmup-iters' fields. Any
+of the corresponding flags take precedence over these overrides when given.
+
+The format for this flag is an integer or decimal number followed by one of
+the units 'h', 'm', 's', 'ms', 'us' (or 'µs') or 'ns'. For example, '2m',
+'90s' or '1.5ms'. A number without units is an error, except for '0'.
 "#,
     );
 
@@ -728,6 +998,10 @@ to give CPUs a chance to adjust their clock speeds up. The idea here is that a
 As a general rule of thumb, warmup time should be one half the benchmark time.
 Indeed, if this is not given, it automatically defaults to half the benchmark
 time.
+
+For runner programs that report it, the number of warmup iterations that were
+actually executed is recorded in the 'warmup_iters' column of each measurement.
+This is useful for checking whether this limit cut warmup short.
 "#,
     );
 
@@ -769,7 +1043,7 @@ time.
         let mut result = String::new();
         for (i, usage) in usages.iter().enumerate() {
             if i > 0 {
-                writeln!(result, "").unwrap();
+                writeln!(result).unwrap();
             }
             writeln!(result, "    {}", usage.format).unwrap();
             for (i, paragraph) in usage.long.trim().split("\n\n").enumerate() {
@@ -786,3 +1060,73 @@ time.
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_translation() {
+        assert_eq!(r"^curated/.*$", glob_to_regex("curated/*"));
+        assert_eq!(r"^.*/sherlock$", glob_to_regex("*/sherlock"));
+        assert_eq!(r"^.*$", glob_to_regex("*"));
+        assert_eq!(r"^rust/regex/.ite$", glob_to_regex("rust/regex/?ite"));
+        assert_eq!(r"^pcre2\.jit$", glob_to_regex("pcre2.jit"));
+        assert_eq!(r"^a\+b\(c\)\[d\]$", glob_to_regex("a+b(c)[d]"));
+        assert_eq!(r"^$", glob_to_regex(""));
+    }
+
+    #[test]
+    fn glob_filter() {
+        let f = Filter::from_pattern("glob:curated/*").unwrap();
+        assert!(f.include("curated/01-literal/sherlock-en"));
+        assert!(!f.include("test/curated/foo"));
+
+        let f = Filter::from_pattern("glob:*-en").unwrap();
+        assert!(f.include("curated/01-literal/sherlock-en"));
+        assert!(!f.include("curated/01-literal/sherlock-en-casei"));
+
+        // Dots are literal in globs, but not in regexes.
+        let f = Filter::from_pattern("glob:rust/regex.meta").unwrap();
+        assert!(f.include("rust/regex.meta"));
+        assert!(!f.include("rust/regex/meta"));
+        let f = Filter::from_pattern("rust/regex.meta").unwrap();
+        assert!(f.include("rust/regex/meta"));
+    }
+
+    #[test]
+    fn whitelist_index() {
+        let mut f = Filter::default();
+        f.whitelist("^pcre2").unwrap();
+        f.blacklist("jit").unwrap();
+        f.whitelist("^rust/").unwrap();
+        f.whitelist("regex").unwrap();
+        assert_eq!(Some(0), f.whitelist_index("pcre2/jit"));
+        assert_eq!(Some(1), f.whitelist_index("rust/regex"));
+        assert_eq!(Some(2), f.whitelist_index("go/regexp"));
+        assert_eq!(None, f.whitelist_index("re2"));
+    }
+
+    #[test]
+    fn patterns_file() {
+        let dir = std::env::temp_dir()
+            .join(format!("rebar-filter-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("ok.txt");
+        std::fs::write(&path, "# comment\n\n  glob:curated/*  \nx\n").unwrap();
+        let mut f = Filter::default();
+        f.patterns_file(&path, false).unwrap();
+        f.patterns_file(&path, true).unwrap();
+        assert_eq!(4, f.rules.len());
+
+        let path = dir.join("bad.txt");
+        std::fs::write(&path, "curated\n# comment\n(\n").unwrap();
+        let mut f = Filter::default();
+        let err = f.patterns_file(&path, false).unwrap_err();
+        let msg = format!("{:#}", err);
+        assert!(msg.contains(&format!("{}:3", path.display())), "{}", msg);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,11 +1,25 @@
-use std::{io::Write, path::PathBuf};
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc,
+    },
+    time::{Duration, Instant},
+};
 
-use {anyhow::Context, bstr::ByteSlice, lexopt::Arg};
+use {
+    anyhow::Context,
+    bstr::{BString, ByteSlice},
+    lexopt::Arg,
+    unicode_width::UnicodeWidthStr,
+};
 
 use crate::{
     args::{self, Color, Filter, Usage},
-    format::benchmarks::{Engine, Engines},
-    util,
+    format::benchmarks::{Engine, Engines, LoadOptions},
+    util::{self, ShortHumanDuration},
 };
 
 const USAGES: &[Usage] = &[
@@ -13,6 +27,53 @@ const USAGES: &[Usage] = &[
     Color::USAGE,
     Filter::USAGE_ENGINE,
     Filter::USAGE_ENGINE_NOT,
+    Usage::new(
+        "--fail-fast",
+        "Stop starting new builds after the first failure.",
+        r#"
+When a regex engine fails to build, don't start building any more regex
+engines. Builds that are already in progress are allowed to finish. Regex
+engines that were never built are reported as skipped.
+
+By default, a failure to build one regex engine has no effect on the others.
+"#,
+    ),
+    Usage::new(
+        "-j, --jobs <number>",
+        "The number of regex engines to build concurrently.",
+        r#"
+The number of regex engines to build concurrently.
+
+Each regex engine's build steps are always run one after the other. This only
+controls how many distinct regex engines are built at the same time. Note that
+some build steps (like 'cargo build') are themselves parallel, so setting this
+too high may not help much.
+
+Regex engines that share a working directory (the 'cwd' in 'engines.toml') are
+never built at the same time, since their build steps typically write to the
+same place (e.g., a single 'target' directory). Instead, all regex engines with
+the same working directory are built one after the other by the same job.
+
+The default is 1.
+"#,
+    ),
+    Usage::new(
+        "--log-dir <dir>",
+        "The directory to write build logs to.",
+        r#"
+The directory to write build logs to. The directory is created if it doesn't
+exist.
+
+The stdout and stderr of every command run to build a regex engine are written
+to a log file named '<engine>.log' in this directory, where any '/' in the
+engine name is replaced with '-'. Existing log files are overwritten.
+
+By default, logs are written to a fresh directory inside the system's temporary
+directory. The path to a regex engine's log file is printed when it fails to
+build.
+"#,
+    ),
+    Usage::LOCKED,
 ];
 
 fn usage_short() -> String {
@@ -21,7 +82,7 @@ fn usage_short() -> String {
 This command builds runner programs that expose regex engines to rebar.
 
 USAGE:
-    rebar build [-e <engine> ...]
+    rebar build [-e <engine> ...] [-j <number>]
 
 TIP:
     use -h for short docs and --help for long docs
@@ -46,19 +107,32 @@ that the runner program has been successfully built and can be executed in the
 current environment.
 
 If building a runner program fails, then a short error message is printed.
-Building then continues with the other runner programs. Rebar in general does
-*not* need to have every runner program build successfully in order to run.
-If a runner program fails to build, then collecting measurements will show
-an error. But those can be squashed with the -i/--ignore-missing-engines flag.
+Building then continues with the other runner programs (unless --fail-fast is
+given). Rebar in general does *not* need to have every runner program build
+successfully in order to run. If a runner program fails to build, then
+collecting measurements will show an error. But those can be squashed with the
+-i/--ignore-missing-engines flag.
+
+Regex engines that set 'supports-validate = true' in 'engines.toml' are also
+validated after they're built. That is, their runner program is run with the
+'--validate' flag, which runs a tiny built-in benchmark and fails if it doesn't
+produce the expected result. This catches runner programs that build but
+don't work. Validation failures are reported separately from build failures.
 
-If a regex engine fails to build, then running this command again with the
-environment variable RUSTLOG set to 'debug' will show more output from the
-failing commands.
+The output of every command run to build a regex engine is written to a log
+file. If a regex engine fails to build, then the path to its log file is
+printed. See the --log-dir flag for more details.
 
-Use the -e/--engine flag to build a subset of engines.
+Once every regex engine has been built (or failed to build), a table is
+printed showing the status of each one along with how long it took to build,
+with the slowest first. If any regex engine failed to build, then this
+command exits with an error.
+
+Use the -e/--engine flag to build a subset of engines, and the -j/--jobs flag
+to build multiple engines at the same time.
 
 USAGE:
-    rebar build [-e <engine> ...]
+    rebar build [-e <engine> ...] [-j <number>]
 
 OPTIONS:
 {options}
@@ -71,140 +145,134 @@ OPTIONS:
 
 pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
     let c = Config::parse(p)?;
-    let engines =
-        Engines::from_file(&c.dir, |e| c.engine_filter.include(&e.name))?;
+    let engines = Engines::from_file(&c.dir, &LoadOptions::default(), |e| {
+        c.engine_filter.include(&e.name)
+    })?;
+    let log_dir = match c.log_dir {
+        Some(ref dir) => dir.clone(),
+        None => std::env::temp_dir()
+            .join(format!("rebar-build-{}", std::process::id())),
+    };
+    std::fs::create_dir_all(&log_dir)
+        .with_context(|| format!("failed to create {}", log_dir.display()))?;
 
-    let mut printed_note = false;
+    let mut builds: Vec<Build> = vec![];
     let mut printed_dep_note = false;
     let mut out = std::io::stdout().lock();
     let mut stderr = c.color.stderr();
-    'ENGINES: for e in engines.list.iter() {
-        for dep in e.dependency.iter() {
-            let mut stdcmd = dep.run.command()?;
-            let out = match util::output(&mut stdcmd) {
-                Ok(out) => out,
-                Err(err) => {
-                    util::colorize_label(&mut stderr, |w| {
-                        write!(w, "{}: ", e.name)
-                    })?;
-                    util::colorize_error(&mut stderr, |w| {
-                        write!(w, "dependency command failed: ")
-                    })?;
-                    writeln!(stderr, "{}", err)?;
-                    print_dep_note(&mut stderr, e, &mut printed_dep_note)?;
-                    print_note(&mut stderr, e, &mut printed_note)?;
-                    continue 'ENGINES;
+    let (send, recv) = mpsc::channel();
+    let groups = group_by_cwd(&engines.list);
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    std::thread::scope(|scope| -> anyhow::Result<()> {
+        for _ in 0..c.jobs {
+            let send = send.clone();
+            let (c, engines, log_dir) = (&c, &engines, &log_dir);
+            let (groups, next, stop) = (&groups, &next, &stop);
+            scope.spawn(move || {
+                while let Some(group) =
+                    groups.get(next.fetch_add(1, Ordering::SeqCst))
+                {
+                    for &i in group.iter() {
+                        let e = &engines.list[i];
+                        let build = if stop.load(Ordering::SeqCst) {
+                            Build::skipped(e, log_path(log_dir, e))
+                        } else {
+                            let log = log_path(log_dir, e);
+                            Build::run(e, log, c.locked, |cmd| {
+                                // If the receiver is gone, then the main
+                                // thread has hit an error and there's nobody
+                                // left to tell.
+                                let _ = send.send(Event::Running(i, cmd));
+                            })
+                        };
+                        if c.fail_fast && build.is_failure() {
+                            stop.store(true, Ordering::SeqCst);
+                        }
+                        if send.send(Event::Done(build)).is_err() {
+                            return;
+                        }
+                    }
                 }
-            };
-            let outstr = match out.to_str() {
-                Ok(outstr) => outstr,
-                Err(err) => {
+            });
+        }
+        // Drop our sender so that the loop below stops once all of the
+        // workers have finished.
+        drop(send);
+        for event in recv {
+            match event {
+                Event::Running(i, cmd) => {
+                    let e = &engines.list[i];
                     util::colorize_label(&mut stderr, |w| {
                         write!(w, "{}: ", e.name)
                     })?;
-                    util::colorize_error(&mut stderr, |w| {
-                        write!(
-                            w,
-                            "dependency command output is not UTF-8: {}",
-                            err,
-                        )
-                    })?;
-                    print_dep_note(&mut stderr, e, &mut printed_dep_note)?;
-                    print_note(&mut stderr, e, &mut printed_note)?;
-                    continue 'ENGINES;
+                    writeln!(out, "running: {}", cmd)?;
                 }
-            };
-            if let Some(ref re) = dep.regex {
-                if !re.is_match(outstr) {
-                    util::colorize_label(&mut stderr, |w| {
-                        write!(w, "{}: ", e.name)
-                    })?;
-                    util::colorize_error(&mut stderr, |w| {
-                        write!(
-                            w,
-                            "dependency command did not \
-                             print expected output: ",
-                        )
-                    })?;
-                    writeln!(
-                        stderr,
-                        "could not find match for {:?} in output of {:?}",
-                        re.as_str(),
-                        stdcmd,
+                Event::Done(build) => {
+                    build.print(
+                        &mut out,
+                        &mut stderr,
+                        &mut printed_dep_note,
                     )?;
-                    print_dep_note(&mut stderr, e, &mut printed_dep_note)?;
-                    print_note(&mut stderr, e, &mut printed_note)?;
-                    if out.trim_with(|c| c.is_whitespace()).is_empty() {
-                        log::debug!(
-                            "output for dependency command {:?}: <EMPTY>",
-                            stdcmd,
-                        );
-                    } else {
-                        log::debug!(
-                            "output for dependency command {:?}: {}",
-                            stdcmd,
-                            out,
-                        );
-                    }
-                    continue 'ENGINES;
+                    builds.push(build);
                 }
             }
         }
-        if e.build.is_empty() {
-            if e.is_missing_version() {
-                util::colorize_label(&mut stderr, |w| {
-                    write!(w, "{}: ", e.name)
-                })?;
-                util::colorize_error(&mut stderr, |w| {
-                    writeln!(w, "no build steps, but version is missing")
-                })?;
-                print_note(&mut stderr, e, &mut printed_note)?;
-            } else {
-                util::colorize_label(&mut stderr, |w| {
-                    write!(w, "{}: ", e.name)
-                })?;
-                writeln!(out, "nothing to do")?;
-            }
-            continue;
-        }
-        for cmd in e.build.iter() {
-            let mut stdcmd = cmd.command()?;
-            util::colorize_label(&mut stderr, |w| write!(w, "{}: ", e.name))?;
-            writeln!(out, "running: {:?}", stdcmd)?;
-            let out = match util::output(&mut stdcmd) {
-                Ok(out) => out,
-                Err(err) => {
-                    util::colorize_label(&mut stderr, |w| {
-                        write!(w, "{}: ", e.name)
-                    })?;
-                    util::colorize_error(&mut stderr, |w| {
-                        write!(w, "build failed: ")
-                    })?;
-                    writeln!(stderr, "{}", err)?;
-                    print_note(&mut stderr, e, &mut printed_note)?;
-                    continue 'ENGINES;
-                }
-            };
-            log::trace!("stdout: {:?}", out);
-        }
-        let version = e.version_config.get()?;
-        util::colorize_label(&mut stderr, |w| write!(w, "{}: ", e.name))?;
-        writeln!(out, "build complete for version {}", version)?;
-    }
+        Ok(())
+    })?;
+
+    builds.sort_by_key(|b| std::cmp::Reverse(b.duration));
+    writeln!(out)?;
+    write_summary(&mut out, &builds)?;
+    let invalid = builds.iter().filter(|b| b.is_invalid()).count();
+    let failed = builds.iter().filter(|b| b.is_failure()).count() - invalid;
+    anyhow::ensure!(
+        invalid == 0,
+        "{} of {} regex engines failed to build and {} failed validation \
+         (logs are in {})",
+        failed,
+        builds.len(),
+        invalid,
+        log_dir.display(),
+    );
+    anyhow::ensure!(
+        failed == 0,
+        "{} of {} regex engines failed to build (logs are in {})",
+        failed,
+        builds.len(),
+        log_dir.display(),
+    );
     Ok(())
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug)]
 struct Config {
     dir: PathBuf,
     engine_filter: Filter,
     color: Color,
+    jobs: usize,
+    log_dir: Option<PathBuf>,
+    fail_fast: bool,
+    locked: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            dir: PathBuf::from("benchmarks"),
+            engine_filter: Filter::default(),
+            color: Color::default(),
+            jobs: 1,
+            log_dir: None,
+            fail_fast: false,
+            locked: false,
+        }
+    }
 }
 
 impl Config {
     fn parse(p: &mut lexopt::Parser) -> anyhow::Result<Config> {
         let mut c = Config::default();
-        c.dir = PathBuf::from("benchmarks");
         while let Some(arg) = p.next()? {
             match arg {
                 Arg::Short('h') => anyhow::bail!("{}", usage_short()),
@@ -221,6 +289,23 @@ impl Config {
                 Arg::Short('E') | Arg::Long("engine-not") => {
                     c.engine_filter.arg_blacklist(p, "-E/--engine-not")?;
                 }
+                Arg::Long("fail-fast") => {
+                    c.fail_fast = true;
+                }
+                Arg::Short('j') | Arg::Long("jobs") => {
+                    c.jobs = args::parse(p, "-j/--jobs")?;
+                    anyhow::ensure!(
+                        c.jobs > 0,
+                        "-j/--jobs must be greater than zero",
+                    );
+                }
+                Arg::Long("log-dir") => {
+                    let dir = p.value().context("--log-dir")?;
+                    c.log_dir = Some(PathBuf::from(dir));
+                }
+                Arg::Long("locked") => {
+                    c.locked = true;
+                }
                 _ => return Err(arg.unexpected().into()),
             }
         }
@@ -228,9 +313,373 @@ impl Config {
     }
 }
 
+/// A message sent from a thread building regex engines to the main thread,
+/// which is responsible for printing everything.
+#[derive(Debug)]
+enum Event {
+    /// The engine at the given index in the list of engines is about to run
+    /// the given command.
+    Running(usize, String),
+    /// An engine has finished building, successfully or not.
+    Done(Build),
+}
+
+/// The outcome of building a single regex engine.
+#[derive(Debug)]
+struct Build {
+    /// The name of the regex engine.
+    name: String,
+    /// What happened.
+    status: BuildStatus,
+    /// The file containing the output of every command that was run.
+    log: PathBuf,
+    /// The wall-clock time it took to run every command.
+    duration: Duration,
+}
+
+#[derive(Debug)]
+enum BuildStatus {
+    /// The build succeeded and the runner program reported this version.
+    Complete(String),
+    /// There were no build steps and a version was found.
+    NothingToDo,
+    /// The build was never started because --fail-fast was given and some
+    /// other regex engine failed to build.
+    Skipped,
+    /// The build failed.
+    Failed(BuildFailure),
+}
+
+#[derive(Debug)]
+struct BuildFailure {
+    /// A short description of what failed.
+    what: &'static str,
+    /// More details about the failure, if any.
+    detail: Option<String>,
+    /// Whether the failure was in a dependency check, as opposed to one of
+    /// the build steps.
+    dependency: bool,
+    /// Whether the build succeeded but the runner program then failed its
+    /// '--validate' self-test.
+    validation: bool,
+}
+
+impl Build {
+    /// Check the dependencies of the given regex engine and run its build
+    /// steps. The output of every command is written to the log path given.
+    /// The `running` callback is called with a description of each build step
+    /// just before it is executed.
+    ///
+    /// When `locked` is true, a version that doesn't match the engine's
+    /// expected version is a build failure instead of a warning.
+    fn run(
+        e: &Engine,
+        log: PathBuf,
+        locked: bool,
+        running: impl FnMut(String),
+    ) -> Build {
+        let start = Instant::now();
+        let status = match BuildStatus::run(e, &log, locked, running) {
+            Ok(status) => status,
+            Err(err) => BuildStatus::Failed(BuildFailure {
+                what: "build failed",
+                detail: Some(format!("{:#}", err)),
+                dependency: false,
+                validation: false,
+            }),
+        };
+        Build { name: e.name.clone(), status, log, duration: start.elapsed() }
+    }
+
+    /// A build that was skipped.
+    fn skipped(e: &Engine, log: PathBuf) -> Build {
+        Build {
+            name: e.name.clone(),
+            status: BuildStatus::Skipped,
+            log,
+            duration: Duration::ZERO,
+        }
+    }
+
+    fn is_failure(&self) -> bool {
+        matches!(self.status, BuildStatus::Failed(_))
+    }
+
+    /// Returns true if this engine was built but failed validation.
+    fn is_invalid(&self) -> bool {
+        matches!(self.status, BuildStatus::Failed(ref f) if f.validation)
+    }
+
+    /// Print the outcome of this build, in the same format used when each
+    /// build step is printed.
+    fn print<W: termcolor::WriteColor>(
+        &self,
+        mut out: impl Write,
+        mut stderr: W,
+        printed_dep_note: &mut bool,
+    ) -> anyhow::Result<()> {
+        util::colorize_label(&mut stderr, |w| write!(w, "{}: ", self.name))?;
+        match self.status {
+            BuildStatus::Complete(ref version) => {
+                writeln!(out, "build complete for version {}", version)?;
+            }
+            BuildStatus::NothingToDo => {
+                writeln!(out, "nothing to do")?;
+            }
+            BuildStatus::Skipped => {
+                writeln!(out, "skipped because of --fail-fast")?;
+            }
+            BuildStatus::Failed(ref fail) => {
+                match fail.detail {
+                    None => util::colorize_error(&mut stderr, |w| {
+                        writeln!(w, "{}", fail.what)
+                    })?,
+                    Some(ref detail) => {
+                        util::colorize_error(&mut stderr, |w| {
+                            write!(w, "{}: ", fail.what)
+                        })?;
+                        writeln!(stderr, "{}", detail)?;
+                    }
+                }
+                if fail.dependency {
+                    print_dep_note(&mut stderr, &self.name, printed_dep_note)?;
+                }
+                util::colorize_note(&mut stderr, |w| write!(w, "note: "))?;
+                writeln!(
+                    stderr,
+                    "the output of every command run to build '{}' is in {}",
+                    self.name,
+                    self.log.display(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BuildStatus {
+    /// Does the actual work of checking dependencies and building a regex
+    /// engine. An error is only returned for failures unrelated to running
+    /// commands, e.g., failing to create the log file.
+    fn run(
+        e: &Engine,
+        log: &Path,
+        locked: bool,
+        mut running: impl FnMut(String),
+    ) -> anyhow::Result<BuildStatus> {
+        let mut log = File::create(log)
+            .with_context(|| format!("failed to create {}", log.display()))?;
+        for dep in e.dependency.iter() {
+            let mut stdcmd = dep.run.command()?;
+            let out = match output(&mut stdcmd, &mut log)? {
+                Ok(out) => out,
+                Err(err) => {
+                    return Ok(BuildStatus::dep_failure(
+                        "dependency command failed",
+                        err.to_string(),
+                    ))
+                }
+            };
+            let outstr = match out.to_str() {
+                Ok(outstr) => outstr,
+                Err(err) => {
+                    return Ok(BuildStatus::dep_failure(
+                        "dependency command output is not UTF-8",
+                        err.to_string(),
+                    ))
+                }
+            };
+            if let Some(ref re) = dep.regex {
+                if !re.is_match(outstr) {
+                    return Ok(BuildStatus::dep_failure(
+                        "dependency command did not print expected output",
+                        format!(
+                            "could not find match for {:?} in output of {:?}",
+                            re.as_str(),
+                            stdcmd,
+                        ),
+                    ));
+                }
+            }
+        }
+        if e.build.is_empty() {
+            if e.is_missing_version() {
+                return Ok(BuildStatus::Failed(BuildFailure {
+                    what: "no build steps, but version is missing",
+                    detail: None,
+                    dependency: false,
+                    validation: false,
+                }));
+            }
+            // A mismatch without --locked was already reported when the
+            // engines were loaded.
+            if locked {
+                if let Err(err) = e.check_version(&e.version) {
+                    return Ok(BuildStatus::version_failure(err));
+                }
+            }
+            return Ok(BuildStatus::NothingToDo);
+        }
+        for cmd in e.build.iter() {
+            let mut stdcmd = cmd.command()?;
+            running(format!("{:?}", stdcmd));
+            if let Err(err) = output(&mut stdcmd, &mut log)? {
+                return Ok(BuildStatus::Failed(BuildFailure {
+                    what: "build failed",
+                    detail: Some(err.to_string()),
+                    dependency: false,
+                    validation: false,
+                }));
+            }
+        }
+        let version = e.version_config.get()?;
+        if let Err(err) = e.check_version(&version) {
+            if locked {
+                return Ok(BuildStatus::version_failure(err));
+            }
+            log::warn!("{:#}", err);
+        }
+        if e.supports_validate {
+            let mut stdcmd = e.run.command()?;
+            stdcmd.arg("--validate");
+            running(format!("{:?}", stdcmd));
+            if let Err(err) = output(&mut stdcmd, &mut log)? {
+                return Ok(BuildStatus::Failed(BuildFailure {
+                    what: "validation failed",
+                    detail: Some(err.to_string()),
+                    dependency: false,
+                    validation: true,
+                }));
+            }
+        }
+        Ok(BuildStatus::Complete(version))
+    }
+
+    fn version_failure(err: anyhow::Error) -> BuildStatus {
+        BuildStatus::Failed(BuildFailure {
+            what: "unexpected version",
+            detail: Some(format!("{:#}", err)),
+            dependency: false,
+            validation: false,
+        })
+    }
+
+    fn dep_failure(what: &'static str, detail: String) -> BuildStatus {
+        BuildStatus::Failed(BuildFailure {
+            what,
+            detail: Some(detail),
+            dependency: true,
+            validation: false,
+        })
+    }
+
+    /// A short label for this status, suitable for the summary table.
+    fn label(&self) -> &'static str {
+        match *self {
+            BuildStatus::Complete(_) => "ok",
+            BuildStatus::NothingToDo => "nothing to do",
+            BuildStatus::Skipped => "skipped",
+            BuildStatus::Failed(ref f) if f.validation => "INVALID",
+            BuildStatus::Failed(_) => "FAILED",
+        }
+    }
+}
+
+/// Runs the given command synchronously and appends its stdout and stderr to
+/// the given log. If the command could not be run, then the outer error is
+/// returned. If it ran but failed, then the inner error is returned with the
+/// last line of stderr (just like `util::output`). Otherwise, its stdout is
+/// returned.
+fn output(
+    cmd: &mut std::process::Command,
+    log: &mut File,
+) -> anyhow::Result<anyhow::Result<BString>> {
+    log::debug!("running command: {:?}", cmd);
+    writeln!(log, "$ {:?}", cmd)?;
+    let out = match cmd.output() {
+        Ok(out) => out,
+        Err(err) => {
+            writeln!(log, "failed to run command: {}", err)?;
+            return Ok(Err(anyhow::Error::from(err)
+                .context("failed to run command and wait for output")));
+        }
+    };
+    log.write_all(&out.stdout)?;
+    log.write_all(&out.stderr)?;
+    writeln!(log, "exit status: {:?}\n", out.status)?;
+    if out.status.success() {
+        return Ok(Ok(BString::from(out.stdout)));
+    }
+    let last = match out.stderr.lines().next_back() {
+        Some(last) => last,
+        None => {
+            return Ok(Err(anyhow::anyhow!(
+                "command failed with {:?} but stderr is empty",
+                out.status,
+            )))
+        }
+    };
+    Ok(Err(anyhow::anyhow!(
+        "command failed, last line of stderr: {:?}",
+        last.as_bstr(),
+    )))
+}
+
+/// Returns the path to the log file for the given regex engine.
+fn log_path(log_dir: &Path, e: &Engine) -> PathBuf {
+    log_dir.join(format!("{}.log", e.name.replace('/', "-")))
+}
+
+/// Groups the given regex engines by their working directory, such that each
+/// group can be built independently of the others. Each group is a list of
+/// indices into the engines given.
+///
+/// Groups are returned in the order in which their first engine appears, and
+/// the engines in each group retain their relative order. Working directories
+/// are canonicalized when possible, so that two different paths to the same
+/// directory are put in the same group.
+fn group_by_cwd(engines: &[Engine]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<(Option<PathBuf>, Vec<usize>)> = vec![];
+    for (i, e) in engines.iter().enumerate() {
+        let cwd = e.cwd.as_ref().map(|cwd| {
+            std::fs::canonicalize(cwd).unwrap_or_else(|_| PathBuf::from(cwd))
+        });
+        match groups.iter_mut().find(|(c, _)| *c == cwd) {
+            Some((_, group)) => group.push(i),
+            None => groups.push((cwd, vec![i])),
+        }
+    }
+    groups.into_iter().map(|(_, group)| group).collect()
+}
+
+/// Writes a table summarizing every build, in the order given.
+fn write_summary(wtr: impl Write, builds: &[Build]) -> anyhow::Result<()> {
+    let mut wtr = tabwriter::TabWriter::new(wtr);
+    let columns = &["Engine", "Status", "Time"];
+    writeln!(wtr, "{}", columns.join("\t"))?;
+    for (i, label) in columns.iter().enumerate() {
+        if i > 0 {
+            write!(wtr, "\t")?;
+        }
+        util::write_divider(&mut wtr, '-', label.width())?;
+    }
+    writeln!(wtr)?;
+    for b in builds.iter() {
+        writeln!(
+            wtr,
+            "{}\t{}\t{}",
+            b.name,
+            b.status.label(),
+            ShortHumanDuration::from(b.duration),
+        )?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
 fn print_dep_note<W: termcolor::WriteColor>(
     mut wtr: W,
-    engine: &Engine,
+    name: &str,
     printed: &mut bool,
 ) -> anyhow::Result<()> {
     if *printed {
@@ -242,26 +691,39 @@ fn print_dep_note<W: termcolor::WriteColor>(
         "a dependency that is required to build '{}' could \
          not be found, either because it isn't installed \
          or because it didn't behave as expected",
-        engine.name,
+        name,
     )?;
     *printed = true;
     Ok(())
 }
 
-fn print_note<W: termcolor::WriteColor>(
-    mut wtr: W,
-    engine: &Engine,
-    printed: &mut bool,
-) -> anyhow::Result<()> {
-    if *printed {
-        return Ok(());
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (a, b) = (tmp.path().join("a"), tmp.path().join("b"));
+        std::fs::create_dir(&a).unwrap();
+        std::fs::create_dir(&b).unwrap();
+
+        let engine = |name: &str, cwd: &Path| {
+            let mut e = Engine::external(name, "1.0.0");
+            e.cwd = Some(cwd.to_str().unwrap().to_string());
+            e
+        };
+        let engines = vec![
+            engine("one", &a),
+            engine("two", &b),
+            // A different path to the same directory as 'one'.
+            engine("three", &b.join("..").join("a")),
+            engine("four", &b),
+            engine("five", &tmp.path().join("does-not-exist")),
+        ];
+        assert_eq!(
+            vec![vec![0, 2], vec![1, 3], vec![4]],
+            group_by_cwd(&engines),
+        );
     }
-    util::colorize_note(&mut wtr, |w| write!(w, "note: "))?;
-    writeln!(
-        wtr,
-        "run `RUST_LOG=debug rebar build -e '^{}$'` to see more details",
-        engine.name,
-    )?;
-    *printed = true;
-    Ok(())
 }
@@ -4,12 +4,40 @@ use {anyhow::Context, lexopt::Arg};
 
 use crate::{
     args::{Filter, Usage},
-    format::benchmarks::Engines,
+    format::{
+        benchmarks::{Engine, Engines, LoadOptions, VersionCache},
+        cache::Cache,
+    },
     util,
 };
 
-const USAGES: &[Usage] =
-    &[Usage::BENCH_DIR, Filter::USAGE_ENGINE, Filter::USAGE_ENGINE_NOT];
+const USAGES: &[Usage] = &[
+    Usage::new(
+        "--cache",
+        "Remove the haystack, regex and version caches instead.",
+        r#"
+Remove the cache of transformed haystacks and regexes in
+'{{dir}}/.rebar-cache/' and the cache of regex engine versions in
+'{{dir}}/.rebar-version-cache.json' instead of running the clean commands for
+each regex engine.
+"#,
+    ),
+    Usage::BENCH_DIR,
+    Usage::new(
+        "--dry-run",
+        "Print the clean commands instead of running them.",
+        r#"
+Print the clean commands that would be run for each regex engine, including
+their working directory and environment, instead of running them. Nothing is
+removed.
+
+This is useful for checking what would be removed when combined with
+-e/--engine.
+"#,
+    ),
+    Filter::USAGE_ENGINE,
+    Filter::USAGE_ENGINE_NOT,
+];
 
 fn usage() -> String {
     format!(
@@ -17,8 +45,13 @@ fn usage() -> String {
 This removes the artifacts produced by 'rebar build'. This is useful for cases
 where one wants to rebuild one or more regex engines after starting fresh.
 
+A failure to clean one regex engine doesn't stop the others from being
+cleaned. Once every regex engine has been tried, a summary is printed and an
+error is returned if any of them failed.
+
 USAGE:
-    rebar clean [-e <engine> ...]
+    rebar clean [-e <engine> ...] [--dry-run]
+    rebar clean --cache
 
 OPTIONS:
 {options}
@@ -31,22 +64,89 @@ OPTIONS:
 
 pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
     let c = Config::parse(p)?;
-    let engines =
-        Engines::from_file(&c.dir, |e| c.engine_filter.include(&e.name))?;
+    if c.cache {
+        for path in [Cache::dir(&c.dir), VersionCache::path(&c.dir)] {
+            if !path.exists() {
+                continue;
+            }
+            if c.dry_run {
+                writeln!(
+                    std::io::stdout(),
+                    "would remove {}",
+                    path.display()
+                )?;
+                continue;
+            }
+            writeln!(std::io::stdout(), "removing {}", path.display())?;
+            let result = if path.is_dir() {
+                std::fs::remove_dir_all(&path)
+            } else {
+                std::fs::remove_file(&path)
+            };
+            result.with_context(|| {
+                format!("failed to remove {}", path.display())
+            })?;
+        }
+        return Ok(());
+    }
+    let engines = Engines::from_file(&c.dir, &LoadOptions::default(), |e| {
+        c.engine_filter.include(&e.name)
+    })?;
 
     let mut out = std::io::stdout().lock();
+    let (mut cleaned, mut skipped, mut failed) = (0, 0, 0);
     for e in engines.list.iter() {
-        let prefix = e.name.clone();
         if e.clean.is_empty() {
+            skipped += 1;
             continue;
         }
-        for cmd in e.clean.iter() {
-            let mut proccmd = cmd.command()?;
-            writeln!(out, "{}: running: {:?}", prefix, proccmd)?;
-            let out = util::output(&mut proccmd)?;
-            log::trace!("stdout: {:?}", out);
+        match clean(&mut out, e, c.dry_run) {
+            Ok(()) => cleaned += 1,
+            Err(err) => {
+                failed += 1;
+                writeln!(
+                    std::io::stderr(),
+                    "{}: clean failed: {:#}",
+                    e.name,
+                    err
+                )?;
+            }
         }
     }
+    writeln!(
+        out,
+        "{} {} regex engines, skipped {} without clean commands, {} failed",
+        if c.dry_run { "would clean" } else { "cleaned" },
+        cleaned,
+        skipped,
+        failed,
+    )?;
+    anyhow::ensure!(
+        failed == 0,
+        "{} of {} regex engines failed to clean",
+        failed,
+        cleaned + failed,
+    );
+    Ok(())
+}
+
+/// Runs every clean command for the given regex engine, stopping at the first
+/// one that fails. When `dry_run` is enabled, the commands are only printed.
+fn clean<W: Write>(
+    mut out: W,
+    e: &Engine,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    for cmd in e.clean.iter() {
+        let mut proccmd = cmd.command()?;
+        if dry_run {
+            writeln!(out, "{}: would run: {:?}", e.name, proccmd)?;
+            continue;
+        }
+        writeln!(out, "{}: running: {:?}", e.name, proccmd)?;
+        let stdout = util::output(&mut proccmd)?;
+        log::trace!("stdout: {:?}", stdout);
+    }
     Ok(())
 }
 
@@ -54,20 +154,28 @@ pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
 struct Config {
     dir: PathBuf,
     engine_filter: Filter,
+    cache: bool,
+    dry_run: bool,
 }
 
 impl Config {
     fn parse(p: &mut lexopt::Parser) -> anyhow::Result<Config> {
-        let mut c = Config::default();
-        c.dir = PathBuf::from("benchmarks");
+        let mut c =
+            Config { dir: PathBuf::from("benchmarks"), ..Config::default() };
         while let Some(arg) = p.next()? {
             match arg {
                 Arg::Short('h') | Arg::Long("help") => {
                     anyhow::bail!("{}", usage())
                 }
+                Arg::Long("cache") => {
+                    c.cache = true;
+                }
                 Arg::Short('d') | Arg::Long("dir") => {
                     c.dir = PathBuf::from(p.value().context("-d/--dir")?);
                 }
+                Arg::Long("dry-run") => {
+                    c.dry_run = true;
+                }
                 Arg::Short('e') | Arg::Long("engine") => {
                     c.engine_filter.arg_whitelist(p, "-e/--engine")?;
                 }
@@ -1,23 +1,130 @@
-use std::path::PathBuf;
+use std::{collections::BTreeMap, io::Write, path::PathBuf};
 
-use unicode_width::UnicodeWidthStr;
+use {anyhow::Context, unicode_width::UnicodeWidthStr};
 
 use crate::{
     args::{self, Color, Filter, Filters, Stat, ThresholdRange, Units, Usage},
-    format::measurement::MeasurementReader,
+    format::{
+        benchmarks::{Benchmarks, LoadOptions},
+        measurement::{
+            self, Measurement, MeasurementReader, StaleCheck, VersionCheck,
+        },
+    },
     grouped,
-    util::{write_divider, ShortHumanDuration},
+    util::{write_divider, HumanBytes, Rate, ShortHumanDuration},
 };
 
 const USAGES: &[Usage] = &[
+    Usage::ALLOW_DOWNLOAD,
+    VersionCheck::USAGE_ALLOW_MISMATCH,
     Color::USAGE,
+    Usage::new(
+        "-d, --dir <path>",
+        "A directory containing rebar benchmarks.",
+        r#"
+A directory containing rebar benchmarks.
+
+This is used when '-u/--units lines-per-sec' is given, since computing the
+number of lines in each benchmark's haystack requires its definition. It is
+also used to detect stale measurements (see --skip-stale). If this directory
+doesn't exist, then stale measurements aren't detected.
+
+This defaults to 'benchmarks'.
+"#,
+    ),
     Filter::USAGE_ENGINE,
     Filter::USAGE_ENGINE_NOT,
+    Filter::USAGE_ENGINE_FILE,
+    Filter::USAGE_ENGINE_NOT_FILE,
     Filter::USAGE_BENCH,
     Filter::USAGE_BENCH_NOT,
+    Filter::USAGE_BENCH_FILE,
+    Filter::USAGE_BENCH_NOT_FILE,
+    Usage::new(
+        "--format <format>",
+        "One of: human, csv, tsv.",
+        r#"
+The format to print the comparison in (default: human).
+
+The 'human' format is the table described by --layout. It is meant to be read
+by humans and its precise format may change.
+
+The 'csv' and 'tsv' formats print comma and tab separated values,
+respectively. They are meant to be pasted into a spreadsheet. The first row is
+a header with a 'benchmark' column followed by one column for each regex
+engine. (And when --ratio is given, one '<engine>_ratio' column for each regex
+engine after that.) Every other row corresponds to one benchmark. Values are
+plain numbers without units: nanoseconds for '-u time', bytes per second for
+'-u throughput', lines per second for '-u lines-per-sec', iterations per
+second for '-u iters-per-sec' and bytes for '-s size'. Like the 'human'
+format, benchmarks without throughputs use nanoseconds even when throughput
+is asked for. A missing measurement is an empty cell.
+
+The regex engine columns are sorted by name. When -e/--engine is given, they
+are instead sorted by the first -e/--engine pattern that matches them, in the
+order the patterns were given, and then by name.
+
+These formats can't be used with --layout, --row or --show-errors.
+"#,
+    ),
     MeasurementReader::USAGE_INTERSECTION,
+    Usage::new(
+        "--label <name> ...",
+        "Label the regex engines from each CSV file.",
+        r#"
+Label the regex engines of the measurements in each CSV file given. When used,
+this flag must be given once for every CSV file, and the labels correspond to
+the CSV files in the order given.
+
+Each label is added as a suffix to the names of the regex engines read from
+its file. For example, 'rebar cmp before.csv after.csv --label before --label
+after' shows the columns 'rust/regex(before)' and 'rust/regex(after)' (and so
+on for every other regex engine) side by side. Without labels, it would be an
+error for the same benchmark and regex engine to appear in both files.
+
+Ratios are still computed against the best value in each row, regardless of
+which file it came from. Filters like -e/--engine match regex engine names
+before labels are added.
+"#,
+    ),
+    Usage::new(
+        "--layout <name>",
+        "One of: wide (default), transposed or long.",
+        r#"
+This flag sets the layout of the table printed. Its value can be 'wide',
+'transposed' or 'long', where 'wide' is the default.
+
+The 'wide' layout has one row per benchmark and one column per regex engine.
+But if there are too many engines with very few benchmarks, this layout
+probably won't work well. In that case, the 'transposed' layout might make more
+sense. It has one row per regex engine and one column per benchmark.
+
+The 'long' layout has one row for every benchmark and regex engine pair, with
+four columns: the benchmark name, the regex engine name, the value being
+compared and its ratio relative to the best value for that benchmark. Pairs
+for which there is no measurement are omitted. This layout never gets wider as
+more regex engines or benchmarks are added, and it's easy to search with tools
+like grep.
+
+In every layout, the best value for each benchmark is highlighted when colors
+are enabled.
+"#,
+    ),
     Filter::USAGE_MODEL,
     Filter::USAGE_MODEL_NOT,
+    Usage::new(
+        "--ratio",
+        "Add ratio columns with --format csv or tsv.",
+        r#"
+When given with '--format csv' or '--format tsv', a '<engine>_ratio' column is
+added for each regex engine after all of the value columns. Each ratio is
+relative to the best value for the same benchmark, so the best regex engine
+has a ratio of 1. A ratio is empty when its value is.
+
+This has no effect on the 'human' format, which always shows ratios.
+"#,
+    ),
+    VersionCheck::USAGE_REQUIRE,
     Usage::new(
         "--row <type>",
         "One of: benchmark (default) or engine.",
@@ -25,16 +132,14 @@ const USAGES: &[Usage] = &[
 This flag sets what the rows are in the table printed. Its value can be either
 'benchmark' or 'engine', where 'benchmark' is the default.
 
-By default, the rows are the benchmark and the columns are the regex engine.
-But if there are too many engines with very few benchmarks, this format
-probably won't work well. In that case, it might make sense to make the rows
-the engines instead (which would make the columns the benchmarks).
-
-If you have both a large number of benchmarks and engines, then you'll have to
-do some kind of filtering to trim it down.
+This is an older way of setting the layout. '--row benchmark' is equivalent to
+'--layout wide' and '--row engine' is equivalent to '--layout transposed'.
 "#,
     ),
-    Stat::USAGE,
+    MeasurementReader::USAGE_SHOW_ERRORS,
+    StaleCheck::USAGE_SKIP,
+    Metric::USAGE,
+    MeasurementReader::USAGE_STRICT_DUPLICATES,
     ThresholdRange::USAGE_MIN,
     ThresholdRange::USAGE_MAX,
     Units::USAGE,
@@ -69,9 +174,15 @@ To compare benchmark results for the same regex engine across time, use the
 'rebar diff' command.
 
 If you find that the table emitted has too many columns to be easily read,
-try running with '--row engine' to flip the rows and columns. If that also has
-too many columns, you'll want to use one or more of the filter flags to trim
-down the results.
+try running with '--layout transposed' to flip the rows and columns. If that
+also has too many columns, then either use '--layout long' to print one row
+for each benchmark and regex engine pair, or use one or more of the filter
+flags to trim down the results.
+
+Values marked with a '†' come from measurements where the runner program
+stopped early because it was close to timing out. (See --timeout for 'rebar
+measure'.) They are based on fewer samples than requested and may be less
+reliable.
 
 USAGE:
     rebar cmp [OPTIONS] <csv-path> ...
@@ -94,24 +205,52 @@ OPTIONS:
 
 pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
     let config = Config::parse(p)?;
-    let measurements = MeasurementReader {
+    let (measurements, errors, versions) = MeasurementReader {
         paths: &config.csv_paths,
         filters: &config.filters,
         intersection: config.intersection,
+        labels: &config.labels,
+        strict_duplicates: config.strict_duplicates,
     }
-    .read()?;
+    .read_with_errors()?;
+    measurement::warn_errors(&errors);
+    config.version_check.check(&versions)?;
+    let hashes = config.stale_check.load_hashes(
+        &config.dir,
+        &measurements,
+        &config.load_options(),
+    );
+    let measurements: Vec<Measurement> = measurements
+        .into_iter()
+        .filter(|m| config.stale_check.keep(&hashes, m))
+        .collect();
+    let line_counts = if config.units.needs_line_counts() {
+        config.read_line_counts(&measurements)?
+    } else {
+        BTreeMap::new()
+    };
     let measurements_by_name = grouped::ByBenchmarkName::new(&measurements)?;
     let engines = measurements_by_name.engine_names();
+    if let Some(delimiter) = config.format.delimiter() {
+        let engines = config.column_order(&measurements, engines);
+        return write_delimited(
+            &config,
+            &measurements_by_name,
+            &engines,
+            &line_counts,
+            delimiter,
+        );
+    }
     let mut wtr = config.color.elastic_stdout();
 
-    match config.row {
-        RowKind::Benchmark => {
+    match config.layout {
+        Layout::Wide => {
             // Write column names.
             write!(wtr, "benchmark")?;
             for engine in engines.iter() {
                 write!(wtr, "\t{}", engine)?;
             }
-            writeln!(wtr, "")?;
+            writeln!(wtr)?;
 
             // Write underlines beneath each column name to give some
             // separation.
@@ -120,10 +259,10 @@ pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
                 write!(wtr, "\t")?;
                 write_divider(&mut wtr, '-', engine.width())?;
             }
-            writeln!(wtr, "")?;
+            writeln!(wtr)?;
 
             for group in measurements_by_name.groups.iter() {
-                if !group.is_within_range(config.stat, config.speedups) {
+                if !config.is_within_range(group) {
                     continue;
                 }
                 write!(wtr, "{}", group.name)?;
@@ -133,48 +272,89 @@ pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
                 // the user can use filters to condense things.
                 for engine in engines.iter() {
                     write!(wtr, "\t")?;
-                    write_datum(&config, &mut wtr, &group, &engine)?;
+                    let lines = line_counts.get(&group.name).copied();
+                    let datum = Datum::new(&config, group, engine, lines);
+                    write_datum(&mut wtr, datum.as_ref())?;
                 }
-                writeln!(wtr, "")?;
+                writeln!(wtr)?;
             }
         }
-        RowKind::Engine => {
+        Layout::Transposed => {
             // Write column names.
             write!(wtr, "engine")?;
             for group in measurements_by_name.groups.iter() {
-                if !group.is_within_range(config.stat, config.speedups) {
+                if !config.is_within_range(group) {
                     continue;
                 }
                 write!(wtr, "\t{}", group.name)?;
             }
-            writeln!(wtr, "")?;
+            writeln!(wtr)?;
 
             // Write underlines beneath each column name to give some
             // separation.
             write_divider(&mut wtr, '-', "engine".width())?;
             for group in measurements_by_name.groups.iter() {
-                if !group.is_within_range(config.stat, config.speedups) {
+                if !config.is_within_range(group) {
                     continue;
                 }
                 write!(wtr, "\t")?;
                 write_divider(&mut wtr, '-', group.name.width())?;
             }
-            writeln!(wtr, "")?;
+            writeln!(wtr)?;
 
             for engine in engines.iter() {
                 write!(wtr, "{}", engine)?;
                 for group in measurements_by_name.groups.iter() {
-                    if !group.is_within_range(config.stat, config.speedups) {
+                    if !config.is_within_range(group) {
                         continue;
                     }
                     write!(wtr, "\t")?;
-                    write_datum(&config, &mut wtr, &group, &engine)?;
+                    let lines = line_counts.get(&group.name).copied();
+                    let datum = Datum::new(&config, group, engine, lines);
+                    write_datum(&mut wtr, datum.as_ref())?;
+                }
+                writeln!(wtr)?;
+            }
+        }
+        Layout::Long => {
+            let columns = ["benchmark", "engine", "value", "ratio"];
+            writeln!(wtr, "{}", columns.join("\t"))?;
+            for (i, label) in columns.iter().enumerate() {
+                if i > 0 {
+                    write!(wtr, "\t")?;
+                }
+                write_divider(&mut wtr, '-', label.width())?;
+            }
+            writeln!(wtr)?;
+
+            for group in measurements_by_name.groups.iter() {
+                if !config.is_within_range(group) {
+                    continue;
+                }
+                let lines = line_counts.get(&group.name).copied();
+                for engine in engines.iter() {
+                    // Unlike the other layouts, there's no need to keep
+                    // anything aligned, so missing entries are just skipped.
+                    let Some(datum) =
+                        Datum::new(&config, group, engine, lines)
+                    else {
+                        continue;
+                    };
+                    write!(wtr, "{}\t{}\t", group.name, engine)?;
+                    datum.write(&mut wtr, false)?;
+                    match datum.ratio {
+                        None => writeln!(wtr, "\t-")?,
+                        Some(ratio) => writeln!(wtr, "\t{:.2}x", ratio)?,
+                    }
                 }
-                writeln!(wtr, "")?;
             }
         }
     }
     wtr.flush()?;
+    if config.show_errors {
+        writeln!(std::io::stdout())?;
+        measurement::write_error_table(&errors)?;
+    }
     Ok(())
 }
 
@@ -183,20 +363,42 @@ pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
 struct Config {
     /// File paths to CSV files.
     csv_paths: Vec<PathBuf>,
+    /// The directory to find benchmark definitions in. This is used when line
+    /// counts are needed and to detect stale measurements.
+    dir: PathBuf,
     /// The benchmark name, model and regex engine filters.
     filters: Filters,
     /// Whether to only consider benchmarks containing all regex engines.
     intersection: bool,
+    /// Whether identical duplicate measurements are an error too.
+    strict_duplicates: bool,
+    /// Labels for the regex engines in each CSV file. This is either empty
+    /// or has the same length as 'csv_paths'.
+    labels: Vec<String>,
     /// The statistic we want to compare.
-    stat: Stat,
+    metric: Metric,
     /// The statistical units we want to use in our comparisons.
     units: Units,
     /// The range of speedup ratios to show.
     speedups: ThresholdRange,
     /// The user's color choice. We default to 'Auto'.
     color: Color,
-    /// What the rows of the comparison table should be.
-    row: RowKind,
+    /// The layout of the comparison table.
+    layout: Layout,
+    /// The format to print the comparison in.
+    format: Format,
+    /// Whether to add ratio columns to the 'csv' and 'tsv' formats.
+    ratio: bool,
+    /// Whether to print a table of measurements that failed.
+    show_errors: bool,
+    /// How to check the rebar versions that recorded the measurements.
+    version_check: VersionCheck,
+    /// How to handle measurements recorded with a different benchmark
+    /// definition.
+    stale_check: StaleCheck,
+    /// When enabled, haystacks defined by a URL may be downloaded. This is
+    /// only relevant when benchmark definitions are loaded.
+    allow_download: bool,
 }
 
 impl Config {
@@ -204,41 +406,105 @@ impl Config {
     fn parse(p: &mut lexopt::Parser) -> anyhow::Result<Config> {
         use lexopt::Arg;
 
-        let mut c = Config::default();
+        let mut c =
+            Config { dir: PathBuf::from("benchmarks"), ..Config::default() };
         while let Some(arg) = p.next()? {
             match arg {
                 Arg::Value(v) => c.csv_paths.push(PathBuf::from(v)),
                 Arg::Short('h') => anyhow::bail!("{}", usage_short()),
                 Arg::Long("help") => anyhow::bail!("{}", usage_long()),
+                Arg::Long("allow-download") => {
+                    c.allow_download = true;
+                }
+                Arg::Long("allow-version-mismatch") => {
+                    c.version_check.allow_mismatch = true;
+                }
                 Arg::Long("color") => {
                     c.color = args::parse(p, "-c/--color")?;
                 }
+                Arg::Short('d') | Arg::Long("dir") => {
+                    c.dir = PathBuf::from(p.value().context("-d/--dir")?);
+                }
                 Arg::Short('e') | Arg::Long("engine") => {
                     c.filters.engine.arg_whitelist(p, "-e/--engine")?;
                 }
                 Arg::Short('E') | Arg::Long("engine-not") => {
                     c.filters.engine.arg_blacklist(p, "-E/--engine-not")?;
                 }
+                Arg::Long("engine-file") => {
+                    c.filters.engine.arg_whitelist_file(p, "--engine-file")?;
+                }
+                Arg::Long("engine-not-file") => {
+                    c.filters
+                        .engine
+                        .arg_blacklist_file(p, "--engine-not-file")?;
+                }
+                Arg::Long("format") => {
+                    c.format = args::parse(p, "--format")?;
+                }
                 Arg::Short('f') | Arg::Long("filter") => {
                     c.filters.name.arg_whitelist(p, "-f/--filter")?;
                 }
                 Arg::Short('F') | Arg::Long("filter-not") => {
                     c.filters.name.arg_blacklist(p, "-F/--filter-not")?;
                 }
+                Arg::Long("filter-file") => {
+                    c.filters.name.arg_whitelist_file(p, "--filter-file")?;
+                }
+                Arg::Long("filter-not-file") => {
+                    c.filters
+                        .name
+                        .arg_blacklist_file(p, "--filter-not-file")?;
+                }
                 Arg::Long("intersection") => {
                     c.intersection = true;
                 }
+                Arg::Long("strict-duplicates") => {
+                    c.strict_duplicates = true;
+                }
+                Arg::Long("label") => {
+                    let label: String = args::parse(p, "--label")?;
+                    anyhow::ensure!(
+                        !label.is_empty()
+                            && !label.contains(['(', ')'])
+                            && !label.contains(char::is_whitespace),
+                        "invalid label '{}' given to --label, labels must be \
+                         non-empty and cannot contain parentheses or \
+                         whitespace",
+                        label,
+                    );
+                    c.labels.push(label);
+                }
                 Arg::Short('m') | Arg::Long("model") => {
                     c.filters.model.arg_whitelist(p, "-m/--model")?;
                 }
                 Arg::Short('M') | Arg::Long("model-not") => {
                     c.filters.model.arg_blacklist(p, "-M/--model-not")?;
                 }
+                Arg::Long("ratio") => {
+                    c.ratio = true;
+                }
+                Arg::Long("require-version") => {
+                    c.version_check.require =
+                        Some(args::parse(p, "--require-version")?);
+                }
+                Arg::Long("layout") => {
+                    c.layout = args::parse(p, "--layout")?;
+                }
                 Arg::Long("row") => {
-                    c.row = args::parse(p, "--row")?;
+                    c.layout = match args::parse(p, "--row")? {
+                        RowKind::Benchmark => Layout::Wide,
+                        RowKind::Engine => Layout::Transposed,
+                    };
+                }
+                Arg::Long("show-errors") => {
+                    c.show_errors = true;
+                }
+                Arg::Long("skip-stale") => {
+                    c.stale_check.skip = true;
                 }
                 Arg::Short('s') | Arg::Long("statistic") => {
-                    c.stat = args::parse(p, "-s/--statistic")?;
+                    c.metric = args::parse(p, "-s/--statistic")?;
                 }
                 Arg::Short('t') | Arg::Long("threshold-min") => {
                     c.speedups.set_min(args::parse(p, "-t/--threshold-min")?);
@@ -253,23 +519,230 @@ impl Config {
             }
         }
         anyhow::ensure!(!c.csv_paths.is_empty(), "no CSV file paths given");
+        anyhow::ensure!(
+            c.labels.is_empty() || c.labels.len() == c.csv_paths.len(),
+            "--label was given {} time(s), but it must be given once for \
+             each of the {} CSV file(s)",
+            c.labels.len(),
+            c.csv_paths.len(),
+        );
+        anyhow::ensure!(
+            c.format == Format::Human
+                || (c.layout == Layout::Wide && !c.show_errors),
+            "--layout, --row and --show-errors can only be used with \
+             '--format human'",
+        );
         Ok(c)
     }
+
+    /// Returns the options to use when loading benchmark definitions.
+    fn load_options(&self) -> LoadOptions {
+        LoadOptions {
+            allow_download: self.allow_download,
+            ..LoadOptions::default()
+        }
+    }
+
+    /// Returns the number of lines in the haystack of each benchmark
+    /// referenced by the given measurements, keyed by benchmark name.
+    ///
+    /// This returns an error if a definition for any of the benchmarks could
+    /// not be found.
+    fn read_line_counts(
+        &self,
+        measurements: &[Measurement],
+    ) -> anyhow::Result<BTreeMap<String, u64>> {
+        let benchmarks = Benchmarks::for_measurements(
+            &self.dir,
+            measurements,
+            &self.load_options(),
+        )
+        .with_context(|| {
+            format!(
+                "failed to load benchmark definitions from {}, \
+                         which are needed for '-u/--units lines-per-sec'",
+                self.dir.display(),
+            )
+        })?;
+        let line_counts: BTreeMap<String, u64> = benchmarks
+            .defs
+            .iter()
+            .map(|def| (def.name.to_string(), def.line_count()))
+            .collect();
+        for m in measurements.iter() {
+            anyhow::ensure!(
+                line_counts.contains_key(&m.name),
+                "could not find benchmark definition for '{}' in {}, \
+                 which is needed to compute its line count for \
+                 '-u/--units lines-per-sec' (use -d/--dir to point \
+                 to a different benchmark directory)",
+                m.name,
+                self.dir.display(),
+            );
+        }
+        Ok(line_counts)
+    }
+
+    /// Returns the given regex engine names (which must be sorted) in the
+    /// order in which their columns are written for the 'csv' and 'tsv'
+    /// formats.
+    ///
+    /// Engines are ordered by the first -e/--engine pattern that matches
+    /// them, and then by name. Since patterns match names before labels are
+    /// added, labels are ignored here.
+    fn column_order(
+        &self,
+        measurements: &[Measurement],
+        mut engines: Vec<String>,
+    ) -> Vec<String> {
+        let unlabeled: BTreeMap<&str, &str> = measurements
+            .iter()
+            .map(|m| (m.engine.as_str(), m.unlabeled_engine()))
+            .collect();
+        // This is a stable sort, so ties remain sorted by name.
+        engines.sort_by_key(|e| {
+            let name = unlabeled.get(e.as_str()).copied().unwrap_or(e);
+            self.filters.engine.whitelist_index(name).unwrap_or(usize::MAX)
+        });
+        engines
+    }
+
+    /// Returns true when the given group should be shown, according to the
+    /// speedup ratio range and the metric being compared.
+    fn is_within_range<T>(
+        &self,
+        group: &grouped::ByBenchmarkNameGroup<T>,
+    ) -> bool {
+        match self.metric {
+            Metric::Stat(stat) => group.is_within_range(stat, self.speedups),
+            Metric::Size => group.is_size_within_range(self.speedups),
+        }
+    }
 }
 
-/// The entity to use for the rows in the comparison table printed.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-enum RowKind {
-    Benchmark,
-    Engine,
+/// The thing being compared in each benchmark. Usually this is an aggregate
+/// statistic of timings, but it can also be the compiled size of a regex as
+/// reported by the regex engine.
+#[derive(Clone, Copy, Debug)]
+enum Metric {
+    Stat(Stat),
+    Size,
+}
+
+impl Metric {
+    const USAGE: Usage = Usage::new(
+        "-s, --statistic <name>",
+        "One of: median, mad, mean, stddev, min, max, size.",
+        r#"
+The aggregate statistic on which to compare (default: median).
+
+Comparisons are only performed on the basis of a single statistic. The choices
+are: median, mad (median absolute deviation), mean, stddev, min, max and size.
+
+The 'size' statistic is special. Instead of comparing timings, it compares the
+median amount of memory used by each compiled regex, as reported by the regex
+engine itself. Only some runner programs report this, and usually only for the
+'compile' model. Benchmarks for which no regex engine reported a size are
+omitted, and regex engines that didn't report a size are shown as 'NO-SIZE'.
+"#,
+    );
+}
+
+impl Default for Metric {
+    fn default() -> Metric {
+        Metric::Stat(Stat::default())
+    }
+}
+
+impl std::str::FromStr for Metric {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Metric> {
+        if s == "size" {
+            return Ok(Metric::Size);
+        }
+        s.parse().map(Metric::Stat)
+    }
+}
+
+/// The layout of the comparison table printed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+enum Layout {
+    /// One row per benchmark and one column per regex engine.
+    #[default]
+    Wide,
+    /// One row per regex engine and one column per benchmark.
+    Transposed,
+    /// One row per benchmark and regex engine pair.
+    Long,
 }
 
-impl Default for RowKind {
-    fn default() -> RowKind {
-        RowKind::Benchmark
+impl std::str::FromStr for Layout {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Layout> {
+        Ok(match s {
+            "wide" => Layout::Wide,
+            "transposed" => Layout::Transposed,
+            "long" => Layout::Long,
+            unknown => anyhow::bail!(
+                "unrecognized layout '{}', must be one of wide, transposed \
+                 or long",
+                unknown,
+            ),
+        })
     }
 }
 
+/// The format to print the comparison in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+enum Format {
+    /// The table described by the layout, meant for humans.
+    #[default]
+    Human,
+    /// Comma separated values with one row per benchmark.
+    Csv,
+    /// Tab separated values with one row per benchmark.
+    Tsv,
+}
+
+impl Format {
+    /// Returns the field delimiter for machine readable formats, or `None`
+    /// for the human format.
+    fn delimiter(&self) -> Option<u8> {
+        match *self {
+            Format::Human => None,
+            Format::Csv => Some(b','),
+            Format::Tsv => Some(b'\t'),
+        }
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Format> {
+        Ok(match s {
+            "human" => Format::Human,
+            "csv" => Format::Csv,
+            "tsv" => Format::Tsv,
+            unknown => anyhow::bail!(
+                "unrecognized format '{}', must be one of human, csv or tsv",
+                unknown,
+            ),
+        })
+    }
+}
+
+/// The entity to use for the rows in the comparison table printed. This is
+/// only used to support the '--row' flag, which predates '--layout'.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+enum RowKind {
+    #[default]
+    Benchmark,
+    Engine,
+}
+
 impl std::str::FromStr for RowKind {
     type Err = anyhow::Error;
 
@@ -282,43 +755,191 @@ impl std::str::FromStr for RowKind {
     }
 }
 
-/// Writes a single aggregate statistic for the given engine from the given
-/// group of measurements.
-fn write_datum<T, W: termcolor::WriteColor>(
-    config: &Config,
-    mut wtr: W,
-    group: &grouped::ByBenchmarkNameGroup<T>,
-    engine: &str,
-) -> anyhow::Result<()> {
-    match group.by_engine.get(engine) {
-        None => {
-            write!(wtr, "-")?;
-        }
-        Some(m) => {
-            if engine == group.best(config.stat) {
-                let mut spec = termcolor::ColorSpec::new();
-                spec.set_fg(Some(termcolor::Color::Green)).set_bold(true);
-                wtr.set_color(&spec)?;
-            }
-            let ratio = group.ratio(engine, config.stat).unwrap();
-            match config.units {
-                Units::Throughput if m.aggregate.tputs.is_some() => {
-                    if let Some(tput) = m.throughput(config.stat) {
-                        write!(wtr, "{} ({:.2}x)", tput, ratio)?;
-                    } else {
-                        write!(wtr, "NO-THROUGHPUT")?;
+/// A single entry in the comparison table, i.e., the value being compared for
+/// one regex engine on one benchmark.
+#[derive(Clone, Debug)]
+struct Datum {
+    /// The formatted value, e.g., a duration, a throughput or a size.
+    value: String,
+    /// The value as a plain number without units, for the 'csv' and 'tsv'
+    /// formats. This is absent when the value couldn't be computed.
+    raw: Option<String>,
+    /// The ratio of this value relative to the best value among all regex
+    /// engines for the same benchmark. This is absent when the value couldn't
+    /// be computed (e.g., when a regex engine didn't report a size).
+    ratio: Option<f64>,
+    /// Whether this is the best value among all regex engines for the same
+    /// benchmark.
+    best: bool,
+}
+
+impl Datum {
+    /// Computes the entry for the given engine from the given group of
+    /// measurements. If the engine has no measurement in the group, then this
+    /// returns `None`.
+    ///
+    /// The number of lines in the benchmark's haystack is only required when
+    /// showing lines per second.
+    fn new<T>(
+        config: &Config,
+        group: &grouped::ByBenchmarkNameGroup<T>,
+        engine: &str,
+        lines: Option<u64>,
+    ) -> Option<Datum> {
+        let stat = match config.metric {
+            Metric::Stat(stat) => stat,
+            Metric::Size => return Datum::size(group, engine),
+        };
+        let m = group.by_engine.get(engine)?;
+        let best = engine == group.best(stat);
+        let ratio = group.ratio(engine, stat);
+        let (value, raw) = match config.units {
+            Units::Throughput if m.aggregate.tputs.is_some() => {
+                match m.throughput(stat) {
+                    Some(tput) => {
+                        (tput.to_string(), tput.bytes_per_second().to_string())
+                    }
+                    None => {
+                        return Some(Datum {
+                            value: "NO-THROUGHPUT".to_string(),
+                            raw: None,
+                            ratio: None,
+                            best,
+                        });
                     }
                 }
-                _ => {
-                    let d = m.duration(config.stat);
-                    let humand = ShortHumanDuration::from(d);
-                    write!(wtr, "{} ({:.2}x)", humand, ratio)?;
-                }
             }
-            if engine == group.best(config.stat) {
-                wtr.reset()?;
+            Units::LinesPerSec => {
+                let d = m.duration(stat);
+                // OK because line counts are always computed for every
+                // benchmark when lines per second are requested.
+                let lines = lines.unwrap();
+                let rate = Rate::new(lines, d, "lines");
+                (rate.to_string(), rate.per_second().to_string())
+            }
+            Units::ItersPerSec => {
+                let rate = Rate::new(1, m.duration(stat), "iters");
+                (rate.to_string(), rate.per_second().to_string())
+            }
+            _ => {
+                let d = m.duration(stat);
+                (
+                    ShortHumanDuration::from(d).to_string(),
+                    d.as_nanos().to_string(),
+                )
             }
+        };
+        let value = if m.partial {
+            format!("{}{}", value, Measurement::PARTIAL_MARKER)
+        } else {
+            value
+        };
+        Some(Datum { value, raw: Some(raw), ratio, best })
+    }
+
+    /// Computes the entry for the compiled size of the given engine from the
+    /// given group of measurements.
+    fn size<T>(
+        group: &grouped::ByBenchmarkNameGroup<T>,
+        engine: &str,
+    ) -> Option<Datum> {
+        let m = group.by_engine.get(engine)?;
+        let best = group.best_size() == Some(engine);
+        let Some(size) = m.compiled_size_bytes else {
+            return Some(Datum {
+                value: "NO-SIZE".to_string(),
+                raw: None,
+                ratio: None,
+                best,
+            });
+        };
+        Some(Datum {
+            value: HumanBytes(size).to_string(),
+            raw: Some(size.to_string()),
+            ratio: group.size_ratio(engine),
+            best,
+        })
+    }
+
+    /// Writes this entry, highlighting it if it's the best. When
+    /// `with_ratio` is true, the ratio (if present) is written after the
+    /// value.
+    fn write<W: termcolor::WriteColor>(
+        &self,
+        mut wtr: W,
+        with_ratio: bool,
+    ) -> anyhow::Result<()> {
+        if self.best {
+            let mut spec = termcolor::ColorSpec::new();
+            spec.set_fg(Some(termcolor::Color::Green)).set_bold(true);
+            wtr.set_color(&spec)?;
+        }
+        write!(wtr, "{}", self.value)?;
+        if let (true, Some(ratio)) = (with_ratio, self.ratio) {
+            write!(wtr, " ({:.2}x)", ratio)?;
+        }
+        if self.best {
+            wtr.reset()?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes a single entry of the comparison table for the 'wide' and
+/// 'transposed' layouts. A missing entry is written as '-'.
+fn write_datum<W: termcolor::WriteColor>(
+    mut wtr: W,
+    datum: Option<&Datum>,
+) -> anyhow::Result<()> {
+    match datum {
+        None => write!(wtr, "-")?,
+        Some(datum) => datum.write(wtr, true)?,
+    }
+    Ok(())
+}
+
+/// Writes the comparison to stdout as delimiter separated values, with one
+/// row per benchmark and one column per regex engine in the order given.
+/// When --ratio is given, the ratio columns follow the value columns.
+fn write_delimited<T>(
+    config: &Config,
+    measurements_by_name: &grouped::ByBenchmarkName<T>,
+    engines: &[String],
+    line_counts: &BTreeMap<String, u64>,
+    delimiter: u8,
+) -> anyhow::Result<()> {
+    let mut wtr = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(std::io::stdout());
+    let mut header = vec!["benchmark".to_string()];
+    header.extend(engines.iter().cloned());
+    if config.ratio {
+        header.extend(engines.iter().map(|e| format!("{}_ratio", e)));
+    }
+    wtr.write_record(&header)?;
+    for group in measurements_by_name.groups.iter() {
+        if !config.is_within_range(group) {
+            continue;
         }
+        let lines = line_counts.get(&group.name).copied();
+        let data: Vec<Option<Datum>> = engines
+            .iter()
+            .map(|engine| Datum::new(config, group, engine, lines))
+            .collect();
+        let mut row = vec![group.name.clone()];
+        row.extend(data.iter().map(|d| {
+            d.as_ref().and_then(|d| d.raw.clone()).unwrap_or_default()
+        }));
+        if config.ratio {
+            row.extend(data.iter().map(|d| {
+                d.as_ref()
+                    .and_then(|d| d.ratio)
+                    .map(|ratio| ratio.to_string())
+                    .unwrap_or_default()
+            }));
+        }
+        wtr.write_record(&row)?;
     }
+    wtr.flush()?;
     Ok(())
 }
@@ -1,25 +1,144 @@
 use std::{
     collections::btree_map::{BTreeMap, Entry},
+    io::Write,
     path::{Path, PathBuf},
 };
 
-use unicode_width::UnicodeWidthStr;
+use {anyhow::Context, unicode_width::UnicodeWidthStr};
 
 use crate::{
     args::{self, Color, Filter, Filters, Stat, ThresholdRange, Units, Usage},
-    format::measurement::Measurement,
-    util::{write_divider, ShortHumanDuration},
+    format::{
+        benchmarks::LoadOptions,
+        measurement::{Measurement, RebarVersions, StaleCheck, VersionCheck},
+    },
+    util::{write_divider, Rate, ShortHumanDuration},
 };
 
 const USAGES: &[Usage] = &[
+    VersionCheck::USAGE_ALLOW_MISMATCH,
     Color::USAGE,
+    Usage::new(
+        "-d, --dir <path>",
+        "A directory containing rebar benchmarks.",
+        r#"
+A directory containing rebar benchmarks.
+
+This is only used to detect stale measurements (see --skip-stale). If this
+directory doesn't exist, then stale measurements aren't detected.
+
+This defaults to 'benchmarks'.
+"#,
+    ),
     Filter::USAGE_ENGINE,
     Filter::USAGE_ENGINE_NOT,
+    Usage::new(
+        "--fail-on-regression",
+        "Exit with status 2 when any benchmark regressed.",
+        r#"
+When set, this command exits with status code 2 if any benchmark for any
+regex engine is classified as 'regressed', 'missing' or 'errored' after all
+filters have been applied. The output is still written as usual before
+exiting.
+
+A benchmark regresses when the ratio of its new timing to its old timing is
+at least the value given to --threshold. The old timing comes from the first
+CSV file given and the new timing comes from the last CSV file given. A
+benchmark is missing when it has a measurement in the first CSV file but not
+in the last, and it is errored when its measurement in the last CSV file
+failed while its measurement in the first CSV file succeeded. So a benchmark
+that goes from working to broken is always caught, but one that was already
+broken (or is new and broken) doesn't fail the run.
+
+This is useful for running this command in CI and failing the job when a
+change makes something slower.
+"#,
+    ),
     Filter::USAGE_BENCH,
     Filter::USAGE_BENCH_NOT,
+    Usage::new(
+        "--format <format>",
+        "One of: human, csv, json.",
+        r#"
+The format to print the comparison in (default: human).
+
+The 'human' format is a table with one column for each CSV file given. It is
+meant to be read by humans and its precise format may change.
+
+The 'csv' and 'json' formats emit one record for each pair of benchmark and
+regex engine. Each record compares the measurement from the first CSV file
+given (the "old" measurement) with the measurement from the last CSV file
+given (the "new" measurement). Pairs that are only in the last CSV file, and
+that didn't fail there, are skipped. Each record has the following fields:
+
+name: The full name of the benchmark.
+model: The benchmark model.
+engine: The name of the regex engine.
+old_nanos: The old timing, in nanoseconds.
+new_nanos: The new timing, in nanoseconds.
+ratio: The new timing divided by the old timing. So a ratio greater than 1
+means the benchmark got slower.
+classification: One of 'regressed', 'improved' or 'unchanged', according to
+--threshold. Or 'missing' when there is no new measurement, or 'errored' when
+the new measurement failed but the old one succeeded. A failed new measurement
+without a successful old one is 'unchanged'.
+err: The error of the new measurement, when it failed.
+
+The 'name', 'model', 'engine' and 'err' fields have the same names and meaning
+as the corresponding columns in CSV files written by 'rebar measure'. Timings
+always use the statistic selected by -s/--statistic. The -u/--units flag has
+no effect on these formats. Fields that don't apply to a record (like
+'new_nanos' and 'ratio' for a missing or errored benchmark) are empty in the
+'csv' format and null in the 'json' format. Missing and errored benchmarks are
+not subject to -t/--threshold-min and -T/--threshold-max, since they have no
+ratio.
+
+The 'json' format emits a single array of records.
+"#,
+    ),
+    Usage::new(
+        "--highlight-max-ratio <factor>",
+        "Warn when the max/median ratio worsens by this factor.",
+        r#"
+Add a column to the table that warns about benchmarks whose tail latency got
+worse, regardless of the statistic selected by -s/--statistic.
+
+The tail latency of a measurement is its maximum time divided by its median
+time. Some regex engines (like those that build a DFA lazily or have garbage
+collection pauses) can have a maximum time that is many times their median
+time. When this flag is given, a warning is shown for every benchmark whose
+max/median ratio in the last CSV file given is more than this factor times its
+max/median ratio in the first CSV file given. For example, a factor of 2 warns
+when the max/median ratio more than doubles.
+
+The max/median ratio of every measurement is also shown next to its speedup
+ratio.
+
+This only impacts the 'human' format. The factor must be at least 1.0.
+"#,
+    ),
     Filter::USAGE_MODEL,
     Filter::USAGE_MODEL_NOT,
+    VersionCheck::USAGE_REQUIRE,
+    StaleCheck::USAGE_SKIP,
     Stat::USAGE,
+    Usage::new(
+        "--threshold <ratio>",
+        "The ratio at which a benchmark is considered changed.",
+        r#"
+The ratio used to classify each benchmark as 'regressed', 'improved' or
+'unchanged' (default: 1.1).
+
+A benchmark is classified as 'regressed' when its new timing divided by its
+old timing is at least this ratio. It is classified as 'improved' when its old
+timing divided by its new timing is at least this ratio. Otherwise, it is
+classified as 'unchanged'. For example, the default of 1.1 means that a
+benchmark needs to be at least 10% slower to count as a regression.
+
+The ratio must be at least 1.0. This is used by --format and
+--fail-on-regression, and has no effect on the default human readable output.
+"#,
+    ),
     ThresholdRange::USAGE_MIN,
     ThresholdRange::USAGE_MAX,
     Units::USAGE,
@@ -75,8 +194,73 @@ OPTIONS:
 pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
     let config = Config::parse(p)?;
     let data_names = config.csv_data_names()?;
-    let grouped_aggs = config.read_measurement_groups()?;
+    let (grouped_aggs, errored, versions) =
+        config.read_measurement_groups()?;
+    config.version_check.check(&versions)?;
+    let groups: Vec<&MeasurementGroup> = grouped_aggs
+        .iter()
+        .filter(|g| g.is_within_range(config.stat, config.speedups))
+        .collect();
+    let records = config.records(&data_names, &groups, &errored);
+    match config.format {
+        Format::Human => {
+            write_defs_revisions(&versions)?;
+            write_human(&config, &data_names, &groups)?;
+        }
+        Format::Csv => write_csv(std::io::stdout(), &records)?,
+        Format::Json => write_json(std::io::stdout().lock(), &records)?,
+    }
+    if config.fail_on_regression {
+        let count = |class: Classification| {
+            records.iter().filter(|r| r.classification == class).count()
+        };
+        let regressed = count(Classification::Regressed);
+        let missing = count(Classification::Missing);
+        let errored = count(Classification::Errored);
+        if regressed + missing + errored > 0 {
+            writeln!(
+                std::io::stderr(),
+                "found {} regressed, {} missing and {} errored benchmark(s) \
+                 with threshold {}",
+                regressed,
+                missing,
+                errored,
+                config.threshold,
+            )?;
+            std::process::exit(2);
+        }
+    }
+    Ok(())
+}
 
+/// If the measurements were recorded against different revisions of the
+/// benchmark definitions, then this writes each revision along with the CSV
+/// files recorded against it. Otherwise, nothing is written.
+fn write_defs_revisions(versions: &RebarVersions) -> anyhow::Result<()> {
+    let Some(revisions) = versions.defs_revisions() else { return Ok(()) };
+    let mut out = std::io::stdout().lock();
+    writeln!(
+        out,
+        "measurements were recorded against {} revisions of the benchmark \
+         definitions:",
+        revisions.len(),
+    )?;
+    for (rev, paths) in revisions.iter() {
+        let paths: Vec<String> =
+            paths.iter().map(|p| p.display().to_string()).collect();
+        writeln!(out, "    {}: {}", rev, paths.join(", "))?;
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Writes the human readable table comparing each of the given groups across
+/// all data sets.
+fn write_human(
+    config: &Config,
+    data_names: &[String],
+    groups: &[&MeasurementGroup],
+) -> anyhow::Result<()> {
     let mut wtr = config.color.elastic_stdout();
 
     // Write column names.
@@ -85,7 +269,10 @@ pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
     for data_name in data_names.iter() {
         write!(wtr, "\t{}", data_name)?;
     }
-    writeln!(wtr, "")?;
+    if config.highlight_max_ratio.is_some() {
+        write!(wtr, "\ttail")?;
+    }
+    writeln!(wtr)?;
 
     // Write underlines beneath each column name to give some separation.
     write_divider(&mut wtr, '-', "benchmark".width())?;
@@ -95,12 +282,13 @@ pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
         write!(wtr, "\t")?;
         write_divider(&mut wtr, '-', data_name.width())?;
     }
-    writeln!(wtr, "")?;
+    if config.highlight_max_ratio.is_some() {
+        write!(wtr, "\t")?;
+        write_divider(&mut wtr, '-', "tail".width())?;
+    }
+    writeln!(wtr)?;
 
-    for group in grouped_aggs.iter() {
-        if !group.is_within_range(config.stat, config.speedups) {
-            continue;
-        }
+    for group in groups.iter() {
         write!(wtr, "{}", group.name)?;
         write!(wtr, "\t{}", group.engine)?;
         // We write an entry for every data set given, even if this benchmark
@@ -123,18 +311,29 @@ pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
                         wtr.set_color(&spec)?;
                     }
                     let ratio = group.ratio(data_name, config.stat);
+                    let paren = match tail_ratio(m) {
+                        Some(tail) if config.highlight_max_ratio.is_some() => {
+                            format!("{:.2}x, max/median {:.2}x", ratio, tail)
+                        }
+                        _ => format!("{:.2}x", ratio),
+                    };
                     match config.units {
                         Units::Throughput if has_throughput => {
                             if let Some(tput) = m.throughput(config.stat) {
-                                write!(wtr, "{} ({:.2}x)", tput, ratio)?;
+                                write!(wtr, "{} ({})", tput, paren)?;
                             } else {
                                 write!(wtr, "NO-THROUGHPUT")?;
                             }
                         }
+                        Units::ItersPerSec => {
+                            let d = m.duration(config.stat);
+                            let rate = Rate::new(1, d, "iters");
+                            write!(wtr, "{} ({})", rate, paren)?;
+                        }
                         _ => {
                             let d = m.duration(config.stat);
                             let humand = ShortHumanDuration::from(d);
-                            write!(wtr, "{} ({:.2}x)", humand, ratio)?;
+                            write!(wtr, "{} ({})", humand, paren)?;
                         }
                     }
                     if best == data_name {
@@ -143,12 +342,43 @@ pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
                 }
             }
         }
-        writeln!(wtr, "")?;
+        if let Some(factor) = config.highlight_max_ratio {
+            write!(wtr, "\t")?;
+            match group.tail_worsened(data_names, factor) {
+                None => write!(wtr, "-")?,
+                Some((old, new)) => {
+                    let mut spec = termcolor::ColorSpec::new();
+                    spec.set_fg(Some(termcolor::Color::Red)).set_bold(true);
+                    wtr.set_color(&spec)?;
+                    write!(wtr, "WORSE: {:.2}x -> {:.2}x", old, new)?;
+                    wtr.reset()?;
+                }
+            }
+        }
+        writeln!(wtr)?;
     }
     wtr.flush()?;
     Ok(())
 }
 
+/// Writes the given records as CSV to the given writer.
+fn write_csv<W: Write>(wtr: W, records: &[Record]) -> anyhow::Result<()> {
+    let mut wtr = csv::Writer::from_writer(wtr);
+    for record in records.iter() {
+        wtr.serialize(record)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Writes the given records as a JSON array to the given writer.
+fn write_json<W: Write>(mut wtr: W, records: &[Record]) -> anyhow::Result<()> {
+    serde_json::to_writer_pretty(&mut wtr, records)?;
+    writeln!(wtr)?;
+    wtr.flush()?;
+    Ok(())
+}
+
 /// The arguments for this 'diff' command parsed from CLI args.
 #[derive(Debug, Default)]
 struct Config {
@@ -164,6 +394,25 @@ struct Config {
     speedups: ThresholdRange,
     /// The user's color choice. We default to 'Auto'.
     color: Color,
+    /// The output format.
+    format: Format,
+    /// The ratio at which a benchmark is considered to have regressed or
+    /// improved.
+    threshold: f64,
+    /// Whether to exit with a special status code when any benchmark has
+    /// regressed.
+    fail_on_regression: bool,
+    /// How to check the rebar versions that recorded the measurements.
+    version_check: VersionCheck,
+    /// The directory to find benchmark definitions in. This is only used to
+    /// detect stale measurements.
+    dir: PathBuf,
+    /// How to handle measurements recorded with a different benchmark
+    /// definition.
+    stale_check: StaleCheck,
+    /// When present, a warning is shown for every benchmark whose max/median
+    /// ratio worsened by more than this factor.
+    highlight_max_ratio: Option<f64>,
 }
 
 impl Config {
@@ -171,36 +420,66 @@ impl Config {
     fn parse(p: &mut lexopt::Parser) -> anyhow::Result<Config> {
         use lexopt::Arg;
 
-        let mut c = Config::default();
+        let mut c = Config {
+            threshold: 1.1,
+            dir: PathBuf::from("benchmarks"),
+            ..Config::default()
+        };
         while let Some(arg) = p.next()? {
             match arg {
                 Arg::Value(v) => c.csv_paths.push(PathBuf::from(v)),
                 Arg::Short('h') => anyhow::bail!("{}", usage_short()),
                 Arg::Long("help") => anyhow::bail!("{}", usage_long()),
+                Arg::Long("allow-version-mismatch") => {
+                    c.version_check.allow_mismatch = true;
+                }
                 Arg::Long("color") => {
                     c.color = args::parse(p, "-c/--color")?;
                 }
+                Arg::Short('d') | Arg::Long("dir") => {
+                    c.dir = PathBuf::from(p.value().context("-d/--dir")?);
+                }
                 Arg::Short('e') | Arg::Long("engine") => {
                     c.filters.engine.arg_whitelist(p, "-e/--engine")?;
                 }
                 Arg::Short('E') | Arg::Long("engine-not") => {
                     c.filters.engine.arg_blacklist(p, "-E/--engine-not")?;
                 }
+                Arg::Long("fail-on-regression") => {
+                    c.fail_on_regression = true;
+                }
                 Arg::Short('f') | Arg::Long("filter") => {
                     c.filters.name.arg_whitelist(p, "-f/--filter")?;
                 }
                 Arg::Short('F') | Arg::Long("filter-not") => {
                     c.filters.name.arg_blacklist(p, "-F/--filter-not")?;
                 }
+                Arg::Long("format") => {
+                    c.format = args::parse(p, "--format")?;
+                }
+                Arg::Long("highlight-max-ratio") => {
+                    c.highlight_max_ratio =
+                        Some(args::parse(p, "--highlight-max-ratio")?);
+                }
                 Arg::Short('m') | Arg::Long("model") => {
                     c.filters.model.arg_whitelist(p, "-m/--model")?;
                 }
                 Arg::Short('M') | Arg::Long("model-not") => {
                     c.filters.model.arg_blacklist(p, "-M/--model-not")?;
                 }
+                Arg::Long("require-version") => {
+                    c.version_check.require =
+                        Some(args::parse(p, "--require-version")?);
+                }
                 Arg::Short('s') | Arg::Long("statistic") => {
                     c.stat = args::parse(p, "-s/--statistic")?;
                 }
+                Arg::Long("skip-stale") => {
+                    c.stale_check.skip = true;
+                }
+                Arg::Long("threshold") => {
+                    c.threshold = args::parse(p, "--threshold")?;
+                }
                 Arg::Short('t') | Arg::Long("threshold-min") => {
                     c.speedups.set_min(args::parse(p, "-t/--threshold-min")?);
                 }
@@ -214,52 +493,211 @@ impl Config {
             }
         }
         anyhow::ensure!(!c.csv_paths.is_empty(), "no CSV file paths given");
+        anyhow::ensure!(
+            !c.units.needs_line_counts(),
+            "'-u/--units lines-per-sec' is not supported by 'rebar diff', \
+             since it compares measurements of the same benchmark (and thus \
+             the same number of lines), use 'iters-per-sec' instead",
+        );
+        anyhow::ensure!(
+            c.threshold >= 1.0,
+            "--threshold must be at least 1.0, but got {}",
+            c.threshold,
+        );
+        if let Some(factor) = c.highlight_max_ratio {
+            anyhow::ensure!(
+                factor >= 1.0,
+                "--highlight-max-ratio must be at least 1.0, but got {}",
+                factor,
+            );
+        }
         Ok(c)
     }
 
+    /// Returns a record comparing the old and new measurements for each of
+    /// the given groups. The old measurement is taken from the first data
+    /// set and the new measurement is taken from the last data set.
+    ///
+    /// A group with an old measurement but no new one is classified as
+    /// missing, unless its new measurement failed. Every failed measurement
+    /// in the last data set is classified as errored, whether or not it has
+    /// an old measurement. Groups that only have a new measurement are
+    /// skipped.
+    fn records(
+        &self,
+        data_names: &[String],
+        groups: &[&MeasurementGroup],
+        errored: &ErroredMeasurements,
+    ) -> Vec<Record> {
+        let (old_name, new_name) =
+            match (data_names.first(), data_names.last()) {
+                (Some(old), Some(new)) => (old, new),
+                _ => return vec![],
+            };
+        let new_err = |name: &str, engine: &str| {
+            errored
+                .get(&(name.to_string(), engine.to_string()))
+                .and_then(|by_data| by_data.get(new_name))
+        };
+        let mut records = vec![];
+        for group in groups.iter() {
+            let old = group.measurements_by_data.get(old_name);
+            let new = group.measurements_by_data.get(new_name);
+            let (old, new) = match (old, new) {
+                (Some(old), Some(new)) => (old, new),
+                (Some(old), None) => {
+                    // Errored measurements are added below.
+                    if new_err(&group.name, &group.engine).is_none() {
+                        records.push(Record {
+                            name: group.name.clone(),
+                            model: old.model.clone(),
+                            engine: group.engine.clone(),
+                            old_nanos: Some(
+                                old.duration(self.stat).as_nanos(),
+                            ),
+                            new_nanos: None,
+                            ratio: None,
+                            classification: Classification::Missing,
+                            err: None,
+                        });
+                    }
+                    continue;
+                }
+                _ => {
+                    log::debug!(
+                        "{}:{}: skipping because it is missing from {}",
+                        group.name,
+                        group.engine,
+                        old_name,
+                    );
+                    continue;
+                }
+            };
+            let old_dur = old.duration(self.stat);
+            let new_dur = new.duration(self.stat);
+            let ratio = new_dur.as_secs_f64() / old_dur.as_secs_f64();
+            let classification = if ratio >= self.threshold {
+                Classification::Regressed
+            } else if ratio * self.threshold <= 1.0 {
+                Classification::Improved
+            } else {
+                Classification::Unchanged
+            };
+            records.push(Record {
+                name: group.name.clone(),
+                model: new.model.clone(),
+                engine: group.engine.clone(),
+                old_nanos: Some(old_dur.as_nanos()),
+                new_nanos: Some(new_dur.as_nanos()),
+                ratio: Some(ratio),
+                classification,
+                err: None,
+            });
+        }
+        for ((name, engine), by_data) in errored.iter() {
+            let Some(new) = by_data.get(new_name) else { continue };
+            let old = groups
+                .iter()
+                .find(|g| &g.name == name && &g.engine == engine)
+                .and_then(|g| g.measurements_by_data.get(old_name));
+            // Only a benchmark that went from working to broken is errored.
+            // One that was already broken (or didn't exist) in the old data
+            // set is reported, but didn't get any worse.
+            let classification = if old.is_some() {
+                Classification::Errored
+            } else {
+                Classification::Unchanged
+            };
+            records.push(Record {
+                name: name.clone(),
+                model: new.model.clone(),
+                engine: engine.clone(),
+                old_nanos: old.map(|m| m.duration(self.stat).as_nanos()),
+                new_nanos: None,
+                ratio: None,
+                classification,
+                err: new.err.clone(),
+            });
+        }
+        records
+    }
+
     /// Reads all aggregate benchmark measurements from all CSV file paths
     /// given, and returns them grouped by the data set. That is, each group
     /// represents all measurements found across the data sets given for a
     /// single (benchmark name, engine name) pair. The filters provided are
     /// applied.
+    ///
+    /// Measurements that failed aren't included in any group. They are
+    /// returned separately so that they can be reported as errored.
+    ///
+    /// The versions of rebar that recorded the measurements are also
+    /// returned.
     fn read_measurement_groups(
         &self,
-    ) -> anyhow::Result<Vec<MeasurementGroup>> {
-        // Our groups are just maps from CSV data name to measurements.
-        let mut groups: Vec<BTreeMap<String, Measurement>> = vec![];
-        // Map from (benchmark, engine) pair to index in 'groups'. We use the
-        // index to find which group to insert each measurement into.
-        let mut pair2idx: BTreeMap<(String, String), usize> = BTreeMap::new();
+    ) -> anyhow::Result<(
+        Vec<MeasurementGroup>,
+        ErroredMeasurements,
+        RebarVersions,
+    )> {
+        let mut versions = RebarVersions::default();
+        // All of the measurements that pass our filters, along with the
+        // name of the data set each one came from.
+        let mut measurements: Vec<(String, Measurement)> = vec![];
         for csv_path in self.csv_paths.iter() {
             let data_name = csv_data_name(csv_path)?;
             let mut rdr = csv::Reader::from_path(csv_path)?;
             for result in rdr.deserialize() {
                 let m: Measurement = result?;
-                if let Some(ref err) = m.err {
-                    log::warn!(
-                        "{}:{}: skipping because of error: {}",
-                        m.name,
-                        m.engine,
-                        err
-                    );
-                    continue;
-                }
                 if !self.filters.include(&m) {
                     continue;
                 }
-                let pair = (m.name.clone(), m.engine.clone());
-                let idx = match pair2idx.entry(pair) {
-                    Entry::Occupied(e) => *e.get(),
-                    Entry::Vacant(e) => {
-                        let idx = groups.len();
-                        groups.push(BTreeMap::new());
-                        *e.insert(idx)
-                    }
-                };
-                groups[idx].insert(data_name.clone(), m);
+                versions.add(&m, csv_path);
+                measurements.push((data_name.clone(), m));
+            }
+        }
+        // We can only check for stale measurements once we have all of them,
+        // since we only load the definitions they refer to.
+        let ms: Vec<Measurement> =
+            measurements.iter().map(|(_, m)| m.clone()).collect();
+        let hashes = self.stale_check.load_hashes(
+            &self.dir,
+            &ms,
+            &LoadOptions::default(),
+        );
+        // Our groups are just maps from CSV data name to measurements.
+        let mut groups: Vec<BTreeMap<String, Measurement>> = vec![];
+        // Map from (benchmark, engine) pair to index in 'groups'. We use the
+        // index to find which group to insert each measurement into.
+        let mut pair2idx: BTreeMap<(String, String), usize> = BTreeMap::new();
+        let mut errored = ErroredMeasurements::new();
+        for (data_name, m) in measurements {
+            if !self.stale_check.keep(&hashes, &m) {
+                continue;
+            }
+            let pair = (m.name.clone(), m.engine.clone());
+            if let Some(ref err) = m.err {
+                log::warn!(
+                    "{}:{}: skipping because of error: {}",
+                    m.name,
+                    m.engine,
+                    err
+                );
+                errored.entry(pair).or_default().insert(data_name, m);
+                continue;
             }
+            let idx = match pair2idx.entry(pair) {
+                Entry::Occupied(e) => *e.get(),
+                Entry::Vacant(e) => {
+                    let idx = groups.len();
+                    groups.push(BTreeMap::new());
+                    *e.insert(idx)
+                }
+            };
+            groups[idx].insert(data_name, m);
         }
-        Ok(groups.into_iter().map(MeasurementGroup::new).collect())
+        let groups = groups.into_iter().map(MeasurementGroup::new).collect();
+        Ok((groups, errored, versions))
     }
 
     /// Returns the "nice" CSV data names from the paths given. These names
@@ -269,6 +707,67 @@ impl Config {
     }
 }
 
+/// The output format of the 'diff' command.
+#[derive(Clone, Copy, Debug, Default)]
+enum Format {
+    #[default]
+    Human,
+    Csv,
+    Json,
+}
+
+impl std::str::FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Format> {
+        let format = match s {
+            "human" => Format::Human,
+            "csv" => Format::Csv,
+            "json" => Format::Json,
+            unknown => {
+                anyhow::bail!(
+                    "unrecognized format '{}', must be \
+                     one of human, csv or json.",
+                    unknown,
+                )
+            }
+        };
+        Ok(format)
+    }
+}
+
+/// Failed measurements, keyed by their (benchmark name, engine name) pair and
+/// then by the name of the data set they came from.
+type ErroredMeasurements =
+    BTreeMap<(String, String), BTreeMap<String, Measurement>>;
+
+/// A single machine readable comparison between an old and a new measurement
+/// for the same benchmark and regex engine.
+#[derive(Clone, Debug, serde::Serialize)]
+struct Record {
+    name: String,
+    model: String,
+    engine: String,
+    old_nanos: Option<u128>,
+    new_nanos: Option<u128>,
+    ratio: Option<f64>,
+    classification: Classification,
+    err: Option<String>,
+}
+
+/// How a benchmark changed between an old and a new measurement.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Classification {
+    Regressed,
+    Improved,
+    Unchanged,
+    /// There is an old measurement but no new one.
+    Missing,
+    /// The new measurement failed, but the old one succeeded.
+    Errored,
+}
+
 /// A group of measurements for a single pair of (benchmark name, engine name).
 /// Every measurement in this group represents an aggregate group of statistic
 /// from a given CSV input.
@@ -381,6 +880,25 @@ impl MeasurementGroup {
         best_data_name
     }
 
+    /// Returns the max/median ratios of the measurements in the first and
+    /// last of the given data sets when the latter is more than `factor`
+    /// times the former. Otherwise, including when this group is missing a
+    /// measurement from either data set, this returns `None`.
+    fn tail_worsened(
+        &self,
+        data_names: &[String],
+        factor: f64,
+    ) -> Option<(f64, f64)> {
+        let old = self.measurements_by_data.get(data_names.first()?)?;
+        let new = self.measurements_by_data.get(data_names.last()?)?;
+        let (old, new) = (tail_ratio(old)?, tail_ratio(new)?);
+        if new > old * factor {
+            Some((old, new))
+        } else {
+            None
+        }
+    }
+
     /// Returns true if and only if at least one measurement in this group
     /// has throughputs available.
     fn any_throughput(&self) -> bool {
@@ -388,6 +906,17 @@ impl MeasurementGroup {
     }
 }
 
+/// Returns the maximum time of the given measurement divided by its median
+/// time. This is a rough indicator of its tail latency. If the median time is
+/// zero, then this returns `None`.
+fn tail_ratio(m: &Measurement) -> Option<f64> {
+    let median = m.duration(Stat::Median).as_secs_f64();
+    if median == 0.0 {
+        return None;
+    }
+    Some(m.duration(Stat::Max).as_secs_f64() / median)
+}
+
 /// Extract a "data set" name from a given CSV file path.
 ///
 /// If there was a problem getting the name (i.e., the file path is "weird" in
@@ -405,3 +934,223 @@ fn csv_data_name<P: AsRef<Path>>(path: P) -> anyhow::Result<String> {
         ),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::format::measurement::{read_fixture, FIXTURE_HEADER};
+
+    use super::*;
+
+    /// Measurements for a handful of benchmarks before and after a change.
+    /// With the default threshold of 1.1, 'a' regressed, 'b' improved, 'c'
+    /// is unchanged, 'd' is missing, 'e' errored, 'f' errored without an old
+    /// measurement, 'g' is new and 'h' errored in both.
+    const OLD: &str = "\
+fixture/a,count,0.1.0,one,1.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+fixture/b,count,0.1.0,one,1.0.0,,10,5,1s,20ns,0ns,20ns,0ns,20ns,20ns
+fixture/c,grep,0.1.0,one,1.0.0,,10,5,1s,20ns,0ns,20ns,0ns,20ns,20ns
+fixture/d,count,0.1.0,one,1.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+fixture/e,count,0.1.0,one,1.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+fixture/h,count,0.1.0,one,1.0.0,timeout,,0,0s,0ns,0ns,0ns,0ns,0ns,0ns
+";
+
+    const NEW: &str = "\
+fixture/a,count,0.1.0,one,1.0.0,,10,5,1s,12ns,0ns,12ns,0ns,12ns,12ns
+fixture/b,count,0.1.0,one,1.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+fixture/c,grep,0.1.0,one,1.0.0,,10,5,1s,21ns,0ns,21ns,0ns,21ns,21ns
+fixture/e,count,0.1.0,one,1.0.0,count mismatch,,0,0s,0ns,0ns,0ns,0ns,0ns,0ns
+fixture/f,count,0.1.0,one,1.0.0,timeout,,0,0s,0ns,0ns,0ns,0ns,0ns,0ns
+fixture/g,count,0.1.0,one,1.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+fixture/h,count,0.1.0,one,1.0.0,count mismatch,,0,0s,0ns,0ns,0ns,0ns,0ns,0ns
+";
+
+    /// Writes the old and new fixtures to CSV files and returns the records
+    /// comparing them with the given threshold.
+    fn records(threshold: f64) -> Vec<Record> {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut csv_paths = vec![];
+        for (name, rows) in [("old.csv", OLD), ("new.csv", NEW)] {
+            let path = tmp.path().join(name);
+            std::fs::write(&path, format!("{}\n{}", FIXTURE_HEADER, rows))
+                .unwrap();
+            csv_paths.push(path);
+        }
+        let config = Config {
+            csv_paths,
+            threshold,
+            dir: tmp.path().join("benchmarks"),
+            ..Config::default()
+        };
+        let data_names = config.csv_data_names().unwrap();
+        let (groups, errored, _) = config.read_measurement_groups().unwrap();
+        let groups: Vec<&MeasurementGroup> = groups.iter().collect();
+        config.records(&data_names, &groups, &errored)
+    }
+
+    /// Returns the name and classification of each record.
+    fn classes(records: &[Record]) -> Vec<(&str, Classification)> {
+        records.iter().map(|r| (&*r.name, r.classification)).collect()
+    }
+
+    #[test]
+    fn classification() {
+        use Classification::*;
+
+        let got = records(1.1);
+        assert_eq!(
+            classes(&got),
+            vec![
+                ("fixture/a", Regressed),
+                ("fixture/b", Improved),
+                ("fixture/c", Unchanged),
+                ("fixture/d", Missing),
+                ("fixture/e", Errored),
+                ("fixture/f", Unchanged),
+                ("fixture/h", Unchanged),
+            ],
+        );
+        let e = &got[4];
+        assert_eq!(
+            (e.old_nanos, e.new_nanos, e.ratio),
+            (Some(10), None, None)
+        );
+        assert_eq!(e.err.as_deref(), Some("count mismatch"));
+        let f = &got[5];
+        assert_eq!((f.old_nanos, f.new_nanos, f.ratio), (None, None, None));
+        assert_eq!(f.err.as_deref(), Some("timeout"));
+        let h = &got[6];
+        assert_eq!((h.old_nanos, h.new_nanos, h.ratio), (None, None, None));
+        assert_eq!(h.err.as_deref(), Some("count mismatch"));
+
+        // A bigger threshold makes 'a' unchanged, but 'b' (2x faster) is
+        // still improved. Missing and errored benchmarks don't depend on the
+        // threshold.
+        assert_eq!(
+            classes(&records(1.5)),
+            vec![
+                ("fixture/a", Unchanged),
+                ("fixture/b", Improved),
+                ("fixture/c", Unchanged),
+                ("fixture/d", Missing),
+                ("fixture/e", Errored),
+                ("fixture/f", Unchanged),
+                ("fixture/h", Unchanged),
+            ],
+        );
+        // The threshold is inclusive on both sides.
+        assert_eq!(
+            classes(&records(2.0))[..2],
+            [("fixture/a", Unchanged), ("fixture/b", Improved)],
+        );
+        assert_eq!(
+            classes(&records(1.0))[..3],
+            [
+                ("fixture/a", Regressed),
+                ("fixture/b", Improved),
+                ("fixture/c", Regressed),
+            ],
+        );
+    }
+
+    // Only a benchmark that went from working to broken should fail
+    // --fail-on-regression. One that errored in both data sets, or that
+    // errored without an old measurement at all, didn't get any worse.
+    #[test]
+    fn already_errored_is_not_a_failure() {
+        use Classification::*;
+
+        let got = records(1.1);
+        let failures: Vec<&str> = got
+            .iter()
+            .filter(|r| {
+                matches!(r.classification, Regressed | Missing | Errored)
+            })
+            .map(|r| &*r.name)
+            .collect();
+        assert_eq!(failures, vec!["fixture/a", "fixture/d", "fixture/e"]);
+    }
+
+    #[test]
+    fn output_csv() {
+        let records = records(1.1);
+        let mut out = vec![];
+        write_csv(&mut out, &records[1..2]).unwrap();
+        write_csv(&mut out, &records[3..5]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\
+name,model,engine,old_nanos,new_nanos,ratio,classification,err
+fixture/b,count,one,20,10,0.5,improved,
+name,model,engine,old_nanos,new_nanos,ratio,classification,err
+fixture/d,count,one,10,,,missing,
+fixture/e,count,one,10,,,errored,count mismatch
+",
+        );
+    }
+
+    #[test]
+    fn output_json() {
+        let records = records(1.1);
+        let mut out = vec![];
+        write_json(&mut out, &[records[1].clone(), records[5].clone()])
+            .unwrap();
+        let got: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            got,
+            serde_json::json!([
+                {
+                    "name": "fixture/b",
+                    "model": "count",
+                    "engine": "one",
+                    "old_nanos": 20,
+                    "new_nanos": 10,
+                    "ratio": 0.5,
+                    "classification": "improved",
+                    "err": null,
+                },
+                {
+                    "name": "fixture/f",
+                    "model": "count",
+                    "engine": "one",
+                    "old_nanos": null,
+                    "new_nanos": null,
+                    "ratio": null,
+                    "classification": "unchanged",
+                    "err": "timeout",
+                },
+            ]),
+        );
+    }
+
+    fn group(rows: &[(&str, &str, &str)]) -> MeasurementGroup {
+        let mut csv = String::new();
+        for (data, median, max) in rows {
+            csv.push_str(&format!(
+                "{data},count,0.1.0,one,1.0.0,,10,5,1s,{median},0ns,\
+                 {median},0ns,{median},{max}\n",
+            ));
+        }
+        let mut by_data = BTreeMap::new();
+        for mut m in read_fixture(&csv) {
+            let data = std::mem::replace(&mut m.name, "fixture".to_string());
+            by_data.insert(data, m);
+        }
+        MeasurementGroup::new(by_data)
+    }
+
+    #[test]
+    fn tail_worsened() {
+        let names = vec!["old".to_string(), "new".to_string()];
+        // The median doesn't change, but the max/median ratio goes from 2x
+        // to 10x.
+        let g = group(&[("old", "10ns", "20ns"), ("new", "10ns", "100ns")]);
+        assert_eq!(Some((2.0, 10.0)), g.tail_worsened(&names, 4.0));
+        assert_eq!(None, g.tail_worsened(&names, 5.0));
+        // An improvement in the tail is never highlighted.
+        let g = group(&[("old", "10ns", "100ns"), ("new", "10ns", "20ns")]);
+        assert_eq!(None, g.tail_worsened(&names, 1.0));
+        // Missing from the new data set.
+        let g = group(&[("old", "10ns", "20ns")]);
+        assert_eq!(None, g.tail_worsened(&names, 1.0));
+    }
+}
@@ -0,0 +1,336 @@
+use std::{collections::BTreeMap, io::Write, path::PathBuf, sync::Arc};
+
+use {
+    anyhow::Context, bstr::ByteSlice, lexopt::Arg,
+    unicode_width::UnicodeWidthStr,
+};
+
+use crate::{
+    args::{Filter, Usage},
+    cmd::measure::{ExecBenchmark, ExecBenchmarkConfig},
+    format::benchmarks::{
+        CountEngine, Definition, DefinitionBudget, DefinitionName,
+        DefinitionOptions, Dependency, Engine, Engines, LoadOptions,
+    },
+    util::write_divider,
+};
+
+const USAGES: &[Usage] = &[
+    Usage::BENCH_DIR,
+    Filter::USAGE_ENGINE,
+    Filter::USAGE_ENGINE_NOT,
+    Usage::new(
+        "--verbose",
+        "Print the full text of every error.",
+        r#"
+After printing the table of results, print the full text of every error that
+occurred for every regex engine. Without this flag, failures are only shown as
+a short label in the table.
+"#,
+    ),
+];
+
+fn usage_short() -> String {
+    format!(
+        "\
+Check that regex engines are built and working.
+
+USAGE:
+    rebar doctor [-e <engine> ...] [--verbose]
+
+TIP:
+    use -h for short docs and --help for long docs
+
+OPTIONS:
+{options}
+",
+        options = Usage::short(USAGES),
+    )
+    .trim()
+    .to_string()
+}
+
+fn usage_long() -> String {
+    format!(
+        "\
+Check that regex engines are built and working.
+
+For every regex engine, this runs its dependency checks, asks its runner
+program for its version and then runs a tiny smoke test benchmark. The smoke
+test searches for 'abc' in 'abc' using the 'count' model and runs for exactly
+one iteration. A few regex engines (like 'rust/regex/nfa' and
+'rust/regex/onepass') don't support the 'count' model, so if it fails, then the
+'compile' model is tried too.
+
+A table is printed with the outcome of each step for each regex engine. The
+'Build needed?' column is 'yes' when a regex engine has build steps but its
+version couldn't be determined, which usually means 'rebar build' hasn't been
+run for it yet (or that it failed). Use --verbose to see why each step failed.
+
+This command exits with an error if any regex engine fails its smoke test.
+
+USAGE:
+    rebar doctor [-e <engine> ...] [--verbose]
+
+OPTIONS:
+{options}
+",
+        options = Usage::long(USAGES),
+    )
+    .trim()
+    .to_string()
+}
+
+pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
+    let c = Config::parse(p)?;
+    // We always run every version command here, since a stale cached version
+    // would hide exactly the kind of problem this command is looking for.
+    let load = LoadOptions { version_cache: false, ..LoadOptions::default() };
+    let engines = Engines::from_file(&c.dir, &load, |e| {
+        c.engine_filter.include(&e.name)
+    })?;
+    let checks: Vec<Check> = engines.list.iter().map(Check::run).collect();
+
+    let mut out = std::io::stdout().lock();
+    write_table(&mut out, &checks)?;
+    let has_errors = checks.iter().any(|check| !check.errors.is_empty());
+    if c.verbose && has_errors {
+        writeln!(out)?;
+        for check in checks.iter() {
+            for (what, err) in check.errors.iter() {
+                writeln!(out, "{}: {}: {}", check.name, what, err)?;
+            }
+        }
+    } else if has_errors {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "note: use --verbose to see the full text of each error"
+        )?;
+    }
+    let failed = checks.iter().filter(|check| !check.smoke.is_ok()).count();
+    anyhow::ensure!(
+        failed == 0,
+        "{} of {} regex engines failed the smoke test",
+        failed,
+        checks.len(),
+    );
+    Ok(())
+}
+
+#[derive(Clone, Debug)]
+struct Config {
+    dir: PathBuf,
+    engine_filter: Filter,
+    verbose: bool,
+}
+
+impl Config {
+    fn parse(p: &mut lexopt::Parser) -> anyhow::Result<Config> {
+        let mut c = Config {
+            dir: PathBuf::from("benchmarks"),
+            engine_filter: Filter::default(),
+            verbose: false,
+        };
+        while let Some(arg) = p.next()? {
+            match arg {
+                Arg::Short('h') => anyhow::bail!("{}", usage_short()),
+                Arg::Long("help") => anyhow::bail!("{}", usage_long()),
+                Arg::Short('d') | Arg::Long("dir") => {
+                    c.dir = PathBuf::from(p.value().context("-d/--dir")?);
+                }
+                Arg::Short('e') | Arg::Long("engine") => {
+                    c.engine_filter.arg_whitelist(p, "-e/--engine")?;
+                }
+                Arg::Short('E') | Arg::Long("engine-not") => {
+                    c.engine_filter.arg_blacklist(p, "-E/--engine-not")?;
+                }
+                Arg::Long("verbose") => {
+                    c.verbose = true;
+                }
+                _ => return Err(arg.unexpected().into()),
+            }
+        }
+        Ok(c)
+    }
+}
+
+/// The outcome of checking a single regex engine.
+#[derive(Debug)]
+struct Check {
+    /// The name of the regex engine.
+    name: String,
+    /// Whether all of the engine's dependency checks passed.
+    deps_ok: bool,
+    /// The version reported by the engine, if it could be found.
+    version: Option<String>,
+    /// Whether the engine has build steps. Combined with a missing version,
+    /// this suggests that the engine needs to be built.
+    has_build: bool,
+    /// The outcome of the smoke test.
+    smoke: Smoke,
+    /// Every error that occurred, paired with a short description of the
+    /// step that failed.
+    errors: Vec<(&'static str, String)>,
+}
+
+#[derive(Debug)]
+enum Smoke {
+    /// The smoke test passed with the given model.
+    Ok(&'static str),
+    /// The smoke test wasn't run because the engine's version is unknown.
+    Skipped,
+    /// The smoke test failed.
+    Failed,
+}
+
+impl Smoke {
+    fn is_ok(&self) -> bool {
+        matches!(*self, Smoke::Ok(_))
+    }
+
+    /// A short label for this outcome, suitable for the table.
+    fn label(&self) -> &'static str {
+        match *self {
+            Smoke::Ok("count") => "ok",
+            Smoke::Ok(_) => "ok (compile)",
+            Smoke::Skipped => "skipped",
+            Smoke::Failed => "FAILED",
+        }
+    }
+}
+
+impl Check {
+    /// Run every check for the given engine. This never fails. Instead, any
+    /// errors are recorded in the check returned.
+    fn run(e: &Engine) -> Check {
+        let mut check = Check {
+            name: e.name.clone(),
+            deps_ok: true,
+            version: None,
+            has_build: !e.build.is_empty(),
+            smoke: Smoke::Skipped,
+            errors: vec![],
+        };
+        for dep in e.dependency.iter() {
+            if let Err(err) = check_dependency(dep) {
+                check.deps_ok = false;
+                check.errors.push(("dependency", format!("{:#}", err)));
+            }
+        }
+        // We don't use the version already attached to the engine, since
+        // the reason for the failure is thrown away when loading engines.
+        match e.version_config.get() {
+            Ok(version) => check.version = Some(version),
+            Err(err) => {
+                check.errors.push(("version", format!("{:#}", err)));
+                return check;
+            }
+        }
+        let mut e = e.clone();
+        e.version = check.version.clone().unwrap();
+        check.smoke = match smoke(&e, "count") {
+            Ok(()) => Smoke::Ok("count"),
+            Err(err) => match smoke(&e, "compile") {
+                Ok(()) => Smoke::Ok("compile"),
+                Err(compile_err) => {
+                    log::debug!(
+                        "{}: smoke test with 'compile' model failed: {:#}",
+                        e.name,
+                        compile_err,
+                    );
+                    check.errors.push(("smoke test", format!("{:#}", err)));
+                    Smoke::Failed
+                }
+            },
+        };
+        check
+    }
+}
+
+/// Runs the given dependency's command and checks that its output matches
+/// the dependency's regex, if it has one.
+fn check_dependency(dep: &Dependency) -> anyhow::Result<()> {
+    let out = dep.run.output().context("dependency command failed")?;
+    let outstr =
+        out.to_str().context("dependency command output is not UTF-8")?;
+    if let Some(ref re) = dep.regex {
+        anyhow::ensure!(
+            re.is_match(outstr),
+            "could not find match for {:?} in output of {:?}",
+            re.as_str(),
+            dep.run.bin,
+        );
+    }
+    Ok(())
+}
+
+/// Runs a single iteration of a trivial benchmark with the given model for
+/// the given engine.
+fn smoke(e: &Engine, model: &str) -> anyhow::Result<()> {
+    let def = Definition {
+        model: model.to_string(),
+        name: DefinitionName {
+            full: "doctor/smoke".to_string(),
+            group: "doctor".to_string(),
+            local: "smoke".to_string(),
+        },
+        regexes: Arc::from(vec!["abc".to_string()]),
+        binary_regexes: None,
+        regex_path: None,
+        options: DefinitionOptions::default(),
+        // The haystack is just the pattern itself so that engines that only
+        // support anchored searches report the same count as everyone else.
+        haystack: Arc::from(&b"abc"[..]),
+        haystack_path: None,
+        haystack_encoding: klv::HaystackEncoding::Utf8,
+        count: vec![CountEngine::all(1)],
+        count_derive_from: None,
+        verify_count: true,
+        engines: vec![e.clone()],
+        analysis: None,
+        requires: vec![],
+        budget: DefinitionBudget::default(),
+        params: BTreeMap::new(),
+    };
+    let config = ExecBenchmarkConfig {
+        max_iters: 1,
+        max_warmup_iters: 0,
+        ..ExecBenchmarkConfig::default()
+    };
+    ExecBenchmark::new(config, def, e.clone()).collect(false)?;
+    Ok(())
+}
+
+/// Writes a table summarizing every check, in the order given.
+fn write_table(wtr: impl Write, checks: &[Check]) -> anyhow::Result<()> {
+    let mut wtr = tabwriter::TabWriter::new(wtr);
+    let columns =
+        &["Engine", "Dependencies", "Version", "Build needed?", "Smoke test"];
+    writeln!(wtr, "{}", columns.join("\t"))?;
+    for (i, label) in columns.iter().enumerate() {
+        if i > 0 {
+            write!(wtr, "\t")?;
+        }
+        write_divider(&mut wtr, '-', label.width())?;
+    }
+    writeln!(wtr)?;
+    for check in checks.iter() {
+        let build_needed = match (check.version.is_some(), check.has_build) {
+            (true, _) => "no",
+            (false, true) => "yes",
+            (false, false) => "n/a",
+        };
+        writeln!(
+            wtr,
+            "{}\t{}\t{}\t{}\t{}",
+            check.name,
+            if check.deps_ok { "ok" } else { "FAILED" },
+            check.version.as_deref().unwrap_or("ERROR"),
+            build_needed,
+            check.smoke.label(),
+        )?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
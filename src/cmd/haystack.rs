@@ -7,11 +7,12 @@ use {
 
 use crate::{
     args::{self, Usage},
-    format::benchmarks::Benchmarks,
+    format::benchmarks::{Benchmarks, LoadOptions},
 };
 
 const USAGES: &[Usage] = &[
     Usage::BENCH_DIR,
+    Usage::ALLOW_DOWNLOAD,
     Usage::new(
         "-r, --repeat <number>",
         "Repeats the haystack this many times.",
@@ -78,6 +79,7 @@ OPTIONS:
 pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
     let mut bench_name = None;
     let mut dir = PathBuf::from("benchmarks");
+    let mut load = LoadOptions::default();
     let mut repeat = 1;
     while let Some(arg) = p.next()? {
         match arg {
@@ -95,6 +97,9 @@ pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
             Arg::Short('d') | Arg::Long("dir") => {
                 dir = PathBuf::from(p.value().context("-d/--dir")?);
             }
+            Arg::Long("allow-download") => {
+                load.allow_download = true;
+            }
             Arg::Short('r') | Arg::Long("repeat") => {
                 repeat = args::parse(p, "-r/--repeat")?;
             }
@@ -105,7 +110,7 @@ pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
         None => anyhow::bail!("missing benchmark name"),
         Some(bench_name) => bench_name,
     };
-    let def = Benchmarks::find_one(&dir, &bench_name)?;
+    let def = Benchmarks::find_one(&dir, &bench_name, &load)?;
     for _ in 0..repeat {
         if let Err(err) = std::io::stdout().write_all(&def.haystack) {
             if err.kind() == std::io::ErrorKind::BrokenPipe {
@@ -7,12 +7,29 @@ use {
 
 use crate::{
     args::{self, Usage},
-    format::benchmarks::Benchmarks,
+    format::benchmarks::{Benchmarks, LoadOptions},
     util::ShortHumanDuration,
 };
 
 const USAGES: &[Usage] = &[
     Usage::BENCH_DIR,
+    Usage::ALLOW_DOWNLOAD,
+    Usage::new(
+        "-e, --engine <engine>",
+        "Print the KLV data as given to a specific regex engine.",
+        r#"
+Print the KLV data exactly as it would be given to the named regex engine.
+
+Some regex engines define rewrite rules in 'engines.toml' that tweak patterns
+before they're sent to the engine's runner program. By default, this command
+prints the patterns as written in the benchmark definition. When this flag is
+given, the named engine's rewrite rules are applied first, so that one can see
+exactly what that engine receives.
+
+The engine given must be one of the engines listed in the benchmark
+definition.
+"#,
+    ),
     Usage::MAX_ITERS,
     Usage::MAX_WARMUP_ITERS,
     Usage::MAX_TIME,
@@ -25,7 +42,7 @@ fn usage_short() -> String {
 Print the given benchmark in key-length-value (KLV) format.
 
 USAGE:
-    rebar klv <benchmark-name>
+    rebar klv <benchmark-name> [-e <engine>]
 
 TIP:
     use -h for short docs and --help for long docs
@@ -58,7 +75,7 @@ See also the 'rebar haystack' command, for cases where you just want the
 haystack for a specific benchmark definition.
 
 USAGE:
-    rebar klv <benchmark-name>
+    rebar klv <benchmark-name> [-e <engine>]
 
 OPTIONS:
 {options}
@@ -72,6 +89,8 @@ OPTIONS:
 pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
     let mut bench_name = None;
     let mut dir = PathBuf::from("benchmarks");
+    let mut load = LoadOptions::default();
+    let mut engine_name = None;
     let mut max_iters = 0;
     let mut max_warmup_iters = 0;
     let mut max_time = Duration::default();
@@ -92,6 +111,13 @@ pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
             Arg::Short('d') | Arg::Long("dir") => {
                 dir = PathBuf::from(p.value().context("-d/--dir")?);
             }
+            Arg::Long("allow-download") => {
+                load.allow_download = true;
+            }
+            Arg::Short('e') | Arg::Long("engine") => {
+                let value = p.value().context("-e/--engine")?;
+                engine_name = Some(value.string().context("-e/--engine")?);
+            }
             Arg::Long("max-iters") => {
                 max_iters = args::parse(p, "--max-iters")?;
             }
@@ -114,20 +140,39 @@ pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
         None => anyhow::bail!("missing benchmark name"),
         Some(bench_name) => bench_name,
     };
-    let def = Benchmarks::find_one(&dir, &bench_name)?;
+    let def = Benchmarks::find_one(&dir, &bench_name, &load)?;
+    let engine = match engine_name {
+        None => None,
+        Some(ref name) => match def.engines.iter().find(|e| &e.name == name) {
+            Some(engine) => Some(engine),
+            None => anyhow::bail!(
+                "benchmark '{}' does not include engine '{}'",
+                def.name,
+                name,
+            ),
+        },
+    };
     let klvbench = klv::Benchmark {
+        version: klv::VERSION,
         name: def.name.as_str().to_string(),
         model: def.model.clone(),
-        regex: klv::Regex {
-            patterns: def.regexes.iter().map(|p| p.to_string()).collect(),
-            case_insensitive: def.options.case_insensitive,
-            unicode: def.options.unicode,
-        },
+        regex: def.klv_regex(engine)?,
         haystack: Arc::clone(&def.haystack),
+        haystack_encoding: def.haystack_encoding,
         max_iters,
         max_warmup_iters,
         max_time,
         max_warmup_time,
+        verify_every: 1,
+        iter_batch: 1,
+        soft_deadline: None,
+        expected_count: None,
+        search_start: def.options.search_start,
+        search_end: def.options.search_end,
+        max_count: def.options.max_count,
+        params: def.params.clone(),
+        ignored: vec![],
+        started: None,
     };
     let mut buf = vec![];
     klvbench.write(&mut buf).context("failed to write KLV data")?;
@@ -0,0 +1,97 @@
+use std::{io::Write, path::PathBuf};
+
+use {anyhow::Context, lexopt::Arg};
+
+use crate::{
+    args::Usage,
+    format::benchmarks::{Benchmarks, LintSeverity},
+};
+
+const USAGES: &[Usage] = &[Usage::BENCH_DIR];
+
+fn usage_short() -> String {
+    format!(
+        "\
+Check benchmark definitions and engines.toml for problems.
+
+USAGE:
+    rebar lint [-d <dir>]
+
+TIP:
+    use -h for short docs and --help for long docs
+
+OPTIONS:
+{options}
+",
+        options = Usage::short(USAGES),
+    )
+    .trim()
+    .to_string()
+}
+
+fn usage_long() -> String {
+    format!(
+        "\
+Check benchmark definitions and engines.toml for problems.
+
+This loads every benchmark definition and regex engine, and reports every
+problem found instead of stopping at the first one. Each problem is printed on
+its own line along with the file and (if applicable) the benchmark it was found
+in. The following problems are reported as errors:
+
+* A benchmark uses a model that rebar doesn't know about.
+* A benchmark refers to a regex engine that isn't defined.
+* A benchmark has more than one count for the same engine (and version).
+* A benchmark fails to load for any other reason, e.g., a missing haystack.
+
+And the following are reported as warnings:
+
+* A count entry's engine name doesn't match any defined regex engine.
+* A file in the 'haystacks' or 'regexes' directories isn't used by any
+benchmark. README.md files and hidden files are never reported.
+
+This command exits with an error if any problem with severity 'error' is
+found. Note that this command needs to run the version command for every regex
+engine, just like 'rebar measure' does, but it never runs any benchmarks.
+
+USAGE:
+    rebar lint [-d <dir>]
+
+OPTIONS:
+{options}
+",
+        options = Usage::long(USAGES),
+    )
+    .trim()
+    .to_string()
+}
+
+pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
+    let mut dir = PathBuf::from("benchmarks");
+    while let Some(arg) = p.next()? {
+        match arg {
+            Arg::Short('h') => anyhow::bail!("{}", usage_short()),
+            Arg::Long("help") => anyhow::bail!("{}", usage_long()),
+            Arg::Short('d') | Arg::Long("dir") => {
+                dir = PathBuf::from(p.value().context("-d/--dir")?);
+            }
+            _ => return Err(arg.unexpected().into()),
+        }
+    }
+    let issues = Benchmarks::lint(&dir)?;
+    let mut out = std::io::stdout().lock();
+    for issue in issues.iter() {
+        writeln!(out, "{}", issue)?;
+    }
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == LintSeverity::Error)
+        .count();
+    anyhow::ensure!(
+        errors == 0,
+        "found {} errors and {} warnings",
+        errors,
+        issues.len() - errors,
+    );
+    Ok(())
+}
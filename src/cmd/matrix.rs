@@ -0,0 +1,276 @@
+use std::{io::Write, path::PathBuf};
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    args::{self, Filter, Filters, Stat, Usage},
+    format::measurement::{MeasurementReader, VersionCheck},
+    grouped::{self, SpeedupMatrix},
+    util::write_divider,
+};
+
+const USAGES: &[Usage] = &[
+    VersionCheck::USAGE_ALLOW_MISMATCH,
+    Filter::USAGE_ENGINE,
+    Filter::USAGE_ENGINE_NOT,
+    Filter::USAGE_BENCH,
+    Filter::USAGE_BENCH_NOT,
+    Usage::new(
+        "--format <name>",
+        "One of: text, markdown.",
+        r#"
+The format to print the matrix in (default: text).
+
+The 'text' format prints a table with aligned columns, which is meant to be
+read in a terminal. The 'markdown' format prints a Markdown table, which is
+meant to be pasted into a blog post or a paper.
+"#,
+    ),
+    MeasurementReader::USAGE_INTERSECTION,
+    Filter::USAGE_MODEL,
+    Filter::USAGE_MODEL_NOT,
+    VersionCheck::USAGE_REQUIRE,
+    Stat::USAGE,
+    MeasurementReader::USAGE_STRICT_DUPLICATES,
+];
+
+fn usage_short() -> String {
+    format!(
+        "\
+Print a matrix of pairwise speedups between regex engines.
+
+USAGE:
+    rebar matrix [OPTIONS] <csv-path> ...
+
+TIP:
+    use -h for short docs and --help for long docs
+
+OPTIONS:
+{options}
+",
+        options = Usage::short(USAGES),
+    )
+    .trim()
+    .to_string()
+}
+
+fn usage_long() -> String {
+    format!(
+        "\
+Print a matrix of pairwise speedups between regex engines.
+
+Each row and column of the matrix corresponds to a regex engine. The cell at
+row i and column j is the geometric mean of the speedup ratios of engine i over
+engine j, restricted to the benchmarks in which both engines have
+measurements. A speedup ratio is the time taken by engine j divided by the
+time taken by engine i for the same benchmark. So a value greater than 1.0
+means the engine in the row is faster than the engine in the column, and a
+value less than 1.0 means it is slower.
+
+This is like the 'rebar rank' command, except that instead of comparing every
+engine to the fastest engine on each benchmark, every pair of engines is
+compared directly on the benchmarks they have in common. Cells for a pair of
+engines that don't share any benchmarks are printed as '-'. The diagonal is
+always 1.00.
+
+As with 'rebar rank', it is usually a good idea to filter out the 'compile'
+model with '-M compile', since mixing search and compile time measurements
+into one aggregate is usually not what you want.
+
+USAGE:
+    rebar matrix [OPTIONS] <csv-path> ...
+
+    This command takes one or more file paths to CSV files written by the
+    'rebar measure' command.
+
+TIP:
+    use -h for short docs and --help for long docs
+
+OPTIONS:
+{options}
+",
+        options = Usage::long(USAGES),
+    )
+    .trim()
+    .to_string()
+}
+
+pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
+    let config = Config::parse(p)?;
+    let (measurements, versions) = MeasurementReader {
+        paths: &config.csv_paths,
+        filters: &config.filters,
+        intersection: config.intersection,
+        labels: &[],
+        strict_duplicates: config.strict_duplicates,
+    }
+    .read_with_versions()?;
+    config.version_check.check(&versions)?;
+    let by_name = grouped::ByBenchmarkName::new(&measurements)?;
+    let matrix = by_name.speedup_matrix(config.stat);
+    let mut out = std::io::stdout().lock();
+    match config.format {
+        Format::Text => write_text(&matrix, &mut out)?,
+        Format::Markdown => write_markdown(&matrix, &mut out)?,
+    }
+    Ok(())
+}
+
+/// Formats a single cell of a speedup matrix.
+fn cell(speedup: Option<f64>) -> String {
+    match speedup {
+        None => "-".to_string(),
+        Some(speedup) => format!("{:.2}", speedup),
+    }
+}
+
+/// Writes the given matrix as a table with aligned columns.
+fn write_text<W: Write>(matrix: &SpeedupMatrix, wtr: W) -> anyhow::Result<()> {
+    let mut wtr = tabwriter::TabWriter::new(wtr);
+    let mut columns = vec!["Engine"];
+    columns.extend(matrix.engines.iter().map(|e| e.as_str()));
+    writeln!(wtr, "{}", columns.join("\t"))?;
+    for (i, label) in columns.iter().enumerate() {
+        if i > 0 {
+            write!(wtr, "\t")?;
+        }
+        write_divider(&mut wtr, '-', label.width())?;
+    }
+    writeln!(wtr)?;
+    for (engine, row) in matrix.engines.iter().zip(matrix.cells.iter()) {
+        write!(wtr, "{}", engine)?;
+        for &speedup in row.iter() {
+            write!(wtr, "\t{}", cell(speedup))?;
+        }
+        writeln!(wtr)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Writes the given matrix as a Markdown table.
+fn write_markdown<W: Write>(
+    matrix: &SpeedupMatrix,
+    mut wtr: W,
+) -> anyhow::Result<()> {
+    write!(wtr, "| Engine |")?;
+    for engine in matrix.engines.iter() {
+        write!(wtr, " {} |", engine)?;
+    }
+    writeln!(wtr)?;
+    write!(wtr, "| - |")?;
+    for _ in matrix.engines.iter() {
+        write!(wtr, " - |")?;
+    }
+    writeln!(wtr)?;
+    for (engine, row) in matrix.engines.iter().zip(matrix.cells.iter()) {
+        write!(wtr, "| {} |", engine)?;
+        for &speedup in row.iter() {
+            write!(wtr, " {} |", cell(speedup))?;
+        }
+        writeln!(wtr)?;
+    }
+    Ok(())
+}
+
+/// The arguments for this 'matrix' command parsed from CLI args.
+#[derive(Debug, Default)]
+struct Config {
+    /// File paths to CSV files.
+    csv_paths: Vec<PathBuf>,
+    /// The benchmark name, model and regex engine filters.
+    filters: Filters,
+    /// Whether to only consider benchmarks containing all regex engines.
+    intersection: bool,
+    /// Whether identical duplicate measurements are an error too.
+    strict_duplicates: bool,
+    /// The statistic we want to compare.
+    stat: Stat,
+    /// The format to print the matrix in.
+    format: Format,
+    /// How to check the rebar versions that recorded the measurements.
+    version_check: VersionCheck,
+}
+
+impl Config {
+    /// Parse 'matrix' args from the given CLI parser.
+    fn parse(p: &mut lexopt::Parser) -> anyhow::Result<Config> {
+        use lexopt::Arg;
+
+        let mut c = Config::default();
+        while let Some(arg) = p.next()? {
+            match arg {
+                Arg::Value(v) => c.csv_paths.push(PathBuf::from(v)),
+                Arg::Short('h') => anyhow::bail!("{}", usage_short()),
+                Arg::Long("help") => anyhow::bail!("{}", usage_long()),
+                Arg::Long("allow-version-mismatch") => {
+                    c.version_check.allow_mismatch = true;
+                }
+                Arg::Short('e') | Arg::Long("engine") => {
+                    c.filters.engine.arg_whitelist(p, "-e/--engine")?;
+                }
+                Arg::Short('E') | Arg::Long("engine-not") => {
+                    c.filters.engine.arg_blacklist(p, "-E/--engine-not")?;
+                }
+                Arg::Short('f') | Arg::Long("filter") => {
+                    c.filters.name.arg_whitelist(p, "-f/--filter")?;
+                }
+                Arg::Short('F') | Arg::Long("filter-not") => {
+                    c.filters.name.arg_blacklist(p, "-F/--filter-not")?;
+                }
+                Arg::Long("format") => {
+                    c.format = args::parse(p, "--format")?;
+                }
+                Arg::Long("intersection") => {
+                    c.intersection = true;
+                }
+                Arg::Long("strict-duplicates") => {
+                    c.strict_duplicates = true;
+                }
+                Arg::Short('m') | Arg::Long("model") => {
+                    c.filters.model.arg_whitelist(p, "-m/--model")?;
+                }
+                Arg::Short('M') | Arg::Long("model-not") => {
+                    c.filters.model.arg_blacklist(p, "-M/--model-not")?;
+                }
+                Arg::Long("require-version") => {
+                    c.version_check.require =
+                        Some(args::parse(p, "--require-version")?);
+                }
+                Arg::Short('s') | Arg::Long("statistic") => {
+                    c.stat = args::parse(p, "-s/--statistic")?;
+                }
+                _ => return Err(arg.unexpected().into()),
+            }
+        }
+        anyhow::ensure!(!c.csv_paths.is_empty(), "no CSV file paths given");
+        Ok(c)
+    }
+}
+
+/// The output format of the matrix.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+enum Format {
+    #[default]
+    Text,
+    Markdown,
+}
+
+impl std::str::FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Format> {
+        let format = match s {
+            "text" => Format::Text,
+            "markdown" => Format::Markdown,
+            unknown => {
+                anyhow::bail!(
+                    "unrecognized format '{}', must be \
+                     one of text or markdown.",
+                    unknown,
+                )
+            }
+        };
+        Ok(format)
+    }
+}
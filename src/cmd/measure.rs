@@ -1,29 +1,188 @@
 use std::{
-    io::{BufReader, Read},
-    path::PathBuf,
+    collections::{BTreeMap, BTreeSet},
+    io::{BufReader, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
     sync::Arc,
     time::{Duration, Instant},
 };
 
-use {anyhow::Context, bstr::ByteSlice};
+use {
+    anyhow::Context,
+    bstr::{BStr, ByteSlice},
+    lexopt::ValueExt,
+};
 
 use crate::{
     args::{self, Filter, Filters, Usage},
     format::{
-        benchmarks::{Benchmarks, Definition, Engine},
+        benchmarks::{
+            Benchmarks, CountEngine, Definition, DefinitionHasher, Engine,
+            Engines, LoadOptions, VersionCache,
+        },
         measurement::{Aggregate, AggregateTimes, Measurement},
     },
-    util::{self, ShortHumanDuration},
+    util::{self, Rng, ShortHumanDuration, ShortHumanSize},
 };
 
 const MIN_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// The maximum number of iterations given to a runner program when --hold is
+/// used.
+///
+/// Ideally this would be unbounded, since --hold sets the maximum time to the
+/// hold duration. But runner programs buffer every sample in memory until
+/// they're done (even though we discard them when holding), and there's no
+/// way to ask them not to. A sample is about 40 bytes in the Rust runner
+/// programs, so this caps that buffer at a few hundred megabytes. Runner
+/// programs in other languages use more per sample, but not so much more
+/// that this becomes a problem. The downside is that a hold on a very fast
+/// benchmark may end early, which can be worked around with --iter-batch.
+const HOLD_MAX_ITERS: u64 = 10_000_000;
+
+/// The maximum number of iterations to run when calibrating a benchmark for
+/// --target-samples or '--iter-batch auto'.
+const CALIBRATION_MAX_ITERS: u64 = 10;
+
+/// The maximum amount of time to spend running calibration iterations. At
+/// least one iteration is always run.
+const CALIBRATION_MAX_TIME: Duration = Duration::from_millis(10);
+
 const USAGES: &[Usage] = &[
     Usage::BENCH_DIR,
+    Usage::ALLOW_DOWNLOAD,
+    Usage::new(
+        "--affinity <core>",
+        "Pin each runner program to a CPU core.",
+        r#"
+Pin each runner program to the CPU core given, where cores are numbered
+starting at 0. This prevents the runner program from migrating between cores
+while it's being measured, which can make measurements less noisy. Only the
+runner program is pinned, not rebar itself.
+
+The core is recorded in the 'affinity' column of every measurement. If the core
+doesn't exist (or isn't available to rebar), then a warning is printed and
+runner programs aren't pinned. Pinning is supported on Linux and Windows. On
+other platforms, including macOS, a warning is printed and this is ignored.
+"#,
+    ),
+    Usage::new(
+        "--compute-counts",
+        "Compute missing counts using an oracle regex engine.",
+        r#"
+Compute counts for benchmark definitions that don't have one.
+
+The 'count' field of a benchmark definition may be omitted, in which case its
+count is "unresolved." Benchmarks with unresolved counts are rejected by normal
+measurement and verification runs. This mode instead runs the regex engine
+given by --oracle (which is required) for a single iteration on each selected
+benchmark with an unresolved count, and reports the count it observes. Selected
+benchmarks that already have a count are skipped.
+
+By default, a TOML snippet is printed for each benchmark. Use --write to
+instead add the count directly to the benchmark definition files.
+
+The oracle doesn't need to be in a benchmark's list of engines, but it does
+need to be defined in 'engines.toml'. Counts computed this way are only as
+good as the oracle. It's a good idea to check them with 'rebar measure --test'
+afterwards, since that will verify the new counts against every regex engine.
+"#,
+    ),
+    Usage::new(
+        "--dump-samples <dir>",
+        "Write every sample for every benchmark to a directory.",
+        r#"
+Write every timing sample collected for every benchmark to the directory
+given, in addition to the usual aggregate measurement written to stdout.
+
+One CSV file is written for each benchmark and regex engine pair. Its name is
+'<benchmark>__<engine>.csv', where every character in the benchmark or engine
+name other than an ASCII letter, digit, '-' or '.' is percent-encoded. This
+guarantees that distinct pairs never share a file. For example, the samples
+for the 'curated/01-literal/sherlock-en' benchmark and the 'rust/regex' engine
+are written to 'curated%2F01-literal%2Fsherlock-en__rust%2Fregex.csv'. The
+directory is created if it doesn't exist, and existing files in it are
+overwritten.
+
+Each row of a dump has an 'iteration' column and a 'duration_nanos' column.
+The iteration is the index reported by the runner program alongside each
+sample, starting at 0. Runner programs that don't report it have their samples
+numbered in the order in which they were printed. Warmup iterations are never
+included. The samples are written before any statistics are computed, which
+makes dumps useful for plotting the distribution of timings. For example, to
+see whether a benchmark is bimodal.
+
+Nothing is written for benchmarks that fail.
+"#,
+    ),
     Filter::USAGE_ENGINE,
     Filter::USAGE_ENGINE_NOT,
+    Filter::USAGE_ENGINE_FILE,
+    Filter::USAGE_ENGINE_NOT_FILE,
+    Usage::new(
+        "--env <engine>:<name>=<value> ...",
+        "Set an environment variable for some regex engines.",
+        r#"
+Set an environment variable in the environment of the runner programs for the
+regex engines whose names match the pattern given. For example,
+'--env rust/.*:MALLOC_ARENA_MAX=1' sets 'MALLOC_ARENA_MAX' to '1' for every
+regex engine whose name starts with 'rust/'. The pattern must match the entire
+name of a regex engine. This flag can be given multiple times. When more than
+one applies to the same variable for a regex engine, the last one wins.
+
+This is useful for quick experiments that would otherwise require editing
+'engines.toml'. Variables set this way take precedence over the ones set for
+a regex engine in 'engines.toml'.
+
+The names of the variables injected are recorded in the 'env' column of each
+measurement for the regex engines they apply to. Use --env-record-values to
+record their values too.
+
+The variable name must consist of ASCII letters, digits and underscores, and
+must not start with a digit. Everything after the first '=' is the value. The
+pattern is everything before the last ':' that precedes the first '=', so
+patterns can contain ':', but not '='.
+"#,
+    ),
+    Usage::new(
+        "--env-record-values",
+        "Record the values of variables set by --env.",
+        r#"
+Record the values of the environment variables set by --env in addition to
+their names. Each variable in the 'env' column of a measurement is then written
+as 'NAME=VALUE' instead of just 'NAME'. This isn't the default because values
+can be long or sensitive.
+"#,
+    ),
     Filter::USAGE_BENCH,
     Filter::USAGE_BENCH_NOT,
+    Filter::USAGE_BENCH_FILE,
+    Filter::USAGE_BENCH_NOT_FILE,
+    Usage::new(
+        "--hold <duration>",
+        "Run one benchmark for a fixed time for profiling.",
+        r#"
+Run a single benchmark for the given duration so that a profiler can be
+attached to the runner program.
+
+In this mode, the filters given must select exactly one benchmark and regex
+engine pair, or else an error is returned. The runner program's maximum time
+is set to the duration given. Warmup is disabled. The timeout is extended, if
+necessary, to be at least 10 seconds longer than the hold duration.
+
+Since runner programs keep every sample in memory until they finish, the
+maximum number of iterations is set to 10 million instead of being unbounded.
+For benchmarks that take less than a microsecond or so, this limit may be
+reached before the hold duration elapses. In that case, use --iter-batch to
+run more iterations per sample.
+
+The PID of the runner program is printed to stderr immediately after it is
+spawned, so that one can do things like 'perf record -p <pid>'. The runner's
+stderr is passed through, and its stdout (the samples) is discarded. No
+measurement is ever written since the timings would be meaningless.
+
+This cannot be combined with --list, --verify, --leak-check or --dump-samples.
+"#,
+    ),
     Usage::new(
         "-i, --ignore-missing-engines",
         "Silently suppress missing regex engines.",
@@ -38,6 +197,83 @@ during 'rebar build'.
 This option is useful for when you just want to implicitly filter out any regex
 engines that cannot be benchmarked. Otherwise, an attempt will still be made
 and it will result in reporting a measurement error.
+"#,
+    ),
+    Usage::new(
+        "--iter-batch <number|auto>",
+        "Run each benchmark this many times per sample.",
+        r#"
+Ask runner programs to run the thing being measured this many times in a row
+for every sample, and to report the total time divided by the number of runs.
+The count reported for each sample is still the count of a single run. The
+default is 1.
+
+This is useful for benchmarks that are so fast (e.g., 'memmem' on a short
+haystack) that the overhead of reading the clock dominates each sample.
+Batching multiple runs into one sample amortizes that overhead.
+
+When set to 'auto', each benchmark is first run a few times (just like with
+--target-samples) to estimate how long a single run takes. If it takes less
+than 1 microsecond, then runs are batched such that each sample takes roughly
+1 microsecond. Otherwise, no batching is done.
+
+This is only supported by runner programs that use the 'timer' crate in
+'shared/timer' (which is most of the Rust runner programs). Other runner
+programs will either fail or ignore it when it's set to anything other than 1.
+"#,
+    ),
+    Usage::new(
+        "--leak-check",
+        "Check 'compile' benchmarks for unbounded memory growth.",
+        r#"
+Check benchmarks using the 'compile' model for unbounded memory growth.
+
+In this mode, only benchmarks using the 'compile' model are run. Each one is
+run for a large fixed number of iterations (see --leak-check-iters), which
+overrides --max-iters. Warmup is disabled. While the benchmark runs, rebar
+samples the resident set size (RSS) of the runner process every 100ms. If the
+RSS grows monotonically over the course of the run, and the estimated growth
+per iteration exceeds --leak-check-max-growth, then the measurement fails. The
+estimated growth is recorded in the 'rss_growth_bytes_per_iter' column of each
+measurement. If the benchmark finishes before enough RSS samples are taken to
+estimate growth, then a warning is printed and the column is left empty.
+
+The idea is to detect regex engines (or runner programs) that leak compiled
+regex objects, since this inflates measurements for the 'compile' model and can
+cause long runs to run out of memory.
+
+Note that --max-time still bounds how long each benchmark runs, so it may need
+to be increased for regex engines with slow compile times in order to get a
+good signal.
+
+This is currently only supported on Unix.
+"#,
+    ),
+    Usage::new(
+        "--leak-check-iters <number>",
+        "The number of iterations to run with --leak-check.",
+        r#"
+The number of iterations to run for each benchmark when --leak-check is
+enabled. This overrides --max-iters.
+
+The default is 100,000.
+"#,
+    ),
+    Usage::new(
+        "--leak-check-max-growth <size>",
+        "The max RSS growth per iteration allowed with --leak-check.",
+        r#"
+The maximum amount of growth in resident set size (RSS), per iteration,
+permitted when --leak-check is enabled. The size is a number of bytes,
+optionally followed by a unit like 'KB' or 'MiB'. Units are always powers of
+2, so 'KB' and 'KiB' both mean 1,024 bytes. If the RSS of a runner
+process grows monotonically and faster than this, then the measurement fails.
+
+Note that most runner programs record every sample in memory until the
+benchmark completes, so some growth is expected even when nothing is leaking.
+The threshold should be big enough to account for that.
+
+The default is 256 bytes.
 "#,
     ),
     Usage::new(
@@ -50,14 +286,216 @@ This command does all of the work to collect benchmarks, haystacks, filter them
 and validate them. But it does not actually run the benchmarks. Instead, it
 prints every benchmark that will be executed. This is useful for seeing what
 work will be done without actually doing it.
+
+By default, this prints a CSV file with the benchmark name, model, regex
+engine and regex engine version. See --list-format for printing more details.
+"#,
+    ),
+    Usage::new(
+        "--list-format <name>",
+        "One of: csv, json. (default: csv)",
+        r#"
+The format to use for --list. This implies --list.
+
+The 'csv' format (the default) prints one row for every benchmark and regex
+engine pair with the benchmark name, model, regex engine name and regex engine
+version.
+
+The 'json' format prints one JSON object per line for every benchmark and
+regex engine pair. Each object includes the full, group and local name of the
+benchmark, its model, the regex engine name and version, the number of
+patterns and their total size in bytes, the length of the haystack, the regex
+options, the expected count for the regex engine (or null if it can't be
+determined) and the effective time and iteration budgets (max-iters,
+max-warmup-iters, max-time, max-warmup-time and timeout) after applying any
+overrides from the benchmark definition. Field names
+match the columns of the CSV files written by this command and the JSON
+written by 'rebar diff --format json' where they overlap.
 "#,
     ),
+    Usage::LOCKED,
     Usage::MAX_ITERS,
     Usage::MAX_WARMUP_ITERS,
     Usage::MAX_TIME,
     Usage::MAX_WARMUP_TIME,
     Filter::USAGE_MODEL,
     Filter::USAGE_MODEL_NOT,
+    Usage::new(
+        "--nice <level>",
+        "Run each runner program with the given niceness.",
+        r#"
+Run each runner program with the given niceness, from -20 (highest priority)
+to 19 (lowest priority). A higher priority makes it less likely for the runner
+program to be interrupted by other processes on the same machine. Setting a
+negative niceness usually requires elevated privileges. If setting it fails,
+then the measurement fails.
+
+The niceness is recorded in the 'nice' column of every measurement. On Unix,
+including macOS, it's set with setpriority(2). On Windows, it's mapped to a
+priority class: -20 to -11 is 'high', -10 to -1 is 'above normal', 0 is
+'normal', 1 to 10 is 'below normal' and 11 to 19 is 'idle'.
+"#,
+    ),
+    Usage::NO_CACHE,
+    Usage::new(
+        "--no-progress",
+        "Disable the progress indicator.",
+        r#"
+Disable the progress indicator on stderr. See --progress.
+"#,
+    ),
+    Usage::NO_VERSION_CACHE,
+    Usage::new(
+        "--oracle <engine>",
+        "The regex engine to use with --compute-counts.",
+        r#"
+The name of the regex engine to use for computing counts with
+--compute-counts. This is required when --compute-counts is used.
+"#,
+    ),
+    Usage::new(
+        "-o, --output <path>",
+        "Write measurements to a file instead of stdout.",
+        r#"
+Write measurements to the file at the given path instead of stdout. The file
+is created if it doesn't exist and truncated if it does. This works with
+every --output-format, and keeps stdout free for other things, e.g., a
+progress display.
+
+This only applies to the measurements collected. The output of --list,
+--verify and --compute-counts is always written to stdout.
+"#,
+    ),
+    Usage::new(
+        "--output-format <name>",
+        "One of: csv, jsonl. (default: csv)",
+        r#"
+The format to write measurements in.
+
+The 'csv' format (the default) writes one row for every measurement, which is
+what every other rebar command reads.
+
+The 'jsonl' format writes one JSON object per line, which is easier to consume
+incrementally (say, for a live dashboard) since each line is complete on its
+own. Every object has a 'type' field. Before each benchmark runs, a progress
+object is written:
+
+    {"type":"progress","completed":3,"total":10,"current":"bench/engine"}
+
+where 'completed' is the number of benchmarks already finished, 'total' is the
+total number of benchmarks to run and 'current' is the full benchmark name
+and regex engine name of the benchmark starting, joined by a '/'. And after
+each benchmark finishes, an object with 'type' set to 'measurement' is written
+containing every field of the measurement (including 'err' for failures).
+Field names match the columns of the CSV files written by this command, except
+that durations are integer nanoseconds with a '_nanos' suffix, like the JSON
+written by 'rebar diff --format json'.
+
+In both formats, output is flushed after every record.
+"#,
+    ),
+    Usage::new(
+        "--progress",
+        "Show progress and an estimated time remaining on stderr.",
+        r#"
+Show the progress of a measurement run on stderr. This is enabled by default
+when stderr is a terminal, and can be disabled with --no-progress.
+
+When stderr is a terminal, a single status line is shown (and rewritten as each
+benchmark starts) with the number of benchmarks completed, the time elapsed,
+an estimate of the time remaining and the benchmark currently running. When
+stderr isn't a terminal, or when --verbose is given (since runner programs may
+then write to stderr too), a plain line is printed as each benchmark starts
+instead, like this:
+
+    [12/240] starting curated/01-literal/sherlock-en: rust/regex
+
+The estimate of the time remaining starts out as the sum of the time budgets
+(--max-time plus --max-warmup-time, or the budgets that a benchmark definition
+overrides them with) of the benchmarks that haven't run yet. Once a few
+benchmarks have completed, it is scaled by how long the completed benchmarks
+actually took relative to their budgets.
+
+This has no effect on --verify, --list or --compute-counts.
+"#,
+    ),
+    Usage::new(
+        "--shuffle[=<seed>]",
+        "Run benchmarks in a random order.",
+        r#"
+Run benchmarks in a pseudo-random order determined by the given seed.
+
+By default, benchmarks are executed in the order in which they are defined.
+This means the same benchmarks always run at the same point in a session,
+e.g., on a CPU that is already hot. Shuffling the order spreads that sort of
+systematic bias across all benchmarks.
+
+When no seed is given, one is chosen at random and printed to stderr so that
+the same order can be reproduced later with --shuffle=<seed>. The shuffle is
+applied after filtering, so --list and --verify show and check benchmarks
+in the same order that they would be measured in. Measurements are written
+in the order in which they are executed.
+"#,
+    ),
+    Usage::new(
+        "--strict-samples",
+        "Fail when a runner prints anything other than samples.",
+        r#"
+Fail a measurement when its runner program prints a line to stdout that isn't
+a valid sample.
+
+By default, lines on stdout that don't parse as samples are ignored, and a
+single warning is emitted for each measurement with the number of lines that
+were ignored along with the first such line. This is useful because some
+runner programs (usually ones that wrap an interpreter) occasionally print
+warnings to stdout. A measurement still fails if no valid samples were
+reported at all, and in that case, the error includes the first non-sample
+line.
+
+This flag restores the stricter behavior where any non-sample line on stdout
+results in an error.
+"#,
+    ),
+    Usage::new(
+        "--strict-capabilities",
+        "Run engines even when they lack a required capability.",
+        r#"
+Run regex engines on benchmarks even when they lack a capability that the
+benchmark requires.
+
+Benchmark definitions may list capabilities (like 'multi-pattern') in their
+'requires' field, and regex engines declare the capabilities they support in
+their 'capabilities' field in 'engines.toml'. By default, a regex engine that
+lacks any of the capabilities required by a benchmark is skipped for that
+benchmark (with a message logged at the debug level). When this flag is given,
+such engines are run anyway, which usually results in an error. This is useful
+for checking whether the capabilities declared in 'engines.toml' are accurate.
+"#,
+    ),
+    Usage::new(
+        "--target-samples <number>",
+        "Size each benchmark's time budget to get about this many samples.",
+        r#"
+Compute a time budget for each benchmark that aims to collect roughly this many
+samples.
+
+Before each benchmark is measured, it is run a few times (just like with
+--verify, but for up to 10 iterations or 10 milliseconds) to estimate how long
+a single iteration takes. This also checks the count reported by the runner
+program, so that an incorrect benchmark fails quickly instead of after a long
+measurement. The estimate is then used to set the maximum time
+of the benchmark to the time it would take to collect the number of samples
+given, and the maximum number of iterations to the number of samples given.
+
+--max-time and --max-iters are still respected as ceilings. So benchmarks on
+very big haystacks will usually collect fewer samples than requested, while
+benchmarks on tiny haystacks no longer spend the full --max-time collecting
+samples that don't improve the measurement. The maximum warmup time is capped
+to half of the computed maximum time.
+
+The budget computed for each benchmark is logged at the debug level.
+"#,
+    ),
     Usage::new(
         "-t/--test",
         "Alias for --verify --verbose.",
@@ -74,12 +512,27 @@ being able to see the full error messages if anything goes wrong.
 Attempts to kill a benchmark if it exceeds this duration.
 
 This is set by default to twice the combined time of --max-time and
---max-warmup-time.
+--max-warmup-time (or the budgets that a benchmark definition overrides them
+with), but never less than 10 seconds.
 
 This is useful to keep long running benchmarks in check. In general, there
 should be no benchmarks that trip this timeout regularly, but the timeout is
 still useful because different environments might execute much more slowly than
 one might expect.
+
+Runner programs are also asked to stop on their own shortly before the timeout.
+When they do, the samples collected so far are kept, and the measurement is
+marked as partial instead of failing. Runner programs that don't understand
+this request ignore it.
+"#,
+    ),
+    Usage::new(
+        "--write",
+        "Write computed counts to benchmark definition files.",
+        r#"
+When used with --compute-counts, counts are added directly to the benchmark
+definition files instead of being printed as TOML snippets. Each count is
+added just before the 'engines' key of its benchmark.
 "#,
     ),
     Usage::new(
@@ -105,9 +558,54 @@ quickly debugging a new benchmark or regex engine where the answers aren't
 lining up.
 
 This collects all errors reported and prints them. If no errors occurred, then
-this prints nothing and exits successfully.
+this prints nothing to stdout and exits successfully. In either case, a summary
+line with the number of benchmarks that passed, failed and were skipped is
+printed to stderr.
+
+When a benchmark fails because of a count mismatch, the count that was actually
+observed is included in the error message.
+"#,
+    ),
+    Usage::new(
+        "--verify-every <number>",
+        "Only verify the count of every Nth iteration.",
+        r#"
+Ask runner programs to only compute the count of the first iteration and then
+every Nth iteration after that. The default is 1, which verifies every
+iteration.
+
+Computing the count is never included in a sample's duration, but it still
+takes time. For the 'compile' model in particular, computing the count requires
+a search in addition to the regex compilation being measured, and that search
+can take much longer than compilation for big haystacks. Setting this to a
+number bigger than 1 means more samples can be collected in the same amount of
+wall clock time.
+
+This is only supported by runner programs that use the 'timer' crate in
+'shared/timer' (which is most of the Rust runner programs). Other runner
+programs will fail when this is set to anything other than 1.
+"#,
+    ),
+    Usage::new(
+        "--verify-repeat <number>",
+        "Verify each benchmark N times to detect flaky counts.",
+        r#"
+Run the verification of each benchmark and regex engine pair the given number
+of times, and compare the counts observed across all of the runs. This implies
+--verify. The default is 1.
+
+A pair that reports different counts on different runs is reported as 'flaky',
+along with each of the distinct counts that were observed. This is reported
+separately from a plain count mismatch, where the same wrong count is reported
+every time. A flaky count usually points to a bug in a runner program, like
+reusing state across iterations or depending on the address of the haystack.
+
+When --verify or --verify-repeat is used, a summary line with the number of
+pairs that were ok, had a count mismatch, were flaky or failed for some other
+reason is printed to stderr at the end.
 "#,
     ),
+    Usage::VERSION_CACHE_TTL,
 ];
 
 fn usage_short() -> String {
@@ -156,8 +654,22 @@ OPTIONS:
 
 pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
     // Parse everything and load what we need.
-    let config = Config::parse(p)?;
+    let mut config = Config::parse(p)?;
+    config.bench_config.scheduling =
+        config.bench_config.scheduling.check(&OsScheduler);
+    if config.bench_config.leak_check {
+        anyhow::ensure!(
+            cfg!(unix),
+            "--leak-check is unsupported on this platform",
+        );
+    }
     let benchmarks = config.read_benchmarks()?;
+    if config.locked {
+        benchmarks.engines.check_locked()?;
+    }
+    if config.compute_counts {
+        return compute_counts(&config, &benchmarks);
+    }
 
     // Collect all of the benchmarks we will run. Each benchmark definition can
     // spawn multiple benchmarks; one for each regex engine specified in the
@@ -176,60 +688,211 @@ pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
             if !config.filters.engine.include(&b.engine.name) {
                 continue;
             }
+            // Leak checking is only meaningful for the 'compile' model, since
+            // it's the only one that builds a new regex on every iteration.
+            if config.bench_config.leak_check && b.def.model != "compile" {
+                log::debug!(
+                    "skipping {}:{} for leak check because its model is '{}'",
+                    b.def.name,
+                    b.engine.name,
+                    b.def.model,
+                );
+                continue;
+            }
             exec_benchmarks.push(b);
         }
     }
+    if config.shuffle {
+        let seed = config.shuffle_seed.unwrap_or_else(|| {
+            let seed = Rng::random_seed();
+            eprintln!(
+                "shuffling benchmarks with seed {} \
+                 (use --shuffle={} to reproduce)",
+                seed, seed,
+            );
+            seed
+        });
+        Rng::new(seed).shuffle(&mut exec_benchmarks);
+    }
+    // If we're holding a benchmark for profiling, then we run it and bail
+    // without emitting any measurement.
+    if let Some(hold) = config.bench_config.hold {
+        anyhow::ensure!(
+            exec_benchmarks.len() == 1,
+            "--hold requires filters that select exactly one benchmark and \
+             regex engine pair, but {} were selected",
+            exec_benchmarks.len(),
+        );
+        let b = &exec_benchmarks[0];
+        writeln!(
+            std::io::stderr(),
+            "{}:{}: holding for {}",
+            b.def.name,
+            b.engine.name,
+            ShortHumanDuration::from(hold),
+        )?;
+        b.collect(true)?;
+        return Ok(());
+    }
     // If we just want to list which benchmarks we'll run, spit that out.
     if config.list {
-        let mut wtr = csv::Writer::from_writer(std::io::stdout());
-        for b in exec_benchmarks.iter() {
-            wtr.write_record(&[
-                b.def.name.to_string(),
-                b.def.model.to_string(),
-                b.engine.name.clone(),
-                b.engine.version.clone(),
-            ])?;
+        match config.list_format {
+            ListFormat::Csv => {
+                let mut wtr = csv::Writer::from_writer(std::io::stdout());
+                for b in exec_benchmarks.iter() {
+                    wtr.write_record(&[
+                        b.def.name.to_string(),
+                        b.def.model.to_string(),
+                        b.engine.name.clone(),
+                        b.engine.version.clone(),
+                    ])?;
+                }
+                wtr.flush()?;
+            }
+            ListFormat::Json => {
+                let mut wtr = std::io::stdout().lock();
+                for b in exec_benchmarks.iter() {
+                    serde_json::to_writer(&mut wtr, &b.list_record())?;
+                    writeln!(wtr)?;
+                }
+                wtr.flush()?;
+            }
         }
-        wtr.flush()?;
         return Ok(());
     }
+    // Benchmarks whose count is derived from a reference regex engine need
+    // that engine to run first.
+    derive_counts(&config, &mut exec_benchmarks)?;
+    // We never want to run benchmarks whose counts haven't been filled in,
+    // since we'd be measuring something that was never verified.
+    for b in exec_benchmarks.iter() {
+        anyhow::ensure!(
+            !b.def.is_count_unresolved(),
+            "benchmark '{}' has no count, use 'rebar measure \
+             --compute-counts --oracle <engine>' to compute one",
+            b.def.name,
+        );
+    }
     // Or if we just want to check that every benchmark runs correctly, do
     // that. We spit out any error we find.
     if config.verify {
-        let mut errored = false;
+        let repeat = config.verify_repeat.unwrap_or(1);
+        let (mut ok, mut mismatch, mut flaky, mut error, mut skipped) =
+            (0, 0, 0, 0, 0);
         let mut wtr = csv::Writer::from_writer(std::io::stdout());
         for b in exec_benchmarks.iter() {
-            let agg = b.aggregate(b.verifier().collect(config.verbose));
-            if let Some(err) = agg.err {
-                errored = true;
-                wtr.write_record(&[
-                    b.def.name.to_string(),
-                    b.def.model.to_string(),
-                    b.engine.name.clone(),
-                    b.engine.version.clone(),
-                    format!("{:#}", err),
-                ])?;
-            } else if config.verbose {
-                wtr.write_record(&[
-                    b.def.name.to_string(),
-                    b.def.model.to_string(),
-                    b.engine.name.clone(),
-                    b.engine.version.clone(),
-                    "OK".to_string(),
-                ])?;
-            }
+            let status = match Verdict::new(b, repeat, config.verbose) {
+                // A regex engine that can't run a benchmark isn't a
+                // failure, but we still mention it in verbose mode.
+                Verdict::Skipped(reason) => {
+                    skipped += 1;
+                    if !config.verbose {
+                        continue;
+                    }
+                    format!("SKIPPED ({})", reason)
+                }
+                Verdict::Mismatch(msg) => {
+                    mismatch += 1;
+                    msg
+                }
+                Verdict::Flaky(counts) => {
+                    flaky += 1;
+                    let counts: Vec<String> =
+                        counts.iter().map(|n| n.to_string()).collect();
+                    format!(
+                        "FLAKY (counts differed across {} runs: {})",
+                        repeat,
+                        counts.join(", "),
+                    )
+                }
+                Verdict::Error(msg) => {
+                    error += 1;
+                    msg
+                }
+                Verdict::Ok => {
+                    ok += 1;
+                    if !config.verbose {
+                        continue;
+                    }
+                    // For derived counts, say where the count came from,
+                    // since it doesn't appear anywhere in the benchmark
+                    // definition.
+                    match b.def.count.first() {
+                        Some(CountEngine {
+                            derived_from: Some(ref from),
+                            count,
+                            ..
+                        }) => format!(
+                            "OK (count {} derived from {})",
+                            count, from
+                        ),
+                        _ => "OK".to_string(),
+                    }
+                }
+            };
+            wtr.write_record(&[
+                b.def.name.to_string(),
+                b.def.model.to_string(),
+                b.engine.name.clone(),
+                b.engine.version.clone(),
+                status,
+            ])?;
             wtr.flush()?;
         }
-        anyhow::ensure!(!errored, "some benchmarks failed");
+        eprintln!(
+            "{} ok, {} mismatch, {} flaky, {} error, {} skipped",
+            ok, mismatch, flaky, error, skipped,
+        );
+        anyhow::ensure!(
+            mismatch + flaky + error == 0,
+            "some benchmarks failed"
+        );
         return Ok(());
     }
-    // Run our benchmarks and emit the results of each as a single CSV record.
-    let mut wtr = csv::Writer::from_writer(std::io::stdout());
-    for b in exec_benchmarks.iter() {
+    if let Some(ref dir) = config.dump_samples {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    // Run our benchmarks and emit the results of each as a single record.
+    let out: Box<dyn Write> = match config.output {
+        None => Box::new(std::io::stdout()),
+        Some(ref path) => Box::new(
+            std::fs::File::create(path)
+                .with_context(|| format!("-o/--output {}", path.display()))?,
+        ),
+    };
+    let mut wtr = MeasurementWriter::new(config.output_format, out);
+    let mut hasher = DefinitionHasher::default();
+    let defs_revision = util::git_revision(&config.dir);
+    let progress = config
+        .progress
+        .unwrap_or_else(|| std::io::stderr().is_terminal())
+        .then(|| Progress::new(config.verbose, &exec_benchmarks));
+    for (i, b) in exec_benchmarks.iter().enumerate() {
+        wtr.progress(i, exec_benchmarks.len(), b)?;
+        if let Some(ref progress) = progress {
+            progress.start(i, b)?;
+        }
         // Run the benchmark, collect the samples and turn the samples into a
         // collection of various aggregate statistics (mean+/-stddev, median,
         // min, max).
-        let agg = b.aggregate(b.collect(config.verbose));
+        let results = match b.calibrate() {
+            Ok(b) => b.collect(config.verbose),
+            Err(err) => Err(err),
+        };
+        if let (Some(ref dir), Ok(ref results)) =
+            (&config.dump_samples, &results)
+        {
+            results.dump_samples(dir)?;
+        }
+        let mut agg = b.aggregate(results);
+        agg.def_hash = Some(hasher.hash(&b.def));
+        agg.defs_revision = defs_revision.clone();
+        config.bench_config.scheduling.record(&mut agg);
+        config.bench_config.env.record(&mut agg);
+        if let Some(ref progress) = progress {
+            progress.clear()?;
+        }
         // Our aggregate is initially captured in terms of how long it takes to
         // execute each iteration of the benchmark. But for searching, this is
         // not particularly intuitive. Instead, we convert strict timings into
@@ -246,14 +909,247 @@ pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
         // benchmarks, and indeed, we set it up so that we don't capture any
         // haystack length for them. This causes the units to be in absolute
         // time by default.
-        wtr.serialize(agg)?;
-        // Flush every record once we have it so that users can see that
-        // progress is being made.
-        wtr.flush()?;
+        wtr.write(agg)?;
+    }
+    Ok(())
+}
+
+/// Resolves the count of every benchmark whose definition derives its count
+/// from a reference regex engine.
+///
+/// The reference engine is run for a single iteration on each such definition
+/// (only once per definition, regardless of how many engines it has), and
+/// whatever count it reports becomes the expected count for every engine.
+/// This returns an error if the reference engine can't be found or fails.
+fn derive_counts(
+    config: &Config,
+    exec_benchmarks: &mut [ExecBenchmark],
+) -> anyhow::Result<()> {
+    // Reference engines don't need to be in the list of engines for each
+    // benchmark (and they might have been filtered out), so we load them
+    // ourselves. We also cache the count we derive for each definition.
+    let mut references: BTreeMap<String, Engine> = BTreeMap::new();
+    let mut derived: BTreeMap<String, CountEngine> = BTreeMap::new();
+    for b in exec_benchmarks.iter_mut() {
+        let Some(ref name) = b.def.count_derive_from else { continue };
+        if let Some(ce) = derived.get(b.def.name.as_str()) {
+            b.def.count = vec![ce.clone()];
+            continue;
+        }
+        if !references.contains_key(name) {
+            let reference = match Engines::from_file(
+                &config.dir,
+                &config.load_options(),
+                |e| e.name == *name,
+            )?
+            .list
+            .pop()
+            {
+                Some(e) if !e.is_missing_version() => e,
+                Some(_) => anyhow::bail!(
+                    "{}: reference regex engine '{}' is not available \
+                         (is it built?)",
+                    b.def.name,
+                    name,
+                ),
+                None => anyhow::bail!(
+                    "{}: could not find reference regex engine '{}' in \
+                         engines.toml",
+                    b.def.name,
+                    name,
+                ),
+            };
+            references.insert(name.clone(), reference);
+        }
+        let reference = &references[name];
+        let results = ExecBenchmark {
+            config: config.bench_config.clone(),
+            def: b.def.clone(),
+            engine: reference.clone(),
+        }
+        .verifier()
+        .collect(config.verbose)
+        .with_context(|| {
+            format!(
+                "{}: failed to derive count from reference regex engine '{}'",
+                b.def.name, reference.name,
+            )
+        })?;
+        let Some(count) = results.count else {
+            anyhow::bail!(
+                "{}: reference regex engine '{}' did not report any samples",
+                b.def.name,
+                reference.name,
+            );
+        };
+        let from = format!("{} {}", reference.name, reference.version);
+        log::debug!("{}: derived count {} from {}", b.def.name, count, from);
+        let mut ce = CountEngine::all(count);
+        ce.derived_from = Some(from);
+        b.def.count = vec![ce.clone()];
+        derived.insert(b.def.name.to_string(), ce);
+    }
+    Ok(())
+}
+
+/// Runs the oracle regex engine on every benchmark definition without a count
+/// and reports the count it finds. When --write is given, the counts are added
+/// to the benchmark definition files. Otherwise, they're printed as TOML.
+fn compute_counts(
+    config: &Config,
+    benchmarks: &Benchmarks,
+) -> anyhow::Result<()> {
+    // The oracle doesn't need to be in the list of engines for each
+    // benchmark (and it might have been filtered out), so we load it
+    // ourselves.
+    let oracle_name = config.oracle.as_deref().unwrap();
+    let oracle =
+        match Engines::from_file(&config.dir, &config.load_options(), |e| {
+            e.name == oracle_name
+        })?
+        .list
+        .pop()
+        {
+            Some(oracle) => oracle,
+            None => anyhow::bail!(
+                "could not find oracle regex engine '{}' in engines.toml",
+                oracle_name,
+            ),
+        };
+    let mut out = std::io::stdout().lock();
+    let mut failed = 0;
+    for def in benchmarks.defs.iter() {
+        if !def.is_count_unresolved() {
+            log::debug!("{}: skipping because it has a count", def.name);
+            continue;
+        }
+        if def.count_derive_from.is_some() {
+            log::debug!(
+                "{}: skipping because its count is derived at measure time",
+                def.name,
+            );
+            continue;
+        }
+        if def.model == "count-per-pattern" {
+            // Runner programs only report a checksum for this model, which
+            // can't be turned back into a count for each pattern.
+            log::warn!(
+                "{}: counts for the 'count-per-pattern' model cannot be \
+                 computed by an oracle, 'counts-per-pattern' must be \
+                 written by hand",
+                def.name,
+            );
+            failed += 1;
+            continue;
+        }
+        let b = ExecBenchmark {
+            config: config.bench_config.clone(),
+            def: def.clone(),
+            engine: oracle.clone(),
+        }
+        .verifier();
+        let count = match b.collect(config.verbose) {
+            Ok(Results { count: Some(count), .. }) => count,
+            Ok(Results { count: None, .. }) => {
+                log::warn!(
+                    "{}: oracle '{}' did not report any samples",
+                    def.name,
+                    oracle.name,
+                );
+                failed += 1;
+                continue;
+            }
+            Err(err) => {
+                log::warn!(
+                    "{}: failed to run oracle '{}': {:#}",
+                    def.name,
+                    oracle.name,
+                    err,
+                );
+                failed += 1;
+                continue;
+            }
+        };
+        if config.write {
+            let path = write_count(&config.dir, def, count)?;
+            writeln!(
+                out,
+                "{}: wrote count = {} to {}",
+                def.name,
+                count,
+                path.display(),
+            )?;
+        } else {
+            writeln!(
+                out,
+                "# {} ({} {})",
+                def.name, oracle.name, oracle.version
+            )?;
+            writeln!(out, "count = {}", count)?;
+            writeln!(out)?;
+        }
     }
+    anyhow::ensure!(
+        failed == 0,
+        "failed to compute counts for {} benchmark(s)",
+        failed,
+    );
     Ok(())
 }
 
+/// Adds 'count = <count>' to the TOML file containing the given definition,
+/// and returns the path to that file.
+///
+/// This works by editing the file as text (instead of round-tripping it
+/// through a TOML serializer) so that comments and formatting are preserved.
+/// The count is added just before the 'engines' key of the definition's
+/// '[[bench]]' table, or just after its 'name' key if there is no 'engines'
+/// key.
+fn write_count(
+    bench_dir: &Path,
+    def: &Definition,
+    count: u64,
+) -> anyhow::Result<PathBuf> {
+    let path =
+        bench_dir.join("definitions").join(format!("{}.toml", def.name.group));
+    let data = std::fs::read_to_string(&path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let mut lines: Vec<&str> = data.lines().collect();
+    let is_name = |line: &str| -> bool {
+        let Some((key, value)) = line.split_once('=') else { return false };
+        let value = value.trim();
+        key.trim() == "name"
+            && (value == format!("\"{}\"", def.name.local)
+                || value == format!("'{}'", def.name.local))
+    };
+    let name_index = match lines.iter().position(|line| is_name(line)) {
+        Some(i) => i,
+        None => anyhow::bail!(
+            "could not find 'name' key for benchmark '{}' in {}",
+            def.name,
+            path.display(),
+        ),
+    };
+    let mut insert_at = name_index + 1;
+    for (i, line) in lines.iter().enumerate().skip(name_index + 1) {
+        let line = line.trim_start();
+        if line.starts_with("[[bench]]") {
+            break;
+        }
+        if line.starts_with("engines") {
+            insert_at = i;
+            break;
+        }
+    }
+    let count_line = format!("count = {}", count);
+    lines.insert(insert_at, &count_line);
+    let mut new = lines.join("\n");
+    new.push('\n');
+    std::fs::write(&path, new)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
+
 /// The CLI arguments parsed from the 'measure' sub-command.
 #[derive(Clone, Debug, Default)]
 struct Config {
@@ -267,11 +1163,51 @@ struct Config {
     /// then quit. This also tests that all of the benchmark data can be
     /// deserialized.
     list: bool,
+    /// The format to use when listing benchmarks.
+    list_format: ListFormat,
     /// Whether to just verify all of the benchmarks without collecting any
     /// measurements.
     verify: bool,
+    /// The number of times to verify each benchmark when 'verify' is
+    /// enabled. When absent, each benchmark is verified once.
+    verify_repeat: Option<u64>,
     /// When enabled, print extra stuff where appropriate.
     verbose: bool,
+    /// When enabled, don't use the on-disk cache of haystacks and regexes.
+    no_cache: bool,
+    /// When enabled, don't use the on-disk cache of regex engine versions.
+    no_version_cache: bool,
+    /// How long a cached regex engine version is valid. When absent, the
+    /// default TTL is used.
+    version_cache_ttl: Option<Duration>,
+    /// When enabled, haystacks defined by a URL may be downloaded.
+    allow_download: bool,
+    /// When enabled, a regex engine whose version doesn't match its expected
+    /// version is an error instead of a warning.
+    locked: bool,
+    /// When present, every sample collected for every benchmark is written
+    /// to a CSV file in this directory.
+    dump_samples: Option<PathBuf>,
+    /// When enabled, compute counts for benchmarks without one instead of
+    /// collecting measurements.
+    compute_counts: bool,
+    /// The regex engine used to compute counts.
+    oracle: Option<String>,
+    /// Whether to write computed counts to benchmark definition files.
+    write: bool,
+    /// Whether to shuffle the order in which benchmarks are executed.
+    shuffle: bool,
+    /// The seed to shuffle with. When absent and shuffling is enabled, a
+    /// seed is chosen at random.
+    shuffle_seed: Option<u64>,
+    /// A file to write measurements to. When absent, measurements are written
+    /// to stdout.
+    output: Option<PathBuf>,
+    /// The format to write measurements in.
+    output_format: OutputFormat,
+    /// Whether to show a progress indicator on stderr. When absent, it's
+    /// shown only when stderr is a terminal.
+    progress: Option<bool>,
 }
 
 impl Config {
@@ -279,8 +1215,8 @@ impl Config {
     fn parse(p: &mut lexopt::Parser) -> anyhow::Result<Config> {
         use lexopt::Arg;
 
-        let mut c = Config::default();
-        c.dir = PathBuf::from("benchmarks");
+        let mut c =
+            Config { dir: PathBuf::from("benchmarks"), ..Config::default() };
         while let Some(arg) = p.next()? {
             match arg {
                 Arg::Short('h') => anyhow::bail!("{}", usage_short()),
@@ -288,35 +1224,96 @@ impl Config {
                 Arg::Short('d') | Arg::Long("dir") => {
                     c.dir = PathBuf::from(p.value().context("-d/--dir")?);
                 }
-                Arg::Short('e') | Arg::Long("engine") => {
-                    c.filters.engine.arg_whitelist(p, "-e/--engine")?;
+                Arg::Long("affinity") => {
+                    c.bench_config.scheduling.affinity =
+                        Some(args::parse(p, "--affinity")?);
+                }
+                Arg::Long("compute-counts") => {
+                    c.compute_counts = true;
+                }
+                Arg::Long("dump-samples") => {
+                    let dir = p.value().context("--dump-samples")?;
+                    c.dump_samples = Some(PathBuf::from(dir));
+                }
+                Arg::Short('e') | Arg::Long("engine") => {
+                    c.filters.engine.arg_whitelist(p, "-e/--engine")?;
                 }
                 Arg::Short('E') | Arg::Long("engine-not") => {
                     c.filters.engine.arg_blacklist(p, "-E/--engine-not")?;
                 }
+                Arg::Long("engine-file") => {
+                    c.filters.engine.arg_whitelist_file(p, "--engine-file")?;
+                }
+                Arg::Long("engine-not-file") => {
+                    c.filters
+                        .engine
+                        .arg_blacklist_file(p, "--engine-not-file")?;
+                }
+                Arg::Long("env") => {
+                    c.bench_config.env.vars.push(args::parse(p, "--env")?);
+                }
+                Arg::Long("env-record-values") => {
+                    c.bench_config.env.record_values = true;
+                }
                 Arg::Short('f') | Arg::Long("filter") => {
                     c.filters.name.arg_whitelist(p, "-f/--filter")?;
                 }
                 Arg::Short('F') | Arg::Long("filter-not") => {
                     c.filters.name.arg_blacklist(p, "-F/--filter-not")?;
                 }
+                Arg::Long("filter-file") => {
+                    c.filters.name.arg_whitelist_file(p, "--filter-file")?;
+                }
+                Arg::Long("filter-not-file") => {
+                    c.filters
+                        .name
+                        .arg_blacklist_file(p, "--filter-not-file")?;
+                }
+                Arg::Long("hold") => {
+                    let hdur = args::parse::<ShortHumanDuration>(p, "--hold")?;
+                    c.bench_config.hold = Some(Duration::from(hdur));
+                }
                 Arg::Short('i') | Arg::Long("ignore-missing-engines") => {
                     c.filters.ignore_missing_engines = true;
                 }
+                Arg::Long("leak-check") => {
+                    c.bench_config.leak_check = true;
+                }
+                Arg::Long("leak-check-iters") => {
+                    c.bench_config.leak_check_iters =
+                        args::parse(p, "--leak-check-iters")?;
+                }
+                Arg::Long("leak-check-max-growth") => {
+                    let size = args::parse::<ShortHumanSize>(
+                        p,
+                        "--leak-check-max-growth",
+                    )?;
+                    c.bench_config.leak_check_max_growth = size.bytes() as f64;
+                }
                 Arg::Long("list") => {
                     c.list = true;
                 }
+                Arg::Long("list-format") => {
+                    c.list = true;
+                    c.list_format = args::parse(p, "--list-format")?;
+                }
+                Arg::Long("locked") => {
+                    c.locked = true;
+                }
                 Arg::Long("max-iters") => {
                     c.bench_config.max_iters = args::parse(p, "--max-iters")?;
+                    c.bench_config.explicit.max_iters = true;
                 }
                 Arg::Long("max-warmup-iters") => {
                     c.bench_config.max_warmup_iters =
                         args::parse(p, "--max-warmup-iters")?;
+                    c.bench_config.explicit.max_warmup_iters = true;
                 }
                 Arg::Long("max-time") => {
                     let hdur =
                         args::parse::<ShortHumanDuration>(p, "--max-time")?;
                     c.bench_config.max_time = Duration::from(hdur);
+                    c.bench_config.explicit.max_time = true;
                 }
                 Arg::Long("max-warmup-time") => {
                     let hdur = args::parse::<ShortHumanDuration>(
@@ -324,6 +1321,7 @@ impl Config {
                         "--max-warmup-time",
                     )?;
                     c.bench_config.max_warmup_time = Duration::from(hdur);
+                    c.bench_config.explicit.max_warmup_time = true;
                 }
                 Arg::Short('m') | Arg::Long("model") => {
                     c.filters.model.arg_whitelist(p, "-m/--model")?;
@@ -331,6 +1329,56 @@ impl Config {
                 Arg::Short('M') | Arg::Long("model-not") => {
                     c.filters.model.arg_blacklist(p, "-M/--model-not")?;
                 }
+                Arg::Long("nice") => {
+                    let nice: i32 = args::parse(p, "--nice")?;
+                    anyhow::ensure!(
+                        Scheduling::NICE_RANGE.contains(&nice),
+                        "--nice must be in the range {:?}",
+                        Scheduling::NICE_RANGE,
+                    );
+                    c.bench_config.scheduling.nice = Some(nice);
+                }
+                Arg::Long("oracle") => {
+                    let name = p.value().context("--oracle")?;
+                    c.oracle = Some(name.string().context("--oracle")?);
+                }
+                Arg::Short('o') | Arg::Long("output") => {
+                    let path = p.value().context("-o/--output")?;
+                    c.output = Some(PathBuf::from(path));
+                }
+                Arg::Long("output-format") => {
+                    c.output_format = args::parse(p, "--output-format")?;
+                }
+                Arg::Long("progress") => {
+                    c.progress = Some(true);
+                }
+                Arg::Long("no-progress") => {
+                    c.progress = Some(false);
+                }
+                Arg::Long("shuffle") => {
+                    c.shuffle = true;
+                    if let Some(seed) = p.optional_value() {
+                        let seed = seed.string().context("--shuffle")?;
+                        let seed = seed.parse().with_context(|| {
+                            format!("--shuffle: invalid seed '{}'", seed)
+                        })?;
+                        c.shuffle_seed = Some(seed);
+                    }
+                }
+                Arg::Long("strict-capabilities") => {
+                    c.filters.strict_capabilities = true;
+                }
+                Arg::Long("strict-samples") => {
+                    c.bench_config.strict_samples = true;
+                }
+                Arg::Long("target-samples") => {
+                    let n = args::parse(p, "--target-samples")?;
+                    anyhow::ensure!(
+                        n > 0,
+                        "--target-samples must be greater than zero",
+                    );
+                    c.bench_config.target_samples = Some(n);
+                }
                 Arg::Short('t') | Arg::Long("test") => {
                     c.verbose = true;
                     c.verify = true;
@@ -339,6 +1387,23 @@ impl Config {
                     let hdur =
                         args::parse::<ShortHumanDuration>(p, "--timeout")?;
                     c.bench_config.timeout = Duration::from(hdur);
+                    c.bench_config.explicit.timeout = true;
+                }
+                Arg::Long("no-cache") => {
+                    c.no_cache = true;
+                }
+                Arg::Long("no-version-cache") => {
+                    c.no_version_cache = true;
+                }
+                Arg::Long("version-cache-ttl") => {
+                    let hdur = args::parse::<ShortHumanDuration>(
+                        p,
+                        "--version-cache-ttl",
+                    )?;
+                    c.version_cache_ttl = Some(Duration::from(hdur));
+                }
+                Arg::Long("allow-download") => {
+                    c.allow_download = true;
                 }
                 Arg::Long("verbose") => {
                     c.verbose = true;
@@ -346,16 +1411,114 @@ impl Config {
                 Arg::Long("verify") => {
                     c.verify = true;
                 }
+                Arg::Long("iter-batch") => {
+                    c.bench_config.iter_batch =
+                        args::parse(p, "--iter-batch")?;
+                }
+                Arg::Long("verify-every") => {
+                    let n = args::parse(p, "--verify-every")?;
+                    anyhow::ensure!(
+                        n > 0,
+                        "--verify-every must be greater than zero",
+                    );
+                    c.bench_config.verify_every = n;
+                }
+                Arg::Long("verify-repeat") => {
+                    let n = args::parse(p, "--verify-repeat")?;
+                    anyhow::ensure!(
+                        n > 0,
+                        "--verify-repeat must be greater than zero",
+                    );
+                    c.verify = true;
+                    c.verify_repeat = Some(n);
+                }
+                Arg::Long("write") => {
+                    c.write = true;
+                }
                 _ => return Err(arg.unexpected().into()),
             }
         }
+        if c.bench_config.leak_check {
+            // Warmup iterations aren't useful for detecting leaks, and
+            // disabling them means that every RSS sample we take is during
+            // an iteration we know about. That in turn makes our estimate of
+            // growth per iteration better.
+            c.bench_config.max_iters = c.bench_config.leak_check_iters;
+            c.bench_config.max_warmup_iters = 0;
+            c.bench_config.explicit.max_iters = true;
+            c.bench_config.explicit.max_warmup_iters = true;
+        }
+        if c.compute_counts {
+            anyhow::ensure!(
+                c.oracle.is_some(),
+                "--compute-counts requires --oracle",
+            );
+        } else {
+            anyhow::ensure!(
+                c.oracle.is_none() && !c.write,
+                "--oracle and --write can only be used with --compute-counts",
+            );
+        }
+        if c.bench_config.iter_batch != IterBatch::Fixed(1) {
+            // Leak checking estimates growth per iteration, which would be
+            // thrown off by batching.
+            anyhow::ensure!(
+                !c.bench_config.leak_check,
+                "--iter-batch cannot be combined with --leak-check",
+            );
+        }
+        if c.bench_config.target_samples.is_some() {
+            anyhow::ensure!(
+                c.bench_config.hold.is_none() && !c.bench_config.leak_check,
+                "--target-samples cannot be combined with --hold or \
+                 --leak-check",
+            );
+        }
+        if let Some(hold) = c.bench_config.hold {
+            anyhow::ensure!(
+                !c.list
+                    && !c.verify
+                    && !c.bench_config.leak_check
+                    && c.dump_samples.is_none(),
+                "--hold cannot be combined with --list, --verify, \
+                 --leak-check or --dump-samples",
+            );
+            c.bench_config.max_iters = HOLD_MAX_ITERS;
+            c.bench_config.max_warmup_iters = 0;
+            c.bench_config.max_time = hold;
+            c.bench_config.timeout =
+                std::cmp::max(c.bench_config.timeout, hold + MIN_TIMEOUT);
+            // None of the above should be overridden by a benchmark
+            // definition, since holding is all about running for the
+            // duration given.
+            c.bench_config.explicit = ExplicitBudget {
+                max_iters: true,
+                max_warmup_iters: true,
+                max_time: true,
+                max_warmup_time: true,
+                timeout: true,
+            };
+        }
         Ok(c)
     }
 
     /// Read and parse benchmark definitions from TOML files in the --dir
     /// directory.
     fn read_benchmarks(&self) -> anyhow::Result<Benchmarks> {
-        Benchmarks::from_dir(&self.dir, &self.filters)
+        Benchmarks::from_dir(&self.dir, &self.filters, &self.load_options())
+    }
+
+    /// Returns the options for loading benchmark definitions and regex
+    /// engines.
+    fn load_options(&self) -> LoadOptions {
+        LoadOptions {
+            cache: !self.no_cache,
+            allow_download: self.allow_download,
+            version_cache: !self.no_version_cache,
+            version_cache_ttl: self
+                .version_cache_ttl
+                .unwrap_or(VersionCache::DEFAULT_TTL),
+        }
     }
 }
 
@@ -366,12 +1529,12 @@ impl Config {
 /// you kind of need to wait a little bit or else benchmark results tend to be
 /// quite noisy.
 #[derive(Clone, Debug)]
-struct ExecBenchmarkConfig {
+pub struct ExecBenchmarkConfig {
     /// The maximum number of samples to collect.
-    max_iters: u64,
+    pub max_iters: u64,
     /// The maximum number of times to execute the benchmark before collecting
     /// samples.
-    max_warmup_iters: u64,
+    pub max_warmup_iters: u64,
     /// The approximate amount of time the benchmark should run. The idea here
     /// is to collect as many samples as possible, up to the max and only for
     /// as long as we are in our time budget.
@@ -380,32 +1543,759 @@ struct ExecBenchmarkConfig {
     /// every benchmark, but this is in practice basically impossible when your
     /// benchmarks include things that are blindingly fast like 'memmem' and
     /// things that are tortoise slow, like the Pike VM.
-    max_time: Duration,
+    pub max_time: Duration,
     /// Like max benchmark time, but for warmup time. As a general rule, it's
     /// usually good to have this be about half the benchmark time.
-    max_warmup_time: Duration,
+    pub max_warmup_time: Duration,
     /// After this amount of time has passed, the benchmark runner is
     /// unceremoniously killed and measurement reporting for that benchmark
     /// fails.
-    timeout: Duration,
+    pub timeout: Duration,
+    /// When enabled, the RSS of the runner process is sampled while the
+    /// benchmark executes, and the measurement fails if it looks like memory
+    /// is growing without bound.
+    pub leak_check: bool,
+    /// The number of iterations to run when leak checking is enabled. This
+    /// overrides 'max_iters'.
+    pub leak_check_iters: u64,
+    /// The maximum RSS growth, in bytes per iteration, that is permitted
+    /// before a leak check fails.
+    pub leak_check_max_growth: f64,
+    /// When present, the benchmark is run for this long for the purposes of
+    /// attaching a profiler to it. Its stdout is discarded and its stderr is
+    /// passed through.
+    pub hold: Option<Duration>,
+    /// When enabled, any line printed to stdout by a runner program that
+    /// isn't a valid sample causes the measurement to fail. Otherwise, such
+    /// lines are ignored (with a warning).
+    pub strict_samples: bool,
+    /// When present, a single calibration iteration is run before each
+    /// benchmark and used to shrink 'max_time' and 'max_iters' such that
+    /// roughly this many samples are collected.
+    pub target_samples: Option<u64>,
+    /// How often runner programs should verify the count of an iteration.
+    /// See the 'verify_every' field on 'klv::Benchmark'.
+    pub verify_every: u64,
+    /// Whether to send the expected count to runner programs that support
+    /// it. When disabled, count mismatches are always detected by rebar
+    /// instead of the runner program, which means the count that was
+    /// actually observed is known.
+    pub send_expected_count: bool,
+    /// How many times runner programs should run a benchmark for each
+    /// sample. See the 'iter_batch' field on 'klv::Benchmark'.
+    pub iter_batch: IterBatch,
+    /// The CPU affinity and priority to give the runner process.
+    pub scheduling: Scheduling,
+    /// The environment variables to inject into the runner process.
+    pub env: InjectedEnv,
+    /// Which of the budgets above were set explicitly, and thus shouldn't be
+    /// overridden by a benchmark definition.
+    pub explicit: ExplicitBudget,
+}
+
+impl ExecBenchmarkConfig {
+    /// Returns the config to use for measuring the given benchmark
+    /// definition.
+    ///
+    /// Every budget overridden by the definition replaces the corresponding
+    /// budget in this config, unless it was set explicitly. And unless the
+    /// timeout was set explicitly, it's derived from the resulting time
+    /// budgets.
+    pub fn for_definition(&self, def: &Definition) -> ExecBenchmarkConfig {
+        let (budget, explicit) = (&def.budget, &self.explicit);
+        let mut config = self.clone();
+        if let Some(n) = budget.max_iters.filter(|_| !explicit.max_iters) {
+            config.max_iters = n;
+        }
+        if let Some(n) =
+            budget.max_warmup_iters.filter(|_| !explicit.max_warmup_iters)
+        {
+            config.max_warmup_iters = n;
+        }
+        if let Some(d) = budget.max_time.filter(|_| !explicit.max_time) {
+            config.max_time = d;
+        }
+        if let Some(d) =
+            budget.max_warmup_time.filter(|_| !explicit.max_warmup_time)
+        {
+            config.max_warmup_time = d;
+        }
+        if !explicit.timeout {
+            config.timeout =
+                default_timeout(config.max_time, config.max_warmup_time);
+        }
+        config
+    }
 }
 
 impl Default for ExecBenchmarkConfig {
     fn default() -> ExecBenchmarkConfig {
         let max_time = Duration::from_millis(3000);
         let max_warmup_time = max_time / 2;
-        let timeout =
-            std::cmp::max(MIN_TIMEOUT, 2 * (max_time + max_warmup_time));
+        let timeout = default_timeout(max_time, max_warmup_time);
         ExecBenchmarkConfig {
             max_warmup_iters: 1_000_000,
             max_iters: 1_000_000,
             max_time,
             max_warmup_time,
             timeout,
+            leak_check: false,
+            leak_check_iters: 100_000,
+            leak_check_max_growth: 256.0,
+            hold: None,
+            strict_samples: false,
+            target_samples: None,
+            verify_every: 1,
+            send_expected_count: true,
+            iter_batch: IterBatch::Fixed(1),
+            scheduling: Scheduling::default(),
+            env: InjectedEnv::default(),
+            explicit: ExplicitBudget::default(),
+        }
+    }
+}
+
+/// Records which budgets in an `ExecBenchmarkConfig` were set explicitly,
+/// e.g., by a command line flag. Explicitly set budgets take precedence over
+/// the overrides in a benchmark definition.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExplicitBudget {
+    pub max_iters: bool,
+    pub max_warmup_iters: bool,
+    pub max_time: bool,
+    pub max_warmup_time: bool,
+    pub timeout: bool,
+}
+
+/// The CPU affinity and priority given to each runner process, as set by
+/// '--affinity' and '--nice'.
+///
+/// This is recorded in every measurement so that CSV files say how they were
+/// captured.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Scheduling {
+    /// The CPU core to pin the runner process to.
+    pub affinity: Option<usize>,
+    /// The niceness to give the runner process. See `NICE_RANGE`.
+    pub nice: Option<i32>,
+}
+
+impl Scheduling {
+    /// The range of valid niceness values, from the highest priority to the
+    /// lowest. This is the same range as on Linux.
+    pub const NICE_RANGE: std::ops::RangeInclusive<i32> = -20..=19;
+
+    /// Returns the scheduling that can actually be applied with the given
+    /// scheduler.
+    ///
+    /// If the requested CPU core doesn't exist (or pinning isn't supported),
+    /// then a warning is printed and the affinity is dropped. That way, it
+    /// isn't recorded in any measurement either.
+    fn check(self, sched: &dyn Scheduler) -> Scheduling {
+        let Some(core) = self.affinity else { return self };
+        let warning = match sched.cores() {
+            None => "pinning to a CPU core is unsupported on this platform"
+                .to_string(),
+            Some(cores) if !cores.contains(&core) => format!(
+                "CPU core {} doesn't exist or isn't available (available \
+                 cores: {:?})",
+                core, cores,
+            ),
+            Some(_) => return self,
+        };
+        log::warn!("{}, so --affinity {} is ignored", warning, core);
+        Scheduling { affinity: None, ..self }
+    }
+
+    /// Applies this scheduling to the process with the given ID.
+    ///
+    /// This is only used on Windows, since a process can only be changed
+    /// after it has been spawned there. On Unix, see `Scheduling::pre_exec`.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    fn apply(&self, sched: &dyn Scheduler, pid: u32) -> anyhow::Result<()> {
+        if let Some(core) = self.affinity {
+            sched.set_affinity(pid, core).with_context(|| {
+                format!("failed to pin runner process to CPU core {}", core)
+            })?;
+        }
+        if let Some(nice) = self.nice {
+            sched.set_nice(pid, nice).with_context(|| {
+                format!("failed to set niceness of runner process to {}", nice)
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Arranges for this scheduling to be applied to the process spawned by
+    /// the given command, just before it executes the runner program.
+    ///
+    /// Changing the process after it has been spawned isn't enough on Unix.
+    /// On Linux in particular, both the CPU affinity and the niceness set
+    /// via a process ID only apply to that process's main thread, and not
+    /// to any threads it has already started. Doing it before exec instead
+    /// means the runner program (and every thread it starts) inherits it.
+    ///
+    /// If applying the scheduling fails, then spawning the command fails.
+    #[cfg(unix)]
+    fn pre_exec(&self, cmd: &mut std::process::Command) {
+        use std::os::unix::process::CommandExt;
+
+        if *self == Scheduling::default() {
+            return;
+        }
+        let sched = *self;
+        // SAFETY: The closure only makes system calls that are
+        // async-signal-safe, and it doesn't allocate, not even on failure.
+        unsafe {
+            cmd.pre_exec(move || {
+                #[cfg(target_os = "linux")]
+                if let Some(core) = sched.affinity {
+                    linux_set_affinity(0, core)?;
+                }
+                if let Some(nice) = sched.nice {
+                    unix_set_nice(0, nice)?;
+                }
+                Ok(())
+            });
+        }
+    }
+
+    /// Returns the command line flags that request this scheduling, e.g.,
+    /// '--affinity 3 --nice -5'. This is used in error messages.
+    #[cfg(unix)]
+    fn flags(&self) -> String {
+        let mut flags = vec![];
+        if let Some(core) = self.affinity {
+            flags.push(format!("--affinity {}", core));
+        }
+        if let Some(nice) = self.nice {
+            flags.push(format!("--nice {}", nice));
+        }
+        flags.join(" ")
+    }
+
+    /// Records this scheduling in the given measurement.
+    fn record(&self, m: &mut Measurement) {
+        m.affinity = self.affinity;
+        m.nice = self.nice;
+    }
+}
+
+/// The environment variables injected into each runner process, as set by
+/// '--env' and '--env-record-values'.
+///
+/// Like `Scheduling`, this is recorded in measurements so that CSV files say
+/// how they were captured.
+#[derive(Clone, Debug, Default)]
+pub struct InjectedEnv {
+    /// The variables to inject, in the order given on the command line.
+    pub vars: Vec<EnvVar>,
+    /// Whether to record the values of the variables in addition to their
+    /// names.
+    pub record_values: bool,
+}
+
+impl InjectedEnv {
+    /// Returns the variables to inject for the regex engine with the given
+    /// name, as a map from name to value. When more than one variable with
+    /// the same name applies to the regex engine, the last one wins.
+    fn resolve(&self, engine: &str) -> BTreeMap<&str, &str> {
+        self.vars
+            .iter()
+            .filter(|v| v.engine.is_match(engine))
+            .map(|v| (v.name.as_str(), v.value.as_str()))
+            .collect()
+    }
+
+    /// Sets the variables that apply to the given regex engine on the given
+    /// command. This is done after the command is built from the regex
+    /// engine's definition, so these take precedence over its 'envs'.
+    fn apply(&self, engine: &str, cmd: &mut std::process::Command) {
+        cmd.envs(self.resolve(engine));
+    }
+
+    /// Records the variables that apply to the regex engine of the given
+    /// measurement in that measurement.
+    fn record(&self, m: &mut Measurement) {
+        let vars = self.resolve(&m.engine);
+        if vars.is_empty() {
+            return;
+        }
+        let recorded: Vec<String> = vars
+            .into_iter()
+            .map(|(name, value)| {
+                if self.record_values {
+                    format!("{}={}", name, value)
+                } else {
+                    name.to_string()
+                }
+            })
+            .collect();
+        m.env = Some(recorded.join(" "));
+    }
+}
+
+/// A single environment variable given to '--env', along with the regex
+/// engines it applies to.
+#[derive(Clone, Debug)]
+pub struct EnvVar {
+    /// A regex that must match the entire name of a regex engine.
+    engine: regex_lite::Regex,
+    name: String,
+    value: String,
+}
+
+impl std::str::FromStr for EnvVar {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> anyhow::Result<EnvVar> {
+        // Variable names can't contain '=' or ':', so splitting this way
+        // permits ':' in both the pattern and the value.
+        let Some((head, value)) = spec.split_once('=') else {
+            anyhow::bail!(
+                "invalid spec '{}': missing '=' between the variable name \
+                 and its value (expected <engine>:<name>=<value>)",
+                spec,
+            )
+        };
+        let Some((pattern, name)) = head.rsplit_once(':') else {
+            anyhow::bail!(
+                "invalid spec '{}': missing ':' between the regex engine \
+                 pattern and the variable name (expected \
+                 <engine>:<name>=<value>)",
+                spec,
+            )
+        };
+        anyhow::ensure!(
+            !pattern.is_empty(),
+            "invalid spec '{}': the regex engine pattern is empty",
+            spec,
+        );
+        let valid_name = name.bytes().enumerate().all(|(i, b)| {
+            b == b'_'
+                || b.is_ascii_alphabetic()
+                || (i > 0 && b.is_ascii_digit())
+        });
+        anyhow::ensure!(
+            !name.is_empty() && valid_name,
+            "invalid spec '{}': variable name '{}' must be non-empty, \
+             consist of ASCII letters, digits and underscores, and must not \
+             start with a digit",
+            spec,
+            name,
+        );
+        anyhow::ensure!(
+            !value.contains('\0'),
+            "invalid spec '{}': the value of '{}' contains a NUL byte",
+            spec,
+            name,
+        );
+        let engine = regex_lite::Regex::new(&format!("^(?:{})$", pattern))
+            .with_context(|| {
+                format!(
+                    "invalid spec '{}': failed to parse regex engine \
+                     pattern '{}'",
+                    spec, pattern,
+                )
+            })?;
+        Ok(EnvVar { engine, name: name.to_string(), value: value.to_string() })
+    }
+}
+
+/// Returns the timeout used for a benchmark with the given time budgets when
+/// one isn't given explicitly. It's twice the combined budget, so that a
+/// benchmark isn't killed just for running a little long, but never less
+/// than `MIN_TIMEOUT`.
+fn default_timeout(max_time: Duration, max_warmup_time: Duration) -> Duration {
+    std::cmp::max(MIN_TIMEOUT, 2 * (max_time + max_warmup_time))
+}
+
+/// How many times a runner program should run a benchmark for each sample.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IterBatch {
+    /// Always use this many runs per sample.
+    Fixed(u64),
+    /// Pick the number of runs per sample based on how long a single run
+    /// takes. This is resolved to a fixed number by a calibration run before
+    /// the benchmark is measured.
+    Auto,
+}
+
+impl IterBatch {
+    /// When a single run of a benchmark takes less than this, 'auto' batches
+    /// runs such that each sample takes at least about this long.
+    const AUTO_MIN_SAMPLE_TIME: Duration = Duration::from_micros(1);
+
+    /// Returns the number of runs per sample to write to the KLV data given
+    /// to runner programs. An unresolved 'auto' setting doesn't batch.
+    fn get(&self) -> u64 {
+        match *self {
+            IterBatch::Fixed(n) => n,
+            IterBatch::Auto => 1,
+        }
+    }
+
+    /// Returns the number of runs per sample that 'auto' picks for a
+    /// benchmark in which a single run takes the given amount of time.
+    fn auto(per_iter: Duration) -> u64 {
+        let (min, per_iter) =
+            (IterBatch::AUTO_MIN_SAMPLE_TIME.as_nanos(), per_iter.as_nanos());
+        if per_iter >= min {
+            return 1;
+        }
+        // Since 'per_iter' is less than 'min', this is always at least 2.
+        u64::try_from(min.div_ceil(per_iter.max(1))).unwrap()
+    }
+}
+
+impl std::str::FromStr for IterBatch {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<IterBatch> {
+        if s == "auto" {
+            return Ok(IterBatch::Auto);
+        }
+        let n: u64 = s.parse().map_err(|_| {
+            anyhow::anyhow!(
+                "unrecognized iteration batch '{}', must be \
+                 'auto' or a number greater than zero",
+                s,
+            )
+        })?;
+        anyhow::ensure!(n > 0, "--iter-batch must be greater than zero");
+        Ok(IterBatch::Fixed(n))
+    }
+}
+
+/// The format used by --list.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+enum ListFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl std::str::FromStr for ListFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<ListFormat> {
+        let format = match s {
+            "csv" => ListFormat::Csv,
+            "json" => ListFormat::Json,
+            unknown => {
+                anyhow::bail!(
+                    "unrecognized list format '{}', must be \
+                     one of csv or json.",
+                    unknown,
+                )
+            }
+        };
+        Ok(format)
+    }
+}
+
+/// The format used for measurements.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+enum OutputFormat {
+    #[default]
+    Csv,
+    Jsonl,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<OutputFormat> {
+        let format = match s {
+            "csv" => OutputFormat::Csv,
+            "jsonl" => OutputFormat::Jsonl,
+            unknown => {
+                anyhow::bail!(
+                    "unrecognized output format '{}', must be \
+                     one of csv or jsonl.",
+                    unknown,
+                )
+            }
+        };
+        Ok(format)
+    }
+}
+
+/// A progress indicator for a measurement run, enabled by --progress.
+///
+/// When stderr is a terminal, this is a single status line that is rewritten
+/// as each benchmark starts and cleared when it finishes, so that it doesn't
+/// get mixed up with measurements written to stdout. Otherwise, a plain line
+/// is printed as each benchmark starts.
+#[derive(Clone, Debug)]
+struct Progress {
+    /// Whether to rewrite a single status line.
+    rewrite: bool,
+    /// When the measurement run started.
+    start: Instant,
+    /// The time budget of each benchmark, in the order in which they're run.
+    /// That is, its maximum time plus its maximum warmup time.
+    budgets: Vec<Duration>,
+}
+
+impl Progress {
+    /// The number of benchmarks that must complete before the estimated time
+    /// remaining takes the time they actually took into account.
+    const MIN_OBSERVED: usize = 5;
+
+    /// Create a new progress indicator for the given benchmarks. When
+    /// `verbose` is enabled, runner programs may write to stderr, so a status
+    /// line is never used.
+    fn new(verbose: bool, benchmarks: &[ExecBenchmark]) -> Progress {
+        let rewrite = !verbose && std::io::stderr().is_terminal();
+        let budgets = benchmarks
+            .iter()
+            .map(|b| b.config.max_time + b.config.max_warmup_time)
+            .collect();
+        Progress { rewrite, start: Instant::now(), budgets }
+    }
+
+    /// Report that the given benchmark is about to start, after `completed`
+    /// benchmarks have finished.
+    fn start(
+        &self,
+        completed: usize,
+        b: &ExecBenchmark,
+    ) -> anyhow::Result<()> {
+        let total = self.budgets.len();
+        let mut stderr = std::io::stderr().lock();
+        if !self.rewrite {
+            writeln!(
+                stderr,
+                "[{}/{}] starting {}: {}",
+                completed + 1,
+                total,
+                b.def.name,
+                b.engine.name,
+            )?;
+            return Ok(());
+        }
+        let elapsed = self.start.elapsed();
+        write!(
+            stderr,
+            "\r\x1B[K[{}/{}] {} elapsed, ~{} left, running {}: {}",
+            completed,
+            total,
+            clock(elapsed),
+            clock(self.remaining(completed, elapsed)),
+            b.def.name,
+            b.engine.name,
+        )?;
+        stderr.flush()?;
+        Ok(())
+    }
+
+    /// Clear the status line, if one is being shown.
+    fn clear(&self) -> anyhow::Result<()> {
+        if self.rewrite {
+            let mut stderr = std::io::stderr().lock();
+            write!(stderr, "\r\x1B[K")?;
+            stderr.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Estimate the time remaining after `completed` benchmarks have finished
+    /// in the `elapsed` time given.
+    ///
+    /// This is the sum of the budgets of the remaining benchmarks. Once
+    /// enough benchmarks have completed, it's scaled by the ratio of the time
+    /// they actually took to their budgets, since most benchmarks finish well
+    /// within (or, with overhead, a bit beyond) their budget.
+    fn remaining(&self, completed: usize, elapsed: Duration) -> Duration {
+        let (done, rest) = self.budgets.split_at(completed);
+        let rest: Duration = rest.iter().sum();
+        let done: Duration = done.iter().sum();
+        if completed < Progress::MIN_OBSERVED || done.is_zero() {
+            return rest;
+        }
+        let ratio = elapsed.as_secs_f64() / done.as_secs_f64();
+        Duration::try_from_secs_f64(rest.as_secs_f64() * ratio).unwrap_or(rest)
+    }
+}
+
+/// Formats the given duration for a progress indicator, e.g., '1h05m',
+/// '4m09s' or '37s'.
+fn clock(d: Duration) -> String {
+    let secs = d.as_secs();
+    let (hours, mins, secs) = (secs / 3600, (secs / 60) % 60, secs % 60);
+    if hours > 0 {
+        format!("{}h{:02}m", hours, mins)
+    } else if mins > 0 {
+        format!("{}m{:02}s", mins, secs)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// A writer for measurements in the format selected by --output-format.
+///
+/// Every record is flushed as soon as it's written so that users (or
+/// programs) watching the output can see that progress is being made.
+enum MeasurementWriter {
+    Csv(Box<csv::Writer<Box<dyn Write>>>),
+    Jsonl(Box<dyn Write>),
+}
+
+impl MeasurementWriter {
+    fn new(format: OutputFormat, wtr: Box<dyn Write>) -> MeasurementWriter {
+        match format {
+            OutputFormat::Csv => {
+                MeasurementWriter::Csv(Box::new(csv::Writer::from_writer(wtr)))
+            }
+            OutputFormat::Jsonl => MeasurementWriter::Jsonl(wtr),
+        }
+    }
+
+    /// Report that the given benchmark is about to start, after `completed`
+    /// of `total` benchmarks have finished. This is a no-op for CSV.
+    fn progress(
+        &mut self,
+        completed: usize,
+        total: usize,
+        b: &ExecBenchmark,
+    ) -> anyhow::Result<()> {
+        let MeasurementWriter::Jsonl(ref mut wtr) = *self else {
+            return Ok(());
+        };
+        let record = JsonRecord::Progress {
+            completed,
+            total,
+            current: format!("{}/{}", b.def.name, b.engine.name),
+        };
+        serde_json::to_writer(&mut *wtr, &record)?;
+        writeln!(wtr)?;
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// Write the given measurement.
+    fn write(&mut self, m: Measurement) -> anyhow::Result<()> {
+        match *self {
+            MeasurementWriter::Csv(ref mut wtr) => {
+                wtr.serialize(m)?;
+                wtr.flush()?;
+            }
+            MeasurementWriter::Jsonl(ref mut wtr) => {
+                let record =
+                    JsonRecord::Measurement(&JsonMeasurement::from(m));
+                serde_json::to_writer(&mut *wtr, &record)?;
+                writeln!(wtr)?;
+                wtr.flush()?;
+            }
         }
+        Ok(())
     }
 }
 
+/// A single line written by '--output-format jsonl'.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum JsonRecord<'a> {
+    Progress { completed: usize, total: usize, current: String },
+    Measurement(&'a JsonMeasurement),
+}
+
+/// A measurement as written by '--output-format jsonl'.
+///
+/// This has the same fields as the CSV columns for a measurement, except
+/// durations are written as integer nanoseconds with a '_nanos' suffix.
+#[derive(Clone, Debug, serde::Serialize)]
+struct JsonMeasurement {
+    name: String,
+    model: String,
+    rebar_version: String,
+    engine: String,
+    engine_version: String,
+    err: Option<String>,
+    haystack_len: Option<u64>,
+    iters: u64,
+    total_nanos: u128,
+    median_nanos: u128,
+    mad_nanos: u128,
+    mean_nanos: u128,
+    stddev_nanos: u128,
+    min_nanos: u128,
+    max_nanos: u128,
+    rss_growth_bytes_per_iter: Option<f64>,
+    compiled_size_bytes: Option<u64>,
+    def_hash: Option<String>,
+    warmup_iters: Option<u64>,
+    warmup_nanos: Option<u128>,
+    overhead_ratio: Option<f64>,
+    defs_revision: Option<String>,
+    engine_metadata: Option<String>,
+    partial: bool,
+    affinity: Option<usize>,
+    nice: Option<i32>,
+    env: Option<String>,
+}
+
+impl From<Measurement> for JsonMeasurement {
+    fn from(m: Measurement) -> JsonMeasurement {
+        let times = &m.aggregate.times;
+        JsonMeasurement {
+            haystack_len: m.aggregate.tputs.as_ref().map(|t| t.len),
+            iters: m.iters,
+            total_nanos: m.total.as_nanos(),
+            median_nanos: times.median.as_nanos(),
+            mad_nanos: times.mad.as_nanos(),
+            mean_nanos: times.mean.as_nanos(),
+            stddev_nanos: times.stddev.as_nanos(),
+            min_nanos: times.min.as_nanos(),
+            max_nanos: times.max.as_nanos(),
+            rss_growth_bytes_per_iter: m.rss_growth_bytes_per_iter,
+            compiled_size_bytes: m.compiled_size_bytes,
+            warmup_iters: m.warmup_iters,
+            warmup_nanos: m.warmup_estimate.map(|d| d.as_nanos()),
+            overhead_ratio: m.overhead_ratio,
+            partial: m.partial,
+            affinity: m.affinity,
+            nice: m.nice,
+            env: m.env,
+            name: m.name,
+            model: m.model,
+            rebar_version: m.rebar_version,
+            engine: m.engine,
+            engine_version: m.engine_version,
+            err: m.err,
+            def_hash: m.def_hash,
+            defs_revision: m.defs_revision,
+            engine_metadata: m.engine_metadata,
+        }
+    }
+}
+
+/// A machine readable description of a single benchmark that would be
+/// executed, as printed by '--list-format json'.
+#[derive(Clone, Debug, serde::Serialize)]
+struct ListRecord {
+    name: String,
+    group: String,
+    local: String,
+    model: String,
+    engine: String,
+    engine_version: String,
+    pattern_count: usize,
+    pattern_bytes: usize,
+    haystack_len: usize,
+    case_insensitive: bool,
+    unicode: bool,
+    multiline: bool,
+    dot_all: bool,
+    count: Option<u64>,
+    max_iters: u64,
+    max_warmup_iters: u64,
+    max_time_nanos: u128,
+    max_warmup_time_nanos: u128,
+    timeout_nanos: u128,
+}
+
 /// An iterator over all benchmarks from a benchmark definition.
 ///
 /// The lifetime `'d` refers to the benchmark definition from which to generate
@@ -432,11 +2322,11 @@ impl<'b> Iterator for ExecBenchmarkIter<'b> {
 
     fn next(&mut self) -> Option<anyhow::Result<ExecBenchmark>> {
         let engine = self.it.next()?.clone();
-        Some(Ok(ExecBenchmark {
-            config: self.config.clone(),
-            def: self.def.clone(),
+        Some(Ok(ExecBenchmark::new(
+            self.config.for_definition(self.def),
+            self.def.clone(),
             engine,
-        }))
+        )))
     }
 }
 
@@ -444,7 +2334,7 @@ impl<'b> Iterator for ExecBenchmarkIter<'b> {
 /// Each sample corresponds to a single run of a single regex engine on a
 /// particular haystack.
 #[derive(Clone, Debug)]
-struct ExecBenchmark {
+pub struct ExecBenchmark {
     /// The config, given from the command line.
     config: ExecBenchmarkConfig,
     /// The definition, taken from TOML data.
@@ -455,12 +2345,78 @@ struct ExecBenchmark {
 }
 
 impl ExecBenchmark {
+    /// Returns a machine readable description of this benchmark for
+    /// '--list-format json'.
+    fn list_record(&self) -> ListRecord {
+        // This fails when the definition has no count or doesn't verify
+        // counts, in which case there's nothing to report.
+        let count =
+            self.def.count(&self.engine.name, &self.engine.version).ok();
+        ListRecord {
+            name: self.def.name.full.clone(),
+            group: self.def.name.group.clone(),
+            local: self.def.name.local.clone(),
+            model: self.def.model.clone(),
+            engine: self.engine.name.clone(),
+            engine_version: self.engine.version.clone(),
+            pattern_count: self.def.regexes.len(),
+            pattern_bytes: self
+                .def
+                .patterns_bytes()
+                .iter()
+                .map(|p| p.len())
+                .sum(),
+            haystack_len: self.def.haystack.len(),
+            case_insensitive: self.def.options.case_insensitive,
+            unicode: self.def.options.unicode,
+            multiline: self.def.options.multiline,
+            dot_all: self.def.options.dot_all,
+            count,
+            max_iters: self.config.max_iters,
+            max_warmup_iters: self.config.max_warmup_iters,
+            max_time_nanos: self.config.max_time.as_nanos(),
+            max_warmup_time_nanos: self.config.max_warmup_time.as_nanos(),
+            timeout_nanos: self.config.timeout.as_nanos(),
+        }
+    }
+
+    /// Create a new benchmark for the given definition and regex engine.
+    ///
+    /// Callers must ensure that the engine is one of the engines in the
+    /// definition.
+    pub fn new(
+        config: ExecBenchmarkConfig,
+        def: Definition,
+        engine: Engine,
+    ) -> ExecBenchmark {
+        ExecBenchmark { config, def, engine }
+    }
+
     /// Run and collect the results of this benchmark.
     ///
     /// This interrogates the benchmark type and runs the corresponding
     /// benchmark function to produce results.
-    fn collect(&self, verbose: bool) -> anyhow::Result<Results> {
-        use std::process::Stdio;
+    ///
+    /// This spawns the engine's runner program, sends it the benchmark in
+    /// KLV format on stdin and parses and verifies the samples it prints to
+    /// stdout.
+    pub fn collect(&self, verbose: bool) -> anyhow::Result<Results> {
+        self.collect_streaming(verbose, |_| {})
+    }
+
+    /// Like `collect`, but calls `on_line` with each line (including its line
+    /// terminator) that the runner program prints to stdout as soon as it's
+    /// read. This is called before any line is parsed or verified, so lines
+    /// that aren't valid samples are passed through too.
+    pub fn collect_streaming(
+        &self,
+        verbose: bool,
+        mut on_line: impl FnMut(&[u8]),
+    ) -> anyhow::Result<Results> {
+        use std::{
+            process::Stdio,
+            sync::mpsc::{self, RecvTimeoutError},
+        };
 
         // If we don't know the version of the engine then we absolutely refuse
         // to collect measurements. Results should always include the version
@@ -483,10 +2439,18 @@ impl ExecBenchmark {
         // are also TONS of a failure points, and for that reason, we try to
         // give descriptive error messages where we can.
 
+        // When holding, nobody cares about the samples and there could be a
+        // lot of them, so we just throw them away. We also always let stderr
+        // through, since it might contain something useful for whoever is
+        // profiling.
+        let hold = self.config.hold.is_some();
+        let verbose = verbose || hold;
         let mut cmd =
             self.engine.run.command().context("failed to build command")?;
+        cmd.args(self.engine.extra_args.iter());
+        self.config.env.apply(&self.engine.name, &mut cmd);
         cmd.stdin(Stdio::piped());
-        cmd.stdout(Stdio::piped());
+        cmd.stdout(if hold { Stdio::null() } else { Stdio::piped() });
         cmd.stderr(if verbose { Stdio::inherit() } else { Stdio::piped() });
         log::debug!(
             "running command: \
@@ -504,28 +2468,61 @@ impl ExecBenchmark {
             self.config.max_warmup_time.as_nanos(),
             cmd,
         );
+        #[cfg(unix)]
+        self.config.scheduling.pre_exec(&mut cmd);
         let spawn_start = Instant::now();
-        let mut child = cmd.spawn().context("failed to spawn process")?;
+        let mut child = cmd.spawn().with_context(|| {
+            #[cfg(unix)]
+            if self.config.scheduling != Scheduling::default() {
+                return format!(
+                    "failed to spawn process with {}",
+                    self.config.scheduling.flags(),
+                );
+            }
+            "failed to spawn process".to_string()
+        })?;
+        #[cfg(windows)]
+        if let Err(err) =
+            self.config.scheduling.apply(&OsScheduler, child.id())
+        {
+            // The runner is blocked reading the benchmark from stdin, so it
+            // won't exit on its own.
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(err);
+        }
+        if hold {
+            writeln!(
+                std::io::stderr(),
+                "{}:{}: runner process PID: {}",
+                self.def.name,
+                self.engine.name,
+                child.id(),
+            )?;
+        }
 
         let handle_stdin = {
             let klvbench = klv::Benchmark {
+                version: klv::VERSION,
                 name: self.def.name.as_str().to_string(),
                 model: self.def.model.clone(),
-                regex: klv::Regex {
-                    patterns: self
-                        .def
-                        .regexes
-                        .iter()
-                        .map(|p| p.to_string())
-                        .collect(),
-                    case_insensitive: self.def.options.case_insensitive,
-                    unicode: self.def.options.unicode,
-                },
+                regex: self.def.klv_regex(Some(&self.engine))?,
                 haystack: Arc::clone(&self.def.haystack),
+                haystack_encoding: self.def.haystack_encoding,
                 max_iters: self.config.max_iters,
                 max_warmup_iters: self.config.max_warmup_iters,
                 max_time: self.config.max_time,
                 max_warmup_time: self.config.max_warmup_time,
+                verify_every: self.config.verify_every,
+                iter_batch: self.config.iter_batch.get(),
+                soft_deadline: self.soft_deadline(),
+                expected_count: self.runner_expected_count()?,
+                search_start: self.def.options.search_start,
+                search_end: self.def.options.search_end,
+                max_count: self.def.options.max_count,
+                params: self.def.params.clone(),
+                ignored: vec![],
+                started: None,
             };
             let mut stdin = child.stdin.take().unwrap();
             std::thread::spawn(move || -> anyhow::Result<()> {
@@ -535,16 +2532,29 @@ impl ExecBenchmark {
                 Ok(())
             })
         };
-        let handle_stdout = {
-            let mut stdout = child.stdout.take().unwrap();
-            std::thread::spawn(move || -> anyhow::Result<Vec<u8>> {
-                let mut buf = vec![];
-                stdout
-                    .read_to_end(&mut buf)
-                    .context("failed to read stdout")?;
-                Ok(buf)
+        // Lines on stdout are sent back to this thread one at a time, so
+        // that they can be handed to 'on_line' while the runner is still
+        // going.
+        let (send_line, recv_line) = mpsc::channel::<Vec<u8>>();
+        let handle_stdout = child.stdout.take().map(move |stdout| {
+            std::thread::spawn(move || -> anyhow::Result<()> {
+                use std::io::BufRead;
+
+                let mut stdout = BufReader::new(stdout);
+                loop {
+                    let mut line = vec![];
+                    let n = stdout
+                        .read_until(b'\n', &mut line)
+                        .context("failed to read stdout")?;
+                    // If sending fails, then we've stopped listening, which
+                    // only happens when collection has already failed.
+                    if n == 0 || send_line.send(line).is_err() {
+                        return Ok(());
+                    }
+                }
             })
-        };
+        });
+        let mut stdout = vec![];
         // When verbose mode is enabled, we let stderr inherit from the rebar
         // process so that it just pipes right through. As a result, if the
         // benchmark fails, since we didn't capture stderr, we just write
@@ -572,12 +2582,29 @@ impl ExecBenchmark {
         // different environments execute things more slowly. This is also
         // useful during experimentation, where you might not know how long a
         // regex will take.
+        //
+        // When leak checking is enabled, this is also where we periodically
+        // sample the memory usage of the process.
+        let mut rss_samples = vec![];
+        let mut last_rss_sample: Option<Instant> = None;
         let status = loop {
             let maybe_status =
                 child.try_wait().context("failed to reap process")?;
             if let Some(status) = maybe_status {
                 break status;
             }
+            if self.config.leak_check
+                && last_rss_sample
+                    .is_none_or(|t| t.elapsed() >= Duration::from_millis(100))
+            {
+                last_rss_sample = Some(Instant::now());
+                // If this fails, it's likely because the process just
+                // exited. There's not much else we can do, so just skip it.
+                match rss(child.id()) {
+                    Ok(bytes) => rss_samples.push(bytes),
+                    Err(err) => log::debug!("failed to sample RSS: {:#}", err),
+                }
+            }
             if spawn_start.elapsed() > self.config.timeout {
                 log::debug!(
                     "benchmark time exceeded {:?}, killing process",
@@ -606,7 +2633,18 @@ impl ExecBenchmark {
                 }
                 anyhow::bail!("timeout: exceeded {:?}", self.config.timeout);
             }
-            std::thread::sleep(Duration::from_millis(50));
+            match recv_line.recv_timeout(Duration::from_millis(50)) {
+                Ok(line) => {
+                    on_line(&line);
+                    stdout.extend_from_slice(&line);
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                // This happens when stdout isn't captured, or when the runner
+                // has closed stdout but hasn't exited yet.
+                Err(RecvTimeoutError::Disconnected) => {
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+            }
         };
         // We wait to handle any errors from writing to stdin until we've dealt
         // with stderr, since stderr is likely to contain the actual error that
@@ -615,7 +2653,15 @@ impl ExecBenchmark {
         // underlying cause is almost certainly on stderr. Still, we join all
         // of the threads to make sure they've completed.
         let result_stdin = handle_stdin.join().unwrap();
-        let result_stdout = handle_stdout.join().unwrap();
+        let result_stdout = match handle_stdout {
+            None => Ok(()),
+            Some(handle) => handle.join().unwrap(),
+        };
+        // The reader thread is done, so this gets every line that's left.
+        for line in recv_line.try_iter() {
+            on_line(&line);
+            stdout.extend_from_slice(&line);
+        }
         let stderr = match handle_stderr {
             None => vec![],
             Some(handle) => handle.join().unwrap()?,
@@ -627,7 +2673,7 @@ impl ExecBenchmark {
                     self.engine.name
                 );
             }
-            let last = match stderr.lines().last() {
+            let last = match stderr.lines().next_back() {
                 Some(last) => last,
                 None => {
                     anyhow::bail!(
@@ -642,63 +2688,295 @@ impl ExecBenchmark {
                 last.as_bstr(),
             );
         }
-        let stdout = result_stdout?;
+        result_stdout?;
         result_stdin?;
+        if hold {
+            return Ok(Results::new(self));
+        }
 
-        let expected_count = self.def.count(&self.engine.name)?;
+        // When a benchmark doesn't have a count, we're computing one via
+        // --compute-counts. (Otherwise, it would have been rejected before
+        // getting here.) In that case, we just record what the runner reports
+        // after checking that it's consistent across all samples.
+        //
+        // When a benchmark opts out of count verification, there's nothing to
+        // check and we just record whatever the runner reports.
+        let mut expected_count = self.expected_count()?;
         let mut results = Results::new(self);
+        // Lines on stdout that don't look like samples. Some runner programs
+        // (usually ones that wrap an interpreter) occasionally print warnings
+        // to stdout. Unless --strict-samples is given, we skip over such lines
+        // and report them all at once at the end.
+        let mut noise: Vec<&[u8]> = vec![];
         for line in stdout.lines() {
-            let (field1, field2) = match line.split_once_str(",") {
-                Some((f1, f2)) => (f1, f2),
+            // A runner program that can't run this benchmark at all (e.g.,
+            // because its regex engine doesn't support case insensitive
+            // searches) says so by printing a single 'skip:<reason>' line
+            // instead of any samples.
+            if let Some(reason) = line.strip_prefix(b"skip:") {
+                anyhow::ensure!(
+                    results.samples.is_empty(),
+                    "when running '{}', got a skip line after samples",
+                    self.engine.name,
+                );
+                let reason = reason.trim_ascii().to_str_lossy().into_owned();
+                return Err(anyhow::Error::new(Skipped(reason)));
+            }
+            // Runner programs may report information about the run as a whole
+            // via header lines before any samples. Headers we don't know
+            // about are ignored, since they might come from a runner written
+            // for a newer version of rebar.
+            if let Some(header) = parse_header(line) {
+                match header {
+                    Ok(Header::WarmupIters(n)) => {
+                        results.warmup_iters = Some(n)
+                    }
+                    Ok(Header::Partial(yes)) => results.partial = yes,
+                    Ok(Header::Unknown(key)) => log::debug!(
+                        "{}:{}: ignoring unrecognized header {:?}",
+                        self.def.name,
+                        self.engine.name,
+                        key,
+                    ),
+                    Err(err) => {
+                        return Err(err.context(format!(
+                            "when running '{}', got invalid header {:?}",
+                            self.engine.name,
+                            line.as_bstr(),
+                        )));
+                    }
+                }
+                continue;
+            }
+            let RawSample { duration, count, size, iter } =
+                match parse_sample(line) {
+                    Ok(sample) => sample,
+                    Err(err) if self.config.strict_samples => {
+                        return Err(err.context(format!(
+                        "when running '{}', got invalid sample format {:?}",
+                        self.engine.name,
+                        line.as_bstr(),
+                    )));
+                    }
+                    Err(err) => {
+                        log::debug!(
+                            "{}:{}: ignoring non-sample line {:?}: {:#}",
+                            self.def.name,
+                            self.engine.name,
+                            line.as_bstr(),
+                            err,
+                        );
+                        noise.push(line);
+                        continue;
+                    }
+                };
+            if let Some(size) = size {
+                results.sizes.push(size);
+            }
+            if let Some(iter) = iter {
+                results.iters.push(iter);
+            }
+            if !self.def.verify_count {
+                results.samples.push(duration);
+                results.count = Some(count);
+                continue;
+            }
+            let expected_count = *expected_count.get_or_insert(count);
+            if count != expected_count {
+                return Err(anyhow::Error::new(CountMismatch {
+                    got: count,
+                    msg: self.count_mismatch_message(expected_count, count)?,
+                }));
+            }
+            results.samples.push(duration);
+            results.count = Some(count);
+        }
+        if results.samples.is_empty() && self.config.max_iters > 0 {
+            match noise.first() {
                 None => anyhow::bail!(
-                    "when running '{}', got invalid sample format {:?}",
+                    "when running '{}', no samples were reported on stdout",
                     self.engine.name,
-                    line.as_bstr()
                 ),
-            };
-            let s1 = field1.to_str().with_context(|| {
-                format!(
-                    "failed to parse duration field {:?} as UTF-8",
-                    field1.as_bstr()
-                )
-            })?;
-            let s2 = field2.to_str().with_context(|| {
-                format!(
-                    "failed to parse count field {:?} as UTF-8",
-                    field2.as_bstr()
-                )
-            })?;
-            let nanos = s1.parse::<u64>().with_context(|| {
-                format!("failed to parse duration field {:?} as u64", s1)
-            })?;
-            // If we get a measurement of 0 nanoseconds, then that winds up
-            // being pretty meaningless. So we "round up" to 1. Basically, we
-            // just give up trying to measure anything that reliably takes less
-            // than 1 nanosecond.
-            let duration =
-                Duration::from_nanos(if nanos == 0 { 1 } else { nanos });
-            let count = s2.parse::<u64>().with_context(|| {
-                format!("failed to parse count field {:?} as u64", s2)
-            })?;
-            anyhow::ensure!(
-                count == expected_count,
-                "count mismatch, expected {}, got {}",
-                expected_count,
-                count,
+                Some(first) => anyhow::bail!(
+                    "when running '{}', no valid samples were reported on \
+                     stdout, but found {} non-sample line(s), first was: {:?}",
+                    self.engine.name,
+                    noise.len(),
+                    first.as_bstr(),
+                ),
+            }
+        }
+        if let Some(first) = noise.first() {
+            log::warn!(
+                "{}:{}: ignored {} non-sample line(s) on stdout, \
+                 first was: {:?}",
+                self.def.name,
+                self.engine.name,
+                noise.len(),
+                first.as_bstr(),
+            );
+        }
+        if results.partial {
+            log::warn!(
+                "{}:{}: runner reached its soft deadline and reported only \
+                 {} sample(s), consider increasing --timeout",
+                self.def.name,
+                self.engine.name,
+                results.samples.len(),
             );
-            results.samples.push(duration);
         }
         results.total = spawn_start.elapsed();
+        if self.config.leak_check {
+            results.rss_growth = self.check_leak(&results, &rss_samples)?;
+        }
         Ok(results)
     }
 
+    /// Returns the soft deadline to send to the runner program, if any.
+    ///
+    /// This is a bit less than the timeout, so that a runner program that
+    /// honors it can report the samples it has before it gets killed. Like
+    /// the timeout, runner programs measure it from when they started, so
+    /// the slack only needs to cover process startup and reporting samples.
+    /// It's never sent when holding a benchmark for profiling, since the
+    /// point of holding is to run for as long as requested.
+    fn soft_deadline(&self) -> Option<Duration> {
+        if self.config.hold.is_some() {
+            return None;
+        }
+        Some(self.config.timeout - self.config.timeout / 10)
+    }
+
+    /// Returns the count every sample reported by the runner program must
+    /// have, if it's known ahead of time.
+    ///
+    /// This is `None` when the benchmark opts out of count verification, or
+    /// when it doesn't have a count because we're computing one via
+    /// --compute-counts.
+    fn expected_count(&self) -> anyhow::Result<Option<u64>> {
+        if !self.def.verify_count || self.def.is_count_unresolved() {
+            return Ok(None);
+        }
+        Ok(Some(self.def.count(&self.engine.name, &self.engine.version)?))
+    }
+
+    /// Returns the error message to use when the runner program reported
+    /// a count that differs from the expected count.
+    fn count_mismatch_message(
+        &self,
+        expected: u64,
+        got: u64,
+    ) -> anyhow::Result<String> {
+        let ce =
+            self.def.count_entry(&self.engine.name, &self.engine.version)?;
+        if let Some(ref counts) = ce.per_pattern {
+            return Ok(format!(
+                "per-pattern count checksum mismatch, expected {} \
+                 (computed from counts-per-pattern = {:?}), got {}, \
+                 which means either the total number of matches is \
+                 wrong or some matches were attributed to the wrong \
+                 pattern",
+                expected, counts, got,
+            ));
+        }
+        if let Some(ref from) = ce.derived_from {
+            return Ok(format!(
+                "count mismatch, expected {} (derived from {}), got {}",
+                expected, from, got,
+            ));
+        }
+        Ok(format!("count mismatch, expected {}, got {}", expected, got))
+    }
+
+    /// Returns the expected count to send to the runner program, if any.
+    ///
+    /// This lets runner programs using the shared timer fail on the first
+    /// iteration with the wrong count.
+    fn runner_expected_count(&self) -> anyhow::Result<Option<u64>> {
+        if !self.config.send_expected_count {
+            return Ok(None);
+        }
+        self.expected_count()
+    }
+
+    /// Estimate how much the RSS of the runner process grew per iteration
+    /// from the given RSS samples (in bytes) taken at fixed intervals. If the
+    /// growth exceeds the configured threshold and the RSS never decreased
+    /// over the course of the run, then this returns an error.
+    ///
+    /// Otherwise, the estimated growth in bytes per iteration is returned.
+    /// If too few RSS samples were collected to estimate growth, then the
+    /// check is inconclusive. A warning is logged and `None` is returned.
+    fn check_leak(
+        &self,
+        results: &Results,
+        rss_samples: &[u64],
+    ) -> anyhow::Result<Option<f64>> {
+        // The first sample is usually taken before the runner has even read
+        // its input, so we skip it since it would otherwise be counted as
+        // growth.
+        let rss_samples = rss_samples.get(1..).unwrap_or(&[]);
+        if rss_samples.len() < 2 || results.samples.is_empty() {
+            log::warn!(
+                "{}:{}: leak check inconclusive: only {} RSS sample(s) were \
+                 collected, benchmark may need to run for longer",
+                self.def.name,
+                self.engine.name,
+                rss_samples.len(),
+            );
+            return Ok(None);
+        }
+        // Our samples are taken at fixed intervals of time, but we want
+        // growth per iteration. So we compute a least squares fit of RSS
+        // against sample index, and then convert that to growth per iteration
+        // by assuming iterations are spread evenly throughout the run.
+        let xs: Vec<f64> = (0..rss_samples.len()).map(|i| i as f64).collect();
+        let ys: Vec<f64> = rss_samples.iter().map(|&y| y as f64).collect();
+        let (xmean, ymean) = (mean(&xs).unwrap(), mean(&ys).unwrap());
+        let (mut num, mut den) = (0.0, 0.0);
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            num += (x - xmean) * (y - ymean);
+            den += (x - xmean).powi(2);
+        }
+        let per_sample = num / den;
+        let iters_per_sample =
+            results.samples.len() as f64 / rss_samples.len() as f64;
+        let growth = per_sample / iters_per_sample;
+        let monotonic = rss_samples.windows(2).all(|w| w[0] <= w[1]);
+        log::debug!(
+            "{}:{}: leak check: RSS samples: {:?}, \
+             growth per iteration: {:.2} bytes, monotonic: {}",
+            self.def.name,
+            self.engine.name,
+            rss_samples,
+            growth,
+            monotonic,
+        );
+        anyhow::ensure!(
+            !(monotonic && growth > self.config.leak_check_max_growth),
+            "leak check failed: RSS grew monotonically from {} to {} bytes, \
+             which is about {:.2} bytes per iteration (max allowed is {})",
+            rss_samples[0],
+            rss_samples[rss_samples.len() - 1],
+            growth,
+            self.config.leak_check_max_growth,
+        );
+        Ok(Some(growth))
+    }
+
     /// Turn the given results collected from running this benchmark into
     /// a single set of aggregate statistics describing the samples in the
     /// results.
-    fn aggregate(&self, result: anyhow::Result<Results>) -> Measurement {
+    pub fn aggregate(&self, result: anyhow::Result<Results>) -> Measurement {
         match result {
             Ok(results) => results.to_measurement(),
-            Err(err) => self.measurement_error(format!("{:#}", err)),
+            // We don't want any context attached to the error here, since
+            // the error message has to start with a particular prefix in
+            // order to be recognized as a skip.
+            Err(err) => match err.downcast_ref::<Skipped>() {
+                Some(skipped) => self.measurement_error(skipped.to_string()),
+                None => self.measurement_error(format!("{:#}", err)),
+            },
         }
     }
 
@@ -714,11 +2992,88 @@ impl ExecBenchmark {
             rebar_version: util::version(),
             engine: self.engine.name.clone(),
             engine_version: self.engine.version.clone(),
+            engine_metadata: self.engine.metadata.clone(),
             err: Some(err),
             ..Measurement::default()
         }
     }
 
+    /// Returns this benchmark with its configuration adjusted based on a
+    /// single calibration iteration. If neither --target-samples nor
+    /// '--iter-batch auto' were given, then this benchmark is returned
+    /// unchanged.
+    ///
+    /// With --target-samples, the time budget is sized to collect roughly
+    /// the number of samples requested. With '--iter-batch auto', the number
+    /// of runs per sample is chosen based on how long a single run takes.
+    ///
+    /// The calibration is run via 'verifier', but with up to
+    /// 'CALIBRATION_MAX_ITERS' iterations (as long as they fit in
+    /// 'CALIBRATION_MAX_TIME'). The fastest iteration is used, since the
+    /// first iteration of a very fast benchmark is usually much slower than
+    /// the rest. If the benchmark reports the wrong count, then this fails
+    /// without going through a full measurement.
+    fn calibrate(&self) -> anyhow::Result<ExecBenchmark> {
+        let auto_batch = self.config.iter_batch == IterBatch::Auto;
+        if self.config.target_samples.is_none() && !auto_batch {
+            return Ok(self.clone());
+        }
+        let mut calibrator = self.verifier();
+        calibrator.config.max_iters = CALIBRATION_MAX_ITERS;
+        calibrator.config.max_time = CALIBRATION_MAX_TIME;
+        let calibration =
+            calibrator.collect(false).context("calibration failed")?;
+        // An iteration could conceivably report a zero duration with a coarse
+        // enough clock, so we pretend it took at least a nanosecond.
+        let per_iter = calibration
+            .samples
+            .iter()
+            .copied()
+            .min()
+            .unwrap_or_default()
+            .max(Duration::from_nanos(1));
+        let mut config = self.config.clone();
+        if auto_batch {
+            config.iter_batch = IterBatch::Fixed(IterBatch::auto(per_iter));
+            log::debug!(
+                "{}:{}: calibration iteration took {:?}, using {} \
+                 iteration(s) per sample",
+                self.def.name,
+                self.engine.name,
+                per_iter,
+                config.iter_batch.get(),
+            );
+        }
+        if let Some(target) = self.config.target_samples {
+            // Every sample consists of a batch of iterations, so that's what
+            // the time budget needs to account for.
+            let per_sample = per_iter.saturating_mul(
+                u32::try_from(config.iter_batch.get()).unwrap_or(u32::MAX),
+            );
+            let estimate = per_sample
+                .saturating_mul(u32::try_from(target).unwrap_or(u32::MAX));
+            config.max_iters = std::cmp::min(target, self.config.max_iters);
+            config.max_time = std::cmp::min(estimate, self.config.max_time);
+            config.max_warmup_time = std::cmp::min(
+                self.config.max_warmup_time,
+                config.max_time / 2,
+            );
+            log::debug!(
+                "{}:{}: calibration iteration took {:?}, using budget of \
+                 max-iters={}, max-time={:?}, max-warmup-time={:?} \
+                 (targeting {} samples)",
+                self.def.name,
+                self.engine.name,
+                per_iter,
+                config.max_iters,
+                config.max_time,
+                config.max_warmup_time,
+                target,
+            );
+        }
+        Ok(ExecBenchmark::new(config, self.def.clone(), self.engine.clone()))
+    }
+
     /// This creates a new `Benchmark` that is suitable purely for
     /// verification. Namely, it modifies any config necessary to ensure that
     /// the benchmark will run only one iteration and report the result.
@@ -729,6 +3084,10 @@ impl ExecBenchmark {
             max_time: Duration::ZERO,
             max_warmup_time: Duration::ZERO,
             timeout: self.config.timeout,
+            leak_check: false,
+            hold: None,
+            send_expected_count: false,
+            ..self.config.clone()
         };
         ExecBenchmark {
             config,
@@ -740,7 +3099,7 @@ impl ExecBenchmark {
 
 /// The raw results generated by running a benchmark.
 #[derive(Clone, Debug)]
-struct Results {
+pub struct Results {
     /// The benchmark that was executed.
     benchmark: ExecBenchmark,
     /// The total amount of time that the benchmark ran for.
@@ -749,6 +3108,26 @@ struct Results {
     /// represents the time it takes for a single run of the thing being
     /// measured. This does not include warmup iterations.
     samples: Vec<Duration>,
+    /// The estimated growth in RSS of the runner process, in bytes per
+    /// iteration. This is only present when leak checking is enabled.
+    rss_growth: Option<f64>,
+    /// The count reported by every sample. This is only absent when there
+    /// are no samples.
+    count: Option<u64>,
+    /// The sizes, in bytes, of the compiled regex as reported by the runner
+    /// for each sample. This is empty when the runner doesn't report sizes.
+    sizes: Vec<u64>,
+    /// The iteration index of each sample as reported by the runner. This is
+    /// empty when the runner doesn't report iteration indices.
+    iters: Vec<u64>,
+    /// The number of warmup iterations the runner actually executed. This is
+    /// only present when the runner reports it via a `# warmup_iters=N`
+    /// header line.
+    warmup_iters: Option<u64>,
+    /// Whether the runner stopped early because it reached its soft deadline.
+    /// This is only set when the runner reports it via a `# partial=true`
+    /// header line.
+    partial: bool,
 }
 
 impl Results {
@@ -758,12 +3137,106 @@ impl Results {
             benchmark: b.clone(),
             total: Duration::default(),
             samples: vec![],
+            rss_growth: None,
+            count: None,
+            sizes: vec![],
+            iters: vec![],
+            warmup_iters: None,
+            partial: false,
+        }
+    }
+
+    /// Create results from samples that were collected without a runner
+    /// program, e.g., by 'rebar wrap'. Each sample must have reported the
+    /// given count.
+    pub fn from_samples(
+        b: &ExecBenchmark,
+        total: Duration,
+        samples: Vec<Duration>,
+        count: Option<u64>,
+        warmup_iters: u64,
+    ) -> Results {
+        Results {
+            total,
+            samples,
+            count,
+            warmup_iters: Some(warmup_iters),
+            ..Results::new(b)
+        }
+    }
+
+    /// Write every sample in these results to a CSV file in the given
+    /// directory. The file is named after the benchmark and engine.
+    fn dump_samples(&self, dir: &Path) -> anyhow::Result<()> {
+        let b = &self.benchmark;
+        let path =
+            dir.join(dump_file_name(b.def.name.as_str(), &b.engine.name));
+        let mut wtr = csv::Writer::from_path(&path)
+            .with_context(|| format!("failed to create {}", path.display()))?;
+        wtr.write_record(["iteration", "duration_nanos"])?;
+        // Prefer the iteration indices reported by the runner, but fall back
+        // to each sample's position when it doesn't report them for every
+        // sample.
+        let reported = self.iters.len() == self.samples.len();
+        for (i, dur) in self.samples.iter().enumerate() {
+            let iter = if reported { self.iters[i] } else { i as u64 };
+            wtr.write_record([iter.to_string(), dur.as_nanos().to_string()])?;
         }
+        wtr.flush()
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// The number of times the thing being measured was run for each sample.
+    fn iter_batch(&self) -> u32 {
+        u32::try_from(self.benchmark.config.iter_batch.get())
+            .unwrap_or(u32::MAX)
+    }
+
+    /// Returns the total time spent in measured iterations. Since each sample
+    /// is the average duration of a batch of runs, this accounts for the
+    /// size of each batch.
+    fn samples_total(&self) -> Duration {
+        self.samples
+            .iter()
+            .fold(Duration::ZERO, |sum, &d| sum.saturating_add(d))
+            .saturating_mul(self.iter_batch())
+    }
+
+    /// Returns an estimate of the time spent in warmup iterations. Runners
+    /// don't report this directly, so it's estimated as the number of warmup
+    /// iterations times the mean duration of a measured iteration.
+    ///
+    /// This returns `None` when the runner doesn't report the number of
+    /// warmup iterations or when there are no samples.
+    fn warmup_estimate(&self) -> Option<Duration> {
+        let warmup_iters = u32::try_from(self.warmup_iters?).ok()?;
+        let samples = u32::try_from(self.samples.len()).ok()?;
+        if samples == 0 {
+            return None;
+        }
+        let mean = self.samples_total() / samples;
+        Some(mean.saturating_mul(warmup_iters))
+    }
+
+    /// Returns the fraction of the total wall clock time of this benchmark
+    /// that was not spent in measured iterations. This includes process
+    /// spawning, piping the benchmark to the runner, warmup and count
+    /// verification.
+    ///
+    /// This returns `None` when the total time is zero, which happens when
+    /// the benchmark never completed.
+    fn overhead_ratio(&self) -> Option<f64> {
+        if self.total.is_zero() {
+            return None;
+        }
+        let overhead = self.total.saturating_sub(self.samples_total());
+        Some(overhead.as_secs_f64() / self.total.as_secs_f64())
     }
 
     /// Convert these results into aggregate statistical values. If there are
     /// no samples, then an "error" measurement is returned.
-    fn to_measurement(&self) -> Measurement {
+    pub fn to_measurement(&self) -> Measurement {
         let mut samples = vec![];
         for &dur in self.samples.iter() {
             samples.push(dur.as_secs_f64());
@@ -783,12 +3256,24 @@ impl Results {
             // string. It would be nice to remove this, but it seems like we'd
             // need to add another layer of configuration to do so? That's a
             // pretty big bummer...
-            "compile" | "regex-redux" => None,
+            "compile" | "first-match" | "regex-redux" | "size" => None,
             _ => {
                 // We don't expect to have haystacks bigger than 2**64.
                 u64::try_from(self.benchmark.def.haystack.len()).ok()
             }
         };
+        let compiled_size_bytes = if self.sizes.is_empty() {
+            None
+        } else {
+            let mut sizes: Vec<f64> =
+                self.sizes.iter().map(|&size| size as f64).collect();
+            // We have no NaNs, so this is fine.
+            sizes.sort_unstable_by(|x, y| x.partial_cmp(y).unwrap());
+            // OK because sizes.len() > 0. And the median of integers is
+            // always either an integer or a half-integer, so rounding is
+            // fine.
+            Some(median(&sizes).unwrap().round() as u64)
+        };
         let times = AggregateTimes {
             // OK because timings.len() > 0
             median: Duration::from_secs_f64(median(&samples).unwrap()),
@@ -814,10 +3299,233 @@ impl Results {
             iters: u64::try_from(samples.len()).unwrap(),
             total: self.total,
             aggregate: Aggregate::new(times, haystack_len),
+            rss_growth_bytes_per_iter: self.rss_growth,
+            compiled_size_bytes,
+            warmup_iters: self.warmup_iters,
+            warmup_estimate: self.warmup_estimate(),
+            overhead_ratio: self.overhead_ratio(),
+            // These are filled in by the caller, since hashing a definition
+            // can be expensive and is shared by all regex engines.
+            def_hash: None,
+            defs_revision: None,
+            engine_metadata: self.benchmark.engine.metadata.clone(),
+            partial: self.partial,
+            // Also filled in by the caller. See 'Scheduling::record' and
+            // 'InjectedEnv::record'.
+            affinity: None,
+            nice: None,
+            env: None,
         }
     }
 }
 
+/// The error returned when a runner program reports that it can't run a
+/// benchmark. This contains the reason given by the runner program.
+///
+/// This is recorded in a measurement's `err` column with a special prefix,
+/// so that it can be distinguished from actual failures.
+#[derive(Debug)]
+pub struct Skipped(pub String);
+
+impl std::fmt::Display for Skipped {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}{}", Measurement::SKIPPED_PREFIX, self.0)
+    }
+}
+
+impl std::error::Error for Skipped {}
+
+/// An error indicating that a runner program reported a count that differs
+/// from the expected count. This keeps the count that was actually observed
+/// around, so that it can be compared across runs by --verify-repeat.
+#[derive(Debug)]
+struct CountMismatch {
+    /// The count reported by the runner program.
+    got: u64,
+    /// The full error message, which includes the expected count.
+    msg: String,
+}
+
+impl std::fmt::Display for CountMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl std::error::Error for CountMismatch {}
+
+/// The outcome of verifying a single benchmark with a single regex engine,
+/// possibly over several runs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Verdict {
+    /// Every run succeeded.
+    Ok,
+    /// The regex engine can't run the benchmark, with the reason given.
+    Skipped(String),
+    /// Every run reported the same wrong count.
+    Mismatch(String),
+    /// Different runs reported different counts. The distinct counts are
+    /// in ascending order.
+    Flaky(Vec<u64>),
+    /// A run failed for a reason other than a count mismatch.
+    Error(String),
+}
+
+impl Verdict {
+    /// Verify the given benchmark `repeat` times and classify the outcome.
+    ///
+    /// This stops early on the first failure that isn't a count mismatch,
+    /// since repeating such a failure rarely tells us anything new.
+    fn new(b: &ExecBenchmark, repeat: u64, verbose: bool) -> Verdict {
+        let mut runs = vec![];
+        for _ in 0..repeat {
+            let run = b.verifier().collect(verbose).map(|r| r.count);
+            let stop = match run {
+                Ok(_) => false,
+                Err(ref err) => !err.is::<CountMismatch>(),
+            };
+            runs.push(run);
+            if stop {
+                break;
+            }
+        }
+        Verdict::from_runs(runs)
+    }
+
+    /// Classify the outcome of verifying a benchmark from the counts (if
+    /// any) or errors reported by each run.
+    ///
+    /// A regex engine that can't run a benchmark takes precedence over
+    /// everything else, followed by counts that differ across runs. Otherwise,
+    /// the first failure is reported.
+    fn from_runs(runs: Vec<anyhow::Result<Option<u64>>>) -> Verdict {
+        let mut observed = BTreeSet::new();
+        let mut failure = None;
+        for run in runs {
+            let err = match run {
+                Ok(count) => {
+                    observed.extend(count);
+                    continue;
+                }
+                Err(err) => err,
+            };
+            if let Some(skipped) = err.downcast_ref::<Skipped>() {
+                return Verdict::Skipped(skipped.0.clone());
+            }
+            let verdict = match err.downcast_ref::<CountMismatch>() {
+                Some(mismatch) => {
+                    observed.insert(mismatch.got);
+                    Verdict::Mismatch(format!("{:#}", err))
+                }
+                None => Verdict::Error(format!("{:#}", err)),
+            };
+            failure.get_or_insert(verdict);
+        }
+        if observed.len() > 1 {
+            return Verdict::Flaky(observed.into_iter().collect());
+        }
+        failure.unwrap_or(Verdict::Ok)
+    }
+}
+
+/// A header line printed by a runner program before its samples.
+enum Header<'a> {
+    /// The number of warmup iterations that were actually executed.
+    WarmupIters(u64),
+    /// Whether the runner stopped early because of its soft deadline.
+    Partial(bool),
+    /// A header with a key that we don't recognize.
+    Unknown(&'a BStr),
+}
+
+/// Parses a header line printed by a runner program. Header lines have the
+/// format '# key=value'. If the given line doesn't start with a '#', then
+/// this returns `None`.
+///
+/// A header line without a '=' is treated as an unknown header. An error is
+/// only returned when the value of a recognized header is invalid.
+fn parse_header(line: &[u8]) -> Option<anyhow::Result<Header<'_>>> {
+    let header = line.strip_prefix(b"#")?.trim_ascii();
+    let Some((key, value)) = header.split_once_str("=") else {
+        return Some(Ok(Header::Unknown(header.as_bstr())));
+    };
+    let (key, value) = (key.trim_ascii(), value.trim_ascii());
+    if key == b"partial" {
+        return Some(match value {
+            b"true" => Ok(Header::Partial(true)),
+            b"false" => Ok(Header::Partial(false)),
+            _ => Err(anyhow::anyhow!(
+                "failed to parse partial {:?} as a boolean",
+                value.as_bstr()
+            )),
+        });
+    }
+    if key != b"warmup_iters" {
+        return Some(Ok(Header::Unknown(key.as_bstr())));
+    }
+    let n = value.to_str().ok().and_then(|s| s.parse::<u64>().ok());
+    Some(n.map(Header::WarmupIters).ok_or_else(|| {
+        anyhow::anyhow!(
+            "failed to parse warmup_iters {:?} as u64",
+            value.as_bstr()
+        )
+    }))
+}
+
+/// A single sample parsed from a line printed by a runner program.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct RawSample {
+    /// The duration of the iteration.
+    duration: Duration,
+    /// The count reported by the iteration.
+    count: u64,
+    /// The size, in bytes, of the compiled regex, if reported.
+    size: Option<u64>,
+    /// The index of the iteration that produced this sample, if reported.
+    iter: Option<u64>,
+}
+
+/// Parses a single sample line printed by a runner program. Each line has
+/// the format 'duration_nanos,count' with an optional third field containing
+/// the size, in bytes, of the compiled regex and an optional fourth field
+/// containing the index of the iteration. The third field may be empty when
+/// the fourth is present.
+fn parse_sample(line: &[u8]) -> anyhow::Result<RawSample> {
+    fn parse_u64(field: &[u8], what: &str) -> anyhow::Result<u64> {
+        let s = field.to_str().with_context(|| {
+            format!(
+                "failed to parse {} field {:?} as UTF-8",
+                what,
+                field.as_bstr()
+            )
+        })?;
+        s.parse::<u64>().with_context(|| {
+            format!("failed to parse {} field {:?} as u64", what, s)
+        })
+    }
+
+    let mut fields = line.split_str(",");
+    let (Some(field1), Some(field2)) = (fields.next(), fields.next()) else {
+        anyhow::bail!("missing ',' separator")
+    };
+    // Runners may optionally report a third field containing the size, in
+    // bytes, of the compiled regex, and a fourth containing the iteration
+    // index. Older runners (and most runners for models other than
+    // 'compile') don't, so it's fine for them to be missing.
+    let field3 = fields.next().filter(|f| !f.is_empty());
+    let field4 = fields.next();
+    anyhow::ensure!(fields.next().is_none(), "too many fields");
+    let nanos = parse_u64(field1, "duration")?;
+    // If we get a measurement of 0 nanoseconds, then that winds up being
+    // pretty meaningless. So we "round up" to 1. Basically, we just give up
+    // trying to measure anything that reliably takes less than 1 nanosecond.
+    let duration = Duration::from_nanos(if nanos == 0 { 1 } else { nanos });
+    let count = parse_u64(field2, "count")?;
+    let size = field3.map(|f| parse_u64(f, "size")).transpose()?;
+    let iter = field4.map(|f| parse_u64(f, "iteration")).transpose()?;
+    Ok(RawSample { duration, count, size, iter })
+}
+
 fn mean(xs: &[f64]) -> Option<f64> {
     if xs.is_empty() {
         None
@@ -878,3 +3586,690 @@ fn max(xs: &[f64]) -> Option<f64> {
     }
     Some(max)
 }
+
+/// Returns the resident set size, in bytes, of the process with the given ID.
+///
+/// On Linux, this reads '/proc/{pid}/status'. On other Unix systems, this
+/// shells out to 'ps', which is much slower but still fine for sampling a few
+/// times per second.
+fn rss(pid: u32) -> anyhow::Result<u64> {
+    if cfg!(target_os = "linux") {
+        let path = format!("/proc/{}/status", pid);
+        let status = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path))?;
+        let line = status
+            .lines()
+            .find(|line| line.starts_with("VmRSS:"))
+            .with_context(|| format!("could not find VmRSS in {}", path))?;
+        let kb = line
+            .trim_start_matches("VmRSS:")
+            .trim()
+            .trim_end_matches("kB")
+            .trim();
+        let kb: u64 = kb
+            .parse()
+            .with_context(|| format!("failed to parse VmRSS '{}'", kb))?;
+        Ok(kb * 1024)
+    } else if cfg!(unix) {
+        let mut cmd = std::process::Command::new("ps");
+        cmd.arg("-o").arg("rss=").arg("-p").arg(pid.to_string());
+        let out = util::output(&mut cmd)?;
+        let kb = out.to_str().context("ps output is not UTF-8")?.trim();
+        let kb: u64 = kb
+            .parse()
+            .with_context(|| format!("failed to parse ps RSS '{}'", kb))?;
+        Ok(kb * 1024)
+    } else {
+        anyhow::bail!("sampling RSS is unsupported on this platform")
+    }
+}
+
+/// The platform specific operations needed to apply a `Scheduling` to a
+/// runner process.
+///
+/// This is a trait so that the logic around these operations can be tested
+/// without elevated privileges or specific hardware.
+trait Scheduler {
+    /// Returns the CPU cores that a process can be pinned to, or `None` if
+    /// pinning isn't supported on this platform.
+    fn cores(&self) -> Option<Vec<usize>>;
+
+    /// Pins the process with the given ID to the given CPU core.
+    fn set_affinity(&self, pid: u32, core: usize) -> anyhow::Result<()>;
+
+    /// Sets the niceness of the process with the given ID.
+    fn set_nice(&self, pid: u32, nice: i32) -> anyhow::Result<()>;
+}
+
+/// A scheduler that uses the operating system's APIs.
+///
+/// On Linux, this uses sched_setaffinity(2) and setpriority(2). On other
+/// Unix systems, pinning isn't supported, but setpriority(2) is still used.
+/// On Windows, this uses SetProcessAffinityMask and SetPriorityClass.
+#[derive(Clone, Copy, Debug)]
+struct OsScheduler;
+
+#[cfg(target_os = "linux")]
+impl Scheduler for OsScheduler {
+    fn cores(&self) -> Option<Vec<usize>> {
+        // SAFETY: 'cpu_set_t' is a plain bit set, so all zeros is valid, and
+        // we pass its correct size.
+        let set = unsafe {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            let size = std::mem::size_of::<libc::cpu_set_t>();
+            if libc::sched_getaffinity(0, size, &mut set) != 0 {
+                log::debug!(
+                    "sched_getaffinity failed: {}",
+                    std::io::Error::last_os_error(),
+                );
+                return Some(vec![]);
+            }
+            set
+        };
+        let max = 8 * std::mem::size_of::<libc::cpu_set_t>();
+        // SAFETY: Every core given is less than the size of the set.
+        Some(
+            (0..max)
+                .filter(|&c| unsafe { libc::CPU_ISSET(c, &set) })
+                .collect(),
+        )
+    }
+
+    fn set_affinity(&self, pid: u32, core: usize) -> anyhow::Result<()> {
+        let max = 8 * std::mem::size_of::<libc::cpu_set_t>();
+        anyhow::ensure!(core < max, "CPU core must be less than {}", max);
+        Ok(linux_set_affinity(pid, core)?)
+    }
+
+    fn set_nice(&self, pid: u32, nice: i32) -> anyhow::Result<()> {
+        Ok(unix_set_nice(pid, nice)?)
+    }
+}
+
+/// Pins the process with the given ID to the given CPU core via
+/// sched_setaffinity(2). A process ID of zero refers to the calling thread.
+///
+/// This is async-signal-safe, so that it can be called between fork and
+/// exec. A core that is out of bounds results in an 'EINVAL' error.
+#[cfg(target_os = "linux")]
+fn linux_set_affinity(pid: u32, core: usize) -> std::io::Result<()> {
+    if core >= 8 * std::mem::size_of::<libc::cpu_set_t>() {
+        return Err(std::io::Error::from_raw_os_error(libc::EINVAL));
+    }
+    // SAFETY: 'cpu_set_t' is a plain bit set, so all zeros is valid, the
+    // core is in bounds and we pass the correct size of the set.
+    let rc = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        let size = std::mem::size_of::<libc::cpu_set_t>();
+        libc::sched_setaffinity(pid as libc::pid_t, size, &set)
+    };
+    if rc != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+impl Scheduler for OsScheduler {
+    fn cores(&self) -> Option<Vec<usize>> {
+        None
+    }
+
+    fn set_affinity(&self, _pid: u32, _core: usize) -> anyhow::Result<()> {
+        anyhow::bail!("pinning to a CPU core is unsupported on this platform")
+    }
+
+    fn set_nice(&self, pid: u32, nice: i32) -> anyhow::Result<()> {
+        Ok(unix_set_nice(pid, nice)?)
+    }
+}
+
+/// Sets the niceness of the process with the given ID via setpriority(2). A
+/// process ID of zero refers to the calling process.
+///
+/// This is async-signal-safe, so that it can be called between fork and
+/// exec.
+#[cfg(unix)]
+fn unix_set_nice(pid: u32, nice: i32) -> std::io::Result<()> {
+    // SAFETY: This has no memory safety requirements.
+    let rc = unsafe {
+        libc::setpriority(libc::PRIO_PROCESS, pid as libc::id_t, nice)
+    };
+    if rc != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+impl Scheduler for OsScheduler {
+    fn cores(&self) -> Option<Vec<usize>> {
+        use windows_sys::Win32::System::Threading::{
+            GetCurrentProcess, GetProcessAffinityMask,
+        };
+
+        let (mut process_mask, mut system_mask) = (0usize, 0usize);
+        // SAFETY: The current process handle is always valid, and both
+        // pointers are valid for writes.
+        let ok = unsafe {
+            GetProcessAffinityMask(
+                GetCurrentProcess(),
+                &mut process_mask,
+                &mut system_mask,
+            )
+        };
+        if ok == 0 {
+            log::debug!(
+                "GetProcessAffinityMask failed: {}",
+                std::io::Error::last_os_error(),
+            );
+            return Some(vec![]);
+        }
+        let max = usize::BITS as usize;
+        Some((0..max).filter(|&c| process_mask & (1 << c) != 0).collect())
+    }
+
+    fn set_affinity(&self, pid: u32, core: usize) -> anyhow::Result<()> {
+        use windows_sys::Win32::System::Threading::SetProcessAffinityMask;
+
+        let max = usize::BITS as usize;
+        anyhow::ensure!(core < max, "CPU core must be less than {}", max);
+        windows_with_process(pid, |handle| {
+            // SAFETY: The handle is valid for as long as this closure runs.
+            unsafe { SetProcessAffinityMask(handle, 1 << core) }
+        })
+    }
+
+    fn set_nice(&self, pid: u32, nice: i32) -> anyhow::Result<()> {
+        use windows_sys::Win32::System::Threading::{
+            SetPriorityClass, ABOVE_NORMAL_PRIORITY_CLASS,
+            BELOW_NORMAL_PRIORITY_CLASS, HIGH_PRIORITY_CLASS,
+            IDLE_PRIORITY_CLASS, NORMAL_PRIORITY_CLASS,
+        };
+
+        let class = match nice {
+            ..=-11 => HIGH_PRIORITY_CLASS,
+            -10..=-1 => ABOVE_NORMAL_PRIORITY_CLASS,
+            0 => NORMAL_PRIORITY_CLASS,
+            1..=10 => BELOW_NORMAL_PRIORITY_CLASS,
+            11.. => IDLE_PRIORITY_CLASS,
+        };
+        windows_with_process(pid, |handle| {
+            // SAFETY: The handle is valid for as long as this closure runs.
+            unsafe { SetPriorityClass(handle, class) }
+        })
+    }
+}
+
+/// Opens a handle to the process with the given ID that permits changing
+/// its scheduling, and calls the given closure with it. The closure should
+/// return the result of a Windows API call, where zero indicates failure.
+#[cfg(windows)]
+fn windows_with_process(
+    pid: u32,
+    f: impl FnOnce(windows_sys::Win32::Foundation::HANDLE) -> i32,
+) -> anyhow::Result<()> {
+    use windows_sys::Win32::{
+        Foundation::CloseHandle,
+        System::Threading::{
+            OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+            PROCESS_SET_INFORMATION,
+        },
+    };
+
+    let access = PROCESS_SET_INFORMATION | PROCESS_QUERY_LIMITED_INFORMATION;
+    // SAFETY: This has no memory safety requirements.
+    let handle = unsafe { OpenProcess(access, 0, pid) };
+    anyhow::ensure!(
+        !handle.is_null(),
+        "failed to open process: {}",
+        std::io::Error::last_os_error(),
+    );
+    let ok = f(handle);
+    // Capture the error before closing the handle, which may clobber it.
+    let err = std::io::Error::last_os_error();
+    // SAFETY: The handle was opened above and isn't used after this.
+    unsafe {
+        CloseHandle(handle);
+    }
+    anyhow::ensure!(ok != 0, "{}", err);
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+impl Scheduler for OsScheduler {
+    fn cores(&self) -> Option<Vec<usize>> {
+        None
+    }
+
+    fn set_affinity(&self, _pid: u32, _core: usize) -> anyhow::Result<()> {
+        anyhow::bail!("pinning to a CPU core is unsupported on this platform")
+    }
+
+    fn set_nice(&self, _pid: u32, _nice: i32) -> anyhow::Result<()> {
+        anyhow::bail!("setting niceness is unsupported on this platform")
+    }
+}
+
+/// Returns the name of the file that samples for the given benchmark and
+/// engine are dumped to.
+///
+/// Every character other than an ASCII alphanumeric, `-` or `.` is
+/// percent-encoded. Since this includes both `_` and `%`, the `__` separator
+/// can't appear in either encoded name and distinct pairs never map to the
+/// same file.
+fn dump_file_name(benchmark: &str, engine: &str) -> String {
+    fn escape(name: &str) -> String {
+        let mut escaped = String::new();
+        for &byte in name.as_bytes() {
+            if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'.' {
+                escaped.push(char::from(byte));
+            } else {
+                escaped.push_str(&format!("%{:02X}", byte));
+            }
+        }
+        escaped
+    }
+    format!("{}__{}.csv", escape(benchmark), escape(engine))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    /// A scheduler that records what it was asked to do instead of doing it.
+    #[derive(Debug, Default)]
+    struct FakeScheduler {
+        /// The cores that exist, or `None` if pinning is unsupported.
+        cores: Option<Vec<usize>>,
+        /// When enabled, setting the niceness fails, like it does when
+        /// trying to raise the priority of a process without privileges.
+        deny_nice: bool,
+        /// Every call made, in order.
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl Scheduler for FakeScheduler {
+        fn cores(&self) -> Option<Vec<usize>> {
+            self.cores.clone()
+        }
+
+        fn set_affinity(&self, pid: u32, core: usize) -> anyhow::Result<()> {
+            self.calls.borrow_mut().push(format!("affinity({pid}, {core})"));
+            Ok(())
+        }
+
+        fn set_nice(&self, pid: u32, nice: i32) -> anyhow::Result<()> {
+            self.calls.borrow_mut().push(format!("nice({pid}, {nice})"));
+            anyhow::ensure!(!self.deny_nice, "permission denied");
+            Ok(())
+        }
+    }
+
+    fn parse(args: &[&str]) -> anyhow::Result<Scheduling> {
+        let mut p = lexopt::Parser::from_args(args);
+        Ok(Config::parse(&mut p)?.bench_config.scheduling)
+    }
+
+    #[test]
+    fn scheduling_parse() {
+        assert_eq!(Scheduling::default(), parse(&[]).unwrap());
+        let got = parse(&["--affinity", "3", "--nice", "-5"]).unwrap();
+        assert_eq!(Scheduling { affinity: Some(3), nice: Some(-5) }, got);
+
+        let err = parse(&["--nice", "20"]).unwrap_err().to_string();
+        assert!(err.contains("-20..=19"), "{}", err);
+        assert!(parse(&["--affinity", "-1"]).is_err());
+    }
+
+    #[test]
+    fn scheduling_check() {
+        let sched = Scheduling { affinity: Some(3), nice: Some(5) };
+        let fake = FakeScheduler {
+            cores: Some(vec![0, 1, 2, 3]),
+            ..FakeScheduler::default()
+        };
+        assert_eq!(sched, sched.check(&fake));
+
+        // A core that doesn't exist is dropped, but the niceness is kept.
+        let fake = FakeScheduler {
+            cores: Some(vec![0, 1]),
+            ..FakeScheduler::default()
+        };
+        let want = Scheduling { affinity: None, nice: Some(5) };
+        assert_eq!(want, sched.check(&fake));
+
+        // Likewise when pinning isn't supported at all.
+        assert_eq!(want, sched.check(&FakeScheduler::default()));
+    }
+
+    #[test]
+    fn scheduling_apply() {
+        let sched = Scheduling { affinity: Some(1), nice: Some(-5) };
+        let fake = FakeScheduler::default();
+        sched.apply(&fake, 42).unwrap();
+        assert_eq!(
+            vec!["affinity(42, 1)", "nice(42, -5)"],
+            *fake.calls.borrow()
+        );
+
+        let fake =
+            FakeScheduler { deny_nice: true, ..FakeScheduler::default() };
+        let err = format!("{:#}", sched.apply(&fake, 42).unwrap_err());
+        assert!(err.contains("niceness of runner process to -5"), "{}", err);
+        assert!(err.contains("permission denied"), "{}", err);
+
+        // Nothing is done when nothing was requested.
+        let fake = FakeScheduler::default();
+        Scheduling::default().apply(&fake, 42).unwrap();
+        assert!(fake.calls.borrow().is_empty());
+    }
+
+    #[test]
+    fn scheduling_record() {
+        let mut m = Measurement::default();
+        Scheduling::default().record(&mut m);
+        assert_eq!((None, None), (m.affinity, m.nice));
+
+        Scheduling { affinity: Some(2), nice: Some(10) }.record(&mut m);
+        assert_eq!((Some(2), Some(10)), (m.affinity, m.nice));
+    }
+
+    fn parse_env(args: &[&str]) -> anyhow::Result<InjectedEnv> {
+        let mut p = lexopt::Parser::from_args(args);
+        Ok(Config::parse(&mut p)?.bench_config.env)
+    }
+
+    #[test]
+    fn env_parse() {
+        let env = parse_env(&[
+            "--env",
+            "rust/.*:MALLOC_ARENA_MAX=1",
+            "--env",
+            "(?:re2|pcre2):PATH=/a:/b=c",
+        ])
+        .unwrap();
+        assert!(!env.record_values);
+        let got: Vec<(&str, &str, &str)> = env
+            .vars
+            .iter()
+            .map(|v| (v.engine.as_str(), v.name.as_str(), v.value.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("^(?:rust/.*)$", "MALLOC_ARENA_MAX", "1"),
+                ("^(?:(?:re2|pcre2))$", "PATH", "/a:/b=c"),
+            ],
+            got,
+        );
+        assert!(parse_env(&["--env-record-values"]).unwrap().record_values);
+        assert_eq!(
+            "",
+            parse_env(&["--env", ".*:EMPTY="]).unwrap().vars[0].value
+        );
+
+        let err = |spec: &str| {
+            format!("{:#}", parse_env(&["--env", spec]).unwrap_err())
+        };
+        assert!(err("rust/regex").contains("missing '='"));
+        assert!(err("FOO=1").contains("missing ':'"));
+        assert!(err(":FOO=1").contains("pattern is empty"));
+        assert!(err("rust/.*:=1").contains("variable name ''"));
+        assert!(err("rust/.*:1FOO=1").contains("variable name '1FOO'"));
+        assert!(err("rust/.*:FOO BAR=1").contains("variable name 'FOO BAR'"));
+        assert!(err("rust/.*:FOO=a\0b").contains("NUL byte"));
+        assert!(err("rust/(:FOO=1").contains("pattern 'rust/('"));
+        assert!(err("rust/(:FOO=1").starts_with("--env: invalid spec"));
+    }
+
+    #[test]
+    fn env_apply_and_record() {
+        let env = parse_env(&[
+            "--env",
+            "rust/.*:A=1",
+            "--env",
+            "rust/regex:B=2",
+            "--env",
+            ".*:A=3",
+        ])
+        .unwrap();
+
+        // The pattern must match the entire name, and the last value wins.
+        let resolved = env.resolve("rust/regex");
+        assert_eq!(
+            vec![("A", "3"), ("B", "2")],
+            resolved.into_iter().collect::<Vec<_>>()
+        );
+        let resolved = env.resolve("rust/regex/meta");
+        assert_eq!(vec![("A", "3")], resolved.into_iter().collect::<Vec<_>>());
+
+        let mut cmd = std::process::Command::new("true");
+        env.apply("rust/regex", &mut cmd);
+        let got: Vec<(String, String)> = cmd
+            .get_envs()
+            .map(|(k, v)| {
+                let s = |x: &std::ffi::OsStr| x.to_str().unwrap().to_string();
+                (s(k), s(v.unwrap()))
+            })
+            .collect();
+        let s = |x: &str| x.to_string();
+        assert_eq!(vec![(s("A"), s("3")), (s("B"), s("2"))], got);
+
+        let record = |env: &InjectedEnv, engine: &str| {
+            let mut m = Measurement {
+                engine: engine.to_string(),
+                ..Measurement::default()
+            };
+            env.record(&mut m);
+            m.env
+        };
+        assert_eq!(Some(s("A B")), record(&env, "rust/regex"));
+        assert_eq!(Some(s("A")), record(&env, "pcre2"));
+        let env = InjectedEnv { record_values: true, ..env };
+        assert_eq!(Some(s("A=3 B=2")), record(&env, "rust/regex"));
+        assert_eq!(None, record(&InjectedEnv::default(), "rust/regex"));
+    }
+
+    fn mismatch(got: u64) -> anyhow::Result<Option<u64>> {
+        Err(anyhow::Error::new(CountMismatch {
+            got,
+            msg: format!("count mismatch, expected 5, got {}", got),
+        }))
+    }
+
+    #[test]
+    fn verdict() {
+        let v = Verdict::from_runs(vec![Ok(Some(5)), Ok(Some(5))]);
+        assert_eq!(Verdict::Ok, v);
+
+        let v = Verdict::from_runs(vec![mismatch(4), mismatch(4)]);
+        let want = "count mismatch, expected 5, got 4".to_string();
+        assert_eq!(Verdict::Mismatch(want), v);
+
+        // Counts that differ are flaky, whether or not any of them is right.
+        let v = Verdict::from_runs(vec![Ok(Some(5)), mismatch(4)]);
+        assert_eq!(Verdict::Flaky(vec![4, 5]), v);
+        let v = Verdict::from_runs(vec![mismatch(6), mismatch(4)]);
+        assert_eq!(Verdict::Flaky(vec![4, 6]), v);
+
+        let err = Err(anyhow::anyhow!("runner crashed"));
+        let v = Verdict::from_runs(vec![Ok(Some(5)), err]);
+        assert_eq!(Verdict::Error("runner crashed".to_string()), v);
+
+        let skip = Err(anyhow::Error::new(Skipped("nope".to_string())));
+        let v = Verdict::from_runs(vec![skip]);
+        assert_eq!(Verdict::Skipped("nope".to_string()), v);
+    }
+
+    #[test]
+    fn progress_remaining() {
+        let secs = Duration::from_secs;
+        let progress = Progress {
+            rewrite: false,
+            start: Instant::now(),
+            budgets: vec![secs(10); 8],
+        };
+        // Before enough benchmarks have completed, only budgets are used.
+        assert_eq!(secs(80), progress.remaining(0, secs(0)));
+        assert_eq!(secs(60), progress.remaining(2, secs(5)));
+        // Afterwards, the remaining budget is scaled by how long completed
+        // benchmarks actually took relative to their budgets.
+        assert_eq!(secs(15), progress.remaining(5, secs(25)));
+        assert_eq!(secs(0), progress.remaining(8, secs(40)));
+    }
+
+    #[test]
+    fn clock() {
+        assert_eq!("0s", super::clock(Duration::from_millis(999)));
+        assert_eq!("37s", super::clock(Duration::from_secs(37)));
+        assert_eq!("4m09s", super::clock(Duration::from_secs(249)));
+        assert_eq!("1h05m", super::clock(Duration::from_secs(3930)));
+    }
+
+    #[test]
+    fn parse_samples() {
+        let sample = |nanos, count, size, iter| RawSample {
+            duration: Duration::from_nanos(nanos),
+            count,
+            size,
+            iter,
+        };
+        assert_eq!(sample(5, 2, None, None), parse_sample(b"5,2").unwrap());
+        assert_eq!(
+            sample(5, 2, Some(100), None),
+            parse_sample(b"5,2,100").unwrap()
+        );
+        assert_eq!(
+            sample(5, 2, Some(100), Some(7)),
+            parse_sample(b"5,2,100,7").unwrap()
+        );
+        // The size may be empty when the iteration index is present.
+        assert_eq!(
+            sample(5, 2, None, Some(7)),
+            parse_sample(b"5,2,,7").unwrap()
+        );
+        // Zero durations are rounded up.
+        assert_eq!(sample(1, 2, None, None), parse_sample(b"0,2").unwrap());
+
+        assert!(parse_sample(b"5").is_err());
+        assert!(parse_sample(b"5,x").is_err());
+        assert!(parse_sample(b"5,2,100,").is_err());
+        assert!(parse_sample(b"5,2,100,7,8").is_err());
+    }
+
+    /// Runs a benchmark whose runner program is the given shell script, with
+    /// the config given.
+    #[cfg(unix)]
+    fn collect_with_script(
+        script: &str,
+        config: ExecBenchmarkConfig,
+    ) -> anyhow::Result<Measurement> {
+        let raw = r#"
+[[bench]]
+model = "count"
+name = "slow"
+regex = 'a'
+haystack = 'a'
+count = 1
+engines = ["fake"]
+"#;
+        let engines = Engines::fake(["fake"]);
+        let benches = Benchmarks::from_slice(
+            &engines,
+            &Filters::default(),
+            "test",
+            raw,
+        )?;
+        let mut engine = engines.list[0].clone();
+        engine.run.bin = "sh".to_string();
+        engine.run.args = vec!["-c".to_string(), script.to_string()];
+        let b = ExecBenchmark::new(config, benches.defs[0].clone(), engine);
+        Ok(b.aggregate(b.collect(false)))
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn soft_deadline_partial() {
+        // A runner program that spends a while setting up and then stops
+        // early because of its soft deadline. The deadline is 90% of the
+        // timeout.
+        let script = r#"
+            case "$(cat)" in
+                *soft-deadline:10:2700000000*) ;;
+                *) echo "missing or wrong soft-deadline" >&2; exit 1 ;;
+            esac
+            sleep 1
+            echo '# warmup_iters=0'
+            echo '# partial=true'
+            echo '1000,1'
+        "#;
+        let config = ExecBenchmarkConfig {
+            timeout: Duration::from_secs(3),
+            ..Default::default()
+        };
+        let m = collect_with_script(script, config).unwrap();
+        assert_eq!(None, m.err);
+        assert!(m.partial);
+        assert_eq!(1, m.iters);
+
+        // A runner program that doesn't stop in time is killed, and there is
+        // no partial measurement.
+        let script = "cat >/dev/null; sleep 5; echo '1000,1'";
+        let config = ExecBenchmarkConfig {
+            timeout: Duration::from_secs(1),
+            ..Default::default()
+        };
+        let m = collect_with_script(script, config).unwrap();
+        assert!(m.err.is_some());
+        assert!(!m.partial);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn scheduling_inherited() {
+        // The niceness is applied before the runner program starts, so it's
+        // inherited by everything the runner starts. Here, that's 'cut',
+        // which reads its own niceness from the 19th field of its stat file.
+        // Lowering priority never needs privileges.
+        let script = r#"
+            cat >/dev/null
+            nice=$(cut -d' ' -f19 /proc/self/stat)
+            if [ "$nice" != 7 ]; then
+                echo "expected niceness 7, got $nice" >&2
+                exit 1
+            fi
+            echo '1000,1'
+        "#;
+        let config = ExecBenchmarkConfig {
+            scheduling: Scheduling { affinity: None, nice: Some(7) },
+            ..Default::default()
+        };
+        let m = collect_with_script(script, config).unwrap();
+        assert_eq!(None, m.err);
+    }
+
+    #[test]
+    fn dump_file_names() {
+        assert_eq!(
+            "curated%2F01-literal%2Fsherlock-en__rust%2Fregex.csv",
+            dump_file_name("curated/01-literal/sherlock-en", "rust/regex"),
+        );
+        // These all collided when '/' was simply replaced with '-' or when
+        // '_' was left alone.
+        let names = [
+            dump_file_name("a/b", "c"),
+            dump_file_name("a-b", "c"),
+            dump_file_name("a__b", "c"),
+            dump_file_name("a", "b__c"),
+            dump_file_name("a%2Fb", "c"),
+        ];
+        for (i, n1) in names.iter().enumerate() {
+            for n2 in names[i + 1..].iter() {
+                assert_ne!(n1, n2);
+            }
+        }
+    }
+}
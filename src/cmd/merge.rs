@@ -0,0 +1,361 @@
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+
+use anyhow::Context;
+
+use crate::{
+    args::{self, Usage},
+    format::measurement::{Aggregate, AggregateTimes, Measurement},
+};
+
+const USAGES: &[Usage] = &[Usage::new(
+    "--strategy <name>",
+    "One of: min, median, mean. (default: median)",
+    r#"
+The strategy used to combine the aggregate timings of duplicate measurements.
+
+Each timing column (median, mad, mean, stddev, min and max) is combined
+independently. For example, with 'min', the merged median is the smallest of
+the medians of each input, and the merged max is the smallest of the maxes
+of each input. Throughputs are recomputed from the merged timings.
+
+The 'min' strategy corresponds to "best of N," which tends to be the least
+noisy. The 'median' strategy is more robust to a single run being unusually
+fast or slow. The 'mean' strategy is provided for completeness.
+"#,
+)];
+
+fn usage_short() -> String {
+    format!(
+        "\
+Merge measurements from repeated runs into a single CSV file.
+
+USAGE:
+    rebar merge [OPTIONS] <csv-path> ...
+
+TIP:
+    use -h for short docs and --help for long docs
+
+OPTIONS:
+{options}
+",
+        options = Usage::short(USAGES),
+    )
+    .trim()
+    .to_string()
+}
+
+fn usage_long() -> String {
+    format!(
+        "\
+Merge measurements from repeated runs into a single CSV file.
+
+Every other command that reads measurements rejects conflicting duplicate
+measurements, i.e., more than one measurement for the same benchmark and regex
+engine that differ in any way. (Identical duplicates are dropped.) This
+command combines such duplicates into a single measurement and writes the
+result to stdout as CSV. This is useful for reducing noise by running the
+same benchmarks multiple times (say, on different days) and merging the
+results.
+
+Duplicate measurements are combined by applying the strategy given by
+--strategy to each aggregate timing. The number of iterations and the total
+time are summed, as are the warmup and overhead columns when every input
+has them. A merged measurement is marked as partial if any of its inputs are.
+All other columns are taken from the first input containing a successful
+measurement. If every input for a benchmark and regex engine is
+an error, then the first error is written. Otherwise, errors are ignored.
+
+Measurements that only appear in some of the inputs are written as-is (after
+merging with whichever inputs they do appear in), and a warning is printed
+with the number of such measurements.
+
+USAGE:
+    rebar merge [OPTIONS] <csv-path> ...
+
+    This command takes one or more file paths to CSV files written by the
+    'rebar measure' command.
+
+TIP:
+    use -h for short docs and --help for long docs
+
+OPTIONS:
+{options}
+",
+        options = Usage::long(USAGES),
+    )
+    .trim()
+    .to_string()
+}
+
+pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
+    let config = Config::parse(p)?;
+    // Groups of measurements keyed by benchmark name and regex engine, in the
+    // order in which each key was first seen.
+    let mut groups: Vec<Vec<Measurement>> = vec![];
+    let mut index: BTreeMap<(String, String), usize> = BTreeMap::new();
+    for path in config.csv_paths.iter() {
+        let mut rdr = csv::Reader::from_path(path)
+            .with_context(|| path.display().to_string())?;
+        for result in rdr.deserialize() {
+            let m: Measurement =
+                result.with_context(|| path.display().to_string())?;
+            let key = (m.name.clone(), m.engine.clone());
+            let i = *index.entry(key).or_insert_with(|| {
+                groups.push(vec![]);
+                groups.len() - 1
+            });
+            groups[i].push(m);
+        }
+    }
+
+    let mut partial = 0;
+    let mut wtr = csv::Writer::from_writer(std::io::stdout().lock());
+    for group in groups {
+        if group.len() < config.csv_paths.len() {
+            partial += 1;
+        }
+        wtr.serialize(merge(config.strategy, group))?;
+    }
+    wtr.flush()?;
+    if partial > 0 {
+        log::warn!(
+            "{} measurement(s) were not present in all {} inputs",
+            partial,
+            config.csv_paths.len(),
+        );
+    }
+    Ok(())
+}
+
+/// Merge the given group of measurements, which must all have the same
+/// benchmark name and regex engine, into a single measurement.
+///
+/// # Panics
+///
+/// This panics if the group is empty.
+fn merge(strategy: Strategy, group: Vec<Measurement>) -> Measurement {
+    let (oks, errs): (Vec<Measurement>, Vec<Measurement>) =
+        group.into_iter().partition(|m| m.err.is_none());
+    let Some(first) = oks.first() else {
+        return errs.into_iter().next().unwrap();
+    };
+    if oks.len() == 1 {
+        return first.clone();
+    }
+    for m in oks.iter().skip(1) {
+        if m.engine_version != first.engine_version {
+            log::warn!(
+                "{}:{}: merging measurements with different regex engine \
+                 versions ('{}' and '{}')",
+                first.name,
+                first.engine,
+                first.engine_version,
+                m.engine_version,
+            );
+        }
+    }
+
+    let combine = |field: fn(&AggregateTimes) -> Duration| {
+        let mut durs: Vec<Duration> =
+            oks.iter().map(|m| field(&m.aggregate.times)).collect();
+        strategy.apply(&mut durs)
+    };
+    let times = AggregateTimes {
+        median: combine(|t| t.median),
+        mad: combine(|t| t.mad),
+        mean: combine(|t| t.mean),
+        stddev: combine(|t| t.stddev),
+        min: combine(|t| t.min),
+        max: combine(|t| t.max),
+    };
+    let haystack_len = first.aggregate.tputs.as_ref().map(|t| t.len);
+    let total: Duration = oks.iter().map(|m| m.total).sum();
+    // The overhead columns are summed just like 'total' is, but only when
+    // every input has them.
+    let overhead = oks
+        .iter()
+        .map(|m| Some(m.total.mul_f64(m.overhead_ratio?)))
+        .sum::<Option<Duration>>();
+    let overhead_ratio = overhead
+        .filter(|_| !total.is_zero())
+        .map(|d| d.as_secs_f64() / total.as_secs_f64());
+    Measurement {
+        iters: oks.iter().map(|m| m.iters).sum(),
+        total,
+        aggregate: Aggregate::new(times, haystack_len),
+        warmup_iters: oks.iter().map(|m| m.warmup_iters).sum(),
+        warmup_estimate: oks.iter().map(|m| m.warmup_estimate).sum(),
+        overhead_ratio,
+        partial: oks.iter().any(|m| m.partial),
+        ..first.clone()
+    }
+}
+
+/// The strategy used to combine duplicate aggregate timings.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+enum Strategy {
+    Min,
+    #[default]
+    Median,
+    Mean,
+}
+
+impl Strategy {
+    /// Combine the given durations into one according to this strategy.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the given slice is empty.
+    fn apply(self, durs: &mut [Duration]) -> Duration {
+        assert!(!durs.is_empty(), "cannot combine zero durations");
+        match self {
+            Strategy::Min => durs.iter().copied().min().unwrap(),
+            Strategy::Median => {
+                durs.sort_unstable();
+                let mid = durs.len() / 2;
+                if durs.len() % 2 == 1 {
+                    durs[mid]
+                } else {
+                    (durs[mid - 1] + durs[mid]) / 2
+                }
+            }
+            Strategy::Mean => {
+                // We don't expect to merge more than 2**32 files.
+                let len = u32::try_from(durs.len()).unwrap();
+                durs.iter().sum::<Duration>() / len
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Strategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Strategy> {
+        let strategy = match s {
+            "min" => Strategy::Min,
+            "median" => Strategy::Median,
+            "mean" => Strategy::Mean,
+            unknown => {
+                anyhow::bail!(
+                    "unrecognized strategy '{}', must be \
+                     one of min, median or mean.",
+                    unknown,
+                )
+            }
+        };
+        Ok(strategy)
+    }
+}
+
+/// The arguments for this 'merge' command parsed from CLI args.
+#[derive(Debug, Default)]
+struct Config {
+    /// File paths to CSV files.
+    csv_paths: Vec<PathBuf>,
+    /// How to combine duplicate measurements.
+    strategy: Strategy,
+}
+
+impl Config {
+    /// Parse 'merge' args from the given CLI parser.
+    fn parse(p: &mut lexopt::Parser) -> anyhow::Result<Config> {
+        use lexopt::Arg;
+
+        let mut c = Config::default();
+        while let Some(arg) = p.next()? {
+            match arg {
+                Arg::Value(v) => c.csv_paths.push(PathBuf::from(v)),
+                Arg::Short('h') => anyhow::bail!("{}", usage_short()),
+                Arg::Long("help") => anyhow::bail!("{}", usage_long()),
+                Arg::Long("strategy") => {
+                    c.strategy = args::parse(p, "--strategy")?;
+                }
+                _ => return Err(arg.unexpected().into()),
+            }
+        }
+        anyhow::ensure!(!c.csv_paths.is_empty(), "no CSV file paths given");
+        Ok(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measurement(median_ms: u64, err: Option<&str>) -> Measurement {
+        let median = Duration::from_millis(median_ms);
+        let times = AggregateTimes {
+            median,
+            mad: median / 10,
+            mean: median,
+            stddev: median / 10,
+            min: median / 2,
+            max: median * 2,
+        };
+        Measurement {
+            name: "curated/01-literal/sherlock-en".to_string(),
+            model: "count".to_string(),
+            engine: "rust/regex".to_string(),
+            err: err.map(|e| e.to_string()),
+            iters: 10,
+            total: Duration::from_secs(1),
+            aggregate: Aggregate::new(times, Some(1_000)),
+            ..Measurement::default()
+        }
+    }
+
+    #[test]
+    fn strategies() {
+        let ms = |xs: &[u64]| -> Vec<Duration> {
+            xs.iter().map(|&x| Duration::from_millis(x)).collect()
+        };
+        let d = Duration::from_millis;
+        assert_eq!(d(1), Strategy::Min.apply(&mut ms(&[3, 1, 2])));
+        assert_eq!(d(2), Strategy::Median.apply(&mut ms(&[3, 1, 2])));
+        assert_eq!(d(2), Strategy::Median.apply(&mut ms(&[4, 1, 3, 0])));
+        assert_eq!(d(3), Strategy::Mean.apply(&mut ms(&[1, 2, 6])));
+        assert_eq!(d(5), Strategy::Median.apply(&mut ms(&[5])));
+    }
+
+    #[test]
+    fn merge_times() {
+        let group = vec![
+            measurement(4, None),
+            measurement(2, None),
+            measurement(9, None),
+        ];
+        let m = merge(Strategy::Min, group);
+        assert_eq!(None, m.err);
+        assert_eq!(30, m.iters);
+        assert_eq!(Duration::from_secs(3), m.total);
+        assert_eq!(Duration::from_millis(2), m.aggregate.times.median);
+        assert_eq!(Duration::from_millis(1), m.aggregate.times.min);
+        assert_eq!(Duration::from_millis(4), m.aggregate.times.max);
+        // Throughputs are recomputed from the merged timings.
+        let tputs = m.aggregate.tputs.unwrap();
+        assert_eq!(1_000, tputs.len);
+        assert_eq!(
+            crate::util::Throughput::new(1_000, Duration::from_millis(2)),
+            tputs.median,
+        );
+    }
+
+    #[test]
+    fn merge_errors() {
+        // Errors are dropped as long as there's at least one success.
+        let group = vec![measurement(4, Some("oops")), measurement(2, None)];
+        let m = merge(Strategy::Mean, group);
+        assert_eq!(None, m.err);
+        assert_eq!(10, m.iters);
+        assert_eq!(Duration::from_millis(2), m.aggregate.times.median);
+
+        // But when everything failed, the first error is propagated.
+        let group = vec![
+            measurement(4, Some("first")),
+            measurement(2, Some("second")),
+        ];
+        let m = merge(Strategy::Mean, group);
+        assert_eq!(Some("first"), m.err.as_deref());
+    }
+}
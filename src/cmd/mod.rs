@@ -2,9 +2,18 @@ pub mod build;
 pub mod clean;
 pub mod cmp;
 pub mod diff;
+pub mod doctor;
 pub mod haystack;
 pub mod klv;
+pub mod lint;
+pub mod matrix;
 pub mod measure;
+pub mod merge;
+pub mod overhead;
 pub mod rank;
+pub mod record;
 pub mod report;
+pub mod run;
+pub mod summary;
 pub mod version;
+pub mod wrap;
@@ -0,0 +1,288 @@
+use std::{io::Write, path::PathBuf, time::Duration};
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    args::{self, Filter, Filters, Usage},
+    format::measurement::{Measurement, MeasurementReader, VersionCheck},
+    util::{write_divider, ShortHumanDuration},
+};
+
+const USAGES: &[Usage] = &[
+    VersionCheck::USAGE_ALLOW_MISMATCH,
+    Filter::USAGE_ENGINE,
+    Filter::USAGE_ENGINE_NOT,
+    Filter::USAGE_BENCH,
+    Filter::USAGE_BENCH_NOT,
+    Usage::new(
+        "--limit <number>",
+        "Show at most this many benchmarks (default: 20).",
+        r#"
+Show at most this many benchmarks, starting with the ones with the most
+overhead. The default is 20. When set to 0, every benchmark is shown.
+
+The summary line always accounts for every measurement that passes the
+filters, regardless of this limit.
+"#,
+    ),
+    Filter::USAGE_MODEL,
+    Filter::USAGE_MODEL_NOT,
+    VersionCheck::USAGE_REQUIRE,
+];
+
+fn usage_short() -> String {
+    format!(
+        "\
+Print the benchmarks that spent the most time outside of measurement.
+
+USAGE:
+    rebar overhead [OPTIONS] <csv-path> ...
+
+TIP:
+    use -h for short docs and --help for long docs
+
+OPTIONS:
+{options}
+",
+        options = Usage::short(USAGES),
+    )
+    .trim()
+    .to_string()
+}
+
+fn usage_long() -> String {
+    format!(
+        "\
+Print the benchmarks that spent the most time outside of measurement.
+
+Running a benchmark with 'rebar measure' takes more time than just the sum of
+its samples. The runner program has to be spawned, the benchmark has to be
+sent to it, it has to warm up and so on. This command lists the benchmarks
+with the most absolute overhead, which is useful for figuring out where the
+time in a 'rebar measure' run goes.
+
+For each benchmark, the following columns are shown:
+
+    Total     The wall clock time of the entire benchmark.
+    Measured  The time spent in measured iterations.
+    Warmup    The estimated time spent in warmup iterations.
+    Other     Everything else, e.g., process spawning and count verification.
+    Overhead  The fraction of the total time that wasn't measured.
+
+Warmup time isn't reported by runner programs directly. Instead, it's
+estimated as the number of warmup iterations reported by the runner times
+the mean duration of a measured iteration. When a runner doesn't report its
+warmup iterations, the Warmup column shows '-' and warmup time is counted
+as part of Other.
+
+Measurements recorded by older versions of rebar don't include overhead
+accounting and are skipped.
+
+USAGE:
+    rebar overhead [OPTIONS] <csv-path> ...
+
+    This command takes one or more file paths to CSV files written by the
+    'rebar measure' command.
+
+TIP:
+    use -h for short docs and --help for long docs
+
+OPTIONS:
+{options}
+",
+        options = Usage::long(USAGES),
+    )
+    .trim()
+    .to_string()
+}
+
+pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
+    let config = Config::parse(p)?;
+    let (measurements, versions) = MeasurementReader {
+        paths: &config.csv_paths,
+        filters: &config.filters,
+        intersection: false,
+        labels: &[],
+        strict_duplicates: false,
+    }
+    .read_with_versions()?;
+    config.version_check.check(&versions)?;
+
+    let mut rows: Vec<Row> =
+        measurements.iter().filter_map(Row::new).collect();
+    let skipped = measurements.len() - rows.len();
+    if skipped > 0 {
+        log::warn!(
+            "skipped {} measurement(s) without overhead accounting \
+             (either they failed or were recorded by an older rebar)",
+            skipped,
+        );
+    }
+    rows.sort_by_key(|r| std::cmp::Reverse(r.overhead()));
+
+    let mut sum = Row::default();
+    for r in rows.iter() {
+        sum.total += r.total;
+        sum.measured += r.measured;
+        sum.warmup = match (sum.warmup, r.warmup) {
+            (None, None) => None,
+            (w1, w2) => Some(w1.unwrap_or_default() + w2.unwrap_or_default()),
+        };
+    }
+    if config.limit > 0 {
+        rows.truncate(config.limit);
+    }
+
+    let mut wtr = tabwriter::TabWriter::new(std::io::stdout().lock());
+    let columns = [
+        "Benchmark",
+        "Engine",
+        "Total",
+        "Measured",
+        "Warmup",
+        "Other",
+        "Overhead",
+    ];
+    writeln!(wtr, "{}", columns.join("\t"))?;
+    for (i, label) in columns.iter().enumerate() {
+        if i > 0 {
+            write!(wtr, "\t")?;
+        }
+        write_divider(&mut wtr, '-', label.width())?;
+    }
+    writeln!(wtr)?;
+    for r in rows.iter() {
+        writeln!(wtr, "{}\t{}\t{}", r.name, r.engine, r.columns())?;
+    }
+    writeln!(wtr, "[all]\t\t{}", sum.columns())?;
+    wtr.flush()?;
+    Ok(())
+}
+
+/// A single row in the overhead table, derived from a measurement.
+#[derive(Debug, Default)]
+struct Row {
+    name: String,
+    engine: String,
+    /// The wall clock time of the benchmark.
+    total: Duration,
+    /// The time spent in measured iterations.
+    measured: Duration,
+    /// The estimated time spent in warmup iterations, if known.
+    warmup: Option<Duration>,
+}
+
+impl Row {
+    /// Create a new row from the given measurement. If the measurement
+    /// doesn't have any overhead accounting, then this returns `None`.
+    fn new(m: &Measurement) -> Option<Row> {
+        let ratio = m.overhead_ratio?;
+        let overhead = m.total.mul_f64(ratio.clamp(0.0, 1.0));
+        Some(Row {
+            name: m.name.clone(),
+            engine: m.engine.clone(),
+            total: m.total,
+            measured: m.total.saturating_sub(overhead),
+            warmup: m.warmup_estimate,
+        })
+    }
+
+    /// The total time not spent in measured iterations.
+    fn overhead(&self) -> Duration {
+        self.total.saturating_sub(self.measured)
+    }
+
+    /// The time not spent in measured or warmup iterations.
+    fn other(&self) -> Duration {
+        self.overhead().saturating_sub(self.warmup.unwrap_or_default())
+    }
+
+    /// Returns the tab separated duration and ratio columns of this row.
+    fn columns(&self) -> String {
+        let dur = |d: Duration| ShortHumanDuration::from(d).to_string();
+        let ratio = if self.total.is_zero() {
+            0.0
+        } else {
+            self.overhead().as_secs_f64() / self.total.as_secs_f64()
+        };
+        format!(
+            "{}\t{}\t{}\t{}\t{:.1}%",
+            dur(self.total),
+            dur(self.measured),
+            self.warmup.map_or("-".to_string(), dur),
+            dur(self.other()),
+            ratio * 100.0,
+        )
+    }
+}
+
+/// The arguments for this 'overhead' command parsed from CLI args.
+#[derive(Debug)]
+struct Config {
+    /// File paths to CSV files.
+    csv_paths: Vec<PathBuf>,
+    /// The benchmark name, model and regex engine filters.
+    filters: Filters,
+    /// The maximum number of benchmarks to show, or 0 for no limit.
+    limit: usize,
+    /// How to check the rebar versions that recorded the measurements.
+    version_check: VersionCheck,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            csv_paths: vec![],
+            filters: Filters::default(),
+            limit: 20,
+            version_check: VersionCheck::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Parse 'overhead' args from the given CLI parser.
+    fn parse(p: &mut lexopt::Parser) -> anyhow::Result<Config> {
+        use lexopt::Arg;
+
+        let mut c = Config::default();
+        while let Some(arg) = p.next()? {
+            match arg {
+                Arg::Value(v) => c.csv_paths.push(PathBuf::from(v)),
+                Arg::Short('h') => anyhow::bail!("{}", usage_short()),
+                Arg::Long("help") => anyhow::bail!("{}", usage_long()),
+                Arg::Long("allow-version-mismatch") => {
+                    c.version_check.allow_mismatch = true;
+                }
+                Arg::Short('e') | Arg::Long("engine") => {
+                    c.filters.engine.arg_whitelist(p, "-e/--engine")?;
+                }
+                Arg::Short('E') | Arg::Long("engine-not") => {
+                    c.filters.engine.arg_blacklist(p, "-E/--engine-not")?;
+                }
+                Arg::Short('f') | Arg::Long("filter") => {
+                    c.filters.name.arg_whitelist(p, "-f/--filter")?;
+                }
+                Arg::Short('F') | Arg::Long("filter-not") => {
+                    c.filters.name.arg_blacklist(p, "-F/--filter-not")?;
+                }
+                Arg::Long("limit") => {
+                    c.limit = args::parse(p, "--limit")?;
+                }
+                Arg::Short('m') | Arg::Long("model") => {
+                    c.filters.model.arg_whitelist(p, "-m/--model")?;
+                }
+                Arg::Short('M') | Arg::Long("model-not") => {
+                    c.filters.model.arg_blacklist(p, "-M/--model-not")?;
+                }
+                Arg::Long("require-version") => {
+                    c.version_check.require =
+                        Some(args::parse(p, "--require-version")?);
+                }
+                _ => return Err(arg.unexpected().into()),
+            }
+        }
+        anyhow::ensure!(!c.csv_paths.is_empty(), "no CSV file paths given");
+        Ok(c)
+    }
+}
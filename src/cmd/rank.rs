@@ -4,20 +4,114 @@ use unicode_width::UnicodeWidthStr;
 
 use crate::{
     args::{self, Filter, Filters, Stat, Usage},
-    format::measurement::MeasurementReader,
+    format::measurement::{self, MeasurementReader, VersionCheck},
     grouped,
     util::write_divider,
 };
 
 const USAGES: &[Usage] = &[
+    grouped::EngineAliases::USAGE,
+    VersionCheck::USAGE_ALLOW_MISMATCH,
+    Usage::new(
+        "--by-group",
+        "Print a separate ranking for each benchmark group.",
+        r#"
+Print a separate ranking table for each group of benchmarks, instead of one
+ranking across all benchmarks.
+
+The group of a benchmark is everything in its name before the last '/'. By
+default, benchmarks are grouped by the first component of their group. For
+example, 'curated/01-literal/sherlock-en' and 'curated/03-date/ascii' are both
+in the 'curated' group. Use --group-depth to group by longer prefixes.
+
+Each table is preceded by a line naming its group. Regex engines without any
+measurements in a group are omitted from that group's table. Tables are
+printed in the order in which each group first appears in the measurements
+given.
+"#,
+    ),
+    Usage::new(
+        "--by-model",
+        "Print a separate ranking for each benchmark model.",
+        r#"
+Print a separate ranking table for each model of benchmarks, instead of one
+ranking across all benchmarks.
+
+Combining compile and search measurements into one ranking is usually not what
+you want. While '-M compile' excludes compile measurements entirely, this flag
+ranks the regex engines on each model separately. For example, there is one
+table for 'compile', one for 'count', one for 'grep' and so on.
+
+Each table is preceded by a line naming its model. Regex engines without any
+measurements for a model are omitted from that model's table. Tables are
+printed in the order in which each model first appears in the measurements
+given.
+
+This cannot be used with --by-group.
+"#,
+    ),
+    Usage::new(
+        "--confidence",
+        "Show a 95% confidence interval for each geometric mean.",
+        r#"
+Show a 95% confidence interval next to each geometric mean, e.g.,
+'2.13 [1.95, 2.34]'.
+
+The interval is computed with a bootstrap: the speed ratios of each regex
+engine are resampled with replacement 1,000 times, and the 2.5th and 97.5th
+percentiles of the geometric means of those resamples are reported. The
+resampling uses a fixed seed, so the same measurements always produce the
+same intervals.
+
+Regex engines with fewer than 10 benchmarks are marked with †, since their
+intervals tend to be too narrow to be trusted.
+"#,
+    ),
     Filter::USAGE_ENGINE,
     Filter::USAGE_ENGINE_NOT,
+    Filter::USAGE_ENGINE_FILE,
+    Filter::USAGE_ENGINE_NOT_FILE,
     Filter::USAGE_BENCH,
     Filter::USAGE_BENCH_NOT,
+    Filter::USAGE_BENCH_FILE,
+    Filter::USAGE_BENCH_NOT_FILE,
+    Usage::new(
+        "--group-depth <number>",
+        "The number of group components to use with --by-group.",
+        r#"
+The number of components of each benchmark's group to use when computing a
+ranking per group. This implies --by-group.
+
+For example, with a depth of 2, 'curated/01-literal/sherlock-en' is in the
+'curated/01-literal' group, while with the default depth of 1, it is in the
+'curated' group. If a benchmark's group has fewer components than the depth
+given, then its entire group is used.
+"#,
+    ),
     MeasurementReader::USAGE_INTERSECTION,
+    Usage::new(
+        "--min-count <number>",
+        "Omit regex engines with fewer than this many benchmarks.",
+        r#"
+Omit regex engines from a ranking when they have fewer than this many
+benchmarks. A geometric mean over only a few benchmarks can put a regex engine
+absurdly high or low in the ranking, so this makes it possible to exclude
+them.
+
+The regex engines omitted are listed after the ranking along with their
+benchmark counts. They are still used to compute the speed ratios of the other
+regex engines. When used with --by-group or --by-model, the threshold applies
+to each table separately.
+
+By default, no regex engines are omitted.
+"#,
+    ),
     Filter::USAGE_MODEL,
     Filter::USAGE_MODEL_NOT,
+    VersionCheck::USAGE_REQUIRE,
+    MeasurementReader::USAGE_SHOW_ERRORS,
     Stat::USAGE,
+    MeasurementReader::USAGE_STRICT_DUPLICATES,
 ];
 
 fn usage_short() -> String {
@@ -69,7 +163,8 @@ hyperscan have measurements are considered. The '-M compile' flag is also given
 to filter out measurements from the 'compile' model, since combining search
 and compile time measurements into one aggregate is usually not what you want.
 You can use '-m compile' to invert it and compute a ranking restricted only to
-compile time measurements.
+compile time measurements. Or, use --by-model to print a
+separate ranking for each model.
 
 USAGE:
     rebar rank [OPTIONS] <csv-path> ...
@@ -92,41 +187,124 @@ OPTIONS:
 
 pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
     let config = Config::parse(p)?;
-    let measurements = MeasurementReader {
+    let (measurements, errors, versions) = MeasurementReader {
         paths: &config.csv_paths,
         filters: &config.filters,
         intersection: config.intersection,
+        labels: &[],
+        strict_duplicates: config.strict_duplicates,
+    }
+    .read_with_errors()?;
+    measurement::warn_errors(&errors);
+    config.version_check.check(&versions)?;
+    let by_name = grouped::ByBenchmarkName::new(&measurements)?
+        .alias(&config.aliases, config.stat);
+    let mut stdout = std::io::stdout();
+    if config.by_group {
+        let splits = by_name.split_by_group(config.group_depth);
+        write_split_rankings(&mut stdout, "group", splits, &config)?;
+    } else if config.by_model {
+        let splits = by_name.split_by_model();
+        write_split_rankings(&mut stdout, "model", splits, &config)?;
+    } else {
+        write_ranking(&mut stdout, by_name.ranking(config.stat)?, &config)?;
+    }
+    if config.show_errors {
+        writeln!(std::io::stdout())?;
+        measurement::write_error_table(&errors)?;
+    }
+    Ok(())
+}
+
+/// Writes a ranking for each of the given splits, where each ranking is
+/// preceded by a line naming the split. `kind` describes what the splits are,
+/// e.g., `group` or `model`.
+fn write_split_rankings<W: Write>(
+    mut wtr: W,
+    kind: &str,
+    splits: Vec<(String, grouped::ByBenchmarkName<()>)>,
+    config: &Config,
+) -> anyhow::Result<()> {
+    for (i, (key, by_name)) in splits.into_iter().enumerate() {
+        if i > 0 {
+            writeln!(wtr)?;
+        }
+        writeln!(wtr, "{}: {}", kind, key)?;
+        write_ranking(&mut wtr, by_name.ranking(config.stat)?, config)?;
     }
-    .read()?;
-    let by_name = grouped::ByBenchmarkName::new(&measurements)?;
-    let ranking = by_name.ranking(config.stat)?;
+    Ok(())
+}
 
-    let mut wtr = tabwriter::TabWriter::new(std::io::stdout());
+/// Writes the given ranking as a table with aligned columns.
+///
+/// When `config.confidence` is true, each geometric mean is shown along with
+/// its confidence interval. Regex engines with fewer than `config.min_count`
+/// benchmarks are omitted from the table and listed after it instead.
+fn write_ranking<W: Write>(
+    mut wtr: W,
+    ranking: Vec<grouped::EngineSummary>,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let confidence = config.confidence;
+    let (ranking, omitted) = apply_min_count(ranking, config.min_count);
+    let mut twtr = tabwriter::TabWriter::new(&mut wtr);
     let columns = &[
         "Engine",
         "Version",
         "Geometric mean of speed ratios",
         "Benchmark count",
     ];
-    writeln!(wtr, "{}", columns.join("\t"))?;
+    writeln!(twtr, "{}", columns.join("\t"))?;
     for (i, label) in columns.iter().enumerate() {
         if i > 0 {
-            write!(wtr, "\t")?;
+            write!(twtr, "\t")?;
         }
-        write_divider(&mut wtr, '-', label.width())?;
+        write_divider(&mut twtr, '-', label.width())?;
+    }
+    writeln!(twtr)?;
+    for summary in ranking.iter() {
+        writeln!(
+            twtr,
+            "{}\t{}\t{}\t{}",
+            summary.name,
+            summary.version,
+            summary.geomean_display(confidence),
+            summary.count,
+        )?;
     }
-    write!(wtr, "\n")?;
-    for summary in ranking {
+    twtr.flush()?;
+    if confidence && ranking.iter().any(|s| s.is_low_sample()) {
         writeln!(
             wtr,
-            "{}\t{}\t{:.2}\t{}",
-            summary.name, summary.version, summary.geomean, summary.count
+            "{} fewer than {} benchmarks, interval may be unreliable",
+            grouped::EngineSummary::LOW_SAMPLE_MARKER,
+            grouped::EngineSummary::MIN_CONFIDENCE_COUNT,
+        )?;
+    }
+    if !omitted.is_empty() {
+        let list: Vec<String> = omitted
+            .iter()
+            .map(|s| format!("{} ({})", s.name, s.count))
+            .collect();
+        writeln!(
+            wtr,
+            "omitted because of fewer than {} benchmarks: {}",
+            config.min_count,
+            list.join(", "),
         )?;
     }
-    wtr.flush()?;
     Ok(())
 }
 
+/// Splits the given ranking into the engines with at least `min_count`
+/// benchmarks and the engines with fewer. The order of each is preserved.
+fn apply_min_count(
+    ranking: Vec<grouped::EngineSummary>,
+    min_count: usize,
+) -> (Vec<grouped::EngineSummary>, Vec<grouped::EngineSummary>) {
+    ranking.into_iter().partition(|s| s.count >= min_count)
+}
+
 /// The arguments for this 'cmp' command parsed from CLI args.
 #[derive(Debug, Default)]
 struct Config {
@@ -134,10 +312,28 @@ struct Config {
     csv_paths: Vec<PathBuf>,
     /// The benchmark name, model and regex engine filters.
     filters: Filters,
+    /// Aliases for merging regex engines together.
+    aliases: grouped::EngineAliases,
     /// Whether to only consider benchmarks containing all regex engines.
     intersection: bool,
+    /// Whether identical duplicate measurements are an error too.
+    strict_duplicates: bool,
     /// The statistic we want to compare.
     stat: Stat,
+    /// Whether to print a separate ranking for each benchmark group.
+    by_group: bool,
+    /// Whether to print a separate ranking for each benchmark model.
+    by_model: bool,
+    /// Whether to show a confidence interval for each geometric mean.
+    confidence: bool,
+    /// The number of group components to use when ranking by group.
+    group_depth: usize,
+    /// Engines with fewer than this many benchmarks are omitted.
+    min_count: usize,
+    /// Whether to print a table of measurements that failed.
+    show_errors: bool,
+    /// How to check the rebar versions that recorded the measurements.
+    version_check: VersionCheck,
 }
 
 impl Config {
@@ -145,33 +341,81 @@ impl Config {
     fn parse(p: &mut lexopt::Parser) -> anyhow::Result<Config> {
         use lexopt::Arg;
 
-        let mut c = Config::default();
+        let mut c = Config { group_depth: 1, ..Config::default() };
         while let Some(arg) = p.next()? {
             match arg {
                 Arg::Value(v) => c.csv_paths.push(PathBuf::from(v)),
                 Arg::Short('h') => anyhow::bail!("{}", usage_short()),
                 Arg::Long("help") => anyhow::bail!("{}", usage_long()),
+                Arg::Long("alias") => {
+                    c.aliases.arg_add(p, "--alias")?;
+                }
+                Arg::Long("allow-version-mismatch") => {
+                    c.version_check.allow_mismatch = true;
+                }
+                Arg::Long("by-group") => {
+                    c.by_group = true;
+                }
+                Arg::Long("by-model") => {
+                    c.by_model = true;
+                }
+                Arg::Long("confidence") => {
+                    c.confidence = true;
+                }
                 Arg::Short('e') | Arg::Long("engine") => {
                     c.filters.engine.arg_whitelist(p, "-e/--engine")?;
                 }
                 Arg::Short('E') | Arg::Long("engine-not") => {
                     c.filters.engine.arg_blacklist(p, "-E/--engine-not")?;
                 }
+                Arg::Long("engine-file") => {
+                    c.filters.engine.arg_whitelist_file(p, "--engine-file")?;
+                }
+                Arg::Long("engine-not-file") => {
+                    c.filters
+                        .engine
+                        .arg_blacklist_file(p, "--engine-not-file")?;
+                }
                 Arg::Short('f') | Arg::Long("filter") => {
                     c.filters.name.arg_whitelist(p, "-f/--filter")?;
                 }
                 Arg::Short('F') | Arg::Long("filter-not") => {
                     c.filters.name.arg_blacklist(p, "-F/--filter-not")?;
                 }
+                Arg::Long("filter-file") => {
+                    c.filters.name.arg_whitelist_file(p, "--filter-file")?;
+                }
+                Arg::Long("filter-not-file") => {
+                    c.filters
+                        .name
+                        .arg_blacklist_file(p, "--filter-not-file")?;
+                }
+                Arg::Long("group-depth") => {
+                    c.group_depth = args::parse(p, "--group-depth")?;
+                    c.by_group = true;
+                }
                 Arg::Long("intersection") => {
                     c.intersection = true;
                 }
+                Arg::Long("strict-duplicates") => {
+                    c.strict_duplicates = true;
+                }
+                Arg::Long("min-count") => {
+                    c.min_count = args::parse(p, "--min-count")?;
+                }
                 Arg::Short('m') | Arg::Long("model") => {
                     c.filters.model.arg_whitelist(p, "-m/--model")?;
                 }
                 Arg::Short('M') | Arg::Long("model-not") => {
                     c.filters.model.arg_blacklist(p, "-M/--model-not")?;
                 }
+                Arg::Long("require-version") => {
+                    c.version_check.require =
+                        Some(args::parse(p, "--require-version")?);
+                }
+                Arg::Long("show-errors") => {
+                    c.show_errors = true;
+                }
                 Arg::Short('s') | Arg::Long("statistic") => {
                     c.stat = args::parse(p, "-s/--statistic")?;
                 }
@@ -179,6 +423,104 @@ impl Config {
             }
         }
         anyhow::ensure!(!c.csv_paths.is_empty(), "no CSV file paths given");
+        anyhow::ensure!(c.group_depth > 0, "--group-depth must be at least 1");
+        anyhow::ensure!(
+            !(c.by_group && c.by_model),
+            "--by-group (or --group-depth) and --by-model cannot be used \
+             together",
+        );
         Ok(c)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::format::measurement::read_fixture;
+
+    use super::*;
+
+    const MEASUREMENTS: &str = "\
+fixture/compile-a,compile,0.1.0,one,1.0.0,,,5,1s,20ns,0ns,20ns,0ns,20ns,20ns
+fixture/compile-a,compile,0.1.0,two,2.0.0,,,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+fixture/count-a,count,0.1.0,one,1.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+fixture/count-a,count,0.1.0,two,2.0.0,,10,5,1s,40ns,0ns,40ns,0ns,40ns,40ns
+fixture/grep-a,grep,0.1.0,one,1.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+fixture/count-b,count,0.1.0,one,1.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+fixture/count-b,count,0.1.0,two,2.0.0,,10,5,1s,20ns,0ns,20ns,0ns,20ns,20ns
+fixture/count-b,count,0.1.0,three,3.0.0,,10,5,1s,5ns,0ns,5ns,0ns,5ns,5ns
+";
+
+    fn fixture() -> grouped::ByBenchmarkName<()> {
+        let measurements = read_fixture(MEASUREMENTS);
+        grouped::ByBenchmarkName::new(&measurements).unwrap()
+    }
+
+    #[test]
+    fn split_by_model() {
+        let got: Vec<(String, Vec<String>)> = fixture()
+            .split_by_model()
+            .into_iter()
+            .map(|(model, by_name)| {
+                let names = by_name.groups.into_iter().map(|g| g.name);
+                (model, names.collect())
+            })
+            .collect();
+        let s = |x: &str| x.to_string();
+        assert_eq!(
+            vec![
+                (s("compile"), vec![s("fixture/compile-a")]),
+                (s("count"), vec![s("fixture/count-a"), s("fixture/count-b")]),
+                (s("grep"), vec![s("fixture/grep-a")]),
+            ],
+            got,
+        );
+    }
+
+    #[test]
+    fn min_count() {
+        let ranking = fixture().ranking(Stat::Median).unwrap();
+        let (kept, omitted) = apply_min_count(ranking, 3);
+        let names = |xs: &[grouped::EngineSummary]| -> Vec<String> {
+            xs.iter().map(|s| s.name.clone()).collect()
+        };
+        // 'one' is in all 4 benchmarks, 'two' in 3 and 'three' in 1.
+        assert_eq!(vec!["one", "two"], names(&kept));
+        assert_eq!(vec!["three"], names(&omitted));
+
+        let ranking = fixture().ranking(Stat::Median).unwrap();
+        let (kept, omitted) = apply_min_count(ranking, 0);
+        assert_eq!(3, kept.len());
+        assert!(omitted.is_empty());
+    }
+
+    #[test]
+    fn by_model_with_min_count() {
+        let config = Config { min_count: 2, ..Config::default() };
+        let mut out = vec![];
+        let splits = fixture().split_by_model();
+        write_split_rankings(&mut out, "model", splits, &config).unwrap();
+        let got = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = got.lines().collect();
+        // Each model gets its own table, and counts are per table. So in the
+        // 'count' table, 'two' has 2 benchmarks and is kept, while 'three'
+        // only has 1. Nobody has 2 benchmarks in the other tables.
+        assert_eq!("model: compile", lines[0]);
+        assert_eq!(
+            "omitted because of fewer than 2 benchmarks: two (1), one (1)",
+            lines[3],
+        );
+        assert_eq!("model: count", lines[5]);
+        assert!(lines[8].starts_with("one "), "{}", lines[8]);
+        assert!(lines[9].starts_with("two "), "{}", lines[9]);
+        assert_eq!(
+            "omitted because of fewer than 2 benchmarks: three (1)",
+            lines[10],
+        );
+        assert_eq!("model: grep", lines[12]);
+        assert_eq!(
+            "omitted because of fewer than 2 benchmarks: one (1)",
+            lines[15],
+        );
+        assert_eq!(16, lines.len());
+    }
+}
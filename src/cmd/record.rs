@@ -0,0 +1,477 @@
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Component, Path, PathBuf},
+    process::Stdio,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use {
+    anyhow::Context,
+    bstr::ByteSlice,
+    lexopt::{Arg, ValueExt},
+};
+
+use crate::{
+    args::{Filter, Filters, Usage},
+    format::measurement::Measurement,
+    util,
+};
+
+const USAGES: &[Usage] = &[
+    Usage::new(
+        "--date <YYYY-MM-DD>",
+        "Use this date instead of today's date.",
+        r#"
+The date to use for the name of the directory that measurements are written to.
+By default, today's date (in UTC) is used.
+"#,
+    ),
+    Usage::BENCH_DIR,
+    Filter::USAGE_ENGINE,
+    Filter::USAGE_ENGINE_NOT,
+    Filter::USAGE_BENCH,
+    Filter::USAGE_BENCH_NOT,
+    Usage::new(
+        "--force",
+        "Replace the dated directory if it already exists.",
+        r#"
+Replace the dated directory if it already exists.
+
+By default, this command refuses to do anything if the directory it would write
+measurements to already exists. When this flag is given, the existing
+directory and everything in it is removed first.
+"#,
+    ),
+    Usage::new(
+        "--git-revision",
+        "Record the git revision of the current checkout.",
+        r#"
+Write the git revision of the current checkout (as reported by
+'git rev-parse HEAD') to a file named 'REVISION' in the dated directory. If the
+checkout has uncommitted changes, then '-dirty' is appended to the revision.
+"#,
+    ),
+    Filter::USAGE_MODEL,
+    Filter::USAGE_MODEL_NOT,
+    Usage::new(
+        "--record-dir <dir>",
+        "The directory containing recorded measurements.",
+        r#"
+The directory containing recorded measurements. This must be a relative path
+from the root of the rebar repository, since it's used to compute the relative
+path to the repo root for hyperlinks in the generated README.
+
+The default is 'record'.
+"#,
+    ),
+    Usage::new(
+        "--tag <name>",
+        "The name of the set of measurements being recorded.",
+        r#"
+The name of the set of measurements being recorded. Measurements are written
+to '{record-dir}/{tag}/{date}/'. For example, the 'all' tag is used for
+measurements of every benchmark and the 'curated' tag is used for measurements
+of just the curated benchmarks.
+
+The default is 'all'.
+"#,
+    ),
+];
+
+fn usage_short() -> String {
+    format!(
+        "\
+Record measurements into rebar's 'record' directory.
+
+USAGE:
+    rebar record [OPTIONS]
+    rebar record [OPTIONS] <csv-path> ...
+
+TIP:
+    use -h for short docs and --help for long docs
+
+OPTIONS:
+{options}
+",
+        options = Usage::short(USAGES),
+    )
+    .trim()
+    .to_string()
+}
+
+fn usage_long() -> String {
+    format!(
+        "\
+Record measurements into rebar's 'record' directory.
+
+When no CSV files are given, this runs 'rebar measure' with the benchmark,
+model and regex engine filters given and records the measurements it produces.
+Use 'rebar measure' directly and pass the resulting CSV files to this command
+if you need other options, like --max-time.
+
+Measurements are written to '{{record-dir}}/{{tag}}/{{date}}/', with one CSV file
+for each regex engine. Each file is named after its regex engine, with every
+'/' replaced by '-'. A 'README.md' containing a report of the measurements is
+generated by 'rebar report', with the relative path to the repo root computed
+from the depth of the directory. If 'lscpu' is available, then its output is
+written to 'lscpu.txt' too.
+
+This command must be run from the root of the rebar repository.
+
+USAGE:
+    rebar record [OPTIONS]
+    rebar record [OPTIONS] <csv-path> ...
+
+OPTIONS:
+{options}
+",
+        options = Usage::long(USAGES),
+    )
+    .trim()
+    .to_string()
+}
+
+pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
+    let c = Config::parse(p)?;
+    let outdir = c.record_dir.join(&c.tag).join(&c.date);
+    let root = relative_path_to_root(&outdir)?;
+    if outdir.exists() {
+        anyhow::ensure!(
+            c.force,
+            "{} already exists (use --force to replace it)",
+            outdir.display(),
+        );
+    }
+
+    // We read everything into memory before touching the output directory,
+    // since the CSV files given might be in the directory we're replacing.
+    let csvs = if c.csv_paths.is_empty() {
+        vec![c.measure()?]
+    } else {
+        let mut csvs = vec![];
+        for path in c.csv_paths.iter() {
+            let data = std::fs::read(path).with_context(|| {
+                format!("failed to read {}", path.display())
+            })?;
+            csvs.push(data);
+        }
+        csvs
+    };
+    let by_engine = split_by_engine(&csvs, &c.filters)?;
+    anyhow::ensure!(!by_engine.is_empty(), "no measurements to record");
+
+    if outdir.exists() {
+        std::fs::remove_dir_all(&outdir).with_context(|| {
+            format!("failed to remove {}", outdir.display())
+        })?;
+    }
+    std::fs::create_dir_all(&outdir)
+        .with_context(|| format!("failed to create {}", outdir.display()))?;
+    let mut out = std::io::stdout().lock();
+    let mut paths = vec![];
+    for (engine, data) in by_engine.iter() {
+        let path = outdir.join(format!("{}.csv", engine.replace('/', "-")));
+        std::fs::write(&path, data)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        writeln!(out, "wrote {}", path.display())?;
+        paths.push(path);
+    }
+
+    let readme = outdir.join("README.md");
+    let report = c.report(&root, &paths)?;
+    std::fs::write(&readme, report)
+        .with_context(|| format!("failed to write {}", readme.display()))?;
+    writeln!(out, "wrote {}", readme.display())?;
+
+    if c.git_revision {
+        let path = outdir.join("REVISION");
+        std::fs::write(&path, format!("{}\n", git_revision()?))
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        writeln!(out, "wrote {}", path.display())?;
+    }
+    // This is probably a Linux-only thing, so we don't fail if it doesn't
+    // work.
+    match util::output(&mut std::process::Command::new("lscpu")) {
+        Ok(lscpu) => {
+            let path = outdir.join("lscpu.txt");
+            std::fs::write(&path, lscpu).with_context(|| {
+                format!("failed to write {}", path.display())
+            })?;
+            writeln!(out, "wrote {}", path.display())?;
+        }
+        Err(err) => log::debug!("not recording CPU info: {:#}", err),
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+struct Config {
+    /// File paths to CSV files. When empty, measurements are collected by
+    /// running 'rebar measure'.
+    csv_paths: Vec<PathBuf>,
+    /// The directory to find benchmark definitions and haystacks.
+    dir: PathBuf,
+    /// The benchmark name, model and regex engine filters.
+    filters: Filters,
+    /// The filter flags given, in the order given, so that they can be
+    /// passed along to 'rebar measure'.
+    filter_args: Vec<String>,
+    /// The directory containing recorded measurements, relative to the root
+    /// of the repository.
+    record_dir: PathBuf,
+    /// The name of the set of measurements being recorded.
+    tag: String,
+    /// The date to use for the directory name, in YYYY-MM-DD format.
+    date: String,
+    /// Whether to replace an existing dated directory.
+    force: bool,
+    /// Whether to write the current git revision to a REVISION file.
+    git_revision: bool,
+}
+
+impl Config {
+    fn parse(p: &mut lexopt::Parser) -> anyhow::Result<Config> {
+        let mut c = Config {
+            csv_paths: vec![],
+            dir: PathBuf::from("benchmarks"),
+            filters: Filters::default(),
+            filter_args: vec![],
+            record_dir: PathBuf::from("record"),
+            tag: "all".to_string(),
+            date: today(),
+            force: false,
+            git_revision: false,
+        };
+        while let Some(arg) = p.next()? {
+            match arg {
+                Arg::Value(v) => c.csv_paths.push(PathBuf::from(v)),
+                Arg::Short('h') => anyhow::bail!("{}", usage_short()),
+                Arg::Long("help") => anyhow::bail!("{}", usage_long()),
+                Arg::Long("date") => {
+                    let date = p.value().context("--date")?;
+                    c.date = date.string().context("--date")?;
+                    anyhow::ensure!(
+                        regex!(r"^[0-9]{4}-[0-9]{2}-[0-9]{2}$")
+                            .is_match(&c.date),
+                        "--date must be in YYYY-MM-DD format, but got '{}'",
+                        c.date,
+                    );
+                }
+                Arg::Short('d') | Arg::Long("dir") => {
+                    c.dir = PathBuf::from(p.value().context("-d/--dir")?);
+                }
+                Arg::Short('e') | Arg::Long("engine") => {
+                    let pat = c.filter_arg(p, "--engine")?;
+                    c.filters.engine.whitelist(&pat).context("-e/--engine")?;
+                }
+                Arg::Short('E') | Arg::Long("engine-not") => {
+                    let pat = c.filter_arg(p, "--engine-not")?;
+                    c.filters
+                        .engine
+                        .blacklist(&pat)
+                        .context("-E/--engine-not")?;
+                }
+                Arg::Short('f') | Arg::Long("filter") => {
+                    let pat = c.filter_arg(p, "--filter")?;
+                    c.filters.name.whitelist(&pat).context("-f/--filter")?;
+                }
+                Arg::Short('F') | Arg::Long("filter-not") => {
+                    let pat = c.filter_arg(p, "--filter-not")?;
+                    c.filters
+                        .name
+                        .blacklist(&pat)
+                        .context("-F/--filter-not")?;
+                }
+                Arg::Long("force") => {
+                    c.force = true;
+                }
+                Arg::Long("git-revision") => {
+                    c.git_revision = true;
+                }
+                Arg::Short('m') | Arg::Long("model") => {
+                    let pat = c.filter_arg(p, "--model")?;
+                    c.filters.model.whitelist(&pat).context("-m/--model")?;
+                }
+                Arg::Short('M') | Arg::Long("model-not") => {
+                    let pat = c.filter_arg(p, "--model-not")?;
+                    c.filters
+                        .model
+                        .blacklist(&pat)
+                        .context("-M/--model-not")?;
+                }
+                Arg::Long("record-dir") => {
+                    let dir = p.value().context("--record-dir")?;
+                    c.record_dir = PathBuf::from(dir);
+                }
+                Arg::Long("tag") => {
+                    let tag = p.value().context("--tag")?;
+                    c.tag = tag.string().context("--tag")?;
+                    anyhow::ensure!(
+                        !c.tag.is_empty() && !c.tag.contains(['/', '\\']),
+                        "--tag must be a non-empty name without any path \
+                         separators, but got '{}'",
+                        c.tag,
+                    );
+                }
+                _ => return Err(arg.unexpected().into()),
+            }
+        }
+        Ok(c)
+    }
+
+    /// Reads the value of a filter flag and remembers it so that it can be
+    /// passed along to 'rebar measure'. The long flag name should be given.
+    fn filter_arg(
+        &mut self,
+        p: &mut lexopt::Parser,
+        long: &'static str,
+    ) -> anyhow::Result<String> {
+        let pat = p.value().context(long)?.string().context(long)?;
+        self.filter_args.push(long.to_string());
+        self.filter_args.push(pat.clone());
+        Ok(pat)
+    }
+
+    /// Runs 'rebar measure' with the filters given and returns its CSV
+    /// output. Its stderr is passed through, so that warnings and errors are
+    /// visible as they happen.
+    fn measure(&self) -> anyhow::Result<Vec<u8>> {
+        let mut cmd = std::process::Command::new(util::current_exe()?);
+        cmd.arg("measure").arg("--dir").arg(&self.dir);
+        cmd.args(&self.filter_args);
+        cmd.stderr(Stdio::inherit());
+        log::debug!("running command: {:?}", cmd);
+        let out = cmd.output().context("failed to run 'rebar measure'")?;
+        anyhow::ensure!(
+            out.status.success(),
+            "'rebar measure' failed with {:?}",
+            out.status,
+        );
+        Ok(out.stdout)
+    }
+
+    /// Runs 'rebar report' on the given CSV files and returns the report.
+    ///
+    /// The options used are the same as the ones used for the reports that
+    /// were recorded before this command existed.
+    fn report(
+        &self,
+        root: &str,
+        csv_paths: &[PathBuf],
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut cmd = std::process::Command::new(util::current_exe()?);
+        cmd.arg("report")
+            .arg("--dir")
+            .arg(&self.dir)
+            .args(["--statistic", "median"])
+            .args(["--units", "throughput"])
+            // Summaries aren't really meaningful across all benchmarks, so
+            // they're excluded to avoid giving a misleading perspective.
+            .args(["--summary-exclude", ".*"])
+            .args(["--relative-path-to-repo-root", root])
+            .args(csv_paths);
+        let out = util::output(&mut cmd).context("'rebar report' failed")?;
+        Ok(out.into())
+    }
+}
+
+/// Splits the measurements in the given CSV data by regex engine, and
+/// returns new CSV data for each regex engine.
+///
+/// Measurements that don't pass the given filters are dropped. Measurements
+/// containing errors are kept, since they're a useful record of what failed.
+/// Since every measurement is parsed and written back out, CSV files written
+/// by older versions of rebar are converted to the current format.
+fn split_by_engine(
+    csvs: &[Vec<u8>],
+    filters: &Filters,
+) -> anyhow::Result<BTreeMap<String, Vec<u8>>> {
+    let mut by_engine: BTreeMap<String, csv::Writer<Vec<u8>>> =
+        BTreeMap::new();
+    for data in csvs.iter() {
+        let mut rdr = csv::Reader::from_reader(data.as_slice());
+        for result in rdr.deserialize() {
+            let m: Measurement =
+                result.context("failed to read measurement")?;
+            if !filters.include(&m) {
+                continue;
+            }
+            by_engine
+                .entry(m.engine.clone())
+                .or_insert_with(|| csv::Writer::from_writer(vec![]))
+                .serialize(m)?;
+        }
+    }
+    let mut csvs = BTreeMap::new();
+    for (engine, wtr) in by_engine {
+        csvs.insert(engine, wtr.into_inner()?);
+    }
+    Ok(csvs)
+}
+
+/// Returns the relative path from the given directory back to the directory
+/// it's relative to, e.g., '../../../' for 'record/all/2023-05-04'.
+///
+/// The directory given must be relative and must not contain any '..'
+/// components, since the depth can't be determined otherwise.
+fn relative_path_to_root(dir: &Path) -> anyhow::Result<String> {
+    let mut root = String::new();
+    for component in dir.components() {
+        match component {
+            Component::CurDir => {}
+            Component::Normal(_) => root.push_str("../"),
+            _ => anyhow::bail!(
+                "record directory {} must be a relative path without any \
+                 '..' components",
+                dir.display(),
+            ),
+        }
+    }
+    Ok(root)
+}
+
+/// Returns the git revision of the current checkout, with '-dirty' appended
+/// if there are uncommitted changes.
+fn git_revision() -> anyhow::Result<String> {
+    let rev = util::output(
+        std::process::Command::new("git").args(["rev-parse", "HEAD"]),
+    )
+    .context("failed to get git revision")?;
+    let status = util::output(
+        std::process::Command::new("git").args(["status", "--porcelain"]),
+    )
+    .context("failed to get git status")?;
+    let mut rev = rev.to_str()?.trim().to_string();
+    if !status.is_empty() {
+        rev.push_str("-dirty");
+    }
+    Ok(rev)
+}
+
+/// Returns today's date (in UTC) in YYYY-MM-DD format.
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (year, month, day) = civil_from_days(secs / 86_400);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Converts the number of days since the Unix epoch to a (year, month, day)
+/// triple in the proleptic Gregorian calendar.
+///
+/// This is Howard Hinnant's 'civil_from_days' algorithm, specialized to dates
+/// after the epoch: https://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
@@ -2,6 +2,7 @@ use std::{
     collections::{BTreeMap, BTreeSet},
     io::Write,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use {anyhow::Context, bstr::ByteSlice, lexopt::ValueExt, regex_lite::Regex};
@@ -9,22 +10,97 @@ use {anyhow::Context, bstr::ByteSlice, lexopt::ValueExt, regex_lite::Regex};
 use crate::{
     args::{self, Filter, Filters, Stat, Units, Usage},
     format::{
-        benchmarks::{Benchmarks, Definition, Engines},
-        measurement::{Measurement, MeasurementReader},
+        benchmarks::{
+            Benchmarks, Definition, Engines, LoadOptions, VersionCache,
+        },
+        measurement::{
+            self, Measurement, MeasurementReader, RebarVersions, StaleCheck,
+            VersionCheck,
+        },
     },
-    grouped::{ByBenchmarkName, ByBenchmarkNameGroup, EngineSummary},
-    util::{self, ShortHumanDuration},
+    grouped::{
+        ByBenchmarkName, ByBenchmarkNameGroup, EngineAliases, EngineSummary,
+    },
+    util::{self, Rate, ShortHumanDuration, Throughput},
 };
 
 const USAGES: &[Usage] = &[
     Usage::BENCH_DIR,
+    EngineAliases::USAGE,
+    Usage::ALLOW_DOWNLOAD,
+    Usage::new(
+        "--baseline <path>",
+        "Annotate results with their change versus a previous run.",
+        r#"
+Annotate results with their change versus a previous run.
+
+The path given is either a CSV file written by 'rebar measure' or a directory,
+in which case every CSV file in that directory is read. For example,
+'record/all/2023-05-04/'. The same filters given to this command are applied
+to the baseline measurements.
+
+Each result is matched with the baseline measurement for the same benchmark
+and regex engine, and is annotated with the percentage change in the
+statistic and units selected, e.g., '5.2 GB/s (+12%)'. Results that got worse
+by more than --trend-threshold are also marked with ⚠. Results without a
+matching baseline measurement aren't annotated.
+
+The summary tables also gain a column with the percentage change of each
+regex engine's geometric mean of speed ratios versus the baseline. The
+baseline geometric means are computed only from baseline measurements whose
+benchmark and regex engine also appear in this report. Since a smaller
+geometric mean is better, a positive change is a regression there.
+
+A warning is printed if the baseline was recorded by a different version of
+rebar, but this is never an error.
+"#,
+    ),
+    Usage::new(
+        "--confidence",
+        "Show confidence intervals in the summary tables.",
+        r#"
+Show a 95% confidence interval next to each geometric mean in the summary
+tables, e.g., '2.13 [1.95, 2.34]'. See 'rebar rank --help' for how the
+interval is computed.
+
+Regex engines with fewer than 10 benchmarks are marked with †, since their
+intervals tend to be too narrow to be trusted.
+"#,
+    ),
     Filter::USAGE_ENGINE,
     Filter::USAGE_ENGINE_NOT,
+    Filter::USAGE_ENGINE_FILE,
+    Filter::USAGE_ENGINE_NOT_FILE,
     Filter::USAGE_BENCH,
     Filter::USAGE_BENCH_NOT,
+    Filter::USAGE_BENCH_FILE,
+    Filter::USAGE_BENCH_NOT_FILE,
+    Usage::new(
+        "--format <format>",
+        "One of: markdown, html.",
+        r#"
+The format to print the report in (default: markdown).
+
+The 'markdown' format is meant to be committed to a repository and rendered
+by GitHub. It can be spliced into an existing Markdown file with --splice.
+
+The 'html' format is a single self-contained HTML file with the same
+structure: the summary tables, a list of links to each benchmark group, the
+results of each group and the parameters of each benchmark. It doesn't load
+any external assets, so it can be served by any static web server or opened
+directly in a browser. The header of each table sticks to the top of the
+window while scrolling, and clicking a column header sorts the table by that
+column. Sorting a results column sorts by speed ratio. The analysis text of
+benchmark definitions is shown as plain text.
+
+The 'html' format can't be used with --splice.
+"#,
+    ),
     MeasurementReader::USAGE_INTERSECTION,
     Filter::USAGE_MODEL,
     Filter::USAGE_MODEL_NOT,
+    Usage::NO_CACHE,
+    Usage::NO_VERSION_CACHE,
     Usage::new(
         "--ratio",
         "Show ratios next to timings.",
@@ -48,9 +124,48 @@ A non-empty path should end with a `/`.
 
 For a report in, for example, record/all/2023-04-11/README.md, the relative
 path to the repo root should be `../../../`.
+"#,
+    ),
+    MeasurementReader::USAGE_SHOW_ERRORS,
+    StaleCheck::USAGE_SKIP,
+    Usage::new(
+        "--splice <path>",
+        "Splice the report into an existing Markdown file.",
+        r#"
+Instead of printing the report to stdout, splice it into the Markdown file
+at the path given. This replaces every line between
+
+    <!-- BEGIN: report -->
+
+and
+
+    <!-- END: report -->
+
+with the report. Use --splice-region to replace a named region instead.
+"#,
+    ),
+    Usage::new(
+        "--splice-region <name>",
+        "Splice the report into a named region. Requires --splice.",
+        r#"
+Splice the report into the named region of the file given to --splice,
+instead of the unnamed region. A region named 'search' is delimited by
+
+    <!-- BEGIN: report(search) -->
+
+and
+
+    <!-- END: report(search) -->
+
+This makes it possible to keep multiple reports (e.g., one for search
+benchmarks and one for compile benchmarks) in the same file. Other regions in
+the file are left untouched, but every region is checked for well-formedness.
+That is, regions can't be nested, every BEGIN marker must have a matching END
+marker and the same region can't appear more than once.
 "#,
     ),
     Stat::USAGE,
+    MeasurementReader::USAGE_STRICT_DUPLICATES,
     Usage::new(
         "--summary-exclude",
         "A pattern for excluding engines from the summary table.",
@@ -65,15 +180,28 @@ the overall ranking in a way that makes it very confusing to interpret.
 Note that this doesn't impact the geometric means computed for other regex
 engines. For example, if an excluded regex engine did the best in a benchmark,
 then other engines in that benchmark will have a speed ratio above 1.
+"#,
+    ),
+    Usage::new(
+        "--trend-threshold <percent>",
+        "Mark regressions bigger than this versus --baseline (default: 5).",
+        r#"
+Mark results that regressed by more than this percentage versus the
+measurements given to --baseline. The default is 5.
+
+Whether a change is a regression depends on the units selected. For example,
+a time that went up is a regression, but so is a throughput that went down.
+This has no effect without --baseline.
 "#,
     ),
     Units::USAGE,
+    Usage::VERSION_CACHE_TTL,
 ];
 
 fn usage_short() -> String {
     format!(
         "\
-Print a Markdown formatted report of results for a group of benchmarks.
+Print a Markdown or HTML report of results for a group of benchmarks.
 
 USAGE:
     rebar report [options] <csv-path> ...
@@ -93,7 +221,7 @@ OPTIONS:
 fn usage_long() -> String {
     format!(
         "\
-Print a Markdown formatted report of results for a group of benchmarks.
+Print a Markdown or HTML report of results for a group of benchmarks.
 
 The primary input for this command is one or more CSV files that were generated
 by the 'rebar measure' command. There must not be any duplicate benchmarks
@@ -108,7 +236,8 @@ and
 
     <!-- END: report -->
 
-and then replacing them with the lines making up the report.
+and then replacing them with the lines making up the report. A single file can
+contain multiple named regions, which are selected with --splice-region.
 
 By default, this command will generate information about every benchmark
 represented in the results given. Filters can be used to select only a subset
@@ -137,22 +266,43 @@ OPTIONS:
 
 pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
     let config = Config::parse(p)?;
-    let measurements = MeasurementReader {
+    let (measurements, errors, versions) = MeasurementReader {
         paths: &config.csv_paths,
         filters: &config.filters,
         intersection: config.intersection,
+        labels: &[],
+        strict_duplicates: config.strict_duplicates,
     }
-    .read()?;
-    let benchmarks = config.read_benchmarks(&measurements)?;
-    let engines = benchmarks.engines.clone();
-    let analysis = benchmarks.analysis.clone();
-    let grouped =
-        ByBenchmarkName::new(&measurements)?.associate(benchmarks.defs)?;
-    let tree = Tree::new(grouped.clone());
+    .read_with_errors()?;
+    measurement::warn_errors(&errors);
+    let errors = config.alias_errors(errors);
+    let mut benchmarks = config.read_benchmarks(&measurements)?;
+    let hashes = config.stale_check.hashes(&benchmarks, &measurements);
+    let measurements: Vec<Measurement> = measurements
+        .into_iter()
+        .filter(|m| config.stale_check.keep(&hashes, m))
+        .collect();
+    let defs = std::mem::take(&mut benchmarks.defs);
+    let grouped = ByBenchmarkName::new(&measurements)?
+        .associate(defs)?
+        .alias(&config.aliases, config.stat);
+    let baseline = config.read_baseline(&grouped)?;
     let mut out = vec![];
-    markdown(&config, &engines, grouped, &analysis, &tree, &mut out)?;
+    let write = match config.format {
+        Format::Markdown => markdown::<&mut Vec<u8>>,
+        Format::Html => html::<&mut Vec<u8>>,
+    };
+    write(
+        &config,
+        &benchmarks,
+        grouped,
+        &errors,
+        &versions,
+        baseline.as_ref(),
+        &mut out,
+    )?;
     if let Some(ref path) = config.splice {
-        splice(path, &out)?;
+        splice(path, config.splice_region.as_deref(), &out)?;
     } else {
         std::io::stdout().write_all(&out)?;
     }
@@ -166,22 +316,54 @@ struct Config {
     csv_paths: Vec<PathBuf>,
     /// The directory to find benchmark definitions and haystacks.
     dir: PathBuf,
+    /// The format to print the report in.
+    format: Format,
     /// A Markdown file to splice the report into.
     splice: Option<PathBuf>,
+    /// The name of the region in the splice file to replace. When absent,
+    /// the unnamed region is replaced.
+    splice_region: Option<String>,
     /// The benchmark name, model and regex engine filters.
     filters: Filters,
+    /// Aliases for merging regex engines together.
+    aliases: EngineAliases,
     /// Whether to only consider benchmarks containing all regex engines.
     intersection: bool,
+    /// Whether identical duplicate measurements are an error too.
+    strict_duplicates: bool,
     /// The statistic we want to compare.
     stat: Stat,
+    /// Whether to show a confidence interval for each geometric mean in the
+    /// summary tables.
+    confidence: bool,
     /// A pattern for excluding regex engines from the summary table.
     summary_exclude: Option<Regex>,
     /// The statistical units we want to use in our comparisons.
     units: Units,
     /// Whether to show ratios with timings.
     ratio: bool,
+    /// Whether to add a table of measurements that failed.
+    show_errors: bool,
     /// Relative path to the repository root.
     relative_path_root: String,
+    /// When enabled, don't use the on-disk cache of haystacks and regexes.
+    no_cache: bool,
+    /// When enabled, don't use the on-disk cache of regex engine versions.
+    no_version_cache: bool,
+    /// How long a cached regex engine version is valid. When absent, the
+    /// default TTL is used.
+    version_cache_ttl: Option<Duration>,
+    /// When enabled, haystacks defined by a URL may be downloaded.
+    allow_download: bool,
+    /// How to handle measurements recorded with a different benchmark
+    /// definition.
+    stale_check: StaleCheck,
+    /// File paths to CSV files containing baseline measurements to compare
+    /// against. When empty, results aren't annotated.
+    baseline_paths: Vec<PathBuf>,
+    /// The percentage change versus the baseline beyond which a result is
+    /// marked as a regression.
+    trend_threshold: f64,
 }
 
 impl Config {
@@ -189,14 +371,30 @@ impl Config {
     fn parse(p: &mut lexopt::Parser) -> anyhow::Result<Config> {
         use lexopt::Arg;
 
-        let mut c = Config::default();
-        c.dir = PathBuf::from("benchmarks");
+        let mut c = Config {
+            dir: PathBuf::from("benchmarks"),
+            trend_threshold: 5.0,
+            ..Config::default()
+        };
         c.filters.ignore_missing_engines = true;
         while let Some(arg) = p.next()? {
             match arg {
                 Arg::Value(v) => c.csv_paths.push(PathBuf::from(v)),
                 Arg::Short('h') => anyhow::bail!("{}", usage_short()),
                 Arg::Long("help") => anyhow::bail!("{}", usage_long()),
+                Arg::Long("alias") => {
+                    c.aliases.arg_add(p, "--alias")?;
+                }
+                Arg::Long("baseline") => {
+                    let path = PathBuf::from(p.value().context("--baseline")?);
+                    c.baseline_paths =
+                        baseline_paths(&path).with_context(|| {
+                            format!("--baseline {}", path.display())
+                        })?;
+                }
+                Arg::Long("confidence") => {
+                    c.confidence = true;
+                }
                 Arg::Short('d') | Arg::Long("dir") => {
                     c.dir = PathBuf::from(p.value().context("-d/--dir")?);
                 }
@@ -206,21 +404,59 @@ impl Config {
                 Arg::Short('E') | Arg::Long("engine-not") => {
                     c.filters.engine.arg_blacklist(p, "-E/--engine-not")?;
                 }
+                Arg::Long("engine-file") => {
+                    c.filters.engine.arg_whitelist_file(p, "--engine-file")?;
+                }
+                Arg::Long("engine-not-file") => {
+                    c.filters
+                        .engine
+                        .arg_blacklist_file(p, "--engine-not-file")?;
+                }
                 Arg::Short('f') | Arg::Long("filter") => {
                     c.filters.name.arg_whitelist(p, "-f/--filter")?;
                 }
                 Arg::Short('F') | Arg::Long("filter-not") => {
                     c.filters.name.arg_blacklist(p, "-F/--filter-not")?;
                 }
+                Arg::Long("filter-file") => {
+                    c.filters.name.arg_whitelist_file(p, "--filter-file")?;
+                }
+                Arg::Long("filter-not-file") => {
+                    c.filters
+                        .name
+                        .arg_blacklist_file(p, "--filter-not-file")?;
+                }
+                Arg::Long("format") => {
+                    c.format = args::parse(p, "--format")?;
+                }
                 Arg::Long("intersection") => {
                     c.intersection = true;
                 }
+                Arg::Long("strict-duplicates") => {
+                    c.strict_duplicates = true;
+                }
                 Arg::Short('m') | Arg::Long("model") => {
                     c.filters.model.arg_whitelist(p, "-m/--model")?;
                 }
                 Arg::Short('M') | Arg::Long("model-not") => {
                     c.filters.model.arg_blacklist(p, "-M/--model-not")?;
                 }
+                Arg::Long("no-cache") => {
+                    c.no_cache = true;
+                }
+                Arg::Long("no-version-cache") => {
+                    c.no_version_cache = true;
+                }
+                Arg::Long("version-cache-ttl") => {
+                    let hdur = args::parse::<ShortHumanDuration>(
+                        p,
+                        "--version-cache-ttl",
+                    )?;
+                    c.version_cache_ttl = Some(Duration::from(hdur));
+                }
+                Arg::Long("allow-download") => {
+                    c.allow_download = true;
+                }
                 Arg::Long("ratio") => {
                     c.ratio = true;
                 }
@@ -235,6 +471,22 @@ impl Config {
                     c.splice =
                         Some(PathBuf::from(p.value().context("--splice")?));
                 }
+                Arg::Long("splice-region") => {
+                    let value = p.value().context("--splice-region")?;
+                    let name = value.string().context("--splice-region")?;
+                    anyhow::ensure!(
+                        !name.is_empty() && !name.contains(')'),
+                        "--splice-region must be non-empty and must not \
+                         contain ')'",
+                    );
+                    c.splice_region = Some(name);
+                }
+                Arg::Long("show-errors") => {
+                    c.show_errors = true;
+                }
+                Arg::Long("skip-stale") => {
+                    c.stale_check.skip = true;
+                }
                 Arg::Short('s') | Arg::Long("statistic") => {
                     c.stat = args::parse(p, "-s/--statistic")?;
                 }
@@ -244,6 +496,13 @@ impl Config {
                     let re = Regex::new(&pat).context("--summary-exclude")?;
                     c.summary_exclude = Some(re);
                 }
+                Arg::Long("trend-threshold") => {
+                    c.trend_threshold = args::parse(p, "--trend-threshold")?;
+                    anyhow::ensure!(
+                        c.trend_threshold >= 0.0,
+                        "--trend-threshold must not be negative",
+                    );
+                }
                 Arg::Short('u') | Arg::Long("units") => {
                     c.units = args::parse(p, "-u/--units")?;
                 }
@@ -251,6 +510,14 @@ impl Config {
             }
         }
         anyhow::ensure!(!c.csv_paths.is_empty(), "no CSV file paths given");
+        anyhow::ensure!(
+            c.splice_region.is_none() || c.splice.is_some(),
+            "--splice-region requires --splice",
+        );
+        anyhow::ensure!(
+            c.splice.is_none() || c.format == Format::Markdown,
+            "--splice can only be used with '--format markdown'",
+        );
         Ok(c)
     }
 
@@ -263,31 +530,16 @@ impl Config {
         &self,
         measurements: &[Measurement],
     ) -> anyhow::Result<Benchmarks> {
-        let mut engine_names: Vec<String> = measurements
-            .iter()
-            .map(|m| regex_lite::escape(&m.engine))
-            .collect();
-        engine_names.sort();
-        engine_names.dedup();
-        let pat = format!("^(?:{})$", engine_names.join("|"));
-        let engine_filter = Filter::from_pattern(&pat)
-            .context("failed to build filter for engine names")?;
-
-        let bench_names: Vec<String> =
-            measurements.iter().map(|m| regex_lite::escape(&m.name)).collect();
-        let pat = format!("^(?:{})$", bench_names.join("|"));
-        let bench_filter = Filter::from_pattern(&pat)
-            .context("failed to build filter for benchmark names")?;
-
-        let mut benchmarks = Benchmarks::from_dir(
-            &self.dir,
-            &Filters {
-                name: bench_filter,
-                engine: engine_filter,
-                model: Filter::default(),
-                ignore_missing_engines: true,
-            },
-        )?;
+        let load = LoadOptions {
+            cache: !self.no_cache,
+            allow_download: self.allow_download,
+            version_cache: !self.no_version_cache,
+            version_cache_ttl: self
+                .version_cache_ttl
+                .unwrap_or(VersionCache::DEFAULT_TTL),
+        };
+        let mut benchmarks =
+            Benchmarks::for_measurements(&self.dir, measurements, &load)?;
         // Sort benchmarks by their group name so that they appear in a
         // consistent order. We retain the order of benchmarks within a
         // group, since that order always corresponds to the order they were
@@ -297,6 +549,55 @@ impl Config {
         Ok(benchmarks)
     }
 
+    /// Read the baseline measurements given by --baseline, if any, for the
+    /// results in the given grouping.
+    ///
+    /// The same filters used for the primary measurements are applied. A
+    /// version mismatch in the baseline is logged as a warning, but is never
+    /// an error.
+    fn read_baseline(
+        &self,
+        grouped: &ByBenchmarkName<Definition>,
+    ) -> anyhow::Result<Option<Baseline>> {
+        if self.baseline_paths.is_empty() {
+            return Ok(None);
+        }
+        let (measurements, versions) = MeasurementReader {
+            paths: &self.baseline_paths,
+            filters: &self.filters,
+            intersection: self.intersection,
+            labels: &[],
+            strict_duplicates: self.strict_duplicates,
+        }
+        .read_with_versions()
+        .context("failed to read --baseline measurements")?;
+        VersionCheck::default().check(&versions)?;
+        let measurements = if self.aliases.is_empty() {
+            measurements
+        } else {
+            ByBenchmarkName::new(&measurements)?
+                .alias(&self.aliases, self.stat)
+                .groups
+                .into_iter()
+                .flat_map(|g| g.by_engine.into_values())
+                .collect()
+        };
+        Ok(Some(Baseline::new(measurements, grouped, self.stat)?))
+    }
+
+    /// Renames the regex engine of each of the given failed measurements to
+    /// the alias it belongs to, if any. This way, a regex engine that is
+    /// merged into an alias doesn't get a row of its own in the results
+    /// because one of its variants couldn't run a benchmark.
+    fn alias_errors(&self, mut errors: Vec<Measurement>) -> Vec<Measurement> {
+        for m in errors.iter_mut() {
+            if let Some(alias) = self.aliases.find(&m.engine) {
+                m.engine = alias.to_string();
+            }
+        }
+        errors
+    }
+
     /// Returns a Markdown link to another document within this repository
     /// with the given display text and URL.
     ///
@@ -304,8 +605,191 @@ impl Config {
     /// That means the URL given here should be written as-if it were from the
     /// root.
     fn url(&self, display: &str, path: &str) -> String {
-        let path = format!("{}{}", self.relative_path_root, path);
-        format!("[{}]({})", display, path)
+        format!("[{}]({})", display, self.href(path))
+    }
+
+    /// Returns the given path to another document within this repository,
+    /// prepended with the relative path to the root.
+    ///
+    /// Links always use '/' as a separator, even if the relative path to the
+    /// root was given with Windows separators.
+    fn href(&self, path: &str) -> String {
+        format!("{}{}", self.relative_path_root, path).replace('\\', "/")
+    }
+}
+
+/// The format to print a report in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+enum Format {
+    /// Markdown meant to be rendered by GitHub.
+    #[default]
+    Markdown,
+    /// A single self-contained HTML file.
+    Html,
+}
+
+impl std::str::FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Format> {
+        Ok(match s {
+            "markdown" => Format::Markdown,
+            "html" => Format::Html,
+            unknown => anyhow::bail!(
+                "unrecognized format '{}', must be one of markdown or html",
+                unknown,
+            ),
+        })
+    }
+}
+
+/// Returns the CSV files to read for the given --baseline path. If the path
+/// is a directory, then this returns every file in it with a `.csv`
+/// extension, in sorted order. Otherwise, the path itself is returned.
+fn baseline_paths(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut paths = vec![];
+    for result in std::fs::read_dir(path)? {
+        let p = result?.path();
+        if p.is_file() && p.extension().is_some_and(|ext| ext == "csv") {
+            paths.push(p);
+        }
+    }
+    anyhow::ensure!(!paths.is_empty(), "no CSV files found in directory");
+    paths.sort();
+    Ok(paths)
+}
+
+/// Measurements from a previous run that a report is compared against. This
+/// is used to implement the `--baseline` flag.
+#[derive(Debug, Default)]
+struct Baseline {
+    /// The baseline measurements, keyed by benchmark name and regex engine.
+    by_key: BTreeMap<(String, String), Measurement>,
+    /// The geometric mean of speed ratios for each regex engine across all
+    /// search-time benchmarks in the baseline.
+    geomeans_search: BTreeMap<String, f64>,
+    /// Like `geomeans_search`, but for compile-time benchmarks.
+    geomeans_compile: BTreeMap<String, f64>,
+}
+
+impl Baseline {
+    /// Create a new baseline from the given measurements, where the summary
+    /// rankings are computed using the given statistic.
+    ///
+    /// Only measurements for a benchmark and regex engine that also appear
+    /// in `grouped` are kept. Otherwise, a regex engine that only appears in
+    /// the baseline (or a benchmark that has since been removed) would skew
+    /// the speed ratios of every other regex engine in the baseline.
+    fn new(
+        mut measurements: Vec<Measurement>,
+        grouped: &ByBenchmarkName<Definition>,
+        stat: Stat,
+    ) -> anyhow::Result<Baseline> {
+        let current: BTreeSet<(&str, &str)> = grouped
+            .groups
+            .iter()
+            .flat_map(|g| g.by_engine.values())
+            .map(|m| (m.name.as_str(), m.engine.as_str()))
+            .collect();
+        measurements.retain(|m| {
+            current.contains(&(m.name.as_str(), m.engine.as_str()))
+        });
+        let geomeans = |grouped: ByBenchmarkName<()>| {
+            anyhow::Ok(
+                grouped
+                    .ranking(stat)?
+                    .into_iter()
+                    .filter(|s| s.count > 0)
+                    .map(|s| (s.name, s.geomean))
+                    .collect::<BTreeMap<String, f64>>(),
+            )
+        };
+        let (compile, search) = ByBenchmarkName::new(&measurements)?
+            .partition(|g| g.by_engine.values().any(|m| m.model == "compile"));
+        let geomeans_compile = geomeans(compile)?;
+        let geomeans_search = geomeans(search)?;
+        let by_key = measurements
+            .into_iter()
+            .map(|m| ((m.name.clone(), m.engine.clone()), m))
+            .collect();
+        Ok(Baseline { by_key, geomeans_search, geomeans_compile })
+    }
+
+    /// Returns the baseline measurement for the given benchmark name and
+    /// regex engine, if one exists.
+    fn get(&self, name: &str, engine: &str) -> Option<&Measurement> {
+        self.by_key.get(&(name.to_string(), engine.to_string()))
+    }
+}
+
+/// The change of a result relative to its baseline, as a percentage of the
+/// baseline in the units shown in a report.
+#[derive(Clone, Copy, Debug)]
+struct Trend {
+    /// The percentage change. For example, `12.0` means the value shown went
+    /// up by 12%.
+    percent: f64,
+    /// Whether the change is a regression beyond the --trend-threshold.
+    regressed: bool,
+}
+
+impl Trend {
+    /// The marker appended to the annotation of a regressed result.
+    const REGRESSION_MARKER: &'static str = "⚠";
+
+    /// Computes the trend of a measurement versus its baseline measurement.
+    ///
+    /// When `is_rate` is true, the value shown is a rate (like throughput),
+    /// where bigger is better. Otherwise, the value shown is a time, where
+    /// smaller is better. This returns `None` when either duration is zero.
+    fn new(
+        config: &Config,
+        is_rate: bool,
+        m: &Measurement,
+        base: &Measurement,
+    ) -> Option<Trend> {
+        let (cur, old) = (
+            m.duration(config.stat).as_secs_f64(),
+            base.duration(config.stat).as_secs_f64(),
+        );
+        if cur == 0.0 || old == 0.0 {
+            return None;
+        }
+        let percent = if is_rate {
+            (old / cur - 1.0) * 100.0
+        } else {
+            (cur / old - 1.0) * 100.0
+        };
+        let worse = if is_rate { -percent } else { percent };
+        Some(Trend { percent, regressed: worse > config.trend_threshold })
+    }
+
+    /// Computes the trend of a regex engine's geometric mean of speed ratios
+    /// versus its baseline geometric mean. Since smaller is better, an
+    /// increase is a regression. This returns `None` when the baseline is
+    /// zero.
+    fn geomean(config: &Config, cur: f64, old: f64) -> Option<Trend> {
+        if old == 0.0 {
+            return None;
+        }
+        let percent = (cur / old - 1.0) * 100.0;
+        Some(Trend { percent, regressed: percent > config.trend_threshold })
+    }
+}
+
+impl std::fmt::Display for Trend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Avoid showing '-0%' for tiny improvements.
+        let percent = self.percent.round();
+        let percent = if percent == 0.0 { 0.0 } else { percent };
+        write!(f, "{:+}%", percent)?;
+        if self.regressed {
+            write!(f, " {}", Trend::REGRESSION_MARKER)?;
+        }
+        Ok(())
     }
 }
 
@@ -313,7 +797,7 @@ impl Config {
 #[derive(Clone, Debug)]
 enum Tree {
     Node { name: String, children: Vec<Tree> },
-    Leaf(ByBenchmarkNameGroup<Definition>),
+    Leaf(Box<ByBenchmarkNameGroup<Definition>>),
 }
 
 impl Tree {
@@ -332,7 +816,7 @@ impl Tree {
         for part in group.data.name.group.split("/") {
             node = node.find_or_insert(part);
         }
-        node.children().push(Tree::Leaf(group));
+        node.children().push(Tree::Leaf(Box::new(group)));
     }
 
     /// Looks for a direct child node with the given name and returns it. If
@@ -422,14 +906,27 @@ impl Tree {
     }
 }
 
-fn markdown<W: Write>(
-    config: &Config,
-    engines: &Engines,
-    grouped: ByBenchmarkName<Definition>,
-    analysis: &BTreeMap<String, String>,
-    tree: &Tree,
-    mut wtr: W,
-) -> anyhow::Result<()> {
+/// A map from benchmark name to the regex engines that reported they can't
+/// run it, along with the reason each one gave.
+type Skipped = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Returns the benchmarks that regex engines reported they can't run, among
+/// the given failed measurements.
+fn skipped(errors: &[Measurement]) -> Skipped {
+    let mut skipped = Skipped::new();
+    for m in errors.iter() {
+        let Some(reason) = m.skipped() else { continue };
+        skipped
+            .entry(m.name.clone())
+            .or_default()
+            .insert(m.engine.clone(), reason.to_string());
+    }
+    skipped
+}
+
+/// Writes the comment at the top of every report that says it was generated
+/// and by which command. Both Markdown and HTML use the same comment syntax.
+fn write_generated_comment<W: Write>(mut wtr: W) -> anyhow::Result<()> {
     writeln!(wtr, "<!-- Auto-generated by rebar, do not edit manually! -->")?;
     writeln!(wtr, "<!-- Generated with command: -->")?;
     write!(wtr, "<!--")?;
@@ -437,15 +934,51 @@ fn markdown<W: Write>(
         write!(wtr, " {}", arg)?;
     }
     writeln!(wtr, " -->")?;
+    Ok(())
+}
 
-    markdown_summary(config, engines, grouped, &mut wtr)?;
-    markdown_bench_list(tree, &mut wtr)?;
-    markdown_results(config, analysis, tree, &mut wtr)?;
+/// Writes the entire report. The benchmark definitions in `benchmarks` are
+/// ignored, since they've already been associated with `grouped`.
+fn markdown<W: Write>(
+    config: &Config,
+    benchmarks: &Benchmarks,
+    grouped: ByBenchmarkName<Definition>,
+    errors: &[Measurement],
+    versions: &RebarVersions,
+    baseline: Option<&Baseline>,
+    mut wtr: W,
+) -> anyhow::Result<()> {
+    let tree = &Tree::new(grouped.clone());
+    let skipped = skipped(errors);
+
+    write_generated_comment(&mut wtr)?;
+
+    markdown_summary(
+        config,
+        &benchmarks.engines,
+        grouped,
+        errors,
+        baseline,
+        &mut wtr,
+    )?;
+    markdown_bench_list(tree, versions, &mut wtr)?;
+    markdown_results(
+        config,
+        &benchmarks.analysis,
+        &skipped,
+        baseline,
+        tree,
+        &mut wtr,
+    )?;
+    if config.show_errors {
+        markdown_errors(errors, &mut wtr)?;
+    }
     Ok(())
 }
 
 fn markdown_bench_list<W: Write>(
     tree: &Tree,
+    versions: &RebarVersions,
     mut wtr: W,
 ) -> anyhow::Result<()> {
     let revision = match util::REBAR_REVISION {
@@ -466,9 +999,24 @@ This report was generated by `rebar {version}{revision}`.
         revision = revision,
     );
     writeln!(wtr, "### Benchmark Groups")?;
-    writeln!(wtr, "")?;
+    writeln!(wtr)?;
     writeln!(wtr, "{}", explanation.trim())?;
-    writeln!(wtr, "")?;
+    writeln!(wtr)?;
+    if let Some(revisions) = versions.defs_revisions() {
+        writeln!(
+            wtr,
+            "**Note**: the measurements in this report were recorded against \
+             {} different revisions of the benchmark definitions:",
+            revisions.len(),
+        )?;
+        writeln!(wtr)?;
+        for (rev, paths) in revisions.iter() {
+            let paths: Vec<String> =
+                paths.iter().map(|p| format!("`{}`", p.display())).collect();
+            writeln!(wtr, "* `{}`: {}", rev, paths.join(", "))?;
+        }
+        writeln!(wtr)?;
+    }
     tree.flattened_depth_first(|tree, depth| {
         let indent = "  ".repeat(depth);
         match *tree {
@@ -480,7 +1028,7 @@ This report was generated by `rebar {version}{revision}`.
         }
         Ok(())
     })?;
-    writeln!(wtr, "")?;
+    writeln!(wtr)?;
     Ok(())
 }
 
@@ -488,6 +1036,8 @@ fn markdown_summary<W: Write>(
     config: &Config,
     engines: &Engines,
     grouped: ByBenchmarkName<Definition>,
+    errors: &[Measurement],
+    baseline: Option<&Baseline>,
     mut wtr: W,
 ) -> anyhow::Result<()> {
     let explanation = format!(
@@ -495,7 +1045,10 @@ fn markdown_summary<W: Write>(
 Below are two tables summarizing the results of regex engines benchmarked.
 Each regex engine includes its version at the time measurements were captured,
 a summary score that ranks it relative to other regex engines across all
-benchmarks and the total number of measurements collected.
+benchmarks, the total number of measurements collected and the number of
+measurements that failed with an error. Failed measurements don't contribute
+to the summary score, so a regex engine with errors is ranked on fewer
+benchmarks than it was run on.
 
 The first table ranks regex engines based on search time. The second table
 ranks regex engines based on compile time.
@@ -509,6 +1062,14 @@ ns` and `25 ns` on a single benchmark, `A` has a speed ratio of `1.4` and
 `B` has a speed ratio of `1.0`. The geometric mean reported here is then the
 "average" speed ratio for that regex engine across all benchmarks.
 
+The search-time table also includes the median throughput of each regex engine
+across all benchmarks that include it, where each benchmark's throughput is
+computed from the {stat} of all timing samples taken. This gives a sense of how
+fast each regex engine is in absolute terms. Benchmarks without a haystack
+length don't have a throughput, and an engine without any throughputs shows
+`-`. Similarly, each table of benchmark results below ends with a row giving
+the absolute measurement of the best regex engine for each benchmark.
+
 If you're looking to compare two regex engines specifically, then it is better
 to do so based only on the benchmarks that they both participate in. For
 example, to compared based on the results recorded on 2023-05-04, one can do:
@@ -538,50 +1099,58 @@ performance profile of any specific regex engine or workload.
 "#,
         stat = config.stat,
     );
+    let explanation = if !config.confidence {
+        explanation.trim().to_string()
+    } else {
+        format!(
+            "{}\n\n{}",
+            explanation.trim(),
+            format!(
+                r#"
+Each geometric mean is followed by a 95% confidence interval, e.g.,
+`2.13 [1.95, 2.34]`. It is computed by resampling each regex engine's speed
+ratios with replacement 1,000 times and taking the 2.5th and 97.5th
+percentiles of the geometric means of those resamples. Regex engines with
+fewer than {min} benchmarks are marked with {marker}, since bootstrapping from
+so few samples tends to produce intervals that are too narrow.
+"#,
+                min = EngineSummary::MIN_CONFIDENCE_COUNT,
+                marker = EngineSummary::LOW_SAMPLE_MARKER,
+            )
+            .trim(),
+        )
+    };
 
-    let (grouped_compile, grouped_search) =
-        grouped.partition(|g| g.data.model == "compile");
-    let ranked_compile: Vec<EngineSummary> = grouped_compile
-        .ranking(config.stat)?
-        .into_iter()
-        .filter(|s| s.count > 0)
-        .filter(|s| {
-            config
-                .summary_exclude
-                .as_ref()
-                .map_or(true, |re| !re.is_match(&s.name))
-        })
-        .collect();
-    let ranked_search: Vec<EngineSummary> = grouped_search
-        .ranking(config.stat)?
-        .into_iter()
-        .filter(|s| s.count > 0)
-        .filter(|s| {
-            config
-                .summary_exclude
-                .as_ref()
-                .map_or(true, |re| !re.is_match(&s.name))
-        })
-        .collect();
-
-    if !ranked_compile.is_empty() || !ranked_search.is_empty() {
+    let rankings = Rankings::new(config, grouped, errors)?;
+    if !rankings.is_empty() {
         writeln!(wtr, "### Summary")?;
-        writeln!(wtr, "")?;
-        writeln!(wtr, "{}", explanation.trim())?;
-        writeln!(wtr, "")?;
+        writeln!(wtr)?;
+        writeln!(wtr, "{}", explanation)?;
+        writeln!(wtr)?;
 
-        if !ranked_search.is_empty() {
+        if !rankings.search.is_empty() {
             writeln!(wtr, "#### Summary of search-time benchmarks")?;
-            writeln!(wtr, "")?;
-            markdown_summary_table(config, engines, &ranked_search, &mut wtr)?;
+            writeln!(wtr)?;
+            markdown_summary_table(
+                config,
+                engines,
+                &rankings.search,
+                &rankings.errors_search,
+                Some(&rankings.search_tputs),
+                baseline.map(|b| &b.geomeans_search),
+                &mut wtr,
+            )?;
         }
-        if !ranked_compile.is_empty() {
+        if !rankings.compile.is_empty() {
             writeln!(wtr, "#### Summary of compile-time benchmarks")?;
-            writeln!(wtr, "")?;
+            writeln!(wtr)?;
             markdown_summary_table(
                 config,
                 engines,
-                &ranked_compile,
+                &rankings.compile,
+                &rankings.errors_compile,
+                None,
+                baseline.map(|b| &b.geomeans_compile),
                 &mut wtr,
             )?;
         }
@@ -590,14 +1159,110 @@ performance profile of any specific regex engine or workload.
     Ok(())
 }
 
+/// The rankings of regex engines shown in the summary tables of a report.
+struct Rankings {
+    /// The ranking of regex engines for search-time benchmarks.
+    search: Vec<EngineSummary>,
+    /// The ranking of regex engines for compile-time benchmarks.
+    compile: Vec<EngineSummary>,
+    /// The median throughput of each regex engine across search-time
+    /// benchmarks.
+    search_tputs: BTreeMap<String, Throughput>,
+    /// The number of failed search-time measurements for each regex engine.
+    errors_search: BTreeMap<String, usize>,
+    /// The number of failed compile-time measurements for each regex engine.
+    errors_compile: BTreeMap<String, usize>,
+}
+
+impl Rankings {
+    /// Computes the rankings for the given results. Regex engines without
+    /// any benchmarks, or that are excluded by --summary-exclude, are
+    /// omitted.
+    fn new(
+        config: &Config,
+        grouped: ByBenchmarkName<Definition>,
+        errors: &[Measurement],
+    ) -> anyhow::Result<Rankings> {
+        let (grouped_compile, grouped_search) =
+            grouped.partition(|g| g.data.model == "compile");
+        let (errors_compile, errors_search) = error_counts(errors);
+        let rank = |grouped: &ByBenchmarkName<Definition>| {
+            anyhow::Ok(
+                grouped
+                    .ranking(config.stat)?
+                    .into_iter()
+                    .filter(|s| s.count > 0)
+                    .filter(|s| {
+                        config
+                            .summary_exclude
+                            .as_ref()
+                            .is_none_or(|re| !re.is_match(&s.name))
+                    })
+                    .collect::<Vec<EngineSummary>>(),
+            )
+        };
+        Ok(Rankings {
+            search: rank(&grouped_search)?,
+            compile: rank(&grouped_compile)?,
+            search_tputs: grouped_search.median_throughputs(config.stat),
+            errors_search,
+            errors_compile,
+        })
+    }
+
+    /// Returns true when there are no regex engines to show in either
+    /// summary table.
+    fn is_empty(&self) -> bool {
+        self.search.is_empty() && self.compile.is_empty()
+    }
+}
+
+/// Returns the number of failed measurements for each regex engine, split
+/// into compile-time and search-time benchmarks (in that order). Benchmarks
+/// that a regex engine reported it can't run aren't counted.
+fn error_counts(
+    errors: &[Measurement],
+) -> (BTreeMap<String, usize>, BTreeMap<String, usize>) {
+    let (mut compile, mut search) = (BTreeMap::new(), BTreeMap::new());
+    for m in errors.iter().filter(|m| m.skipped().is_none()) {
+        let counts =
+            if m.model == "compile" { &mut compile } else { &mut search };
+        *counts.entry(m.engine.clone()).or_insert(0) += 1;
+    }
+    (compile, search)
+}
+
+/// Writes a table of the given engine summaries, along with the number of
+/// errors for each engine in `errors`. When `tputs` is given, an extra column
+/// with the median throughput of each engine is included. An engine missing
+/// from `tputs` gets a `-` in that column. Similarly, when `base_geomeans` is
+/// given, an extra column with the change in each engine's geometric mean
+/// versus the baseline is included.
 fn markdown_summary_table<W: Write>(
     config: &Config,
     engines: &Engines,
     summaries: &[EngineSummary],
+    errors: &BTreeMap<String, usize>,
+    tputs: Option<&BTreeMap<String, Throughput>>,
+    base_geomeans: Option<&BTreeMap<String, f64>>,
     mut wtr: W,
 ) -> anyhow::Result<()> {
-    writeln!(wtr, "| Engine | Version | Geometric mean of speed ratios | Benchmark count |")?;
-    writeln!(wtr, "| ------ | ------- | ------------------------------ | --------------- |")?;
+    write!(wtr, "| Engine | Version | Geometric mean of speed ratios | Benchmark count | Errors |")?;
+    if tputs.is_some() {
+        write!(wtr, " Median throughput |")?;
+    }
+    if base_geomeans.is_some() {
+        write!(wtr, " Change vs baseline |")?;
+    }
+    writeln!(wtr)?;
+    write!(wtr, "| ------ | ------- | ------------------------------ | --------------- | ------ |")?;
+    if tputs.is_some() {
+        write!(wtr, " ----------------- |")?;
+    }
+    if base_geomeans.is_some() {
+        write!(wtr, " ------------------ |")?;
+    }
+    writeln!(wtr)?;
     for summary in summaries.iter() {
         if summary.count == 0 {
             continue;
@@ -615,19 +1280,43 @@ fn markdown_summary_table<W: Write>(
             None => write!(wtr, "{}", summary.name)?,
             Some(dir) => write!(wtr, "{}", config.url(&summary.name, dir))?,
         }
-        writeln!(
+        write!(wtr, " | {}", summary.version)?;
+        if let Some(ref metadata) = summary.metadata {
+            write!(wtr, " ({})", metadata)?;
+        }
+        write!(
             wtr,
-            " | {} | {:.2} | {} |",
-            summary.version, summary.geomean, summary.count,
+            " | {} | {} | {} |",
+            summary.geomean_display(config.confidence),
+            summary.count,
+            errors.get(&summary.name).copied().unwrap_or(0),
         )?;
+        if let Some(tputs) = tputs {
+            match tputs.get(&summary.name) {
+                None => write!(wtr, " - |")?,
+                Some(tput) => write!(wtr, " {} |", tput)?,
+            }
+        }
+        if let Some(base_geomeans) = base_geomeans {
+            let trend = base_geomeans
+                .get(&summary.name)
+                .and_then(|&old| Trend::geomean(config, summary.geomean, old));
+            match trend {
+                None => write!(wtr, " - |")?,
+                Some(trend) => write!(wtr, " {} |", trend)?,
+            }
+        }
+        writeln!(wtr)?;
     }
-    writeln!(wtr, "")?;
+    writeln!(wtr)?;
     Ok(())
 }
 
 fn markdown_results<W: Write>(
     config: &Config,
     analysis: &BTreeMap<String, String>,
+    skipped: &Skipped,
+    baseline: Option<&Baseline>,
     tree: &Tree,
     mut wtr: W,
 ) -> anyhow::Result<()> {
@@ -638,17 +1327,19 @@ fn markdown_results<W: Write>(
                 let header = "#".repeat(depth + 3);
                 let nice_name = nice_name(name);
                 writeln!(wtr, "{} {}", header, nice_name)?;
-                writeln!(wtr, "")?;
+                writeln!(wtr)?;
                 if children.iter().all(Tree::is_leaf) {
                     let mut defms = vec![];
                     for c in children.iter() {
                         let defm = match *c {
-                            Tree::Leaf(ref defm) => defm,
+                            Tree::Leaf(ref defm) => &**defm,
                             Tree::Node { .. } => unreachable!(),
                         };
                         defms.push(defm);
                     }
-                    markdown_result_group(config, analysis, &defms, &mut wtr)?
+                    markdown_result_group(
+                        config, analysis, skipped, baseline, &defms, &mut wtr,
+                    )?
                 }
             }
         }
@@ -659,6 +1350,8 @@ fn markdown_results<W: Write>(
 fn markdown_result_group<W: Write>(
     config: &Config,
     analysis: &BTreeMap<String, String>,
+    skipped: &Skipped,
+    baseline: Option<&Baseline>,
     groups: &[&ByBenchmarkNameGroup<Definition>],
     wtr: &mut W,
 ) -> anyhow::Result<()> {
@@ -666,34 +1359,47 @@ fn markdown_result_group<W: Write>(
         writeln!(wtr, "NO MEASUREMENTS TO REPORT")?;
         return Ok(());
     }
-    if let Some(ref analysis) = analysis.get(&groups[0].data.name.group) {
+    if let Some(analysis) = analysis.get(&groups[0].data.name.group) {
         writeln!(wtr, "{}", analysis.trim())?;
-        writeln!(wtr, "")?;
+        writeln!(wtr)?;
     }
 
     write!(wtr, "| Engine |")?;
     for group in groups.iter() {
         write!(wtr, " {} |", group.data.name.local)?;
     }
-    writeln!(wtr, "")?;
+    writeln!(wtr)?;
     write!(wtr, "| - |")?;
     for _ in groups.iter() {
         write!(wtr, " - |")?;
     }
-    writeln!(wtr, "")?;
+    writeln!(wtr)?;
 
+    // Regex engines that reported they can't run a benchmark get a row too,
+    // so that it's clear they weren't just left out.
+    let skipped_in = |group: &ByBenchmarkNameGroup<Definition>| {
+        skipped.get(group.data.name.as_str())
+    };
+    let mut any_regressed = false;
     let mut engines = BTreeSet::new();
     for group in groups.iter() {
         for e in group.by_engine.keys() {
             engines.insert(e.clone());
         }
+        for e in skipped_in(group).into_iter().flat_map(|s| s.keys()) {
+            engines.insert(e.clone());
+        }
     }
     for e in engines.iter() {
         write!(wtr, "| {} |", e)?;
         for group in groups.iter() {
             let m = match group.by_engine.get(e) {
                 None => {
-                    write!(wtr, " - |")?;
+                    if skipped_in(group).is_some_and(|s| s.contains_key(e)) {
+                        write!(wtr, " n/a |")?;
+                    } else {
+                        write!(wtr, " - |")?;
+                    }
                     continue;
                 }
                 Some(m) => m,
@@ -704,37 +1410,79 @@ fn markdown_result_group<W: Write>(
             if is_best {
                 write!(wtr, "**")?;
             }
-            match config.units {
-                Units::Throughput if m.aggregate.tputs.is_some() => {
-                    let tput = m.throughput(config.stat).unwrap();
-                    write!(wtr, "{}", tput)?;
-                }
-                _ => {
-                    let d = m.duration(config.stat);
-                    let humand = ShortHumanDuration::from(d);
-                    write!(wtr, "{}", humand)?;
-                }
-            }
+            write!(wtr, "{}", format_measurement(config, group, m))?;
             if config.ratio {
                 write!(wtr, " ({:.2}x)", ratio)?;
             }
+            let trend = baseline
+                .and_then(|b| b.get(&m.name, &m.engine))
+                .and_then(|base| {
+                    let is_rate = is_rate(config, m, base);
+                    Trend::new(config, is_rate, m, base)
+                });
+            if let Some(trend) = trend {
+                any_regressed = any_regressed || trend.regressed;
+                write!(wtr, " ({})", trend)?;
+            }
             if is_best {
                 write!(wtr, "**")?;
             }
             write!(wtr, " |")?;
         }
-        writeln!(wtr, "")?;
+        writeln!(wtr)?;
+    }
+    // A final row with the absolute measurement of the best engine for each
+    // benchmark. It duplicates the bolded entry in each column, but makes it
+    // easy to see how fast the fastest engine is without hunting for it.
+    write!(wtr, "| _best_ |")?;
+    for group in groups.iter() {
+        if group.by_engine.is_empty() {
+            write!(wtr, " - |")?;
+            continue;
+        }
+        let m = &group.by_engine[group.best(config.stat)];
+        write!(wtr, " {} |", format_measurement(config, group, m))?;
+    }
+    writeln!(wtr)?;
+    writeln!(wtr)?;
+    let any_partial =
+        groups.iter().flat_map(|g| g.by_engine.values()).any(|m| m.partial);
+    if any_partial {
+        writeln!(
+            wtr,
+            "{} The runner stopped early because it was close to timing out, \
+             so this measurement is based on fewer samples than requested.",
+            Measurement::PARTIAL_MARKER,
+        )?;
+        writeln!(wtr)?;
+    }
+    if any_regressed {
+        writeln!(
+            wtr,
+            "{} This result regressed by more than {}% versus the baseline.",
+            Trend::REGRESSION_MARKER,
+            config.trend_threshold,
+        )?;
+        writeln!(wtr)?;
+    }
+    if groups.iter().any(|g| !g.variants.is_empty()) {
+        writeln!(
+            wtr,
+            "_Note:_ aliased regex engines show their best variant for \
+             each benchmark, which is given by the `variant` parameters \
+             below.",
+        )?;
+        writeln!(wtr)?;
     }
-    writeln!(wtr, "")?;
 
     writeln!(wtr, "<details>")?;
     writeln!(wtr, "<summary>Show individual benchmark parameters.</summary>")?;
-    writeln!(wtr, "")?;
+    writeln!(wtr)?;
     for group in groups.iter() {
         let def = &group.data;
 
         writeln!(wtr, "**{}**", def.name.local)?;
-        writeln!(wtr, "")?;
+        writeln!(wtr)?;
 
         writeln!(wtr, "| Parameter | Value |")?;
         writeln!(wtr, "| --------- | ----- |")?;
@@ -780,6 +1528,30 @@ fn markdown_result_group<W: Write>(
             def.options.case_insensitive
         )?;
         writeln!(wtr, "| unicode | `{}` |", def.options.unicode)?;
+        // These are rarely used, so we only show them when they're enabled.
+        if def.options.multiline {
+            writeln!(wtr, "| multiline | `true` |")?;
+        }
+        if def.options.dot_all {
+            writeln!(wtr, "| dot-all | `true` |")?;
+        }
+        if let Some(start) = def.options.search_start {
+            writeln!(wtr, "| search-start | `{}` |", start)?;
+        }
+        if let Some(end) = def.options.search_end {
+            writeln!(wtr, "| search-end | `{}` |", end)?;
+        }
+        if let Some(max) = def.options.max_count {
+            writeln!(wtr, "| max-count | `{}` |", max)?;
+        }
+        for (name, value) in def.params.iter() {
+            writeln!(
+                wtr,
+                "| param(`{}`) | `{}` |",
+                name,
+                markdown_table_escape(value),
+            )?;
+        }
         if let Some(ref path) = def.haystack_path {
             writeln!(
                 wtr,
@@ -801,47 +1573,1643 @@ fn markdown_result_group<W: Write>(
             writeln!(wtr, " |")?;
         }
         for ec in def.count.iter() {
-            writeln!(wtr, "| count(`{}`) | {} |", ec.engine, ec.count,)?;
+            // For per-pattern counts, the checksum isn't very useful to
+            // readers, so we show the counts for each pattern instead.
+            let count = match ec.per_pattern {
+                None => ec.count.to_string(),
+                Some(ref counts) => format!("{:?}", counts),
+            };
+            match ec.version {
+                None => write!(wtr, "| count(`{}`) | {}", ec.engine, count)?,
+                Some(ref req) => write!(
+                    wtr,
+                    "| count(`{}`, `{}`) | {}",
+                    ec.engine, req, count,
+                )?,
+            }
+            if let Some(ref semantics) = ec.semantics {
+                write!(wtr, " (`{}`)", markdown_table_escape(semantics))?;
+            }
+            writeln!(wtr, " |")?;
+        }
+        // Not every runner reports this, so engines without it are omitted.
+        for (name, m) in group.by_engine.iter() {
+            if let Some(n) = m.warmup_iters {
+                writeln!(wtr, "| warmup-iters(`{}`) | {} |", name, n)?;
+            }
+        }
+        for (alias, engine) in group.variants.iter() {
+            writeln!(wtr, "| variant(`{}`) | `{}` |", alias, engine)?;
+        }
+        for (name, reason) in skipped_in(group).into_iter().flatten() {
+            writeln!(
+                wtr,
+                "| skipped(`{}`) | {} |",
+                name,
+                markdown_table_escape(reason),
+            )?;
         }
 
-        writeln!(wtr, "")?;
+        writeln!(wtr)?;
+        markdown_count_footnote(group, wtr)?;
         if let Some(ref analysis) = def.analysis {
             writeln!(wtr, "{}", analysis.trim())?;
         }
-        writeln!(wtr, "")?;
+        writeln!(wtr)?;
     }
     writeln!(wtr, "</details>")?;
-    writeln!(wtr, "")?;
+    writeln!(wtr)?;
     Ok(())
 }
 
-fn markdown_table_escape(v: &str) -> String {
-    v.replace("|", r"\|")
+/// Formats the given measurement from the given group in the units selected
+/// by the configuration. This doesn't include any ratio.
+///
+/// A measurement from a runner that stopped early because of its soft
+/// deadline is suffixed with `Measurement::PARTIAL_MARKER`.
+fn format_measurement(
+    config: &Config,
+    group: &ByBenchmarkNameGroup<Definition>,
+    m: &Measurement,
+) -> String {
+    let mut value = match config.units {
+        Units::Throughput if m.aggregate.tputs.is_some() => {
+            m.throughput(config.stat).unwrap().to_string()
+        }
+        Units::LinesPerSec => {
+            let d = m.duration(config.stat);
+            Rate::new(group.data.line_count(), d, "lines").to_string()
+        }
+        Units::ItersPerSec => {
+            let d = m.duration(config.stat);
+            Rate::new(1, d, "iters").to_string()
+        }
+        _ => ShortHumanDuration::from(m.duration(config.stat)).to_string(),
+    };
+    if m.partial {
+        value.push_str(Measurement::PARTIAL_MARKER);
+    }
+    value
 }
 
-/// Splices the given report into the given file path. This returns an error if
-/// reading or writing the file fails, or if the report isn't valid UTF-8, or
-/// if appropriate begin and end markers for the report could not be found.
-fn splice(path: &Path, report: &[u8]) -> anyhow::Result<()> {
-    let re =
-        regex!(r"\n<!-- BEGIN: report -->\n((?s:.*?))<!-- END: report -->\n",);
-    let src = std::fs::read_to_string(path)
-        .with_context(|| path.display().to_string())?;
-    let remove = match re.captures(&src) {
-        None => anyhow::bail!("could not find report markers in splice file"),
-        Some(caps) => caps.get(1).unwrap(),
-    };
-    let mut out = vec![];
-    out.extend_from_slice(src[..remove.start()].as_bytes());
-    out.extend_from_slice(report);
-    out.extend_from_slice(&src[remove.end()..].as_bytes());
-    std::fs::write(path, &out).with_context(|| path.display().to_string())?;
+/// Returns true when the given measurement is shown as a rate (where bigger is
+/// better) rather than a time by `format_measurement`. A baseline
+/// measurement without a throughput causes time to be compared instead, since
+/// the two wouldn't otherwise be comparable.
+fn is_rate(config: &Config, m: &Measurement, base: &Measurement) -> bool {
+    match config.units {
+        Units::Throughput => {
+            m.aggregate.tputs.is_some() && base.aggregate.tputs.is_some()
+        }
+        Units::LinesPerSec | Units::ItersPerSec => true,
+        Units::Time => false,
+    }
+}
+
+/// Writes a footnote for the given group when the engines measured in it
+/// were verified against different counts. This is usually because of
+/// legitimate differences in match semantics (like how case insensitive
+/// matching works with Unicode), and the footnote says which engines got
+/// which count so that readers don't have to puzzle it out.
+fn markdown_count_footnote<W: Write>(
+    group: &ByBenchmarkNameGroup<Definition>,
+    wtr: &mut W,
+) -> anyhow::Result<()> {
+    let Some(by_count) = count_variants(group) else { return Ok(()) };
+    write!(wtr, "_Note:_ engines were verified against different counts:")?;
+    for (i, ((count, semantics), names)) in by_count.iter().enumerate() {
+        let sep = if i == 0 { "" } else { ";" };
+        let names = names
+            .iter()
+            .map(|n| format!("`{}`", n))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(wtr, "{} {} for {}", sep, count, names)?;
+        if let Some(semantics) = semantics {
+            write!(wtr, " (`{}`)", semantics)?;
+        }
+    }
+    writeln!(wtr, ".")?;
+    writeln!(wtr)?;
     Ok(())
 }
 
-/// Formats the name of something by applying various conventions used in
-/// benchmark definitions.
-fn nice_name(name: &str) -> String {
-    let re = regex!(r"^([0-9]+-)");
-    re.replace(name, "").into_owned()
+/// A map from a (count, semantics) pair to the regex engines in a group that
+/// were verified against it.
+type CountVariants<'a> = BTreeMap<(u64, Option<&'a str>), Vec<&'a str>>;
+
+/// Returns the counts that the regex engines in the given group were
+/// verified against, but only when there is more than one distinct count.
+fn count_variants(
+    group: &ByBenchmarkNameGroup<Definition>,
+) -> Option<CountVariants<'_>> {
+    let def = &group.data;
+    // A map from (count, semantics) to the engines verified against it.
+    let mut by_count = CountVariants::new();
+    for (name, m) in group.by_engine.iter() {
+        // Measurements with an error were never verified, and an engine
+        // without a count entry is already reported elsewhere.
+        if m.err.is_some() {
+            continue;
+        }
+        let Ok(ce) = def.count_entry(name, &m.engine_version) else {
+            continue;
+        };
+        by_count
+            .entry((ce.count, ce.semantics.as_deref()))
+            .or_default()
+            .push(name);
+    }
+    let distinct: BTreeSet<u64> = by_count.keys().map(|&(c, _)| c).collect();
+    if distinct.len() <= 1 {
+        return None;
+    }
+    Some(by_count)
+}
+
+/// Writes a table with the benchmark name, regex engine and error message of
+/// each of the given measurements. This is used to implement the
+/// `--show-errors` flag.
+fn markdown_errors<W: Write>(
+    errors: &[Measurement],
+    mut wtr: W,
+) -> anyhow::Result<()> {
+    writeln!(wtr, "### Errors")?;
+    writeln!(wtr)?;
+    if errors.is_empty() {
+        writeln!(wtr, "NO ERRORS TO REPORT")?;
+        return Ok(());
+    }
+    writeln!(wtr, "| Benchmark | Engine | Error |")?;
+    writeln!(wtr, "| - | - | - |")?;
+    for m in errors.iter() {
+        let err = m.err.as_deref().unwrap_or_default().replace('\n', " ");
+        writeln!(
+            wtr,
+            "| {} | {} | {} |",
+            m.name,
+            m.engine,
+            markdown_table_escape(err.trim()),
+        )?;
+    }
+    writeln!(wtr)?;
+    Ok(())
+}
+
+fn markdown_table_escape(v: &str) -> String {
+    v.replace("|", r"\|")
+}
+
+/// The CSS embedded in every HTML report.
+const HTML_STYLE: &str = r#"
+body {
+  font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Helvetica,
+    Arial, sans-serif;
+  line-height: 1.5;
+  color: #1f2328;
+  max-width: 80rem;
+  margin: 0 auto;
+  padding: 0 1rem 2rem;
+}
+code {
+  font-family: ui-monospace, SFMono-Regular, Menlo, Consolas, monospace;
+  font-size: 0.9em;
+}
+table {
+  border-collapse: collapse;
+  margin: 1rem 0;
+}
+th, td {
+  border: 1px solid #d0d7de;
+  padding: 0.25rem 0.75rem;
+  text-align: left;
+}
+thead th {
+  position: sticky;
+  top: 0;
+  background: #f6f8fa;
+}
+table.sortable thead th {
+  cursor: pointer;
+  user-select: none;
+}
+table.sortable thead th[aria-sort="ascending"]::after {
+  content: " \25B2";
+}
+table.sortable thead th[aria-sort="descending"]::after {
+  content: " \25BC";
+}
+tfoot td {
+  background: #f6f8fa;
+}
+td.best {
+  background: #dafbe1;
+}
+.regressed {
+  color: #cf222e;
+}
+"#;
+
+/// The JavaScript embedded in every HTML report. It makes every table with
+/// the 'sortable' class sortable by clicking on a column header.
+///
+/// Cells with a 'data-value' attribute are compared as numbers. Other cells
+/// (like '-' for a missing result) are compared as text, and always come after
+/// cells with a number, regardless of the direction of the sort.
+const HTML_SCRIPT: &str = r#"
+(function() {
+  function key(cell) {
+    var value = cell.getAttribute("data-value");
+    return value === null ? cell.textContent.trim() : parseFloat(value);
+  }
+  document.querySelectorAll("table.sortable").forEach(function(table) {
+    var headers = Array.from(table.tHead.rows[0].cells);
+    headers.forEach(function(th, col) {
+      th.addEventListener("click", function() {
+        var asc = th.getAttribute("aria-sort") !== "ascending";
+        headers.forEach(function(h) { h.removeAttribute("aria-sort"); });
+        th.setAttribute("aria-sort", asc ? "ascending" : "descending");
+        var body = table.tBodies[0];
+        var rows = Array.from(body.rows);
+        rows.sort(function(r1, r2) {
+          var k1 = key(r1.cells[col]), k2 = key(r2.cells[col]);
+          if (typeof k1 !== typeof k2) {
+            return typeof k1 === "number" ? -1 : 1;
+          }
+          var c = typeof k1 === "number" ? k1 - k2 : k1.localeCompare(k2);
+          return asc ? c : -c;
+        });
+        rows.forEach(function(row) { body.appendChild(row); });
+      });
+    });
+  });
+})();
+"#;
+
+/// Writes the entire report as a single self-contained HTML file. Like with
+/// `markdown`, the benchmark definitions in `benchmarks` are ignored.
+fn html<W: Write>(
+    config: &Config,
+    benchmarks: &Benchmarks,
+    grouped: ByBenchmarkName<Definition>,
+    errors: &[Measurement],
+    versions: &RebarVersions,
+    baseline: Option<&Baseline>,
+    mut wtr: W,
+) -> anyhow::Result<()> {
+    let tree = &Tree::new(grouped.clone());
+    let skipped = skipped(errors);
+
+    writeln!(wtr, "<!DOCTYPE html>")?;
+    write_generated_comment(&mut wtr)?;
+    writeln!(wtr, "<html lang=\"en\">")?;
+    writeln!(wtr, "<head>")?;
+    writeln!(wtr, "<meta charset=\"utf-8\">")?;
+    writeln!(
+        wtr,
+        "<meta name=\"viewport\" \
+         content=\"width=device-width, initial-scale=1\">"
+    )?;
+    writeln!(wtr, "<title>rebar report</title>")?;
+    write!(wtr, "<style>{}</style>", HTML_STYLE)?;
+    writeln!(wtr)?;
+    writeln!(wtr, "</head>")?;
+    writeln!(wtr, "<body>")?;
+    html_summary(
+        config,
+        &benchmarks.engines,
+        grouped,
+        errors,
+        baseline,
+        &mut wtr,
+    )?;
+    html_bench_list(tree, versions, &mut wtr)?;
+    html_results(
+        config,
+        &benchmarks.analysis,
+        &skipped,
+        baseline,
+        tree,
+        &mut wtr,
+    )?;
+    if config.show_errors {
+        html_errors(errors, &mut wtr)?;
+    }
+    write!(wtr, "<script>{}</script>", HTML_SCRIPT)?;
+    writeln!(wtr)?;
+    writeln!(wtr, "</body>")?;
+    writeln!(wtr, "</html>")?;
+    Ok(())
+}
+
+/// Hands out unique values for the `id` attribute of the headings of
+/// benchmark groups in an HTML report.
+///
+/// The same sequence of names always gets the same sequence of ids. So the
+/// list of links to each group and the headings they link to agree, as long
+/// as both visit the tree of results in the same order.
+#[derive(Debug)]
+struct HtmlAnchors {
+    /// The number of times each id has been handed out so far.
+    seen: BTreeMap<String, usize>,
+}
+
+impl HtmlAnchors {
+    /// The ids of headings that aren't for benchmark groups.
+    const RESERVED: &'static [&'static str] =
+        &["summary", "benchmark-groups", "errors"];
+
+    fn new() -> HtmlAnchors {
+        let seen = HtmlAnchors::RESERVED
+            .iter()
+            .map(|&id| (id.to_string(), 1))
+            .collect();
+        HtmlAnchors { seen }
+    }
+
+    /// Returns a unique id for a heading with the given name. Like GitHub,
+    /// a name that was already seen gets a `-N` suffix.
+    fn id(&mut self, name: &str) -> String {
+        let slug: String = name
+            .chars()
+            .flat_map(|ch| {
+                let ch =
+                    if ch.is_alphanumeric() || ch == '_' { ch } else { '-' };
+                ch.to_lowercase()
+            })
+            .collect();
+        let count = self.seen.entry(slug.clone()).or_insert(0);
+        *count += 1;
+        if *count == 1 {
+            slug
+        } else {
+            format!("{}-{}", slug, *count - 1)
+        }
+    }
+}
+
+fn html_bench_list<W: Write>(
+    tree: &Tree,
+    versions: &RebarVersions,
+    mut wtr: W,
+) -> anyhow::Result<()> {
+    writeln!(wtr, "<h2 id=\"benchmark-groups\">Benchmark Groups</h2>")?;
+    writeln!(
+        wtr,
+        "<p>Below is a list of links to each benchmark group in this \
+         particular barometer. Each benchmark group contains 1 or more \
+         related benchmarks. The idea of each group is to tell some kind of \
+         story about related workloads, and to give a sense of how \
+         performance changes based on the variations between each \
+         benchmark.</p>"
+    )?;
+    writeln!(
+        wtr,
+        "<p>This report was generated by <code>rebar {}</code>.</p>",
+        html_escape(&util::version()),
+    )?;
+    if let Some(revisions) = versions.defs_revisions() {
+        writeln!(
+            wtr,
+            "<p><strong>Note</strong>: the measurements in this report were \
+             recorded against {} different revisions of the benchmark \
+             definitions:</p>",
+            revisions.len(),
+        )?;
+        writeln!(wtr, "<ul>")?;
+        for (rev, paths) in revisions.iter() {
+            let paths: Vec<String> = paths
+                .iter()
+                .map(|p| {
+                    format!(
+                        "<code>{}</code>",
+                        html_escape(&p.to_string_lossy())
+                    )
+                })
+                .collect();
+            writeln!(
+                wtr,
+                "<li><code>{}</code>: {}</li>",
+                html_escape(rev),
+                paths.join(", "),
+            )?;
+        }
+        writeln!(wtr, "</ul>")?;
+    }
+    // The number of lists that are currently open. Every open list has an
+    // item that is still open too, since a nested list goes inside of the
+    // item for its parent.
+    let mut open = 0;
+    let mut anchors = HtmlAnchors::new();
+    tree.flattened_depth_first(|tree, depth| {
+        let Tree::Node { ref name, .. } = *tree else { return Ok(()) };
+        if open <= depth {
+            while open <= depth {
+                writeln!(wtr, "<ul>")?;
+                open += 1;
+            }
+        } else {
+            writeln!(wtr, "</li>")?;
+            while open > depth + 1 {
+                writeln!(wtr, "</ul>")?;
+                writeln!(wtr, "</li>")?;
+                open -= 1;
+            }
+        }
+        let nice_name = nice_name(name);
+        write!(
+            wtr,
+            "<li><a href=\"#{}\">{}</a>",
+            anchors.id(&nice_name),
+            html_escape(&nice_name),
+        )?;
+        Ok(())
+    })?;
+    if open > 0 {
+        writeln!(wtr, "</li>")?;
+    }
+    while open > 0 {
+        writeln!(wtr, "</ul>")?;
+        open -= 1;
+        if open > 0 {
+            writeln!(wtr, "</li>")?;
+        }
+    }
+    Ok(())
+}
+
+fn html_summary<W: Write>(
+    config: &Config,
+    engines: &Engines,
+    grouped: ByBenchmarkName<Definition>,
+    errors: &[Measurement],
+    baseline: Option<&Baseline>,
+    mut wtr: W,
+) -> anyhow::Result<()> {
+    let rankings = Rankings::new(config, grouped, errors)?;
+    if rankings.is_empty() {
+        return Ok(());
+    }
+    writeln!(wtr, "<h2 id=\"summary\">Summary</h2>")?;
+    writeln!(
+        wtr,
+        "<p>Below are two tables summarizing the results of regex engines \
+         benchmarked. The first table ranks regex engines based on search \
+         time. The second table ranks regex engines based on compile time. \
+         Failed measurements don't contribute to the summary score, so a \
+         regex engine with errors is ranked on fewer benchmarks than it was \
+         run on.</p>"
+    )?;
+    writeln!(
+        wtr,
+        "<p>The summary statistic used is the \
+         <a href=\"https://dl.acm.org/doi/pdf/10.1145/5666.5673\">geometric \
+         mean</a> of the speed ratios for each regex engine across all \
+         benchmarks that include it. The ratios within each benchmark are \
+         computed from the {stat} of all timing samples taken, and dividing \
+         it by the best {stat} of the regex engines that participated in the \
+         benchmark.</p>",
+        stat = config.stat,
+    )?;
+    if config.confidence {
+        writeln!(
+            wtr,
+            "<p>Each geometric mean is followed by a 95% confidence \
+             interval. Regex engines with fewer than {min} benchmarks are \
+             marked with {marker}, since their intervals tend to be too \
+             narrow to be trusted.</p>",
+            min = EngineSummary::MIN_CONFIDENCE_COUNT,
+            marker = EngineSummary::LOW_SAMPLE_MARKER,
+        )?;
+    }
+    if !rankings.search.is_empty() {
+        writeln!(wtr, "<h3>Summary of search-time benchmarks</h3>")?;
+        html_summary_table(
+            config,
+            engines,
+            &rankings.search,
+            &rankings.errors_search,
+            Some(&rankings.search_tputs),
+            baseline.map(|b| &b.geomeans_search),
+            &mut wtr,
+        )?;
+    }
+    if !rankings.compile.is_empty() {
+        writeln!(wtr, "<h3>Summary of compile-time benchmarks</h3>")?;
+        html_summary_table(
+            config,
+            engines,
+            &rankings.compile,
+            &rankings.errors_compile,
+            None,
+            baseline.map(|b| &b.geomeans_compile),
+            &mut wtr,
+        )?;
+    }
+    Ok(())
+}
+
+/// Like `markdown_summary_table`, but writes an HTML table. Every cell with
+/// a number gets a `data-value` attribute so that it's sorted numerically.
+fn html_summary_table<W: Write>(
+    config: &Config,
+    engines: &Engines,
+    summaries: &[EngineSummary],
+    errors: &BTreeMap<String, usize>,
+    tputs: Option<&BTreeMap<String, Throughput>>,
+    base_geomeans: Option<&BTreeMap<String, f64>>,
+    mut wtr: W,
+) -> anyhow::Result<()> {
+    writeln!(wtr, "<table class=\"sortable\">")?;
+    writeln!(wtr, "<thead>")?;
+    write!(
+        wtr,
+        "<tr><th>Engine</th><th>Version</th>\
+         <th>Geometric mean of speed ratios</th><th>Benchmark count</th>\
+         <th>Errors</th>"
+    )?;
+    if tputs.is_some() {
+        write!(wtr, "<th>Median throughput</th>")?;
+    }
+    if base_geomeans.is_some() {
+        write!(wtr, "<th>Change vs baseline</th>")?;
+    }
+    writeln!(wtr, "</tr>")?;
+    writeln!(wtr, "</thead>")?;
+    writeln!(wtr, "<tbody>")?;
+    for summary in summaries.iter() {
+        if summary.count == 0 {
+            continue;
+        }
+        let name = html_escape(&summary.name);
+        let linkdir = engines
+            .by_name
+            .get(&summary.name)
+            .and_then(|e| e.run.cwd.as_ref());
+        match linkdir {
+            None => write!(wtr, "<tr><td>{}</td>", name)?,
+            Some(dir) => write!(
+                wtr,
+                "<tr><td><a href=\"{}\">{}</a></td>",
+                html_escape(&config.href(dir)),
+                name,
+            )?,
+        }
+        write!(wtr, "<td>{}", html_escape(&summary.version))?;
+        if let Some(ref metadata) = summary.metadata {
+            write!(wtr, " ({})", html_escape(metadata))?;
+        }
+        let errors = errors.get(&summary.name).copied().unwrap_or(0);
+        write!(
+            wtr,
+            "</td><td data-value=\"{}\">{}</td>\
+             <td data-value=\"{count}\">{count}</td>\
+             <td data-value=\"{errors}\">{errors}</td>",
+            summary.geomean,
+            summary.geomean_display(config.confidence),
+            count = summary.count,
+            errors = errors,
+        )?;
+        if let Some(tputs) = tputs {
+            match tputs.get(&summary.name) {
+                None => write!(wtr, "<td>-</td>")?,
+                Some(tput) => write!(
+                    wtr,
+                    "<td data-value=\"{}\">{}</td>",
+                    tput.bytes_per_second(),
+                    tput,
+                )?,
+            }
+        }
+        if let Some(base_geomeans) = base_geomeans {
+            let trend = base_geomeans
+                .get(&summary.name)
+                .and_then(|&old| Trend::geomean(config, summary.geomean, old));
+            match trend {
+                None => write!(wtr, "<td>-</td>")?,
+                Some(trend) => write!(
+                    wtr,
+                    "<td{} data-value=\"{}\">{}</td>",
+                    if trend.regressed { " class=\"regressed\"" } else { "" },
+                    trend.percent,
+                    trend,
+                )?,
+            }
+        }
+        writeln!(wtr, "</tr>")?;
+    }
+    writeln!(wtr, "</tbody>")?;
+    writeln!(wtr, "</table>")?;
+    Ok(())
+}
+
+fn html_results<W: Write>(
+    config: &Config,
+    analysis: &BTreeMap<String, String>,
+    skipped: &Skipped,
+    baseline: Option<&Baseline>,
+    tree: &Tree,
+    mut wtr: W,
+) -> anyhow::Result<()> {
+    let mut anchors = HtmlAnchors::new();
+    tree.flattened_depth_first(|tree, depth| {
+        let Tree::Node { ref name, ref children } = *tree else {
+            return Ok(());
+        };
+        // HTML only has 6 levels of headings, so deeper groups share the
+        // last level.
+        let level = std::cmp::min(depth + 2, 6);
+        let nice_name = nice_name(name);
+        writeln!(
+            wtr,
+            "<h{level} id=\"{}\">{}</h{level}>",
+            anchors.id(&nice_name),
+            html_escape(&nice_name),
+            level = level,
+        )?;
+        if children.iter().all(Tree::is_leaf) {
+            let mut defms = vec![];
+            for c in children.iter() {
+                let defm = match *c {
+                    Tree::Leaf(ref defm) => &**defm,
+                    Tree::Node { .. } => unreachable!(),
+                };
+                defms.push(defm);
+            }
+            html_result_group(
+                config, analysis, skipped, baseline, &defms, &mut wtr,
+            )?
+        }
+        Ok(())
+    })
+}
+
+fn html_result_group<W: Write>(
+    config: &Config,
+    analysis: &BTreeMap<String, String>,
+    skipped: &Skipped,
+    baseline: Option<&Baseline>,
+    groups: &[&ByBenchmarkNameGroup<Definition>],
+    wtr: &mut W,
+) -> anyhow::Result<()> {
+    if groups.is_empty() {
+        writeln!(wtr, "<p>NO MEASUREMENTS TO REPORT</p>")?;
+        return Ok(());
+    }
+    if let Some(analysis) = analysis.get(&groups[0].data.name.group) {
+        html_paragraphs(analysis, wtr)?;
+    }
+
+    writeln!(wtr, "<table class=\"sortable\">")?;
+    writeln!(wtr, "<thead>")?;
+    write!(wtr, "<tr><th>Engine</th>")?;
+    for group in groups.iter() {
+        write!(wtr, "<th>{}</th>", html_escape(&group.data.name.local))?;
+    }
+    writeln!(wtr, "</tr>")?;
+    writeln!(wtr, "</thead>")?;
+    writeln!(wtr, "<tbody>")?;
+
+    let skipped_in = |group: &ByBenchmarkNameGroup<Definition>| {
+        skipped.get(group.data.name.as_str())
+    };
+    let mut any_regressed = false;
+    let mut engines = BTreeSet::new();
+    for group in groups.iter() {
+        for e in group.by_engine.keys() {
+            engines.insert(e.clone());
+        }
+        for e in skipped_in(group).into_iter().flat_map(|s| s.keys()) {
+            engines.insert(e.clone());
+        }
+    }
+    for e in engines.iter() {
+        write!(wtr, "<tr><td>{}</td>", html_escape(e))?;
+        for group in groups.iter() {
+            let m = match group.by_engine.get(e) {
+                None => {
+                    if skipped_in(group).is_some_and(|s| s.contains_key(e)) {
+                        write!(wtr, "<td>n/a</td>")?;
+                    } else {
+                        write!(wtr, "<td>-</td>")?;
+                    }
+                    continue;
+                }
+                Some(m) => m,
+            };
+            // Sorting by the ratio is the same as sorting by the value
+            // shown, but works regardless of the units.
+            let ratio = group.ratio(e, config.stat).unwrap();
+            let is_best = e == group.best(config.stat);
+            if is_best {
+                write!(wtr, "<td class=\"best\" data-value=\"{}\">", ratio)?;
+                write!(wtr, "<strong>")?;
+            } else {
+                write!(wtr, "<td data-value=\"{}\">", ratio)?;
+            }
+            write!(
+                wtr,
+                "{}",
+                html_escape(&format_measurement(config, group, m))
+            )?;
+            if config.ratio {
+                write!(wtr, " ({:.2}x)", ratio)?;
+            }
+            let trend = baseline
+                .and_then(|b| b.get(&m.name, &m.engine))
+                .and_then(|base| {
+                    let is_rate = is_rate(config, m, base);
+                    Trend::new(config, is_rate, m, base)
+                });
+            if let Some(trend) = trend {
+                any_regressed = any_regressed || trend.regressed;
+                if trend.regressed {
+                    write!(
+                        wtr,
+                        " <span class=\"regressed\">({})</span>",
+                        trend
+                    )?;
+                } else {
+                    write!(wtr, " ({})", trend)?;
+                }
+            }
+            if is_best {
+                write!(wtr, "</strong>")?;
+            }
+            write!(wtr, "</td>")?;
+        }
+        writeln!(wtr, "</tr>")?;
+    }
+    writeln!(wtr, "</tbody>")?;
+    // The row with the best measurement for each benchmark goes in the
+    // footer, so that it stays at the bottom when the table is sorted.
+    writeln!(wtr, "<tfoot>")?;
+    write!(wtr, "<tr><td><em>best</em></td>")?;
+    for group in groups.iter() {
+        if group.by_engine.is_empty() {
+            write!(wtr, "<td>-</td>")?;
+            continue;
+        }
+        let m = &group.by_engine[group.best(config.stat)];
+        write!(
+            wtr,
+            "<td>{}</td>",
+            html_escape(&format_measurement(config, group, m)),
+        )?;
+    }
+    writeln!(wtr, "</tr>")?;
+    writeln!(wtr, "</tfoot>")?;
+    writeln!(wtr, "</table>")?;
+    let any_partial =
+        groups.iter().flat_map(|g| g.by_engine.values()).any(|m| m.partial);
+    if any_partial {
+        writeln!(
+            wtr,
+            "<p>{} The runner stopped early because it was close to timing \
+             out, so this measurement is based on fewer samples than \
+             requested.</p>",
+            html_escape(Measurement::PARTIAL_MARKER),
+        )?;
+    }
+    if any_regressed {
+        writeln!(
+            wtr,
+            "<p>{} This result regressed by more than {}% versus the \
+             baseline.</p>",
+            Trend::REGRESSION_MARKER,
+            config.trend_threshold,
+        )?;
+    }
+    if groups.iter().any(|g| !g.variants.is_empty()) {
+        writeln!(
+            wtr,
+            "<p><em>Note:</em> aliased regex engines show their best \
+             variant for each benchmark, which is given by the \
+             <code>variant</code> parameters below.</p>",
+        )?;
+    }
+
+    writeln!(wtr, "<details>")?;
+    writeln!(wtr, "<summary>Show individual benchmark parameters.</summary>")?;
+    for group in groups.iter() {
+        let def = &group.data;
+        let code = |v: &str| format!("<code>{}</code>", html_escape(v));
+        let link = |display: &str, path: &str| {
+            format!(
+                "<a href=\"{}\">{}</a>",
+                html_escape(&config.href(path)),
+                code(display),
+            )
+        };
+
+        writeln!(
+            wtr,
+            "<p><strong>{}</strong></p>",
+            html_escape(&def.name.local)
+        )?;
+        writeln!(wtr, "<table>")?;
+        writeln!(wtr, "<thead>")?;
+        writeln!(wtr, "<tr><th>Parameter</th><th>Value</th></tr>")?;
+        writeln!(wtr, "</thead>")?;
+        writeln!(wtr, "<tbody>")?;
+        html_param(wtr, "full name", &code(def.name.as_str()))?;
+        html_param(
+            wtr,
+            "model",
+            &link(&def.model, &format!("MODELS.md#{}", def.model)),
+        )?;
+        if let Some(ref path) = def.regex_path {
+            html_param(
+                wtr,
+                "regex-path",
+                &link(path, &format!("benchmarks/regexes/{}", path)),
+            )?;
+        } else if def.regexes.is_empty() {
+            html_param(wtr, "regex", "NONE")?;
+        } else if def.regexes.len() == 1 {
+            html_param(wtr, "regex", &code(&def.regexes[0]))?;
+        } else {
+            for (i, re) in def.regexes.iter().enumerate() {
+                html_param(wtr, &format!("regex({})", i), &code(re))?;
+            }
+        }
+        html_param(
+            wtr,
+            "case-insensitive",
+            &code(&def.options.case_insensitive.to_string()),
+        )?;
+        html_param(wtr, "unicode", &code(&def.options.unicode.to_string()))?;
+        // These are rarely used, so we only show them when they're enabled.
+        if def.options.multiline {
+            html_param(wtr, "multiline", &code("true"))?;
+        }
+        if def.options.dot_all {
+            html_param(wtr, "dot-all", &code("true"))?;
+        }
+        if let Some(start) = def.options.search_start {
+            html_param(wtr, "search-start", &code(&start.to_string()))?;
+        }
+        if let Some(end) = def.options.search_end {
+            html_param(wtr, "search-end", &code(&end.to_string()))?;
+        }
+        if let Some(max) = def.options.max_count {
+            html_param(wtr, "max-count", &code(&max.to_string()))?;
+        }
+        for (name, value) in def.params.iter() {
+            writeln!(
+                wtr,
+                "<tr><td>param({})</td><td>{}</td></tr>",
+                code(name),
+                code(value),
+            )?;
+        }
+        if let Some(ref path) = def.haystack_path {
+            html_param(
+                wtr,
+                "haystack-path",
+                &link(path, &format!("benchmarks/haystacks/{}", path)),
+            )?;
+        } else {
+            const LIMIT: usize = 60;
+            let haystack = &def.haystack;
+            let value = if haystack.len() > LIMIT {
+                format!("{} [.. snip ..]", haystack[..LIMIT].as_bstr())
+            } else {
+                haystack.as_bstr().to_string()
+            };
+            html_param(wtr, "haystack", &code(&value))?;
+        }
+        for ec in def.count.iter() {
+            let mut value = match ec.per_pattern {
+                None => ec.count.to_string(),
+                Some(ref counts) => format!("{:?}", counts),
+            };
+            if let Some(ref semantics) = ec.semantics {
+                value = format!("{} ({})", value, code(semantics));
+            }
+            let name = match ec.version {
+                None => format!("count({})", code(&ec.engine)),
+                Some(ref req) => format!(
+                    "count({}, {})",
+                    code(&ec.engine),
+                    code(&req.to_string()),
+                ),
+            };
+            writeln!(wtr, "<tr><td>{}</td><td>{}</td></tr>", name, value)?;
+        }
+        for (name, m) in group.by_engine.iter() {
+            if let Some(n) = m.warmup_iters {
+                writeln!(
+                    wtr,
+                    "<tr><td>warmup-iters({})</td><td>{}</td></tr>",
+                    code(name),
+                    n,
+                )?;
+            }
+        }
+        for (alias, engine) in group.variants.iter() {
+            writeln!(
+                wtr,
+                "<tr><td>variant({})</td><td>{}</td></tr>",
+                code(alias),
+                code(engine),
+            )?;
+        }
+        for (name, reason) in skipped_in(group).into_iter().flatten() {
+            writeln!(
+                wtr,
+                "<tr><td>skipped({})</td><td>{}</td></tr>",
+                code(name),
+                html_escape(reason),
+            )?;
+        }
+        writeln!(wtr, "</tbody>")?;
+        writeln!(wtr, "</table>")?;
+        html_count_footnote(group, wtr)?;
+        if let Some(ref analysis) = def.analysis {
+            html_paragraphs(analysis, wtr)?;
+        }
+    }
+    writeln!(wtr, "</details>")?;
+    Ok(())
+}
+
+/// Writes a row of a benchmark's parameter table, where the name is plain
+/// text and the value is already HTML.
+fn html_param<W: Write>(
+    wtr: &mut W,
+    name: &str,
+    value: &str,
+) -> anyhow::Result<()> {
+    writeln!(
+        wtr,
+        "<tr><td>{}</td><td>{}</td></tr>",
+        html_escape(name),
+        value
+    )?;
+    Ok(())
+}
+
+/// Like `markdown_count_footnote`, but writes HTML.
+fn html_count_footnote<W: Write>(
+    group: &ByBenchmarkNameGroup<Definition>,
+    wtr: &mut W,
+) -> anyhow::Result<()> {
+    let Some(by_count) = count_variants(group) else { return Ok(()) };
+    write!(
+        wtr,
+        "<p><em>Note:</em> engines were verified against different counts:"
+    )?;
+    for (i, ((count, semantics), names)) in by_count.iter().enumerate() {
+        let sep = if i == 0 { "" } else { ";" };
+        let names = names
+            .iter()
+            .map(|n| format!("<code>{}</code>", html_escape(n)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(wtr, "{} {} for {}", sep, count, names)?;
+        if let Some(semantics) = semantics {
+            write!(wtr, " (<code>{}</code>)", html_escape(semantics))?;
+        }
+    }
+    writeln!(wtr, ".</p>")?;
+    Ok(())
+}
+
+/// Like `markdown_errors`, but writes an HTML table.
+fn html_errors<W: Write>(
+    errors: &[Measurement],
+    mut wtr: W,
+) -> anyhow::Result<()> {
+    writeln!(wtr, "<h2 id=\"errors\">Errors</h2>")?;
+    if errors.is_empty() {
+        writeln!(wtr, "<p>NO ERRORS TO REPORT</p>")?;
+        return Ok(());
+    }
+    writeln!(wtr, "<table class=\"sortable\">")?;
+    writeln!(wtr, "<thead>")?;
+    writeln!(wtr, "<tr><th>Benchmark</th><th>Engine</th><th>Error</th></tr>")?;
+    writeln!(wtr, "</thead>")?;
+    writeln!(wtr, "<tbody>")?;
+    for m in errors.iter() {
+        let err = m.err.as_deref().unwrap_or_default().replace('\n', " ");
+        writeln!(
+            wtr,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            html_escape(&m.name),
+            html_escape(&m.engine),
+            html_escape(err.trim()),
+        )?;
+    }
+    writeln!(wtr, "</tbody>")?;
+    writeln!(wtr, "</table>")?;
+    Ok(())
+}
+
+/// Writes the given text as a sequence of HTML paragraphs, where paragraphs
+/// are separated by blank lines. The text is usually the analysis from a
+/// benchmark definition, which is written in Markdown. But since it's mostly
+/// prose, it's shown as plain text instead of trying to render it.
+fn html_paragraphs<W: Write>(text: &str, wtr: &mut W) -> anyhow::Result<()> {
+    let mut paragraph: Vec<&str> = vec![];
+    for line in text.trim().lines().chain(std::iter::once("")) {
+        if !line.trim().is_empty() {
+            paragraph.push(line.trim());
+            continue;
+        }
+        if !paragraph.is_empty() {
+            writeln!(wtr, "<p>{}</p>", html_escape(&paragraph.join("\n")))?;
+            paragraph.clear();
+        }
+    }
+    Ok(())
+}
+
+/// Escapes the given text so that it can be used in HTML, including in
+/// attribute values.
+fn html_escape(v: &str) -> String {
+    let mut escaped = String::with_capacity(v.len());
+    for ch in v.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Splices the given report into the given file path, replacing the region
+/// with the given name (or the unnamed region when `region` is `None`). This
+/// returns an error if reading or writing the file fails, or if the report
+/// isn't valid UTF-8, or if the markers in the file are invalid. See
+/// `splice_str` for details.
+fn splice(
+    path: &Path,
+    region: Option<&str>,
+    report: &[u8],
+) -> anyhow::Result<()> {
+    let src = std::fs::read_to_string(path)
+        .with_context(|| path.display().to_string())?;
+    let report = report.to_str().context("report is not valid UTF-8")?;
+    let out = splice_str(&src, region, report).with_context(|| {
+        format!("failed to splice into {}", path.display())
+    })?;
+    std::fs::write(path, &out).with_context(|| path.display().to_string())?;
+    Ok(())
+}
+
+/// Replaces every line between the BEGIN and END markers of the given region
+/// in `src` with `report`, and returns the result.
+///
+/// The markers for the unnamed region are `<!-- BEGIN: report -->` and
+/// `<!-- END: report -->`. The markers for a region named `foo` are
+/// `<!-- BEGIN: report(foo) -->` and `<!-- END: report(foo) -->`. Each marker
+/// must be on its own line.
+///
+/// Every region in `src` is checked, not just the one being replaced. An
+/// error is returned if regions are nested, if a BEGIN marker doesn't have a
+/// matching END marker (or vice versa) or if a region appears more than once.
+/// An error is also returned if the requested region doesn't exist.
+fn splice_str(
+    src: &str,
+    region: Option<&str>,
+    report: &str,
+) -> anyhow::Result<String> {
+    /// A region found in `src`.
+    struct Found<'a> {
+        name: Option<&'a str>,
+        /// The line number of the BEGIN marker.
+        line: usize,
+        /// The byte offsets of the lines between the markers.
+        start: usize,
+        end: usize,
+    }
+
+    fn label(name: Option<&str>) -> String {
+        match name {
+            None => "the unnamed report region".to_string(),
+            Some(name) => format!("report region '{}'", name),
+        }
+    }
+
+    let re = regex!(r"^<!-- (BEGIN|END): report(?:\(([^)]*)\))? -->$");
+    let mut found: Vec<Found> = vec![];
+    let mut open: Option<Found> = None;
+    let mut offset = 0;
+    for (i, line) in src.split_inclusive('\n').enumerate() {
+        let (lineno, line_start) = (i + 1, offset);
+        offset += line.len();
+        let Some(caps) = re.captures(line.trim_end()) else { continue };
+        let name = caps.get(2).map(|m| m.as_str());
+        if &caps[1] == "BEGIN" {
+            if let Some(ref o) = open {
+                anyhow::bail!(
+                    "line {}: found BEGIN marker for {} inside of {} \
+                     (which begins on line {}), but report regions \
+                     cannot be nested",
+                    lineno,
+                    label(name),
+                    label(o.name),
+                    o.line,
+                );
+            }
+            if let Some(f) = found.iter().find(|f| f.name == name) {
+                anyhow::bail!(
+                    "line {}: found BEGIN marker for {}, but it already \
+                     appeared on line {}",
+                    lineno,
+                    label(name),
+                    f.line,
+                );
+            }
+            open = Some(Found { name, line: lineno, start: offset, end: 0 });
+            continue;
+        }
+        let Some(mut o) = open.take() else {
+            anyhow::bail!(
+                "line {}: found END marker for {} without a \
+                 preceding BEGIN marker",
+                lineno,
+                label(name),
+            );
+        };
+        anyhow::ensure!(
+            o.name == name,
+            "line {}: found END marker for {}, but expected the END marker \
+             for {} (which begins on line {})",
+            lineno,
+            label(name),
+            label(o.name),
+            o.line,
+        );
+        o.end = line_start;
+        found.push(o);
+    }
+    if let Some(o) = open {
+        anyhow::bail!(
+            "line {}: BEGIN marker for {} has no matching END marker",
+            o.line,
+            label(o.name),
+        );
+    }
+    let Some(f) = found.iter().find(|f| f.name == region) else {
+        anyhow::bail!("could not find markers for {}", label(region));
+    };
+    let mut out = String::new();
+    out.push_str(&src[..f.start]);
+    out.push_str(report);
+    out.push_str(&src[f.end..]);
+    Ok(out)
+}
+
+/// Formats the name of something by applying various conventions used in
+/// benchmark definitions.
+fn nice_name(name: &str) -> String {
+    let re = regex!(r"^([0-9]+-)");
+    re.replace(name, "").into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        args::Filters,
+        format::{benchmarks::Engines, measurement::read_fixture},
+    };
+
+    use super::*;
+
+    const DEFINITIONS: &str = r#"
+[[bench]]
+model = "count"
+name = "big"
+regex = "a"
+haystack = "aaaaaaaaaa"
+count = 10
+engines = ["one", "two", "three"]
+
+[[bench]]
+model = "count"
+name = "small"
+regex = "a"
+haystack = "a"
+count = 1
+engines = ["one", "two"]
+"#;
+
+    const MEASUREMENTS: &str = "\
+fixture/big,count,0.1.0,one,1.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+fixture/big,count,0.1.0,two,2.0.0,,10,5,1s,20ns,0ns,20ns,0ns,20ns,20ns
+fixture/big,count,0.1.0,three,3.0.0,,10,5,1s,5ns,0ns,5ns,0ns,5ns,5ns
+fixture/small,count,0.1.0,one,1.0.0,,1,5,1s,4ns,0ns,4ns,0ns,4ns,4ns
+fixture/small,count,0.1.0,two,2.0.0,,1,5,1s,2ns,0ns,2ns,0ns,2ns,2ns
+";
+
+    /// Returns the fixture definitions.
+    fn fixture_benchmarks() -> Benchmarks {
+        let engines = Engines::fake(["one", "two", "three"]);
+        let filters = Filters::default();
+        Benchmarks::from_slice(&engines, &filters, "fixture", DEFINITIONS)
+            .unwrap()
+    }
+
+    /// Returns the fixture measurements grouped by benchmark and associated
+    /// with the fixture definitions.
+    fn fixture() -> ByBenchmarkName<Definition> {
+        let defs = fixture_benchmarks().defs;
+        let measurements = read_fixture(MEASUREMENTS);
+        ByBenchmarkName::new(&measurements).unwrap().associate(defs).unwrap()
+    }
+
+    /// Returns a baseline for the fixture, where 'one' got slower on 'big',
+    /// 'two' is unchanged on 'small' and 'three' is missing.
+    fn fixture_baseline() -> Baseline {
+        let measurements = read_fixture(
+            "\
+fixture/big,count,0.1.0,one,1.0.0,,10,5,1s,8ns,0ns,8ns,0ns,8ns,8ns
+fixture/big,count,0.1.0,two,2.0.0,,10,5,1s,20ns,0ns,20ns,0ns,20ns,20ns
+fixture/small,count,0.1.0,one,1.0.0,,1,5,1s,4ns,0ns,4ns,0ns,4ns,4ns
+fixture/small,count,0.1.0,two,2.0.0,,1,5,1s,2ns,0ns,2ns,0ns,2ns,2ns
+",
+        );
+        Baseline::new(measurements, &fixture(), Stat::Median).unwrap()
+    }
+
+    /// Renders the table of results (without the benchmark parameters) for
+    /// the fixture using the given units, annotated with changes versus the
+    /// given baseline.
+    fn result_table(
+        units: Units,
+        skipped: &Skipped,
+        baseline: Option<&Baseline>,
+    ) -> String {
+        let config =
+            Config { units, trend_threshold: 5.0, ..Config::default() };
+        let grouped = fixture();
+        let groups: Vec<&ByBenchmarkNameGroup<Definition>> =
+            grouped.groups.iter().collect();
+        let mut out = vec![];
+        markdown_result_group(
+            &config,
+            &BTreeMap::new(),
+            skipped,
+            baseline,
+            &groups,
+            &mut out,
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        out.split("<details>").next().unwrap().to_string()
+    }
+
+    #[test]
+    fn result_group_time() {
+        let expected = "\
+| Engine | big | small |
+| - | - | - |
+| one | 10.00ns | 4.00ns |
+| three | **5.00ns** | - |
+| two | 20.00ns | **2.00ns** |
+| _best_ | 5.00ns | 2.00ns |
+
+";
+        assert_eq!(expected, result_table(Units::Time, &Skipped::new(), None));
+    }
+
+    #[test]
+    fn result_group_skipped() {
+        let mut skipped = Skipped::new();
+        skipped.entry("fixture/small".to_string()).or_default().insert(
+            "three".to_string(),
+            "case insensitive search is unsupported".to_string(),
+        );
+        let expected = "\
+| Engine | big | small |
+| - | - | - |
+| one | 10.00ns | 4.00ns |
+| three | **5.00ns** | n/a |
+| two | 20.00ns | **2.00ns** |
+| _best_ | 5.00ns | 2.00ns |
+
+";
+        assert_eq!(expected, result_table(Units::Time, &skipped, None));
+    }
+
+    #[test]
+    fn result_group_throughput() {
+        let expected = "\
+| Engine | big | small |
+| - | - | - |
+| one | 953.7 MB/s | 238.4 MB/s |
+| three | **1907.3 MB/s** | - |
+| two | 476.8 MB/s | **476.8 MB/s** |
+| _best_ | 1907.3 MB/s | 476.8 MB/s |
+
+";
+        assert_eq!(
+            expected,
+            result_table(Units::Throughput, &Skipped::new(), None)
+        );
+    }
+
+    #[test]
+    fn result_group_baseline() {
+        let baseline = fixture_baseline();
+        let expected = "\
+| Engine | big | small |
+| - | - | - |
+| one | 10.00ns (+25% ⚠) | 4.00ns (+0%) |
+| three | **5.00ns** | - |
+| two | 20.00ns (+0%) | **2.00ns (+0%)** |
+| _best_ | 5.00ns | 2.00ns |
+
+⚠ This result regressed by more than 5% versus the baseline.
+
+";
+        let got = result_table(Units::Time, &Skipped::new(), Some(&baseline));
+        assert_eq!(expected, got);
+
+        // In throughput, the same regression is a decrease.
+        let got =
+            result_table(Units::Throughput, &Skipped::new(), Some(&baseline));
+        assert!(got.contains("| one | 953.7 MB/s (-20% ⚠) |"), "{}", got);
+    }
+
+    #[test]
+    fn summary_table_baseline() {
+        let config = Config { trend_threshold: 5.0, ..Config::default() };
+        let grouped = fixture();
+        let summaries = grouped.ranking(config.stat).unwrap();
+        let baseline = fixture_baseline();
+        let mut out = vec![];
+        markdown_summary_table(
+            &config,
+            &Engines::default(),
+            &summaries,
+            &BTreeMap::new(),
+            None,
+            Some(&baseline.geomeans_search),
+            &mut out,
+        )
+        .unwrap();
+        // In the baseline, 'one' was the best on 'big'. Now 'three' is, which
+        // makes both 'one' and 'two' look worse relative to the best engine.
+        // 'three' wasn't in the baseline, so it gets no annotation.
+        let expected = "\
+| Engine | Version | Geometric mean of speed ratios | Benchmark count | Errors | Change vs baseline |
+| ------ | ------- | ------------------------------ | --------------- | ------ | ------------------ |
+| three | 3.0.0 | 1.00 | 1 | 0 | - |
+| two | 2.0.0 | 2.00 | 2 | 0 | +26% ⚠ |
+| one | 1.0.0 | 2.00 | 2 | 0 | +41% ⚠ |
+
+";
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn summary_table_throughput() {
+        let config = Config::default();
+        let grouped = fixture();
+        let summaries = grouped.ranking(config.stat).unwrap();
+        let tputs = grouped.median_throughputs(config.stat);
+        let mut out = vec![];
+        markdown_summary_table(
+            &config,
+            &Engines::default(),
+            &summaries,
+            &BTreeMap::from([("two".to_string(), 3)]),
+            Some(&tputs),
+            None,
+            &mut out,
+        )
+        .unwrap();
+        let expected = "\
+| Engine | Version | Geometric mean of speed ratios | Benchmark count | Errors | Median throughput |
+| ------ | ------- | ------------------------------ | --------------- | ------ | ----------------- |
+| three | 3.0.0 | 1.00 | 1 | 0 | 1907.3 MB/s |
+| two | 2.0.0 | 2.00 | 2 | 3 | 476.8 MB/s |
+| one | 1.0.0 | 2.00 | 2 | 0 | 596.0 MB/s |
+
+";
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn summary_table_confidence() {
+        let config = Config { confidence: true, ..Config::default() };
+        let grouped = fixture();
+        let summaries = grouped.ranking(config.stat).unwrap();
+        let mut out = vec![];
+        markdown_summary_table(
+            &config,
+            &Engines::default(),
+            &summaries,
+            &BTreeMap::new(),
+            None,
+            None,
+            &mut out,
+        )
+        .unwrap();
+        let got = String::from_utf8(out).unwrap();
+        assert!(
+            got.contains("| three | 3.0.0 | 1.00 [1.00, 1.00]† |"),
+            "{}",
+            got
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let m =
+            |name: &str, model: &str, engine: &str, err: &str| Measurement {
+                name: name.to_string(),
+                model: model.to_string(),
+                engine: engine.to_string(),
+                err: Some(err.to_string()),
+                ..Measurement::default()
+            };
+        let errors = vec![
+            m("fixture/big", "count", "one", "count mismatch"),
+            m("fixture/big", "compile", "one", "oops"),
+            m("fixture/small", "count", "one", "a|b\nfailed"),
+            m("fixture/small", "count", "two", "skipped: no (?i)"),
+        ];
+
+        let (compile, search) = error_counts(&errors);
+        assert_eq!(BTreeMap::from([("one".to_string(), 1)]), compile);
+        assert_eq!(BTreeMap::from([("one".to_string(), 2)]), search);
+
+        let mut out = vec![];
+        markdown_errors(&errors, &mut out).unwrap();
+        let expected = "\
+### Errors
+
+| Benchmark | Engine | Error |
+| - | - | - |
+| fixture/big | one | count mismatch |
+| fixture/big | one | oops |
+| fixture/small | one | a\\|b failed |
+| fixture/small | two | skipped: no (?i) |
+
+";
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+    }
+
+    /// Tests that the HTML report for the fixture matches the one checked
+    /// in, except for the command that generated it and the version of
+    /// rebar. To update it after an intentional change, write the output
+    /// of this test to 'testdata/report/fixture.html'.
+    #[test]
+    fn html_golden() {
+        let config = Config {
+            ratio: true,
+            relative_path_root: "../".to_string(),
+            trend_threshold: 5.0,
+            ..Config::default()
+        };
+        let baseline = fixture_baseline();
+        let skipped = Measurement {
+            name: "fixture/small".to_string(),
+            model: "count".to_string(),
+            engine: "three".to_string(),
+            err: Some("skipped: no support for <(?i)>".to_string()),
+            ..Measurement::default()
+        };
+        let mut out = vec![];
+        html(
+            &config,
+            &fixture_benchmarks(),
+            fixture(),
+            &[skipped],
+            &RebarVersions::default(),
+            Some(&baseline),
+            &mut out,
+        )
+        .unwrap();
+        let got = String::from_utf8(out).unwrap();
+        // The generated comment has the command on the line after this one.
+        let marker = "<!-- Generated with command: -->\n";
+        let start = got.find(marker).unwrap() + marker.len();
+        let end = start + got[start..].find('\n').unwrap() + 1;
+        let got = format!("{}{}", &got[..start], &got[end..])
+            .replace(&util::version(), "VERSION");
+        let expected = include_str!("testdata/report/fixture.html");
+        assert_eq!(expected, got);
+    }
+
+    #[test]
+    fn html_anchors() {
+        let mut anchors = HtmlAnchors::new();
+        assert_eq!("literal", anchors.id("literal"));
+        assert_eq!("literal-1", anchors.id("literal"));
+        assert_eq!("unicode-word", anchors.id("Unicode Word"));
+        assert_eq!("summary-1", anchors.id("summary"));
+    }
+
+    #[test]
+    fn html_splice() {
+        let args = ["--format", "html", "--splice", "README.md", "a.csv"];
+        let mut p = lexopt::Parser::from_args(args);
+        let err = Config::parse(&mut p).unwrap_err().to_string();
+        assert!(err.contains("--splice can only be used"), "{}", err);
+    }
+
+    const SPLICE_REGIONS: &str = include_str!("testdata/splice/regions.md");
+
+    fn splice_err(src: &str, region: Option<&str>) -> String {
+        format!("{:#}", splice_str(src, region, "new\n").unwrap_err())
+    }
+
+    #[test]
+    fn splice_unnamed() {
+        let got = splice_str(SPLICE_REGIONS, None, "new\n").unwrap();
+        assert!(got.contains("<!-- BEGIN: report -->\nnew\n<!-- END: report"));
+        assert!(!got.contains("old unnamed report"));
+        assert!(got.contains("old search report"));
+        assert!(got.contains("old compile report"));
+    }
+
+    #[test]
+    fn splice_named() {
+        let got = splice_str(SPLICE_REGIONS, Some("search"), "new\n").unwrap();
+        assert!(got.contains(
+            "<!-- BEGIN: report(search) -->\nnew\n<!-- END: report(search)"
+        ));
+        assert!(!got.contains("old search report"));
+        assert!(got.contains("old unnamed report"));
+        assert!(got.contains("old compile report"));
+
+        // Splicing again gives the same result.
+        assert_eq!(got, splice_str(&got, Some("search"), "new\n").unwrap());
+    }
+
+    #[test]
+    fn splice_missing_region() {
+        let err = splice_err(SPLICE_REGIONS, Some("nope"));
+        assert!(err.contains("report region 'nope'"), "{}", err);
+
+        let err = splice_err("# Results\n", None);
+        assert!(err.contains("the unnamed report region"), "{}", err);
+    }
+
+    #[test]
+    fn splice_nested() {
+        let src = include_str!("testdata/splice/nested.md");
+        let err = splice_err(src, Some("search"));
+        assert!(err.starts_with("line 4:"), "{}", err);
+        assert!(err.contains("cannot be nested"), "{}", err);
+        assert!(err.contains("begins on line 3"), "{}", err);
+    }
+
+    #[test]
+    fn splice_misordered() {
+        let src = include_str!("testdata/splice/misordered.md");
+        let err = splice_err(src, Some("search"));
+        assert!(err.starts_with("line 3:"), "{}", err);
+        assert!(err.contains("without a preceding BEGIN"), "{}", err);
+    }
+
+    #[test]
+    fn splice_mismatched() {
+        let src = include_str!("testdata/splice/mismatched.md");
+        let err = splice_err(src, Some("search"));
+        assert!(err.starts_with("line 5:"), "{}", err);
+        assert!(err.contains("report region 'compile'"), "{}", err);
+        assert!(err.contains("begins on line 3"), "{}", err);
+    }
+
+    #[test]
+    fn splice_duplicate() {
+        let src = include_str!("testdata/splice/duplicate.md");
+        let err = splice_err(src, Some("search"));
+        assert!(err.starts_with("line 7:"), "{}", err);
+        assert!(err.contains("already appeared on line 3"), "{}", err);
+    }
+
+    #[test]
+    fn splice_unclosed() {
+        let src = include_str!("testdata/splice/unclosed.md");
+        let err = splice_err(src, None);
+        assert!(err.starts_with("line 3:"), "{}", err);
+        assert!(err.contains("no matching END"), "{}", err);
+    }
+
+    #[test]
+    fn links_use_slashes() {
+        let config = Config {
+            relative_path_root: "..\\..\\".to_string(),
+            ..Config::default()
+        };
+        assert_eq!(
+            "../../benchmarks/regexes/sub/re.txt",
+            config.href("benchmarks/regexes/sub\\re.txt"),
+        );
+        assert_eq!(
+            "[`sub/hay.txt`](../../benchmarks/haystacks/sub/hay.txt)",
+            config.url("`sub/hay.txt`", "benchmarks\\haystacks\\sub/hay.txt"),
+        );
+    }
 }
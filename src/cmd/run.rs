@@ -0,0 +1,220 @@
+use std::{io::Write, path::PathBuf, time::Duration};
+
+use {
+    anyhow::Context,
+    lexopt::{Arg, ValueExt},
+};
+
+use crate::{
+    args::{self, Stat, Usage},
+    cmd::measure::{ExecBenchmark, ExecBenchmarkConfig},
+    format::benchmarks::{Benchmarks, LoadOptions},
+    util::ShortHumanDuration,
+};
+
+const USAGES: &[Usage] = &[
+    Usage::BENCH_DIR,
+    Usage::ALLOW_DOWNLOAD,
+    Usage::new(
+        "-e, --engine <engine>",
+        "The regex engine to run the benchmark with.",
+        r#"
+The regex engine to run the benchmark with. This flag is required.
+
+The engine given must be one of the engines listed in the benchmark
+definition.
+"#,
+    ),
+    Usage::MAX_ITERS,
+    Usage::MAX_WARMUP_ITERS,
+    Usage::MAX_TIME,
+    Usage::MAX_WARMUP_TIME,
+    Usage::new(
+        "-q, --quiet",
+        "Don't print a summary or the runner's stderr.",
+        r#"
+Don't print a summary of the samples collected to stderr when the benchmark is
+done. This also suppresses anything the runner program prints to stderr,
+unless it fails, in which case the last line it printed is included in the
+error message.
+
+Only the raw samples are printed to stdout in this mode.
+"#,
+    ),
+];
+
+fn usage_short() -> String {
+    format!(
+        "\
+Run a single benchmark with a single regex engine and print its samples.
+
+USAGE:
+    rebar run <benchmark-name> -e <engine>
+
+TIP:
+    use -h for short docs and --help for long docs
+
+OPTIONS:
+{options}
+",
+        options = Usage::short(USAGES),
+    )
+    .trim()
+    .to_string()
+}
+
+fn usage_long() -> String {
+    format!(
+        "\
+Run a single benchmark with a single regex engine and print its samples.
+
+This runs the given benchmark with the given regex engine exactly as 'rebar
+measure' would, except the raw samples are printed to stdout as the runner
+program writes them instead of being aggregated into a CSV row. Each sample is
+printed on its own line in the format used by runner programs, i.e.,
+'duration,count' with optional size and iteration fields. Once the runner
+program is done, a short human readable summary (iterations, median, min, max
+and throughput) is printed to stderr.
+
+This is meant as a quick way to profile or eyeball a single benchmark while
+working on a regex engine or a benchmark definition. Unlike 'rebar measure
+--hold', the samples are still collected and verified, so this command fails
+if the runner program reports the wrong count.
+
+The default limits are the same as for 'rebar measure', and can be changed
+with the --max-* flags.
+
+USAGE:
+    rebar run <benchmark-name> -e <engine>
+
+OPTIONS:
+{options}
+",
+        options = Usage::long(USAGES),
+    )
+    .trim()
+    .to_string()
+}
+
+pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
+    let mut bench_name = None;
+    let mut dir = PathBuf::from("benchmarks");
+    let mut load = LoadOptions::default();
+    let mut engine_name = None;
+    let mut config = ExecBenchmarkConfig::default();
+    let mut quiet = false;
+    while let Some(arg) = p.next()? {
+        match arg {
+            Arg::Value(name) => {
+                if bench_name.is_some() {
+                    anyhow::bail!(
+                        "only one benchmark name is accepted, \
+                         but multiple were given",
+                    );
+                }
+                bench_name = Some(name.string()?);
+            }
+            Arg::Short('h') => anyhow::bail!("{}", usage_short()),
+            Arg::Long("help") => anyhow::bail!("{}", usage_long()),
+            Arg::Short('d') | Arg::Long("dir") => {
+                dir = PathBuf::from(p.value().context("-d/--dir")?);
+            }
+            Arg::Long("allow-download") => {
+                load.allow_download = true;
+            }
+            Arg::Short('e') | Arg::Long("engine") => {
+                let value = p.value().context("-e/--engine")?;
+                engine_name = Some(value.string().context("-e/--engine")?);
+            }
+            Arg::Long("max-iters") => {
+                config.max_iters = args::parse(p, "--max-iters")?;
+                config.explicit.max_iters = true;
+            }
+            Arg::Long("max-warmup-iters") => {
+                config.max_warmup_iters =
+                    args::parse(p, "--max-warmup-iters")?;
+                config.explicit.max_warmup_iters = true;
+            }
+            Arg::Long("max-time") => {
+                let hdur = args::parse::<ShortHumanDuration>(p, "--max-time")?;
+                config.max_time = Duration::from(hdur);
+                config.explicit.max_time = true;
+            }
+            Arg::Long("max-warmup-time") => {
+                let hdur =
+                    args::parse::<ShortHumanDuration>(p, "--max-warmup-time")?;
+                config.max_warmup_time = Duration::from(hdur);
+                config.explicit.max_warmup_time = true;
+            }
+            Arg::Short('q') | Arg::Long("quiet") => {
+                quiet = true;
+            }
+            _ => return Err(arg.unexpected().into()),
+        }
+    }
+    let bench_name = match bench_name {
+        None => anyhow::bail!("missing benchmark name"),
+        Some(bench_name) => bench_name,
+    };
+    let engine_name = match engine_name {
+        None => anyhow::bail!("missing regex engine name (use -e/--engine)"),
+        Some(engine_name) => engine_name,
+    };
+    let def = Benchmarks::find_one(&dir, &bench_name, &load)?;
+    // This also makes sure a bigger time budget doesn't get cut short by the
+    // timeout.
+    let config = config.for_definition(&def);
+    let engine = match def.engines.iter().find(|e| e.name == engine_name) {
+        Some(engine) => engine.clone(),
+        None => anyhow::bail!(
+            "benchmark '{}' does not include engine '{}'",
+            def.name,
+            engine_name,
+        ),
+    };
+    let exec = ExecBenchmark::new(config, def, engine);
+
+    // We stop writing once stdout fails (usually because of a broken pipe),
+    // but we still let the runner program finish so that it's reaped and its
+    // samples get verified.
+    let mut out = std::io::stdout().lock();
+    let mut write_err = None;
+    let results = exec.collect_streaming(!quiet, |line| {
+        if write_err.is_some() {
+            return;
+        }
+        if let Err(err) = out.write_all(line).and_then(|_| out.flush()) {
+            write_err = Some(err);
+        }
+    })?;
+    if let Some(err) = write_err {
+        if err.kind() != std::io::ErrorKind::BrokenPipe {
+            return Err(anyhow::Error::from(err)
+                .context("failed to write samples to stdout"));
+        }
+    }
+    let m = results.to_measurement();
+    if let Some(ref err) = m.err {
+        anyhow::bail!("{}", err);
+    }
+    if quiet {
+        return Ok(());
+    }
+    let times = &m.aggregate.times;
+    let mut summary = format!(
+        "{name} ({engine} {version}): {iters} iterations, \
+         median {median}, min {min}, max {max}",
+        name = m.name,
+        engine = m.engine,
+        version = m.engine_version,
+        iters = m.iters,
+        median = ShortHumanDuration::from(times.median),
+        min = ShortHumanDuration::from(times.min),
+        max = ShortHumanDuration::from(times.max),
+    );
+    if let Some(tput) = m.throughput(Stat::Median) {
+        summary.push_str(&format!(", throughput {}", tput));
+    }
+    writeln!(std::io::stderr(), "{}", summary)?;
+    Ok(())
+}
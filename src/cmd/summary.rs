@@ -0,0 +1,455 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+    path::PathBuf,
+    time::Duration,
+};
+
+use crate::{
+    args::{self, Filter, Filters, Stat, Usage},
+    format::measurement::{
+        self, Measurement, MeasurementReader, VersionCheck,
+    },
+    grouped::ByBenchmarkName,
+    util::ShortHumanDuration,
+};
+
+/// The maximum number of distinct error messages included in a summary.
+const TOP_ERRORS: usize = 3;
+
+const USAGES: &[Usage] = &[
+    VersionCheck::USAGE_ALLOW_MISMATCH,
+    Filter::USAGE_ENGINE,
+    Filter::USAGE_ENGINE_NOT,
+    Filter::USAGE_BENCH,
+    Filter::USAGE_BENCH_NOT,
+    Usage::new(
+        "--format <name>",
+        "One of: text, json.",
+        r#"
+The format to print the summary in (default: text).
+
+The 'text' format prints the entire summary on a single line, which is meant
+to be read in CI logs. The 'json' format prints a JSON object with the same
+information, which is meant to be parsed by other programs.
+"#,
+    ),
+    Filter::USAGE_MODEL,
+    Filter::USAGE_MODEL_NOT,
+    VersionCheck::USAGE_REQUIRE,
+    Stat::USAGE,
+    MeasurementReader::USAGE_STRICT_DUPLICATES,
+];
+
+fn usage_short() -> String {
+    format!(
+        "\
+Print a one line summary of benchmark results.
+
+USAGE:
+    rebar summary [OPTIONS] <csv-path> ...
+
+TIP:
+    use -h for short docs and --help for long docs
+
+OPTIONS:
+{options}
+",
+        options = Usage::short(USAGES),
+    )
+    .trim()
+    .to_string()
+}
+
+fn usage_long() -> String {
+    format!(
+        "\
+Print a one line summary of benchmark results.
+
+This is meant for CI logs, where a full ranking or report is too much. The
+summary includes the number of benchmarks and regex engines, the number of
+measurements that failed along with their most frequent error messages, the
+total wall clock time spent measuring and the ranking of every regex engine.
+For example:
+
+    25 benchmarks, 3 engines, 2 errors (\"count mismatch\" x2), 65.00s total; \\
+    rust/regex=1.00(25) pcre2/jit=2.13(25) pcre2=4.51(24)
+
+(Although the summary is always printed on a single line.) Each regex engine
+in the ranking is shown as 'name=geomean(count)', where 'geomean' is the
+geometric mean of its speed ratios and 'count' is the number of benchmarks
+it participated in. This is the same as what 'rebar rank' computes.
+
+Benchmarks that a regex engine reported it can't run aren't counted as
+errors. Instead, they're counted as skipped.
+
+USAGE:
+    rebar summary [OPTIONS] <csv-path> ...
+
+    This command takes one or more file paths to CSV files written by the
+    'rebar measure' command.
+
+TIP:
+    use -h for short docs and --help for long docs
+
+OPTIONS:
+{options}
+",
+        options = Usage::long(USAGES),
+    )
+    .trim()
+    .to_string()
+}
+
+pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
+    let config = Config::parse(p)?;
+    let (measurements, errors, versions) = MeasurementReader {
+        paths: &config.csv_paths,
+        filters: &config.filters,
+        intersection: false,
+        labels: &[],
+        strict_duplicates: config.strict_duplicates,
+    }
+    .read_with_errors()?;
+    measurement::warn_errors(&errors);
+    config.version_check.check(&versions)?;
+
+    let summary = Summary::new(&measurements, &errors, config.stat)?;
+    let mut wtr = std::io::stdout().lock();
+    match config.format {
+        Format::Text => summary.write_text(&mut wtr)?,
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut wtr, &summary)?;
+            writeln!(wtr)?;
+        }
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// A summary of a set of measurements, including the ones with errors.
+#[derive(Clone, Debug, serde::Serialize)]
+struct Summary {
+    /// The number of distinct benchmark names.
+    benchmarks: usize,
+    /// The number of distinct regex engines.
+    engines: usize,
+    /// The number of measurements that failed. This doesn't include
+    /// benchmarks that a regex engine reported it can't run.
+    errors: usize,
+    /// The number of benchmarks that a regex engine reported it can't run.
+    skipped: usize,
+    /// The most frequent error messages, in descending order of frequency.
+    top_errors: Vec<ErrorCount>,
+    /// The sum of the wall clock time of every measurement.
+    #[serde(serialize_with = "ShortHumanDuration::serialize_with")]
+    total: Duration,
+    /// The ranking of regex engines, fastest first.
+    ranking: Vec<RankedEngine>,
+}
+
+/// An error message along with the number of measurements that failed with
+/// it.
+#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize)]
+struct ErrorCount {
+    message: String,
+    count: usize,
+}
+
+/// A single regex engine in the ranking of a summary.
+#[derive(Clone, Debug, serde::Serialize)]
+struct RankedEngine {
+    name: String,
+    version: String,
+    geomean: f64,
+    count: usize,
+}
+
+impl Summary {
+    /// Create a summary from the given successful measurements and the
+    /// measurements with errors (skipped or otherwise), as returned by
+    /// `MeasurementReader::read_with_errors`. The ranking is computed using
+    /// the given statistic.
+    fn new(
+        measurements: &[Measurement],
+        errors: &[Measurement],
+        stat: Stat,
+    ) -> anyhow::Result<Summary> {
+        let all = || measurements.iter().chain(errors.iter());
+        let benchmarks = all().map(|m| &m.name).collect::<BTreeSet<_>>();
+        let engines = all().map(|m| &m.engine).collect::<BTreeSet<_>>();
+        let total = all().map(|m| m.total).sum::<Duration>();
+        let skipped = errors.iter().filter(|m| m.skipped().is_some()).count();
+        let ranking = ByBenchmarkName::new(measurements)?
+            .ranking(stat)?
+            .into_iter()
+            .map(|s| RankedEngine {
+                name: s.name,
+                version: s.version,
+                geomean: s.geomean,
+                count: s.count,
+            })
+            .collect();
+        Ok(Summary {
+            benchmarks: benchmarks.len(),
+            engines: engines.len(),
+            errors: errors.len() - skipped,
+            skipped,
+            top_errors: top_errors(errors, TOP_ERRORS),
+            total,
+            ranking,
+        })
+    }
+
+    /// Write this summary as a single line of text.
+    fn write_text<W: Write>(&self, mut wtr: W) -> anyhow::Result<()> {
+        write!(
+            wtr,
+            "{} benchmarks, {} engines, {} errors",
+            self.benchmarks, self.engines, self.errors,
+        )?;
+        if !self.top_errors.is_empty() {
+            let top: Vec<String> = self
+                .top_errors
+                .iter()
+                .map(|e| format!("{:?} x{}", e.message, e.count))
+                .collect();
+            write!(wtr, " ({})", top.join(", "))?;
+        }
+        if self.skipped > 0 {
+            write!(wtr, ", {} skipped", self.skipped)?;
+        }
+        write!(wtr, ", {} total", ShortHumanDuration::from(self.total))?;
+        if !self.ranking.is_empty() {
+            let ranking: Vec<String> = self
+                .ranking
+                .iter()
+                .map(|e| format!("{}={:.2}({})", e.name, e.geomean, e.count))
+                .collect();
+            write!(wtr, "; {}", ranking.join(" "))?;
+        }
+        writeln!(wtr)?;
+        Ok(())
+    }
+}
+
+/// Returns at most `limit` of the most frequent error messages in the given
+/// measurements, in descending order of frequency. Ties are broken by the
+/// error message itself. Skipped benchmarks are ignored.
+///
+/// Only the first line of each error message is used, since that's usually
+/// the part that identifies the problem. The rest tends to be a dump of the
+/// runner program's stderr, which would make otherwise identical errors
+/// distinct.
+fn top_errors(errors: &[Measurement], limit: usize) -> Vec<ErrorCount> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for m in errors.iter().filter(|m| m.skipped().is_none()) {
+        let Some(ref err) = m.err else { continue };
+        let message = err.lines().next().unwrap_or("").trim();
+        *counts.entry(message).or_insert(0) += 1;
+    }
+    let mut counts: Vec<ErrorCount> = counts
+        .into_iter()
+        .map(|(message, count)| ErrorCount {
+            message: message.to_string(),
+            count,
+        })
+        .collect();
+    // This is a stable sort and the counts are already sorted by message, so
+    // ties remain sorted by message.
+    counts.sort_by_key(|e| std::cmp::Reverse(e.count));
+    counts.truncate(limit);
+    counts
+}
+
+/// The arguments for this 'summary' command parsed from CLI args.
+#[derive(Debug, Default)]
+struct Config {
+    /// File paths to CSV files.
+    csv_paths: Vec<PathBuf>,
+    /// The benchmark name, model and regex engine filters.
+    filters: Filters,
+    /// The format to print the summary in.
+    format: Format,
+    /// The statistic we want to compare.
+    stat: Stat,
+    /// Whether to fail on any duplicate measurements, even identical ones.
+    strict_duplicates: bool,
+    /// How to check the rebar versions that recorded the measurements.
+    version_check: VersionCheck,
+}
+
+impl Config {
+    /// Parse 'summary' args from the given CLI parser.
+    fn parse(p: &mut lexopt::Parser) -> anyhow::Result<Config> {
+        use lexopt::Arg;
+
+        let mut c = Config::default();
+        while let Some(arg) = p.next()? {
+            match arg {
+                Arg::Value(v) => c.csv_paths.push(PathBuf::from(v)),
+                Arg::Short('h') => anyhow::bail!("{}", usage_short()),
+                Arg::Long("help") => anyhow::bail!("{}", usage_long()),
+                Arg::Long("allow-version-mismatch") => {
+                    c.version_check.allow_mismatch = true;
+                }
+                Arg::Short('e') | Arg::Long("engine") => {
+                    c.filters.engine.arg_whitelist(p, "-e/--engine")?;
+                }
+                Arg::Short('E') | Arg::Long("engine-not") => {
+                    c.filters.engine.arg_blacklist(p, "-E/--engine-not")?;
+                }
+                Arg::Short('f') | Arg::Long("filter") => {
+                    c.filters.name.arg_whitelist(p, "-f/--filter")?;
+                }
+                Arg::Short('F') | Arg::Long("filter-not") => {
+                    c.filters.name.arg_blacklist(p, "-F/--filter-not")?;
+                }
+                Arg::Long("format") => {
+                    c.format = args::parse(p, "--format")?;
+                }
+                Arg::Short('m') | Arg::Long("model") => {
+                    c.filters.model.arg_whitelist(p, "-m/--model")?;
+                }
+                Arg::Short('M') | Arg::Long("model-not") => {
+                    c.filters.model.arg_blacklist(p, "-M/--model-not")?;
+                }
+                Arg::Long("require-version") => {
+                    c.version_check.require =
+                        Some(args::parse(p, "--require-version")?);
+                }
+                Arg::Short('s') | Arg::Long("statistic") => {
+                    c.stat = args::parse(p, "-s/--statistic")?;
+                }
+                Arg::Long("strict-duplicates") => {
+                    c.strict_duplicates = true;
+                }
+                _ => return Err(arg.unexpected().into()),
+            }
+        }
+        anyhow::ensure!(!c.csv_paths.is_empty(), "no CSV file paths given");
+        Ok(c)
+    }
+}
+
+/// The output format of the summary.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+enum Format {
+    #[default]
+    Text,
+    Json,
+}
+
+impl std::str::FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Format> {
+        let format = match s {
+            "text" => Format::Text,
+            "json" => Format::Json,
+            unknown => {
+                anyhow::bail!(
+                    "unrecognized format '{}', must be one of text or json.",
+                    unknown,
+                )
+            }
+        };
+        Ok(format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::measurement::read_fixture;
+
+    use super::*;
+
+    const MEASUREMENTS: &str = "\
+fixture/a,count,0.1.0,one,1.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+fixture/a,count,0.1.0,two,2.0.0,,10,5,2s,40ns,0ns,40ns,0ns,40ns,40ns
+fixture/b,count,0.1.0,one,1.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+fixture/b,count,0.1.0,two,2.0.0,,10,5,2s,10ns,0ns,10ns,0ns,10ns,10ns
+fixture/c,count,0.1.0,one,1.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+fixture/c,count,0.1.0,three,3.0.0,skipped: no Unicode,,0,0s,0ns,0ns,0ns,0ns,0ns,0ns
+fixture/d,count,0.1.0,two,2.0.0,\"count mismatch\nstderr: oops\",,0,500ms,0ns,0ns,0ns,0ns,0ns,0ns
+fixture/d,count,0.1.0,three,3.0.0,count mismatch,,0,0s,0ns,0ns,0ns,0ns,0ns,0ns
+fixture/e,count,0.1.0,two,2.0.0,timeout,,0,0s,0ns,0ns,0ns,0ns,0ns,0ns
+fixture/e,count,0.1.0,three,3.0.0,compile failed,,0,0s,0ns,0ns,0ns,0ns,0ns,0ns
+fixture/e,count,0.1.0,one,1.0.0,bad haystack,,0,0s,0ns,0ns,0ns,0ns,0ns,0ns
+";
+
+    /// Returns the fixture measurements split into the successful ones and
+    /// the ones with errors.
+    fn fixture() -> (Vec<Measurement>, Vec<Measurement>) {
+        read_fixture(MEASUREMENTS).into_iter().partition(|m| m.err.is_none())
+    }
+
+    #[test]
+    fn error_frequency() {
+        let (_, errors) = fixture();
+        let got = top_errors(&errors, 3);
+        let e = |message: &str, count| ErrorCount {
+            message: message.to_string(),
+            count,
+        };
+        // The two 'count mismatch' errors only share their first line. The
+        // ties are broken by message, and the skipped benchmark is ignored.
+        assert_eq!(
+            vec![
+                e("count mismatch", 2),
+                e("bad haystack", 1),
+                e("compile failed", 1),
+            ],
+            got,
+        );
+        assert_eq!(4, top_errors(&errors, 10).len());
+        assert!(top_errors(&[], 3).is_empty());
+    }
+
+    #[test]
+    fn text() {
+        let (measurements, errors) = fixture();
+        let summary =
+            Summary::new(&measurements, &errors, Stat::Median).unwrap();
+        let mut out = vec![];
+        summary.write_text(&mut out).unwrap();
+        assert_eq!(
+            "5 benchmarks, 3 engines, 5 errors (\"count mismatch\" x2, \
+             \"bad haystack\" x1, \"compile failed\" x1), 1 skipped, \
+             7.50s total; one=1.00(3) two=2.00(2)\n",
+            String::from_utf8(out).unwrap(),
+        );
+    }
+
+    #[test]
+    fn text_without_errors() {
+        let (measurements, _) = fixture();
+        let summary = Summary::new(&measurements, &[], Stat::Median).unwrap();
+        let mut out = vec![];
+        summary.write_text(&mut out).unwrap();
+        assert_eq!(
+            "3 benchmarks, 2 engines, 0 errors, 7.00s total; \
+             one=1.00(3) two=2.00(2)\n",
+            String::from_utf8(out).unwrap(),
+        );
+    }
+
+    #[test]
+    fn json() {
+        let (measurements, errors) = fixture();
+        let summary =
+            Summary::new(&measurements, &errors, Stat::Median).unwrap();
+        let got = serde_json::to_value(&summary).unwrap();
+        assert_eq!(5, got["benchmarks"]);
+        assert_eq!(3, got["engines"]);
+        assert_eq!(5, got["errors"]);
+        assert_eq!(1, got["skipped"]);
+        assert_eq!("7.50s", got["total"]);
+        assert_eq!("count mismatch", got["top_errors"][0]["message"]);
+        assert_eq!(2, got["top_errors"][0]["count"]);
+        assert_eq!("two", got["ranking"][1]["name"]);
+        assert_eq!("2.0.0", got["ranking"][1]["version"]);
+        assert_eq!(2.0, got["ranking"][1]["geomean"]);
+        assert_eq!(2, got["ranking"][1]["count"]);
+    }
+}
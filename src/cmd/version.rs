@@ -1,34 +1,131 @@
-use std::io::Write;
+use std::{io::Write, path::PathBuf};
 
-use lexopt::Arg;
+use {anyhow::Context, lexopt::Arg, unicode_width::UnicodeWidthStr};
 
-use crate::util;
+use crate::{
+    args::Usage,
+    format::benchmarks::{Engine, Engines, LoadOptions},
+    util::{self, write_divider},
+};
 
-fn usage() -> String {
+const USAGES: &[Usage] = &[
+    Usage::BENCH_DIR,
+    Usage::new(
+        "--engines",
+        "Print the version of every regex engine.",
+        r#"
+Print the version of every regex engine instead of the version of rebar.
+
+This prints a table with the version detected for every regex engine defined
+in 'engines.toml', along with its expected version (if it has one) and whether
+the two match. A regex engine whose version couldn't be detected (usually
+because it hasn't been built) is shown as 'missing'.
+
+Detecting the version of a regex engine requires running its version command,
+so this may take a moment.
+"#,
+    ),
+];
+
+fn usage_short() -> String {
     format!(
         "\
 Print the version of this rebar command.
 
 USAGE:
-    rebar version
+    rebar version [--engines]
+
+TIP:
+    use -h for short docs and --help for long docs
 
+OPTIONS:
+{options}
 ",
+        options = Usage::short(USAGES),
+    )
+    .trim()
+    .to_string()
+}
+
+fn usage_long() -> String {
+    format!(
+        "\
+Print the version of this rebar command.
+
+When --engines is given, the detected version of every regex engine is printed
+instead. This is useful for checking which versions of each regex engine would
+be used by 'rebar measure', and for filling in 'expected-version' in
+'engines.toml'.
+
+USAGE:
+    rebar version [--engines]
+
+OPTIONS:
+{options}
+",
+        options = Usage::long(USAGES),
     )
     .trim()
     .to_string()
 }
 
 pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
+    let mut dir = PathBuf::from("benchmarks");
+    let mut engines = false;
     while let Some(arg) = p.next()? {
         match arg {
-            Arg::Short('h') | Arg::Long("help") => {
-                anyhow::bail!("{}", usage())
+            Arg::Short('h') => anyhow::bail!("{}", usage_short()),
+            Arg::Long("help") => anyhow::bail!("{}", usage_long()),
+            Arg::Short('d') | Arg::Long("dir") => {
+                dir = PathBuf::from(p.value().context("-d/--dir")?);
+            }
+            Arg::Long("engines") => {
+                engines = true;
             }
             _ => return Err(arg.unexpected().into()),
         }
     }
 
-    let mut wtr = std::io::stdout();
-    writeln!(wtr, "{}", util::version())?;
+    let mut wtr = std::io::stdout().lock();
+    if !engines {
+        writeln!(wtr, "{}", util::version())?;
+        return Ok(());
+    }
+    let engines = Engines::from_file(&dir, &LoadOptions::default(), |_| true)?;
+    let mut wtr = tabwriter::TabWriter::new(wtr);
+    let columns = ["Engine", "Version", "Expected", "Status"];
+    writeln!(wtr, "{}", columns.join("\t"))?;
+    for (i, label) in columns.iter().enumerate() {
+        if i > 0 {
+            write!(wtr, "\t")?;
+        }
+        write_divider(&mut wtr, '-', label.width())?;
+    }
+    writeln!(wtr)?;
+    for e in engines.list.iter() {
+        writeln!(
+            wtr,
+            "{}\t{}\t{}\t{}",
+            e.name,
+            e.version,
+            e.expected_version.as_deref().unwrap_or("-"),
+            status(e),
+        )?;
+    }
+    wtr.flush()?;
     Ok(())
 }
+
+/// A short label describing whether the given engine's detected version
+/// matches its expected version.
+fn status(e: &Engine) -> &'static str {
+    if e.is_missing_version() {
+        "missing"
+    } else if e.expected_version.is_none() {
+        "-"
+    } else if e.check_version(&e.version).is_ok() {
+        "ok"
+    } else {
+        "MISMATCH"
+    }
+}
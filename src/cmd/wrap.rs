@@ -0,0 +1,745 @@
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use {
+    anyhow::Context,
+    bstr::ByteSlice,
+    lexopt::{Arg, ValueExt},
+};
+
+use crate::{
+    args::{self, Filter, Filters, Usage},
+    cmd::measure::{ExecBenchmark, ExecBenchmarkConfig, Results, Skipped},
+    format::{
+        benchmarks::{
+            Benchmarks, Definition, DefinitionHasher, Engine, LoadOptions,
+        },
+        measurement::Measurement,
+    },
+    util::{self, ShortHumanDuration},
+};
+
+/// When the median time of an iteration is less than this, a warning is
+/// printed since the measurement is probably dominated by the time it takes
+/// to spawn a process.
+const MIN_ITER_TIME: Duration = Duration::from_millis(10);
+
+const USAGES: &[Usage] = &[
+    Usage::BENCH_DIR,
+    Usage::ALLOW_DOWNLOAD,
+    Usage::new(
+        "--cmd <template>",
+        "The command to run for each iteration. Required.",
+        r#"
+The command to run for each iteration of each benchmark. This flag is required.
+
+The template is split on whitespace into a program name and its arguments. It
+is not run via a shell, so there is no quoting. Each argument may contain the
+following placeholders, which are replaced before the command is run:
+
+{pattern} is replaced with the benchmark's regex. This can only be used with
+benchmarks that have exactly one regex.
+
+{pattern-file} is replaced with the path to a file containing the benchmark's
+regexes, one per line.
+
+{haystack-file} is replaced with the path to a file containing the benchmark's
+haystack.
+
+For example, '--cmd "grep -c -E {pattern} {haystack-file}"'.
+"#,
+    ),
+    Usage::new(
+        "--count-regex <regex>",
+        "Extract the count from the command's stdout with a regex.",
+        r#"
+Extract the count from the command's stdout with a regex.
+
+The count is the first capture group of the first match of this regex in
+the command's stdout, or the entire match if the regex has no capture groups.
+When this isn't given, the entire stdout (with leading and trailing whitespace
+trimmed) must be the count.
+
+The count must match the count given in the benchmark definition for the
+'--engine-name' given, and it must have the semantics of the benchmark's
+model. For example, the 'grep' model counts matching lines, which is what
+'grep -c' prints.
+"#,
+    ),
+    Usage::new(
+        "--engine-name <name>",
+        "The regex engine name to record measurements with. Required.",
+        r#"
+The regex engine name to record measurements with. This flag is required.
+
+This name doesn't need to be in 'engines.toml', but it is used to look up the
+expected count of each benchmark. So the name usually needs to be matched by
+a catch-all count entry like '{ engine = ".*", count = 1 }', or by a count that
+isn't specific to any regex engine.
+"#,
+    ),
+    Filter::USAGE_BENCH,
+    Filter::USAGE_BENCH_NOT,
+    Usage::MAX_ITERS,
+    Usage::MAX_WARMUP_ITERS,
+    Usage::MAX_TIME,
+    Usage::MAX_WARMUP_TIME,
+    Filter::USAGE_MODEL,
+    Filter::USAGE_MODEL_NOT,
+    Usage::new(
+        "--timeout <duration>",
+        "Fail a benchmark that runs for longer than this.",
+        r#"
+Fail a benchmark that runs for longer than this. When a benchmark times out,
+the command running at the time is killed and the measurement records an
+error. By default, this is derived from the time budgets in the same way as
+for 'rebar measure'.
+"#,
+    ),
+    Usage::new(
+        "--version-cmd <command>",
+        "The command to get the version of the program. Required.",
+        r#"
+The command to run to get the version of the program being benchmarked. This
+flag is required.
+
+Like --cmd, this is split on whitespace and not run via a shell. The first
+non-empty line printed to stdout, with leading and trailing whitespace
+trimmed, is used as the version. For example, '--version-cmd "grep -V"'.
+"#,
+    ),
+];
+
+fn usage_short() -> String {
+    format!(
+        "\
+Run benchmarks with an arbitrary command and write measurements.
+
+USAGE:
+    rebar wrap --cmd <template> --engine-name <name> --version-cmd <command>
+
+TIP:
+    use -h for short docs and --help for long docs
+
+OPTIONS:
+{options}
+",
+        options = Usage::short(USAGES),
+    )
+    .trim()
+    .to_string()
+}
+
+fn usage_long() -> String {
+    format!(
+        "\
+Run benchmarks with an arbitrary command and write measurements.
+
+This makes it possible to benchmark command line tools like grep or ripgrep
+without writing a runner program for them. Instead of sending the benchmark to
+a runner program that times itself, rebar writes the haystack (and regexes) to
+temporary files and runs the command given by --cmd once per iteration. The
+wall clock time of each run of the command is a sample, and the count printed
+by the command is checked against the benchmark definition. Measurements are
+written to stdout in the same CSV format as 'rebar measure'.
+
+Every sample includes the time it takes to spawn a process, start the program
+and read the haystack from a file. For small haystacks, this overhead dominates
+the measurement, and comparing such measurements with ones collected by 'rebar
+measure' is meaningless. A warning is printed when the median time of an
+iteration is less than {min_iter_time}. In general, this command is best used
+with large haystacks, and for comparing wrapped commands with one another.
+
+Since the command can't be told about benchmark options like case insensitivity
+or a search window, benchmarks that use them are skipped. So are benchmarks
+with binary regexes or a haystack that isn't UTF-8. Unicode mode is not
+skipped, since most benchmarks enable it, but the command is responsible for
+enabling or disabling it to match the benchmark.
+
+USAGE:
+    rebar wrap --cmd <template> --engine-name <name> --version-cmd <command>
+
+OPTIONS:
+{options}
+",
+        min_iter_time = ShortHumanDuration::from(MIN_ITER_TIME),
+        options = Usage::long(USAGES),
+    )
+    .trim()
+    .to_string()
+}
+
+pub fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
+    let c = Config::parse(p)?;
+    let version = c.version()?;
+    let engine = Engine::external(&c.engine_name, &version);
+    let benchmarks =
+        Benchmarks::from_dir_without_engines(&c.dir, &c.filters, &c.load)?;
+    let tmpdir = std::env::temp_dir()
+        .join(format!("rebar-wrap-{}", std::process::id()));
+    std::fs::create_dir_all(&tmpdir)
+        .with_context(|| format!("failed to create {}", tmpdir.display()))?;
+    let result = c.measure_all(&benchmarks, &engine, &tmpdir);
+    if let Err(err) = std::fs::remove_dir_all(&tmpdir) {
+        log::warn!("failed to remove {}: {}", tmpdir.display(), err);
+    }
+    result
+}
+
+#[derive(Debug)]
+struct Config {
+    dir: PathBuf,
+    load: LoadOptions,
+    filters: Filters,
+    template: Template,
+    count_regex: Option<regex_lite::Regex>,
+    engine_name: String,
+    version_cmd: Vec<String>,
+    bench_config: ExecBenchmarkConfig,
+}
+
+impl Config {
+    fn parse(p: &mut lexopt::Parser) -> anyhow::Result<Config> {
+        let mut dir = PathBuf::from("benchmarks");
+        let mut load = LoadOptions::default();
+        let mut filters = Filters::default();
+        let mut template = None;
+        let mut count_regex = None;
+        let mut engine_name = None;
+        let mut version_cmd = None;
+        let mut bench_config = ExecBenchmarkConfig::default();
+        while let Some(arg) = p.next()? {
+            match arg {
+                Arg::Short('h') => anyhow::bail!("{}", usage_short()),
+                Arg::Long("help") => anyhow::bail!("{}", usage_long()),
+                Arg::Short('d') | Arg::Long("dir") => {
+                    dir = PathBuf::from(p.value().context("-d/--dir")?);
+                }
+                Arg::Long("allow-download") => {
+                    load.allow_download = true;
+                }
+                Arg::Long("cmd") => {
+                    let value = p.value().context("--cmd")?;
+                    let value = value.string().context("--cmd")?;
+                    template = Some(Template::new(&value).context("--cmd")?);
+                }
+                Arg::Long("count-regex") => {
+                    let value = p.value().context("--count-regex")?;
+                    let value = value.string().context("--count-regex")?;
+                    let re = regex_lite::Regex::new(&value)
+                        .context("--count-regex")?;
+                    count_regex = Some(re);
+                }
+                Arg::Long("engine-name") => {
+                    let value = p.value().context("--engine-name")?;
+                    engine_name =
+                        Some(value.string().context("--engine-name")?);
+                }
+                Arg::Short('f') | Arg::Long("filter") => {
+                    filters.name.arg_whitelist(p, "-f/--filter")?;
+                }
+                Arg::Short('F') | Arg::Long("filter-not") => {
+                    filters.name.arg_blacklist(p, "-F/--filter-not")?;
+                }
+                Arg::Long("max-iters") => {
+                    bench_config.max_iters = args::parse(p, "--max-iters")?;
+                    bench_config.explicit.max_iters = true;
+                }
+                Arg::Long("max-warmup-iters") => {
+                    bench_config.max_warmup_iters =
+                        args::parse(p, "--max-warmup-iters")?;
+                    bench_config.explicit.max_warmup_iters = true;
+                }
+                Arg::Long("max-time") => {
+                    let hdur =
+                        args::parse::<ShortHumanDuration>(p, "--max-time")?;
+                    bench_config.max_time = Duration::from(hdur);
+                    bench_config.explicit.max_time = true;
+                }
+                Arg::Long("max-warmup-time") => {
+                    let hdur = args::parse::<ShortHumanDuration>(
+                        p,
+                        "--max-warmup-time",
+                    )?;
+                    bench_config.max_warmup_time = Duration::from(hdur);
+                    bench_config.explicit.max_warmup_time = true;
+                }
+                Arg::Short('m') | Arg::Long("model") => {
+                    filters.model.arg_whitelist(p, "-m/--model")?;
+                }
+                Arg::Short('M') | Arg::Long("model-not") => {
+                    filters.model.arg_blacklist(p, "-M/--model-not")?;
+                }
+                Arg::Long("timeout") => {
+                    let hdur =
+                        args::parse::<ShortHumanDuration>(p, "--timeout")?;
+                    bench_config.timeout = Duration::from(hdur);
+                    bench_config.explicit.timeout = true;
+                }
+                Arg::Long("version-cmd") => {
+                    let value = p.value().context("--version-cmd")?;
+                    let value = value.string().context("--version-cmd")?;
+                    let words: Vec<String> =
+                        value.split_whitespace().map(String::from).collect();
+                    anyhow::ensure!(
+                        !words.is_empty(),
+                        "--version-cmd: command is empty",
+                    );
+                    version_cmd = Some(words);
+                }
+                _ => return Err(arg.unexpected().into()),
+            }
+        }
+        let template = template.context("missing command (use --cmd)")?;
+        let engine_name =
+            engine_name.context("missing engine name (use --engine-name)")?;
+        let re_engine = regex!(r"^[-A-Za-z0-9]+(/[-A-Za-z0-9]+)*$");
+        anyhow::ensure!(
+            re_engine.is_match(&engine_name),
+            "engine name '{}' does not match format '{}'",
+            engine_name,
+            re_engine.as_str(),
+        );
+        let version_cmd = version_cmd
+            .context("missing version command (use --version-cmd)")?;
+        Ok(Config {
+            dir,
+            load,
+            filters,
+            template,
+            count_regex,
+            engine_name,
+            version_cmd,
+            bench_config,
+        })
+    }
+
+    /// Run the version command and return the version it reports.
+    fn version(&self) -> anyhow::Result<String> {
+        let mut cmd = std::process::Command::new(&self.version_cmd[0]);
+        cmd.args(&self.version_cmd[1..]);
+        let out = util::output(&mut cmd).with_context(|| {
+            format!("--version-cmd: failed to run {:?}", self.version_cmd)
+        })?;
+        let version = out
+            .lines()
+            .map(|line| line.trim_with(char::is_whitespace))
+            .find(|line| !line.is_empty())
+            .with_context(|| {
+                format!(
+                    "--version-cmd: {:?} didn't print a version",
+                    self.version_cmd
+                )
+            })?;
+        Ok(version.to_str_lossy().into_owned())
+    }
+
+    /// Measure every benchmark with the wrapped command and write a CSV
+    /// record for each one to stdout. Temporary files are written to the
+    /// given directory.
+    fn measure_all(
+        &self,
+        benchmarks: &Benchmarks,
+        engine: &Engine,
+        tmpdir: &Path,
+    ) -> anyhow::Result<()> {
+        let mut wtr = csv::Writer::from_writer(std::io::stdout());
+        let mut hasher = DefinitionHasher::default();
+        let defs_revision = util::git_revision(&self.dir);
+        for def in benchmarks.defs.iter() {
+            let config = self.bench_config.for_definition(def);
+            let exec = ExecBenchmark::new(config, def.clone(), engine.clone());
+            let result = self.collect(&exec, def, engine, tmpdir);
+            let mut m = exec.aggregate(result);
+            m.def_hash = Some(hasher.hash(def));
+            m.defs_revision = defs_revision.clone();
+            warn_if_short(&m);
+            wtr.serialize(m)?;
+            wtr.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Run the wrapped command for the given benchmark until its iteration
+    /// or time budget is exhausted, and return the samples collected.
+    fn collect(
+        &self,
+        exec: &ExecBenchmark,
+        def: &Definition,
+        engine: &Engine,
+        tmpdir: &Path,
+    ) -> anyhow::Result<Results> {
+        let config = self.bench_config.for_definition(def);
+        if let Some(reason) = unsupported(def, &self.template) {
+            return Err(anyhow::Error::new(Skipped(reason)));
+        }
+        let expected = if def.verify_count {
+            Some(def.count(&engine.name, &engine.version)?)
+        } else {
+            None
+        };
+        let inputs = Inputs::write(def, tmpdir)?;
+        let cmd = self.template.command(&inputs);
+        let start = Instant::now();
+        let deadline = start + config.timeout;
+        let check = |count: u64, warmup: bool, i: u64| -> anyhow::Result<()> {
+            let Some(expected) = expected else { return Ok(()) };
+            anyhow::ensure!(
+                count == expected,
+                "count mismatch on {} iteration {}: expected {} but got {}",
+                if warmup { "warmup" } else { "measured" },
+                i,
+                expected,
+                count,
+            );
+            Ok(())
+        };
+
+        let mut warmup_iters = 0;
+        while warmup_iters < config.max_warmup_iters
+            && start.elapsed() < config.max_warmup_time
+        {
+            let (_, count) = self.run_once(&cmd, deadline, config.timeout)?;
+            check(count, true, warmup_iters)?;
+            warmup_iters += 1;
+        }
+        let measure_start = Instant::now();
+        let (mut samples, mut first_count) = (vec![], None);
+        // We always collect at least one sample.
+        while samples.is_empty()
+            || (u64::try_from(samples.len()).unwrap() < config.max_iters
+                && measure_start.elapsed() < config.max_time)
+        {
+            let (dur, count) =
+                self.run_once(&cmd, deadline, config.timeout)?;
+            check(count, false, u64::try_from(samples.len()).unwrap())?;
+            let first = *first_count.get_or_insert(count);
+            anyhow::ensure!(
+                count == first,
+                "command reported a count of {} on iteration {}, but \
+                 reported {} on the first iteration",
+                count,
+                samples.len(),
+                first,
+            );
+            samples.push(dur);
+        }
+        Ok(Results::from_samples(
+            exec,
+            start.elapsed(),
+            samples,
+            first_count,
+            warmup_iters,
+        ))
+    }
+
+    /// Run the given command once and return how long it took along with the
+    /// count it reported. If the command is still running at the deadline,
+    /// then it's killed and an error is returned.
+    fn run_once(
+        &self,
+        cmd: &[String],
+        deadline: Instant,
+        timeout: Duration,
+    ) -> anyhow::Result<(Duration, u64)> {
+        let mut command = std::process::Command::new(&cmd[0]);
+        command
+            .args(&cmd[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let start = Instant::now();
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("failed to run {:?}", cmd))?;
+        // We read stdout in another thread so that we can kill the command if
+        // it runs past the deadline. We read stderr in another thread too,
+        // since a command that fills the stderr pipe would otherwise block.
+        let (mut stdout, mut stderr) =
+            (child.stdout.take().unwrap(), child.stderr.take().unwrap());
+        let (send, recv) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buf = vec![];
+            let _ = send.send(stdout.read_to_end(&mut buf).map(|_| buf));
+        });
+        let stderr_handle = std::thread::spawn(move || {
+            let mut buf = vec![];
+            let _ = stderr.read_to_end(&mut buf);
+            buf
+        });
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let stdout = match recv.recv_timeout(remaining) {
+            Ok(result) => result.context("failed to read command's stdout")?,
+            Err(_) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                anyhow::bail!("timeout: exceeded {:?}", timeout);
+            }
+        };
+        let status = child.wait().context("failed to wait for command")?;
+        let elapsed = start.elapsed();
+        // OK because the thread never panics.
+        let stderr = stderr_handle.join().unwrap();
+        // grep and friends exit with 1 when nothing matched, which is a
+        // perfectly fine outcome for a benchmark.
+        if !status.success() && status.code() != Some(1) {
+            let last = stderr.lines().next_back().unwrap_or(b"");
+            anyhow::bail!(
+                "command failed with {}, last line of stderr: {:?}",
+                status,
+                last.as_bstr(),
+            );
+        }
+        let count = parse_count(&stdout, self.count_regex.as_ref())?;
+        Ok((elapsed, count))
+    }
+}
+
+/// A command template given by --cmd.
+#[derive(Clone, Debug)]
+struct Template {
+    words: Vec<String>,
+}
+
+impl Template {
+    const PATTERN: &'static str = "{pattern}";
+    const PATTERN_FILE: &'static str = "{pattern-file}";
+    const HAYSTACK_FILE: &'static str = "{haystack-file}";
+
+    /// Parse a template by splitting it on whitespace. An error is returned
+    /// if it's empty or if it contains a placeholder we don't recognize.
+    fn new(template: &str) -> anyhow::Result<Template> {
+        let words: Vec<String> =
+            template.split_whitespace().map(String::from).collect();
+        anyhow::ensure!(!words.is_empty(), "command template is empty");
+        let known = [Self::PATTERN, Self::PATTERN_FILE, Self::HAYSTACK_FILE];
+        for word in words.iter() {
+            for m in regex!(r"\{[^{}]*\}").find_iter(word) {
+                anyhow::ensure!(
+                    known.contains(&m.as_str()),
+                    "unrecognized placeholder '{}' in command template \
+                     (expected one of {})",
+                    m.as_str(),
+                    known.join(", "),
+                );
+            }
+        }
+        Ok(Template { words })
+    }
+
+    /// Returns true if the given placeholder appears in this template.
+    fn uses(&self, placeholder: &str) -> bool {
+        self.words.iter().any(|w| w.contains(placeholder))
+    }
+
+    /// Returns the program and its arguments with every placeholder replaced
+    /// by the corresponding input.
+    fn command(&self, inputs: &Inputs) -> Vec<String> {
+        self.words
+            .iter()
+            .map(|w| {
+                w.replace(Self::PATTERN, &inputs.pattern)
+                    .replace(Self::PATTERN_FILE, &inputs.pattern_file)
+                    .replace(Self::HAYSTACK_FILE, &inputs.haystack_file)
+            })
+            .collect()
+    }
+}
+
+/// The values substituted into a command template for one benchmark.
+#[derive(Clone, Debug)]
+struct Inputs {
+    /// The first regex of the benchmark. The template can only use it when
+    /// the benchmark has exactly one regex.
+    pattern: String,
+    pattern_file: String,
+    haystack_file: String,
+}
+
+impl Inputs {
+    /// Write the regexes and haystack for the given benchmark to files in
+    /// the given directory. Files from the previous benchmark are
+    /// overwritten.
+    fn write(def: &Definition, dir: &Path) -> anyhow::Result<Inputs> {
+        let pattern_path = dir.join("patterns.txt");
+        let mut patterns = vec![];
+        for re in def.regexes.iter() {
+            writeln!(patterns, "{}", re)?;
+        }
+        std::fs::write(&pattern_path, patterns).with_context(|| {
+            format!("failed to write {}", pattern_path.display())
+        })?;
+        let haystack_path = dir.join("haystack");
+        std::fs::write(&haystack_path, &def.haystack).with_context(|| {
+            format!("failed to write {}", haystack_path.display())
+        })?;
+        let path_string = |path: &Path| -> anyhow::Result<String> {
+            path.to_str().map(String::from).with_context(|| {
+                format!("temporary path {} is not UTF-8", path.display())
+            })
+        };
+        Ok(Inputs {
+            pattern: def.regexes.first().cloned().unwrap_or_default(),
+            pattern_file: path_string(&pattern_path)?,
+            haystack_file: path_string(&haystack_path)?,
+        })
+    }
+}
+
+/// Returns a reason why the given benchmark can't be run by a wrapped
+/// command, if there is one.
+fn unsupported(def: &Definition, template: &Template) -> Option<String> {
+    let opts = &def.options;
+    let option = if opts.case_insensitive {
+        Some("case-insensitive")
+    } else if opts.multiline {
+        Some("multiline")
+    } else if opts.dot_all {
+        Some("dot-all")
+    } else if opts.has_search_window() {
+        Some("search-start/search-end")
+    } else {
+        None
+    };
+    if let Some(option) = option {
+        return Some(format!("wrapped commands don't support '{}'", option));
+    }
+    if def.binary_regexes.is_some() {
+        return Some("wrapped commands don't support binary regexes".into());
+    }
+    if def.haystack_encoding != klv::HaystackEncoding::Utf8 {
+        return Some(
+            "wrapped commands only support UTF-8 haystacks".to_string(),
+        );
+    }
+    if template.uses(Template::PATTERN) && def.regexes.len() != 1 {
+        return Some(format!(
+            "{} requires exactly one regex, but benchmark has {} \
+             (use {} instead)",
+            Template::PATTERN,
+            def.regexes.len(),
+            Template::PATTERN_FILE,
+        ));
+    }
+    if template.uses(Template::PATTERN_FILE)
+        && def.regexes.iter().any(|re| re.contains('\n'))
+    {
+        return Some(format!(
+            "{} can't be used with regexes containing line terminators",
+            Template::PATTERN_FILE,
+        ));
+    }
+    None
+}
+
+/// Extract a count from the stdout of a command. When a regex is given,
+/// the count is its first capture group (or the entire match if it has no
+/// groups). Otherwise, the count is the entirety of stdout.
+fn parse_count(
+    stdout: &[u8],
+    re: Option<&regex_lite::Regex>,
+) -> anyhow::Result<u64> {
+    let stdout = stdout.to_str_lossy();
+    let raw = match re {
+        None => stdout.trim(),
+        Some(re) => {
+            let caps = re.captures(&stdout).with_context(|| {
+                format!(
+                    "--count-regex '{}' did not match stdout {:?}",
+                    re.as_str(),
+                    snippet(&stdout),
+                )
+            })?;
+            // OK because group 0 always participates in a match.
+            caps.get(1).or_else(|| caps.get(0)).unwrap().as_str().trim()
+        }
+    };
+    raw.parse().with_context(|| {
+        format!(
+            "failed to parse count from {:?} in stdout {:?}",
+            snippet(raw),
+            snippet(&stdout),
+        )
+    })
+}
+
+/// Returns a prefix of the given string that's short enough to include in
+/// an error message.
+fn snippet(s: &str) -> &str {
+    const LIMIT: usize = 60;
+    match s.char_indices().nth(LIMIT) {
+        None => s,
+        Some((i, _)) => &s[..i],
+    }
+}
+
+/// Print a warning if the median time of an iteration in the given
+/// measurement is so short that it's probably dominated by the overhead of
+/// spawning a process.
+fn warn_if_short(m: &Measurement) {
+    if m.err.is_some() || m.aggregate.times.median >= MIN_ITER_TIME {
+        return;
+    }
+    log::warn!(
+        "{}: median iteration time of {} is less than {}, so the \
+         measurement is probably dominated by the overhead of spawning a \
+         process",
+        m.name,
+        ShortHumanDuration::from(m.aggregate.times.median),
+        ShortHumanDuration::from(MIN_ITER_TIME),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn template() {
+        let t =
+            Template::new("  grep -c  -E {pattern} {haystack-file} ").unwrap();
+        assert!(t.uses(Template::PATTERN));
+        assert!(!t.uses(Template::PATTERN_FILE));
+        let inputs = Inputs {
+            pattern: "a b".to_string(),
+            pattern_file: "/tmp/patterns.txt".to_string(),
+            haystack_file: "/tmp/haystack".to_string(),
+        };
+        assert_eq!(
+            vec!["grep", "-c", "-E", "a b", "/tmp/haystack"],
+            t.command(&inputs),
+        );
+        let t = Template::new("grep --file={pattern-file}").unwrap();
+        assert_eq!(
+            vec!["grep", "--file=/tmp/patterns.txt"],
+            t.command(&inputs),
+        );
+
+        assert!(Template::new("   ").is_err());
+        let err = Template::new("grep {regex} {haystack-file}").unwrap_err();
+        assert!(err.to_string().contains("'{regex}'"), "{}", err);
+    }
+
+    #[test]
+    fn count() {
+        let re = |pat: &str| regex_lite::Regex::new(pat).unwrap();
+        assert_eq!(5, parse_count(b"5\n", None).unwrap());
+        assert_eq!(5, parse_count(b"  5  ", None).unwrap());
+        assert!(parse_count(b"5 matches\n", None).is_err());
+        assert!(parse_count(b"", None).is_err());
+
+        let out = b"Scanning...\nmatches: 42\nbytes: 1000\n";
+        let re1 = re(r"matches: (\d+)");
+        assert_eq!(42, parse_count(out, Some(&re1)).unwrap());
+        let re2 = re(r"\d+");
+        assert_eq!(42, parse_count(out, Some(&re2)).unwrap());
+        let re3 = re(r"total: (\d+)");
+        let err = parse_count(out, Some(&re3)).unwrap_err();
+        assert!(err.to_string().contains("did not match"), "{}", err);
+    }
+}
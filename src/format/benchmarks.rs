@@ -1,8 +1,10 @@
 use std::{
     collections::{BTreeMap, BTreeSet},
+    io::Read,
     path::{Path, PathBuf},
     process,
     sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use {
@@ -13,7 +15,8 @@ use {
 
 use crate::{
     args::{Filter, Filters},
-    util,
+    format::{cache::Cache, measurement::Measurement},
+    util::{self, ShortHumanDuration},
 };
 
 #[derive(Clone, Debug)]
@@ -24,28 +27,73 @@ pub struct Benchmarks {
 }
 
 impl Benchmarks {
+    /// Load all benchmark definitions from the given directory that match
+    /// the filters given.
+    ///
+    /// See `LoadOptions` for how the cache and downloads are handled.
     pub fn from_dir<P: AsRef<Path>>(
         dir: P,
         filters: &Filters,
+        opts: &LoadOptions,
     ) -> anyhow::Result<Benchmarks> {
         let dir = dir.as_ref();
+        Benchmarks::load(dir, filters, opts, |wire| {
+            wire.filter_by_engine(&filters.engine);
+            // Now that we've filtered out our benchmarks, we now collect our
+            // engines. We are careful to only collect engines that both pass
+            // our engine filter and have an actual explicit reference in a
+            // benchmark that has passed our filters. We would otherwise wind
+            // up getting the version info for every regex engine in some
+            // cases even when we don't need to.
+            let enginerefs = wire.engine_references(&filters.engine);
+            Engines::from_file(dir, opts, |e| enginerefs.contains(&e.name))
+        })
+    }
+
+    /// Load all benchmark definitions from the given directory that match
+    /// the name and model filters given, but without any regex engines.
+    ///
+    /// The engine filter is ignored, and every definition returned has an
+    /// empty list of engines. This is for running benchmarks with programs
+    /// that aren't in 'engines.toml', which is what 'rebar wrap' does.
+    pub fn from_dir_without_engines<P: AsRef<Path>>(
+        dir: P,
+        filters: &Filters,
+        opts: &LoadOptions,
+    ) -> anyhow::Result<Benchmarks> {
+        // A filter that excludes everything, so that the engines referenced
+        // by each definition are never looked up.
+        let mut no_engines = Filter::default();
+        no_engines.blacklist(".*")?;
+        let filters = Filters { engine: no_engines, ..filters.clone() };
+        Benchmarks::load(dir.as_ref(), &filters, opts, |_| {
+            Ok(Engines::from_list(vec![]))
+        })
+    }
+
+    /// Load all benchmark definitions from the given directory that match
+    /// the name and model filters given, along with the regex engines
+    /// returned by `engines`.
+    ///
+    /// `engines` is called after the name and model filters are applied, and
+    /// may filter the definitions further. The engine filter is only applied
+    /// when each remaining definition is resolved against the engines
+    /// returned.
+    fn load(
+        dir: &Path,
+        filters: &Filters,
+        opts: &LoadOptions,
+        engines: impl FnOnce(&mut WireDefinitions) -> anyhow::Result<Engines>,
+    ) -> anyhow::Result<Benchmarks> {
+        let cache = Cache::new(dir, opts.cache);
         let mut wire = WireDefinitions::new();
         wire.load_dir(dir)?;
         wire.check_duplicates()?;
         wire.filter_by_name(&filters.name);
         wire.filter_by_model(&filters.model);
-        wire.filter_by_engine(&filters.engine);
-        // Now that we've filtered out our benchmarks, we now collect our
-        // engines. We are careful to only collect engines that both pass our
-        // engine filter and have an actual explicit reference in a benchmark
-        // that has passed our filters. We would otherwise wind up getting the
-        // version info for every regex engine in some cases even when we don't
-        // need to.
-        let enginerefs = wire.engine_references(&filters.engine);
-        let engines =
-            Engines::from_file(dir, |e| enginerefs.contains(&e.name))?;
-        let res = Regexes::new(dir, &wire)?;
-        let hays = Haystacks::new(dir, &wire)?;
+        let engines = engines(&mut wire)?;
+        let res = Regexes::new(dir, &cache, &wire)?;
+        let hays = Haystacks::new(dir, &cache, &wire, opts.allow_download)?;
         let mut defs = vec![];
         for wire_def in wire.definitions.iter() {
             let def =
@@ -55,17 +103,68 @@ impl Benchmarks {
         Ok(Benchmarks { engines, defs, analysis: wire.all_analysis })
     }
 
+    /// Returns a map from the full name of each benchmark definition to its
+    /// hash. See `DefinitionHasher`.
+    pub fn hashes(&self) -> BTreeMap<String, String> {
+        let mut hasher = DefinitionHasher::default();
+        self.defs
+            .iter()
+            .map(|def| (def.name.to_string(), hasher.hash(def)))
+            .collect()
+    }
+
+    /// Load only the benchmark definitions (and regex engines) referenced by
+    /// the given measurements from the given directory.
+    ///
+    /// Definitions referenced by the measurements that don't exist are not
+    /// reported as an error here. Callers should check for them if their
+    /// absence matters.
+    pub fn for_measurements<P: AsRef<Path>>(
+        dir: P,
+        measurements: &[Measurement],
+        opts: &LoadOptions,
+    ) -> anyhow::Result<Benchmarks> {
+        let mut engine_names: Vec<String> = measurements
+            .iter()
+            .map(|m| regex_lite::escape(m.unlabeled_engine()))
+            .collect();
+        engine_names.sort();
+        engine_names.dedup();
+        let pat = format!("^(?:{})$", engine_names.join("|"));
+        let engine_filter = Filter::from_pattern(&pat)
+            .context("failed to build filter for engine names")?;
+
+        let bench_names: Vec<String> =
+            measurements.iter().map(|m| regex_lite::escape(&m.name)).collect();
+        let pat = format!("^(?:{})$", bench_names.join("|"));
+        let bench_filter = Filter::from_pattern(&pat)
+            .context("failed to build filter for benchmark names")?;
+
+        Benchmarks::from_dir(
+            dir,
+            &Filters {
+                name: bench_filter,
+                engine: engine_filter,
+                model: Filter::default(),
+                ignore_missing_engines: true,
+                strict_capabilities: false,
+            },
+            opts,
+        )
+    }
+
     pub fn find_one<P: AsRef<Path>>(
         dir: P,
         name: &str,
+        opts: &LoadOptions,
     ) -> anyhow::Result<Definition> {
         // This is a little cumbersome, but we go to war with the army we have.
-        let pattern = format!("^(?:{})$", regex_lite::escape(&name));
+        let pattern = format!("^(?:{})$", regex_lite::escape(name));
         let filters = Filters {
             name: Filter::from_pattern(&pattern)?,
             ..Filters::default()
         };
-        let mut defs = Benchmarks::from_dir(dir, &filters)?;
+        let mut defs = Benchmarks::from_dir(dir, &filters, opts)?;
         anyhow::ensure!(
             defs.defs.len() == 1,
             "expected to match 1 benchmark definition but matched {}",
@@ -74,6 +173,64 @@ impl Benchmarks {
         Ok(defs.defs.pop().unwrap())
     }
 
+    /// Check every benchmark definition in the given directory, along with
+    /// the regex engines in its 'engines.toml', for problems.
+    ///
+    /// Unlike `from_dir`, this doesn't stop at the first problem found with a
+    /// definition. Instead, every problem found is returned. An error is
+    /// only returned when the definitions can't be loaded at all, e.g., when
+    /// a TOML file is invalid.
+    pub fn lint<P: AsRef<Path>>(dir: P) -> anyhow::Result<Vec<LintIssue>> {
+        let dir = dir.as_ref();
+        let mut wire = WireDefinitions::new();
+        wire.load_dir(dir)?;
+        wire.check_duplicates()?;
+        let engines =
+            Engines::from_file(dir, &LoadOptions::default(), |_| true)?;
+        let mut issues = wire.lint(dir, &engines);
+        issues.extend(wire.lint_unreferenced(dir)?);
+        // We only try to fully load the definitions that don't already have
+        // errors, since the errors above would otherwise just be reported a
+        // second time in a less helpful way.
+        let has_errors: BTreeSet<String> = issues
+            .iter()
+            .filter(|issue| issue.severity == LintSeverity::Error)
+            .filter_map(|issue| issue.benchmark.clone())
+            .collect();
+        let cache = Cache::new(dir, true);
+        let loaded = Regexes::new(dir, &cache, &wire).and_then(|res| {
+            Ok((res, Haystacks::new(dir, &cache, &wire, false)?))
+        });
+        let (res, hays) = match loaded {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                issues.push(LintIssue {
+                    severity: LintSeverity::Error,
+                    path: dir.join("definitions"),
+                    benchmark: None,
+                    message: format!("{:#}", err),
+                });
+                return Ok(issues);
+            }
+        };
+        for def in wire.definitions.iter() {
+            if has_errors.contains(&def.name) {
+                continue;
+            }
+            let result =
+                def.to_definition(&Filters::default(), &engines, &res, &hays);
+            if let Err(err) = result {
+                issues.push(LintIssue {
+                    severity: LintSeverity::Error,
+                    path: def.path(dir),
+                    benchmark: Some(def.name.clone()),
+                    message: format!("{:#}", err),
+                });
+            }
+        }
+        Ok(issues)
+    }
+
     #[cfg(test)]
     pub fn from_slice<B: AsRef<[u8]>>(
         engines: &Engines,
@@ -87,12 +244,12 @@ impl Benchmarks {
         wire.filter_by_name(&filters.name);
         wire.filter_by_model(&filters.model);
         wire.filter_by_engine(&filters.engine);
-        let res = Regexes::new(Path::new("dummy"), &wire)?;
-        let hays = Haystacks::new(Path::new("dummy"), &wire)?;
+        let cache = Cache::new(Path::new("dummy"), false);
+        let res = Regexes::new(Path::new("dummy"), &cache, &wire)?;
+        let hays = Haystacks::new(Path::new("dummy"), &cache, &wire, false)?;
         let mut defs = vec![];
         for wire_def in wire.definitions.iter() {
-            let def =
-                wire_def.to_definition(filters, &engines, &res, &hays)?;
+            let def = wire_def.to_definition(filters, engines, &res, &hays)?;
             defs.push(def);
         }
         Ok(Benchmarks {
@@ -103,6 +260,99 @@ impl Benchmarks {
     }
 }
 
+/// Options that control how benchmark definitions are loaded from disk.
+#[derive(Clone, Debug)]
+pub struct LoadOptions {
+    /// When enabled, transformed haystacks and regexes are read from (and
+    /// written to) an on-disk cache in the benchmark directory.
+    pub cache: bool,
+    /// When enabled, haystacks that are defined by a URL are downloaded if
+    /// they haven't been downloaded yet (or if the copy on disk doesn't match
+    /// the expected checksum). When disabled, loading a benchmark with such a
+    /// haystack fails instead. No network access happens unless this is
+    /// enabled.
+    pub allow_download: bool,
+    /// When enabled, the versions of regex engines are read from (and written
+    /// to) an on-disk cache in the benchmark directory instead of always
+    /// running each engine's version command. See `VersionCache`.
+    pub version_cache: bool,
+    /// How long a cached regex engine version remains valid.
+    pub version_cache_ttl: Duration,
+}
+
+impl Default for LoadOptions {
+    fn default() -> LoadOptions {
+        LoadOptions {
+            cache: true,
+            allow_download: false,
+            version_cache: true,
+            version_cache_ttl: VersionCache::DEFAULT_TTL,
+        }
+    }
+}
+
+/// The names of every benchmark model supported by rebar. See MODELS.md for
+/// what each one means.
+pub const MODELS: &[&str] = &[
+    "compile",
+    "count",
+    "count-cold",
+    "count-spans",
+    "count-offsets",
+    "count-longest",
+    "count-captures",
+    "count-empty",
+    "count-per-pattern",
+    "first-match",
+    "grep",
+    "grep-fast",
+    "grep-captures",
+    "grep-max",
+    "anchored-count",
+    "regex-redux",
+    "size",
+];
+
+/// A problem found with a benchmark definition by `Benchmarks::lint`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LintIssue {
+    pub severity: LintSeverity,
+    /// The file in which the problem was found.
+    pub path: PathBuf,
+    /// The full name of the benchmark with the problem, if the problem is
+    /// specific to one benchmark.
+    pub benchmark: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: ", self.path.display())?;
+        if let Some(ref benchmark) = self.benchmark {
+            write!(f, "{}: ", benchmark)?;
+        }
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// How bad a lint issue is. Errors are problems that will cause a benchmark
+/// to fail to load or to behave incorrectly, while warnings are things that
+/// are probably a mistake but are otherwise harmless.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LintSeverity {
+    Error,
+    Warning,
+}
+
+impl std::fmt::Display for LintSeverity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            LintSeverity::Error => write!(f, "error"),
+            LintSeverity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, serde::Deserialize)]
 pub struct Engines {
     #[serde(skip)]
@@ -110,20 +360,89 @@ pub struct Engines {
     #[serde(rename = "engine")]
     #[serde(default)] // allows empty TOML files
     pub list: Vec<Engine>,
+    /// Glob patterns, relative to the benchmark directory, of additional
+    /// TOML files containing engine definitions. This is only permitted in
+    /// the top-level 'engines.toml' file.
+    #[serde(default)]
+    include: Vec<String>,
 }
 
 impl Engines {
+    /// Returns a set of engines with the given names. Each engine has a
+    /// bogus runner program and a version of '0.0.0'.
     #[cfg(test)]
+    pub fn fake(names: impl IntoIterator<Item = impl AsRef<str>>) -> Engines {
+        let list = names
+            .into_iter()
+            .map(|n| Engine {
+                name: n.as_ref().to_string(),
+                cwd: None,
+                run: Command {
+                    cwd: None,
+                    bin: "rebar".to_string(),
+                    args: vec![],
+                    envs: vec![],
+                },
+                version: "0.0.0".to_string(),
+                version_config: VersionConfig {
+                    regex: None,
+                    file: None,
+                    run: None,
+                    metadata: None,
+                },
+                metadata: None,
+                dependency: vec![],
+                build: vec![],
+                clean: vec![],
+                rewrite: vec![],
+                capabilities: vec![],
+                expected_version: None,
+                supports_validate: false,
+                extra_args: vec![],
+            })
+            .collect();
+        Engines::from_list(list)
+    }
+
     fn from_list(list: Vec<Engine>) -> Engines {
-        let mut engines = Engines { by_name: BTreeMap::new(), list };
+        let mut engines =
+            Engines { by_name: BTreeMap::new(), list, include: vec![] };
         for e in engines.list.iter() {
             engines.by_name.insert(e.name.clone(), e.clone());
         }
         engines
     }
 
+    /// Returns an error naming every engine whose version doesn't match its
+    /// expected version. Engines that are missing a version are skipped.
+    ///
+    /// This is used to implement '--locked'.
+    pub fn check_locked(&self) -> anyhow::Result<()> {
+        let mismatches: Vec<String> = self
+            .list
+            .iter()
+            .filter(|e| !e.is_missing_version())
+            .filter_map(|e| e.check_version(&e.version).err())
+            .map(|err| format!("{:#}", err))
+            .collect();
+        anyhow::ensure!(
+            mismatches.is_empty(),
+            "found regex engines with unexpected versions \
+             (because --locked was given):\n{}",
+            mismatches.join("\n"),
+        );
+        Ok(())
+    }
+
+    /// Read the engines defined in the 'engines.toml' file in the given
+    /// benchmark directory (along with any files it includes) for which
+    /// `include` returns true.
+    ///
+    /// The version of each included engine is detected as part of loading
+    /// it. See `LoadOptions` for how detected versions are cached.
     pub fn from_file(
         parent_dir: &Path,
+        opts: &LoadOptions,
         mut include: impl FnMut(&Engine) -> bool,
     ) -> anyhow::Result<Engines> {
         let Some(parent) = parent_dir.to_str() else {
@@ -134,32 +453,101 @@ impl Engines {
             );
         };
         let path = parent_dir.join("engines.toml");
-        let data = std::fs::read(&path).with_context(|| {
+        let mut engines = Engines::read(&path)?;
+        // Every engine along with the file it came from, so that we can
+        // report where duplicates were defined.
+        let mut sourced: Vec<(PathBuf, Engine)> =
+            engines.list.drain(..).map(|e| (path.clone(), e)).collect();
+        for pattern in std::mem::take(&mut engines.include) {
+            for include_path in Engines::glob(parent, &pattern)? {
+                let included = Engines::read(&include_path)?;
+                anyhow::ensure!(
+                    included.include.is_empty(),
+                    "{} contains an 'include' key, but 'include' is only \
+                     permitted in {}",
+                    include_path.display(),
+                    path.display(),
+                );
+                sourced.extend(
+                    included
+                        .list
+                        .into_iter()
+                        .map(|e| (include_path.clone(), e)),
+                );
+            }
+        }
+        let mut versions = VersionCache::open(parent_dir, opts);
+        let mut sources: BTreeMap<String, PathBuf> = BTreeMap::new();
+        for (source, mut e) in sourced {
+            if !include(&e) {
+                continue;
+            }
+            // Note that validate can modify parts of the engine, e.g.,
+            // to populate empty bin names with the path to the current
+            // executable. Relative paths in included files are always
+            // resolved relative to the benchmark directory, just like
+            // engines in the top-level engines.toml.
+            e.validate(parent, &mut versions).with_context(|| {
+                format!(
+                    "validation for engine '{}' (from {}) failed",
+                    e.name,
+                    source.display(),
+                )
+            })?;
+            if let Some(first) = sources.get(&e.name) {
+                anyhow::bail!(
+                    "found duplicate regex engine '{}' (defined in {} and {})",
+                    e.name,
+                    first.display(),
+                    source.display(),
+                );
+            }
+            sources.insert(e.name.clone(), source);
+            engines.by_name.insert(e.name.clone(), e.clone());
+            engines.list.push(e);
+        }
+        versions.save();
+        Ok(engines)
+    }
+
+    /// Read and decode a single TOML file of engine definitions. This does
+    /// not follow any includes or validate the engines.
+    fn read(path: &Path) -> anyhow::Result<Engines> {
+        let data = std::fs::read(path).with_context(|| {
             format!("failed to read engines from {}", path.display())
         })?;
         let data = std::str::from_utf8(&data).with_context(|| {
             format!("data in {} is not valid UTF-8", path.display())
         })?;
-        let mut engines: Engines =
-            toml::from_str(&data).with_context(|| {
-                format!("error decoding TOML for {}", path.display())
-            })?;
-        engines.list.retain(|e| include(e));
-        for e in engines.list.iter_mut() {
-            // Note that validate can modify parts of the engine, e.g.,
-            // to populate empty bin names with the path to the current
-            // executable.
-            e.validate(&parent).with_context(|| {
-                format!("validation for engine '{}' failed", e.name)
+        toml::from_str(data).with_context(|| {
+            format!("error decoding TOML for {}", path.display())
+        })
+    }
+
+    /// Returns the paths, in sorted order, matching the given glob pattern.
+    /// The pattern is interpreted relative to the given benchmark directory.
+    fn glob(bench_dir: &str, pattern: &str) -> anyhow::Result<Vec<PathBuf>> {
+        let full = Path::new(&glob::Pattern::escape(bench_dir)).join(pattern);
+        // OK because both bench_dir and pattern are valid UTF-8.
+        let full = full.to_str().unwrap();
+        let mut paths = vec![];
+        let entries = glob::glob(full).with_context(|| {
+            format!("invalid include pattern '{}' in engines.toml", pattern)
+        })?;
+        for result in entries {
+            let path = result.with_context(|| {
+                format!(
+                    "failed to read paths for include pattern '{}'",
+                    pattern
+                )
             })?;
-            anyhow::ensure!(
-                !engines.by_name.contains_key(&e.name),
-                "found duplicate regex engine '{}'",
-                e.name,
-            );
-            engines.by_name.insert(e.name.clone(), e.clone());
+            paths.push(path);
         }
-        Ok(engines)
+        if paths.is_empty() {
+            log::debug!("include pattern '{}' matched no files", pattern);
+        }
+        paths.sort();
+        Ok(paths)
     }
 }
 
@@ -172,15 +560,134 @@ pub struct Engine {
     pub version_config: VersionConfig,
     #[serde(skip)]
     pub version: String,
+    /// The build metadata reported by this engine's metadata command, if it
+    /// has one and it succeeded.
+    #[serde(skip)]
+    pub metadata: Option<String>,
     #[serde(default)]
     pub dependency: Vec<Dependency>,
     #[serde(default)]
     pub build: Vec<Command>,
     #[serde(default)]
     pub clean: Vec<Command>,
+    #[serde(default)]
+    pub rewrite: Vec<Rewrite>,
+    /// Free-form names of the things this engine supports, like
+    /// 'multi-pattern'. Benchmarks that require a capability are only run
+    /// with engines that declare it.
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// The version this engine is expected to have. It's either the exact
+    /// version string or a regex that must match the entire version string.
+    /// A mismatch is a warning, unless a command is run with '--locked'.
+    #[serde(default, rename = "expected-version")]
+    pub expected_version: Option<String>,
+    /// Whether this engine's runner program accepts a '--validate' flag that
+    /// runs a tiny built-in benchmark and exits with an error if it fails.
+    /// When true, 'rebar build' runs it after building the engine.
+    #[serde(default, rename = "supports-validate")]
+    pub supports_validate: bool,
+    /// Extra arguments appended to the 'run' command. These are never set in
+    /// 'engines.toml', but come from the engine reference in a benchmark
+    /// definition. When that reference also gives a label, then `name` is
+    /// the label instead of the engine's name in 'engines.toml'.
+    #[serde(skip)]
+    pub extra_args: Vec<String>,
 }
 
 impl Engine {
+    /// Returns an engine with the given name and version that isn't defined
+    /// in 'engines.toml'. It has no commands, so its runner program can't be
+    /// run. This is for 'rebar wrap', which runs a command of its own.
+    pub fn external(name: &str, version: &str) -> Engine {
+        Engine {
+            name: name.to_string(),
+            cwd: None,
+            run: Command {
+                cwd: None,
+                bin: String::new(),
+                args: vec![],
+                envs: vec![],
+            },
+            version: version.to_string(),
+            version_config: VersionConfig {
+                regex: None,
+                file: None,
+                run: None,
+                metadata: None,
+            },
+            metadata: None,
+            dependency: vec![],
+            build: vec![],
+            clean: vec![],
+            rewrite: vec![],
+            capabilities: vec![],
+            expected_version: None,
+            supports_validate: false,
+            extra_args: vec![],
+        }
+    }
+
+    /// Returns the first capability in the list given that this engine
+    /// doesn't declare, if one exists.
+    pub fn missing_capability<'a>(
+        &self,
+        requires: &'a [String],
+    ) -> Option<&'a str> {
+        requires
+            .iter()
+            .find(|cap| !self.capabilities.contains(cap))
+            .map(|cap| cap.as_str())
+    }
+
+    /// Apply this engine's rewrite rules to each of the given patterns and
+    /// return the result. Rules are applied in the order in which they are
+    /// defined, with each rule replacing all of its matches. If this engine
+    /// has no rewrite rules, then the patterns are returned unchanged.
+    pub fn rewrite_patterns(&self, patterns: &[String]) -> Vec<String> {
+        patterns
+            .iter()
+            .map(|p| {
+                let mut p = p.clone();
+                for rule in self.rewrite.iter() {
+                    let new = rule.find.replace_all(&p, &*rule.replace);
+                    if new != p {
+                        log::debug!(
+                            "engine '{}' rewrote pattern {:?} to {:?} \
+                             via rule {:?} => {:?}",
+                            self.name,
+                            p,
+                            new,
+                            rule.find.as_str(),
+                            rule.replace,
+                        );
+                    }
+                    p = new.into_owned();
+                }
+                p
+            })
+            .collect()
+    }
+
+    /// Returns an error if this engine has an expected version and the given
+    /// version doesn't match it. The error names the engine along with the
+    /// expected and detected versions.
+    pub fn check_version(&self, version: &str) -> anyhow::Result<()> {
+        let Some(ref expected) = self.expected_version else { return Ok(()) };
+        if version == expected {
+            return Ok(());
+        }
+        let re = expected_version_regex(expected)?;
+        anyhow::ensure!(
+            re.is_match(version),
+            "engine '{}' has version '{}', but its expected version is '{}'",
+            self.name,
+            version,
+            expected,
+        );
+        Ok(())
+    }
+
     /// Returns true if this engine is missing version information. This
     /// occurs when running the engine's version command fails.
     pub fn is_missing_version(&self) -> bool {
@@ -191,7 +698,11 @@ impl Engine {
         self.version == "ERROR"
     }
 
-    fn validate(&mut self, bench_dir: &str) -> anyhow::Result<()> {
+    fn validate(
+        &mut self,
+        bench_dir: &str,
+        versions: &mut VersionCache,
+    ) -> anyhow::Result<()> {
         let re_engine = regex!(r"^[-A-Za-z0-9]+(/[-A-Za-z0-9]+)*$");
 
         anyhow::ensure!(
@@ -212,17 +723,33 @@ impl Engine {
             Some(cwd.into_os_string().into_string().unwrap())
         };
         let cwd = self.cwd.as_deref();
-        self.run.validate(cwd)?;
+        // OK because we just set the cwd above.
+        let vars = CommandVars::new(bench_dir, cwd.unwrap())?;
+        self.run.validate(cwd, &vars).context("invalid 'run' command")?;
         if let Some(ref mut run) = self.version_config.run {
-            run.validate(cwd)?;
+            run.validate(cwd, &vars).context("invalid 'version' command")?;
+        }
+        if let Some(ref mut run) = self.version_config.metadata {
+            run.validate(cwd, &vars)
+                .context("invalid 'version.metadata' command")?;
+        }
+        for dep in self.dependency.iter_mut() {
+            // Dependency commands have never inherited the engine's working
+            // directory, so we don't give it to them here either.
+            dep.run
+                .validate(None, &vars)
+                .context("invalid 'dependency' command")?;
         }
         for cmd in self.build.iter_mut() {
-            cmd.validate(cwd)?;
+            cmd.validate(cwd, &vars).context("invalid 'build' command")?;
         }
         for cmd in self.clean.iter_mut() {
-            cmd.validate(cwd)?;
+            cmd.validate(cwd, &vars).context("invalid 'clean' command")?;
+        }
+        if let Some(ref expected) = self.expected_version {
+            expected_version_regex(expected)?;
         }
-        self.version = match self.version_config.get() {
+        self.version = match versions.get(self) {
             Ok(version) => version,
             Err(err) => {
                 log::debug!(
@@ -233,10 +760,213 @@ impl Engine {
                 "ERROR".to_string()
             }
         };
+        // An engine that isn't built yet doesn't have a version to check, and
+        // that's already reported elsewhere.
+        if !self.is_missing_version() {
+            self.metadata = match self.version_config.get_metadata() {
+                Ok(metadata) => metadata,
+                Err(err) => {
+                    log::warn!(
+                        "extracting metadata for engine '{}' failed: {:#}",
+                        self.name,
+                        err
+                    );
+                    None
+                }
+            };
+            if let Err(err) = self.check_version(&self.version) {
+                log::warn!("{:#}", err);
+            }
+        }
         Ok(())
     }
 }
 
+/// Compiles the given expected version of a regex engine into a regex that
+/// must match the entire version string.
+fn expected_version_regex(expected: &str) -> anyhow::Result<RRegex> {
+    RRegex::new(&format!("^(?:{})$", expected)).with_context(|| {
+        format!("expected-version '{}' is not a valid regex", expected)
+    })
+}
+
+/// The name of the file, relative to the benchmark directory, in which
+/// detected regex engine versions are cached.
+const VERSION_CACHE_FILE: &str = ".rebar-version-cache.json";
+
+/// An on-disk cache of detected regex engine versions.
+///
+/// Detecting the version of a regex engine requires running its version
+/// command, and some of those (like the ones that boot up an interpreter or
+/// a JVM) are slow. Since nearly every command loads regex engines, this adds
+/// up. So after a version is detected successfully, it's stored in a JSON
+/// file in the benchmark directory keyed by engine name, along with the
+/// modification time of the engine's runner program and when the version
+/// was detected.
+///
+/// A cached version is only reused when the runner program's modification
+/// time is unchanged (e.g., it hasn't been rebuilt) and the entry is younger
+/// than the configured TTL. Otherwise, the version command is run again.
+/// Engines whose runner program can't be found on disk (like one that is
+/// looked up via `PATH`) are never cached.
+///
+/// Like the haystack and regex cache, failures to read or write this cache
+/// are never fatal.
+#[derive(Debug)]
+pub struct VersionCache {
+    /// The path to the cache file, or `None` when caching is disabled.
+    path: Option<PathBuf>,
+    /// How long an entry remains valid after it was written.
+    ttl: Duration,
+    /// The entries read from the cache file, plus any updates.
+    entries: BTreeMap<String, VersionCacheEntry>,
+    /// Whether any entries have changed since they were read.
+    dirty: bool,
+}
+
+/// A single cached version for a regex engine.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+struct VersionCacheEntry {
+    version: String,
+    /// The modification time of the engine's runner program, relative to
+    /// the Unix epoch.
+    bin_mtime: Duration,
+    /// When the version was detected, in seconds since the Unix epoch.
+    timestamp: u64,
+}
+
+impl VersionCache {
+    /// The default time-to-live of a cached version.
+    pub const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+    /// Open the version cache for the given benchmark directory. When the
+    /// version cache is disabled by the options given, the cache returned
+    /// always runs each engine's version command and never writes anything.
+    pub fn open(bench_dir: &Path, opts: &LoadOptions) -> VersionCache {
+        let mut cache = VersionCache {
+            path: None,
+            ttl: opts.version_cache_ttl,
+            entries: BTreeMap::new(),
+            dirty: false,
+        };
+        if !opts.version_cache {
+            return cache;
+        }
+        let path = VersionCache::path(bench_dir);
+        match std::fs::read(&path) {
+            Ok(data) => match serde_json::from_slice(&data) {
+                Ok(entries) => cache.entries = entries,
+                Err(err) => log::debug!(
+                    "failed to decode version cache {}, ignoring it: {}",
+                    path.display(),
+                    err,
+                ),
+            },
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => log::debug!(
+                "failed to read version cache {}, ignoring it: {}",
+                path.display(),
+                err,
+            ),
+        }
+        cache.path = Some(path);
+        cache
+    }
+
+    /// Returns the path of the version cache file for the given benchmark
+    /// directory.
+    pub fn path(bench_dir: &Path) -> PathBuf {
+        bench_dir.join(VERSION_CACHE_FILE)
+    }
+
+    /// Returns the version of the given engine, either from the cache or by
+    /// running its version command. Successfully detected versions are added
+    /// to the cache, but only written to disk by `save`.
+    fn get(&mut self, engine: &Engine) -> anyhow::Result<String> {
+        if self.path.is_none() {
+            return engine.version_config.get();
+        }
+        let bin_mtime = match engine.run.bin().and_then(|bin| mtime(&bin)) {
+            Ok(bin_mtime) => bin_mtime,
+            Err(err) => {
+                log::debug!(
+                    "not caching version for engine '{}': {:#}",
+                    engine.name,
+                    err,
+                );
+                return engine.version_config.get();
+            }
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        if let Some(entry) = self.entries.get(&engine.name) {
+            let age = Duration::from_secs(now.saturating_sub(entry.timestamp));
+            if entry.bin_mtime == bin_mtime && age < self.ttl {
+                log::trace!(
+                    "using cached version '{}' for engine '{}'",
+                    entry.version,
+                    engine.name,
+                );
+                return Ok(entry.version.clone());
+            }
+            log::trace!("cached version for '{}' is stale", engine.name);
+        }
+        let version = engine.version_config.get()?;
+        let entry = VersionCacheEntry {
+            version: version.clone(),
+            bin_mtime,
+            timestamp: now,
+        };
+        self.entries.insert(engine.name.clone(), entry);
+        self.dirty = true;
+        Ok(version)
+    }
+
+    /// Write this cache back to disk if it has changed. Failures are logged.
+    fn save(&self) {
+        let Some(ref path) = self.path else { return };
+        if !self.dirty {
+            return;
+        }
+        let result = serde_json::to_vec_pretty(&self.entries)
+            .map_err(anyhow::Error::from)
+            .and_then(|data| Ok(std::fs::write(path, data)?));
+        if let Err(err) = result {
+            log::warn!(
+                "failed to write version cache {}: {:#}",
+                path.display(),
+                err,
+            );
+        }
+    }
+}
+
+/// Returns the modification time of the given file relative to the Unix
+/// epoch.
+fn mtime(path: &Path) -> anyhow::Result<Duration> {
+    let md = std::fs::metadata(path)
+        .with_context(|| format!("failed to stat {}", path.display()))?;
+    let modified = md.modified().with_context(|| {
+        format!("failed to get modification time of {}", path.display())
+    })?;
+    Ok(modified.duration_since(UNIX_EPOCH).unwrap_or_default())
+}
+
+/// A rule for rewriting a pattern before it's given to a particular regex
+/// engine. This is useful when an engine needs a mechanical tweak to a pattern
+/// in order to run a benchmark, e.g., when it spells a Unicode class
+/// differently from everyone else.
+///
+/// Every match of `find` in a pattern is replaced with `replace`, which may
+/// refer to capture groups in `find` via `$name` or `${name}` syntax.
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize)]
+pub struct Rewrite {
+    pub find: Regex,
+    pub replace: String,
+}
+
 /// Represents the configuration required to attain the version of a regex
 /// engine. This generally follows the process model and requires that the
 /// version string is accessible by running a sub-process. It does also permit
@@ -244,10 +974,16 @@ impl Engine {
 /// the output.
 ///
 /// Alternatively, the version string can just be stored in a flat file.
+///
+/// Optionally, a separate command can be given that reports build metadata
+/// for the regex engine, e.g., its enabled features or the CPU features it
+/// detected. This is useful because two builds of the same version of an
+/// engine can perform very differently.
 #[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize)]
 pub struct VersionConfig {
     pub regex: Option<Regex>,
     pub file: Option<String>,
+    pub metadata: Option<Command>,
     #[serde(flatten)]
     pub run: Option<Command>,
 }
@@ -273,7 +1009,7 @@ impl VersionConfig {
         let re = match self.regex {
             Some(ref re) => re,
             None => {
-                let last = match out.lines().last() {
+                let last = match out.lines().next_back() {
                     None => anyhow::bail!("version stdout was empty"),
                     Some(last) => last,
                 };
@@ -281,7 +1017,7 @@ impl VersionConfig {
             }
         };
         anyhow::ensure!(
-            re.capture_names().filter_map(|n| n).any(|n| n == "version"),
+            re.capture_names().flatten().any(|n| n == "version"),
             "version regex {:?} does not contain a 'version' capture group",
             re.as_str(),
         );
@@ -307,6 +1043,26 @@ impl VersionConfig {
         );
         Ok(version)
     }
+
+    /// Executes the metadata command in this `Version`, if present, and
+    /// returns its trimmed output. If there is no metadata command, then
+    /// this returns `None`.
+    ///
+    /// An error is returned if the command fails or if its output isn't a
+    /// single non-empty line.
+    pub fn get_metadata(&self) -> anyhow::Result<Option<String>> {
+        let Some(ref run) = self.metadata else { return Ok(None) };
+        let out = run.output().context("failed to get metadata")?;
+        log::trace!("metadata command output: {:?}", out.as_bstr());
+        let metadata = out.to_str()?.trim();
+        anyhow::ensure!(!metadata.is_empty(), "metadata stdout was empty");
+        anyhow::ensure!(
+            !metadata.contains('\n'),
+            "metadata stdout must be a single line, but got {:?}",
+            metadata,
+        );
+        Ok(Some(metadata.to_string()))
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize)]
@@ -399,14 +1155,114 @@ impl Command {
         })
     }
 
-    fn validate(&mut self, cwd: Option<&str>) -> anyhow::Result<()> {
+    /// Sets the working directory of this command to `cwd` if it doesn't
+    /// have one, and expands any placeholders in its arguments and
+    /// environment variable values.
+    fn validate(
+        &mut self,
+        cwd: Option<&str>,
+        vars: &CommandVars,
+    ) -> anyhow::Result<()> {
         if self.cwd.is_none() {
             self.cwd = cwd.map(|s| s.to_string());
         }
+        for arg in self.args.iter_mut() {
+            *arg = vars
+                .expand(arg)
+                .with_context(|| format!("failed to expand arg '{}'", arg))?;
+        }
+        for env in self.envs.iter_mut() {
+            env.value = vars.expand(&env.value).with_context(|| {
+                format!(
+                    "failed to expand value of environment variable '{}'",
+                    env.name,
+                )
+            })?;
+        }
         Ok(())
     }
 }
 
+/// The values that placeholders like `{engine-dir}` in the arguments and
+/// environment variable values of an engine's commands expand to.
+///
+/// This exists so that engine definitions can refer to rebar-managed
+/// locations without hard-coding paths that only work in one checkout. Every
+/// directory is made absolute, since commands may run in a different working
+/// directory than rebar.
+#[derive(Clone, Debug)]
+struct CommandVars {
+    vars: Vec<(&'static str, String)>,
+}
+
+impl CommandVars {
+    /// Create the placeholder values for an engine in the given benchmark
+    /// directory whose resolved working directory is `engine_dir`.
+    fn new(bench_dir: &str, engine_dir: &str) -> anyhow::Result<CommandVars> {
+        let absolute = |path: &Path| -> anyhow::Result<String> {
+            std::path::absolute(path)
+                .with_context(|| {
+                    format!("failed to make '{}' absolute", path.display())
+                })?
+                .into_os_string()
+                .into_string()
+                .map_err(|_| anyhow::anyhow!("path is not valid UTF-8"))
+        };
+        let engine_dir = Path::new(engine_dir);
+        let vars = vec![
+            ("bench-dir", absolute(Path::new(bench_dir))?),
+            ("engine-dir", absolute(engine_dir)?),
+            ("rebar-exe", util::current_exe()?),
+            ("target-dir", absolute(&engine_dir.join("target"))?),
+        ];
+        Ok(CommandVars { vars })
+    }
+
+    /// Expand every `{name}` placeholder in the given string with its value.
+    /// A literal `{` or `}` can be written as `{{` or `}}`, respectively.
+    ///
+    /// This returns an error if a placeholder is unknown or isn't closed.
+    fn expand(&self, template: &str) -> anyhow::Result<String> {
+        let mut expanded = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(i) = rest.find(['{', '}']) {
+            expanded.push_str(&rest[..i]);
+            rest = &rest[i..];
+            if rest.starts_with("{{") || rest.starts_with("}}") {
+                expanded.push_str(&rest[..1]);
+                rest = &rest[2..];
+                continue;
+            }
+            if rest.starts_with('}') {
+                expanded.push('}');
+                rest = &rest[1..];
+                continue;
+            }
+            let Some(end) = rest.find('}') else {
+                anyhow::bail!("found unclosed placeholder in '{}'", template);
+            };
+            let name = &rest[1..end];
+            let Some((_, value)) = self.vars.iter().find(|(n, _)| *n == name)
+            else {
+                let names: Vec<String> = self
+                    .vars
+                    .iter()
+                    .map(|(n, _)| format!("{{{}}}", n))
+                    .collect();
+                anyhow::bail!(
+                    "unknown placeholder '{{{}}}', must be one of {}",
+                    name,
+                    names.join(", "),
+                );
+            };
+            expanded.push_str(value);
+            rest = &rest[end + 1..];
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize)]
 pub struct CommandEnv {
     pub name: String,
@@ -417,26 +1273,392 @@ pub struct CommandEnv {
 pub struct Definition {
     pub model: String,
     pub name: DefinitionName,
+    /// The patterns of this benchmark. When the patterns were read from a
+    /// file with 'binary = true', then any bytes that aren't valid UTF-8 are
+    /// shown as '\xNN' escapes here, and the actual patterns are in
+    /// `binary_regexes`.
     pub regexes: Arc<[String]>,
+    /// The patterns of this benchmark as raw bytes, but only when they were
+    /// read from a file with 'binary = true'. Otherwise, this is `None`.
+    pub binary_regexes: Option<Arc<[Vec<u8>]>>,
     pub regex_path: Option<String>,
     pub options: DefinitionOptions,
     pub haystack: Arc<[u8]>,
     pub haystack_path: Option<String>,
+    /// The encoding of `haystack`. This is sent to runner programs so that
+    /// they know how to interpret the haystack bytes.
+    pub haystack_encoding: klv::HaystackEncoding,
     pub count: Vec<CountEngine>,
+    /// When present, the expected count for every regex engine is whatever
+    /// this reference regex engine reports. In this case, `count` is empty
+    /// until it is resolved by running the reference engine, which 'rebar
+    /// measure' does before running any benchmarks.
+    pub count_derive_from: Option<String>,
+    /// Whether the count reported by a regex engine should be checked against
+    /// `count`. When this is false, `count` is always empty and whatever
+    /// count a regex engine reports is accepted.
+    pub verify_count: bool,
     pub engines: Vec<Engine>,
     pub analysis: Option<String>,
+    /// The capabilities that a regex engine must declare in order to run
+    /// this benchmark.
+    pub requires: Vec<String>,
+    /// Overrides of the default time and iteration budgets used when
+    /// measuring this benchmark.
+    pub budget: DefinitionBudget,
+    /// Free-form parameters sent to runner programs, which may use them to
+    /// configure the regex engine (e.g., its size limits). Runner programs
+    /// ignore the parameters they don't understand.
+    pub params: BTreeMap<String, String>,
 }
 
 impl Definition {
-    pub fn count(&self, engine: &str) -> anyhow::Result<u64> {
-        for ce in self.count.iter() {
-            if ce.re.is_match(engine) {
-                return Ok(ce.count);
+    /// The capability that a regex engine must declare in order to run a
+    /// benchmark whose patterns were read from a file with 'binary = true'.
+    pub const BINARY_PATTERN_CAPABILITY: &'static str = "binary-pattern";
+
+    /// Returns the patterns of this benchmark as raw bytes.
+    pub fn patterns_bytes(&self) -> Vec<&[u8]> {
+        match self.binary_regexes {
+            Some(ref ps) => ps.iter().map(|p| p.as_slice()).collect(),
+            None => self.regexes.iter().map(|p| p.as_bytes()).collect(),
+        }
+    }
+
+    /// Returns the regex configuration to send to a runner program for this
+    /// benchmark. When an engine is given, its rewrite rules are applied to
+    /// the patterns.
+    ///
+    /// This returns an error if an engine with rewrite rules is given and
+    /// this benchmark has binary patterns, since rewrite rules only apply to
+    /// patterns that are valid UTF-8.
+    pub fn klv_regex(
+        &self,
+        engine: Option<&Engine>,
+    ) -> anyhow::Result<klv::Regex> {
+        let mut regex = klv::Regex {
+            case_insensitive: self.options.case_insensitive,
+            unicode: self.options.unicode,
+            multiline: self.options.multiline,
+            dot_all: self.options.dot_all,
+            ..klv::Regex::default()
+        };
+        match self.binary_regexes {
+            Some(ref ps) => {
+                if let Some(e) = engine {
+                    anyhow::ensure!(
+                        e.rewrite.is_empty(),
+                        "engine '{}' has rewrite rules, but they can't be \
+                         applied to the binary patterns of benchmark '{}'",
+                        e.name,
+                        self.name,
+                    );
+                }
+                regex.set_patterns_bytes(ps.to_vec());
+            }
+            None => {
+                regex.patterns = match engine {
+                    None => self.regexes.to_vec(),
+                    Some(e) => e.rewrite_patterns(&self.regexes),
+                };
             }
         }
-        anyhow::bail!("no count available for engine '{}'", engine)
+        Ok(regex)
     }
-}
+
+    /// Returns the expected count for the given engine name and version.
+    ///
+    /// The first count entry whose engine regex matches the engine name and
+    /// whose version requirement (if present) matches the engine version is
+    /// used. If at least one entry matches the engine name but none of them
+    /// match its version, then the error returned says so. An error is also
+    /// returned if a later entry with a different count is shadowed by an
+    /// earlier one. (See `check_shadowed_counts`.)
+    pub fn count(&self, engine: &str, version: &str) -> anyhow::Result<u64> {
+        self.count_entry(engine, version).map(|ce| ce.count)
+    }
+
+    /// Returns the count entry used for the given engine name and version.
+    ///
+    /// This is like `count`, but returns the entire entry so that callers can
+    /// see any other details attached to it, like its semantics annotation.
+    pub fn count_entry(
+        &self,
+        engine: &str,
+        version: &str,
+    ) -> anyhow::Result<&CountEngine> {
+        if let Some(ref name) = self.count_derive_from {
+            anyhow::ensure!(
+                !self.count.is_empty(),
+                "count for benchmark '{}' is derived from regex engine '{}' \
+                 and hasn't been resolved yet",
+                self.name,
+                name,
+            );
+        }
+        anyhow::ensure!(
+            !self.is_count_unresolved(),
+            "benchmark '{}' has no count, use 'rebar measure --compute-counts \
+             --oracle <engine>' to compute one",
+            self.name,
+        );
+        // Engines listed in the definition were already checked when it was
+        // loaded, but callers may ask about other engines too.
+        if !self.engines.iter().any(|e| e.name == engine) {
+            let mut engines: Vec<&str> =
+                self.engines.iter().map(|e| &*e.name).collect();
+            engines.push(engine);
+            self.check_shadowed_counts(&engines)?;
+        }
+        let mut matched_engine = false;
+        for ce in self.count.iter() {
+            if !ce.re.is_match(engine) {
+                continue;
+            }
+            matched_engine = true;
+            if ce.version.as_ref().is_none_or(|req| req.matches(version)) {
+                return Ok(ce);
+            }
+        }
+        if matched_engine {
+            anyhow::bail!(
+                "no count entry matches engine version '{}' for engine '{}'",
+                version,
+                engine,
+            )
+        }
+        anyhow::bail!("no count available for engine '{}'", engine)
+    }
+
+    /// Returns an error if a count entry is shadowed by earlier entries with a
+    /// different count, considering only the engine names given.
+    ///
+    /// An entry is shadowed when every given engine it matches is also
+    /// matched by an earlier entry, where that earlier entry either has no
+    /// version requirement or has the same one. Such an entry is never used,
+    /// so its count is almost certainly not the count its author intended.
+    /// For example, an entry for 'rust/regex/lite' after an entry for
+    /// 'rust/.*'. An entry that matches at least one engine not matched by
+    /// any earlier entry isn't shadowed, which permits a catch-all entry
+    /// like '.*' at the end. Shadowed entries with the same count as the
+    /// entry shadowing them are harmless and thus permitted.
+    fn check_shadowed_counts(&self, engines: &[&str]) -> anyhow::Result<()> {
+        for (i, later) in self.count.iter().enumerate() {
+            let mut conflict = None;
+            let mut shadowed = false;
+            for &engine in engines.iter() {
+                if !later.re.is_match(engine) {
+                    continue;
+                }
+                let earlier = self.count[..i].iter().find(|ce| {
+                    ce.re.is_match(engine)
+                        && (ce.version.is_none()
+                            || ce.version == later.version)
+                });
+                let Some(earlier) = earlier else {
+                    shadowed = false;
+                    break;
+                };
+                shadowed = true;
+                if earlier.count != later.count && conflict.is_none() {
+                    conflict = Some((engine, earlier));
+                }
+            }
+            let Some((engine, earlier)) = conflict.filter(|_| shadowed) else {
+                continue;
+            };
+            anyhow::bail!(
+                "benchmark '{}' has conflicting counts for regex engine '{}': \
+                 the count entry for '{}' (count = {}) shadows the later \
+                 entry for '{}' (count = {}), so the later entry is never \
+                 used (put more specific entries first)",
+                self.name,
+                engine,
+                earlier.engine,
+                earlier.count,
+                later.engine,
+                later.count,
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns true when this definition doesn't have a count. Such
+    /// definitions can't be measured or verified until a count is added, but
+    /// one can be computed with 'rebar measure --compute-counts'.
+    pub fn is_count_unresolved(&self) -> bool {
+        self.verify_count && self.count.is_empty()
+    }
+
+    /// Returns the number of lines in this definition's haystack.
+    ///
+    /// This uses the same notion of a line as the 'grep' model: lines are
+    /// terminated by '\n' (or '\r\n') and a trailing line terminator does
+    /// not start a new empty line.
+    pub fn line_count(&self) -> u64 {
+        // OK because a usize always fits in a u64 on supported platforms.
+        u64::try_from(self.haystack.lines().count()).unwrap()
+    }
+
+    /// Returns an error if this definition has a search window that is out
+    /// of bounds for its haystack, or if its model doesn't support search
+    /// windows.
+    fn check_search_window(&self) -> anyhow::Result<()> {
+        if !self.options.has_search_window() {
+            return Ok(());
+        }
+        // Other models could support this in principle, but every runner
+        // program would need to thread the window through every model.
+        anyhow::ensure!(
+            self.model == "count",
+            "benchmark '{}' sets 'search-start' or 'search-end', but search \
+             windows are only supported by the 'count' model",
+            self.name,
+        );
+        let start = self.options.search_start.unwrap_or(0);
+        let end = self.options.search_end.unwrap_or(self.haystack.len());
+        anyhow::ensure!(
+            start <= end && end <= self.haystack.len(),
+            "benchmark '{}' has invalid search window {}..{} for a haystack \
+             of length {}",
+            self.name,
+            start,
+            end,
+            self.haystack.len(),
+        );
+        Ok(())
+    }
+
+    /// Returns an error if this definition has a 'max-count' that is zero,
+    /// or if its model isn't 'grep-max'.
+    fn check_max_count(&self) -> anyhow::Result<()> {
+        let Some(max) = self.options.max_count else { return Ok(()) };
+        anyhow::ensure!(
+            self.model == "grep-max",
+            "benchmark '{}' sets 'max-count', but it's only supported by the \
+             'grep-max' model",
+            self.name,
+        );
+        anyhow::ensure!(
+            max >= 1,
+            "benchmark '{}' has 'max-count = 0', but it must be at least 1 \
+             (omit it for no limit)",
+            self.name,
+        );
+        Ok(())
+    }
+}
+
+/// Computes hashes of benchmark definitions.
+///
+/// The hash of a definition covers everything in it that can influence a
+/// measurement of it: its model, patterns, options, (transformed) haystack
+/// and counts. The hash is recorded with every measurement so that
+/// measurements taken before a definition changed can be detected. It is a
+/// truncated SHA-256 digest of a canonical serialization of the definition,
+/// so it is the same on every platform.
+///
+/// Many definitions share the same haystack (which can be quite large), so
+/// this remembers the digest of each haystack it has seen and only hashes it
+/// once.
+#[derive(Clone, Debug, Default)]
+pub struct DefinitionHasher {
+    /// A map from the address of a haystack to the haystack itself and its
+    /// digest. We hang on to the haystack so that its address can't be reused
+    /// by a different haystack while this hasher is alive.
+    haystacks: BTreeMap<usize, (Arc<[u8]>, String)>,
+}
+
+impl DefinitionHasher {
+    /// Returns the hash of the given definition.
+    pub fn hash(&mut self, def: &Definition) -> String {
+        // The canonical serialization is a sequence of KLV items (the same
+        // format used to send benchmarks to runner programs), which ensures
+        // that distinct definitions can't have the same serialization.
+        fn item(buf: &mut Vec<u8>, key: &str, value: &[u8]) {
+            buf.extend_from_slice(key.as_bytes());
+            buf.push(b':');
+            buf.extend_from_slice(value.len().to_string().as_bytes());
+            buf.push(b':');
+            buf.extend_from_slice(value);
+            buf.push(b'\n');
+        }
+
+        let addr = Arc::as_ptr(&def.haystack) as *const u8 as usize;
+        let (_, haystack_digest) =
+            self.haystacks.entry(addr).or_insert_with(|| {
+                let digest = util::sha256_hex(&def.haystack);
+                (Arc::clone(&def.haystack), digest)
+            });
+        let mut buf = vec![];
+        item(&mut buf, "model", def.model.as_bytes());
+        for pattern in def.patterns_bytes() {
+            item(&mut buf, "regex", pattern);
+        }
+        let ci = def.options.case_insensitive.to_string();
+        item(&mut buf, "case-insensitive", ci.as_bytes());
+        let unicode = def.options.unicode.to_string();
+        item(&mut buf, "unicode", unicode.as_bytes());
+        // Like 'verify-count' below, these are only added when enabled so
+        // that the hashes of all other definitions don't change.
+        if def.options.multiline {
+            item(&mut buf, "multiline", b"true");
+        }
+        if def.options.dot_all {
+            item(&mut buf, "dot-all", b"true");
+        }
+        if let Some(start) = def.options.search_start {
+            item(&mut buf, "search-start", start.to_string().as_bytes());
+        }
+        if let Some(end) = def.options.search_end {
+            item(&mut buf, "search-end", end.to_string().as_bytes());
+        }
+        if let Some(max) = def.options.max_count {
+            item(&mut buf, "max-count", max.to_string().as_bytes());
+        }
+        // Parameters can change what a regex engine does, so they're
+        // included. But there aren't any for most definitions, which means
+        // their hashes don't change.
+        for (name, value) in def.params.iter() {
+            item(&mut buf, "param", format!("{}={}", name, value).as_bytes());
+        }
+        item(&mut buf, "haystack", haystack_digest.as_bytes());
+        // Only added when it isn't UTF-8 so that the hashes of all other
+        // definitions don't change.
+        if def.haystack_encoding != klv::HaystackEncoding::Utf8 {
+            let enc = def.haystack_encoding.as_str();
+            item(&mut buf, "haystack-encoding", enc.as_bytes());
+        }
+        for ce in def.count.iter() {
+            let version = ce.version.as_ref().map(|v| v.to_string());
+            let per_pattern = ce.per_pattern.as_ref().map(|counts| {
+                counts.iter().map(|c| c.to_string()).collect::<Vec<_>>()
+            });
+            let count = format!(
+                "{},{},{},{}",
+                ce.engine,
+                version.unwrap_or_default(),
+                ce.count,
+                per_pattern.map(|c| c.join(" ")).unwrap_or_default(),
+            );
+            item(&mut buf, "count", count.as_bytes());
+        }
+        // A derived count is resolved before hashing when measuring, so the
+        // count itself is covered above. But we also record which engine it
+        // came from, so that changing the reference engine changes the hash.
+        if let Some(ref name) = def.count_derive_from {
+            item(&mut buf, "count-derive-from", name.as_bytes());
+        }
+        // Only added when disabled so that the hashes of all other definitions
+        // don't change.
+        if !def.verify_count {
+            item(&mut buf, "verify-count", b"false");
+        }
+        let mut digest = util::sha256_hex(&buf);
+        digest.truncate(16);
+        digest
+    }
+}
 
 // We hand-roll our own Debug impl so that the 'haystack' field doesn't vomit
 // a huge string (since most haystacks are quite large).
@@ -454,16 +1676,37 @@ impl std::fmt::Debug for Definition {
             .field("model", &self.model)
             .field("name", &self.name)
             .field("regexes", &self.regexes)
+            .field("binary_regexes", &self.binary_regexes.is_some())
             .field("regex_path", &self.regex_path)
             .field("options", &self.options)
             .field("haystack", &haystack.as_bstr())
             .field("haystack_path", &self.haystack_path)
             .field("count", &self.count)
+            .field("count_derive_from", &self.count_derive_from)
+            .field("verify_count", &self.verify_count)
             .field("engines", &self.engines)
+            .field("requires", &self.requires)
+            .field("budget", &self.budget)
+            .field("params", &self.params)
             .finish()
     }
 }
 
+/// Overrides of the time and iteration budgets used by 'rebar measure' for a
+/// single benchmark definition. Each budget that is present replaces the
+/// default for that benchmark, but budgets given explicitly on the command
+/// line take precedence over these.
+///
+/// This is useful for benchmarks that need a much bigger (or much smaller)
+/// budget than everything else, e.g., ones that search huge haystacks.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DefinitionBudget {
+    pub max_iters: Option<u64>,
+    pub max_warmup_iters: Option<u64>,
+    pub max_time: Option<Duration>,
+    pub max_warmup_time: Option<Duration>,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct DefinitionName {
     pub full: String,
@@ -487,7 +1730,167 @@ impl std::fmt::Display for DefinitionName {
 pub struct CountEngine {
     pub re: Regex,
     pub engine: String,
+    pub version: Option<VersionReq>,
     pub count: u64,
+    /// A short free-form description of the match semantics that produce
+    /// this count, like 'simple-fold' or 'full-fold'. This is purely
+    /// informational and is used to explain why different engines are
+    /// verified against different counts in the same benchmark.
+    pub semantics: Option<String>,
+    /// For the 'count-per-pattern' model, the expected number of matches for
+    /// each pattern, in the order the patterns are given. In this case,
+    /// `count` is the checksum of these counts computed by
+    /// `per_pattern_checksum`, since that's what runner programs report.
+    pub per_pattern: Option<Vec<u64>>,
+    /// When this count was derived from a reference regex engine, this
+    /// contains the name and version of that engine, like 'rust/regex
+    /// 1.10.2'. This is used to explain where a count came from, since it
+    /// doesn't appear in the benchmark definition.
+    pub derived_from: Option<String>,
+}
+
+impl CountEngine {
+    /// Returns a count entry that applies to every engine.
+    pub fn all(count: u64) -> CountEngine {
+        CountEngine {
+            re: Regex(RRegex::new(r"^.*$").unwrap()),
+            engine: r".*".to_string(),
+            version: None,
+            count,
+            semantics: None,
+            per_pattern: None,
+            derived_from: None,
+        }
+    }
+}
+
+/// Returns the checksum that runner programs report for the
+/// 'count-per-pattern' model, given the number of matches for each pattern.
+///
+/// Every match contributes the square of its pattern index plus one to the
+/// checksum. So when a runner attributes a match to the wrong pattern, the
+/// checksum changes even though the total number of matches doesn't.
+///
+/// Squaring isn't strictly necessary to catch a single misattributed match,
+/// but it makes it much less likely that several of them cancel each other
+/// out. For example, with a weight of just the index plus one, the counts
+/// `[2, 1, 2]` and `[1, 3, 1]` have the same checksum.
+pub fn per_pattern_checksum(counts: &[u64]) -> u64 {
+    counts.iter().zip(1u64..).map(|(&count, id)| id * id * count).sum()
+}
+
+/// A requirement on the version of a regex engine.
+///
+/// This is used to pick different counts for different versions of the same
+/// regex engine, for example, when an upgrade changes match semantics. It's
+/// a lightweight and forgiving take on semver requirements: a requirement is
+/// a comma separated list of comparators like '>=10.43' or '<2', all of which
+/// must match. A comparator without an operator (or with '=') matches any
+/// version that starts with the numbers given, so '10.42' matches '10.42.1'.
+///
+/// Engine version strings are free-form, so the version that is compared is
+/// the first sequence of dot-separated integers found in it. For example,
+/// '10.42 2022-12-11' is treated as '10.42'. Missing trailing components are
+/// treated as zero when comparing with '<', '<=', '>' or '>='.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VersionReq {
+    raw: String,
+    comparators: Vec<(VersionOp, Vec<u64>)>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum VersionOp {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl VersionReq {
+    /// Parse a version requirement, e.g., '>=10.43' or '>=1.2, <1.5'.
+    pub fn parse(raw: &str) -> anyhow::Result<VersionReq> {
+        let mut comparators = vec![];
+        for part in raw.split(',') {
+            let part = part.trim();
+            let (op, rest) = if let Some(rest) = part.strip_prefix(">=") {
+                (VersionOp::Ge, rest)
+            } else if let Some(rest) = part.strip_prefix("<=") {
+                (VersionOp::Le, rest)
+            } else if let Some(rest) = part.strip_prefix('>') {
+                (VersionOp::Gt, rest)
+            } else if let Some(rest) = part.strip_prefix('<') {
+                (VersionOp::Lt, rest)
+            } else if let Some(rest) = part.strip_prefix('=') {
+                (VersionOp::Eq, rest)
+            } else {
+                (VersionOp::Eq, part)
+            };
+            let nums = rest
+                .trim()
+                .split('.')
+                .map(|n| n.parse::<u64>())
+                .collect::<Result<Vec<u64>, _>>()
+                .with_context(|| {
+                    format!(
+                        "invalid version '{}' in version requirement '{}'",
+                        rest.trim(),
+                        raw,
+                    )
+                })?;
+            comparators.push((op, nums));
+        }
+        Ok(VersionReq { raw: raw.to_string(), comparators })
+    }
+
+    /// Returns true if and only if the given engine version string satisfies
+    /// this requirement. If no version number could be found in the string
+    /// given, then this always returns false.
+    pub fn matches(&self, version: &str) -> bool {
+        let re = regex!(r"[0-9]+(?:\.[0-9]+)*");
+        let nums: Vec<u64> = match re.find(version) {
+            None => return false,
+            Some(m) => {
+                match m.as_str().split('.').map(|n| n.parse()).collect() {
+                    Ok(nums) => nums,
+                    Err(_) => return false,
+                }
+            }
+        };
+        self.comparators.iter().all(|(op, want)| {
+            if *op == VersionOp::Eq {
+                return nums.len() >= want.len()
+                    && nums[..want.len()] == want[..];
+            }
+            let len = nums.len().max(want.len());
+            let get = |v: &[u64], i: usize| v.get(i).copied().unwrap_or(0);
+            let ord = (0..len)
+                .map(|i| get(&nums, i).cmp(&get(want, i)))
+                .find(|ord| ord.is_ne())
+                .unwrap_or(std::cmp::Ordering::Equal);
+            match *op {
+                VersionOp::Eq => unreachable!(),
+                VersionOp::Lt => ord.is_lt(),
+                VersionOp::Le => ord.is_le(),
+                VersionOp::Gt => ord.is_gt(),
+                VersionOp::Ge => ord.is_ge(),
+            }
+        })
+    }
+}
+
+impl std::fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.raw.fmt(f)
+    }
+}
+
+impl std::str::FromStr for VersionReq {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<VersionReq> {
+        VersionReq::parse(s)
+    }
 }
 
 #[derive(Clone, Debug, Default, Eq, PartialEq, serde::Deserialize)]
@@ -497,6 +1900,36 @@ pub struct DefinitionOptions {
     pub case_insensitive: bool,
     #[serde(default)]
     pub unicode: bool,
+    /// When enabled, `^` and `$` match at the beginning and end of lines.
+    #[serde(default)]
+    pub multiline: bool,
+    /// When enabled, `.` matches any character, including `\n`.
+    #[serde(default)]
+    pub dot_all: bool,
+    /// When present, searches start at this byte offset in the haystack.
+    /// Unlike slicing the haystack, the bytes before it are still visible to
+    /// look-around assertions.
+    #[serde(default)]
+    pub search_start: Option<usize>,
+    /// When present, searches end at this byte offset in the haystack.
+    #[serde(default)]
+    pub search_end: Option<usize>,
+    /// When present, the 'grep-max' model stops counting the matches in each
+    /// line after this many. When absent, there is no limit.
+    #[serde(default)]
+    pub max_count: Option<u64>,
+}
+
+impl DefinitionOptions {
+    /// The capability that regex engines must declare in order to run
+    /// benchmarks with a search window. Definitions with a search window
+    /// require it implicitly.
+    pub const SEARCH_WINDOW_CAPABILITY: &'static str = "search-window";
+
+    /// Returns true when either end of the search window is set.
+    pub fn has_search_window(&self) -> bool {
+        self.search_start.is_some() || self.search_end.is_some()
+    }
 }
 
 #[derive(Clone, Debug, serde::Deserialize)]
@@ -551,16 +1984,9 @@ impl WireDefinitions {
                 dir.display(),
             )
         })?;
-        let group = suffix
-            .with_extension("")
-            .to_str()
-            .with_context(|| {
-                format!("invalid UTF-8 found in {}", path.display())
-            })?
-            // If we're on Windows and get \ path separators,
-            // change them to /.
-            .replace("\\", "/")
-            .to_string();
+        let group = group_name(suffix).with_context(|| {
+            format!("invalid UTF-8 found in {}", path.display())
+        })?;
         let data = std::fs::read(path)
             .with_context(|| format!("failed to read {}", path.display()))?;
         self.load_slice(&group, &data)
@@ -573,11 +1999,17 @@ impl WireDefinitions {
     /// stem of the file name.
     fn load_slice(&mut self, group: &str, data: &[u8]) -> anyhow::Result<()> {
         let data = std::str::from_utf8(data)?;
-        let top: WireDefinitions = toml::from_str(data)
-            .with_context(|| format!("error decoding TOML for '{}'", group))?;
+        let top: WireDefinitions = match expand_matrices(data)? {
+            None => toml::from_str(data),
+            Some(expanded) => expanded.try_into(),
+        }
+        .with_context(|| format!("error decoding TOML for '{}'", group))?;
         for mut def in top.definitions {
             def.group = group.to_string();
             def.name = format!("{}/{}", def.group, def.local);
+            def.normalize_paths().with_context(|| {
+                format!("invalid path in benchmark '{}'", def.name)
+            })?;
             self.definitions.push(def);
         }
         if let Some(ref analysis) = top.analysis {
@@ -628,7 +2060,7 @@ impl WireDefinitions {
                 return true;
             }
             for engine in def.engines.iter() {
-                if filter.include(engine) {
+                if filter.include(engine.label()) {
                     return true;
                 }
             }
@@ -638,18 +2070,364 @@ impl WireDefinitions {
 
     /// Returns a set of all engines that both pass the given filter and
     /// have an explicit reference in these benchmarks.
+    ///
+    /// The filter is applied to the label of each reference, but the names
+    /// returned are always the names of engines in 'engines.toml'.
     fn engine_references(&self, filter: &Filter) -> BTreeSet<String> {
         let mut set = BTreeSet::new();
         for def in self.definitions.iter() {
             for engine in def.engines.iter() {
-                if !filter.include(engine) {
+                if !filter.include(engine.label()) {
                     continue;
                 }
-                set.insert(engine.clone());
+                set.insert(engine.name().to_string());
             }
         }
         set
     }
+
+    /// Check every definition for problems that can be found without loading
+    /// its regexes or haystack: unknown models, references to regex engines
+    /// that don't exist, count entries that are dead or match nothing and
+    /// required capabilities that no regex engine has.
+    fn lint(&self, dir: &Path, engines: &Engines) -> Vec<LintIssue> {
+        let capabilities: BTreeSet<&str> = engines
+            .list
+            .iter()
+            .flat_map(|e| e.capabilities.iter().map(|c| c.as_str()))
+            .collect();
+        let mut issues = vec![];
+        for def in self.definitions.iter() {
+            let mut issue = |severity, message| {
+                issues.push(LintIssue {
+                    severity,
+                    path: def.path(dir),
+                    benchmark: Some(def.name.clone()),
+                    message,
+                })
+            };
+            if !MODELS.contains(&&*def.model) {
+                issue(
+                    LintSeverity::Error,
+                    format!("unknown model '{}'", def.model),
+                );
+            }
+            for engine in def.engines.iter() {
+                if !engines.by_name.contains_key(engine.name()) {
+                    issue(
+                        LintSeverity::Error,
+                        format!("unknown regex engine '{}'", engine.name()),
+                    );
+                }
+                // A label that's also the name of a different engine would
+                // make the measurements of the two indistinguishable.
+                let label = engine.label();
+                if label != engine.name()
+                    && engines.by_name.contains_key(label)
+                {
+                    issue(
+                        LintSeverity::Error,
+                        format!(
+                            "label '{}' for regex engine '{}' is also the \
+                             name of a regex engine",
+                            label,
+                            engine.name(),
+                        ),
+                    );
+                }
+            }
+            if let Err(err) = def.check_engine_labels() {
+                issue(LintSeverity::Error, format!("{:#}", err));
+            }
+            for cap in def.requires().iter() {
+                // This is probably a typo, and if it isn't, then the
+                // benchmark can't be run by any engine.
+                if !capabilities.contains(cap.as_str()) {
+                    issue(
+                        LintSeverity::Warning,
+                        format!(
+                            "required capability '{}' is not declared by \
+                             any regex engine",
+                            cap,
+                        ),
+                    );
+                }
+            }
+            let mut seen = BTreeSet::new();
+            for (engine, version) in def.count_engines() {
+                if !seen.insert((engine, version)) {
+                    let version = match version {
+                        None => String::new(),
+                        Some(v) => format!(" with version '{}'", v),
+                    };
+                    issue(
+                        LintSeverity::Error,
+                        format!(
+                            "duplicate count for engine '{}'{}",
+                            engine, version
+                        ),
+                    );
+                }
+                // If this fails, then loading the definition reports it.
+                let Ok(re) = RRegex::new(&format!("^(?:{})$", engine)) else {
+                    continue;
+                };
+                let mut names = engines
+                    .list
+                    .iter()
+                    .map(|e| e.name.as_str())
+                    .chain(def.engines.iter().map(|e| e.label()));
+                if !names.any(|name| re.is_match(name)) {
+                    issue(
+                        LintSeverity::Warning,
+                        format!(
+                            "count for engine '{}' does not match any \
+                             regex engine",
+                            engine,
+                        ),
+                    );
+                }
+            }
+        }
+        issues
+    }
+
+    /// Look for haystack and regex files in the given benchmark directory
+    /// that aren't referenced by any definition. Hidden files and README.md
+    /// files are never reported.
+    fn lint_unreferenced(&self, dir: &Path) -> anyhow::Result<Vec<LintIssue>> {
+        let mut hays = BTreeSet::new();
+        let mut res = BTreeSet::new();
+        for def in self.definitions.iter() {
+            if let Some(path) = def.haystack_path() {
+                hays.insert(path);
+            }
+            if let Some(path) = def.regex_path() {
+                res.insert(path);
+            }
+        }
+        let mut issues = vec![];
+        for (kind, refs) in [("haystacks", &hays), ("regexes", &res)] {
+            let root = dir.join(kind);
+            let walker = walkdir::WalkDir::new(&root)
+                .sort_by_file_name()
+                .into_iter()
+                .filter_entry(|dent| {
+                    dent.depth() == 0
+                        || !dent.file_name().to_string_lossy().starts_with('.')
+                });
+            for result in walker {
+                let dent = result?;
+                if !dent.file_type().is_file()
+                    || dent.file_name() == "README.md"
+                {
+                    continue;
+                }
+                // OK because every path yielded is beneath the root.
+                let suffix = dent.path().strip_prefix(&root).unwrap();
+                let Some(suffix) = suffix.to_str() else { continue };
+                // Paths in definitions always use / as a separator.
+                if refs.contains(&suffix.replace("\\", "/")) {
+                    continue;
+                }
+                issues.push(LintIssue {
+                    severity: LintSeverity::Warning,
+                    path: dent.path().to_path_buf(),
+                    benchmark: None,
+                    message: "not referenced by any benchmark definition"
+                        .to_string(),
+                });
+            }
+        }
+        Ok(issues)
+    }
+}
+
+/// If any benchmark definition in the given TOML data has a 'matrix' key,
+/// then this returns the data with each such definition replaced by its
+/// expansions. (See `expand_matrix`.) Otherwise, this returns `None` so that
+/// the data can be deserialized directly, which gives better error messages.
+fn expand_matrices(data: &str) -> anyhow::Result<Option<toml::Value>> {
+    let mut top: toml::Value = toml::from_str(data)?;
+    let Some(benches) = top.get_mut("bench").and_then(|v| v.as_array_mut())
+    else {
+        return Ok(None);
+    };
+    if !benches.iter().any(|b| b.get("matrix").is_some()) {
+        return Ok(None);
+    }
+    let mut expanded = vec![];
+    for bench in benches.drain(..) {
+        let toml::Value::Table(table) = bench else {
+            expanded.push(bench);
+            continue;
+        };
+        if !table.contains_key("matrix") {
+            expanded.push(toml::Value::Table(table));
+            continue;
+        }
+        let name = table
+            .get("name")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string();
+        let defs = expand_matrix(table).with_context(|| {
+            format!("failed to expand matrix for benchmark '{}'", name)
+        })?;
+        expanded.extend(defs);
+    }
+    *benches = expanded;
+    Ok(Some(top))
+}
+
+/// Expands a single benchmark definition with a 'matrix' key into one
+/// definition for every combination of the values in the matrix.
+///
+/// Every occurrence of '{key}' in a string, where 'key' is a key in the
+/// matrix, is replaced with the value for that combination. A string that
+/// consists of only a placeholder is replaced by the value itself, which
+/// permits placeholders for integer options like 'repeat'.
+///
+/// When 'count' is a table of the form '{ matrix = { ... } }', the count for
+/// each combination is looked up in that table by its combination key, e.g.,
+/// 'repeat=4' or 'repeat=4,width=8'. Keys are always given in sorted order.
+fn expand_matrix(
+    mut bench: toml::value::Table,
+) -> anyhow::Result<Vec<toml::Value>> {
+    let re_key = regex!(r"^[A-Za-z][-A-Za-z0-9_]*$");
+
+    let Some(toml::Value::Table(matrix)) = bench.remove("matrix") else {
+        anyhow::bail!("'matrix' must be a table")
+    };
+    anyhow::ensure!(!matrix.is_empty(), "'matrix' must not be empty");
+    let mut axes = vec![];
+    for (key, values) in matrix {
+        anyhow::ensure!(
+            re_key.is_match(&key),
+            "matrix key '{}' does not match format '{}'",
+            key,
+            re_key.as_str(),
+        );
+        let values = match values {
+            toml::Value::Array(values) if !values.is_empty() => values,
+            _ => anyhow::bail!(
+                "matrix key '{}' must have a non-empty array of values",
+                key,
+            ),
+        };
+        for v in values.iter() {
+            anyhow::ensure!(
+                v.is_str() || v.is_integer(),
+                "matrix key '{}' has value {}, but values must be strings \
+                 or integers",
+                key,
+                v,
+            );
+        }
+        axes.push((key, values));
+    }
+    let mut counts = match bench.remove("count") {
+        Some(toml::Value::Table(mut count))
+            if count.contains_key("matrix") =>
+        {
+            anyhow::ensure!(
+                count.len() == 1,
+                "a count with a 'matrix' key must not have any other keys",
+            );
+            let Some(toml::Value::Table(counts)) = count.remove("matrix")
+            else {
+                anyhow::bail!("'count.matrix' must be a table")
+            };
+            Some(counts)
+        }
+        Some(count) => {
+            bench.insert("count".to_string(), count);
+            None
+        }
+        None => None,
+    };
+
+    // Build the cartesian product of all values, where the first key varies
+    // the slowest.
+    let mut combos: Vec<Vec<(&str, &toml::Value)>> = vec![vec![]];
+    for (key, values) in axes.iter() {
+        combos = combos
+            .into_iter()
+            .flat_map(|combo| {
+                values.iter().map(move |v| {
+                    let mut combo = combo.clone();
+                    combo.push((key.as_str(), v));
+                    combo
+                })
+            })
+            .collect();
+    }
+    let mut expanded = vec![];
+    for combo in combos {
+        let mut def = toml::Value::Table(bench.clone());
+        matrix_substitute(&mut def, &combo);
+        if let Some(ref mut counts) = counts {
+            let key = combo
+                .iter()
+                .map(|&(k, v)| format!("{}={}", k, matrix_value(v)))
+                .collect::<Vec<String>>()
+                .join(",");
+            let Some(count) = counts.remove(&key) else {
+                anyhow::bail!("'count.matrix' has no entry for '{}'", key)
+            };
+            // OK because we created 'def' as a table above.
+            def.as_table_mut().unwrap().insert("count".to_string(), count);
+        }
+        expanded.push(def);
+    }
+    if let Some(key) = counts.as_ref().and_then(|c| c.keys().next()) {
+        anyhow::bail!(
+            "'count.matrix' has an entry for '{}', which is not a \
+             combination of the values in the matrix",
+            key,
+        );
+    }
+    Ok(expanded)
+}
+
+/// Replaces the placeholders in every string in the given TOML value with
+/// the corresponding value in the given matrix combination. See
+/// `expand_matrix` for details.
+fn matrix_substitute(value: &mut toml::Value, combo: &[(&str, &toml::Value)]) {
+    match *value {
+        toml::Value::String(ref s) => {
+            let exact = combo.iter().find(|&&(k, _)| *s == format!("{{{k}}}"));
+            if let Some(&(_, v)) = exact {
+                *value = v.clone();
+                return;
+            }
+            let mut s = s.clone();
+            for &(k, v) in combo.iter() {
+                s = s.replace(&format!("{{{k}}}"), &matrix_value(v));
+            }
+            *value = toml::Value::String(s);
+        }
+        toml::Value::Array(ref mut values) => {
+            for v in values.iter_mut() {
+                matrix_substitute(v, combo);
+            }
+        }
+        toml::Value::Table(ref mut table) => {
+            for (_, v) in table.iter_mut() {
+                matrix_substitute(v, combo);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns the given matrix value as it's written in a placeholder
+/// substitution or a combination key.
+fn matrix_value(v: &toml::Value) -> String {
+    match *v {
+        toml::Value::String(ref s) => s.clone(),
+        ref v => v.to_string(),
+    }
 }
 
 #[derive(Clone, Debug, serde::Deserialize)]
@@ -666,12 +2444,59 @@ struct WireDefinition {
     #[serde(flatten)]
     options: DefinitionOptions,
     haystack: WireHaystack,
-    count: WireCount,
-    engines: Vec<String>,
+    // When this is missing, the count is "unresolved." We permit this so that
+    // counts can be computed by an oracle regex engine, but such definitions
+    // are rejected when measuring.
+    #[serde(default)]
+    count: Option<WireCount>,
+    // Only used for the 'count-per-pattern' model, in place of 'count'.
+    #[serde(default)]
+    counts_per_pattern: Option<WireCountsPerPattern>,
+    // When false, counts reported by regex engines aren't checked, and thus
+    // neither 'count' nor 'counts-per-pattern' may be given. This is meant
+    // for models like 'size', where the count isn't stable across engines or
+    // even versions of the same engine.
+    #[serde(default)]
+    verify_count: Option<bool>,
+    engines: Vec<WireEngineRef>,
     analysis: Option<String>,
+    #[serde(default)]
+    requires: Vec<String>,
+    // Overrides of the time and iteration budgets used when measuring this
+    // benchmark. See 'DefinitionBudget'.
+    max_iters: Option<u64>,
+    max_warmup_iters: Option<u64>,
+    max_time: Option<ShortHumanDuration>,
+    max_warmup_time: Option<ShortHumanDuration>,
+    #[serde(default)]
+    params: BTreeMap<String, WireParam>,
 }
 
 impl WireDefinition {
+    /// Normalize every path in this definition that is relative to the
+    /// benchmark directory so that it uses '/' as its separator, regardless
+    /// of how it was written. This means paths can be compared and shown in
+    /// reports without caring about the platform. Paths that could escape
+    /// the benchmark directory are rejected.
+    fn normalize_paths(&mut self) -> anyhow::Result<()> {
+        let mut paths = vec![];
+        if let WireRegex::Full(ref mut full) = self.regex {
+            paths.push(&mut full.path);
+        }
+        if let WireHaystack::Full(ref mut full) = self.haystack {
+            let full = &mut **full;
+            paths.push(&mut full.path);
+            paths.push(&mut full.path_cache);
+            if let Some(ref mut generate) = full.generate {
+                paths.push(&mut generate.unit_file);
+            }
+        }
+        for path in paths.into_iter().flatten() {
+            *path = normalize_relative_path(path)?;
+        }
+        Ok(())
+    }
+
     fn to_definition(
         &self,
         filters: &Filters,
@@ -679,26 +2504,104 @@ impl WireDefinition {
         res: &Regexes,
         hays: &Haystacks,
     ) -> anyhow::Result<Definition> {
+        self.check_engine_labels()?;
+        let (regexes, binary_regexes) = self.regexes(res)?;
+        let count = self.count(regexes.len())?;
+        let count_derive_from = self.count_derive_from();
         let def = Definition {
             model: self.model.clone(),
             name: self.name()?,
-            regexes: self.regexes(res)?,
+            regexes,
+            binary_regexes,
             regex_path: self.regex_path(),
             options: self.options.clone(),
             haystack: self.haystack(hays)?,
             haystack_path: self.haystack_path(),
-            count: self.count()?,
+            haystack_encoding: self.haystack_encoding(),
+            count,
+            count_derive_from,
+            verify_count: self.verify_count(),
             engines: self.engines(filters, engines)?,
             analysis: self.analysis.clone(),
+            requires: self.requires(),
+            budget: self.budget(),
+            params: self.params()?,
         };
+        let engines: Vec<&str> =
+            self.engines.iter().map(|e| e.label()).collect();
+        def.check_shadowed_counts(&engines)?;
+        def.check_search_window()?;
+        def.check_max_count()?;
         Ok(def)
     }
 
-    fn name(&self) -> anyhow::Result<DefinitionName> {
-        let re_group = regex!(r"^[-A-Za-z0-9]+$");
-        let re_name = regex!(r"^[-A-Za-z0-9]+$");
+    /// Returns the capabilities a regex engine must declare in order to run
+    /// this benchmark. Besides the ones given explicitly, a definition with
+    /// a search window requires the 'search-window' capability and a
+    /// definition with binary patterns requires the 'binary-pattern'
+    /// capability.
+    fn requires(&self) -> Vec<String> {
+        let mut requires = self.requires.clone();
+        let implicit = [
+            (
+                DefinitionOptions::SEARCH_WINDOW_CAPABILITY,
+                self.options.has_search_window(),
+            ),
+            (Definition::BINARY_PATTERN_CAPABILITY, self.is_binary_regex()),
+        ];
+        for (cap, required) in implicit {
+            if required && !requires.iter().any(|c| c == cap) {
+                requires.push(cap.to_string());
+            }
+        }
+        requires
+    }
 
-        for piece in self.group.split("/") {
+    /// Returns true when this definition's patterns are read from a file
+    /// with 'binary = true'.
+    fn is_binary_regex(&self) -> bool {
+        match self.regex {
+            WireRegex::Inline(_) => false,
+            WireRegex::Full(ref full) => full.options.binary,
+        }
+    }
+
+    fn budget(&self) -> DefinitionBudget {
+        DefinitionBudget {
+            max_iters: self.max_iters,
+            max_warmup_iters: self.max_warmup_iters,
+            max_time: self.max_time.map(Duration::from),
+            max_warmup_time: self.max_warmup_time.map(Duration::from),
+        }
+    }
+
+    /// Returns the parameters of this definition, with every value converted
+    /// to a string. This returns an error if any parameter name isn't valid.
+    fn params(&self) -> anyhow::Result<BTreeMap<String, String>> {
+        let re_name = regex!(r"^[A-Za-z][-A-Za-z0-9_]*$");
+
+        let mut params = BTreeMap::new();
+        for (name, value) in self.params.iter() {
+            anyhow::ensure!(
+                re_name.is_match(name),
+                "parameter name '{}' does not match format '{}'",
+                name,
+                re_name.as_str(),
+            );
+            let value = match *value {
+                WireParam::String(ref s) => s.clone(),
+                WireParam::Integer(n) => n.to_string(),
+            };
+            params.insert(name.clone(), value);
+        }
+        Ok(params)
+    }
+
+    fn name(&self) -> anyhow::Result<DefinitionName> {
+        let re_group = regex!(r"^[-A-Za-z0-9]+$");
+        let re_name = regex!(r"^[-A-Za-z0-9]+$");
+
+        for piece in self.group.split("/") {
             anyhow::ensure!(
                 re_group.is_match(piece),
                 "part '{}' from group name '{}' does not match format '{}' \
@@ -721,33 +2624,86 @@ impl WireDefinition {
         })
     }
 
+    /// Returns the path to the TOML file this definition was loaded from,
+    /// given the benchmark directory it was loaded from.
+    fn path(&self, dir: &Path) -> PathBuf {
+        dir.join("definitions").join(format!("{}.toml", self.group))
+    }
+
+    /// Checks that the label of every engine reference is a valid engine
+    /// name, and that no two references in this definition have the same
+    /// label. (References without a label use the engine's name.)
+    fn check_engine_labels(&self) -> anyhow::Result<()> {
+        let re_engine = regex!(r"^[-A-Za-z0-9]+(/[-A-Za-z0-9]+)*$");
+
+        let mut seen = BTreeSet::new();
+        for engine in self.engines.iter() {
+            let label = engine.label();
+            anyhow::ensure!(
+                re_engine.is_match(label),
+                "engine label '{}' does not match format '{}'",
+                label,
+                re_engine.as_str(),
+            );
+            anyhow::ensure!(
+                seen.insert(label),
+                "regex engine '{}' is referenced more than once in benchmark \
+                 '{}' (use a distinct 'label' for each reference)",
+                label,
+                self.name,
+            );
+        }
+        Ok(())
+    }
+
     fn engines(
         &self,
         filters: &Filters,
         engines: &Engines,
     ) -> anyhow::Result<Vec<Engine>> {
         let mut resolved = vec![];
-        for name in self.engines.iter() {
-            if !filters.engine.include(name) {
+        for engine in self.engines.iter() {
+            if !filters.engine.include(engine.label()) {
                 continue;
             }
-            let e = match engines.by_name.get(name) {
+            let mut e = match engines.by_name.get(engine.name()) {
                 Some(e) => e.clone(),
                 None => anyhow::bail!(
                     "could not find regex engine '{}' for benchmark '{}'",
-                    name,
+                    engine.name(),
                     self.name,
                 ),
             };
+            e.name = engine.label().to_string();
+            e.extra_args = engine.args().to_vec();
             if filters.ignore_missing_engines && e.is_missing_version() {
                 continue;
             }
+            if !filters.strict_capabilities {
+                if let Some(cap) = e.missing_capability(&self.requires()) {
+                    log::debug!(
+                        "skipping regex engine '{}' for benchmark '{}' \
+                         because it lacks required capability '{}'",
+                        e.name,
+                        self.name,
+                        cap,
+                    );
+                    continue;
+                }
+            }
             resolved.push(e);
         }
         Ok(resolved)
     }
 
-    fn regexes(&self, res: &Regexes) -> anyhow::Result<Arc<[String]>> {
+    /// Returns the patterns for this definition. When the patterns were read
+    /// from a file with 'binary = true', then the raw patterns are returned
+    /// too. In that case, the first element of the tuple has any bytes that
+    /// aren't valid UTF-8 escaped.
+    fn regexes(
+        &self,
+        res: &Regexes,
+    ) -> anyhow::Result<(Arc<[String]>, Option<RawPatterns>)> {
         let patterns: Arc<[String]> = match self.regex {
             WireRegex::Inline(ref inline) => Arc::from(inline.patterns()),
             WireRegex::Full(ref full) => {
@@ -760,10 +2716,30 @@ impl WireDefinition {
                     // Every "full" definition that can have a key constructed
                     // is guaranteed to be in our 'res' map, and if it isn't,
                     // there's a bug somewhere in this module.
-                    return Ok(res.map.get(&key).unwrap().clone());
+                    let raw = res.map.get(&key).unwrap();
+                    if full.options.binary {
+                        let escaped: Vec<String> = raw
+                            .iter()
+                            .map(|p| escape_invalid_utf8(p))
+                            .collect();
+                        return Ok((Arc::from(escaped), Some(raw.clone())));
+                    }
+                    // OK because patterns from files without 'binary = true'
+                    // are checked to be valid UTF-8 when they're read.
+                    let patterns: Vec<String> = raw
+                        .iter()
+                        .map(|p| p.to_str().unwrap().to_string())
+                        .collect();
+                    return Ok((Arc::from(patterns), None));
                 }
                 // There's a key if and only if the actual regex is in a file.
                 assert!(full.path.is_none());
+                anyhow::ensure!(
+                    !full.options.binary,
+                    "benchmark '{}' sets 'binary = true', but that is only \
+                     supported for patterns read from a file via 'path'",
+                    self.name,
+                );
                 let patterns = match full.patterns {
                     None => anyhow::bail!(
                         "missing regex patterns for benchmark '{}'",
@@ -774,7 +2750,7 @@ impl WireDefinition {
                 Arc::from(full.options.transform_from_inline(patterns))
             }
         };
-        Ok(patterns)
+        Ok((patterns, None))
     }
 
     fn regex_path(&self) -> Option<String> {
@@ -791,10 +2767,18 @@ impl WireDefinition {
             }
             WireHaystack::Full(ref full) => {
                 if let Some(key) = HaystackKey::from_wire(full) {
+                    let source = if full.generate.is_some() {
+                        "generate"
+                    } else if full.url.is_some() {
+                        "url"
+                    } else {
+                        "path"
+                    };
                     anyhow::ensure!(
                         full.contents.is_none(),
-                        "benchmark '{}' defines both 'contents' and 'path'",
+                        "benchmark '{}' defines both 'contents' and '{}'",
                         self.name,
+                        source,
                     );
                     // Every "full" definition that can have a key constructed
                     // is guaranteed to be in our 'hays' map, and if it isn't,
@@ -802,8 +2786,8 @@ impl WireDefinition {
                     return Ok(hays.map.get(&key).unwrap().clone());
                 }
                 // There's a key if and only if the actual haystack is in a
-                // file.
-                assert!(full.path.is_none());
+                // file or is generated.
+                assert!(full.path.is_none() && full.url.is_none());
                 let haystack = match full.contents {
                     None => anyhow::bail!(
                         "missing haystack for benchmark '{}'",
@@ -811,55 +2795,298 @@ impl WireDefinition {
                     ),
                     Some(ref haystack) => haystack,
                 };
-                Ok(Arc::from(full.options.transform(haystack.as_bytes())))
+                let raw = full.options.encoding.encode(haystack)?;
+                Ok(Arc::from(full.options.transform(&raw)?))
             }
         }
     }
 
+    fn haystack_encoding(&self) -> klv::HaystackEncoding {
+        match self.haystack {
+            WireHaystack::Inline(_) => klv::HaystackEncoding::Utf8,
+            WireHaystack::Full(ref full) => full.options.encoding.to_klv(),
+        }
+    }
+
+    /// Returns the path of the file this definition's haystack is read
+    /// from. For a generated haystack, this is the path of its unit file, if
+    /// it has one.
     fn haystack_path(&self) -> Option<String> {
         match self.haystack {
             WireHaystack::Inline(_) => None,
-            WireHaystack::Full(ref full) => full.path.clone(),
+            WireHaystack::Full(ref full) => HaystackKey::from_wire(full)?.path,
         }
     }
 
-    fn count(&self) -> anyhow::Result<Vec<CountEngine>> {
-        match self.count {
+    /// Returns the count entries for this definition. The number of patterns
+    /// is used to check that per-pattern counts have one count per pattern.
+    fn count(&self, patterns: usize) -> anyhow::Result<Vec<CountEngine>> {
+        if !self.verify_count() {
+            anyhow::ensure!(
+                self.count.is_none() && self.counts_per_pattern.is_none(),
+                "'count' and 'counts-per-pattern' cannot be used when \
+                 'verify-count = false' is set",
+            );
+            return Ok(vec![]);
+        }
+        if let Some(WireCount::Derived(ref derived)) = self.count {
+            anyhow::ensure!(
+                self.counts_per_pattern.is_none(),
+                "'count = {{ derive-from = ... }}' cannot be used with \
+                 'counts-per-pattern'",
+            );
+            anyhow::ensure!(
+                !derived.derive_from.is_empty(),
+                "'derive-from' must name a regex engine",
+            );
+            // The count is resolved later by running the reference engine.
+            return Ok(vec![]);
+        }
+        if self.model == "count-per-pattern" {
+            anyhow::ensure!(
+                self.count.is_none(),
+                "'count' cannot be used with the 'count-per-pattern' model, \
+                 use 'counts-per-pattern' instead",
+            );
+            return self.counts_per_pattern(patterns);
+        }
+        anyhow::ensure!(
+            self.counts_per_pattern.is_none(),
+            "'counts-per-pattern' can only be used with the \
+             'count-per-pattern' model",
+        );
+        let count = match self.count {
+            None => return Ok(vec![]),
+            Some(ref count) => count,
+        };
+        match *count {
             WireCount::Engines(ref engine_counts) => {
+                anyhow::ensure!(
+                    !engine_counts.is_empty(),
+                    "list of engine counts must be non-empty",
+                );
                 let mut counts = vec![];
                 for wire in engine_counts.iter() {
                     let pat = format!("^(?:{})$", wire.engine);
                     let re = RRegex::new(&pat).context(
                         "failed to parse engine count name as regex",
                     )?;
+                    let version = match wire.version {
+                        None => None,
+                        Some(ref raw) => Some(VersionReq::parse(raw)?),
+                    };
                     counts.push(CountEngine {
                         re: Regex(re),
                         engine: wire.engine.clone(),
+                        version,
                         count: wire.count,
+                        semantics: wire.semantics.clone(),
+                        per_pattern: None,
+                        derived_from: None,
+                    });
+                }
+                Ok(counts)
+            }
+            WireCount::All(count) => Ok(vec![CountEngine::all(count)]),
+            WireCount::Derived(_) => unreachable!(),
+        }
+    }
+
+    /// Returns the name of the reference regex engine that the count for
+    /// this definition should be derived from, if one was given.
+    fn count_derive_from(&self) -> Option<String> {
+        match self.count {
+            Some(WireCount::Derived(ref derived)) => {
+                Some(derived.derive_from.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns whether counts reported by regex engines should be verified.
+    /// This is true unless 'verify-count = false' is set.
+    fn verify_count(&self) -> bool {
+        self.verify_count.unwrap_or(true)
+    }
+
+    fn counts_per_pattern(
+        &self,
+        patterns: usize,
+    ) -> anyhow::Result<Vec<CountEngine>> {
+        let check_len = |counts: &[u64]| {
+            anyhow::ensure!(
+                counts.len() == patterns,
+                "got {} counts in 'counts-per-pattern', but the benchmark \
+                 has {} patterns (there must be exactly one count for each \
+                 pattern)",
+                counts.len(),
+                patterns,
+            );
+            Ok(())
+        };
+        let wire = match self.counts_per_pattern {
+            None => return Ok(vec![]),
+            Some(ref wire) => wire,
+        };
+        match *wire {
+            WireCountsPerPattern::Engines(ref engine_counts) => {
+                anyhow::ensure!(
+                    !engine_counts.is_empty(),
+                    "list of engine counts must be non-empty",
+                );
+                let mut counts = vec![];
+                for wire in engine_counts.iter() {
+                    check_len(&wire.counts)?;
+                    let pat = format!("^(?:{})$", wire.engine);
+                    let re = RRegex::new(&pat).context(
+                        "failed to parse engine count name as regex",
+                    )?;
+                    let version = match wire.version {
+                        None => None,
+                        Some(ref raw) => Some(VersionReq::parse(raw)?),
+                    };
+                    counts.push(CountEngine {
+                        re: Regex(re),
+                        engine: wire.engine.clone(),
+                        version,
+                        count: per_pattern_checksum(&wire.counts),
+                        semantics: wire.semantics.clone(),
+                        per_pattern: Some(wire.counts.clone()),
+                        derived_from: None,
                     });
                 }
                 Ok(counts)
             }
-            WireCount::All(count) => Ok(vec![CountEngine {
-                re: Regex(RRegex::new(r"^.*$").unwrap()),
-                engine: r".*".to_string(),
-                count,
-            }]),
+            WireCountsPerPattern::All(ref counts) => {
+                check_len(counts)?;
+                let mut ce = CountEngine::all(per_pattern_checksum(counts));
+                ce.per_pattern = Some(counts.clone());
+                Ok(vec![ce])
+            }
+        }
+    }
+
+    /// Returns the engine name pattern and version requirement of every
+    /// engine specific count entry in this definition, without validating
+    /// any of them.
+    fn count_engines(&self) -> Vec<(&str, Option<&str>)> {
+        let mut entries = vec![];
+        if let Some(WireCount::Engines(ref counts)) = self.count {
+            for c in counts.iter() {
+                entries.push((&*c.engine, c.version.as_deref()));
+            }
+        }
+        if let Some(WireCountsPerPattern::Engines(ref counts)) =
+            self.counts_per_pattern
+        {
+            for c in counts.iter() {
+                entries.push((&*c.engine, c.version.as_deref()));
+            }
+        }
+        entries
+    }
+}
+
+/// A reference to a regex engine in the 'engines' array of a benchmark
+/// definition. This is either just the name of the engine, or a table that
+/// also gives extra arguments for its runner program and a label to use in
+/// place of its name.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(untagged)]
+enum WireEngineRef {
+    Name(String),
+    Full(WireEngineRefFull),
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WireEngineRefFull {
+    name: String,
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+impl WireEngineRef {
+    /// The name of the regex engine in 'engines.toml'.
+    fn name(&self) -> &str {
+        match *self {
+            WireEngineRef::Name(ref name) => name,
+            WireEngineRef::Full(ref full) => &full.name,
+        }
+    }
+
+    /// The name used for this engine in counts and measurements. This is
+    /// the label when one is given, and the engine's name otherwise.
+    fn label(&self) -> &str {
+        match *self {
+            WireEngineRef::Name(ref name) => name,
+            WireEngineRef::Full(ref full) => {
+                full.label.as_deref().unwrap_or(&full.name)
+            }
+        }
+    }
+
+    /// The extra arguments to append to the engine's 'run' command.
+    fn args(&self) -> &[String] {
+        match *self {
+            WireEngineRef::Name(_) => &[],
+            WireEngineRef::Full(ref full) => &full.args,
         }
     }
 }
 
+/// The value of a parameter in the 'params' table of a benchmark definition.
+/// Integers are permitted so that a matrix placeholder that expands to an
+/// integer can be used as a value. Either way, runner programs are given a
+/// string.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(untagged)]
+enum WireParam {
+    String(String),
+    Integer(u64),
+}
+
 #[derive(Clone, Debug, serde::Deserialize)]
 #[serde(untagged)]
 enum WireCount {
     Engines(Vec<WireCountEngine>),
     All(u64),
+    Derived(WireCountDerived),
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct WireCountDerived {
+    derive_from: String,
 }
 
 #[derive(Clone, Debug, serde::Deserialize)]
 struct WireCountEngine {
     engine: String,
     count: u64,
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    semantics: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(untagged)]
+enum WireCountsPerPattern {
+    Engines(Vec<WireCountsPerPatternEngine>),
+    All(Vec<u64>),
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+struct WireCountsPerPatternEngine {
+    engine: String,
+    counts: Vec<u64>,
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    semantics: Option<String>,
 }
 
 #[derive(Clone, Debug, serde::Deserialize)]
@@ -898,6 +3125,9 @@ struct WireRegexFull {
 )]
 #[serde(rename_all = "kebab-case")]
 struct WireRegexOptions {
+    /// When set, the patterns read from 'path' don't need to be valid UTF-8.
+    #[serde(default)]
+    binary: bool,
     #[serde(default)]
     literal: bool,
     #[serde(default)]
@@ -907,77 +3137,180 @@ struct WireRegexOptions {
 }
 
 impl WireRegexOptions {
-    fn transform_from_file(&self, raw: &str) -> Vec<String> {
+    /// Transform the raw contents of a regex file into patterns. This works
+    /// on bytes since a file with 'binary = true' may contain patterns that
+    /// aren't valid UTF-8. When `raw` is valid UTF-8, then so is every
+    /// pattern returned.
+    fn transform_from_file(&self, raw: &[u8]) -> Vec<Vec<u8>> {
         match self.per_line {
-            WireRegexOptionPerLine::None => {
-                self.transform(vec![raw.trim().to_string()])
-            }
+            WireRegexOptionPerLine::None => self
+                .transform(vec![raw.trim_with(char::is_whitespace).to_vec()]),
             WireRegexOptionPerLine::Alternate => {
-                let mut pats = raw.lines().map(|p| p.to_string()).collect();
+                let mut pats = raw.lines().map(|p| p.to_vec()).collect();
                 pats = self.transform(pats);
-                pats =
-                    pats.into_iter().map(|p| format!("(?:{})", p)).collect();
-                vec![pats.join("|")]
+                let pats: Vec<Vec<u8>> = pats
+                    .into_iter()
+                    .map(|p| [&b"(?:"[..], &p, b")"].concat())
+                    .collect();
+                vec![pats.join(&b'|')]
             }
             WireRegexOptionPerLine::Pattern => {
-                self.transform(raw.lines().map(|x| x.to_string()).collect())
+                self.transform(raw.lines().map(|x| x.to_vec()).collect())
             }
         }
     }
 
     fn transform_from_inline(&self, patterns: &[String]) -> Vec<String> {
-        self.transform(patterns.to_vec())
+        let pats = patterns.iter().map(|p| p.as_bytes().to_vec()).collect();
+        // OK because the patterns given are valid UTF-8, and transforming
+        // them preserves that.
+        self.transform(pats)
+            .into_iter()
+            .map(|p| String::from_utf8(p).unwrap())
+            .collect()
     }
 
-    fn transform(&self, mut pats: Vec<String>) -> Vec<String> {
+    fn transform(&self, mut pats: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
         if self.literal {
             for p in pats.iter_mut() {
-                *p = regex_lite::escape(p);
+                // Only the valid UTF-8 parts of a pattern can contain meta
+                // characters, so bytes that aren't valid UTF-8 are kept as-is.
+                let mut escaped = vec![];
+                for chunk in p.utf8_chunks() {
+                    let valid = regex_lite::escape(chunk.valid());
+                    escaped.extend_from_slice(valid.as_bytes());
+                    escaped.extend_from_slice(chunk.invalid());
+                }
+                *p = escaped;
             }
         }
         if let Some(ref prepend) = self.prepend {
             for p in pats.iter_mut() {
-                *p = format!("{}{}", prepend, p);
+                p.splice(0..0, prepend.bytes());
             }
         }
         if let Some(ref append) = self.append {
             for p in pats.iter_mut() {
-                p.push_str(append);
+                p.extend_from_slice(append.as_bytes());
             }
         }
         pats
     }
 }
 
+/// Returns the given pattern as a string, where every byte that isn't part of
+/// valid UTF-8 is replaced by a '\xNN' escape.
+fn escape_invalid_utf8(pattern: &[u8]) -> String {
+    let mut escaped = String::new();
+    for chunk in pattern.utf8_chunks() {
+        escaped.push_str(chunk.valid());
+        for &b in chunk.invalid() {
+            escaped.push_str(&format!("\\x{:02X}", b));
+        }
+    }
+    escaped
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord, serde::Deserialize)]
 #[serde(rename_all = "kebab-case")]
+#[derive(Default)]
 enum WireRegexOptionPerLine {
+    #[default]
     None,
     Alternate,
     Pattern,
 }
 
-impl Default for WireRegexOptionPerLine {
-    fn default() -> WireRegexOptionPerLine {
-        WireRegexOptionPerLine::None
-    }
-}
-
 #[derive(Clone, Debug, serde::Deserialize)]
 #[serde(untagged)]
 enum WireHaystack {
     Inline(String),
-    Full(WireHaystackFull),
+    Full(Box<WireHaystackFull>),
 }
 
 #[derive(Clone, Debug, serde::Deserialize)]
 struct WireHaystackFull {
     contents: Option<String>,
     path: Option<String>,
+    /// A URL to download the haystack from. When this is set, 'sha256' and
+    /// 'path-cache' must be set too.
+    url: Option<String>,
+    /// The expected SHA-256 checksum, in hex, of the data at 'url'.
+    sha256: Option<String>,
+    /// Where to store the haystack downloaded from 'url', relative to the
+    /// haystack directory. This must be inside the 'downloads' directory.
+    #[serde(rename = "path-cache")]
+    path_cache: Option<String>,
+    /// When set, the haystack is generated according to these instructions
+    /// instead of being given by 'contents', 'path' or 'url'.
+    generate: Option<WireHaystackGenerate>,
     #[serde(flatten)]
     options: WireHaystackOptions,
 }
 
+/// Instructions for generating a haystack, usually to provoke pathological
+/// behavior in some regex engines without committing huge and nearly
+/// identical haystack files.
+///
+/// The haystack generated is 'unit' (or the contents of 'unit-file')
+/// repeated 'count' times followed by 'suffix'. All of the usual haystack
+/// options, like 'repeat' and 'prepend', are applied to it afterwards.
+#[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord, serde::Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct WireHaystackGenerate {
+    kind: WireHaystackGenerateKind,
+    unit: Option<String>,
+    /// A path to a file containing the unit, relative to the haystack
+    /// directory. Like haystacks given by 'path', it may be compressed.
+    unit_file: Option<String>,
+    count: usize,
+    #[serde(default)]
+    suffix: String,
+}
+
+/// The kind of haystack to generate.
+///
+/// There's only one kind right now. It's required anyway so that other kinds
+/// can be added without making existing definitions ambiguous.
+#[derive(
+    Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+enum WireHaystackGenerateKind {
+    /// A unit repeated many times, followed by a suffix that usually causes
+    /// the regex to fail to match, e.g., 'xxxxxxxxy'.
+    RepeatThenFail,
+}
+
+impl WireHaystackGenerate {
+    /// Generate the haystack from the given unit, which must already be in
+    /// the given encoding. The suffix is encoded in that encoding too.
+    fn generate(
+        &self,
+        unit: &[u8],
+        encoding: WireHaystackEncoding,
+    ) -> anyhow::Result<Vec<u8>> {
+        match self.kind {
+            WireHaystackGenerateKind::RepeatThenFail => {}
+        }
+        let suffix = encoding.encode(&self.suffix)?;
+        let len = unit
+            .len()
+            .checked_mul(self.count)
+            .and_then(|len| len.checked_add(suffix.len()))
+            .context("generated haystack is too big")?;
+        let mut raw = vec![];
+        raw.try_reserve_exact(len).with_context(|| {
+            format!("generated haystack is too big ({} bytes)", len)
+        })?;
+        for _ in 0..self.count {
+            raw.extend_from_slice(unit);
+        }
+        raw.extend_from_slice(&suffix);
+        Ok(raw)
+    }
+}
+
 #[derive(
     Clone, Debug, Default, Eq, PartialEq, PartialOrd, Ord, serde::Deserialize,
 )]
@@ -992,10 +3325,190 @@ struct WireHaystackOptions {
     repeat: Option<usize>,
     prepend: Option<String>,
     append: Option<String>,
+    #[serde(default)]
+    encoding: WireHaystackEncoding,
+}
+
+/// The encoding of a haystack, as written in a benchmark definition.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    PartialOrd,
+    Ord,
+    serde::Deserialize,
+)]
+enum WireHaystackEncoding {
+    #[default]
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[serde(rename = "utf-16le")]
+    Utf16le,
+    #[serde(rename = "latin-1")]
+    Latin1,
+}
+
+impl WireHaystackEncoding {
+    fn to_klv(self) -> klv::HaystackEncoding {
+        match self {
+            WireHaystackEncoding::Utf8 => klv::HaystackEncoding::Utf8,
+            WireHaystackEncoding::Utf16le => klv::HaystackEncoding::Utf16le,
+            WireHaystackEncoding::Latin1 => klv::HaystackEncoding::Latin1,
+        }
+    }
+
+    /// Encode the given string in this encoding. This fails when the string
+    /// contains a codepoint that can't be represented in this encoding.
+    fn encode(self, s: &str) -> anyhow::Result<Vec<u8>> {
+        let bytes = match self {
+            WireHaystackEncoding::Utf8 => s.as_bytes().to_vec(),
+            WireHaystackEncoding::Utf16le => {
+                s.encode_utf16().flat_map(|u| u.to_le_bytes()).collect()
+            }
+            WireHaystackEncoding::Latin1 => {
+                let mut bytes = Vec::with_capacity(s.len());
+                for ch in s.chars() {
+                    let byte = u8::try_from(u32::from(ch)).ok();
+                    bytes.push(byte.with_context(|| {
+                        format!("{:?} cannot be encoded as latin-1", ch)
+                    })?);
+                }
+                bytes
+            }
+        };
+        Ok(bytes)
+    }
+
+    /// Check that the given haystack is valid for this encoding.
+    ///
+    /// UTF-8 haystacks aren't validated, since some benchmarks deliberately
+    /// use haystacks containing invalid UTF-8. Every sequence of bytes is
+    /// valid latin-1.
+    fn validate(self, raw: &[u8]) -> anyhow::Result<()> {
+        if self != WireHaystackEncoding::Utf16le {
+            return Ok(());
+        }
+        anyhow::ensure!(
+            raw.len().is_multiple_of(2),
+            "haystack encoded as utf-16le must have an even number of \
+             bytes, but it has {} bytes",
+            raw.len(),
+        );
+        let units =
+            raw.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]]));
+        for (i, result) in char::decode_utf16(units).enumerate() {
+            if let Err(err) = result {
+                anyhow::bail!(
+                    "haystack is not valid utf-16le: unpaired surrogate \
+                     0x{:04X} near byte offset {}",
+                    err.unpaired_surrogate(),
+                    i * 2,
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl WireHaystackFull {
+    /// Check that 'generate' isn't combined with any other source of the
+    /// haystack, and that exactly one of its 'unit' and 'unit-file' fields
+    /// is set.
+    fn check_generate(&self) -> anyhow::Result<()> {
+        let Some(ref generate) = self.generate else { return Ok(()) };
+        let sources = [
+            ("contents", self.contents.is_some()),
+            ("path", self.path.is_some()),
+            ("url", self.url.is_some()),
+        ];
+        for (name, present) in sources {
+            anyhow::ensure!(
+                !present,
+                "haystack cannot have both 'generate' and '{}'",
+                name,
+            );
+        }
+        anyhow::ensure!(
+            generate.unit.is_some() != generate.unit_file.is_some(),
+            "generated haystack must have exactly one of 'unit' and \
+             'unit-file'",
+        );
+        Ok(())
+    }
+
+    /// Check that the fields for downloading a haystack are used correctly.
+    /// That is, they must either all be absent or all be present, and when
+    /// present, there can't also be a 'path'.
+    fn check_download(&self) -> anyhow::Result<()> {
+        let url = match self.url {
+            Some(ref url) => url,
+            None => {
+                anyhow::ensure!(
+                    self.sha256.is_none() && self.path_cache.is_none(),
+                    "'sha256' and 'path-cache' can only be used with 'url'",
+                );
+                return Ok(());
+            }
+        };
+        anyhow::ensure!(
+            self.path.is_none(),
+            "haystack cannot have both 'url' and 'path'",
+        );
+        let sha256 = self.sha256.as_deref().with_context(|| {
+            format!("haystack with 'url' {} is missing 'sha256'", url)
+        })?;
+        anyhow::ensure!(
+            sha256.len() == 64
+                && sha256.bytes().all(|b| b.is_ascii_hexdigit()),
+            "'sha256' for haystack at {} must be 64 hex digits, got '{}'",
+            url,
+            sha256,
+        );
+        let path_cache = self.path_cache.as_deref().with_context(|| {
+            format!("haystack with 'url' {} is missing 'path-cache'", url)
+        })?;
+        let in_downloads =
+            path_cache.strip_prefix("downloads/").is_some_and(|rest| {
+                !rest.is_empty() && !rest.split('/').any(|c| c == "..")
+            });
+        anyhow::ensure!(
+            in_downloads,
+            "'path-cache' for haystack at {} must be a file inside \
+             'downloads/', got '{}'",
+            url,
+            path_cache,
+        );
+        Ok(())
+    }
 }
 
 impl WireHaystackOptions {
-    fn transform(&self, raw: &[u8]) -> Vec<u8> {
+    /// Apply these options to the given haystack, which must already be
+    /// in the encoding given by these options.
+    ///
+    /// When the encoding isn't UTF-8, only 'repeat', 'prepend' and 'append'
+    /// are supported. The latter two are encoded in the haystack's encoding.
+    fn transform(&self, raw: &[u8]) -> anyhow::Result<Vec<u8>> {
+        self.encoding.validate(raw)?;
+        if self.encoding != WireHaystackEncoding::Utf8 {
+            let unsupported = [
+                ("utf8-lossy", self.utf8_lossy),
+                ("trim", self.trim),
+                ("line-start", self.line_start.is_some()),
+                ("line-end", self.line_end.is_some()),
+            ];
+            for (name, enabled) in unsupported {
+                anyhow::ensure!(
+                    !enabled,
+                    "haystack option '{}' cannot be used with encoding '{}'",
+                    name,
+                    self.encoding.to_klv().as_str(),
+                );
+            }
+        }
         let mut raw = raw.to_vec();
         if self.utf8_lossy {
             raw = String::from_utf8_lossy(&raw).into_owned().into_bytes();
@@ -1020,28 +3533,37 @@ impl WireHaystackOptions {
             raw = raw.repeat(n);
         }
         if let Some(ref prepend) = self.prepend {
-            raw.splice(0..0, prepend.as_bytes().iter().copied());
+            raw.splice(0..0, self.encoding.encode(prepend)?);
         }
         if let Some(ref append) = self.append {
-            raw.extend_from_slice(append.as_bytes());
+            raw.extend_from_slice(&self.encoding.encode(append)?);
         }
-        raw
+        Ok(raw)
     }
 }
 
+/// Patterns as they were read from a regex file, which may not be valid UTF-8
+/// when the definition sets 'binary = true'.
+type RawPatterns = Arc<[Vec<u8>]>;
+
 #[derive(Clone, Debug)]
 struct Regexes {
     dir: PathBuf,
-    map: BTreeMap<RegexKey, Arc<[String]>>,
+    cache: Cache,
+    map: BTreeMap<RegexKey, RawPatterns>,
 }
 
 impl Regexes {
     fn new(
         bench_dir: &Path,
+        cache: &Cache,
         defs: &WireDefinitions,
     ) -> anyhow::Result<Regexes> {
-        let mut res =
-            Regexes { dir: bench_dir.join("regexes"), map: BTreeMap::new() };
+        let mut res = Regexes {
+            dir: bench_dir.join("regexes"),
+            cache: cache.clone(),
+            map: BTreeMap::new(),
+        };
         for def in defs.definitions.iter() {
             if let WireRegex::Full(ref full) = def.regex {
                 res.add(full).with_context(|| {
@@ -1068,11 +3590,31 @@ impl Regexes {
         if self.map.contains_key(&key) {
             return Ok(());
         }
-        let path = self.dir.join(&key.path);
-        let raw = std::fs::read_to_string(&path).with_context(|| {
-            format!("failed to read regex at {}", path.display())
+        let path = join_relative(&self.dir, &key.path);
+        let encoded = self.cache.get_or_create(
+            "regex",
+            &format!("{:?}", key),
+            &path,
+            || {
+                let raw = std::fs::read(&path).with_context(|| {
+                    format!("failed to read regex at {}", path.display())
+                })?;
+                if !full.options.binary {
+                    raw.to_str().with_context(|| {
+                        format!(
+                            "regex at {} is not valid UTF-8 (set \
+                             'binary = true' to permit this)",
+                            path.display(),
+                        )
+                    })?;
+                }
+                let patterns = full.options.transform_from_file(&raw);
+                Ok(RegexKey::encode(&patterns))
+            },
+        )?;
+        let patterns = RegexKey::decode(&encoded).with_context(|| {
+            format!("failed to decode cached regex for {}", path.display())
         })?;
-        let patterns = full.options.transform_from_file(&raw);
         self.map.insert(key, Arc::from(patterns));
         Ok(())
     }
@@ -1081,16 +3623,22 @@ impl Regexes {
 #[derive(Clone, Debug)]
 struct Haystacks {
     dir: PathBuf,
+    cache: Cache,
+    allow_download: bool,
     map: BTreeMap<HaystackKey, Arc<[u8]>>,
 }
 
 impl Haystacks {
     fn new(
         bench_dir: &Path,
+        cache: &Cache,
         defs: &WireDefinitions,
+        allow_download: bool,
     ) -> anyhow::Result<Haystacks> {
         let mut hays = Haystacks {
             dir: bench_dir.join("haystacks"),
+            cache: cache.clone(),
+            allow_download,
             map: BTreeMap::new(),
         };
         for def in defs.definitions.iter() {
@@ -1112,6 +3660,8 @@ impl Haystacks {
         // to be small enough that reuse doesn't matter. Moreover, there isn't
         // any sensible way to create a key for an inline haystack that is
         // independent from the benchmark itself.
+        full.check_download()?;
+        full.check_generate()?;
         let key = match HaystackKey::from_wire(full) {
             None => return Ok(()),
             Some(key) => key,
@@ -1119,41 +3669,290 @@ impl Haystacks {
         if self.map.contains_key(&key) {
             return Ok(());
         }
-        let path = self.dir.join(&key.path);
-        let raw = std::fs::read(&path).with_context(|| {
-            format!("failed to read haystack at {}", path.display())
-        })?;
-        let haystack = full.options.transform(&raw);
+        // Generated haystacks aren't cached, since generating one is about
+        // as fast as reading it back from the cache.
+        if let Some(ref generate) = full.generate {
+            let unit = match generate.unit_file {
+                None => {
+                    // OK because 'check_generate' guarantees that a unit is
+                    // given when there's no unit file.
+                    let unit = generate.unit.as_deref().unwrap();
+                    full.options.encoding.encode(unit)?
+                }
+                Some(ref unit_file) => {
+                    let path = join_relative(&self.dir, unit_file);
+                    let raw = std::fs::read(&path).with_context(|| {
+                        format!(
+                            "failed to read haystack unit at {}",
+                            path.display()
+                        )
+                    })?;
+                    decompress(&path, raw)?
+                }
+            };
+            let raw = generate.generate(&unit, full.options.encoding)?;
+            let haystack = full.options.transform(&raw)?;
+            self.map.insert(key, Arc::from(haystack));
+            return Ok(());
+        }
+        // OK because a key without a path is always for a generated
+        // haystack, which is handled above.
+        let path = join_relative(&self.dir, key.path.as_deref().unwrap());
+        if let Some(ref url) = full.url {
+            if !path.exists() {
+                self.download(url, full.sha256.as_deref().unwrap(), &path)?;
+            }
+        }
+        // Downloaded haystacks have their checksum verified whenever they're
+        // read, but not when the transformed haystack is in the cache. That's
+        // OK, since a cache entry can only exist if the haystack was verified
+        // when it was created, and the entry is invalidated if the file
+        // changes.
+        let haystack = self.cache.get_or_create(
+            "haystack",
+            &format!("{:?}", key),
+            &path,
+            || {
+                let raw = match full.url {
+                    None => std::fs::read(&path).with_context(|| {
+                        format!(
+                            "failed to read haystack at {}",
+                            path.display()
+                        )
+                    })?,
+                    Some(ref url) => self.read_downloaded(
+                        url,
+                        full.sha256.as_deref().unwrap(),
+                        &path,
+                    )?,
+                };
+                let raw = decompress(&path, raw)?;
+                full.options.transform(&raw)
+            },
+        )?;
         self.map.insert(key, Arc::from(haystack));
         Ok(())
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
-struct RegexKey {
-    path: String,
-    options: WireRegexOptions,
-}
+impl Haystacks {
+    /// Read a previously downloaded haystack at `path` and verify that its
+    /// checksum matches `sha256`. If it doesn't match, then it's downloaded
+    /// again when downloads are allowed. Otherwise, an error is returned.
+    fn read_downloaded(
+        &self,
+        url: &str,
+        sha256: &str,
+        path: &Path,
+    ) -> anyhow::Result<Vec<u8>> {
+        let raw = std::fs::read(path).with_context(|| {
+            format!("failed to read haystack at {}", path.display())
+        })?;
+        let got = util::sha256_hex(&raw);
+        if got.eq_ignore_ascii_case(sha256) {
+            return Ok(raw);
+        }
+        anyhow::ensure!(
+            self.allow_download,
+            "checksum mismatch for haystack at {} (expected {}, got {}), \
+             re-run with --allow-download to download it again from {}",
+            path.display(),
+            sha256,
+            got,
+            url,
+        );
+        self.download(url, sha256, path)
+    }
 
-impl RegexKey {
-    fn from_wire(full: &WireRegexFull) -> Option<RegexKey> {
+    /// Download the haystack at `url`, verify that its checksum matches
+    /// `sha256` and write it to `path`. The downloaded haystack is returned.
+    ///
+    /// This returns an error without doing anything if downloads aren't
+    /// allowed. And nothing is written if the checksum doesn't match.
+    fn download(
+        &self,
+        url: &str,
+        sha256: &str,
+        path: &Path,
+    ) -> anyhow::Result<Vec<u8>> {
+        anyhow::ensure!(
+            self.allow_download,
+            "haystack at {} has not been downloaded yet, \
+             re-run with --allow-download to download it from {}",
+            path.display(),
+            url,
+        );
+        log::info!("downloading {} to {}", url, path.display());
+        let mut cmd = process::Command::new("curl");
+        cmd.arg("--fail")
+            .arg("--silent")
+            .arg("--show-error")
+            .arg("--location")
+            .arg(url);
+        let raw = util::output(&mut cmd).with_context(|| {
+            format!("failed to download {} with curl", url)
+        })?;
+        let raw = Vec::from(raw);
+        let got = util::sha256_hex(&raw);
+        anyhow::ensure!(
+            got.eq_ignore_ascii_case(sha256),
+            "checksum mismatch for haystack downloaded from {} \
+             (expected {}, got {})",
+            url,
+            sha256,
+            got,
+        );
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| {
+                format!("failed to create {}", parent.display())
+            })?;
+        }
+        std::fs::write(path, &raw).with_context(|| {
+            format!("failed to write haystack to {}", path.display())
+        })?;
+        Ok(raw)
+    }
+}
+
+/// Returns the group name for a definition file, given its path relative to
+/// the definitions directory. Group names always use '/' as a separator,
+/// even on Windows.
+///
+/// This returns `None` when the path isn't valid UTF-8.
+fn group_name(suffix: &Path) -> Option<String> {
+    Some(suffix.with_extension("").to_str()?.replace('\\', "/"))
+}
+
+/// Normalize a path from a benchmark definition, which is relative to some
+/// directory inside the benchmark directory.
+///
+/// Both '/' and '\\' are accepted as separators, but the path returned
+/// always uses '/'. Empty and '.' components are dropped. An error is
+/// returned if the path is empty, absolute or contains a '..' component,
+/// since such paths could refer to files outside of the benchmark directory.
+fn normalize_relative_path(path: &str) -> anyhow::Result<String> {
+    let unified = path.replace('\\', "/");
+    anyhow::ensure!(
+        !unified.starts_with('/'),
+        "path '{}' must be relative, but it is absolute",
+        path,
+    );
+    let mut components = vec![];
+    for (i, c) in unified.split('/').enumerate() {
+        anyhow::ensure!(
+            c != "..",
+            "path '{}' must not contain a '..' component",
+            path,
+        );
+        // A Windows path like 'C:/foo' or 'C:foo' is never relative to the
+        // benchmark directory.
+        anyhow::ensure!(
+            !(i == 0 && c.len() >= 2 && c.as_bytes()[1] == b':'),
+            "path '{}' must be relative, but it has a drive prefix",
+            path,
+        );
+        if !c.is_empty() && c != "." {
+            components.push(c);
+        }
+    }
+    anyhow::ensure!(!components.is_empty(), "path '{}' is empty", path);
+    Ok(components.join("/"))
+}
+
+/// Join a normalized relative path (as returned by 'normalize_relative_path')
+/// to the given directory. Each component is pushed separately so that the
+/// platform's own separator is used.
+fn join_relative(dir: &Path, path: &str) -> PathBuf {
+    let mut joined = dir.to_path_buf();
+    for c in path.split('/') {
+        joined.push(c);
+    }
+    joined
+}
+
+/// Decompress the given haystack read from `path` if its file extension
+/// indicates that it's compressed. Currently, '.gz' (gzip) and '.xz' are
+/// supported. Otherwise, the haystack is returned unchanged.
+fn decompress(path: &Path, raw: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+    let mut out = vec![];
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("gz") => {
+            let mut dec = flate2::read::MultiGzDecoder::new(&*raw);
+            dec.read_to_end(&mut out).map(|_| ()).map_err(anyhow::Error::from)
+        }
+        Some("xz") => lzma_rs::xz_decompress(&mut &*raw, &mut out)
+            .map_err(|err| anyhow::anyhow!("{}", err)),
+        _ => return Ok(raw),
+    }
+    .with_context(|| {
+        format!("failed to decompress haystack at {}", path.display())
+    })?;
+    Ok(out)
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+struct RegexKey {
+    path: String,
+    options: WireRegexOptions,
+}
+
+impl RegexKey {
+    fn from_wire(full: &WireRegexFull) -> Option<RegexKey> {
         Some(RegexKey {
             path: full.path.clone()?,
             options: full.options.clone(),
         })
     }
+
+    /// Encode a sequence of patterns into a flat sequence of bytes suitable
+    /// for storing in a cache. Each pattern is written as its length in
+    /// bytes, a line terminator and then the pattern itself. We can't just
+    /// use one pattern per line since patterns may contain line terminators.
+    fn encode(patterns: &[Vec<u8>]) -> Vec<u8> {
+        let mut buf = vec![];
+        for p in patterns.iter() {
+            buf.extend_from_slice(p.len().to_string().as_bytes());
+            buf.push(b'\n');
+            buf.extend_from_slice(p);
+        }
+        buf
+    }
+
+    /// Decode a sequence of patterns previously encoded with `encode`.
+    fn decode(mut buf: &[u8]) -> anyhow::Result<Vec<Vec<u8>>> {
+        let mut patterns = vec![];
+        while !buf.is_empty() {
+            let nl = buf.find_byte(b'\n').context("missing length")?;
+            let len: usize = buf[..nl].to_str()?.parse()?;
+            buf = &buf[nl + 1..];
+            anyhow::ensure!(len <= buf.len(), "pattern length out of bounds");
+            patterns.push(buf[..len].to_vec());
+            buf = &buf[len..];
+        }
+        Ok(patterns)
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
 struct HaystackKey {
-    path: String,
+    /// The path of the haystack file. For a generated haystack, this is the
+    /// path of its unit file, if it has one.
+    path: Option<String>,
+    generate: Option<WireHaystackGenerate>,
     options: WireHaystackOptions,
 }
 
 impl HaystackKey {
     fn from_wire(full: &WireHaystackFull) -> Option<HaystackKey> {
+        let path = full.path.clone().or_else(|| full.path_cache.clone());
+        if path.is_none() && full.generate.is_none() {
+            return None;
+        }
+        let unit_file =
+            full.generate.as_ref().and_then(|g| g.unit_file.clone());
         Some(HaystackKey {
-            path: full.path.clone()?,
+            path: path.or(unit_file),
+            generate: full.generate.clone(),
             options: full.options.clone(),
         })
     }
@@ -1232,36 +4031,11 @@ mod tests {
     fn engines(
         names: impl IntoIterator<Item = impl AsRef<str>>,
     ) -> Vec<Engine> {
-        names
-            .into_iter()
-            .map(|n| Engine {
-                name: n.as_ref().to_string(),
-                cwd: None,
-                run: Command {
-                    cwd: None,
-                    bin: "rebar".to_string(),
-                    args: vec![],
-                    envs: vec![],
-                },
-                version: "0.0.0".to_string(),
-                version_config: VersionConfig {
-                    regex: None,
-                    file: None,
-                    run: None,
-                },
-                dependency: vec![],
-                build: vec![],
-                clean: vec![],
-            })
-            .collect()
+        Engines::fake(names).list
     }
 
     fn count_all(count: u64) -> Vec<CountEngine> {
-        vec![CountEngine {
-            re: Regex(RRegex::new(r"^.*$").unwrap()),
-            engine: r".*".to_string(),
-            count,
-        }]
+        vec![CountEngine::all(count)]
     }
 
     #[test]
@@ -1273,6 +4047,8 @@ name = "test"
 regex = 'foo'
 case-insensitive = true
 unicode = true
+multiline = true
+dot-all = true
 haystack = "quuxfoo"
 engines = ["regex/api"]
 count = 1
@@ -1287,20 +4063,111 @@ count = 1
             model: "count".to_string(),
             name: name("group", "test"),
             regexes: regexes(["foo"]),
+            binary_regexes: None,
             regex_path: None,
             options: DefinitionOptions {
                 case_insensitive: true,
                 unicode: true,
+                multiline: true,
+                dot_all: true,
+                search_start: None,
+                search_end: None,
+                max_count: None,
             },
             haystack: haystack("quuxfoo"),
             haystack_path: None,
+            haystack_encoding: klv::HaystackEncoding::Utf8,
             count: count_all(1),
+            count_derive_from: None,
+            verify_count: true,
             engines: engines(["regex/api"]),
             analysis: None,
+            requires: vec![],
+            budget: DefinitionBudget::default(),
+            params: BTreeMap::new(),
         };
         assert_eq!(expected, *got);
     }
 
+    #[test]
+    fn search_window() {
+        let raw = |model: &str, end: usize| {
+            format!(
+                r#"
+[[bench]]
+model = "{model}"
+name = "test"
+regex = 'foo'
+haystack = "foobarfoo"
+search-start = 3
+search-end = {end}
+engines = ["regex/api", "regex/other"]
+count = 1
+"#
+            )
+        };
+        let mut list = engines(["regex/api", "regex/other"]);
+        list[0].capabilities.push("search-window".to_string());
+        let es = Engines::from_list(list);
+        let filters = Filters::default();
+
+        let benches =
+            Benchmarks::from_slice(&es, &filters, "group", raw("count", 9))
+                .unwrap();
+        let def = &benches.defs[0];
+        assert_eq!(Some(3), def.options.search_start);
+        assert_eq!(Some(9), def.options.search_end);
+        // The capability is required implicitly, so engines without it are
+        // skipped.
+        assert_eq!(vec!["search-window".to_string()], def.requires);
+        assert_eq!(1, def.engines.len());
+        assert_eq!("regex/api", def.engines[0].name);
+
+        let err =
+            Benchmarks::from_slice(&es, &filters, "group", raw("count", 10))
+                .unwrap_err();
+        let msg = format!("{:#}", err);
+        assert!(msg.contains("invalid search window 3..10"), "{}", msg);
+
+        let err =
+            Benchmarks::from_slice(&es, &filters, "group", raw("grep", 9))
+                .unwrap_err();
+        let msg = format!("{:#}", err);
+        assert!(msg.contains("only supported by the 'count'"), "{}", msg);
+    }
+
+    #[test]
+    fn max_count() {
+        let load = |model: &str, max_count: &str| {
+            let raw = format!(
+                r#"
+[[bench]]
+model = "{model}"
+name = "test"
+regex = 'a'
+haystack = "aaa\na"
+{max_count}
+engines = ["regex/api"]
+count = 2
+"#
+            );
+            let es = Engines::from_list(engines(["regex/api"]));
+            Benchmarks::from_slice(&es, &Filters::default(), "group", &raw)
+                .map_err(|err| format!("{:#}", err))
+        };
+
+        let benches = load("grep-max", "max-count = 1").unwrap();
+        assert_eq!(Some(1), benches.defs[0].options.max_count);
+        // The cap is optional, in which case there is no limit.
+        let benches = load("grep-max", "").unwrap();
+        assert_eq!(None, benches.defs[0].options.max_count);
+
+        let err = load("grep-max", "max-count = 0").unwrap_err();
+        assert!(err.contains("must be at least 1"), "{}", err);
+        let err = load("grep", "max-count = 1").unwrap_err();
+        assert!(err.contains("only supported by the 'grep-max'"), "{}", err);
+    }
+
     #[test]
     fn regex_empty() {
         let raw = r#"
@@ -1322,13 +4189,20 @@ count = 1
             model: "count".to_string(),
             name: name("group", "test"),
             regexes: Arc::from([]),
+            binary_regexes: None,
             regex_path: None,
             options: DefinitionOptions::default(),
             haystack: haystack("quuxfoo"),
             haystack_path: None,
+            haystack_encoding: klv::HaystackEncoding::Utf8,
             count: count_all(1),
+            count_derive_from: None,
+            verify_count: true,
             engines: engines(["regex/api"]),
             analysis: None,
+            requires: vec![],
+            budget: DefinitionBudget::default(),
+            params: BTreeMap::new(),
         };
         assert_eq!(expected, *got);
     }
@@ -1354,13 +4228,20 @@ count = 1
             model: "count".to_string(),
             name: name("group", "test"),
             regexes: regexes(["foo", "bar"]),
+            binary_regexes: None,
             regex_path: None,
             options: DefinitionOptions::default(),
             haystack: haystack("quuxfoo"),
             haystack_path: None,
+            haystack_encoding: klv::HaystackEncoding::Utf8,
             count: count_all(1),
+            count_derive_from: None,
+            verify_count: true,
             engines: engines(["regex/api"]),
             analysis: None,
+            requires: vec![],
+            budget: DefinitionBudget::default(),
+            params: BTreeMap::new(),
         };
         assert_eq!(expected, *got);
     }
@@ -1386,13 +4267,20 @@ count = 1
             model: "count".to_string(),
             name: name("group", "test"),
             regexes: regexes(["foo"]),
+            binary_regexes: None,
             regex_path: None,
             options: DefinitionOptions::default(),
             haystack: haystack("quuxfoo"),
             haystack_path: None,
+            haystack_encoding: klv::HaystackEncoding::Utf8,
             count: count_all(1),
+            count_derive_from: None,
+            verify_count: true,
             engines: engines(["regex/api"]),
             analysis: None,
+            requires: vec![],
+            budget: DefinitionBudget::default(),
+            params: BTreeMap::new(),
         };
         assert_eq!(expected, *got);
     }
@@ -1418,13 +4306,20 @@ count = 1
             model: "count".to_string(),
             name: name("group", "test"),
             regexes: Arc::from([]),
+            binary_regexes: None,
             regex_path: None,
             options: DefinitionOptions::default(),
             haystack: haystack("quuxfoo"),
             haystack_path: None,
+            haystack_encoding: klv::HaystackEncoding::Utf8,
             count: count_all(1),
+            count_derive_from: None,
+            verify_count: true,
             engines: engines(["regex/api"]),
             analysis: None,
+            requires: vec![],
+            budget: DefinitionBudget::default(),
+            params: BTreeMap::new(),
         };
         assert_eq!(expected, *got);
     }
@@ -1450,13 +4345,20 @@ count = 1
             model: "count".to_string(),
             name: name("group", "test"),
             regexes: regexes(["foo", "bar"]),
+            binary_regexes: None,
             regex_path: None,
             options: DefinitionOptions::default(),
             haystack: haystack("quuxfoo"),
             haystack_path: None,
+            haystack_encoding: klv::HaystackEncoding::Utf8,
             count: count_all(1),
+            count_derive_from: None,
+            verify_count: true,
             engines: engines(["regex/api"]),
             analysis: None,
+            requires: vec![],
+            budget: DefinitionBudget::default(),
+            params: BTreeMap::new(),
         };
         assert_eq!(expected, *got);
     }
@@ -1482,13 +4384,20 @@ count = 1
             model: "count".to_string(),
             name: name("group", "test"),
             regexes: regexes([r"f\*oo"]),
+            binary_regexes: None,
             regex_path: None,
             options: DefinitionOptions::default(),
             haystack: haystack("quuxfoo"),
             haystack_path: None,
+            haystack_encoding: klv::HaystackEncoding::Utf8,
             count: count_all(1),
+            count_derive_from: None,
+            verify_count: true,
             engines: engines(["regex/api"]),
             analysis: None,
+            requires: vec![],
+            budget: DefinitionBudget::default(),
+            params: BTreeMap::new(),
         };
         assert_eq!(expected, *got);
     }
@@ -1514,13 +4423,20 @@ count = 1
             model: "count".to_string(),
             name: name("group", "test"),
             regexes: regexes([r"f\*oo", r"b\*ar"]),
+            binary_regexes: None,
             regex_path: None,
             options: DefinitionOptions::default(),
             haystack: haystack("quuxfoo"),
             haystack_path: None,
+            haystack_encoding: klv::HaystackEncoding::Utf8,
             count: count_all(1),
+            count_derive_from: None,
+            verify_count: true,
             engines: engines(["regex/api"]),
             analysis: None,
+            requires: vec![],
+            budget: DefinitionBudget::default(),
+            params: BTreeMap::new(),
         };
         assert_eq!(expected, *got);
     }
@@ -1546,13 +4462,20 @@ count = 1
             model: "count".to_string(),
             name: name("group", "test"),
             regexes: regexes(["foo"]),
+            binary_regexes: None,
             regex_path: None,
             options: DefinitionOptions::default(),
             haystack: haystack("quuxfoo"),
             haystack_path: None,
+            haystack_encoding: klv::HaystackEncoding::Utf8,
             count: count_all(1),
+            count_derive_from: None,
+            verify_count: true,
             engines: engines(["regex/api"]),
             analysis: None,
+            requires: vec![],
+            budget: DefinitionBudget::default(),
+            params: BTreeMap::new(),
         };
         assert_eq!(expected, *got);
     }
@@ -1578,13 +4501,20 @@ count = 1
             model: "count".to_string(),
             name: name("group", "test"),
             regexes: regexes(["foo"]),
+            binary_regexes: None,
             regex_path: None,
             options: DefinitionOptions::default(),
             haystack: haystack("quuxfoo"),
             haystack_path: None,
+            haystack_encoding: klv::HaystackEncoding::Utf8,
             count: count_all(1),
+            count_derive_from: None,
+            verify_count: true,
             engines: engines(["regex/api"]),
             analysis: None,
+            requires: vec![],
+            budget: DefinitionBudget::default(),
+            params: BTreeMap::new(),
         };
         assert_eq!(expected, *got);
     }
@@ -1610,13 +4540,20 @@ count = 1
             model: "count".to_string(),
             name: name("group", "test"),
             regexes: regexes(["foo"]),
+            binary_regexes: None,
             regex_path: None,
             options: DefinitionOptions::default(),
             haystack: haystack("barquuxfoo"),
             haystack_path: None,
+            haystack_encoding: klv::HaystackEncoding::Utf8,
             count: count_all(1),
+            count_derive_from: None,
+            verify_count: true,
             engines: engines(["regex/api"]),
             analysis: None,
+            requires: vec![],
+            budget: DefinitionBudget::default(),
+            params: BTreeMap::new(),
         };
         assert_eq!(expected, *got);
     }
@@ -1642,13 +4579,20 @@ count = 1
             model: "count".to_string(),
             name: name("group", "test"),
             regexes: regexes(["foo"]),
+            binary_regexes: None,
             regex_path: None,
             options: DefinitionOptions::default(),
             haystack: haystack("quuxfoobar"),
             haystack_path: None,
+            haystack_encoding: klv::HaystackEncoding::Utf8,
             count: count_all(1),
+            count_derive_from: None,
+            verify_count: true,
             engines: engines(["regex/api"]),
             analysis: None,
+            requires: vec![],
+            budget: DefinitionBudget::default(),
+            params: BTreeMap::new(),
         };
         assert_eq!(expected, *got);
     }
@@ -1674,17 +4618,83 @@ count = 1
             model: "count".to_string(),
             name: name("group", "test"),
             regexes: regexes(["foo"]),
+            binary_regexes: None,
             regex_path: None,
             options: DefinitionOptions::default(),
             haystack: haystack(" quuxfoo "),
             haystack_path: None,
+            haystack_encoding: klv::HaystackEncoding::Utf8,
             count: count_all(1),
+            count_derive_from: None,
+            verify_count: true,
             engines: engines(["regex/api"]),
             analysis: None,
+            requires: vec![],
+            budget: DefinitionBudget::default(),
+            params: BTreeMap::new(),
         };
         assert_eq!(expected, *got);
     }
 
+    #[test]
+    fn haystack_full_inline_encoding() {
+        let es = Engines::from_list(engines(["icu"]));
+        let filters = Filters::default();
+        let load = |haystack: &str| {
+            let raw = format!(
+                r#"
+[[bench]]
+model = "count"
+name = "test"
+regex = 'foo'
+haystack = {haystack}
+engines = ["icu"]
+count = 1
+"#
+            );
+            Benchmarks::from_slice(&es, &filters, "group", &raw)
+                .map(|b| b.defs.into_iter().next().unwrap())
+                .map_err(|err| format!("{:#}", err))
+        };
+
+        // Contents, along with 'prepend' and 'append', are encoded.
+        let def = load(
+            r#"{ contents = "fé", append = "!", encoding = "utf-16le" }"#,
+        )
+        .unwrap();
+        assert_eq!(klv::HaystackEncoding::Utf16le, def.haystack_encoding);
+        assert_eq!(haystack(b"f\x00\xE9\x00!\x00"), def.haystack);
+        let def = load(
+            r#"{ contents = "fé", prepend = "à", encoding = "latin-1" }"#,
+        )
+        .unwrap();
+        assert_eq!(klv::HaystackEncoding::Latin1, def.haystack_encoding);
+        assert_eq!(haystack(b"\xE0f\xE9"), def.haystack);
+
+        let err = load(r#"{ contents = "☃", encoding = "latin-1" }"#);
+        assert!(err.unwrap_err().contains("cannot be encoded"));
+        let err =
+            load(r#"{ contents = "a", trim = true, encoding = "utf-16le" }"#);
+        assert!(err.unwrap_err().contains("'trim' cannot be used"));
+        let err = load(r#"{ contents = "a", encoding = "utf-32" }"#);
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn haystack_encoding_validate() {
+        let utf16 = WireHaystackEncoding::Utf16le;
+        assert!(utf16.validate(b"a\x00\x3D\xD8\xA9\xDC").is_ok());
+        // Odd number of bytes.
+        let err = utf16.validate(b"a\x00b").unwrap_err().to_string();
+        assert!(err.contains("even number"), "{}", err);
+        // Lone high surrogate.
+        let err = utf16.validate(b"a\x00\x3D\xD8").unwrap_err().to_string();
+        assert!(err.contains("0xD83D near byte offset 2"), "{}", err);
+        // Anything goes for UTF-8 and latin-1.
+        assert!(WireHaystackEncoding::Utf8.validate(b"\xFF").is_ok());
+        assert!(WireHaystackEncoding::Latin1.validate(b"\xFF").is_ok());
+    }
+
     // We used to treat `engines = []` as an error, but I changed that because
     // it was needlessly inflexible. It can be nice to define a benchmark
     // with no engines to get started, and then incrementally add them. Also,
@@ -1736,4 +4746,1537 @@ engines = ["regex/api"]
         let filters = Filters::default();
         assert!(Benchmarks::from_slice(&es, &filters, "group", raw).is_err());
     }
+
+    #[test]
+    fn engine_rewrite() {
+        let rewrite = |find: &str, replace: &str| Rewrite {
+            find: Regex(RRegex::new(find).unwrap()),
+            replace: replace.to_string(),
+        };
+        let mut engine = engines(["foo"]).pop().unwrap();
+        let pats = regexes([r"(?u)\w+", r"\pL\b", "abc"]);
+        assert_eq!(engine.rewrite_patterns(&pats), &*pats);
+
+        engine.rewrite = vec![
+            rewrite(r"^\(\?u\)", ""),
+            rewrite(r"\\pL", r"\p{L}"),
+            rewrite(r"\\(?P<assertion>b)", "[$assertion]"),
+        ];
+        assert_eq!(
+            engine.rewrite_patterns(&pats),
+            vec![r"\w+", r"\p{L}[b]", "abc"],
+        );
+    }
+
+    #[test]
+    fn version_req() {
+        let req = |raw: &str| VersionReq::parse(raw).unwrap();
+
+        assert!(req(">=10.43").matches("10.43"));
+        assert!(req(">=10.43").matches("10.44 2024-06-07"));
+        assert!(!req(">=10.43").matches("10.42 2022-12-11"));
+        assert!(req("<10.43").matches("10.42.1"));
+        assert!(!req("<10.43").matches("10.43.0"));
+        assert!(req("10.42").matches("10.42.1"));
+        assert!(!req("10.42").matches("10.421"));
+        assert!(!req("=10.42.1").matches("10.42"));
+        assert!(req(">1, <=2").matches("go1.21.3"));
+        assert!(!req(">1, <=2").matches("2.0.1"));
+        assert!(!req(">=1").matches("unknown"));
+
+        assert!(VersionReq::parse(">=10.x").is_err());
+        assert!(VersionReq::parse("").is_err());
+    }
+
+    #[test]
+    fn count_version() {
+        let raw = r#"
+[[bench]]
+model = "count"
+name = "foo"
+regex = "a"
+haystack = "a"
+count = [
+  { engine = "pcre2", count = 2, version = ">=10.43" },
+  { engine = "pcre2", count = 1, version = "<10.42" },
+  { engine = "other", count = 3 },
+]
+engines = ["pcre2", "other"]
+"#;
+        let es = Engines::from_list(engines(["pcre2", "other"]));
+        let filters = Filters::default();
+        let benches =
+            Benchmarks::from_slice(&es, &filters, "group", raw).unwrap();
+        let def = &benches.defs[0];
+        assert_eq!(def.count("pcre2", "10.43 2024-02-16").unwrap(), 2);
+        assert_eq!(def.count("pcre2", "10.40").unwrap(), 1);
+        assert_eq!(def.count("other", "1.0").unwrap(), 3);
+        let err = def.count("pcre2", "10.42").unwrap_err().to_string();
+        assert!(err.contains("no count entry matches engine version"));
+        let err = def.count("nope", "1.0").unwrap_err().to_string();
+        assert!(err.contains("no count available"));
+    }
+
+    #[test]
+    fn count_shadowed() {
+        let load = |counts: &str| {
+            let raw = format!(
+                r#"
+[[bench]]
+model = "count"
+name = "foo"
+regex = "a"
+haystack = "a"
+count = [
+{counts}
+]
+engines = ["rust/regex", "rust/regex/lite", "icu"]
+"#
+            );
+            let es = Engines::from_list(engines([
+                "rust/regex",
+                "rust/regex/lite",
+                "icu",
+            ]));
+            Benchmarks::from_slice(&es, &Filters::default(), "group", &raw)
+                .map_err(|err| format!("{:#}", err))
+        };
+
+        // A broad entry shadows a more specific entry after it.
+        let err = load(
+            r#"
+  { engine = "rust/.*", count = 1 },
+  { engine = "rust/regex/lite", count = 2 },
+  { engine = "icu", count = 3 },
+"#,
+        )
+        .unwrap_err();
+        assert!(err.contains("benchmark 'group/foo'"), "{err}");
+        assert!(err.contains("regex engine 'rust/regex/lite'"), "{err}");
+        assert!(err.contains("'rust/.*' (count = 1)"), "{err}");
+        assert!(err.contains("'rust/regex/lite' (count = 2)"), "{err}");
+
+        // An entry without a version shadows one with a version.
+        let err = load(
+            r#"
+  { engine = "icu", count = 3 },
+  { engine = "icu", count = 4, version = ">=70" },
+  { engine = ".*", count = 1 },
+"#,
+        )
+        .unwrap_err();
+        assert!(err.contains("regex engine 'icu'"), "{err}");
+
+        // Shadowed entries with the same count are fine.
+        let benches = load(
+            r#"
+  { engine = "rust/.*", count = 1 },
+  { engine = "rust/regex/lite", count = 1 },
+  { engine = "icu", count = 3 },
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            benches.defs[0].count("rust/regex/lite", "0.1").unwrap(),
+            1
+        );
+
+        // A catch-all entry at the end is still used for some engines, and
+        // so is a fallback for versions not matched by an earlier entry.
+        let benches = load(
+            r#"
+  { engine = "icu", count = 3, version = ">=70" },
+  { engine = "icu", count = 4 },
+  { engine = ".*", count = 1 },
+"#,
+        )
+        .unwrap();
+        let def = &benches.defs[0];
+        assert_eq!(def.count("icu", "72.1").unwrap(), 3);
+        assert_eq!(def.count("icu", "69.1").unwrap(), 4);
+        assert_eq!(def.count("rust/regex", "1.10.2").unwrap(), 1);
+
+        // Engines not listed in the definition are checked when asked for.
+        let benches = load(
+            r#"
+  { engine = "rust/regex.*", count = 1 },
+  { engine = "rust/regexold", count = 2 },
+  { engine = "icu", count = 3 },
+"#,
+        )
+        .unwrap();
+        let def = &benches.defs[0];
+        assert_eq!(def.count("rust/regex", "1.10.2").unwrap(), 1);
+        let err =
+            format!("{:#}", def.count("rust/regexold", "1.7").unwrap_err());
+        assert!(err.contains("regex engine 'rust/regexold'"), "{err}");
+    }
+
+    #[test]
+    fn count_semantics() {
+        let raw = r#"
+[[bench]]
+model = "count"
+name = "foo"
+regex = "a"
+haystack = "a"
+count = [
+  { engine = "rust/.*", count = 10, semantics = "simple-fold" },
+  { engine = "icu", count = 12, semantics = "full-fold" },
+  { engine = "other", count = 9 },
+]
+engines = ["rust/regex", "icu", "other"]
+"#;
+        let es = Engines::from_list(engines(["rust/regex", "icu", "other"]));
+        let filters = Filters::default();
+        let benches =
+            Benchmarks::from_slice(&es, &filters, "group", raw).unwrap();
+        let def = &benches.defs[0];
+        let ce = def.count_entry("rust/regex", "1.0").unwrap();
+        assert_eq!(ce.count, 10);
+        assert_eq!(ce.semantics.as_deref(), Some("simple-fold"));
+        let ce = def.count_entry("icu", "72.1").unwrap();
+        assert_eq!(ce.count, 12);
+        assert_eq!(ce.semantics.as_deref(), Some("full-fold"));
+        let ce = def.count_entry("other", "1.0").unwrap();
+        assert_eq!(ce.count, 9);
+        assert_eq!(ce.semantics, None);
+    }
+
+    #[test]
+    fn count_per_pattern() {
+        let raw = r#"
+[[bench]]
+model = "count-per-pattern"
+name = "foo"
+regex = ["a", "b", "c"]
+haystack = "abcab"
+counts-per-pattern = [
+  { engine = "rust/.*", counts = [2, 2, 1] },
+  { engine = "hyperscan", counts = [2, 1, 2] },
+]
+engines = ["rust/regex", "hyperscan"]
+"#;
+        let es = Engines::from_list(engines(["rust/regex", "hyperscan"]));
+        let filters = Filters::default();
+        let benches =
+            Benchmarks::from_slice(&es, &filters, "group", raw).unwrap();
+        let def = &benches.defs[0];
+        let ce = def.count_entry("rust/regex", "1.0").unwrap();
+        assert_eq!(ce.count, 19); // 1*2 + 4*2 + 9*1
+        assert_eq!(ce.per_pattern.as_deref(), Some(&[2, 2, 1][..]));
+        // Same total, but different attribution, so different checksum.
+        let ce = def.count_entry("hyperscan", "1.0").unwrap();
+        assert_eq!(ce.count, 24); // 1*2 + 4*1 + 9*2
+    }
+
+    #[test]
+    fn per_pattern_checksum_attribution() {
+        let base = per_pattern_checksum(&[3, 1, 2]);
+        // Swapping the counts of any two patterns is detected.
+        assert_ne!(base, per_pattern_checksum(&[1, 3, 2]));
+        assert_ne!(base, per_pattern_checksum(&[2, 1, 3]));
+        assert_ne!(base, per_pattern_checksum(&[3, 2, 1]));
+        // Moving matches from both ends to the middle is detected too. This
+        // went unnoticed when each match only contributed its index plus one.
+        assert_ne!(
+            per_pattern_checksum(&[2, 1, 2]),
+            per_pattern_checksum(&[1, 3, 1]),
+        );
+    }
+
+    #[test]
+    fn count_per_pattern_errors() {
+        let es = Engines::from_list(engines(["rust/regex"]));
+        let filters = Filters::default();
+        let load = |model: &str, counts: &str| {
+            let raw = format!(
+                r#"
+[[bench]]
+model = "{model}"
+name = "foo"
+regex = ["a", "b"]
+haystack = "ab"
+{counts}
+engines = ["rust/regex"]
+"#
+            );
+            Benchmarks::from_slice(&es, &filters, "group", &raw)
+                .map(|_| ())
+                .map_err(|err| format!("{:#}", err))
+        };
+        let err =
+            load("count-per-pattern", "counts-per-pattern = [1]").unwrap_err();
+        assert!(err.contains("exactly one count for each pattern"), "{err}");
+        let err = load("count-per-pattern", "count = 2").unwrap_err();
+        assert!(err.contains("use 'counts-per-pattern' instead"), "{err}");
+        let err = load("count", "counts-per-pattern = [1, 1]").unwrap_err();
+        assert!(err.contains("can only be used with"), "{err}");
+        assert!(
+            load("count-per-pattern", "counts-per-pattern = [1, 1]").is_ok()
+        );
+    }
+
+    #[test]
+    fn count_unresolved() {
+        let raw = r#"
+[[bench]]
+model = "count"
+name = "foo"
+regex = "a"
+haystack = "a"
+engines = ["rust/regex"]
+"#;
+        let es = Engines::from_list(engines(["rust/regex"]));
+        let filters = Filters::default();
+        let benches =
+            Benchmarks::from_slice(&es, &filters, "group", raw).unwrap();
+        let def = &benches.defs[0];
+        assert!(def.is_count_unresolved());
+        let err = def.count("rust/regex", "1.0").unwrap_err().to_string();
+        assert!(err.contains("benchmark 'group/foo' has no count"));
+    }
+
+    #[test]
+    fn count_derive_from() {
+        let es = Engines::from_list(engines(["rust/regex", "pcre2"]));
+        let filters = Filters::default();
+        let load = |model: &str, counts: &str| {
+            let raw = format!(
+                r#"
+[[bench]]
+model = "{model}"
+name = "foo"
+regex = ["a", "b"]
+haystack = "ab"
+{counts}
+engines = ["rust/regex", "pcre2"]
+"#
+            );
+            Benchmarks::from_slice(&es, &filters, "group", &raw)
+                .map_err(|err| format!("{:#}", err))
+        };
+        let count = r#"count = { derive-from = "rust/regex" }"#;
+        for model in ["count", "grep", "count-per-pattern"] {
+            let benches = load(model, count).unwrap();
+            let def = &benches.defs[0];
+            assert_eq!(Some("rust/regex"), def.count_derive_from.as_deref());
+            assert!(def.count.is_empty());
+            // The count isn't known until it's resolved at measure time.
+            let err = format!("{:#}", def.count("pcre2", "1.0").unwrap_err());
+            assert!(err.contains("hasn't been resolved"), "{err}");
+        }
+
+        let err = load("count", r#"count = { derive-from = "" }"#);
+        assert!(err.unwrap_err().contains("must name a regex engine"));
+        let err = load(
+            "count-per-pattern",
+            &format!(
+                "{count}
+counts-per-pattern = [1, 1]"
+            ),
+        );
+        assert!(err.unwrap_err().contains("cannot be used with"));
+        let err = load("count", r#"count = { derive-from = "a", x = 1 }"#);
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn matrix() {
+        let raw = r#"
+[[bench]]
+model = "count"
+name = "repeat-{repeat}-{word}"
+matrix = { repeat = [1, 4], word = ["foo", "bar"] }
+regex = '(?:{word}){1}'
+haystack = { contents = "{word} ", repeat = "{repeat}" }
+count = { matrix = { "repeat=1,word=foo" = 1, "repeat=1,word=bar" = 1, "repeat=4,word=foo" = 4, "repeat=4,word=bar" = 4 } }
+engines = ["rust/regex"]
+
+[[bench]]
+model = "count"
+name = "plain"
+regex = 'a'
+haystack = "a"
+count = 1
+engines = ["rust/regex"]
+"#;
+        let es = Engines::from_list(engines(["rust/regex"]));
+        let mut filters = Filters::default();
+        let benches =
+            Benchmarks::from_slice(&es, &filters, "group", raw).unwrap();
+        let got: Vec<(&str, &str, &[u8], u64)> = benches
+            .defs
+            .iter()
+            .map(|d| {
+                let count = d.count("rust/regex", "1.0").unwrap();
+                (d.name.local.as_str(), &*d.regexes[0], &*d.haystack, count)
+            })
+            .collect();
+        let expected: Vec<(&str, &str, &[u8], u64)> = vec![
+            ("repeat-1-foo", "(?:foo){1}", b"foo ", 1),
+            ("repeat-1-bar", "(?:bar){1}", b"bar ", 1),
+            ("repeat-4-foo", "(?:foo){1}", b"foo foo foo foo ", 4),
+            ("repeat-4-bar", "(?:bar){1}", b"bar bar bar bar ", 4),
+            ("plain", "a", b"a", 1),
+        ];
+        assert_eq!(expected, got);
+
+        // Filters apply to the expanded names.
+        filters.name.whitelist(r"repeat-4-").unwrap();
+        let benches =
+            Benchmarks::from_slice(&es, &filters, "group", raw).unwrap();
+        let names: Vec<&str> =
+            benches.defs.iter().map(|d| d.name.local.as_str()).collect();
+        assert_eq!(vec!["repeat-4-foo", "repeat-4-bar"], names);
+    }
+
+    #[test]
+    fn matrix_errors() {
+        let es = Engines::from_list(engines(["rust/regex"]));
+        let filters = Filters::default();
+        let load = |name: &str, matrix: &str, count: &str| {
+            let raw = format!(
+                r#"
+[[bench]]
+model = "count"
+name = "{name}"
+matrix = {matrix}
+regex = 'a'
+haystack = {{ contents = "a", repeat = "{{n}}" }}
+count = {count}
+engines = ["rust/regex"]
+"#
+            );
+            Benchmarks::from_slice(&es, &filters, "group", &raw)
+                .map_err(|err| format!("{:#}", err))
+        };
+        let err = load("foo", "{ n = [1, 2] }", "1").unwrap_err();
+        assert!(err.contains("same name 'group/foo'"), "{err}");
+        let err = load("foo-{n}", "{ n = [] }", "1").unwrap_err();
+        assert!(err.contains("non-empty array"), "{err}");
+        let err = load("foo-{n}", "{ n = [1.5] }", "1").unwrap_err();
+        assert!(err.contains("strings or integers"), "{err}");
+        let err = load("foo-{n}", "{ 1 = [1] }", "1").unwrap_err();
+        assert!(err.contains("does not match format"), "{err}");
+        let count = r#"{ matrix = { "n=1" = 1 } }"#;
+        let err = load("foo-{n}", "{ n = [1, 2] }", count).unwrap_err();
+        assert!(err.contains("no entry for 'n=2'"), "{err}");
+        let count = r#"{ matrix = { "n=1" = 1, "n=3" = 3 } }"#;
+        let err = load("foo-{n}", "{ n = [1] }", count).unwrap_err();
+        assert!(err.contains("entry for 'n=3'"), "{err}");
+        // Placeholders that aren't matrix keys are left alone, and so the
+        // name is rejected.
+        let err = load("foo-{m}", "{ n = [1] }", "1").unwrap_err();
+        assert!(err.contains("does not match format"), "{err}");
+    }
+
+    #[test]
+    fn verify_count_disabled() {
+        let raw = r#"
+[[bench]]
+model = "size"
+name = "foo"
+regex = "a"
+haystack = "a"
+verify-count = false
+engines = ["rust/regex"]
+"#;
+        let es = Engines::from_list(engines(["rust/regex"]));
+        let filters = Filters::default();
+        let benches =
+            Benchmarks::from_slice(&es, &filters, "group", raw).unwrap();
+        let def = &benches.defs[0];
+        assert!(!def.verify_count);
+        assert!(!def.is_count_unresolved());
+
+        let raw = raw.replace("verify-count", "count = 1\nverify-count");
+        let err =
+            Benchmarks::from_slice(&es, &filters, "group", &raw).unwrap_err();
+        assert!(format!("{:#}", err).contains("'verify-count = false'"));
+    }
+
+    #[test]
+    fn version_metadata() {
+        let config: VersionConfig = toml::from_str(
+            r#"
+bin = "echo"
+args = ["1.2.3"]
+[metadata]
+bin = "echo"
+args = ["  features=std cpu=avx2  "]
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.run.as_ref().unwrap().args, vec!["1.2.3"]);
+        assert_eq!(config.get().unwrap(), "1.2.3");
+        assert_eq!(
+            config.get_metadata().unwrap().as_deref(),
+            Some("features=std cpu=avx2"),
+        );
+
+        let config: VersionConfig =
+            toml::from_str("bin = \"echo\"\nargs = [\"1.2.3\"]").unwrap();
+        assert_eq!(config.get_metadata().unwrap(), None);
+    }
+
+    #[test]
+    fn engines_include() {
+        let engine = |name: &str, cwd: &str| {
+            format!(
+                r#"
+[[engine]]
+name = "{name}"
+cwd = "{cwd}"
+[engine.run]
+bin = "runner"
+[engine.version]
+bin = "runner"
+args = ["--version"]
+"#
+            )
+        };
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::create_dir(dir.join("engines.d")).unwrap();
+        std::fs::write(
+            dir.join("engines.toml"),
+            format!(
+                "include = [\"engines.d/*.toml\"]\n{}",
+                engine("main", "../engines/main"),
+            ),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("engines.d").join("b.toml"),
+            engine("b", "../engines/b"),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("engines.d").join("a.toml"),
+            engine("a", "../engines/a"),
+        )
+        .unwrap();
+        // Not matched by the glob, so it should be ignored.
+        std::fs::write(
+            dir.join("engines.d").join("c.txt"),
+            engine("c", "../engines/c"),
+        )
+        .unwrap();
+
+        let opts = LoadOptions::default();
+        let es = Engines::from_file(dir, &opts, |_| true).unwrap();
+        let names: Vec<&str> =
+            es.list.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["main", "a", "b"]);
+        assert!(es.by_name.contains_key("a"));
+        // Relative paths in included files are resolved relative to the
+        // benchmark directory, not the directory of the included file.
+        let a = &es.by_name["a"];
+        let want = dir.join("../engines/a").to_str().unwrap().to_string();
+        assert_eq!(a.cwd.as_deref(), Some(&*want));
+        assert_eq!(a.run.cwd.as_deref(), Some(&*want));
+
+        let es = Engines::from_file(dir, &opts, |e| e.name == "b").unwrap();
+        let names: Vec<&str> =
+            es.list.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["b"]);
+
+        // Duplicates are detected across files.
+        std::fs::write(
+            dir.join("engines.d").join("dupe.toml"),
+            engine("main", "../engines/other"),
+        )
+        .unwrap();
+        let err =
+            Engines::from_file(dir, &opts, |_| true).unwrap_err().to_string();
+        assert!(err.contains("found duplicate regex engine 'main'"), "{err}");
+        assert!(err.contains("dupe.toml"), "{err}");
+    }
+
+    #[test]
+    fn command_vars_expand() {
+        let vars = CommandVars {
+            vars: vec![
+                ("bench-dir", "/rebar/benchmarks".to_string()),
+                ("engine-dir", "/rebar/engines/foo".to_string()),
+            ],
+        };
+        assert_eq!(
+            vars.expand("{engine-dir}/lib:{bench-dir}").unwrap(),
+            "/rebar/engines/foo/lib:/rebar/benchmarks",
+        );
+        assert_eq!(vars.expand("no placeholders").unwrap(), "no placeholders");
+        assert_eq!(vars.expand("{{engine-dir}} }").unwrap(), "{engine-dir} }");
+
+        let err = vars.expand("{nope}/lib").unwrap_err().to_string();
+        assert!(err.contains("unknown placeholder '{nope}'"), "{err}");
+        assert!(err.contains("{bench-dir}, {engine-dir}"), "{err}");
+        let err = vars.expand("x{engine-dir").unwrap_err().to_string();
+        assert!(err.contains("unclosed placeholder"), "{err}");
+    }
+
+    #[test]
+    fn engines_command_vars() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::write(
+            dir.join("engines.toml"),
+            r#"
+[[engine]]
+name = "templated"
+cwd = "engine"
+[engine.run]
+bin = "runner"
+args = ["--bench-dir", "{bench-dir}", "{rebar-exe}"]
+envs = [{ name = "LD_LIBRARY_PATH", value = "{target-dir}/lib" }]
+[engine.version]
+bin = "echo"
+args = ["{engine-dir}"]
+"#,
+        )
+        .unwrap();
+        std::fs::create_dir(dir.join("engine")).unwrap();
+        let opts =
+            LoadOptions { version_cache: false, ..LoadOptions::default() };
+        let es = Engines::from_file(dir, &opts, |_| true).unwrap();
+        let e = &es.by_name["templated"];
+        let engine_dir = dir.join("engine");
+        let path = |p: &Path| p.to_str().unwrap().to_string();
+        assert_eq!(
+            e.run.args,
+            vec![
+                "--bench-dir".to_string(),
+                path(dir),
+                crate::util::current_exe().unwrap(),
+            ],
+        );
+        assert_eq!(e.run.envs[0].name, "LD_LIBRARY_PATH");
+        assert_eq!(
+            e.run.envs[0].value,
+            format!("{}/lib", path(&engine_dir.join("target"))),
+        );
+        // The version command is expanded before it's run.
+        assert_eq!(e.version, path(&engine_dir));
+
+        std::fs::write(
+            dir.join("engines.toml"),
+            r#"
+[[engine]]
+name = "typo"
+[engine.run]
+bin = "runner"
+envs = [{ name = "LIB", value = "{engine_dir}" }]
+[engine.version]
+bin = "echo"
+args = ["1.0.0"]
+"#,
+        )
+        .unwrap();
+        let err = Engines::from_file(dir, &opts, |_| true).unwrap_err();
+        let err = format!("{:#}", err);
+        assert!(err.contains("engine 'typo'"), "{err}");
+        assert!(err.contains("'run' command"), "{err}");
+        assert!(err.contains("environment variable 'LIB'"), "{err}");
+        assert!(err.contains("unknown placeholder '{engine_dir}'"), "{err}");
+    }
+
+    #[test]
+    fn engines_version_cache() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::write(
+            dir.join("engines.toml"),
+            r#"
+[[engine]]
+name = "slow"
+[engine.run]
+bin = "./runner"
+[engine.version]
+bin = "sh"
+args = ["version.sh"]
+"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("runner"), "").unwrap();
+        // A version command that is slow and records each time it runs.
+        std::fs::write(
+            dir.join("version.sh"),
+            "echo x >> calls\nsleep 0.1\necho 1.2.3\n",
+        )
+        .unwrap();
+        let calls = || {
+            std::fs::read_to_string(dir.join("calls"))
+                .map_or(0, |s| s.lines().count())
+        };
+        let load = |opts: &LoadOptions| {
+            let es = Engines::from_file(dir, opts, |_| true).unwrap();
+            es.list[0].version.clone()
+        };
+
+        let opts = LoadOptions::default();
+        assert_eq!("1.2.3", load(&opts));
+        assert_eq!(1, calls());
+        assert!(VersionCache::path(dir).exists());
+        // The second load uses the cached version.
+        assert_eq!("1.2.3", load(&opts));
+        assert_eq!(1, calls());
+
+        // Disabling the cache always runs the version command.
+        let disabled = LoadOptions { version_cache: false, ..opts.clone() };
+        assert_eq!("1.2.3", load(&disabled));
+        assert_eq!(2, calls());
+
+        // So does an expired entry.
+        let expired =
+            LoadOptions { version_cache_ttl: Duration::ZERO, ..opts.clone() };
+        assert_eq!("1.2.3", load(&expired));
+        assert_eq!(3, calls());
+
+        // And a change to the modification time of the runner program.
+        std::fs::File::options()
+            .write(true)
+            .open(dir.join("runner"))
+            .unwrap()
+            .set_modified(UNIX_EPOCH + Duration::from_secs(1_000))
+            .unwrap();
+        assert_eq!("1.2.3", load(&opts));
+        assert_eq!(4, calls());
+        assert_eq!("1.2.3", load(&opts));
+        assert_eq!(4, calls());
+
+        // A corrupt cache file is ignored.
+        std::fs::write(VersionCache::path(dir), "not json").unwrap();
+        assert_eq!("1.2.3", load(&opts));
+        assert_eq!(5, calls());
+    }
+
+    #[test]
+    fn expected_version() {
+        let mut es = Engines::fake(["exact", "regex", "none", "missing"]);
+        es.list[0].expected_version = Some("1.2.3".to_string());
+        es.list[0].version = "1.2.3".to_string();
+        es.list[1].expected_version = Some(r"1\.2\..*".to_string());
+        es.list[1].version = "1.2.9".to_string();
+        es.list[3].expected_version = Some("1.0.0".to_string());
+        es.list[3].version = "ERROR".to_string();
+        // Engines without an expected version or that are missing a version
+        // are never a mismatch.
+        es.check_locked().unwrap();
+
+        // The regex must match the entire version string.
+        assert!(es.list[1].check_version("11.2.9").is_err());
+        es.list[0].version = "1.2.4".to_string();
+        let err = es.check_locked().unwrap_err().to_string();
+        assert!(err.contains("engine 'exact' has version '1.2.4'"), "{err}");
+        assert!(err.contains("expected version is '1.2.3'"), "{err}");
+    }
+
+    #[test]
+    fn lint() {
+        let raw = r#"
+[[bench]]
+model = "count"
+name = "ok"
+regex = "a"
+haystack = "a"
+count = 1
+engines = ["rust/regex"]
+
+[[bench]]
+model = "counting"
+name = "bad"
+regex = "a"
+haystack = "a"
+count = [
+  { engine = "rust/.*", count = 1 },
+  { engine = "rust/.*", count = 2 },
+  { engine = "python/re", count = 1 },
+]
+engines = ["rust/regex", "rust/regexp"]
+requires = ["multi-patern"]
+"#;
+        let mut wire = WireDefinitions::new();
+        wire.load_slice("group", raw.as_bytes()).unwrap();
+        let engines = Engines::from_list(engines(["rust/regex"]));
+        let issues = wire.lint(Path::new("bench"), &engines);
+        let got: Vec<String> = issues.iter().map(|i| i.to_string()).collect();
+        let path = Path::new("bench").join("definitions").join("group.toml");
+        let want: Vec<String> = [
+            "error: unknown model 'counting'",
+            "error: unknown regex engine 'rust/regexp'",
+            "warning: required capability 'multi-patern' is not declared by \
+             any regex engine",
+            "error: duplicate count for engine 'rust/.*'",
+            "warning: count for engine 'python/re' does not match any \
+             regex engine",
+        ]
+        .iter()
+        .map(|msg| format!("{}: group/bad: {}", path.display(), msg))
+        .collect();
+        assert_eq!(want, got);
+    }
+
+    #[test]
+    fn requires() {
+        let raw = r#"
+[[bench]]
+model = "count"
+name = "test"
+regex = ["a", "b"]
+haystack = "ab"
+count = 2
+engines = ["one", "two"]
+requires = ["multi-pattern"]
+"#;
+        let mut es = engines(["one", "two"]);
+        es[0].capabilities = vec!["multi-pattern".to_string()];
+        let es = Engines::from_list(es);
+
+        let filters = Filters::default();
+        let b = Benchmarks::from_slice(&es, &filters, "group", raw).unwrap();
+        let got: Vec<&str> =
+            b.defs[0].engines.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(vec!["one"], got);
+        assert_eq!(vec!["multi-pattern".to_string()], b.defs[0].requires);
+
+        let filters = Filters { strict_capabilities: true, ..filters };
+        let b = Benchmarks::from_slice(&es, &filters, "group", raw).unwrap();
+        let got: Vec<&str> =
+            b.defs[0].engines.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(vec!["one", "two"], got);
+    }
+
+    #[test]
+    fn params() {
+        let raw = r#"
+[[bench]]
+model = "count"
+name = "test"
+regex = "a"
+haystack = "a"
+count = 1
+engines = ["one", "two"]
+params = { nfa-size-limit = "200M", hybrid-cache-capacity = 1024 }
+"#;
+        let es = Engines::from_list(engines(["one", "two"]));
+        let filters = Filters::default();
+        let b = Benchmarks::from_slice(&es, &filters, "group", raw).unwrap();
+        let def = &b.defs[0];
+        let expected = BTreeMap::from([
+            ("hybrid-cache-capacity".to_string(), "1024".to_string()),
+            ("nfa-size-limit".to_string(), "200M".to_string()),
+        ]);
+        assert_eq!(expected, def.params);
+
+        let raw = raw.replace("nfa-size-limit", "'nfa size limit'");
+        let err =
+            Benchmarks::from_slice(&es, &filters, "group", &raw).unwrap_err();
+        assert!(format!("{:#}", err).contains("'nfa size limit'"), "{}", err);
+    }
+
+    #[test]
+    fn engine_args() {
+        let raw = r#"
+[[bench]]
+model = "count"
+name = "test"
+regex = "a"
+haystack = "aa"
+count = [
+  { engine = "rust/regex/dfa1m", count = 2 },
+  { engine = ".*", count = 1 },
+]
+engines = [
+  "rust/regex",
+  { name = "rust/regex", label = "rust/regex/dfa1m", args = ["--dfa", "1M"] },
+  { name = "pcre2", args = ["--no-jit"] },
+]
+"#;
+        let es = Engines::from_list(engines(["rust/regex", "pcre2"]));
+        let filters = Filters::default();
+        let b = Benchmarks::from_slice(&es, &filters, "group", raw).unwrap();
+        let got: Vec<(&str, Vec<&str>)> = b.defs[0]
+            .engines
+            .iter()
+            .map(|e| {
+                let args = e.extra_args.iter().map(|a| a.as_str()).collect();
+                (e.name.as_str(), args)
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("rust/regex", vec![]),
+                ("rust/regex/dfa1m", vec!["--dfa", "1M"]),
+                ("pcre2", vec!["--no-jit"]),
+            ],
+            got,
+        );
+        assert_eq!(1, b.defs[0].count("rust/regex", "0.0.0").unwrap());
+        assert_eq!(2, b.defs[0].count("rust/regex/dfa1m", "0.0.0").unwrap());
+
+        // Engine filters apply to labels, but the engines that need to be
+        // loaded are the ones named in 'engines.toml'.
+        let filters = Filters {
+            engine: Filter::from_pattern("^rust/regex/dfa1m$").unwrap(),
+            ..Filters::default()
+        };
+        let b = Benchmarks::from_slice(&es, &filters, "group", raw).unwrap();
+        assert_eq!(1, b.defs[0].engines.len());
+        assert_eq!("rust/regex/dfa1m", b.defs[0].engines[0].name);
+        let mut wire = WireDefinitions::new();
+        wire.load_slice("group", raw.as_bytes()).unwrap();
+        let refs = wire.engine_references(&filters.engine);
+        assert_eq!(vec!["rust/regex"], refs.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn engine_args_errors() {
+        let load = |refs: &str| {
+            let raw = format!(
+                "[[bench]]\n\
+                 model = 'count'\n\
+                 name = 'test'\n\
+                 regex = 'a'\n\
+                 haystack = 'a'\n\
+                 count = 1\n\
+                 engines = {}\n",
+                refs,
+            );
+            let es = Engines::from_list(engines(["rust/regex"]));
+            let filters = Filters::default();
+            Benchmarks::from_slice(&es, &filters, "group", raw)
+                .map_err(|err| format!("{:#}", err))
+        };
+        assert!(load("[{ name = 'rust/regex', args = ['-x'] }]").is_ok());
+
+        let err = load("['rust/regex', { name = 'rust/regex' }]").unwrap_err();
+        assert!(err.contains("referenced more than once"), "{}", err);
+        let err = load(
+            "[{ name = 'rust/regex', label = 'a' }, \
+              { name = 'rust/regex', label = 'a' }]",
+        )
+        .unwrap_err();
+        assert!(err.contains("referenced more than once"), "{}", err);
+        let err =
+            load("[{ name = 'rust/regex', label = 'a(b)' }]").unwrap_err();
+        assert!(err.contains("engine label 'a(b)'"), "{}", err);
+        assert!(load("[{ name = 'rust/regex', argz = ['-x'] }]").is_err());
+
+        let raw = r#"
+[[bench]]
+model = "count"
+name = "test"
+regex = "a"
+haystack = "a"
+count = [{ engine = "fast", count = 1 }]
+engines = [
+  "rust/regex",
+  { name = "rust/regex", label = "pcre2" },
+  { name = "rust/regex", label = "fast" },
+]
+"#;
+        let mut wire = WireDefinitions::new();
+        wire.load_slice("group", raw.as_bytes()).unwrap();
+        let es = Engines::from_list(engines(["rust/regex", "pcre2"]));
+        let issues = wire.lint(Path::new("bench"), &es);
+        let got: Vec<String> =
+            issues.iter().map(|i| i.message.clone()).collect();
+        assert_eq!(
+            vec![
+                "label 'pcre2' for regex engine 'rust/regex' is also the \
+                 name of a regex engine",
+            ],
+            got,
+        );
+    }
+
+    #[test]
+    fn budget() {
+        let raw = r#"
+[[bench]]
+model = "count"
+name = "test"
+regex = "a"
+haystack = "a"
+count = 1
+engines = ["one"]
+max-time = "10s"
+max-warmup-time = "500ms"
+max-iters = 5
+"#;
+        let es = Engines::fake(["one"]);
+        let filters = Filters::default();
+        let b = Benchmarks::from_slice(&es, &filters, "group", raw).unwrap();
+        let expected = DefinitionBudget {
+            max_iters: Some(5),
+            max_warmup_iters: None,
+            max_time: Some(Duration::from_secs(10)),
+            max_warmup_time: Some(Duration::from_millis(500)),
+        };
+        assert_eq!(expected, b.defs[0].budget);
+
+        let raw = raw.replace("10s", "10 seconds");
+        let err =
+            Benchmarks::from_slice(&es, &filters, "group", &raw).unwrap_err();
+        assert!(format!("{:#}", err).contains("duration '10 seconds'"));
+    }
+
+    #[test]
+    fn definition_hash() {
+        let engines = Engines::fake(["one", "two"]);
+        let filters = Filters::default();
+        let hash = |raw: &str| {
+            let b = Benchmarks::from_slice(&engines, &filters, "group", raw)
+                .unwrap();
+            DefinitionHasher::default().hash(&b.defs[0])
+        };
+        let raw = r#"
+[[bench]]
+model = "count"
+name = "foo"
+regex = "a"
+haystack = "aaa"
+count = [{ engine = "one", count = 3 }, { engine = "two", count = 2 }]
+engines = ["one", "two"]
+"#;
+        // This pins the canonical serialization. If it changes, then every
+        // previously recorded measurement will be reported as stale.
+        assert_eq!("7093fbbb1a623f19", hash(raw));
+        assert_eq!(hash(raw), hash(raw));
+        // Things that don't influence measurements don't change the hash.
+        // Haystacks are hashed after they've been transformed.
+        let unchanged = [
+            format!("{}analysis = 'blah'\n", raw),
+            format!("{}max-time = '10s'\n", raw),
+            raw.replace(
+                r#"haystack = "aaa""#,
+                r#"haystack = { contents = "a", repeat = 3 }"#,
+            ),
+        ];
+        for (i, unchanged) in unchanged.iter().enumerate() {
+            assert_eq!(hash(raw), hash(unchanged), "unchanged {}", i);
+        }
+        let changed = [
+            raw.replace(r#"haystack = "aaa""#, r#"haystack = "aab""#),
+            raw.replace(r#"regex = "a""#, r#"regex = "b""#),
+            raw.replace(r#"model = "count""#, r#"model = "grep""#),
+            raw.replace("count = 2", "count = 1"),
+            raw.replace(r#"name = "foo""#, "name = 'foo'\nunicode = true"),
+            format!("{}params = {{ nfa-size-limit = '1K' }}\n", raw),
+        ];
+        for (i, changed) in changed.iter().enumerate() {
+            assert_ne!(hash(raw), hash(changed), "changed {}", i);
+        }
+    }
+
+    #[test]
+    fn download_haystack() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let cache = Cache::new(dir, false);
+        let load = |raw: String| -> anyhow::Result<Haystacks> {
+            let mut wire = WireDefinitions::new();
+            wire.load_slice("group", raw.as_bytes())?;
+            Haystacks::new(dir, &cache, &wire, false)
+        };
+        let def = |haystack: &str| {
+            format!(
+                r#"
+[[bench]]
+model = "count"
+name = "download"
+regex = "a"
+haystack = {haystack}
+count = 1
+engines = ["rust/regex"]
+"#
+            )
+        };
+        let sha = util::sha256_hex(b"abc");
+        let full = def(&format!(
+            "{{ url = 'https://example.com/a.txt', sha256 = '{sha}', \
+             path-cache = 'downloads/a.txt', repeat = 2 }}",
+        ));
+
+        // No network access is allowed, so a missing file is an error.
+        let err = load(full.clone()).unwrap_err();
+        assert!(format!("{:#}", err).contains("--allow-download"));
+
+        let downloads = dir.join("haystacks").join("downloads");
+        std::fs::create_dir_all(&downloads).unwrap();
+        std::fs::write(downloads.join("a.txt"), "abc").unwrap();
+        let hays = load(full.clone()).unwrap();
+        let haystacks: Vec<&[u8]> = hays.map.values().map(|h| &**h).collect();
+        assert_eq!(vec![&b"abcabc"[..]], haystacks);
+
+        std::fs::write(downloads.join("a.txt"), "abd").unwrap();
+        let err = load(full).unwrap_err();
+        assert!(format!("{:#}", err).contains("checksum mismatch"));
+
+        let bad = [
+            "{ url = 'x', path-cache = 'downloads/a.txt' }".to_string(),
+            format!("{{ url = 'x', sha256 = '{sha}' }}"),
+            "{ url = 'x', sha256 = 'abc', path-cache = 'downloads/a' }"
+                .to_string(),
+            format!("{{ url = 'x', sha256 = '{sha}', path-cache = 'a.txt' }}"),
+            format!(
+                "{{ url = 'x', sha256 = '{sha}', \
+                 path-cache = 'downloads/../a.txt' }}"
+            ),
+            format!(
+                "{{ url = 'x', sha256 = '{sha}', \
+                 path-cache = 'downloads/a.txt', path = 'a.txt' }}"
+            ),
+            format!("{{ path = 'a.txt', sha256 = '{sha}' }}"),
+        ];
+        for haystack in bad.iter() {
+            assert!(load(def(haystack)).is_err(), "{}", haystack);
+        }
+    }
+
+    #[test]
+    fn binary_regex() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let cache = Cache::new(dir, false);
+        std::fs::create_dir_all(dir.join("regexes")).unwrap();
+        std::fs::write(dir.join("regexes").join("xFF.txt"), b"a*\xFF+\n")
+            .unwrap();
+        let load = |regex: &str| -> anyhow::Result<_> {
+            let raw = format!(
+                r#"
+[[bench]]
+model = "count"
+name = "binary"
+regex = {regex}
+haystack = "a"
+count = 1
+engines = ["rust/regex"]
+"#
+            );
+            let mut wire = WireDefinitions::new();
+            wire.load_slice("group", raw.as_bytes()).unwrap();
+            let res = Regexes::new(dir, &cache, &wire)?;
+            let wdef = &wire.definitions[0];
+            let (regexes, binary) = wdef.regexes(&res)?;
+            Ok((regexes, binary, wdef.requires()))
+        };
+
+        let err = format!("{:#}", load("{ path = 'xFF.txt' }").unwrap_err());
+        assert!(err.contains("not valid UTF-8"), "{err}");
+        assert!(err.contains("binary = true"), "{err}");
+
+        let err = load("{ patterns = 'a', binary = true }").unwrap_err();
+        let err = format!("{:#}", err);
+        assert!(err.contains("only supported for patterns read"), "{err}");
+
+        let (regexes, binary, requires) =
+            load("{ path = 'xFF.txt', binary = true, literal = true }")
+                .unwrap();
+        // Only the valid UTF-8 parts of the pattern are escaped.
+        assert_eq!(&*regexes, &[r"a\*\xFF\+".to_string()]);
+        assert_eq!(&*binary.unwrap(), &[b"a\\*\xFF\\+".to_vec()]);
+        assert_eq!(requires, vec!["binary-pattern".to_string()]);
+
+        let (regexes, binary, requires) = load("'a'").unwrap();
+        assert_eq!(&*regexes, &["a".to_string()]);
+        assert!(binary.is_none());
+        assert!(requires.is_empty());
+    }
+
+    #[test]
+    fn compressed_haystack() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let cache = Cache::new(dir, false);
+        let load = |path: &str| -> anyhow::Result<Haystacks> {
+            let raw = format!(
+                r#"
+[[bench]]
+model = "count"
+name = "compressed"
+regex = "a"
+haystack = {{ path = '{path}', line-start = 1 }}
+count = 1
+engines = ["rust/regex"]
+"#
+            );
+            let mut wire = WireDefinitions::new();
+            wire.load_slice("group", raw.as_bytes()).unwrap();
+            Haystacks::new(dir, &cache, &wire, false)
+        };
+        let hays = dir.join("haystacks");
+        std::fs::create_dir_all(&hays).unwrap();
+        let fixtures = [
+            ("hay.txt.gz", &include_bytes!("testdata/hay.txt.gz")[..]),
+            ("hay.txt.xz", &include_bytes!("testdata/hay.txt.xz")[..]),
+            ("corrupt.txt.gz", &include_bytes!("testdata/corrupt.txt.gz")[..]),
+            ("corrupt.txt.xz", &include_bytes!("testdata/corrupt.txt.xz")[..]),
+        ];
+        for (name, data) in fixtures {
+            std::fs::write(hays.join(name), data).unwrap();
+        }
+
+        // The transform options apply to the decompressed haystack.
+        for path in ["hay.txt.gz", "hay.txt.xz"] {
+            let hays = load(path).unwrap();
+            let haystacks: Vec<&[u8]> =
+                hays.map.values().map(|h| &**h).collect();
+            assert_eq!(vec![&b"watson\n"[..]], haystacks, "{path}");
+        }
+
+        for path in ["corrupt.txt.gz", "corrupt.txt.xz"] {
+            let err = format!("{:#}", load(path).unwrap_err());
+            assert!(err.contains("benchmark 'group/compressed'"), "{err}");
+            assert!(err.contains("failed to decompress haystack"), "{err}");
+            assert!(err.contains(path), "{err}");
+        }
+    }
+
+    #[test]
+    fn regex_key_encoding() {
+        let patterns = vec![
+            b"a".to_vec(),
+            b"".to_vec(),
+            b"multi\nline\r\n".to_vec(),
+            b"12\n34".to_vec(),
+            b"\xFF".to_vec(),
+        ];
+        let encoded = RegexKey::encode(&patterns);
+        assert_eq!(RegexKey::decode(&encoded).unwrap(), patterns);
+        assert_eq!(RegexKey::decode(b"").unwrap(), Vec::<Vec<u8>>::new());
+
+        assert!(RegexKey::decode(b"5\nabc").is_err());
+        assert!(RegexKey::decode(b"x\nabc").is_err());
+        assert!(RegexKey::decode(b"3").is_err());
+    }
+
+    #[test]
+    fn cached_haystacks_and_regexes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::create_dir_all(dir.join("haystacks")).unwrap();
+        std::fs::create_dir_all(dir.join("regexes")).unwrap();
+        std::fs::write(dir.join("haystacks").join("hay.txt"), "abc").unwrap();
+        std::fs::write(dir.join("regexes").join("re.txt"), "a\nb\n").unwrap();
+        let raw = r#"
+[[bench]]
+model = "count"
+name = "plain"
+regex = { path = 're.txt', per-line = 'pattern' }
+haystack = { path = 'hay.txt' }
+count = 1
+engines = ["rust/regex"]
+
+[[bench]]
+model = "count"
+name = "repeated"
+regex = { path = 're.txt', per-line = 'alternate' }
+haystack = { path = 'hay.txt', repeat = 2 }
+count = 1
+engines = ["rust/regex"]
+"#;
+        let mut wire = WireDefinitions::new();
+        wire.load_slice("group", raw.as_bytes()).unwrap();
+        let load = || {
+            let cache = Cache::new(dir, true);
+            let res = Regexes::new(dir, &cache, &wire).unwrap();
+            let hays = Haystacks::new(dir, &cache, &wire, false).unwrap();
+            let res: Vec<Vec<Vec<u8>>> =
+                res.map.values().map(|r| r.to_vec()).collect();
+            let hays: Vec<Vec<u8>> =
+                hays.map.values().map(|h| h.to_vec()).collect();
+            (res, hays)
+        };
+        let (res, hays) = load();
+        // Each distinct set of options gets its own key, and thus its own
+        // cache entry, even when they share a source file.
+        assert_eq!(res.len(), 2);
+        assert!(res.contains(&vec![b"a".to_vec(), b"b".to_vec()]));
+        assert!(res.contains(&vec![b"(?:a)|(?:b)".to_vec()]));
+        assert_eq!(hays.len(), 2);
+        assert!(hays.contains(&b"abc".to_vec()));
+        assert!(hays.contains(&b"abcabc".to_vec()));
+        let count = |kind: &str| {
+            std::fs::read_dir(Cache::dir(dir).join(kind)).unwrap().count()
+        };
+        assert_eq!(count("regex"), 2);
+        assert_eq!(count("haystack"), 2);
+
+        // Loading again reads from the cache and gives the same results.
+        assert_eq!(load(), (res, hays));
+        assert_eq!(count("regex"), 2);
+        assert_eq!(count("haystack"), 2);
+    }
+
+    #[test]
+    fn generated_haystack() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let hays = dir.join("haystacks");
+        std::fs::create_dir_all(&hays).unwrap();
+        std::fs::write(hays.join("unit.txt"), "ab\n").unwrap();
+
+        let load = |haystack: &str| -> anyhow::Result<Definition> {
+            let raw = format!(
+                r#"
+[[bench]]
+model = "count"
+name = "generated"
+regex = "a"
+haystack = {haystack}
+count = 1
+engines = ["rust/regex"]
+"#
+            );
+            let mut wire = WireDefinitions::new();
+            wire.load_slice("group", raw.as_bytes())?;
+            let cache = Cache::new(dir, false);
+            let res = Regexes::new(dir, &cache, &wire)?;
+            let hays = Haystacks::new(dir, &cache, &wire, false)?;
+            let engines = Engines::from_list(engines(["rust/regex"]));
+            wire.definitions[0].to_definition(
+                &Filters::default(),
+                &engines,
+                &res,
+                &hays,
+            )
+        };
+        let haystack = |haystack: &str| -> String {
+            let def = load(haystack).unwrap();
+            String::from_utf8(def.haystack.to_vec()).unwrap()
+        };
+
+        assert_eq!(
+            "xxxxy",
+            haystack(
+                "{ generate = { kind = 'repeat-then-fail', unit = 'x', \
+                   count = 4, suffix = 'y' } }"
+            ),
+        );
+        // The suffix is optional and the count may be zero.
+        assert_eq!(
+            "",
+            haystack(
+                "{ generate = { kind = 'repeat-then-fail', unit = 'x', \
+                   count = 0 } }"
+            ),
+        );
+        // The usual options are applied after generation.
+        assert_eq!(
+            "<xyxy>",
+            haystack(
+                "{ generate = { kind = 'repeat-then-fail', unit = 'x', \
+                   count = 1, suffix = 'y' }, repeat = 2, prepend = '<', \
+                   append = '>' }"
+            ),
+        );
+        let def = load(
+            "{ generate = { kind = 'repeat-then-fail', unit-file = \
+               'unit.txt', count = 2, suffix = '!' }, trim = true }",
+        )
+        .unwrap();
+        assert_eq!(&b"ab\nab\n!"[..], &*def.haystack);
+        assert_eq!(Some("unit.txt"), def.haystack_path.as_deref());
+
+        let errors = [
+            (
+                "{ generate = { kind = 'repeat-then-fail', count = 1 } }",
+                "exactly one of 'unit' and 'unit-file'",
+            ),
+            (
+                "{ generate = { kind = 'repeat-then-fail', unit = 'x', \
+                   unit-file = 'unit.txt', count = 1 } }",
+                "exactly one of 'unit' and 'unit-file'",
+            ),
+            (
+                "{ generate = { kind = 'repeat-then-fail', unit = 'x', \
+                   count = 1 }, contents = 'x' }",
+                "both 'generate' and 'contents'",
+            ),
+            (
+                "{ generate = { kind = 'repeat-then-fail', unit = 'x', \
+                   count = 1 }, path = 'unit.txt' }",
+                "both 'generate' and 'path'",
+            ),
+            (
+                "{ generate = { kind = 'repeat-then-fail', unit-file = \
+                   'nope.txt', count = 1 } }",
+                "failed to read haystack unit",
+            ),
+            (
+                "{ generate = { kind = 'repeat-then-fail', unit = 'xxx', \
+                   count = 9223372036854775807 } }",
+                "too big",
+            ),
+        ];
+        for (haystack, want) in errors {
+            let err = format!("{:#}", load(haystack).unwrap_err());
+            assert!(err.contains(want), "{haystack}: {err}");
+        }
+        assert!(load(
+            "{ generate = { kind = 'repeat-then-succeed', unit = 'x', \
+               count = 1 } }"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn group_name_separators() {
+        let group = |p: &str| group_name(Path::new(p)).unwrap();
+        assert_eq!("curated/01-literal", group("curated/01-literal.toml"));
+        // Windows separators are normalized, even when constructed by hand
+        // on a platform that doesn't treat them as separators.
+        assert_eq!("curated/01-literal", group("curated\\01-literal.toml"));
+        assert_eq!("a/b/c", group("a\\b/c.toml"));
+    }
+
+    #[test]
+    fn load_file_group() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("definitions");
+        let path = dir.join("curated").join("literal.toml");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(
+            &path,
+            "[[bench]]\nmodel = 'count'\nname = 'foo'\nregex = 'a'\n\
+             haystack = 'a'\ncount = 1\nengines = ['rust/regex']\n",
+        )
+        .unwrap();
+        let mut wire = WireDefinitions::new();
+        wire.load_file(&dir, &path).unwrap();
+        assert_eq!("curated/literal", wire.definitions[0].group);
+        assert_eq!("curated/literal/foo", wire.definitions[0].name);
+    }
+
+    #[test]
+    fn relative_paths() {
+        let norm = |p: &str| normalize_relative_path(p).unwrap();
+        assert_eq!("a.txt", norm("a.txt"));
+        assert_eq!("a/b.txt", norm("a/b.txt"));
+        assert_eq!("a/b.txt", norm("a\\b.txt"));
+        assert_eq!("a/b.txt", norm(".\\a\\\\b.txt"));
+        assert_eq!("a/b.txt", norm("./a//b.txt/"));
+        assert_eq!("a/..b/c..", norm("a/..b/c.."));
+
+        let err =
+            |p: &str| format!("{:#}", normalize_relative_path(p).unwrap_err());
+        assert!(err("../a.txt").contains("'..'"));
+        assert!(err("a\\..\\..\\b.txt").contains("'..'"));
+        assert!(err("/a.txt").contains("absolute"));
+        assert!(err("\\a.txt").contains("absolute"));
+        assert!(err("\\\\server\\share\\a.txt").contains("absolute"));
+        assert!(err("C:\\a.txt").contains("drive prefix"));
+        assert!(err("C:a.txt").contains("drive prefix"));
+        assert!(err("").contains("empty"));
+        assert!(err(".\\").contains("empty"));
+
+        let dir = Path::new("bench").join("haystacks");
+        assert_eq!(
+            dir.join("a").join("b.txt"),
+            join_relative(&dir, &norm("a\\b.txt")),
+        );
+    }
+
+    #[test]
+    fn windows_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let sub = |kind: &str| {
+            let sub = dir.join(kind).join("sub");
+            std::fs::create_dir_all(&sub).unwrap();
+            sub
+        };
+        std::fs::write(sub("regexes").join("re.txt"), "b+\n").unwrap();
+        std::fs::write(sub("haystacks").join("hay.txt"), "abbc\n").unwrap();
+        std::fs::write(sub("haystacks").join("unit.txt"), "ab").unwrap();
+
+        let load = |regex: &str,
+                    haystack: &str|
+         -> anyhow::Result<(
+            WireDefinitions,
+            Regexes,
+            Haystacks,
+        )> {
+            let raw = format!(
+                r#"
+[[bench]]
+model = "count"
+name = "slash"
+regex = {{ path = "sub/re.txt" }}
+haystack = {{ path = "sub/hay.txt" }}
+count = 1
+engines = ["rust/regex"]
+
+[[bench]]
+model = "count"
+name = "other"
+regex = {regex}
+haystack = {haystack}
+count = 1
+engines = ["rust/regex"]
+"#
+            );
+            let mut wire = WireDefinitions::new();
+            wire.load_slice("group", raw.as_bytes())?;
+            let cache = Cache::new(dir, false);
+            let res = Regexes::new(dir, &cache, &wire)?;
+            let hays = Haystacks::new(dir, &cache, &wire, false)?;
+            Ok((wire, res, hays))
+        };
+
+        // Backslashes refer to the same files as slashes, so they share an
+        // entry in each map, and the paths shown in reports use slashes.
+        let (wire, res, hays) =
+            load("{ path = 'sub\\re.txt' }", "{ path = '.\\sub\\hay.txt' }")
+                .unwrap();
+        assert_eq!(1, res.map.len());
+        assert_eq!(1, hays.map.len());
+        let engines = Engines::from_list(engines(["rust/regex"]));
+        for wdef in wire.definitions.iter() {
+            let def = wdef
+                .to_definition(&Filters::default(), &engines, &res, &hays)
+                .unwrap();
+            assert_eq!(Some("sub/re.txt"), def.regex_path.as_deref());
+            assert_eq!(Some("sub/hay.txt"), def.haystack_path.as_deref());
+            assert_eq!(&["b+".to_string()][..], &*def.regexes);
+            assert_eq!(&b"abbc\n"[..], &*def.haystack);
+        }
+
+        // Unit files for generated haystacks are normalized too.
+        let (wire, _, hays) = load(
+            "'b'",
+            "{ generate = { kind = 'repeat-then-fail', \
+               unit-file = 'sub\\unit.txt', count = 2 } }",
+        )
+        .unwrap();
+        assert_eq!(
+            Some("sub/unit.txt".to_string()),
+            wire.definitions[1].haystack_path()
+        );
+        let generated: Vec<&[u8]> = hays.map.values().map(|h| &**h).collect();
+        assert!(generated.contains(&&b"abab"[..]));
+
+        // Paths that could escape the benchmark directory are rejected
+        // before anything is read.
+        let bad = [
+            ("{ path = '..\\re.txt' }", "'a'"),
+            ("{ path = 'C:\\re.txt' }", "'a'"),
+            ("'a'", "{ path = 'sub\\..\\..\\hay.txt' }"),
+            ("'a'", "{ path = '/etc/passwd' }"),
+            (
+                "'a'",
+                "{ generate = { kind = 'repeat-then-fail', \
+                   unit-file = '..\\unit.txt', count = 1 } }",
+            ),
+        ];
+        for (regex, haystack) in bad {
+            let err = match load(regex, haystack) {
+                Ok(_) => panic!("expected error for {regex} and {haystack}"),
+                Err(err) => format!("{:#}", err),
+            };
+            assert!(err.contains("benchmark 'group/other'"), "{err}");
+        }
+    }
 }
@@ -0,0 +1,342 @@
+use std::{
+    hash::{Hash, Hasher},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+/// The name of the directory, relative to the benchmark directory, in which
+/// cache entries are stored.
+const DIR_NAME: &str = ".rebar-cache";
+
+/// The first line of every cache entry. If the format of cache entries ever
+/// changes, then this should be bumped so that old entries are ignored.
+const MAGIC: &str = "rebar-cache-v2";
+
+/// An on-disk cache of transformed haystacks and regexes.
+///
+/// Loading benchmark definitions requires reading every haystack and regex
+/// file referenced by a definition and then applying the transformations
+/// requested by that definition. Some haystacks are quite big and some
+/// transformations (like 'repeat') make them even bigger, which makes
+/// otherwise interactive commands like 'rebar report' feel sluggish. So this
+/// cache stores the final transformed bytes on disk, and on subsequent loads,
+/// we read them back directly when the source file hasn't changed.
+///
+/// Each entry is keyed by the kind of thing being cached ('haystack' or
+/// 'regex'), the path to the source file and a description of how it was
+/// transformed (i.e., the `HaystackKey` or `RegexKey`). The entry's file name
+/// is derived from this key alone, so there is at most one entry for each
+/// haystack or regex. This keeps the cache from growing every time a source
+/// file changes. Entries for haystacks and regexes that are no longer used by
+/// any benchmark definition are never removed automatically, but they can be
+/// removed with 'rebar clean --cache'.
+///
+/// Each entry also records a fingerprint of the contents of its source file.
+/// An entry is only used when the fingerprint of the source file still
+/// matches. Otherwise, it's overwritten with a fresh one. This means the
+/// source file is still read on every load, but the (sometimes expensive)
+/// transformations, like decompression and 'repeat', are not re-applied.
+/// We use a hash of the contents instead of something cheaper like the
+/// file's size and modification time, since the latter are often preserved
+/// when a file is rewritten (e.g., by 'git checkout' or 'cp -p').
+///
+/// The full key and fingerprint are written at the start of each entry and
+/// checked on every read, so a hash collision in the entry's file name can
+/// never result in the wrong data being used.
+///
+/// Failures to read or write the cache are never fatal. When something goes
+/// wrong, we log it and fall back to doing things the slow way.
+#[derive(Clone, Debug)]
+pub struct Cache {
+    /// The directory containing cache entries, or `None` when caching is
+    /// disabled.
+    dir: Option<PathBuf>,
+}
+
+impl Cache {
+    /// Create a new cache for the given benchmark directory. When `enabled`
+    /// is false, the cache returned never reads or writes anything.
+    pub fn new(bench_dir: &Path, enabled: bool) -> Cache {
+        Cache { dir: if enabled { Some(Cache::dir(bench_dir)) } else { None } }
+    }
+
+    /// Returns the directory in which cache entries are stored for the given
+    /// benchmark directory.
+    pub fn dir(bench_dir: &Path) -> PathBuf {
+        bench_dir.join(DIR_NAME)
+    }
+
+    /// Look for an entry corresponding to the given kind, key and source
+    /// file. If one exists and it's up to date, then its contents are
+    /// returned. Otherwise, `create` is called to produce the contents, which
+    /// are then stored in the cache and returned.
+    ///
+    /// The key should be a description of all of the transformations that
+    /// `create` applies to the contents of `source`.
+    pub fn get_or_create(
+        &self,
+        kind: &str,
+        key: &str,
+        source: &Path,
+        create: impl FnOnce() -> anyhow::Result<Vec<u8>>,
+    ) -> anyhow::Result<Vec<u8>> {
+        let dir = match self.dir {
+            None => return create(),
+            Some(ref dir) => dir,
+        };
+        let full_key = format!("{:?} {:?} {:?}", kind, source, key);
+        let path = dir.join(kind).join(entry_name(&full_key));
+        let header = match fingerprint(source) {
+            Ok(fp) => format!("{}\n{}\n{}\n", MAGIC, full_key, fp),
+            Err(err) => {
+                // If we can't even read the source, then we let 'create'
+                // report a (presumably) better error.
+                log::debug!(
+                    "failed to fingerprint {}, skipping cache: {:#}",
+                    source.display(),
+                    err,
+                );
+                return create();
+            }
+        };
+        match read_entry(&path, &header) {
+            Ok(Some(data)) => {
+                log::trace!("cache hit for {}", source.display());
+                return Ok(data);
+            }
+            Ok(None) => {
+                log::trace!("cache miss for {}", source.display());
+            }
+            Err(err) => {
+                log::debug!(
+                    "failed to read cache entry {}: {:#}",
+                    path.display(),
+                    err,
+                );
+            }
+        }
+        let data = create()?;
+        if let Err(err) = write_entry(&path, &header, &data) {
+            log::warn!(
+                "failed to write cache entry {}: {:#}",
+                path.display(),
+                err,
+            );
+        }
+        Ok(data)
+    }
+}
+
+/// Returns a fingerprint of the contents of the given file.
+///
+/// Like entry names, this doesn't need to be stable across different builds
+/// of rebar. If it changes, then the worst that happens is that every entry
+/// is a cache miss once.
+fn fingerprint(path: &Path) -> anyhow::Result<String> {
+    let data = std::fs::read(path)?;
+    Ok(format!("{}:{}", data.len(), hash(&data)))
+}
+
+/// Returns a file name for the cache entry with the given key.
+///
+/// The name doesn't need to be stable across different builds of rebar. If
+/// it changes, the worst that happens is that we have some cache misses and
+/// a few stale entries that can be removed with 'rebar clean --cache'.
+fn entry_name(full_key: &str) -> String {
+    hash(full_key.as_bytes())
+}
+
+/// Returns a hex encoded hash of the given bytes.
+fn hash(data: &[u8]) -> String {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    data.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Reads the cache entry at the given path. If it doesn't exist or if its
+/// header doesn't match the one given, then `None` is returned.
+fn read_entry(path: &Path, header: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    let mut data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(None)
+        }
+        Err(err) => return Err(err.into()),
+    };
+    if !data.starts_with(header.as_bytes()) {
+        return Ok(None);
+    }
+    data.drain(..header.len());
+    Ok(Some(data))
+}
+
+/// Writes a cache entry to the given path, replacing any entry already there.
+///
+/// This writes to a temporary file first and then renames it into place. This
+/// way, concurrent invocations of rebar never see a partially written entry.
+fn write_entry(path: &Path, header: &str, data: &[u8]) -> anyhow::Result<()> {
+    let parent = path.parent().expect("cache entry has a parent directory");
+    std::fs::create_dir_all(parent)
+        .with_context(|| format!("failed to create {}", parent.display()))?;
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    let mut file = std::fs::File::create(&tmp)
+        .with_context(|| format!("failed to create {}", tmp.display()))?;
+    file.write_all(header.as_bytes())?;
+    file.write_all(data)?;
+    drop(file);
+    std::fs::rename(&tmp, path).with_context(|| {
+        format!("failed to rename {} to {}", tmp.display(), path.display())
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    /// Returns the names of all entries of the given kind in the cache.
+    fn entries(bench_dir: &Path, kind: &str) -> Vec<PathBuf> {
+        let dir = Cache::dir(bench_dir).join(kind);
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn hit_miss_invalidate() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let source = dir.join("source.txt");
+        std::fs::write(&source, "abc").unwrap();
+
+        let cache = Cache::new(dir, true);
+        let created = Cell::new(0);
+        let get = |key: &str| {
+            cache
+                .get_or_create("haystack", key, &source, || {
+                    created.set(created.get() + 1);
+                    let raw = std::fs::read(&source)?;
+                    Ok(raw.to_ascii_uppercase())
+                })
+                .unwrap()
+        };
+
+        assert_eq!(get("k"), b"ABC");
+        assert_eq!(created.get(), 1);
+        // The second time is a hit and 'create' isn't called.
+        assert_eq!(get("k"), b"ABC");
+        assert_eq!(created.get(), 1);
+        // A different key is a miss, even with the same source.
+        assert_eq!(get("other"), b"ABC");
+        assert_eq!(created.get(), 2);
+        assert_eq!(entries(dir, "haystack").len(), 2);
+
+        // Rewriting the source with the same size and modification time
+        // still invalidates the entry, since the fingerprint is computed from
+        // the contents of the source.
+        let mtime = std::fs::metadata(&source).unwrap().modified().unwrap();
+        std::fs::write(&source, "xyz").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        assert_eq!(get("k"), b"XYZ");
+        assert_eq!(created.get(), 3);
+        assert_eq!(get("k"), b"XYZ");
+        assert_eq!(created.get(), 3);
+        // The stale entry is replaced instead of a new one being added.
+        assert_eq!(entries(dir, "haystack").len(), 2);
+    }
+
+    #[test]
+    fn corrupt_entries() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let source = dir.join("source.txt");
+        std::fs::write(&source, "abc").unwrap();
+
+        let cache = Cache::new(dir, true);
+        let created = Cell::new(0);
+        let get = || {
+            cache
+                .get_or_create("regex", "k", &source, || {
+                    created.set(created.get() + 1);
+                    Ok(b"fresh".to_vec())
+                })
+                .unwrap()
+        };
+        assert_eq!(get(), b"fresh");
+        let entry = entries(dir, "regex").pop().unwrap();
+
+        let bogus: &[&[u8]] = &[
+            // Truncated in the middle of the header.
+            b"rebar-cache-v2\n",
+            // Not even close to an entry.
+            b"\xFF\x00garbage",
+            // Empty.
+            b"",
+        ];
+        for (i, data) in bogus.iter().enumerate() {
+            std::fs::write(&entry, data).unwrap();
+            assert_eq!(get(), b"fresh");
+            assert_eq!(created.get(), i + 2);
+        }
+
+        // An entry written by a different version of the cache format is
+        // ignored, even if everything after the magic line matches.
+        let data = std::fs::read(&entry).unwrap();
+        let foreign = [
+            &b"rebar-cache-v1"[..],
+            &data[MAGIC.len()..data.len() - b"fresh".len()],
+            b"stale",
+        ]
+        .concat();
+        std::fs::write(&entry, foreign).unwrap();
+        assert_eq!(get(), b"fresh");
+        assert_eq!(created.get(), bogus.len() + 2);
+        // And it's replaced by a valid entry.
+        assert_eq!(std::fs::read(&entry).unwrap(), data);
+    }
+
+    #[test]
+    fn disabled() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let source = dir.join("source.txt");
+        std::fs::write(&source, "abc").unwrap();
+
+        let cache = Cache::new(dir, false);
+        for _ in 0..2 {
+            let got = cache
+                .get_or_create("haystack", "k", &source, || Ok(b"x".to_vec()))
+                .unwrap();
+            assert_eq!(got, b"x");
+        }
+        assert!(!Cache::dir(dir).exists());
+    }
+
+    #[test]
+    fn missing_source() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let cache = Cache::new(dir, true);
+        // When the source can't be read, 'create' is responsible for
+        // reporting the error.
+        let err = cache
+            .get_or_create("haystack", "k", &dir.join("nope"), || {
+                anyhow::bail!("no such haystack")
+            })
+            .unwrap_err();
+        assert_eq!(err.to_string(), "no such haystack");
+        assert!(!Cache::dir(dir).exists());
+    }
+}
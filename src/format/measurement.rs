@@ -1,6 +1,6 @@
 use std::{
     collections::{BTreeMap, BTreeSet},
-    path::PathBuf,
+    path::{Path, PathBuf},
     time::Duration,
 };
 
@@ -8,7 +8,8 @@ use anyhow::Context;
 
 use crate::{
     args::{Filters, Stat, Usage},
-    util::{ShortHumanDuration, Throughput},
+    format::benchmarks::{Benchmarks, LoadOptions, VersionReq},
+    util::{self, ShortHumanDuration, Throughput},
 };
 
 /// A simple loader for reading and deserializing measurements, with filter
@@ -26,6 +27,19 @@ pub struct MeasurementReader<'a> {
     /// Whether to only retain measurements for which there are measurements
     /// for every regex engine.
     pub intersection: bool,
+    /// Labels corresponding to each of the paths above. When non-empty, the
+    /// regex engine name of every measurement read from a path is suffixed
+    /// with its label, e.g., 'rust/regex(before)'. This permits comparing
+    /// the same regex engine across multiple files, since measurements from
+    /// different files are then never duplicates of each other.
+    ///
+    /// Filters are applied to the regex engine names before they're labeled.
+    pub labels: &'a [String],
+    /// Whether any two measurements with the same benchmark name and regex
+    /// engine are an error. When disabled, duplicates that are identical in
+    /// every other field are silently dropped, and only duplicates that
+    /// conflict are an error.
+    pub strict_duplicates: bool,
 }
 
 impl<'p> MeasurementReader<'p> {
@@ -44,48 +58,136 @@ two regex engines. That is, only benchmarks containing measurements for both
 "#,
     );
 
-    /// Attempts to load measurements from the given loader configuration. If
-    /// there was a problem reading the files or if there are any duplicate
-    /// measurements.
-    pub fn read(self) -> anyhow::Result<Vec<Measurement>> {
+    pub const USAGE_STRICT_DUPLICATES: Usage = Usage::new(
+        "--strict-duplicates",
+        "Error on any duplicate measurement, even identical ones.",
+        r#"
+When this flag is set, it is an error for the measurements given to contain
+more than one measurement with the same benchmark name and regex engine.
+
+By default, duplicates that are identical in every field (as happens when
+overlapping CSV files are given, e.g., a file with every regex engine along
+with a file for just one of them) are silently dropped, and a count of the
+dropped rows is logged at the info level. Duplicates that differ in any field
+are always an error, and the error message includes the files that each was
+read from along with the fields that differ.
+"#,
+    );
+
+    pub const USAGE_SHOW_ERRORS: Usage = Usage::new(
+        "--show-errors",
+        "Print a table of every measurement that failed.",
+        r#"
+When this flag is set, a table listing the benchmark name, regex engine and
+error message of every measurement that failed is added after the usual
+output. Only errors that pass the filters given are included. This also
+includes benchmarks that a regex engine reported it can't run, whose error
+messages start with 'skipped:'.
+
+Without this flag, errors are only reported as warnings (which are hidden
+when RUST_LOG=error is set).
+"#,
+    );
+
+    /// Attempts to load measurements from the given loader configuration,
+    /// along with the distinct versions of rebar that recorded them. This
+    /// returns an error if there was a problem reading the files or if there
+    /// are any conflicting duplicate measurements. (See
+    /// `strict_duplicates`.)
+    ///
+    /// Measurements with an error are logged (see `warn_errors`) and dropped.
+    pub fn read_with_versions(
+        self,
+    ) -> anyhow::Result<(Vec<Measurement>, RebarVersions)> {
+        let (measurements, errors, versions) = self.read_with_errors()?;
+        warn_errors(&errors);
+        Ok((measurements, versions))
+    }
+
+    /// Like `read_with_versions`, but the measurements with an error are
+    /// returned separately instead of being dropped. This includes the
+    /// measurements of benchmarks that a regex engine reported it can't run.
+    /// (See `Measurement::skipped`.) Nothing is logged about them, so callers
+    /// should usually pass them to `warn_errors`.
+    ///
+    /// The errors returned have had the filters applied, but not
+    /// `intersection`. They are never considered duplicates of each other or
+    /// of successful measurements.
+    pub fn read_with_errors(
+        self,
+    ) -> anyhow::Result<(Vec<Measurement>, Vec<Measurement>, RebarVersions)>
+    {
         let mut measurements = vec![];
+        let mut errors = vec![];
+        let mut versions = RebarVersions::default();
         // A map from benchmark full name to the set of regex engines
         // for which we have measurements. We use this to detect duplicate
         // measurements, and it's also how we implement the 'intersection'
         // filtering.
         let mut name_to_engines: BTreeMap<String, BTreeSet<String>> =
             BTreeMap::new();
-        for path in self.paths.iter() {
+        // A map from benchmark full name and regex engine to the index of
+        // its measurement and the path it was read from. This lets us compare
+        // duplicates with the measurement we already have.
+        let mut seen: BTreeMap<(String, String), (usize, &Path)> =
+            BTreeMap::new();
+        let mut deduped = 0;
+        for (i, path) in self.paths.iter().enumerate() {
+            let label = self.labels.get(i);
             let mut rdr = csv::Reader::from_path(path)
                 .with_context(|| path.display().to_string())?;
             for result in rdr.deserialize() {
-                let m: Measurement = result?;
-                if let Some(ref err) = m.err {
-                    log::warn!(
-                        "{}:{}: skipping because of error: {}",
+                let mut m: Measurement = result?;
+                if !self.filters.include(&m) {
+                    continue;
+                }
+                if let Some(label) = label {
+                    m.engine = format!("{}({})", m.engine, label);
+                }
+                if m.err.is_some() {
+                    errors.push(m);
+                    continue;
+                }
+                let key = (m.name.clone(), m.engine.clone());
+                if let Some(&(j, first_path)) = seen.get(&key) {
+                    anyhow::ensure!(
+                        !self.strict_duplicates,
+                        "duplicate measurement with name '{}' \
+                         and regex engine '{}'",
                         m.name,
                         m.engine,
-                        err
                     );
+                    let diff = differing_fields(&measurements[j], &m)?;
+                    anyhow::ensure!(
+                        diff.is_empty(),
+                        "conflicting duplicate measurements with name '{}' \
+                         and regex engine '{}' in {} and {}, which differ \
+                         in: {}",
+                        m.name,
+                        m.engine,
+                        first_path.display(),
+                        path.display(),
+                        diff.join(", "),
+                    );
+                    deduped += 1;
                     continue;
                 }
-                if !self.filters.include(&m) {
-                    continue;
-                }
-                let is_new = name_to_engines
+                name_to_engines
                     .entry(m.name.clone())
-                    .or_insert_with(|| BTreeSet::new())
+                    .or_default()
                     .insert(m.engine.clone());
-                anyhow::ensure!(
-                    is_new,
-                    "duplicate measurement with name '{}' \
-                     and regex engine '{}'",
-                    m.name,
-                    m.engine,
-                );
+                seen.insert(key, (measurements.len(), path));
+                versions.add(&m, path);
                 measurements.push(m);
             }
         }
+        if deduped > 0 {
+            log::info!(
+                "dropped {} duplicate measurements that were identical to \
+                 measurements already read",
+                deduped,
+            );
+        }
         if self.intersection {
             let engines_len = name_to_engines
                 .values()
@@ -95,7 +197,452 @@ two regex engines. That is, only benchmarks containing measurements for both
             measurements
                 .retain(|m| name_to_engines[&m.name].len() == engines_len);
         }
-        Ok(measurements)
+        Ok((measurements, errors, versions))
+    }
+}
+
+/// Returns a description of each field that differs between the given
+/// measurements, e.g., `median (1.00ms vs 2.00ms)`. Fields are named after the
+/// columns of the CSV files that measurements are read from. If the
+/// measurements are identical, then this returns an empty vec.
+fn differing_fields(
+    m1: &Measurement,
+    m2: &Measurement,
+) -> anyhow::Result<Vec<String>> {
+    let (v1, v2) = (serde_json::to_value(m1)?, serde_json::to_value(m2)?);
+    let (Some(fields1), Some(fields2)) = (v1.as_object(), v2.as_object())
+    else {
+        anyhow::bail!("measurements did not serialize to JSON objects")
+    };
+    // Values are shown as they'd appear in a CSV file, so without quotes
+    // around strings.
+    let show = |value: Option<&serde_json::Value>| match value {
+        None | Some(serde_json::Value::Null) => "empty".to_string(),
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    };
+    let mut diff = vec![];
+    for (field, value1) in fields1.iter() {
+        let value2 = fields2.get(field);
+        if Some(value1) != value2 {
+            let (value1, value2) = (show(Some(value1)), show(value2));
+            diff.push(format!("{} ({} vs {})", field, value1, value2));
+        }
+    }
+    Ok(diff)
+}
+
+/// Logs every measurement with an error in the given slice. Benchmarks that
+/// a regex engine reported it can't run are only logged at the debug level,
+/// since they aren't failures and there can be a lot of them for regex engines
+/// that only support a narrow set of features.
+pub fn warn_errors(errors: &[Measurement]) {
+    for m in errors.iter() {
+        if let Some(reason) = m.skipped() {
+            log::debug!(
+                "{}:{}: skipping because the regex engine can't run it: {}",
+                m.name,
+                m.engine,
+                reason,
+            );
+        } else if let Some(ref err) = m.err {
+            log::warn!(
+                "{}:{}: skipping because of error: {}",
+                m.name,
+                m.engine,
+                err
+            );
+        }
+    }
+}
+
+/// Writes a table with the benchmark name, regex engine and error message of
+/// each of the given measurements to stdout. This is used to implement the
+/// `--show-errors` flag.
+///
+/// Newlines and tabs in error messages are replaced with spaces so that each
+/// error fits on one row.
+pub fn write_error_table(errors: &[Measurement]) -> anyhow::Result<()> {
+    use {std::io::Write, unicode_width::UnicodeWidthStr};
+
+    let mut wtr = tabwriter::TabWriter::new(std::io::stdout().lock());
+    let columns = ["Benchmark", "Engine", "Error"];
+    writeln!(wtr, "{}", columns.join("\t"))?;
+    for (i, label) in columns.iter().enumerate() {
+        if i > 0 {
+            write!(wtr, "\t")?;
+        }
+        util::write_divider(&mut wtr, '-', label.width())?;
+    }
+    writeln!(wtr)?;
+    for m in errors.iter() {
+        let err =
+            m.err.as_deref().unwrap_or_default().replace(['\n', '\t'], " ");
+        writeln!(wtr, "{}\t{}\t{}", m.name, m.engine, err.trim())?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// The distinct versions of rebar that recorded a set of measurements, along
+/// with the CSV files in which each version was found.
+///
+/// Versions are the full strings recorded in the 'rebar_version' column,
+/// which usually includes a revision, e.g., '0.1.0 (rev 79305bcb5f)'.
+///
+/// This also tracks the distinct revisions of the benchmark definitions that
+/// the measurements were recorded against, for the measurements that have
+/// one. (See `Measurement::defs_revision`.)
+///
+/// And it tracks the distinct build metadata of each regex engine, for the
+/// measurements that have it. (See `Measurement::engine_metadata`.)
+#[derive(Clone, Debug, Default)]
+pub struct RebarVersions {
+    by_version: BTreeMap<String, BTreeSet<PathBuf>>,
+    by_defs_revision: BTreeMap<String, BTreeSet<PathBuf>>,
+    by_engine_metadata: BTreeMap<String, BTreeMap<String, BTreeSet<PathBuf>>>,
+}
+
+impl RebarVersions {
+    /// Record the version of rebar (and the revision of the benchmark
+    /// definitions and the engine's build metadata, if present) used to
+    /// produce the given measurement, which was read from the given file
+    /// path.
+    pub fn add(&mut self, m: &Measurement, path: &Path) {
+        self.by_version
+            .entry(m.rebar_version.clone())
+            .or_default()
+            .insert(path.to_path_buf());
+        if let Some(ref rev) = m.defs_revision {
+            self.by_defs_revision
+                .entry(rev.clone())
+                .or_default()
+                .insert(path.to_path_buf());
+        }
+        if let Some(ref metadata) = m.engine_metadata {
+            self.by_engine_metadata
+                .entry(m.engine.clone())
+                .or_default()
+                .entry(metadata.clone())
+                .or_default()
+                .insert(path.to_path_buf());
+        }
+    }
+
+    /// If the measurements were recorded against more than one revision of
+    /// the benchmark definitions, then this returns each revision along with
+    /// the files containing measurements recorded against it. Otherwise, this
+    /// returns `None`. Measurements without a revision are ignored.
+    pub fn defs_revisions(
+        &self,
+    ) -> Option<&BTreeMap<String, BTreeSet<PathBuf>>> {
+        if self.by_defs_revision.len() <= 1 {
+            return None;
+        }
+        Some(&self.by_defs_revision)
+    }
+
+    /// Returns a warning message if these versions are inconsistent with one
+    /// another or with the given version of the running rebar. If all
+    /// measurements were recorded by the same version of rebar as `current`,
+    /// then this returns `None`.
+    ///
+    /// The version of the running rebar is only compared with the version
+    /// number of each recorded version, since revisions are only available
+    /// when rebar is built from a git checkout.
+    fn mismatch(&self, current: &str) -> Option<String> {
+        let differs =
+            self.by_version.keys().any(|v| version_number(v) != current);
+        if self.by_version.len() <= 1 && !differs {
+            return None;
+        }
+        let mut msg = if self.by_version.len() > 1 {
+            format!(
+                "measurements were recorded by {} different versions of \
+                 rebar, so comparisons between them may be misleading:",
+                self.by_version.len(),
+            )
+        } else {
+            format!(
+                "measurements were recorded by a different version of rebar \
+                 than the one running ({}):",
+                current,
+            )
+        };
+        msg.push_str(&self.describe(|_| true));
+        msg.push_str(
+            "\n(use --allow-version-mismatch to silence this warning)",
+        );
+        Some(msg)
+    }
+
+    /// Returns a warning message if any regex engine has measurements with
+    /// more than one distinct build metadata string. For example, when one
+    /// set of measurements was recorded with a build of an engine that
+    /// detected AVX2 and another with a build that didn't. Measurements
+    /// without metadata are ignored.
+    fn engine_metadata_mismatch(&self) -> Option<String> {
+        let mut msg = String::new();
+        for (engine, by_metadata) in self.by_engine_metadata.iter() {
+            if by_metadata.len() <= 1 {
+                continue;
+            }
+            msg.push_str(&format!("\n    {}:", engine));
+            for (metadata, paths) in by_metadata.iter() {
+                let paths: Vec<String> =
+                    paths.iter().map(|p| p.display().to_string()).collect();
+                msg.push_str(&format!(
+                    "\n        {}: {}",
+                    metadata,
+                    paths.join(", "),
+                ));
+            }
+        }
+        if msg.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "measurements were recorded with regex engines built with \
+             different metadata, so comparisons between them may be \
+             misleading:{}\n(use --allow-version-mismatch to silence this \
+             warning)",
+            msg,
+        ))
+    }
+
+    /// Returns one line for each version accepted by the given predicate,
+    /// listing the files containing that version. Each line is preceded by a
+    /// line terminator.
+    fn describe(&self, mut include: impl FnMut(&str) -> bool) -> String {
+        let mut out = String::new();
+        for (version, paths) in self.by_version.iter() {
+            if !include(version) {
+                continue;
+            }
+            let paths: Vec<String> =
+                paths.iter().map(|p| p.display().to_string()).collect();
+            out.push_str(&format!("\n    {}: {}", version, paths.join(", ")));
+        }
+        out
+    }
+}
+
+/// Returns the version number from a version string recorded in a
+/// measurement, e.g., '0.1.0' from '0.1.0 (rev 79305bcb5f)'.
+fn version_number(version: &str) -> &str {
+    version.split_once(" (").map_or(version, |(number, _)| number).trim()
+}
+
+/// Settings for checking the versions of rebar that recorded a set of
+/// measurements. This is shared by all commands that compare measurements.
+#[derive(Clone, Debug, Default)]
+pub struct VersionCheck {
+    /// When enabled, version mismatches don't produce a warning.
+    pub allow_mismatch: bool,
+    /// When present, every measurement must have been recorded by a version
+    /// of rebar satisfying this requirement.
+    pub require: Option<VersionReq>,
+}
+
+impl VersionCheck {
+    pub const USAGE_ALLOW_MISMATCH: Usage = Usage::new(
+        "--allow-version-mismatch",
+        "Don't warn when measurements come from different rebar versions.",
+        r#"
+Don't warn when measurements come from different rebar versions.
+
+By default, a warning is printed when the measurements given were recorded by
+more than one version of rebar, or by a version that differs from the version
+of rebar that is running. This is because changes to rebar itself (like how
+samples are timed) can change measurements, which can look like a regression
+or improvement in a regex engine when there isn't one.
+
+Similarly, a warning is printed when the measurements for a regex engine were
+recorded with builds of that engine reporting different metadata, e.g., one
+with SIMD acceleration enabled and one without.
+
+This flag silences these warnings. It does not have any effect on
+--require-version.
+"#,
+    );
+
+    pub const USAGE_REQUIRE: Usage = Usage::new(
+        "--require-version <req>",
+        "Fail unless all measurements come from a matching rebar version.",
+        r#"
+Fail unless all measurements come from a matching rebar version.
+
+The requirement given uses the same syntax as the 'version' field for counts
+in benchmark definitions, e.g., '>=0.1.0' or '>=0.1, <0.3'. If any measurement
+was recorded by a version of rebar that doesn't satisfy this requirement, then
+an error is returned listing the offending versions and the files containing
+them. This is useful in CI pipelines.
+"#,
+    );
+
+    /// Check the given rebar versions according to this configuration.
+    ///
+    /// An error is returned if a version requirement was given and any of
+    /// the versions don't satisfy it. Otherwise, a warning is logged if
+    /// there's a version (or engine metadata) mismatch and mismatches aren't
+    /// allowed.
+    pub fn check(&self, versions: &RebarVersions) -> anyhow::Result<()> {
+        self.check_with_current(versions, util::REBAR_VERSION)
+    }
+
+    fn check_with_current(
+        &self,
+        versions: &RebarVersions,
+        current: &str,
+    ) -> anyhow::Result<()> {
+        if let Some(ref req) = self.require {
+            let bad = versions.describe(|v| !req.matches(v));
+            anyhow::ensure!(
+                bad.is_empty(),
+                "found measurements recorded by versions of rebar that do \
+                 not satisfy --require-version '{}':{}",
+                req,
+                bad,
+            );
+        }
+        if !self.allow_mismatch {
+            if let Some(msg) = versions.mismatch(current) {
+                log::warn!("{}", msg);
+            }
+            if let Some(msg) = versions.engine_metadata_mismatch() {
+                log::warn!("{}", msg);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Settings for checking whether measurements were recorded against a
+/// benchmark definition that has since changed. This is shared by all
+/// commands that compare measurements.
+#[derive(Clone, Debug, Default)]
+pub struct StaleCheck {
+    /// When enabled, stale measurements are dropped instead of being kept
+    /// with a warning.
+    pub skip: bool,
+}
+
+impl StaleCheck {
+    pub const USAGE_SKIP: Usage = Usage::new(
+        "--skip-stale",
+        "Drop measurements recorded with a different benchmark definition.",
+        r#"
+Drop measurements recorded with a different benchmark definition.
+
+Every measurement records a hash of its benchmark definition (its model,
+patterns, options, haystack and counts) at the time it was measured. If the
+hash doesn't match the hash of the benchmark definition currently in the
+benchmark directory (see -d/--dir), then the measurement is stale: the
+benchmark has changed since it was measured, and comparing it with other
+measurements may lead to nonsense conclusions.
+
+By default, a warning is printed for each stale measurement, but it is still
+used. When this flag is set, stale measurements are dropped instead.
+Measurements recorded by older versions of rebar don't have a hash and are
+never considered stale.
+"#,
+    );
+
+    /// Returns a map from benchmark name to the hash of its current
+    /// definition, for use with `keep`.
+    ///
+    /// Hashing definitions requires hashing their haystacks, which isn't
+    /// free. So if none of the given measurements recorded a hash, then this
+    /// skips hashing and returns an empty map.
+    pub fn hashes(
+        &self,
+        benchmarks: &Benchmarks,
+        measurements: &[Measurement],
+    ) -> BTreeMap<String, String> {
+        if measurements.iter().all(|m| m.def_hash.is_none()) {
+            return BTreeMap::new();
+        }
+        benchmarks.hashes()
+    }
+
+    /// Like `hashes`, but loads the definitions for each benchmark referenced
+    /// by the given measurements from the given directory.
+    ///
+    /// This is meant for commands that don't otherwise need benchmark
+    /// definitions, so it is best effort. If the given directory doesn't
+    /// exist or the definitions fail to load, then this logs why and returns
+    /// an empty map, which means no measurement is considered stale.
+    pub fn load_hashes(
+        &self,
+        dir: &Path,
+        measurements: &[Measurement],
+        opts: &LoadOptions,
+    ) -> BTreeMap<String, String> {
+        if measurements.iter().all(|m| m.def_hash.is_none()) {
+            return BTreeMap::new();
+        }
+        if !dir.join("definitions").is_dir() {
+            log::debug!(
+                "not checking for stale measurements since {} does not \
+                 contain benchmark definitions",
+                dir.display(),
+            );
+            return BTreeMap::new();
+        }
+        match Benchmarks::for_measurements(dir, measurements, opts) {
+            Ok(benchmarks) => self.hashes(&benchmarks, measurements),
+            Err(err) => {
+                log::warn!(
+                    "not checking for stale measurements since benchmark \
+                     definitions in {} failed to load: {:#}",
+                    dir.display(),
+                    err,
+                );
+                BTreeMap::new()
+            }
+        }
+    }
+
+    /// Returns true if the given measurement should be kept, given a map from
+    /// benchmark name to the hash of its current definition (as returned by
+    /// `Benchmarks::hashes`).
+    ///
+    /// A stale measurement is logged as a warning if it's kept. Measurements
+    /// without a hash, or without a current definition, are always kept.
+    pub fn keep(
+        &self,
+        hashes: &BTreeMap<String, String>,
+        m: &Measurement,
+    ) -> bool {
+        let (Some(recorded), Some(current)) =
+            (m.def_hash.as_ref(), hashes.get(&m.name))
+        else {
+            return true;
+        };
+        if recorded == current {
+            return true;
+        }
+        if self.skip {
+            log::debug!(
+                "{}:{}: skipping stale measurement (definition hash {} \
+                 differs from current hash {})",
+                m.name,
+                m.engine,
+                recorded,
+                current,
+            );
+            return false;
+        }
+        log::warn!(
+            "{}:{}: measurement is stale since its benchmark definition has \
+             changed (definition hash {} differs from current hash {}), \
+             use --skip-stale to drop it",
+            m.name,
+            m.engine,
+            recorded,
+            current,
+        );
+        true
     }
 }
 
@@ -118,6 +665,61 @@ pub struct Measurement {
     pub iters: u64,
     pub total: Duration,
     pub aggregate: Aggregate,
+    /// The estimated growth in resident memory of the runner process, in
+    /// bytes per iteration. This is only recorded when 'rebar measure' is run
+    /// with '--leak-check'.
+    pub rss_growth_bytes_per_iter: Option<f64>,
+    /// The median size, in bytes, of the compiled regex as reported by the
+    /// regex engine itself. This is only recorded for runners that report it,
+    /// which is usually limited to the 'compile' model.
+    pub compiled_size_bytes: Option<u64>,
+    /// The number of warmup iterations that the runner program actually
+    /// executed before taking samples. This is only recorded for runners that
+    /// report it, and may be less than '--max-warmup-iters' when
+    /// '--max-warmup-time' is reached first.
+    pub warmup_iters: Option<u64>,
+    /// The estimated time the runner program spent on warmup iterations.
+    /// This is computed from 'warmup_iters', so it's only present when that
+    /// is.
+    pub warmup_estimate: Option<Duration>,
+    /// The fraction of 'total' that was not spent in measured iterations.
+    /// That is, the time spent spawning the runner program, sending it the
+    /// benchmark, warming up, verifying counts and so on. This is absent in
+    /// measurements recorded by older versions of rebar.
+    pub overhead_ratio: Option<f64>,
+    /// The hash of the benchmark definition at the time this measurement was
+    /// recorded. See `DefinitionHasher`. This is used to detect measurements
+    /// that are stale because their benchmark definition has since changed.
+    /// It's absent in measurements recorded by older versions of rebar.
+    pub def_hash: Option<String>,
+    /// The abbreviated git revision of the benchmark directory that the
+    /// benchmark definition was loaded from, with a '-dirty' suffix when it
+    /// had uncommitted changes. This is absent when the benchmark directory
+    /// isn't in a git work tree (or git isn't available), and in measurements
+    /// recorded by older versions of rebar.
+    pub defs_revision: Option<String>,
+    /// Build metadata reported by the regex engine, e.g., which optional
+    /// features it was compiled with and which CPU features it detected.
+    /// Unlike `engine_version`, this is only present for engines with a
+    /// metadata command, and it's absent in measurements recorded by older
+    /// versions of rebar.
+    pub engine_metadata: Option<String>,
+    /// Whether the runner program stopped early because it reached the soft
+    /// deadline given to it by 'rebar measure'. When this is true, the
+    /// aggregate statistics are computed from fewer samples than requested,
+    /// so they may be less reliable.
+    pub partial: bool,
+    /// The CPU core that the runner program was pinned to via
+    /// 'rebar measure --affinity'. This is absent when it wasn't pinned.
+    pub affinity: Option<usize>,
+    /// The niceness that the runner program was run with via
+    /// 'rebar measure --nice'. This is absent when it wasn't changed.
+    pub nice: Option<i32>,
+    /// The environment variables injected into the runner program via
+    /// 'rebar measure --env', as a space separated list of names. With
+    /// '--env-record-values', each name is followed by '=' and its value.
+    /// This is absent when no variables were injected.
+    pub env: Option<String>,
 }
 
 /// The aggregate statistics computed from samples taken from a benchmark.
@@ -154,6 +756,37 @@ pub struct AggregateThroughputs {
 }
 
 impl Measurement {
+    /// The prefix of the `err` column for benchmarks that the regex engine
+    /// reported it can't run, e.g., because it doesn't support a feature the
+    /// benchmark requires.
+    ///
+    /// Storing this in the `err` column means that everything that doesn't
+    /// know about skipped benchmarks still treats them like failures (since
+    /// there are no timings), but consumers that care can tell "can't run"
+    /// apart from "broke."
+    pub const SKIPPED_PREFIX: &'static str = "skipped: ";
+
+    /// The marker appended to values derived from partial measurements when
+    /// they're shown in a table. See `Measurement::partial`.
+    pub const PARTIAL_MARKER: &'static str = "†";
+
+    /// Returns the name of the regex engine of this measurement without any
+    /// label added by `MeasurementReader::labels`. Since regex engine names
+    /// can't contain parentheses, stripping a label is never ambiguous.
+    pub fn unlabeled_engine(&self) -> &str {
+        match self.engine.find('(') {
+            Some(i) if self.engine.ends_with(')') => &self.engine[..i],
+            _ => &self.engine,
+        }
+    }
+
+    /// If the regex engine reported that it can't run this benchmark, then
+    /// this returns the reason it gave. Otherwise, this returns `None`, even
+    /// if this measurement is some other kind of error.
+    pub fn skipped(&self) -> Option<&str> {
+        self.err.as_deref()?.strip_prefix(Measurement::SKIPPED_PREFIX)
+    }
+
     /// Get the corresponding throughput statistic from this aggregate.
     ///
     /// If this measurement doesn't have any throughputs (i.e., its haystack
@@ -252,6 +885,37 @@ struct WireMeasurement {
     #[serde(serialize_with = "ShortHumanDuration::serialize_with")]
     #[serde(deserialize_with = "ShortHumanDuration::deserialize_with")]
     max: Duration,
+    // The remaining columns were added after the ones above, so we permit
+    // them to be missing in order to read older CSV files.
+    #[serde(default)]
+    rss_growth_bytes_per_iter: Option<f64>,
+    #[serde(default)]
+    compiled_size_bytes: Option<u64>,
+    #[serde(default)]
+    def_hash: Option<String>,
+    #[serde(default)]
+    warmup_iters: Option<u64>,
+    // This is the same as 'total', but with full precision.
+    #[serde(default)]
+    total_secs: Option<f64>,
+    #[serde(default)]
+    warmup_secs: Option<f64>,
+    #[serde(default)]
+    overhead_ratio: Option<f64>,
+    #[serde(default)]
+    defs_revision: Option<String>,
+    #[serde(default)]
+    engine_metadata: Option<String>,
+    // This is only written when it's true, which keeps it out of the way in
+    // the common case.
+    #[serde(default)]
+    partial: Option<bool>,
+    #[serde(default)]
+    affinity: Option<usize>,
+    #[serde(default)]
+    nice: Option<i32>,
+    #[serde(default)]
+    env: Option<String>,
 }
 
 impl From<WireMeasurement> for Measurement {
@@ -273,8 +937,20 @@ impl From<WireMeasurement> for Measurement {
             engine_version: w.engine_version,
             err: w.err,
             iters: w.iters,
-            total: w.total,
+            total: w.total_secs.map_or(w.total, Duration::from_secs_f64),
             aggregate,
+            rss_growth_bytes_per_iter: w.rss_growth_bytes_per_iter,
+            compiled_size_bytes: w.compiled_size_bytes,
+            warmup_iters: w.warmup_iters,
+            warmup_estimate: w.warmup_secs.map(Duration::from_secs_f64),
+            overhead_ratio: w.overhead_ratio,
+            def_hash: w.def_hash,
+            defs_revision: w.defs_revision,
+            engine_metadata: w.engine_metadata,
+            partial: w.partial.unwrap_or(false),
+            affinity: w.affinity,
+            nice: w.nice,
+            env: w.env,
         }
     }
 }
@@ -297,6 +973,427 @@ impl From<Measurement> for WireMeasurement {
             stddev: m.aggregate.times.stddev,
             min: m.aggregate.times.min,
             max: m.aggregate.times.max,
+            rss_growth_bytes_per_iter: m.rss_growth_bytes_per_iter,
+            compiled_size_bytes: m.compiled_size_bytes,
+            def_hash: m.def_hash,
+            warmup_iters: m.warmup_iters,
+            // We only write the precise total when the rest of the overhead
+            // accounting is present, which notably excludes errors.
+            total_secs: m.overhead_ratio.map(|_| m.total.as_secs_f64()),
+            warmup_secs: m.warmup_estimate.map(|d| d.as_secs_f64()),
+            overhead_ratio: m.overhead_ratio,
+            defs_revision: m.defs_revision,
+            engine_metadata: m.engine_metadata,
+            partial: m.partial.then_some(true),
+            affinity: m.affinity,
+            nice: m.nice,
+            env: m.env,
         }
     }
 }
+
+/// The columns of the CSV rows given to `read_fixture`.
+#[cfg(test)]
+pub const FIXTURE_HEADER: &str = "name,model,rebar_version,engine,\
+                                  engine_version,err,haystack_len,iters,\
+                                  total,median,mad,mean,stddev,min,max";
+
+/// Parses measurements from the given CSV rows, which must not have a header
+/// and must have the columns in `FIXTURE_HEADER`. This is used to write
+/// compact measurement fixtures in tests.
+#[cfg(test)]
+pub fn read_fixture(rows: &str) -> Vec<Measurement> {
+    let data = format!("{}\n{}", FIXTURE_HEADER, rows);
+    let mut rdr = csv::Reader::from_reader(data.as_bytes());
+    rdr.deserialize().collect::<Result<_, _>>().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FIXTURE_HEADER as HEADER, *};
+
+    /// Reads the versions from the given CSV data (without a header) as if
+    /// they came from the file path given.
+    fn add_csv(versions: &mut RebarVersions, path: &str, rows: &[&str]) {
+        for m in read_fixture(&rows.join("\n")) {
+            versions.add(&m, Path::new(path));
+        }
+    }
+
+    fn row(name: &str, version: &str) -> String {
+        format!(
+            "{name},count,{version},rust/regex,1.10.2,,10,5,1s,1ms,\
+             1ms,1ms,1ms,1ms,1ms"
+        )
+    }
+
+    #[test]
+    fn versions_same() {
+        let mut versions = RebarVersions::default();
+        let (r1, r2) =
+            (row("a", "0.1.0 (rev abc)"), row("b", "0.1.0 (rev abc)"));
+        add_csv(&mut versions, "old.csv", &[&r1]);
+        add_csv(&mut versions, "new.csv", &[&r2]);
+        assert_eq!(versions.mismatch("0.1.0"), None);
+        // Same version in every file, but it isn't the running version.
+        let msg = versions.mismatch("0.2.0").unwrap();
+        assert!(msg.contains("than the one running (0.2.0)"), "{}", msg);
+        assert!(msg.contains("0.1.0 (rev abc): new.csv, old.csv"), "{}", msg);
+    }
+
+    #[test]
+    fn versions_mixed() {
+        let mut versions = RebarVersions::default();
+        let (r1, r2, r3) = (
+            row("a", "0.1.0 (rev abc)"),
+            row("b", "0.1.0 (rev def)"),
+            row("c", "0.0.1 (rev 123)"),
+        );
+        add_csv(&mut versions, "old.csv", &[&r1, &r3]);
+        add_csv(&mut versions, "new.csv", &[&r2]);
+        let msg = versions.mismatch("0.1.0").unwrap();
+        assert!(msg.contains("3 different versions"), "{}", msg);
+        assert!(msg.contains("0.0.1 (rev 123): old.csv"), "{}", msg);
+        assert!(msg.contains("0.1.0 (rev abc): old.csv"), "{}", msg);
+        assert!(msg.contains("0.1.0 (rev def): new.csv"), "{}", msg);
+
+        // Mismatches are only warnings...
+        let check = VersionCheck::default();
+        assert!(check.check_with_current(&versions, "0.1.0").is_ok());
+        // ... unless a version is required.
+        let check = VersionCheck {
+            allow_mismatch: true,
+            require: Some(VersionReq::parse(">=0.1.0").unwrap()),
+        };
+        let err = check
+            .check_with_current(&versions, "0.1.0")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("0.0.1 (rev 123): old.csv"), "{}", err);
+        assert!(!err.contains("0.1.0 (rev"), "{}", err);
+
+        let check = VersionCheck {
+            allow_mismatch: false,
+            require: Some(VersionReq::parse(">=0.0.1").unwrap()),
+        };
+        assert!(check.check_with_current(&versions, "0.1.0").is_ok());
+    }
+
+    #[test]
+    fn engine_metadata() {
+        let data = format!(
+            "{HEADER},engine_metadata\n{},avx2\n{},\n",
+            row("a", "0.1.0"),
+            row("b", "0.1.0"),
+        );
+        let mut rdr = csv::Reader::from_reader(data.as_bytes());
+        let ms: Vec<Measurement> =
+            rdr.deserialize().collect::<Result<_, _>>().unwrap();
+        assert_eq!(Some("avx2"), ms[0].engine_metadata.as_deref());
+        assert_eq!(None, ms[1].engine_metadata);
+
+        let mut versions = RebarVersions::default();
+        versions.add(&ms[0], Path::new("old.csv"));
+        versions.add(&ms[1], Path::new("new.csv"));
+        // A measurement without metadata is never a mismatch.
+        assert_eq!(None, versions.engine_metadata_mismatch());
+
+        let mut m = ms[0].clone();
+        m.engine_metadata = Some("sse4".to_string());
+        versions.add(&m, Path::new("new.csv"));
+        let msg = versions.engine_metadata_mismatch().unwrap();
+        assert!(msg.contains("\n    rust/regex:"), "{}", msg);
+        assert!(msg.contains("avx2: old.csv"), "{}", msg);
+        assert!(msg.contains("sse4: new.csv"), "{}", msg);
+    }
+
+    #[test]
+    fn partial() {
+        let data = format!(
+            "{HEADER},partial\n{},true\n{},\n",
+            row("a", "0.1.0"),
+            row("b", "0.1.0"),
+        );
+        let mut rdr = csv::Reader::from_reader(data.as_bytes());
+        let ms: Vec<Measurement> =
+            rdr.deserialize().collect::<Result<_, _>>().unwrap();
+        assert!(ms[0].partial);
+        assert!(!ms[1].partial);
+
+        // The column is only written when the measurement is partial.
+        let mut wtr = csv::Writer::from_writer(vec![]);
+        for m in ms.iter() {
+            wtr.serialize(m).unwrap();
+        }
+        let out = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        let header = ",partial,affinity,nice,env";
+        assert!(lines[0].ends_with(header), "{}", lines[0]);
+        assert!(lines[1].ends_with(",true,,,"), "{}", lines[1]);
+        assert!(lines[2].ends_with(",,,,"), "{}", lines[2]);
+    }
+
+    #[test]
+    fn scheduling() {
+        let data = format!(
+            "{HEADER},affinity,nice\n{},3,-5\n{},,\n",
+            row("a", "0.1.0"),
+            row("b", "0.1.0"),
+        );
+        let mut rdr = csv::Reader::from_reader(data.as_bytes());
+        let ms: Vec<Measurement> =
+            rdr.deserialize().collect::<Result<_, _>>().unwrap();
+        assert_eq!((Some(3), Some(-5)), (ms[0].affinity, ms[0].nice));
+        assert_eq!((None, None), (ms[1].affinity, ms[1].nice));
+
+        let mut wtr = csv::Writer::from_writer(vec![]);
+        wtr.serialize(&ms[0]).unwrap();
+        let out = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(lines[1].ends_with(",3,-5,"), "{}", lines[1]);
+    }
+
+    #[test]
+    fn env() {
+        let data = format!(
+            "{HEADER},env\n{},A=1 B=x:y\n{},\n",
+            row("a", "0.1.0"),
+            row("b", "0.1.0"),
+        );
+        let mut rdr = csv::Reader::from_reader(data.as_bytes());
+        let ms: Vec<Measurement> =
+            rdr.deserialize().collect::<Result<_, _>>().unwrap();
+        assert_eq!(Some("A=1 B=x:y"), ms[0].env.as_deref());
+        assert_eq!(None, ms[1].env);
+
+        // Older CSV files without the column are still readable.
+        let data = format!("{HEADER}\n{}\n", row("a", "0.1.0"));
+        let mut rdr = csv::Reader::from_reader(data.as_bytes());
+        let ms: Vec<Measurement> =
+            rdr.deserialize().collect::<Result<_, _>>().unwrap();
+        assert_eq!(None, ms[0].env);
+    }
+
+    #[test]
+    fn stale() {
+        let data = format!(
+            "{HEADER},def_hash\n{},abc\n{},def\n{},\n{},abc\n",
+            row("a", "0.1.0"),
+            row("b", "0.1.0"),
+            row("c", "0.1.0"),
+            row("d", "0.1.0"),
+        );
+        let mut rdr = csv::Reader::from_reader(data.as_bytes());
+        let measurements: Vec<Measurement> =
+            rdr.deserialize().collect::<Result<_, _>>().unwrap();
+        // There's no current definition for 'd', so it's never stale.
+        let hashes: BTreeMap<String, String> =
+            [("a", "abc"), ("b", "abc"), ("c", "abc")]
+                .into_iter()
+                .map(|(name, hash)| (name.to_string(), hash.to_string()))
+                .collect();
+        let kept = |check: StaleCheck| -> Vec<&str> {
+            measurements
+                .iter()
+                .filter(|m| check.keep(&hashes, m))
+                .map(|m| m.name.as_str())
+                .collect()
+        };
+        assert_eq!(vec!["a", "b", "c", "d"], kept(StaleCheck { skip: false }));
+        assert_eq!(vec!["a", "c", "d"], kept(StaleCheck { skip: true }));
+
+        // Older CSV files without the column can still be read.
+        let data = format!("{HEADER}\n{}\n", row("a", "0.1.0"));
+        let mut rdr = csv::Reader::from_reader(data.as_bytes());
+        let m: Measurement = rdr.deserialize().next().unwrap().unwrap();
+        assert_eq!(None, m.def_hash);
+    }
+
+    #[test]
+    fn warmup_iters() {
+        let data = format!(
+            "{HEADER},def_hash,warmup_iters\n{},,123\n{},,\n",
+            row("a", "0.1.0"),
+            row("b", "0.1.0"),
+        );
+        let mut rdr = csv::Reader::from_reader(data.as_bytes());
+        let measurements: Vec<Measurement> =
+            rdr.deserialize().collect::<Result<_, _>>().unwrap();
+        assert_eq!(Some(123), measurements[0].warmup_iters);
+        assert_eq!(None, measurements[1].warmup_iters);
+
+        // The column is written last, after the columns added before it.
+        let mut wtr = csv::Writer::from_writer(vec![]);
+        wtr.serialize(&measurements[0]).unwrap();
+        let out = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+        let header = out.lines().next().unwrap();
+        assert!(header.contains(",def_hash,warmup_iters,"), "{}", header);
+    }
+
+    #[test]
+    fn overhead() {
+        // Older CSV files don't have any of the overhead columns.
+        let data = format!(
+            "{HEADER}
+{}
+",
+            row("a", "0.1.0")
+        );
+        let mut rdr = csv::Reader::from_reader(data.as_bytes());
+        let m: Measurement = rdr.deserialize().next().unwrap().unwrap();
+        assert_eq!(Duration::from_secs(1), m.total);
+        assert_eq!(None, m.warmup_estimate);
+        assert_eq!(None, m.overhead_ratio);
+
+        // When present, 'total_secs' takes precedence over 'total'.
+        let data = format!(
+            "{HEADER},total_secs,warmup_secs,overhead_ratio\n\
+             {},1.23456,0.5,0.75\n",
+            row("a", "0.1.0"),
+        );
+        let mut rdr = csv::Reader::from_reader(data.as_bytes());
+        let m: Measurement = rdr.deserialize().next().unwrap().unwrap();
+        assert_eq!(Duration::from_secs_f64(1.23456), m.total);
+        assert_eq!(Some(Duration::from_millis(500)), m.warmup_estimate);
+        assert_eq!(Some(0.75), m.overhead_ratio);
+
+        let mut wtr = csv::Writer::from_writer(vec![]);
+        wtr.serialize(&m).unwrap();
+        let out = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+        let mut lines = out.lines();
+        let header = lines.next().unwrap();
+        assert!(
+            header.contains(",total_secs,warmup_secs,overhead_ratio,"),
+            "{}",
+            header,
+        );
+        let row = lines.next().unwrap();
+        assert!(row.contains(",1.23456,0.5,0.75,"), "{}", row);
+    }
+
+    #[test]
+    fn defs_revisions() {
+        let data = format!(
+            "{HEADER},defs_revision\n{},abc\n{},abc-dirty\n{},\n",
+            row("a", "0.1.0"),
+            row("b", "0.1.0"),
+            row("c", "0.1.0"),
+        );
+        let mut rdr = csv::Reader::from_reader(data.as_bytes());
+        let ms: Vec<Measurement> =
+            rdr.deserialize().collect::<Result<_, _>>().unwrap();
+        assert_eq!(Some("abc"), ms[0].defs_revision.as_deref());
+        assert_eq!(None, ms[2].defs_revision);
+
+        // One revision (plus measurements without one) is fine.
+        let mut versions = RebarVersions::default();
+        versions.add(&ms[0], Path::new("old.csv"));
+        versions.add(&ms[2], Path::new("new.csv"));
+        assert!(versions.defs_revisions().is_none());
+        // But a dirty work tree counts as a different revision.
+        versions.add(&ms[1], Path::new("new.csv"));
+        let revs = versions.defs_revisions().unwrap();
+        let got: Vec<(&str, usize)> = revs
+            .iter()
+            .map(|(rev, paths)| (rev.as_str(), paths.len()))
+            .collect();
+        assert_eq!(vec![("abc", 1), ("abc-dirty", 1)], got);
+    }
+
+    #[test]
+    fn skipped() {
+        let m = |err: Option<&str>| Measurement {
+            err: err.map(|e| e.to_string()),
+            ..Measurement::default()
+        };
+        assert_eq!(Some("no (?i)"), m(Some("skipped: no (?i)")).skipped());
+        assert_eq!(None, m(Some("count mismatch")).skipped());
+        assert_eq!(None, m(None).skipped());
+    }
+
+    #[test]
+    fn labels() {
+        let tmp = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = ["before.csv", "after.csv"]
+            .iter()
+            .map(|name| tmp.path().join(name))
+            .collect();
+        for path in paths.iter() {
+            let data = format!("{}\n{}\n", HEADER, row("a", "0.1.0"));
+            std::fs::write(path, data).unwrap();
+        }
+        let read = |labels: &[String]| {
+            MeasurementReader {
+                paths: &paths,
+                filters: &Filters::default(),
+                intersection: false,
+                labels,
+                strict_duplicates: true,
+            }
+            .read_with_errors()
+            .map(|(ms, _, _)| ms)
+        };
+
+        let err = read(&[]).unwrap_err().to_string();
+        assert!(err.contains("duplicate measurement"), "{}", err);
+
+        let labels = vec!["before".to_string(), "after".to_string()];
+        let ms = read(&labels).unwrap();
+        let engines: Vec<&str> =
+            ms.iter().map(|m| m.engine.as_str()).collect();
+        assert_eq!(vec!["rust/regex(before)", "rust/regex(after)"], engines);
+        assert_eq!("rust/regex", ms[0].unlabeled_engine());
+        assert_eq!("rust/regex", ms[1].unlabeled_engine());
+    }
+
+    #[test]
+    fn duplicates() {
+        let tmp = tempfile::tempdir().unwrap();
+        let write = |name: &str, rows: &[&str]| {
+            let path = tmp.path().join(name);
+            let data = format!("{}\n{}\n", HEADER, rows.join("\n"));
+            std::fs::write(&path, data).unwrap();
+            path
+        };
+        let read = |paths: &[PathBuf], strict_duplicates: bool| {
+            MeasurementReader {
+                paths,
+                filters: &Filters::default(),
+                intersection: false,
+                labels: &[],
+                strict_duplicates,
+            }
+            .read_with_errors()
+            .map(|(ms, _, _)| ms)
+        };
+        let (a, b) = (row("a", "0.1.0"), row("b", "0.1.0"));
+        let conflict = a.replace(",1s,", ",2s,");
+
+        // Identical duplicates in the same file.
+        let same = write("same.csv", &[&a, &b, &a]);
+        let ms = read(std::slice::from_ref(&same), false).unwrap();
+        let names: Vec<&str> = ms.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(vec!["a", "b"], names);
+        let err = read(&[same], true).unwrap_err().to_string();
+        assert!(err.contains("duplicate measurement"), "{}", err);
+
+        // Identical duplicates across files, e.g., an aggregate file along
+        // with a file for a single regex engine.
+        let all = write("all.csv", &[&a, &b]);
+        let one = write("one.csv", &[&b]);
+        let ms = read(&[all.clone(), one.clone()], false).unwrap();
+        assert_eq!(2, ms.len());
+        assert!(read(&[all.clone(), one], true).is_err());
+
+        // Duplicates that conflict are always an error, and the error names
+        // both files and the fields that differ.
+        let other = write("other.csv", &[&conflict]);
+        let err = read(&[all.clone(), other.clone()], false)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("conflicting duplicate"), "{}", err);
+        assert!(err.contains(&all.display().to_string()), "{}", err);
+        assert!(err.contains(&other.display().to_string()), "{}", err);
+        assert!(err.contains("total (1.00s vs 2.00s)"), "{}", err);
+    }
+}
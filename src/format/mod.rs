@@ -1,2 +1,3 @@
 pub mod benchmarks;
+pub mod cache;
 pub mod measurement;
@@ -18,9 +18,12 @@ benchmarks.
 
 use std::collections::{BTreeMap, BTreeSet};
 
+use {anyhow::Context, lexopt::ValueExt, regex_lite::Regex};
+
 use crate::{
-    args::{Stat, ThresholdRange},
+    args::{Stat, ThresholdRange, Usage},
     format::{benchmarks::Definition, measurement::Measurement},
+    util::{Rng, Throughput},
 };
 
 /// Groups measurements by benchmark name.
@@ -160,6 +163,7 @@ impl<T> ByBenchmarkName<T> {
             groups.push(ByBenchmarkNameGroup {
                 name: oldgroup.name,
                 by_engine,
+                variants: oldgroup.variants,
                 data: def,
             });
         }
@@ -178,6 +182,70 @@ impl<T> ByBenchmarkName<T> {
         Ok(ByBenchmarkName { groups })
     }
 
+    /// Renames every regex engine that matches one of the given aliases to
+    /// the name of that alias. When more than one regex engine in the same
+    /// group maps to the same alias, only the measurement with the best value
+    /// for the given statistic is kept, and the name of the regex engine it
+    /// came from is recorded in the group's `variants`.
+    ///
+    /// The version of each alias is the set of distinct versions of all
+    /// regex engines mapped to it (across all groups), joined by `, `. This
+    /// way, the versions stay consistent across groups even though a
+    /// different variant may win in each group.
+    ///
+    /// When no aliases are given, this grouping is returned unchanged.
+    pub fn alias(
+        self,
+        aliases: &EngineAliases,
+        stat: Stat,
+    ) -> ByBenchmarkName<T> {
+        if aliases.is_empty() {
+            return self;
+        }
+        let mut versions: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for m in self.groups.iter().flat_map(|g| g.by_engine.values()) {
+            if let Some(alias) = aliases.find(&m.engine) {
+                versions
+                    .entry(alias.to_string())
+                    .or_default()
+                    .insert(m.engine_version.clone());
+            }
+        }
+        let versions: BTreeMap<String, String> = versions
+            .into_iter()
+            .map(|(alias, vs)| {
+                (alias, vs.into_iter().collect::<Vec<String>>().join(", "))
+            })
+            .collect();
+
+        let mut groups = vec![];
+        for mut group in self.groups {
+            let mut by_engine: BTreeMap<String, Measurement> = BTreeMap::new();
+            for (engine, mut m) in group.by_engine {
+                let Some(alias) = aliases.find(&engine) else {
+                    by_engine.insert(engine, m);
+                    continue;
+                };
+                if let Some(best) = by_engine.get(alias) {
+                    if best.duration(stat) <= m.duration(stat) {
+                        continue;
+                    }
+                }
+                m.engine = alias.to_string();
+                m.engine_version = versions[alias].clone();
+                if engine == alias {
+                    group.variants.remove(alias);
+                } else {
+                    group.variants.insert(alias.to_string(), engine);
+                }
+                by_engine.insert(alias.to_string(), m);
+            }
+            group.by_engine = by_engine;
+            groups.push(group);
+        }
+        ByBenchmarkName { groups }
+    }
+
     /// Partitions this grouping according to the predicate given. The first
     /// element of the tuple contains all groups for which the given predicate
     /// returns true and the second element of the tuple contains all groups
@@ -201,6 +269,64 @@ impl<T> ByBenchmarkName<T> {
         (true_grouping, false_grouping)
     }
 
+    /// Splits this grouping into one grouping for each distinct prefix of
+    /// the benchmark group names. The prefix is made up of the first `depth`
+    /// components of each benchmark's group, where components are separated
+    /// by a `/`. For example, the benchmark `curated/01-literal/sherlock-en`
+    /// has a group of `curated/01-literal`, which has a prefix of `curated`
+    /// for a depth of `1` and `curated/01-literal` for a depth of `2` or
+    /// greater.
+    ///
+    /// The groupings returned are in the order in which each prefix first
+    /// appears, and the order of benchmarks within each grouping is
+    /// preserved.
+    pub fn split_by_group(
+        self,
+        depth: usize,
+    ) -> Vec<(String, ByBenchmarkName<T>)> {
+        self.split_by(|group| group_prefix(&group.name, depth))
+    }
+
+    /// Splits this grouping into one grouping for each distinct model among
+    /// the measurements. This generalizes the compile versus search split
+    /// done via `partition` to every model.
+    ///
+    /// The groupings returned are in the order in which each model first
+    /// appears, and the order of benchmarks within each grouping is
+    /// preserved.
+    pub fn split_by_model(self) -> Vec<(String, ByBenchmarkName<T>)> {
+        self.split_by(|group| {
+            // Every measurement for the same benchmark has the same model,
+            // so we just pick the first one.
+            group
+                .by_engine
+                .values()
+                .next()
+                .map(|m| m.model.clone())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Splits this grouping into one grouping for each distinct key returned
+    /// by the function given. The groupings returned are in the order in
+    /// which each key first appears.
+    fn split_by(
+        self,
+        mut key: impl FnMut(&ByBenchmarkNameGroup<T>) -> String,
+    ) -> Vec<(String, ByBenchmarkName<T>)> {
+        let mut splits: Vec<(String, ByBenchmarkName<T>)> = vec![];
+        for group in self.groups {
+            let k = key(&group);
+            match splits.iter_mut().find(|(k2, _)| *k2 == k) {
+                Some((_, split)) => split.groups.push(group),
+                None => {
+                    splits.push((k, ByBenchmarkName { groups: vec![group] }))
+                }
+            }
+        }
+        splits
+    }
+
     /// Returns all of the engines (name, version and geometric mean of speed
     /// ratios) from the *measurements*.
     ///
@@ -214,6 +340,9 @@ impl<T> ByBenchmarkName<T> {
     ///
     /// THe vector returned is sorted by geometric mean of the speedup ratios
     /// across all participating benchmarks in ascending order.
+    ///
+    /// Each summary also includes a bootstrap confidence interval for its
+    /// geometric mean. See `ConfidenceInterval` for details.
     pub fn ranking(&self, stat: Stat) -> anyhow::Result<Vec<EngineSummary>> {
         /// This is like EngineSummary, but contains all of the speedup ratios.
         /// The speedup ratios are converted to a geometric mean at the end.
@@ -221,6 +350,7 @@ impl<T> ByBenchmarkName<T> {
         struct SummaryWithData {
             name: String,
             version: String,
+            metadata: Option<String>,
             ratios: Vec<f64>,
         }
 
@@ -231,27 +361,34 @@ impl<T> ByBenchmarkName<T> {
                     SummaryWithData {
                         name: m.engine.clone(),
                         version: m.engine_version.clone(),
+                        metadata: None,
                         ratios: vec![],
                     }
                 });
+                if e.metadata.is_none() {
+                    e.metadata = m.engine_metadata.clone();
+                }
                 // OK because we know m.engine is in this group.
                 let ratio = group.ratio(&m.engine, stat).unwrap();
                 e.ratios.push(ratio);
             }
         }
         let mut summaries: Vec<EngineSummary> = map
-            .into_iter()
-            .map(|(_, summary)| {
+            .into_values()
+            .map(|summary| {
                 let mut geomean = 1.0;
                 let count = summary.ratios.len();
                 for &ratio in summary.ratios.iter() {
                     geomean *= ratio.powf(1.0 / count as f64);
                 }
 
+                let interval = ConfidenceInterval::bootstrap(&summary.ratios);
                 EngineSummary {
                     name: summary.name,
                     version: summary.version,
+                    metadata: summary.metadata,
                     geomean,
+                    interval,
                     count,
                 }
             })
@@ -260,6 +397,89 @@ impl<T> ByBenchmarkName<T> {
         Ok(summaries)
     }
 
+    /// Returns the median throughput of each regex engine across all of the
+    /// benchmarks it participated in. The throughput for each benchmark is
+    /// computed from the statistic given.
+    ///
+    /// Measurements without a throughput (i.e., without a haystack length)
+    /// are ignored. So an engine for which no measurement has a throughput
+    /// isn't included in the map returned.
+    pub fn median_throughputs(
+        &self,
+        stat: Stat,
+    ) -> BTreeMap<String, Throughput> {
+        let mut map: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+        for group in self.groups.iter() {
+            for m in group.by_engine.values() {
+                let Some(tput) = m.throughput(stat) else { continue };
+                map.entry(m.engine.clone())
+                    .or_default()
+                    .push(tput.bytes_per_second());
+            }
+        }
+        map.into_iter()
+            .map(|(name, mut tputs)| {
+                tputs.sort_by(|t1, t2| t1.total_cmp(t2));
+                let mid = tputs.len() / 2;
+                // OK because we only insert non-empty vectors.
+                let median = if tputs.len() % 2 == 1 {
+                    tputs[mid]
+                } else {
+                    (tputs[mid - 1] + tputs[mid]) / 2.0
+                };
+                (name, Throughput::from_bytes_per_second(median))
+            })
+            .collect()
+    }
+
+    /// Returns a matrix of pairwise speedups between every pair of regex
+    /// engines in this collection of measurements.
+    ///
+    /// Unlike `ranking`, which compares each engine to the best engine for
+    /// each benchmark, each cell in the matrix only considers the benchmarks
+    /// in which both engines have a measurement. For each such benchmark,
+    /// the speedup ratio of engine `i` over engine `j` is the time taken by
+    /// `j` divided by the time taken by `i`. The cell `(i, j)` is then the
+    /// geometric mean of those ratios. So a value greater than 1.0 means `i`
+    /// is faster than `j`.
+    ///
+    /// A cell is `None` when the two engines don't share any benchmarks. The
+    /// diagonal is always `1.0`.
+    pub fn speedup_matrix(&self, stat: Stat) -> SpeedupMatrix {
+        let engines = self.engine_names();
+        let mut cells = vec![vec![None; engines.len()]; engines.len()];
+        for (i, this) in engines.iter().enumerate() {
+            for (j, other) in engines.iter().enumerate() {
+                if i == j {
+                    cells[i][j] = Some(1.0);
+                    continue;
+                }
+                let mut ratios = vec![];
+                for group in self.groups.iter() {
+                    let (Some(m1), Some(m2)) = (
+                        group.by_engine.get(this),
+                        group.by_engine.get(other),
+                    ) else {
+                        continue;
+                    };
+                    let t1 = m1.duration(stat).as_secs_f64();
+                    let t2 = m2.duration(stat).as_secs_f64();
+                    ratios.push(t2 / t1);
+                }
+                if ratios.is_empty() {
+                    continue;
+                }
+                let mut geomean = 1.0;
+                let count = ratios.len();
+                for &ratio in ratios.iter() {
+                    geomean *= ratio.powf(1.0 / count as f64);
+                }
+                cells[i][j] = Some(geomean);
+            }
+        }
+        SpeedupMatrix { engines, cells }
+    }
+
     /// Returns a lexicographically sorted list of all regex engine names in
     /// this collection of measurements. The order is ascending.
     pub fn engine_names(&self) -> Vec<String> {
@@ -273,6 +493,17 @@ impl<T> ByBenchmarkName<T> {
     }
 }
 
+/// Returns the first `depth` components of the group in the given full
+/// benchmark name. If the group has fewer than `depth` components, then the
+/// entire group is returned.
+fn group_prefix(name: &str, depth: usize) -> String {
+    let group = match name.rsplit_once('/') {
+        Some((group, _)) => group,
+        None => return String::new(),
+    };
+    group.split('/').take(depth).collect::<Vec<&str>>().join("/")
+}
+
 /// A single group of measurements, where every measurement has the same
 /// benchmark name.
 ///
@@ -289,6 +520,12 @@ pub struct ByBenchmarkNameGroup<T> {
     /// A map from engine name to the corresponding measurement, where every
     /// measurement in this group has the same benchmark name.
     pub by_engine: BTreeMap<String, Measurement>,
+    /// A map from an alias in `by_engine` to the name of the regex engine
+    /// whose measurement was kept for it. This is empty unless engine
+    /// aliases were applied via `ByBenchmarkName::alias`. An alias whose
+    /// measurement came from a regex engine with the same name as the alias
+    /// isn't included.
+    pub variants: BTreeMap<String, String>,
     /// Extra data attached to this group. Usually either `()` (for nothing),
     /// or the benchmark definition.
     pub data: T,
@@ -317,7 +554,12 @@ impl ByBenchmarkNameGroup<()> {
                 name, m.name, m.engine,
             );
         }
-        ByBenchmarkNameGroup { name, by_engine, data: () }
+        ByBenchmarkNameGroup {
+            name,
+            by_engine,
+            variants: BTreeMap::new(),
+            data: (),
+        }
     }
 }
 
@@ -381,6 +623,143 @@ impl<T> ByBenchmarkNameGroup<T> {
         }
         best_engine
     }
+
+    /// Return the ratio between the compiled size reported by the `this`
+    /// engine and the smallest compiled size in the group. If `this` is the
+    /// smallest, then the ratio returned is 1.0.
+    ///
+    /// This returns `None` if `this` does not correspond to an engine in this
+    /// group or if it didn't report a compiled size.
+    pub fn size_ratio(&self, this: &str) -> Option<f64> {
+        let this = self.by_engine.get(this)?.compiled_size_bytes?;
+        let best = self.by_engine[self.best_size()?].compiled_size_bytes?;
+        // A zero size isn't really possible in practice, but we don't want to
+        // divide by zero.
+        Some((this.max(1) as f64) / (best.max(1) as f64))
+    }
+
+    /// Returns true only when this group contains at least one compiled size
+    /// whose ratio (relative to the smallest size) falls within the given
+    /// range. This always returns false when no engine in this group
+    /// reported a compiled size.
+    pub fn is_size_within_range(&self, range: ThresholdRange) -> bool {
+        let Some(best_engine) = self.best_size() else { return false };
+        let sized = self
+            .by_engine
+            .values()
+            .filter(|m| m.compiled_size_bytes.is_some())
+            .count();
+        // See 'is_within_range' for why a group of size 1 is special.
+        if sized == 1 {
+            return range.contains(1.0);
+        }
+        self.by_engine.keys().filter(|&e| e != best_engine).any(|e| {
+            self.size_ratio(e).is_some_and(|ratio| range.contains(ratio))
+        })
+    }
+
+    /// Return the engine name of the measurement with the smallest compiled
+    /// size in this group. If no measurement reported a compiled size, then
+    /// this returns `None`.
+    pub fn best_size(&self) -> Option<&str> {
+        self.by_engine
+            .iter()
+            .filter_map(|(e, m)| Some((e, m.compiled_size_bytes?)))
+            .min_by_key(|&(_, size)| size)
+            .map(|(e, _)| e.as_str())
+    }
+}
+
+/// A list of aliases that merge regex engines together, usually given via
+/// repeated uses of the `--alias` flag.
+///
+/// Each alias has a name and a regex. A regex engine belongs to the first
+/// alias whose regex matches its name, or whose name is equivalent to its
+/// name. See `ByBenchmarkName::alias` for how the measurements of regex
+/// engines belonging to the same alias are merged.
+#[derive(Clone, Debug, Default)]
+pub struct EngineAliases {
+    aliases: Vec<(String, Regex)>,
+}
+
+impl EngineAliases {
+    pub const USAGE: Usage = Usage::new(
+        "--alias <name=regex>",
+        "Merge regex engines matching a regex into one named engine.",
+        r#"
+Merge every regex engine whose name matches the regex given into a single
+regex engine with the name given. For example, '--alias pcre2=^pcre2' merges
+'pcre2' and 'pcre2/jit' into a regex engine named 'pcre2'. This flag can be
+given multiple times, and a regex engine belongs to the first alias that it
+matches. A regex engine whose name is the same as an alias always belongs to
+that alias, even if it doesn't match the regex.
+
+Aliases are applied after filtering, so filters still refer to the original
+names of each regex engine. When more than one regex engine in the same
+benchmark belongs to the same alias, only the one with the best value for
+the statistic selected is kept. The version shown for an alias is the set of
+distinct versions of all regex engines that belong to it.
+
+This is useful for summaries that don't care about the differences between
+the variants of a regex engine, e.g., an interpreter versus a JIT.
+"#,
+    );
+
+    /// Add an alias to this list by parsing `name=regex` from the given arg
+    /// parser.
+    ///
+    /// If the value could not be extracted from the arg parser or isn't a
+    /// valid alias, then this returns an error. The flag name given is used
+    /// in the error message.
+    pub fn arg_add(
+        &mut self,
+        p: &mut lexopt::Parser,
+        flag_name: &'static str,
+    ) -> anyhow::Result<()> {
+        let value = p.value().context(flag_name)?;
+        let value = value.string().context(flag_name)?;
+        self.add(&value).context(flag_name)
+    }
+
+    /// Add an alias to this list from a string of the form `name=regex`.
+    pub fn add(&mut self, spec: &str) -> anyhow::Result<()> {
+        let Some((name, pattern)) = spec.split_once('=') else {
+            anyhow::bail!("expected 'name=regex', but got '{}'", spec)
+        };
+        anyhow::ensure!(!name.is_empty(), "alias name must not be empty");
+        let re = Regex::new(pattern)
+            .with_context(|| format!("invalid regex for alias '{}'", name))?;
+        self.aliases.push((name.to_string(), re));
+        Ok(())
+    }
+
+    /// Returns true when this list has no aliases.
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty()
+    }
+
+    /// Returns the name of the alias that the given regex engine belongs to,
+    /// if any.
+    pub fn find(&self, engine: &str) -> Option<&str> {
+        self.aliases
+            .iter()
+            .find(|(name, re)| name == engine || re.is_match(engine))
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// A matrix of pairwise speedups between regex engines. See
+/// `ByBenchmarkName::speedup_matrix` for how each cell is computed.
+#[derive(Clone, Debug)]
+pub struct SpeedupMatrix {
+    /// The names of the regex engines, sorted lexicographically in ascending
+    /// order. The index of each engine corresponds to its row and column in
+    /// `cells`.
+    pub engines: Vec<String>,
+    /// The speedups, where `cells[i][j]` is the geometric mean of the
+    /// speedup ratios of `engines[i]` over `engines[j]`, or `None` if the two
+    /// engines have no benchmarks in common.
+    pub cells: Vec<Vec<Option<f64>>>,
 }
 
 /// A summary result for a single engine. Usually this only makes sense in the
@@ -393,11 +772,370 @@ pub struct EngineSummary {
     /// The version of the regex engine, also confirmed to be identical in all
     /// measurements that participated in this summary.
     pub version: String,
+    /// The build metadata of the regex engine, if any measurement that
+    /// participated in this summary recorded it. Unlike the version, this
+    /// isn't confirmed to be identical in all measurements. (Mismatches are
+    /// reported as warnings when measurements are read.)
+    pub metadata: Option<String>,
     /// The geometric mean of the speedup ratios for this engine, relative
     /// to other engines, for every group of measurements for each unique
     /// benchmark name that this engine participated in.
     pub geomean: f64,
+    /// A 95% confidence interval for `geomean`.
+    pub interval: ConfidenceInterval,
     /// The total number of unique benchmark names that contributed to the
     /// `geomean` result.
     pub count: usize,
 }
+
+impl EngineSummary {
+    /// Engines that participated in fewer than this many benchmarks get
+    /// flagged when their confidence interval is shown, since bootstrapping
+    /// from so few samples tends to produce intervals that are too narrow.
+    pub const MIN_CONFIDENCE_COUNT: usize = 10;
+
+    /// The marker appended to a geometric mean shown with its confidence
+    /// interval when the engine has fewer than `MIN_CONFIDENCE_COUNT`
+    /// benchmarks.
+    pub const LOW_SAMPLE_MARKER: &'static str = "†";
+
+    /// Returns true when this summary has too few benchmarks for its
+    /// confidence interval to be trusted.
+    pub fn is_low_sample(&self) -> bool {
+        self.count < EngineSummary::MIN_CONFIDENCE_COUNT
+    }
+
+    /// Returns the geometric mean of this summary, formatted with two
+    /// decimal places. When `confidence` is true, the confidence interval is
+    /// included too, e.g., `2.13 [1.95, 2.34]`, along with
+    /// `LOW_SAMPLE_MARKER` when `is_low_sample` returns true.
+    pub fn geomean_display(&self, confidence: bool) -> String {
+        if !confidence {
+            return format!("{:.2}", self.geomean);
+        }
+        format!(
+            "{:.2} [{:.2}, {:.2}]{}",
+            self.geomean,
+            self.interval.low,
+            self.interval.high,
+            if self.is_low_sample() {
+                EngineSummary::LOW_SAMPLE_MARKER
+            } else {
+                ""
+            },
+        )
+    }
+}
+
+/// A confidence interval for the geometric mean of a set of speedup ratios.
+///
+/// This is computed with a percentile bootstrap: the ratios are resampled
+/// with replacement `ITERATIONS` times, the geometric mean of each resample
+/// is computed and the 2.5th and 97.5th percentiles of those geometric means
+/// become the bounds of the interval. The resampling uses a fixed seed, so
+/// the same ratios always produce the same interval.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ConfidenceInterval {
+    /// The lower bound of the interval.
+    pub low: f64,
+    /// The upper bound of the interval.
+    pub high: f64,
+}
+
+impl ConfidenceInterval {
+    /// The number of resamples to take.
+    const ITERATIONS: usize = 1000;
+
+    /// The seed used for resampling.
+    const SEED: u64 = 0x5EBA_5EED;
+
+    /// Compute a 95% bootstrap confidence interval for the geometric mean of
+    /// the given ratios. When there are no ratios, the interval is `[1, 1]`,
+    /// which matches the geometric mean of an empty set.
+    fn bootstrap(ratios: &[f64]) -> ConfidenceInterval {
+        ConfidenceInterval::bootstrap_with(
+            ratios,
+            ConfidenceInterval::ITERATIONS,
+            &mut Rng::new(ConfidenceInterval::SEED),
+        )
+    }
+
+    /// Like `bootstrap`, but with a caller provided number of resamples and
+    /// random number generator.
+    fn bootstrap_with(
+        ratios: &[f64],
+        iterations: usize,
+        rng: &mut Rng,
+    ) -> ConfidenceInterval {
+        if ratios.is_empty() || iterations == 0 {
+            return ConfidenceInterval { low: 1.0, high: 1.0 };
+        }
+        // Working in log space turns each geometric mean into an arithmetic
+        // mean, which is both cheaper and less prone to overflow.
+        let logs: Vec<f64> = ratios.iter().map(|r| r.ln()).collect();
+        let len = logs.len() as u64;
+        let mut geomeans: Vec<f64> = (0..iterations)
+            .map(|_| {
+                let sum: f64 = (0..logs.len())
+                    // OK because the result is less than logs.len().
+                    .map(|_| logs[(rng.next_u64() % len) as usize])
+                    .sum();
+                (sum / logs.len() as f64).exp()
+            })
+            .collect();
+        geomeans.sort_by(|a, b| a.total_cmp(b));
+        let percentile = |p: f64| {
+            let i = (p * (geomeans.len() - 1) as f64).round() as usize;
+            geomeans[i]
+        };
+        ConfidenceInterval { low: percentile(0.025), high: percentile(0.975) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::measurement::read_fixture;
+
+    use super::*;
+
+    const MEASUREMENTS: &str = "\
+fixture/a,count,0.1.0,one,1.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+fixture/a,count,0.1.0,two,2.0.0,,10,5,1s,40ns,0ns,40ns,0ns,40ns,40ns
+fixture/b,count,0.1.0,one,1.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+fixture/b,count,0.1.0,two,2.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+fixture/b,count,0.1.0,three,3.0.0,,10,5,1s,30ns,0ns,30ns,0ns,30ns,30ns
+fixture/c,count,0.1.0,four,4.0.0,,10,5,1s,5ns,0ns,5ns,0ns,5ns,5ns
+";
+
+    fn fixture() -> ByBenchmarkName<()> {
+        let measurements = read_fixture(MEASUREMENTS);
+        ByBenchmarkName::new(&measurements).unwrap()
+    }
+
+    /// Returns the prefix and benchmark names of each split.
+    fn splits(
+        splits: &[(String, ByBenchmarkName<()>)],
+    ) -> Vec<(&str, Vec<&str>)> {
+        splits
+            .iter()
+            .map(|(prefix, grouped)| {
+                let names = grouped.groups.iter().map(|g| &*g.name).collect();
+                (&**prefix, names)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn group_prefixes() {
+        let name = "curated/01-literal/sherlock-en";
+        assert_eq!("", group_prefix(name, 0));
+        assert_eq!("curated", group_prefix(name, 1));
+        assert_eq!("curated/01-literal", group_prefix(name, 2));
+        // A depth deeper than the group is the entire group.
+        assert_eq!("curated/01-literal", group_prefix(name, 3));
+        assert_eq!("curated/01-literal", group_prefix(name, usize::MAX));
+        // A name without a '/' has no group.
+        assert_eq!("", group_prefix("sherlock-en", 0));
+        assert_eq!("", group_prefix("sherlock-en", 1));
+    }
+
+    #[test]
+    fn split_by_group() {
+        // Every benchmark in the fixture has the same group.
+        let all = vec!["fixture/a", "fixture/b", "fixture/c"];
+        assert_eq!(
+            vec![("fixture", all.clone())],
+            splits(&fixture().split_by_group(1)),
+        );
+        assert_eq!(
+            vec![("fixture", all.clone())],
+            splits(&fixture().split_by_group(5)),
+        );
+        assert_eq!(vec![("", all)], splits(&fixture().split_by_group(0)));
+
+        // Splits are ordered by the first appearance of each prefix, and
+        // benchmarks keep their order within each split.
+        let measurements = read_fixture(
+            "\
+x/1/a,count,0.1.0,one,1.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+y/b,count,0.1.0,one,1.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+x/2/c,count,0.1.0,one,1.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+d,count,0.1.0,one,1.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+x/1/e,count,0.1.0,one,1.0.0,,10,5,1s,10ns,0ns,10ns,0ns,10ns,10ns
+",
+        );
+        let grouped = ByBenchmarkName::new(&measurements).unwrap();
+        assert_eq!(
+            vec![
+                ("x", vec!["x/1/a", "x/2/c", "x/1/e"]),
+                ("y", vec!["y/b"]),
+                ("", vec!["d"]),
+            ],
+            splits(&grouped.clone().split_by_group(1)),
+        );
+        assert_eq!(
+            vec![
+                ("x/1", vec!["x/1/a", "x/1/e"]),
+                ("y", vec!["y/b"]),
+                ("x/2", vec!["x/2/c"]),
+                ("", vec!["d"]),
+            ],
+            splits(&grouped.split_by_group(2)),
+        );
+    }
+
+    #[test]
+    fn speedup_matrix() {
+        let matrix = fixture().speedup_matrix(Stat::Median);
+        assert_eq!(matrix.engines, vec!["four", "one", "three", "two"]);
+        // Round each cell so that we don't have to worry about floating point
+        // error in the geometric mean.
+        let cells: Vec<Vec<Option<String>>> = matrix
+            .cells
+            .iter()
+            .map(|row| {
+                row.iter().map(|c| c.map(|c| format!("{:.2}", c))).collect()
+            })
+            .collect();
+        let s = |x: &str| Some(x.to_string());
+        assert_eq!(
+            cells,
+            vec![
+                // four
+                vec![s("1.00"), None, None, None],
+                // one: 'two' is 4x slower on 'a' and the same on 'b', so the
+                // geometric mean is sqrt(4 * 1) = 2.
+                vec![None, s("1.00"), s("3.00"), s("2.00")],
+                // three
+                vec![None, s("0.33"), s("1.00"), s("0.33")],
+                // two
+                vec![None, s("0.50"), s("3.00"), s("1.00")],
+            ],
+        );
+    }
+
+    #[test]
+    fn alias() {
+        let mut aliases = EngineAliases::default();
+        aliases.add("pair=^(one|two)$").unwrap();
+        let grouped = fixture().alias(&aliases, Stat::Median);
+        let got: Vec<(String, Vec<String>, Vec<String>)> = grouped
+            .groups
+            .iter()
+            .map(|g| {
+                let engines = g
+                    .by_engine
+                    .values()
+                    .map(|m| {
+                        format!(
+                            "{} {} {:?}",
+                            m.engine,
+                            m.engine_version,
+                            m.duration(Stat::Median)
+                        )
+                    })
+                    .collect();
+                let variants = g
+                    .variants
+                    .iter()
+                    .map(|(alias, engine)| format!("{}={}", alias, engine))
+                    .collect();
+                (g.name.clone(), engines, variants)
+            })
+            .collect();
+        let s = |x: &str| x.to_string();
+        assert_eq!(
+            vec![
+                (
+                    s("fixture/a"),
+                    vec![s("pair 1.0.0, 2.0.0 10ns")],
+                    vec![s("pair=one")],
+                ),
+                // 'one' and 'two' are tied, so the first one is kept.
+                (
+                    s("fixture/b"),
+                    vec![s("pair 1.0.0, 2.0.0 10ns"), s("three 3.0.0 30ns"),],
+                    vec![s("pair=one")],
+                ),
+                (s("fixture/c"), vec![s("four 4.0.0 5ns")], vec![]),
+            ],
+            got,
+        );
+        let ranking = grouped.ranking(Stat::Median).unwrap();
+        let pair = ranking.iter().find(|s| s.name == "pair").unwrap();
+        assert_eq!(("1.0.0, 2.0.0", 2), (pair.version.as_str(), pair.count));
+    }
+
+    #[test]
+    fn alias_best_variant() {
+        // An engine with the same name as an alias belongs to it, even if it
+        // doesn't match the regex.
+        let mut aliases = EngineAliases::default();
+        aliases.add("two=^one$").unwrap();
+        let grouped = fixture().alias(&aliases, Stat::Median);
+        let a = &grouped.groups[0];
+        assert_eq!(vec!["two"], a.by_engine.keys().collect::<Vec<_>>());
+        assert_eq!("one", a.variants["two"]);
+        // 'one' is kept in 'b' since it ties with 'two' and comes first.
+        assert_eq!("one", grouped.groups[1].variants["two"]);
+
+        // Without any aliases, nothing changes.
+        let grouped = fixture().alias(&EngineAliases::default(), Stat::Median);
+        assert!(grouped.groups.iter().all(|g| g.variants.is_empty()));
+        assert_eq!(2, grouped.groups[0].by_engine.len());
+    }
+
+    #[test]
+    fn alias_parse() {
+        let mut aliases = EngineAliases::default();
+        assert!(aliases.add("pcre2").is_err());
+        assert!(aliases.add("=pcre2").is_err());
+        assert!(aliases.add("pcre2=(").is_err());
+        assert!(aliases.is_empty());
+
+        aliases.add("pcre2=^pcre2").unwrap();
+        aliases.add("rust=^rust/").unwrap();
+        aliases.add("other=.*").unwrap();
+        assert_eq!(Some("pcre2"), aliases.find("pcre2/jit"));
+        assert_eq!(Some("rust"), aliases.find("rust/regex"));
+        assert_eq!(Some("other"), aliases.find("re2"));
+    }
+
+    #[test]
+    fn confidence_interval() {
+        let ratios = [1.0, 1.2, 1.5, 2.0, 2.5, 3.0, 4.0, 1.1];
+        let ci = ConfidenceInterval::bootstrap(&ratios);
+        let got = format!("[{:.4}, {:.4}]", ci.low, ci.high);
+        // The geometric mean of these ratios is about 1.82.
+        assert_eq!("[1.3107, 2.5597]", got);
+
+        // Every resample of a single ratio is that ratio.
+        let ci = ConfidenceInterval::bootstrap(&[2.5]);
+        assert_eq!(ConfidenceInterval { low: 2.5, high: 2.5 }, ci);
+        let ci = ConfidenceInterval::bootstrap(&[]);
+        assert_eq!(ConfidenceInterval { low: 1.0, high: 1.0 }, ci);
+    }
+
+    #[test]
+    fn ranking_confidence() {
+        let ranking = fixture().ranking(Stat::Median).unwrap();
+        let got: Vec<String> = ranking
+            .iter()
+            .map(|s| format!("{}: {}", s.name, s.geomean_display(true)))
+            .collect();
+        // Every engine has fewer than MIN_CONFIDENCE_COUNT benchmarks, so
+        // they're all marked. 'two' has ratios of 4 and 1, so resamples have
+        // geometric means of 1, 2 or 4.
+        assert_eq!(
+            vec![
+                "four: 1.00 [1.00, 1.00]†",
+                "one: 1.00 [1.00, 1.00]†",
+                "two: 2.00 [1.00, 4.00]†",
+                "three: 3.00 [3.00, 3.00]†",
+            ],
+            got,
+        );
+        let one = ranking.iter().find(|s| s.name == "one").unwrap();
+        assert_eq!("1.00", one.geomean_display(false));
+    }
+}
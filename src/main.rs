@@ -9,7 +9,7 @@ mod format;
 mod grouped;
 mod util;
 
-const USAGE: &'static str = "\
+const USAGE: &str = "\
 A regex barometer tool for running benchmarks and comparing results.
 
 USAGE:
@@ -20,12 +20,21 @@ COMMANDS:
     clean     Clean artifacts produced by 'rebar build'.
     cmp       Compare timings across regex engines.
     diff      Compare timings across time for the same regex engine.
+    doctor    Check that regex engines are built and working.
     haystack  Print the haystack contents of a benchmark to stdout.
     klv       Print the KLV format of a benchmark.
+    lint      Check benchmark definitions for problems.
+    matrix    Print a matrix of pairwise speedups between regex engines.
     measure   Capture timings to CSV by running benchmarks.
+    merge     Merge measurements from repeated runs into one CSV file.
+    overhead  Print the benchmarks with the most unmeasured time.
     rank      Print a ranking of regex engines from benchmark results.
-    report    Print a Markdown formatted report of benchmark results.
+    record    Record measurements into the 'record' directory.
+    report    Print a Markdown or HTML report of benchmark results.
+    run       Run a single benchmark and print its samples.
+    summary   Print a one line summary of benchmark results.
     version   Print the version of rebar and exit.
+    wrap      Measure benchmarks by running an arbitrary command.
 
 ";
 
@@ -35,7 +44,7 @@ fn main() -> anyhow::Result<()> {
     )
     .init();
     if let Err(err) = run(&mut lexopt::Parser::from_env()) {
-        if std::env::var("RUST_BACKTRACE").map_or(false, |v| v == "1") {
+        if std::env::var("RUST_BACKTRACE").is_ok_and(|v| v == "1") {
             writeln!(&mut std::io::stderr(), "{:?}", err).unwrap();
         } else {
             writeln!(&mut std::io::stderr(), "{:#}", err).unwrap();
@@ -52,12 +61,21 @@ fn run(p: &mut lexopt::Parser) -> anyhow::Result<()> {
         "clean" => cmd::clean::run(p),
         "cmp" => cmd::cmp::run(p),
         "diff" => cmd::diff::run(p),
+        "doctor" => cmd::doctor::run(p),
         "haystack" => cmd::haystack::run(p),
         "klv" => cmd::klv::run(p),
+        "lint" => cmd::lint::run(p),
+        "matrix" => cmd::matrix::run(p),
         "measure" => cmd::measure::run(p),
+        "merge" => cmd::merge::run(p),
+        "overhead" => cmd::overhead::run(p),
         "rank" => cmd::rank::run(p),
+        "record" => cmd::record::run(p),
         "report" => cmd::report::run(p),
+        "run" => cmd::run::run(p),
+        "summary" => cmd::summary::run(p),
         "version" => cmd::version::run(p),
+        "wrap" => cmd::wrap::run(p),
         unk => anyhow::bail!("unrecognized command '{}'", unk),
     }
 }
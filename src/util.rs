@@ -7,7 +7,7 @@ use {
 
 /// The rebar Cargo package version. This environment variable is guaranteed
 /// to be made available by Cargo.
-pub const REBAR_VERSION: &'static str = env!("CARGO_PKG_VERSION");
+pub const REBAR_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// The commit revision hash that rebar was built from. This environment
 /// variable is set by a custom build script, and is only available when `git`
@@ -30,8 +30,14 @@ pub fn version() -> String {
 /// serializing and deserializing using a basic human friendly short duration.
 ///
 /// We can get away with being simple here by assuming the duration is short.
-/// i.e., No longer than one minute. So all we handle here are seconds,
-/// milliseconds, microseconds and nanoseconds.
+/// Durations are always printed in seconds or smaller units. When parsing,
+/// a duration is an integer or decimal number followed immediately (or after
+/// some whitespace) by one of the units `h`, `m`, `s`, `ms`, `us`, `µs` or
+/// `ns`. For example, `2m`, `90s`, `1.5ms` or `.5us`.
+///
+/// A number without a unit is rejected, since it's too easy to get the
+/// units wrong (e.g., `--max-time 5` could mean seconds or milliseconds). The
+/// only exception is `0`, which is the same regardless of units.
 ///
 /// This avoids bringing in another crate to do this work (like humantime).
 /// Hah, incidentally, when I wrote this, I had forgotten that I already had
@@ -97,7 +103,8 @@ impl std::str::FromStr for ShortHumanDuration {
             r"(?x)
                 ^
                 (?P<float>[0-9]+(?:\.[0-9]*)?|\.[0-9]+)
-                (?P<units>s|ms|us|ns)
+                \s*
+                (?P<units>h|ms|m|s|us|µs|ns)?
                 $
             ",
         );
@@ -108,20 +115,32 @@ impl std::str::FromStr for ShortHumanDuration {
         let caps = match re.captures(s) {
             Some(caps) => caps,
             None => anyhow::bail!(
-                "duration '{}' not in '<decimal>(s|ms|us|ns)' format",
+                "duration '{}' not in '<decimal>(h|m|s|ms|us|ns)' format",
                 s,
             ),
         };
+        let Some(units) = caps.name("units") else {
+            anyhow::bail!(
+                "duration '{}' is missing units, try '{}s' or '{}ms'",
+                s,
+                s,
+                s,
+            )
+        };
         let mut value: f64 =
             caps["float"].parse().context("invalid duration decimal")?;
-        match &caps["units"] {
+        match units.as_str() {
+            "h" => value *= 3_600.0,
+            "m" => value *= 60.0,
             "s" => value /= 1.0,
             "ms" => value /= 1_000.0,
-            "us" => value /= 1_000_000.0,
+            "us" | "µs" => value /= 1_000_000.0,
             "ns" => value /= 1_000_000_000.0,
             unit => unreachable!("impossible unit '{}'", unit),
         }
-        Ok(ShortHumanDuration(Duration::from_secs_f64(value)))
+        let dur = Duration::try_from_secs_f64(value)
+            .with_context(|| format!("duration '{}' is too big", s))?;
+        Ok(ShortHumanDuration(dur))
     }
 }
 
@@ -148,7 +167,10 @@ impl<'de> serde::Deserialize<'de> for ShortHumanDuration {
                 &self,
                 f: &mut std::fmt::Formatter,
             ) -> std::fmt::Result {
-                write!(f, "duration string of the form <decimal>(s|ms|us|ns)")
+                write!(
+                    f,
+                    "duration string of the form <decimal>(h|m|s|ms|us|ns)"
+                )
             }
 
             fn visit_str<E>(self, s: &str) -> Result<ShortHumanDuration, E>
@@ -186,6 +208,11 @@ impl Throughput {
     pub fn from_bytes_per_second(bytes_per_second: f64) -> Throughput {
         Throughput(bytes_per_second)
     }
+
+    /// Returns this throughput in units of B/sec.
+    pub fn bytes_per_second(&self) -> f64 {
+        self.0
+    }
 }
 
 impl std::fmt::Debug for Throughput {
@@ -203,7 +230,7 @@ impl std::fmt::Display for Throughput {
         const MIN_MB: f64 = 2.0 * MB;
         const MIN_GB: f64 = 2.0 * GB;
 
-        let bytes_per_second = self.0 as f64;
+        let bytes_per_second = self.0;
         if bytes_per_second < MIN_KB {
             write!(f, "{} B/s", bytes_per_second as u64)
         } else if bytes_per_second < MIN_MB {
@@ -216,6 +243,154 @@ impl std::fmt::Display for Throughput {
     }
 }
 
+/// A rate of things per second, e.g., lines or iterations.
+///
+/// Like `Throughput`, the time units are fixed to "per second," but large
+/// rates are shown with a K, M or G (powers of 1000) suffix.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct Rate {
+    per_second: f64,
+    unit: &'static str,
+}
+
+impl Rate {
+    /// Create a new rate from the number of things processed and the amount
+    /// of time taken to process them. The unit should be the plural name of
+    /// the thing being counted, e.g., 'lines'.
+    pub fn new(count: u64, duration: Duration, unit: &'static str) -> Rate {
+        Rate { per_second: (count as f64) / duration.as_secs_f64(), unit }
+    }
+
+    /// Returns the number of things processed per second.
+    pub fn per_second(&self) -> f64 {
+        self.per_second
+    }
+}
+
+impl std::fmt::Display for Rate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        const K: f64 = 1_000.0;
+        const M: f64 = 1_000_000.0;
+        const G: f64 = 1_000_000_000.0;
+
+        let (rate, unit) = (self.per_second, self.unit);
+        if rate < K {
+            write!(f, "{:.1} {}/s", rate, unit)
+        } else if rate < M {
+            write!(f, "{:.1}K {}/s", rate / K, unit)
+        } else if rate < G {
+            write!(f, "{:.1}M {}/s", rate / M, unit)
+        } else {
+            write!(f, "{:.1}G {}/s", rate / G, unit)
+        }
+    }
+}
+
+/// A size in bytes, printed in convenient units, e.g., GB, MB, KB or B.
+#[derive(Clone, Copy, Debug)]
+pub struct HumanBytes(pub u64);
+
+/// A size in bytes that can be parsed from a short human friendly string.
+///
+/// A size is an integer or decimal number optionally followed by a unit. The
+/// units are `B`, `K`, `KB`, `KiB`, `M`, `MB`, `MiB`, `G`, `GB`, `GiB`, `T`,
+/// `TB` and `TiB`. Every unit is a power of 2, i.e., `K`, `KB` and `KiB` all
+/// mean 1,024 bytes. This matches how rebar prints sizes and throughputs (see
+/// `HumanBytes` and `Throughput`), so anything rebar prints can be parsed
+/// back. A number without a unit is a number of bytes.
+///
+/// Fractional sizes are rounded to the nearest byte, e.g., `1.5K` is 1,536
+/// bytes.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ShortHumanSize(u64);
+
+impl ShortHumanSize {
+    /// Returns this size in bytes.
+    pub fn bytes(&self) -> u64 {
+        self.0
+    }
+}
+
+impl std::fmt::Display for ShortHumanSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Display::fmt(&HumanBytes(self.0), f)
+    }
+}
+
+impl std::str::FromStr for ShortHumanSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<ShortHumanSize> {
+        let re = regex!(
+            r"(?x)
+                ^
+                (?P<float>[0-9]+(?:\.[0-9]*)?|\.[0-9]+)
+                \s*
+                (?P<units>[KMGT]?B|[KMGT]iB|[KMGT])?
+                $
+            ",
+        );
+        let caps = match re.captures(s) {
+            Some(caps) => caps,
+            None => anyhow::bail!(
+                "size '{}' not in '<decimal>(B|KB|MB|GB|TB)' format",
+                s,
+            ),
+        };
+        let value: f64 =
+            caps["float"].parse().context("invalid size decimal number")?;
+        let multiplier =
+            caps.name("units").map_or(1.0, |u| size_multiplier(u.as_str()));
+        let bytes = (value * multiplier).round();
+        // N.B. 'u64::MAX as f64' rounds up to 2^64, which is why this isn't
+        // a '<=' comparison.
+        anyhow::ensure!(bytes < u64::MAX as f64, "size '{}' is too big", s);
+        // OK because we just checked that it fits.
+        Ok(ShortHumanSize(bytes as u64))
+    }
+}
+
+/// Returns the number of bytes in one of the given size units. The units
+/// given must be one of the units recognized by `ShortHumanSize`.
+fn size_multiplier(units: &str) -> f64 {
+    let shift = match units.as_bytes()[0] {
+        b'B' => 0,
+        b'K' => 10,
+        b'M' => 20,
+        b'G' => 30,
+        b'T' => 40,
+        _ => unreachable!("impossible unit '{}'", units),
+    };
+    (1u64 << shift) as f64
+}
+
+impl std::fmt::Display for HumanBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        const KB: f64 = (1 << 10) as f64;
+        const MB: f64 = (1 << 20) as f64;
+        const GB: f64 = (1 << 30) as f64;
+        const MIN_KB: f64 = 2.0 * KB;
+        const MIN_MB: f64 = 2.0 * MB;
+        const MIN_GB: f64 = 2.0 * GB;
+
+        let bytes = self.0 as f64;
+        if bytes < MIN_KB {
+            write!(f, "{} B", self.0)
+        } else if bytes < MIN_MB {
+            write!(f, "{:.1} KB", bytes / KB)
+        } else if bytes < MIN_GB {
+            write!(f, "{:.1} MB", bytes / MB)
+        } else {
+            write!(f, "{:.1} GB", bytes / GB)
+        }
+    }
+}
+
+/// Parses a throughput like `1.2GB/s` or `500 KiB/s`.
+///
+/// The size part of a throughput accepts the same units as
+/// `ShortHumanSize`, and like it, every unit is a power of 2. Unlike a size,
+/// the units are required.
 impl std::str::FromStr for Throughput {
     type Err = anyhow::Error;
 
@@ -225,7 +400,7 @@ impl std::str::FromStr for Throughput {
                 ^
                 (?P<float>[0-9]+(?:\.[0-9]*)?|\.[0-9]+)
                 \s*
-                (?P<units>B|KB|MB|GB)/s
+                (?P<units>[KMGT]?B|[KMGT]iB|[KMGT])/s
                 $
             ",
         );
@@ -236,16 +411,16 @@ impl std::str::FromStr for Throughput {
                 s,
             ),
         };
-        let mut bytes_per_second: f64 = caps["float"]
+        let bytes_per_second: f64 = caps["float"]
             .parse()
             .context("invalid throughput decimal number")?;
-        match &caps["units"] {
-            "B" => bytes_per_second *= (1 << 0) as f64,
-            "KB" => bytes_per_second *= (1 << 10) as f64,
-            "MB" => bytes_per_second *= (1 << 20) as f64,
-            "GB" => bytes_per_second *= (1 << 30) as f64,
-            unit => unreachable!("impossible unit '{}'", unit),
-        }
+        let bytes_per_second =
+            bytes_per_second * size_multiplier(&caps["units"]);
+        anyhow::ensure!(
+            bytes_per_second.is_finite(),
+            "throughput '{}' is too big",
+            s,
+        );
         Ok(Throughput(bytes_per_second))
     }
 }
@@ -310,7 +485,7 @@ pub fn write_divider<W: std::io::Write>(
     divider: char,
     width: usize,
 ) -> anyhow::Result<()> {
-    let div: String = std::iter::repeat(divider).take(width).collect();
+    let div: String = std::iter::repeat_n(divider, width).collect();
     write!(wtr, "{}", div)?;
     Ok(())
 }
@@ -380,7 +555,7 @@ pub fn output(cmd: &mut std::process::Command) -> anyhow::Result<BString> {
         "command failed with {:?} but stderr is empty",
         out.status,
     );
-    let last = match out.stderr.lines().last() {
+    let last = match out.stderr.lines().next_back() {
         Some(last) => last,
         None => {
             anyhow::bail!(
@@ -394,3 +569,220 @@ pub fn output(cmd: &mut std::process::Command) -> anyhow::Result<BString> {
         last.as_bstr(),
     ))
 }
+
+/// Returns the abbreviated git revision of the commit checked out in the work
+/// tree containing the given directory. If there are uncommitted changes
+/// (including untracked files) beneath the directory, then '-dirty' is
+/// appended to the revision.
+///
+/// If git isn't available or the directory isn't inside a git work tree,
+/// then this returns `None`. This is only logged at the debug level, since
+/// it's perfectly normal to run rebar outside of a git checkout.
+pub fn git_revision(dir: &std::path::Path) -> Option<String> {
+    let git = |args: &[&str]| -> Option<String> {
+        let mut cmd = std::process::Command::new("git");
+        cmd.arg("-C").arg(dir).args(args);
+        match output(&mut cmd) {
+            Ok(out) => Some(out.to_str_lossy().trim().to_string()),
+            Err(err) => {
+                log::debug!(
+                    "could not get git revision of {}: {:#}",
+                    dir.display(),
+                    err,
+                );
+                None
+            }
+        }
+    };
+    let rev = git(&["rev-parse", "--short", "HEAD"])?;
+    if rev.is_empty() {
+        return None;
+    }
+    let status = git(&["status", "--porcelain", "--", "."])?;
+    if status.is_empty() {
+        Some(rev)
+    } else {
+        Some(format!("{}-dirty", rev))
+    }
+}
+
+/// A small seeded pseudo-random number generator.
+///
+/// This is an implementation of SplitMix64. It is not suitable for anything
+/// that needs real randomness, but it is plenty good enough for shuffling
+/// the order in which benchmarks are executed, and it saves us a dependency.
+#[derive(Clone, Debug)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// Create a new generator from the given seed. The same seed always
+    /// produces the same sequence of numbers.
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    /// Returns a seed derived from the current time and process ID. This is
+    /// used when the caller didn't provide one.
+    pub fn random_seed() -> u64 {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        // Only the low bits vary in interesting ways, so truncation is fine.
+        Rng::new((nanos as u64) ^ u64::from(std::process::id())).next_u64()
+    }
+
+    /// Returns the next pseudo-random number in this generator's sequence.
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Shuffle the given slice in place using a Fisher-Yates shuffle.
+    ///
+    /// The modulo bias here is negligible for any slice we might shuffle.
+    pub fn shuffle<T>(&mut self, slice: &mut [T]) {
+        for i in (1..slice.len()).rev() {
+            // OK because 'i' came from a usize, so the result fits.
+            let j = (self.next_u64() % (i as u64 + 1)) as usize;
+            slice.swap(i, j);
+        }
+    }
+}
+
+/// Returns the SHA-256 digest of the given bytes as a lowercase hex string.
+///
+/// This is used to check that haystacks downloaded from a URL are exactly
+/// what their benchmark definition expects.
+pub fn sha256_hex(data: &[u8]) -> String {
+    use sha2::Digest;
+
+    sha2::Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration() {
+        let p =
+            |s: &str| Duration::from(s.parse::<ShortHumanDuration>().unwrap());
+        assert_eq!(Duration::ZERO, p("0"));
+        assert_eq!(Duration::from_nanos(5), p("5ns"));
+        assert_eq!(Duration::from_micros(5), p("5us"));
+        assert_eq!(Duration::from_micros(5), p("5µs"));
+        assert_eq!(Duration::from_micros(1_500), p("1.5ms"));
+        assert_eq!(Duration::from_millis(500), p(".5s"));
+        assert_eq!(Duration::from_secs(90), p("90s"));
+        assert_eq!(Duration::from_secs(120), p("2m"));
+        assert_eq!(Duration::from_secs(5_400), p("1.5h"));
+        assert_eq!(Duration::from_secs(3), p("3 s"));
+        assert_eq!(Duration::from_secs(3), p("3.s"));
+    }
+
+    #[test]
+    fn parse_duration_errors() {
+        let err =
+            |s: &str| s.parse::<ShortHumanDuration>().unwrap_err().to_string();
+        // Bare numbers (other than 0) are rejected since their units are
+        // ambiguous.
+        assert!(err("5").contains("missing units"), "{}", err("5"));
+        assert!(err("1.5").contains("missing units"));
+        for garbage in ["", "s", "5x", "5 sec", "-5s", "5s5", "1e3s", "5mss"] {
+            assert!(err(garbage).contains("format"), "{:?}", garbage);
+        }
+        let big = format!("{}h", "9".repeat(30));
+        assert!(err(&big).contains("too big"), "{}", err(&big));
+    }
+
+    #[test]
+    fn duration_roundtrip() {
+        // Anything we print should parse back to about the same duration.
+        for nanos in [0, 7, 1_234, 5_678_901, 2_500_000_000, 90_000_000_000] {
+            let dur = Duration::from_nanos(nanos);
+            let printed = ShortHumanDuration::from(dur).to_string();
+            let parsed: Duration =
+                printed.parse::<ShortHumanDuration>().unwrap().into();
+            let diff = parsed.abs_diff(dur).as_secs_f64();
+            assert!(diff <= dur.as_secs_f64() * 0.01, "{}", printed);
+        }
+    }
+
+    #[test]
+    fn parse_size() {
+        let p = |s: &str| s.parse::<ShortHumanSize>().unwrap().bytes();
+        assert_eq!(0, p("0"));
+        assert_eq!(256, p("256"));
+        assert_eq!(256, p("256B"));
+        assert_eq!(1 << 10, p("1K"));
+        assert_eq!(1 << 10, p("1KB"));
+        assert_eq!(1 << 10, p("1KiB"));
+        assert_eq!(1_536, p("1.5K"));
+        assert_eq!(3 << 20, p("3 MB"));
+        assert_eq!(3 << 20, p("3MiB"));
+        assert_eq!(1 << 30, p("1G"));
+        assert_eq!(1 << 30, p("1GiB"));
+        assert_eq!(2 << 40, p("2TB"));
+        // Fractional bytes are rounded.
+        assert_eq!(2, p("1.5"));
+        // Sizes printed by rebar parse back.
+        assert_eq!(1_536, p(&HumanBytes(1_536).to_string()));
+        assert_eq!("4.0 MB", ShortHumanSize(4 << 20).to_string());
+    }
+
+    #[test]
+    fn parse_size_errors() {
+        let err =
+            |s: &str| s.parse::<ShortHumanSize>().unwrap_err().to_string();
+        for garbage in ["", "K", "1kb", "1Kb", "1KIB", "1PB", "-1", "1 K B"] {
+            assert!(err(garbage).contains("format"), "{:?}", garbage);
+        }
+        assert!(err("16777216TB").contains("too big"));
+    }
+
+    #[test]
+    fn parse_throughput() {
+        let p = |s: &str| s.parse::<Throughput>().unwrap().bytes_per_second();
+        assert_eq!(500.0, p("500B/s"));
+        assert_eq!(1.5 * 1024.0, p("1.5KB/s"));
+        assert_eq!(1.5 * 1024.0, p("1.5 KiB/s"));
+        assert_eq!(2.0 * (1 << 20) as f64, p("2M/s"));
+        assert_eq!(1.2 * (1 << 30) as f64, p("1.2GB/s"));
+        // Throughputs printed by rebar parse back.
+        let tput = Throughput::from_bytes_per_second(3.5 * (1 << 30) as f64);
+        assert_eq!(tput, tput.to_string().parse().unwrap());
+
+        let err = |s: &str| s.parse::<Throughput>().unwrap_err().to_string();
+        for garbage in ["", "500", "500B", "5GB/m", "/s", "1.2 gb/s"] {
+            assert!(err(garbage).contains("format"), "{:?}", garbage);
+        }
+    }
+
+    #[test]
+    fn shuffle_deterministic() {
+        let shuffled = |seed: u64| {
+            let mut xs: Vec<u32> = (0..100).collect();
+            Rng::new(seed).shuffle(&mut xs);
+            xs
+        };
+        assert_eq!(shuffled(42), shuffled(42));
+        assert_ne!(shuffled(42), shuffled(43));
+        assert_ne!((0..100).collect::<Vec<u32>>(), shuffled(42));
+
+        let mut sorted = shuffled(42);
+        sorted.sort();
+        assert_eq!((0..100).collect::<Vec<u32>>(), sorted);
+
+        // Shuffling empty or single element slices is fine.
+        Rng::new(1).shuffle::<u32>(&mut []);
+        let mut one = [5];
+        Rng::new(1).shuffle(&mut one);
+        assert_eq!([5], one);
+    }
+}